@@ -0,0 +1,238 @@
+//! Bandwidth reflector for agent-to-agent bandwidth tests
+//!
+//! When `[bandwidth_reflector]` is configured, the agent serves the same
+//! download and upload endpoints as the central server, so that other agents
+//! can measure the path to it (for example between two branch offices).
+// Unlike the server, the reflector keeps no test queue. The testing agent
+// chooses the size of each stream through the `size_bytes` query parameter,
+// up to `max_test_size_mb`, and at most MAX_BANDWIDTH_PARALLEL_STREAMS
+// transfers are served at once; further requests are answered with 503.
+// Testing agents that need exclusive use of a path coordinate through the
+// central server instead.
+
+use anyhow::{Context, Result};
+use axum::{
+    body::{Body, Bytes},
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use futures_util::StreamExt;
+use shared::api::{endpoints, headers, BandwidthUploadResponse};
+use shared::config::{BandwidthReflectorConfig, MAX_BANDWIDTH_PARALLEL_STREAMS};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio::sync::Semaphore;
+use tracing::{debug, info, warn};
+
+/// Query parameter carrying the per-stream size in bytes
+pub const SIZE_QUERY_PARAM: &str = "size_bytes";
+
+/// Size of the chunks streamed during download
+const CHUNK_SIZE: usize = 64 * 1024;
+
+type ReflectorError = (StatusCode, String);
+
+/// State shared by the reflector handlers
+#[derive(Clone)]
+struct ReflectorState {
+    api_key: Arc<str>,
+    max_size_bytes: u64,
+    streams: Arc<Semaphore>,
+}
+
+/// HTTP listener serving bandwidth test data to other agents
+pub struct BandwidthReflector {
+    listen_address: String,
+    state: ReflectorState,
+}
+
+impl BandwidthReflector {
+    /// Create a new reflector from validated configuration
+    ///
+    /// `api_key` is the resolved key testing agents must send.
+    pub fn new(config: &BandwidthReflectorConfig, api_key: String) -> Result<Self> {
+        if api_key.is_empty() {
+            return Err(anyhow::anyhow!("Bandwidth reflector requires an API key"));
+        }
+        Ok(Self {
+            listen_address: config.listen_address.clone(),
+            state: ReflectorState {
+                api_key: api_key.into(),
+                max_size_bytes: config.max_test_size_mb as u64 * 1024 * 1024,
+                streams: Arc::new(Semaphore::new(MAX_BANDWIDTH_PARALLEL_STREAMS as usize)),
+            },
+        })
+    }
+
+    /// Bind the listening socket
+    pub async fn bind(&self) -> Result<TcpListener> {
+        let listener = TcpListener::bind(&self.listen_address)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to bind bandwidth reflector to {}",
+                    self.listen_address
+                )
+            })?;
+        info!(
+            listen_address = %self.listen_address,
+            "Bandwidth reflector listening"
+        );
+        Ok(listener)
+    }
+
+    /// Serve test data until a shutdown signal arrives
+    pub async fn run(
+        self,
+        listener: TcpListener,
+        mut shutdown_rx: tokio::sync::broadcast::Receiver<()>,
+    ) {
+        let router = Router::new()
+            .route(endpoints::BANDWIDTH_DOWNLOAD, get(handle_download))
+            .route(endpoints::BANDWIDTH_UPLOAD, post(handle_upload))
+            .with_state(self.state);
+
+        let result = axum::serve(listener, router)
+            .with_graceful_shutdown(async move {
+                let _ = shutdown_rx.recv().await;
+                info!("Bandwidth reflector shutting down");
+            })
+            .await;
+        if let Err(e) = result {
+            warn!("Bandwidth reflector stopped: {}", e);
+        }
+    }
+}
+
+fn check_api_key(headers: &HeaderMap, expected_key: &str) -> Result<(), ReflectorError> {
+    use subtle::ConstantTimeEq;
+
+    let provided_key = headers
+        .get(headers::API_KEY)
+        .and_then(|key| key.to_str().ok())
+        .unwrap_or("");
+    if provided_key.is_empty()
+        || !bool::from(provided_key.as_bytes().ct_eq(expected_key.as_bytes()))
+    {
+        return Err((StatusCode::UNAUTHORIZED, "Invalid API key".to_string()));
+    }
+    Ok(())
+}
+
+/// Claims one of the reflector's stream permits
+fn acquire_stream(
+    state: &ReflectorState,
+) -> Result<tokio::sync::OwnedSemaphorePermit, ReflectorError> {
+    state.streams.clone().try_acquire_owned().map_err(|_| {
+        (
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Bandwidth reflector is busy".to_string(),
+        )
+    })
+}
+
+/// Returns the size requested by the testing agent, within the reflector's limit
+fn requested_size(
+    state: &ReflectorState,
+    params: &HashMap<String, String>,
+) -> Result<u64, ReflectorError> {
+    let size = params
+        .get(SIZE_QUERY_PARAM)
+        .and_then(|size| size.parse::<u64>().ok())
+        .ok_or_else(|| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Missing or invalid '{}'", SIZE_QUERY_PARAM),
+            )
+        })?;
+    if size == 0 || size > state.max_size_bytes {
+        return Err((
+            StatusCode::BAD_REQUEST,
+            format!(
+                "'{}' must be between 1 and {}",
+                SIZE_QUERY_PARAM, state.max_size_bytes
+            ),
+        ));
+    }
+    Ok(size)
+}
+
+/// Streams `size_bytes` of incompressible test data
+async fn handle_download(
+    State(state): State<ReflectorState>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+) -> Result<Response, ReflectorError> {
+    check_api_key(&headers, &state.api_key)?;
+    let total_size = requested_size(&state, &params)? as usize;
+    let permit = acquire_stream(&state)?;
+
+    debug!(
+        agent_id = params.get("agent_id").map_or("", |s| s.as_str()),
+        total_size = total_size,
+        "Reflector serving bandwidth test data"
+    );
+
+    // The permit is released when the response body is dropped
+    let seed: u64 = rand::random();
+    let byte_stream =
+        futures_util::stream::iter((0..total_size.div_ceil(CHUNK_SIZE)).map(move |i| {
+            let _permit = &permit;
+            let chunk_len = std::cmp::min(total_size - i * CHUNK_SIZE, CHUNK_SIZE);
+            let mut chunk = vec![0u8; chunk_len];
+            shared::utils::fill_incompressible(seed, i as u64, &mut chunk);
+            Ok::<_, std::io::Error>(Bytes::from(chunk))
+        }));
+
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(axum::http::header::CONTENT_TYPE, "application/octet-stream")
+        .header(axum::http::header::CONTENT_LENGTH, total_size)
+        .body(Body::from_stream(byte_stream))
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+/// Reads and discards up to `size_bytes` of uploaded data
+async fn handle_upload(
+    State(state): State<ReflectorState>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+    body: Body,
+) -> Result<impl IntoResponse, ReflectorError> {
+    check_api_key(&headers, &state.api_key)?;
+    let max_size = requested_size(&state, &params)?;
+    let _permit = acquire_stream(&state)?;
+
+    let mut bytes_received: u64 = 0;
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| {
+            (
+                StatusCode::BAD_REQUEST,
+                format!("Failed to read upload body: {}", e),
+            )
+        })?;
+        bytes_received += chunk.len() as u64;
+        if bytes_received > max_size {
+            return Err((
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("Upload exceeds requested size of {} bytes", max_size),
+            ));
+        }
+    }
+
+    debug!(
+        agent_id = params.get("agent_id").map_or("", |s| s.as_str()),
+        bytes_received = bytes_received,
+        "Reflector received bandwidth test data"
+    );
+
+    Ok(Json(BandwidthUploadResponse {
+        status: "ok".to_string(),
+        bytes_received,
+    }))
+}
@@ -177,6 +177,10 @@ impl ConfigManager {
             "  max_concurrent_tasks: {}",
             agent_config.max_concurrent_tasks
         );
+        debug!(
+            "  max_concurrent_per_type: {:?}",
+            agent_config.max_concurrent_per_type
+        );
         debug!(
             "  http_response_max_size_mb: {}",
             agent_config.http_response_max_size_mb
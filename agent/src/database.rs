@@ -18,16 +18,25 @@
 // Task-specific database modules
 mod db_bandwidth;
 mod db_dns;
+mod db_execution;
 mod db_http;
 mod db_http_content;
+mod db_labels;
+mod db_latency;
+mod db_ntp;
 mod db_ping;
 mod db_queue;
 #[cfg(feature = "snmp-tasks")]
 mod db_snmp;
+#[cfg(feature = "snmp-tasks")]
+mod db_snmp_trap;
 #[cfg(feature = "sql-tasks")]
 mod db_sql;
 mod db_tcp;
+mod db_tcp_sweep;
 mod db_tls;
+mod db_twamp;
+mod db_udp;
 
 use anyhow::{Context, Result};
 use rusqlite::Connection;
@@ -42,8 +51,27 @@ use tracing::{debug, info, warn};
 /// Default database file name. Using a constant avoids magic strings.
 const DATABASE_FILE: &str = "agent_metrics.db";
 
+/// Columns recording how a task ran during an aggregation period: in a maintenance
+/// window, adaptively rescheduled, suppressed by a down prerequisite, and how long
+/// its runs waited for a concurrency slot
+const ANNOTATION_COLUMNS: [&str; 5] = [
+    "maintenance BOOLEAN NOT NULL DEFAULT 0",
+    "adaptive BOOLEAN NOT NULL DEFAULT 0",
+    "upstream_down BOOLEAN NOT NULL DEFAULT 0",
+    "avg_queue_wait_ms REAL",
+    "max_queue_wait_ms REAL",
+];
+
+/// Add columns to a table created before they existed (migration)
+fn add_columns(conn: &Connection, table: &str, columns: &[&str]) {
+    // SQLite doesn't support IF NOT EXISTS for ALTER TABLE, so we ignore errors
+    for column in columns {
+        let _ = conn.execute(&format!("ALTER TABLE {} ADD COLUMN {}", table, column), []);
+    }
+}
+
 // Re-export queue types for public API
-pub use db_queue::{QueueStats, QueuedMetric};
+pub use db_queue::{QueueStats, QueuedMetric, QueuedRawMetric};
 
 /// SQLite database manager for agent metrics.
 /// This struct encapsulates the database connection and related operations.
@@ -97,10 +125,17 @@ impl AgentDatabase {
         db_http_content::create_tables(conn)?;
         db_dns::create_tables(conn)?;
         db_bandwidth::create_tables(conn)?;
+        db_udp::create_tables(conn)?;
+        db_twamp::create_tables(conn)?;
+        db_tcp_sweep::create_tables(conn)?;
+        db_ntp::create_tables(conn)?;
         #[cfg(feature = "sql-tasks")]
         db_sql::create_tables(conn)?;
         #[cfg(feature = "snmp-tasks")]
         db_snmp::create_tables(conn)?;
+        #[cfg(feature = "snmp-tasks")]
+        db_snmp_trap::create_tables(conn)?;
+        db_execution::create_tables(conn)?;
 
         // Create queue table
         db_queue::create_queue_table(conn)?;
@@ -163,6 +198,14 @@ impl AgentDatabase {
             RawMetricData::Bandwidth(bandwidth_data) => {
                 db_bandwidth::store_raw_metric(conn, metric, bandwidth_data)
             }
+            RawMetricData::UdpProbe(udp_data) => db_udp::store_raw_metric(conn, metric, udp_data),
+            RawMetricData::Twamp(twamp_data) => {
+                db_twamp::store_raw_metric(conn, metric, twamp_data)
+            }
+            RawMetricData::TcpSweep(sweep_data) => {
+                db_tcp_sweep::store_raw_metric(conn, metric, sweep_data)
+            }
+            RawMetricData::Ntp(ntp_data) => db_ntp::store_raw_metric(conn, metric, ntp_data),
             #[cfg(feature = "sql-tasks")]
             RawMetricData::SqlQuery(sql_data) => db_sql::store_raw_metric(conn, metric, sql_data),
             #[cfg(not(feature = "sql-tasks"))]
@@ -175,6 +218,14 @@ impl AgentDatabase {
         }
     }
 
+    /// Store a raw metric and enqueue it for sending, for tasks with `upload_raw` enabled
+    pub async fn store_and_enqueue_raw_metric(&mut self, metric: &MetricData) -> Result<i64> {
+        let row_id = self.store_raw_metric(metric).await?;
+        let conn = self.get_connection()?;
+        db_queue::enqueue_raw_metric_for_send(conn, metric, row_id)?;
+        Ok(row_id)
+    }
+
     /// Generate aggregated metrics using SQL GROUP BY for a specific task and time period.
     pub async fn generate_aggregated_metrics(
         &mut self,
@@ -215,6 +266,18 @@ impl AgentDatabase {
             TaskType::Bandwidth => {
                 db_bandwidth::generate_aggregated_metrics(conn, task_name, period_start, period_end)
             }
+            TaskType::UdpProbe => {
+                db_udp::generate_aggregated_metrics(conn, task_name, period_start, period_end)
+            }
+            TaskType::Twamp => {
+                db_twamp::generate_aggregated_metrics(conn, task_name, period_start, period_end)
+            }
+            TaskType::TcpSweep => {
+                db_tcp_sweep::generate_aggregated_metrics(conn, task_name, period_start, period_end)
+            }
+            TaskType::Ntp => {
+                db_ntp::generate_aggregated_metrics(conn, task_name, period_start, period_end)
+            }
             #[cfg(feature = "sql-tasks")]
             TaskType::SqlQuery => {
                 db_sql::generate_aggregated_metrics(conn, task_name, period_start, period_end)
@@ -247,6 +310,10 @@ impl AgentDatabase {
         let (raw_tls, agg_tls) = db_tls::cleanup_old_data(conn, cutoff_time)?;
         let (raw_dns, agg_dns) = db_dns::cleanup_old_data(conn, cutoff_time)?;
         let (raw_bandwidth, agg_bandwidth) = db_bandwidth::cleanup_old_data(conn, cutoff_time)?;
+        let (raw_udp, agg_udp) = db_udp::cleanup_old_data(conn, cutoff_time)?;
+        let (raw_twamp, agg_twamp) = db_twamp::cleanup_old_data(conn, cutoff_time)?;
+        let (raw_tcp_sweep, agg_tcp_sweep) = db_tcp_sweep::cleanup_old_data(conn, cutoff_time)?;
+        let (raw_ntp, agg_ntp) = db_ntp::cleanup_old_data(conn, cutoff_time)?;
         let (raw_http_content, agg_http_content) =
             db_http_content::cleanup_old_data(conn, cutoff_time)?;
 
@@ -260,12 +327,23 @@ impl AgentDatabase {
         #[cfg(not(feature = "snmp-tasks"))]
         let (raw_snmp, agg_snmp) = (0, 0);
 
+        #[cfg(feature = "snmp-tasks")]
+        let snmp_trap_events = db_snmp_trap::cleanup_old_data(conn, cutoff_time)?;
+        #[cfg(not(feature = "snmp-tasks"))]
+        let snmp_trap_events = 0;
+
+        let execution_rows = db_execution::cleanup_old_data(conn, cutoff_time)?;
+
         let total_raw_deleted = raw_ping
             + raw_tcp
             + raw_http
             + raw_tls
             + raw_dns
             + raw_bandwidth
+            + raw_udp
+            + raw_twamp
+            + raw_tcp_sweep
+            + raw_ntp
             + raw_http_content
             + raw_sql
             + raw_snmp;
@@ -275,9 +353,15 @@ impl AgentDatabase {
             + agg_tls
             + agg_dns
             + agg_bandwidth
+            + agg_udp
+            + agg_twamp
+            + agg_tcp_sweep
+            + agg_ntp
             + agg_http_content
             + agg_sql
-            + agg_snmp;
+            + agg_snmp
+            + snmp_trap_events
+            + execution_rows;
 
         info!(
             "Cleanup complete: {} raw metrics, {} aggregated metrics deleted",
@@ -389,6 +473,18 @@ impl AgentDatabase {
             AggregatedMetricData::Bandwidth(bandwidth_data) => {
                 db_bandwidth::store_aggregated_metric(conn, metrics, bandwidth_data)?
             }
+            AggregatedMetricData::UdpProbe(udp_data) => {
+                db_udp::store_aggregated_metric(conn, metrics, udp_data)?
+            }
+            AggregatedMetricData::Twamp(twamp_data) => {
+                db_twamp::store_aggregated_metric(conn, metrics, twamp_data)?
+            }
+            AggregatedMetricData::TcpSweep(sweep_data) => {
+                db_tcp_sweep::store_aggregated_metric(conn, metrics, sweep_data)?
+            }
+            AggregatedMetricData::Ntp(ntp_data) => {
+                db_ntp::store_aggregated_metric(conn, metrics, ntp_data)?
+            }
             #[cfg(feature = "sql-tasks")]
             AggregatedMetricData::SqlQuery(sql_data) => {
                 db_sql::store_aggregated_metric(conn, metrics, sql_data)?
@@ -405,6 +501,17 @@ impl AgentDatabase {
             AggregatedMetricData::Snmp(_) => {
                 return Err(anyhow::anyhow!("SNMP tasks feature not enabled"));
             }
+            #[cfg(feature = "snmp-tasks")]
+            AggregatedMetricData::SnmpTrap(trap_data) => {
+                db_snmp_trap::store_event(conn, metrics, trap_data)?
+            }
+            #[cfg(not(feature = "snmp-tasks"))]
+            AggregatedMetricData::SnmpTrap(_) => {
+                return Err(anyhow::anyhow!("SNMP tasks feature not enabled"));
+            }
+            AggregatedMetricData::Execution(execution) => {
+                db_execution::store_metric(conn, metrics, execution)?
+            }
             AggregatedMetricData::Unknown => {
                 return Err(anyhow::anyhow!("Unknown metric type cannot be stored"));
             }
@@ -422,6 +529,16 @@ impl AgentDatabase {
         db_queue::get_queued_metrics(conn, batch_size)
     }
 
+    /// Get next batch of raw metrics to send, up to `max_bytes` of serialized JSON,
+    /// respecting exponential backoff
+    pub async fn get_raw_metrics_to_send(
+        &mut self,
+        max_bytes: usize,
+    ) -> Result<Vec<QueuedRawMetric>> {
+        let conn = self.get_connection()?;
+        db_queue::get_queued_raw_metrics(conn, max_bytes)
+    }
+
     /// Mark metrics as being sent (status = 'sending')
     pub async fn mark_as_sending(&mut self, queue_ids: &[i64]) -> Result<()> {
         let conn = self.get_connection()?;
@@ -6,11 +6,13 @@ use shared::{
     config::TaskType,
     metrics::{
         AggregatedBandwidthMetric, AggregatedMetricData, AggregatedMetrics, MetricData,
-        RawBandwidthMetric,
+        RawBandwidthMetric, RawMetricData,
     },
 };
 use tracing::debug;
 
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
+
 /// Create bandwidth-specific tables and indexes
 pub(super) fn create_tables(conn: &Connection) -> Result<()> {
     conn.execute(
@@ -24,7 +26,17 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
             bytes_downloaded INTEGER,
             success BOOLEAN NOT NULL,
             error TEXT,
-            target_id TEXT
+            target_id TEXT,
+            upload_mbps REAL,
+            upload_duration_ms REAL,
+            bytes_uploaded INTEGER,
+            parallel_streams INTEGER,
+            stream_mbps TEXT,
+            throughput_series_mbps TEXT,
+            upload_stream_mbps TEXT,
+            upload_throughput_series_mbps TEXT,
+            target_url TEXT,
+            labels TEXT
         )
         "#,
         [],
@@ -45,6 +57,15 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
             successful_tests INTEGER NOT NULL,
             failed_tests INTEGER NOT NULL,
             target_id TEXT,
+            avg_upload_mbps REAL,
+            max_upload_mbps REAL,
+            min_upload_mbps REAL,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
             UNIQUE(task_name, period_start, period_end)
         )
         "#,
@@ -65,19 +86,60 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Add upload, multi-stream and target columns to existing tables (migration)
+    // SQLite doesn't support IF NOT EXISTS for ALTER TABLE, so we ignore errors
+    for column in [
+        "upload_mbps REAL",
+        "upload_duration_ms REAL",
+        "bytes_uploaded INTEGER",
+        "parallel_streams INTEGER",
+        "stream_mbps TEXT",
+        "throughput_series_mbps TEXT",
+        "upload_stream_mbps TEXT",
+        "upload_throughput_series_mbps TEXT",
+        "target_url TEXT",
+    ] {
+        let _ = conn.execute(
+            &format!("ALTER TABLE raw_metric_bandwidth ADD COLUMN {}", column),
+            [],
+        );
+    }
+    for column in ["avg_upload_mbps", "max_upload_mbps", "min_upload_mbps"] {
+        let _ = conn.execute(
+            &format!(
+                "ALTER TABLE agg_metric_bandwidth ADD COLUMN {} REAL",
+                column
+            ),
+            [],
+        );
+    }
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_bandwidth", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "raw_metric_bandwidth");
+    db_labels::add_column(conn, "agg_metric_bandwidth");
+
     Ok(())
 }
 
 /// Store a raw bandwidth metric
+///
+/// Per-stream throughput and throughput time series are stored as JSON arrays.
 pub(super) fn store_raw_metric(
     conn: &Connection,
     metric: &MetricData,
     bandwidth_data: &RawBandwidthMetric,
 ) -> Result<i64> {
-    let row_id = conn.execute(
+    let labels = db_labels::column_value(&metric.labels)?;
+    let to_json = |values: &Option<Vec<f64>>| -> Result<Option<String>> {
+        Ok(values.as_ref().map(serde_json::to_string).transpose()?)
+    };
+    conn.execute(
         r#"
-        INSERT INTO raw_metric_bandwidth (task_name, timestamp, bandwidth_mbps, duration_ms, bytes_downloaded, success, error, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        INSERT INTO raw_metric_bandwidth (task_name, timestamp, bandwidth_mbps, duration_ms, bytes_downloaded, success, error, target_id, upload_mbps, upload_duration_ms, bytes_uploaded, parallel_streams, stream_mbps, throughput_series_mbps, upload_stream_mbps, upload_throughput_series_mbps, target_url, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)
         "#,
         params![
             metric.task_name,
@@ -87,11 +149,80 @@ pub(super) fn store_raw_metric(
             bandwidth_data.bytes_downloaded.map(|b| b as i64),
             bandwidth_data.success,
             bandwidth_data.error,
-            bandwidth_data.target_id
+            bandwidth_data.target_id,
+            bandwidth_data.upload_mbps,
+            bandwidth_data.upload_duration_ms,
+            bandwidth_data.bytes_uploaded.map(|b| b as i64),
+            bandwidth_data.parallel_streams,
+            to_json(&bandwidth_data.stream_mbps)?,
+            to_json(&bandwidth_data.throughput_series_mbps)?,
+            to_json(&bandwidth_data.upload_stream_mbps)?,
+            to_json(&bandwidth_data.upload_throughput_series_mbps)?,
+            bandwidth_data.target_url,
+            labels
         ],
     )?;
+    let row_id = conn.last_insert_rowid();
     debug!("Stored bandwidth metric with ID: {}", row_id);
-    Ok(row_id as i64)
+    Ok(row_id)
+}
+
+/// Load a raw bandwidth metric by row ID
+pub(super) fn load_raw_metric(conn: &Connection, row_id: i64) -> Result<Option<MetricData>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, timestamp, labels, bandwidth_mbps, duration_ms, bytes_downloaded, upload_mbps, upload_duration_ms, bytes_uploaded, parallel_streams, stream_mbps, throughput_series_mbps, upload_stream_mbps, upload_throughput_series_mbps, target_url, success, error, target_id
+         FROM raw_metric_bandwidth WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        let from_json = |index: usize| -> rusqlite::Result<Option<Vec<f64>>> {
+            let values: Option<String> = row.get(index)?;
+            values
+                .map(|json| serde_json::from_str(&json))
+                .transpose()
+                .map_err(|e| {
+                    rusqlite::Error::FromSqlConversionFailure(
+                        index,
+                        rusqlite::types::Type::Text,
+                        Box::new(e),
+                    )
+                })
+        };
+        let stream_mbps = from_json(10)?;
+        let throughput_series_mbps = from_json(11)?;
+        let upload_stream_mbps = from_json(12)?;
+        let upload_throughput_series_mbps = from_json(13)?;
+        Ok(MetricData {
+            task_name: row.get(0)?,
+            task_type: TaskType::Bandwidth,
+            timestamp: row.get::<_, i64>(1)? as u64,
+            labels: db_labels::read_column(row, 2)?,
+            row_id: Some(row_id),
+            data: RawMetricData::Bandwidth(RawBandwidthMetric {
+                bandwidth_mbps: row.get(3)?,
+                duration_ms: row.get(4)?,
+                bytes_downloaded: row.get(5)?,
+                upload_mbps: row.get(6)?,
+                upload_duration_ms: row.get(7)?,
+                bytes_uploaded: row.get(8)?,
+                parallel_streams: row.get(9)?,
+                stream_mbps,
+                throughput_series_mbps,
+                upload_stream_mbps,
+                upload_throughput_series_mbps,
+                target_url: row.get(14)?,
+                success: row.get(15)?,
+                error: row.get(16)?,
+                target_id: row.get(17)?,
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
 }
 
 /// Generate aggregated bandwidth metrics for a time period
@@ -108,6 +239,9 @@ pub(super) fn generate_aggregated_metrics(
             AVG(CASE WHEN success = 1 AND bandwidth_mbps IS NOT NULL THEN bandwidth_mbps END) as avg_bandwidth,
             MAX(CASE WHEN success = 1 AND bandwidth_mbps IS NOT NULL THEN bandwidth_mbps END) as max_bandwidth,
             MIN(CASE WHEN success = 1 AND bandwidth_mbps IS NOT NULL THEN bandwidth_mbps END) as min_bandwidth,
+            AVG(CASE WHEN success = 1 THEN upload_mbps END) as avg_upload,
+            MAX(CASE WHEN success = 1 THEN upload_mbps END) as max_upload,
+            MIN(CASE WHEN success = 1 THEN upload_mbps END) as min_upload,
             SUM(CASE WHEN success = 1 THEN 1 ELSE 0 END) as successful_tests,
             SUM(CASE WHEN success = 0 THEN 1 ELSE 0 END) as failed_tests,
             (SELECT target_id FROM raw_metric_bandwidth
@@ -136,6 +270,9 @@ pub(super) fn generate_aggregated_metrics(
                 avg_bandwidth_mbps: row.get("avg_bandwidth").unwrap_or(0.0),
                 max_bandwidth_mbps: row.get("max_bandwidth").unwrap_or(0.0),
                 min_bandwidth_mbps: row.get("min_bandwidth").unwrap_or(0.0),
+                avg_upload_mbps: row.get("avg_upload")?,
+                max_upload_mbps: row.get("max_upload")?,
+                min_upload_mbps: row.get("min_upload")?,
                 successful_tests: successful_tests as u32,
                 failed_tests: failed_tests as u32,
                 target_id,
@@ -164,11 +301,12 @@ pub(super) fn store_aggregated_metric(
     metrics: &AggregatedMetrics,
     bandwidth_data: &AggregatedBandwidthMetric,
 ) -> Result<i64> {
+    let labels = db_labels::column_value(&metrics.labels)?;
     conn.execute(
         r#"
         INSERT OR REPLACE INTO agg_metric_bandwidth
-        (task_name, period_start, period_end, sample_count, avg_bandwidth_mbps, max_bandwidth_mbps, min_bandwidth_mbps, successful_tests, failed_tests, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+        (task_name, period_start, period_end, sample_count, avg_bandwidth_mbps, max_bandwidth_mbps, min_bandwidth_mbps, successful_tests, failed_tests, target_id, avg_upload_mbps, max_upload_mbps, min_upload_mbps, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)
         "#,
         params![
             metrics.task_name,
@@ -180,7 +318,16 @@ pub(super) fn store_aggregated_metric(
             bandwidth_data.min_bandwidth_mbps,
             bandwidth_data.successful_tests,
             bandwidth_data.failed_tests,
-            bandwidth_data.target_id
+            bandwidth_data.target_id,
+            bandwidth_data.avg_upload_mbps,
+            bandwidth_data.max_upload_mbps,
+            bandwidth_data.min_upload_mbps,
+            metrics.maintenance,
+            metrics.adaptive,
+            metrics.upstream_down,
+            metrics.avg_queue_wait_ms,
+            metrics.max_queue_wait_ms,
+            labels,
         ],
     )?;
     Ok(conn.last_insert_rowid())
@@ -194,7 +341,8 @@ pub(super) fn load_aggregated_metric(
     let mut stmt = conn.prepare(
         "SELECT task_name, period_start, period_end, sample_count,
                 avg_bandwidth_mbps, max_bandwidth_mbps, min_bandwidth_mbps,
-                successful_tests, failed_tests, target_id
+                successful_tests, failed_tests, target_id,
+                avg_upload_mbps, max_upload_mbps, min_upload_mbps, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels
          FROM agg_metric_bandwidth WHERE id = ?1",
     )?;
 
@@ -205,6 +353,12 @@ pub(super) fn load_aggregated_metric(
             period_start: row.get::<_, i64>(1)? as u64,
             period_end: row.get::<_, i64>(2)? as u64,
             sample_count: row.get(3)?,
+            maintenance: row.get(13)?,
+            adaptive: row.get(14)?,
+            upstream_down: row.get(15)?,
+            avg_queue_wait_ms: row.get(16)?,
+            max_queue_wait_ms: row.get(17)?,
+            labels: db_labels::read_column(row, 18)?,
             data: AggregatedMetricData::Bandwidth(AggregatedBandwidthMetric {
                 avg_bandwidth_mbps: row.get(4)?,
                 max_bandwidth_mbps: row.get(5)?,
@@ -212,6 +366,9 @@ pub(super) fn load_aggregated_metric(
                 successful_tests: row.get(7)?,
                 failed_tests: row.get(8)?,
                 target_id: row.get(9).ok(),
+                avg_upload_mbps: row.get(10)?,
+                max_upload_mbps: row.get(11)?,
+                min_upload_mbps: row.get(12)?,
             }),
         })
     });
@@ -226,7 +383,14 @@ pub(super) fn load_aggregated_metric(
 /// Clean up old bandwidth metrics
 pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<(usize, usize)> {
     let raw_deleted = conn.execute(
-        "DELETE FROM raw_metric_bandwidth WHERE timestamp < ?1",
+        r#"
+        DELETE FROM raw_metric_bandwidth
+        WHERE timestamp < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'raw_bandwidth' AND status != 'sent'
+          )
+        "#,
         params![cutoff_time],
     )?;
 
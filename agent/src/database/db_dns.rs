@@ -6,11 +6,14 @@ use shared::{
     config::TaskType,
     metrics::{
         AggregatedDnsMetric, AggregatedMetricData, AggregatedMetrics, MetricData, RawDnsMetric,
+        RawMetricData,
     },
 };
 use std::collections::HashSet;
 use tracing::debug;
 
+use super::{add_columns, db_labels, db_latency, ANNOTATION_COLUMNS};
+
 /// Create DNS-specific tables and indexes
 pub(super) fn create_tables(conn: &Connection) -> Result<()> {
     conn.execute(
@@ -28,7 +31,8 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
             expected_ip TEXT,
             resolved_ip TEXT,
             correct_resolution BOOLEAN NOT NULL DEFAULT 1,
-            target_id TEXT
+            target_id TEXT,
+            labels TEXT
         )
         "#,
         [],
@@ -52,6 +56,17 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
             domain_queried TEXT NOT NULL,
             correct_resolution_percent REAL NOT NULL DEFAULT 100.0,
             target_id TEXT,
+            p50_ms REAL,
+            p90_ms REAL,
+            p95_ms REAL,
+            p99_ms REAL,
+            latency_histogram TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
             UNIQUE(task_name, period_start, period_end)
         )
         "#,
@@ -72,6 +87,16 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Add latency distribution columns to existing tables (migration)
+    db_latency::add_columns(conn, "agg_metric_dns");
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_dns", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "raw_metric_dns");
+    db_labels::add_column(conn, "agg_metric_dns");
+
     Ok(())
 }
 
@@ -81,15 +106,16 @@ pub(super) fn store_raw_metric(
     metric: &MetricData,
     dns_data: &RawDnsMetric,
 ) -> Result<i64> {
+    let labels = db_labels::column_value(&metric.labels)?;
     let resolved_addresses_json = dns_data
         .resolved_addresses
         .as_ref()
         .map(|addrs| serde_json::to_string(addrs).unwrap_or_else(|_| "[]".to_string()));
 
-    let row_id = conn.execute(
+    conn.execute(
         r#"
-        INSERT INTO raw_metric_dns (task_name, timestamp, query_time_ms, success, record_count, resolved_addresses, domain_queried, error, expected_ip, resolved_ip, correct_resolution, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        INSERT INTO raw_metric_dns (task_name, timestamp, query_time_ms, success, record_count, resolved_addresses, domain_queried, error, expected_ip, resolved_ip, correct_resolution, target_id, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
         "#,
         params![
             metric.task_name,
@@ -103,11 +129,60 @@ pub(super) fn store_raw_metric(
             dns_data.expected_ip,
             dns_data.resolved_ip,
             dns_data.correct_resolution,
-            dns_data.target_id
+            dns_data.target_id,
+            labels
         ],
     )?;
+    let row_id = conn.last_insert_rowid();
     debug!("Stored DNS metric with ID: {}", row_id);
-    Ok(row_id as i64)
+    Ok(row_id)
+}
+
+/// Load a raw DNS metric by row ID
+pub(super) fn load_raw_metric(conn: &Connection, row_id: i64) -> Result<Option<MetricData>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, timestamp, labels, query_time_ms, success, record_count, resolved_addresses, domain_queried, error, expected_ip, resolved_ip, correct_resolution, target_id
+         FROM raw_metric_dns WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        let resolved_addresses: Option<String> = row.get(6)?;
+        let resolved_addresses = resolved_addresses
+            .map(|json| serde_json::from_str(&json))
+            .transpose()
+            .map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(
+                    6,
+                    rusqlite::types::Type::Text,
+                    Box::new(e),
+                )
+            })?;
+        Ok(MetricData {
+            task_name: row.get(0)?,
+            task_type: TaskType::DnsQuery,
+            timestamp: row.get::<_, i64>(1)? as u64,
+            labels: db_labels::read_column(row, 2)?,
+            row_id: Some(row_id),
+            data: RawMetricData::DnsQuery(RawDnsMetric {
+                query_time_ms: row.get(3)?,
+                success: row.get(4)?,
+                record_count: row.get(5)?,
+                resolved_addresses,
+                domain_queried: row.get(7)?,
+                error: row.get(8)?,
+                expected_ip: row.get(9)?,
+                resolved_ip: row.get(10)?,
+                correct_resolution: row.get(11)?,
+                target_id: row.get(12)?,
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
 }
 
 /// Generate aggregated DNS metrics for a time period
@@ -207,16 +282,26 @@ pub(super) fn generate_aggregated_metrics(
         |row| row.get::<_, String>(0),
     )?;
 
-    for json_result in rows {
-        if let Ok(json_str) = json_result {
-            if let Ok(addresses) = serde_json::from_str::<Vec<String>>(&json_str) {
-                for addr in addresses {
-                    all_resolved_addresses.insert(addr);
-                }
+    for json_str in rows.flatten() {
+        if let Ok(addresses) = serde_json::from_str::<Vec<String>>(&json_str) {
+            for addr in addresses {
+                all_resolved_addresses.insert(addr);
             }
         }
     }
 
+    let latency = db_latency::query_distribution(
+        conn,
+        r#"
+        SELECT query_time_ms FROM raw_metric_dns
+        WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+        AND success = 1 AND query_time_ms IS NOT NULL
+        "#,
+        task_name,
+        period_start,
+        period_end,
+    )?;
+
     let dns_metric = AggregatedDnsMetric {
         success_rate_percent,
         avg_query_time_ms,
@@ -227,6 +312,7 @@ pub(super) fn generate_aggregated_metrics(
         domain_queried,
         correct_resolution_percent,
         target_id,
+        latency,
     };
 
     let total_samples = dns_metric.successful_queries + dns_metric.failed_queries;
@@ -246,14 +332,18 @@ pub(super) fn store_aggregated_metric(
     metrics: &AggregatedMetrics,
     dns_data: &AggregatedDnsMetric,
 ) -> Result<i64> {
+    let labels = db_labels::column_value(&metrics.labels)?;
     let all_addresses_json = serde_json::to_string(&dns_data.all_resolved_addresses)
         .unwrap_or_else(|_| "[]".to_string());
 
+    let (p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram) =
+        db_latency::column_values(&dns_data.latency)?;
     conn.execute(
         r#"
         INSERT OR REPLACE INTO agg_metric_dns
-        (task_name, period_start, period_end, sample_count, success_rate_percent, avg_query_time_ms, max_query_time_ms, successful_queries, failed_queries, all_resolved_addresses, domain_queried, correct_resolution_percent, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+        (task_name, period_start, period_end, sample_count, success_rate_percent, avg_query_time_ms, max_query_time_ms, successful_queries, failed_queries, all_resolved_addresses, domain_queried, correct_resolution_percent, target_id,
+         p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24)
         "#,
         params![
             metrics.task_name,
@@ -268,7 +358,18 @@ pub(super) fn store_aggregated_metric(
             all_addresses_json,
             dns_data.domain_queried,
             dns_data.correct_resolution_percent,
-            dns_data.target_id
+            dns_data.target_id,
+            p50_ms,
+            p90_ms,
+            p95_ms,
+            p99_ms,
+            latency_histogram,
+            metrics.maintenance,
+            metrics.adaptive,
+            metrics.upstream_down,
+            metrics.avg_queue_wait_ms,
+            metrics.max_queue_wait_ms,
+            labels,
         ],
     )?;
     Ok(conn.last_insert_rowid())
@@ -283,7 +384,8 @@ pub(super) fn load_aggregated_metric(
         "SELECT task_name, period_start, period_end, sample_count,
                 success_rate_percent, avg_query_time_ms, max_query_time_ms,
                 successful_queries, failed_queries, all_resolved_addresses,
-                domain_queried, correct_resolution_percent, target_id
+                domain_queried, correct_resolution_percent, target_id,
+                p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels
          FROM agg_metric_dns WHERE id = ?1",
     )?;
 
@@ -298,6 +400,12 @@ pub(super) fn load_aggregated_metric(
             period_start: row.get::<_, i64>(1)? as u64,
             period_end: row.get::<_, i64>(2)? as u64,
             sample_count: row.get(3)?,
+            maintenance: row.get(18)?,
+            adaptive: row.get(19)?,
+            upstream_down: row.get(20)?,
+            avg_queue_wait_ms: row.get(21)?,
+            max_queue_wait_ms: row.get(22)?,
+            labels: db_labels::read_column(row, 23)?,
             data: AggregatedMetricData::DnsQuery(AggregatedDnsMetric {
                 success_rate_percent: row.get(4)?,
                 avg_query_time_ms: row.get(5)?,
@@ -308,6 +416,7 @@ pub(super) fn load_aggregated_metric(
                 domain_queried: row.get(10)?,
                 correct_resolution_percent: row.get(11)?,
                 target_id: row.get(12).ok(),
+                latency: db_latency::read_columns(row, 13)?,
             }),
         })
     });
@@ -322,7 +431,14 @@ pub(super) fn load_aggregated_metric(
 /// Clean up old DNS metrics
 pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<(usize, usize)> {
     let raw_deleted = conn.execute(
-        "DELETE FROM raw_metric_dns WHERE timestamp < ?1",
+        r#"
+        DELETE FROM raw_metric_dns
+        WHERE timestamp < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'raw_dns' AND status != 'sent'
+          )
+        "#,
         params![cutoff_time],
     )?;
 
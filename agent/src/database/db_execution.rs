@@ -0,0 +1,164 @@
+//! Task execution statistics database operations
+//!
+//! One row per task and aggregation period in which runs were skipped because
+//! the previous run overran or a prerequisite was down, started late or timed
+//! out. Rows are forwarded to
+//! the server through the send queue alongside the task's aggregated metrics.
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use shared::{
+    config::TaskType,
+    metrics::{AggregatedMetricData, AggregatedMetrics, TaskExecutionMetric},
+};
+use tracing::debug;
+
+use super::db_labels;
+
+/// Create task execution table and indexes
+pub(super) fn create_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS task_execution (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_name TEXT NOT NULL,
+            task_type TEXT NOT NULL,
+            period_start INTEGER NOT NULL,
+            period_end INTEGER NOT NULL,
+            runs_started INTEGER NOT NULL,
+            skipped_overruns INTEGER NOT NULL,
+            delayed_starts INTEGER NOT NULL,
+            max_start_delay_ms REAL NOT NULL,
+            timed_out_runs INTEGER NOT NULL,
+            suppressed_runs INTEGER NOT NULL DEFAULT 0,
+            labels TEXT
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create task_execution table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_task_execution_period ON task_execution(period_start)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_task_execution_task ON task_execution(task_name, period_start)",
+        [],
+    )?;
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "task_execution");
+
+    // Add suppressed_runs column to existing tables (migration)
+    let _ = conn.execute(
+        "ALTER TABLE task_execution ADD COLUMN suppressed_runs INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+
+    Ok(())
+}
+
+/// Store the execution statistics of a task for one period
+pub(super) fn store_metric(
+    conn: &Connection,
+    metrics: &AggregatedMetrics,
+    execution: &TaskExecutionMetric,
+) -> Result<i64> {
+    let task_type = serde_json::to_value(&metrics.task_type)
+        .context("Failed to serialize task type")?
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    let labels = db_labels::column_value(&metrics.labels)?;
+
+    conn.execute(
+        r#"
+        INSERT INTO task_execution
+        (task_name, task_type, period_start, period_end, runs_started,
+         skipped_overruns, delayed_starts, max_start_delay_ms, timed_out_runs, suppressed_runs, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+        "#,
+        params![
+            metrics.task_name,
+            task_type,
+            metrics.period_start as i64,
+            metrics.period_end as i64,
+            execution.runs_started,
+            execution.skipped_overruns,
+            execution.delayed_starts,
+            execution.max_start_delay_ms,
+            execution.timed_out_runs,
+            execution.suppressed_runs,
+            labels
+        ],
+    )?;
+    let row_id = conn.last_insert_rowid();
+    debug!("Stored task execution statistics with ID: {}", row_id);
+    Ok(row_id)
+}
+
+/// Load the execution statistics stored under a row ID
+pub(super) fn load_aggregated_metric(
+    conn: &Connection,
+    row_id: i64,
+) -> Result<Option<AggregatedMetrics>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, task_type, period_start, period_end, runs_started,
+                skipped_overruns, delayed_starts, max_start_delay_ms, timed_out_runs, suppressed_runs, labels
+         FROM task_execution WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        let task_type: String = row.get(1)?;
+        let task_type = serde_json::from_value::<TaskType>(serde_json::Value::String(task_type))
+            .map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, e.into())
+            })?;
+        let runs_started: u32 = row.get(4)?;
+        Ok(AggregatedMetrics {
+            task_name: row.get(0)?,
+            task_type,
+            period_start: row.get::<_, i64>(2)? as u64,
+            period_end: row.get::<_, i64>(3)? as u64,
+            sample_count: runs_started,
+            maintenance: false,
+            adaptive: false,
+            upstream_down: false,
+            avg_queue_wait_ms: None,
+            max_queue_wait_ms: None,
+            labels: db_labels::read_column(row, 10)?,
+            data: AggregatedMetricData::Execution(TaskExecutionMetric {
+                runs_started,
+                skipped_overruns: row.get(5)?,
+                delayed_starts: row.get(6)?,
+                max_start_delay_ms: row.get(7)?,
+                timed_out_runs: row.get(8)?,
+                suppressed_runs: row.get(9)?,
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Clean up old execution statistics that are no longer waiting to be sent
+pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        r#"
+        DELETE FROM task_execution
+        WHERE period_end < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'execution' AND status != 'sent'
+          )
+        "#,
+        params![cutoff_time],
+    )?;
+
+    Ok(deleted)
+}
@@ -11,10 +11,13 @@ use rusqlite::{params, Connection};
 use shared::config::TaskType;
 use shared::metrics::{
     AggregatedHttpMetric, AggregatedMetricData, AggregatedMetrics, MetricData, RawHttpMetric,
+    RawMetricData,
 };
 use std::collections::HashMap;
 use tracing::debug;
 
+use super::{add_columns, db_labels, db_latency, ANNOTATION_COLUMNS};
+
 /// Create HTTP-specific tables and indexes
 pub(super) fn create_tables(conn: &Connection) -> Result<()> {
     conn.execute(
@@ -33,7 +36,8 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
             error TEXT,
             ssl_valid BOOLEAN,
             ssl_cert_days_until_expiry INTEGER,
-            target_id TEXT
+            target_id TEXT,
+            labels TEXT
         )
         "#,
         [],
@@ -61,6 +65,17 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
             ssl_valid_percent REAL,
             avg_ssl_cert_days_until_expiry REAL,
             target_id TEXT,
+            p50_ms REAL,
+            p90_ms REAL,
+            p95_ms REAL,
+            p99_ms REAL,
+            latency_histogram TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
             UNIQUE(task_name, period_start, period_end)
         )
         "#,
@@ -81,6 +96,16 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Add latency distribution columns to existing tables (migration)
+    db_latency::add_columns(conn, "agg_metric_http");
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_http", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "raw_metric_http");
+    db_labels::add_column(conn, "agg_metric_http");
+
     Ok(())
 }
 
@@ -90,10 +115,11 @@ pub(super) fn store_raw_metric(
     metric: &MetricData,
     http_data: &RawHttpMetric,
 ) -> Result<i64> {
-    let row_id = conn.execute(
+    let labels = db_labels::column_value(&metric.labels)?;
+    conn.execute(
         r#"
-        INSERT INTO raw_metric_http (task_name, timestamp, status_code, tcp_timing_ms, tls_timing_ms, ttfb_timing_ms, content_download_timing_ms, total_time_ms, success, error, ssl_valid, ssl_cert_days_until_expiry, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+        INSERT INTO raw_metric_http (task_name, timestamp, status_code, tcp_timing_ms, tls_timing_ms, ttfb_timing_ms, content_download_timing_ms, total_time_ms, success, error, ssl_valid, ssl_cert_days_until_expiry, target_id, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
         "#,
         params![
             metric.task_name,
@@ -108,11 +134,50 @@ pub(super) fn store_raw_metric(
             http_data.error,
             http_data.ssl_valid,
             http_data.ssl_cert_days_until_expiry,
-            http_data.target_id
+            http_data.target_id,
+            labels
         ],
     )?;
+    let row_id = conn.last_insert_rowid();
     debug!("Stored HTTP metric with ID: {}", row_id);
-    Ok(row_id as i64)
+    Ok(row_id)
+}
+
+/// Load a raw HTTP metric by row ID
+pub(super) fn load_raw_metric(conn: &Connection, row_id: i64) -> Result<Option<MetricData>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, timestamp, labels, status_code, tcp_timing_ms, tls_timing_ms, ttfb_timing_ms, content_download_timing_ms, total_time_ms, success, error, ssl_valid, ssl_cert_days_until_expiry, target_id
+         FROM raw_metric_http WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        Ok(MetricData {
+            task_name: row.get(0)?,
+            task_type: TaskType::HttpGet,
+            timestamp: row.get::<_, i64>(1)? as u64,
+            labels: db_labels::read_column(row, 2)?,
+            row_id: Some(row_id),
+            data: RawMetricData::HttpGet(RawHttpMetric {
+                status_code: row.get(3)?,
+                tcp_timing_ms: row.get(4)?,
+                tls_timing_ms: row.get(5)?,
+                ttfb_timing_ms: row.get(6)?,
+                content_download_timing_ms: row.get(7)?,
+                total_time_ms: row.get(8)?,
+                success: row.get(9)?,
+                error: row.get(10)?,
+                ssl_valid: row.get(11)?,
+                ssl_cert_days_until_expiry: row.get(12)?,
+                target_id: row.get(13)?,
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
 }
 
 /// Generate aggregated HTTP metrics for a period
@@ -169,6 +234,18 @@ pub(super) fn generate_aggregated_metrics(
         status_code_distribution.insert(row.0, row.1);
     }
 
+    let latency = db_latency::query_distribution(
+        conn,
+        r#"
+        SELECT total_time_ms FROM raw_metric_http
+        WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+        AND success = 1 AND total_time_ms IS NOT NULL
+        "#,
+        task_name,
+        period_start,
+        period_end,
+    )?;
+
     let row = stmt.query_row(
         params![task_name, period_start as i64, period_end as i64],
         |row| {
@@ -201,6 +278,7 @@ pub(super) fn generate_aggregated_metrics(
                 ssl_valid_percent: row.get("ssl_valid_percent").ok(),
                 avg_ssl_cert_days_until_expiry: row.get("avg_ssl_cert_days_until_expiry").ok(),
                 target_id,
+                latency,
             }))
         },
     )?;
@@ -226,6 +304,7 @@ pub(super) fn store_aggregated_metric(
     metrics: &AggregatedMetrics,
     http_data: &AggregatedHttpMetric,
 ) -> Result<i64> {
+    let labels = db_labels::column_value(&metrics.labels)?;
     // Convert HashMap to Vec of tuples for proper JSON serialization
     // JSON only supports string keys, so we serialize as array of [code, count] pairs
     let status_code_vec: Vec<(u16, u32)> = http_data
@@ -234,11 +313,14 @@ pub(super) fn store_aggregated_metric(
         .map(|(&k, &v)| (k, v))
         .collect();
     let status_code_json = serde_json::to_string(&status_code_vec)?;
+    let (p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram) =
+        db_latency::column_values(&http_data.latency)?;
     conn.execute(
         r#"
         INSERT OR REPLACE INTO agg_metric_http
-        (task_name, period_start, period_end, sample_count, success_rate_percent, avg_tcp_timing_ms, avg_tls_timing_ms, avg_ttfb_timing_ms, avg_content_download_timing_ms, avg_total_time_ms, max_total_time_ms, successful_requests, failed_requests, status_code_distribution, ssl_valid_percent, avg_ssl_cert_days_until_expiry, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
+        (task_name, period_start, period_end, sample_count, success_rate_percent, avg_tcp_timing_ms, avg_tls_timing_ms, avg_ttfb_timing_ms, avg_content_download_timing_ms, avg_total_time_ms, max_total_time_ms, successful_requests, failed_requests, status_code_distribution, ssl_valid_percent, avg_ssl_cert_days_until_expiry, target_id,
+         p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28)
         "#,
         params![
             metrics.task_name,
@@ -257,7 +339,18 @@ pub(super) fn store_aggregated_metric(
             status_code_json,
             http_data.ssl_valid_percent,
             http_data.avg_ssl_cert_days_until_expiry,
-            http_data.target_id
+            http_data.target_id,
+            p50_ms,
+            p90_ms,
+            p95_ms,
+            p99_ms,
+            latency_histogram,
+            metrics.maintenance,
+            metrics.adaptive,
+            metrics.upstream_down,
+            metrics.avg_queue_wait_ms,
+            metrics.max_queue_wait_ms,
+            labels,
         ],
     )?;
     Ok(conn.last_insert_rowid())
@@ -274,7 +367,8 @@ pub(super) fn load_aggregated_metric(
                 avg_tls_timing_ms, avg_ttfb_timing_ms, avg_content_download_timing_ms,
                 avg_total_time_ms, max_total_time_ms, successful_requests,
                 failed_requests, status_code_distribution, ssl_valid_percent,
-                avg_ssl_cert_days_until_expiry, target_id
+                avg_ssl_cert_days_until_expiry, target_id,
+                p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels
          FROM agg_metric_http WHERE id = ?1",
     )?;
 
@@ -291,6 +385,12 @@ pub(super) fn load_aggregated_metric(
             period_start: row.get::<_, i64>(1)? as u64,
             period_end: row.get::<_, i64>(2)? as u64,
             sample_count: row.get(3)?,
+            maintenance: row.get(22)?,
+            adaptive: row.get(23)?,
+            upstream_down: row.get(24)?,
+            avg_queue_wait_ms: row.get(25)?,
+            max_queue_wait_ms: row.get(26)?,
+            labels: db_labels::read_column(row, 27)?,
             data: AggregatedMetricData::HttpGet(AggregatedHttpMetric {
                 success_rate_percent: row.get(4)?,
                 avg_tcp_timing_ms: row.get(5)?,
@@ -305,6 +405,7 @@ pub(super) fn load_aggregated_metric(
                 ssl_valid_percent: row.get(14).ok(),
                 avg_ssl_cert_days_until_expiry: row.get(15).ok(),
                 target_id: row.get(16).ok(),
+                latency: db_latency::read_columns(row, 17)?,
             }),
         })
     });
@@ -319,7 +420,14 @@ pub(super) fn load_aggregated_metric(
 /// Clean up old HTTP metrics
 pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<(usize, usize)> {
     let raw_deleted = conn.execute(
-        "DELETE FROM raw_metric_http WHERE timestamp < ?1",
+        r#"
+        DELETE FROM raw_metric_http
+        WHERE timestamp < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'raw_http' AND status != 'sent'
+          )
+        "#,
         params![cutoff_time],
     )?;
 
@@ -6,11 +6,13 @@ use shared::{
     config::TaskType,
     metrics::{
         AggregatedHttpContentMetric, AggregatedMetricData, AggregatedMetrics, MetricData,
-        RawHttpContentMetric,
+        RawHttpContentMetric, RawMetricData,
     },
 };
 use tracing::debug;
 
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
+
 /// Create HTTP content check-specific tables and indexes
 pub(super) fn create_tables(conn: &Connection) -> Result<()> {
     conn.execute(
@@ -25,7 +27,8 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
             regexp_match BOOLEAN,
             success BOOLEAN NOT NULL,
             error TEXT,
-            target_id TEXT
+            target_id TEXT,
+            labels TEXT
         )
         "#,
         [],
@@ -49,6 +52,12 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
             failed_requests INTEGER NOT NULL,
             regexp_matched_count INTEGER NOT NULL,
             target_id TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
             UNIQUE(task_name, period_start, period_end)
         )
         "#,
@@ -69,6 +78,13 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_http_content", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "raw_metric_http_content");
+    db_labels::add_column(conn, "agg_metric_http_content");
+
     Ok(())
 }
 
@@ -78,10 +94,11 @@ pub(super) fn store_raw_metric(
     metric: &MetricData,
     http_content_data: &RawHttpContentMetric,
 ) -> Result<i64> {
-    let row_id = conn.execute(
+    let labels = db_labels::column_value(&metric.labels)?;
+    conn.execute(
         r#"
-        INSERT INTO raw_metric_http_content (task_name, timestamp, status_code, total_time_ms, total_size, regexp_match, success, error, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        INSERT INTO raw_metric_http_content (task_name, timestamp, status_code, total_time_ms, total_size, regexp_match, success, error, target_id, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
         "#,
         params![
             metric.task_name,
@@ -92,11 +109,46 @@ pub(super) fn store_raw_metric(
             http_content_data.regexp_match,
             http_content_data.success,
             http_content_data.error,
-            http_content_data.target_id
+            http_content_data.target_id,
+            labels
         ],
     )?;
+    let row_id = conn.last_insert_rowid();
     debug!("Stored HTTP content metric with ID: {}", row_id);
-    Ok(row_id as i64)
+    Ok(row_id)
+}
+
+/// Load a raw HTTP content metric by row ID
+pub(super) fn load_raw_metric(conn: &Connection, row_id: i64) -> Result<Option<MetricData>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, timestamp, labels, status_code, total_time_ms, total_size, regexp_match, success, error, target_id
+         FROM raw_metric_http_content WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        Ok(MetricData {
+            task_name: row.get(0)?,
+            task_type: TaskType::HttpContent,
+            timestamp: row.get::<_, i64>(1)? as u64,
+            labels: db_labels::read_column(row, 2)?,
+            row_id: Some(row_id),
+            data: RawMetricData::HttpContent(RawHttpContentMetric {
+                status_code: row.get(3)?,
+                total_time_ms: row.get(4)?,
+                total_size: row.get(5)?,
+                regexp_match: row.get(6)?,
+                success: row.get(7)?,
+                error: row.get(8)?,
+                target_id: row.get(9)?,
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
 }
 
 /// Generate aggregated HTTP content metrics for a time period
@@ -188,11 +240,12 @@ pub(super) fn store_aggregated_metric(
     metrics: &AggregatedMetrics,
     http_content_data: &AggregatedHttpContentMetric,
 ) -> Result<i64> {
+    let labels = db_labels::column_value(&metrics.labels)?;
     conn.execute(
         r#"
         INSERT OR REPLACE INTO agg_metric_http_content
-        (task_name, period_start, period_end, sample_count, success_rate_percent, avg_total_time_ms, max_total_time_ms, avg_total_size, regexp_match_rate_percent, successful_requests, failed_requests, regexp_matched_count, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+        (task_name, period_start, period_end, sample_count, success_rate_percent, avg_total_time_ms, max_total_time_ms, avg_total_size, regexp_match_rate_percent, successful_requests, failed_requests, regexp_matched_count, target_id, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)
         "#,
         params![
             metrics.task_name,
@@ -207,7 +260,13 @@ pub(super) fn store_aggregated_metric(
             http_content_data.successful_requests,
             http_content_data.failed_requests,
             http_content_data.regexp_matched_count,
-            http_content_data.target_id
+            http_content_data.target_id,
+            metrics.maintenance,
+            metrics.adaptive,
+            metrics.upstream_down,
+            metrics.avg_queue_wait_ms,
+            metrics.max_queue_wait_ms,
+            labels,
         ],
     )?;
     Ok(conn.last_insert_rowid())
@@ -222,7 +281,7 @@ pub(super) fn load_aggregated_metric(
         "SELECT task_name, period_start, period_end, sample_count,
                 success_rate_percent, avg_total_time_ms, max_total_time_ms,
                 avg_total_size, regexp_match_rate_percent, successful_requests,
-                failed_requests, regexp_matched_count, target_id
+                failed_requests, regexp_matched_count, target_id, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels
          FROM agg_metric_http_content WHERE id = ?1",
     )?;
 
@@ -233,6 +292,12 @@ pub(super) fn load_aggregated_metric(
             period_start: row.get::<_, i64>(1)? as u64,
             period_end: row.get::<_, i64>(2)? as u64,
             sample_count: row.get(3)?,
+            maintenance: row.get(13)?,
+            adaptive: row.get(14)?,
+            upstream_down: row.get(15)?,
+            avg_queue_wait_ms: row.get(16)?,
+            max_queue_wait_ms: row.get(17)?,
+            labels: db_labels::read_column(row, 18)?,
             data: AggregatedMetricData::HttpContent(AggregatedHttpContentMetric {
                 success_rate_percent: row.get(4)?,
                 avg_total_time_ms: row.get(5)?,
@@ -257,7 +322,14 @@ pub(super) fn load_aggregated_metric(
 /// Clean up old HTTP content metrics
 pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<(usize, usize)> {
     let raw_deleted = conn.execute(
-        "DELETE FROM raw_metric_http_content WHERE timestamp < ?1",
+        r#"
+        DELETE FROM raw_metric_http_content
+        WHERE timestamp < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'raw_http_content' AND status != 'sent'
+          )
+        "#,
         params![cutoff_time],
     )?;
 
@@ -0,0 +1,37 @@
+//! Task label database operations
+//!
+//! Helpers shared by the task modules for the `labels` column of raw and
+//! aggregated tables. Labels are stored as a JSON object, or NULL when the
+//! task has no labels.
+
+use anyhow::Result;
+use rusqlite::{Connection, Row};
+use std::collections::BTreeMap;
+
+/// Add the labels column to a table created before it existed
+pub(super) fn add_column(conn: &Connection, table: &str) {
+    super::add_columns(conn, table, &["labels TEXT"]);
+}
+
+/// Column value for storing a task's labels
+pub(super) fn column_value(labels: &BTreeMap<String, String>) -> Result<Option<String>> {
+    if labels.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::to_string(labels)?))
+}
+
+/// Read the labels stored in the column at `index`
+pub(super) fn read_column(row: &Row, index: usize) -> rusqlite::Result<BTreeMap<String, String>> {
+    let labels: Option<String> = row.get(index)?;
+    match labels {
+        Some(labels) => serde_json::from_str(&labels).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(
+                index,
+                rusqlite::types::Type::Text,
+                Box::new(e),
+            )
+        }),
+        None => Ok(BTreeMap::new()),
+    }
+}
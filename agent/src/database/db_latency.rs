@@ -0,0 +1,95 @@
+//! Latency distribution database operations
+//!
+//! Helpers shared by the task modules whose aggregated metrics carry a
+//! `LatencyDistribution` (percentiles and histogram):
+//! - Computing the distribution from raw samples
+//! - Adding the distribution columns to existing tables
+//! - Converting between the distribution and its columns
+
+use anyhow::Result;
+use rusqlite::{params, Connection, Row};
+use shared::metrics::LatencyDistribution;
+
+/// Columns of a latency distribution, in storage order
+const LATENCY_COLUMNS: [&str; 5] = [
+    "p50_ms REAL",
+    "p90_ms REAL",
+    "p95_ms REAL",
+    "p99_ms REAL",
+    "latency_histogram TEXT",
+];
+
+/// Values of the latency distribution columns, in storage order
+pub(super) type LatencyColumnValues = (
+    Option<f64>,
+    Option<f64>,
+    Option<f64>,
+    Option<f64>,
+    Option<String>,
+);
+
+/// Add the latency distribution columns to an aggregated table created before they existed
+pub(super) fn add_columns(conn: &Connection, table: &str) {
+    super::add_columns(conn, table, &LATENCY_COLUMNS);
+}
+
+/// Compute the latency distribution of a task over a period
+///
+/// `samples_sql` selects a single latency column and takes the task name,
+/// period start and period end as parameters ?1, ?2 and ?3.
+pub(super) fn query_distribution(
+    conn: &Connection,
+    samples_sql: &str,
+    task_name: &str,
+    period_start: u64,
+    period_end: u64,
+) -> Result<Option<LatencyDistribution>> {
+    let mut stmt = conn.prepare(samples_sql)?;
+    let samples = stmt
+        .query_map(
+            params![task_name, period_start as i64, period_end as i64],
+            |row| row.get::<_, f64>(0),
+        )?
+        .collect::<rusqlite::Result<Vec<f64>>>()?;
+    Ok(LatencyDistribution::from_samples(&samples))
+}
+
+/// Column values for storing a latency distribution
+pub(super) fn column_values(latency: &Option<LatencyDistribution>) -> Result<LatencyColumnValues> {
+    Ok(match latency {
+        Some(latency) => (
+            Some(latency.p50_ms),
+            Some(latency.p90_ms),
+            Some(latency.p95_ms),
+            Some(latency.p99_ms),
+            Some(serde_json::to_string(&latency.histogram)?),
+        ),
+        None => (None, None, None, None, None),
+    })
+}
+
+/// Read a latency distribution stored in the five columns starting at `first`
+pub(super) fn read_columns(
+    row: &Row,
+    first: usize,
+) -> rusqlite::Result<Option<LatencyDistribution>> {
+    let p50_ms: Option<f64> = row.get(first)?;
+    let histogram: Option<String> = row.get(first + 4)?;
+    let (Some(p50_ms), Some(histogram)) = (p50_ms, histogram) else {
+        return Ok(None);
+    };
+    let histogram = serde_json::from_str(&histogram).map_err(|e| {
+        rusqlite::Error::FromSqlConversionFailure(
+            first + 4,
+            rusqlite::types::Type::Text,
+            Box::new(e),
+        )
+    })?;
+    Ok(Some(LatencyDistribution {
+        p50_ms,
+        p90_ms: row.get(first + 1)?,
+        p95_ms: row.get(first + 2)?,
+        p99_ms: row.get(first + 3)?,
+        histogram,
+    }))
+}
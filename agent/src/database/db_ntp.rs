@@ -0,0 +1,415 @@
+//! NTP task database operations
+//!
+//! This module handles all database operations specific to NTP monitoring:
+//! - Table creation and indexing
+//! - Raw metric storage
+//! - Aggregated metric generation and storage
+//! - Loading aggregated metrics
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use shared::config::TaskType;
+use shared::metrics::{
+    AggregatedMetricData, AggregatedMetrics, AggregatedNtpMetric, MetricData, RawMetricData,
+    RawNtpMetric, ThresholdStatus,
+};
+use tracing::debug;
+
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
+
+/// Create NTP-specific tables and indexes
+pub(super) fn create_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS raw_metric_ntp (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_name TEXT NOT NULL,
+            timestamp INTEGER NOT NULL,
+            offset_ms REAL,
+            delay_ms REAL,
+            stratum INTEGER,
+            reference_id TEXT,
+            leap_indicator INTEGER,
+            success BOOLEAN NOT NULL,
+            error TEXT,
+            status TEXT NOT NULL DEFAULT 'ok',
+            status_message TEXT,
+            host TEXT NOT NULL,
+            target_id TEXT,
+            labels TEXT
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create raw_metric_ntp table")?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS agg_metric_ntp (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_name TEXT NOT NULL,
+            period_start INTEGER NOT NULL,
+            period_end INTEGER NOT NULL,
+            sample_count INTEGER NOT NULL,
+            avg_offset_ms REAL NOT NULL,
+            min_offset_ms REAL NOT NULL,
+            max_offset_ms REAL NOT NULL,
+            max_abs_offset_ms REAL NOT NULL,
+            avg_delay_ms REAL NOT NULL,
+            max_delay_ms REAL NOT NULL,
+            stratum INTEGER,
+            reference_id TEXT,
+            leap_indicator INTEGER,
+            successful_queries INTEGER NOT NULL,
+            failed_queries INTEGER NOT NULL,
+            ok_count INTEGER NOT NULL,
+            warning_count INTEGER NOT NULL,
+            critical_count INTEGER NOT NULL,
+            error_count INTEGER NOT NULL,
+            host TEXT,
+            target_id TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
+            UNIQUE(task_name, period_start, period_end)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create agg_metric_ntp table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_ntp_timestamp ON raw_metric_ntp(timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_ntp_task ON raw_metric_ntp(task_name, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agg_ntp_period ON agg_metric_ntp(period_start, period_end)",
+        [],
+    )?;
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_ntp", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "raw_metric_ntp");
+    db_labels::add_column(conn, "agg_metric_ntp");
+
+    Ok(())
+}
+
+/// Store a raw NTP metric
+pub(super) fn store_raw_metric(
+    conn: &Connection,
+    metric: &MetricData,
+    ntp_data: &RawNtpMetric,
+) -> Result<i64> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    conn.execute(
+        r#"
+        INSERT INTO raw_metric_ntp (task_name, timestamp, offset_ms, delay_ms, stratum, reference_id, leap_indicator, success, error, status, status_message, host, target_id, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+        "#,
+        params![
+            metric.task_name,
+            metric.timestamp as i64,
+            ntp_data.offset_ms,
+            ntp_data.delay_ms,
+            ntp_data.stratum,
+            ntp_data.reference_id,
+            ntp_data.leap_indicator,
+            ntp_data.success,
+            ntp_data.error,
+            ntp_data.status.as_str(),
+            ntp_data.status_message,
+            ntp_data.host,
+            ntp_data.target_id,
+            labels
+        ],
+    )?;
+    let row_id = conn.last_insert_rowid();
+    debug!("Stored NTP metric with ID: {}", row_id);
+    Ok(row_id)
+}
+
+/// Load a raw NTP metric by row ID
+pub(super) fn load_raw_metric(conn: &Connection, row_id: i64) -> Result<Option<MetricData>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, timestamp, labels, offset_ms, delay_ms, stratum, reference_id, leap_indicator, success, error, status, status_message, host, target_id
+         FROM raw_metric_ntp WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        let status = match row.get::<_, String>(10)?.as_str() {
+            "warning" => ThresholdStatus::Warning,
+            "critical" => ThresholdStatus::Critical,
+            "error" => ThresholdStatus::Error,
+            _ => ThresholdStatus::Ok,
+        };
+        Ok(MetricData {
+            task_name: row.get(0)?,
+            task_type: TaskType::Ntp,
+            timestamp: row.get::<_, i64>(1)? as u64,
+            labels: db_labels::read_column(row, 2)?,
+            row_id: Some(row_id),
+            data: RawMetricData::Ntp(RawNtpMetric {
+                offset_ms: row.get(3)?,
+                delay_ms: row.get(4)?,
+                stratum: row.get(5)?,
+                reference_id: row.get(6)?,
+                leap_indicator: row.get(7)?,
+                success: row.get(8)?,
+                error: row.get(9)?,
+                status,
+                status_message: row.get(11)?,
+                host: row.get(12)?,
+                target_id: row.get(13)?,
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Generate aggregated NTP metrics for a period
+///
+/// Offsets and delays are taken over successful queries. Stratum and reference
+/// ID come from the last successful query, so a change of upstream shows up in
+/// the period it happened.
+pub(super) fn generate_aggregated_metrics(
+    conn: &Connection,
+    task_name: &str,
+    period_start: u64,
+    period_end: u64,
+) -> Result<Option<AggregatedMetrics>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT
+            COUNT(*) as total_count,
+            AVG(CASE WHEN success = 1 THEN offset_ms END) as avg_offset,
+            MIN(CASE WHEN success = 1 THEN offset_ms END) as min_offset,
+            MAX(CASE WHEN success = 1 THEN offset_ms END) as max_offset,
+            MAX(CASE WHEN success = 1 THEN ABS(offset_ms) END) as max_abs_offset,
+            AVG(CASE WHEN success = 1 THEN delay_ms END) as avg_delay,
+            MAX(CASE WHEN success = 1 THEN delay_ms END) as max_delay,
+            SUM(CASE WHEN success = 1 THEN 1 ELSE 0 END) as successful_queries,
+            SUM(CASE WHEN success = 0 THEN 1 ELSE 0 END) as failed_queries,
+            SUM(CASE WHEN success = 1 AND status = 'ok' THEN 1 ELSE 0 END) as ok_count,
+            SUM(CASE WHEN success = 1 AND status = 'warning' THEN 1 ELSE 0 END) as warning_count,
+            SUM(CASE WHEN success = 1 AND status = 'critical' THEN 1 ELSE 0 END) as critical_count,
+            (SELECT stratum FROM raw_metric_ntp
+             WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+             AND success = 1
+             ORDER BY timestamp DESC, id DESC
+             LIMIT 1) as last_stratum,
+            (SELECT reference_id FROM raw_metric_ntp
+             WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+             AND success = 1
+             ORDER BY timestamp DESC, id DESC
+             LIMIT 1) as last_reference_id,
+            (SELECT leap_indicator FROM raw_metric_ntp
+             WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+             AND leap_indicator IS NOT NULL
+             ORDER BY timestamp DESC, id DESC
+             LIMIT 1) as last_leap_indicator,
+            (SELECT host FROM raw_metric_ntp
+             WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+             ORDER BY timestamp ASC
+             LIMIT 1) as first_host,
+            (SELECT target_id FROM raw_metric_ntp
+             WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+             AND target_id IS NOT NULL
+             ORDER BY timestamp ASC
+             LIMIT 1) as first_target_id
+        FROM raw_metric_ntp
+        WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+        "#,
+    )?;
+
+    let row = stmt.query_row(
+        params![task_name, period_start as i64, period_end as i64],
+        |row| {
+            let total_count: i64 = row.get("total_count")?;
+            if total_count == 0 {
+                return Ok(None);
+            }
+
+            let failed_queries = row.get::<_, i64>("failed_queries")? as u32;
+            Ok(Some(AggregatedNtpMetric {
+                avg_offset_ms: row.get("avg_offset").unwrap_or(0.0),
+                min_offset_ms: row.get("min_offset").unwrap_or(0.0),
+                max_offset_ms: row.get("max_offset").unwrap_or(0.0),
+                max_abs_offset_ms: row.get("max_abs_offset").unwrap_or(0.0),
+                avg_delay_ms: row.get("avg_delay").unwrap_or(0.0),
+                max_delay_ms: row.get("max_delay").unwrap_or(0.0),
+                stratum: row.get("last_stratum").ok().flatten(),
+                reference_id: row.get("last_reference_id").ok().flatten(),
+                leap_indicator: row.get("last_leap_indicator").ok().flatten(),
+                successful_queries: row.get::<_, i64>("successful_queries")? as u32,
+                failed_queries,
+                ok_count: row.get::<_, i64>("ok_count").unwrap_or(0) as u32,
+                warning_count: row.get::<_, i64>("warning_count").unwrap_or(0) as u32,
+                critical_count: row.get::<_, i64>("critical_count").unwrap_or(0) as u32,
+                // Every failed query is an error run
+                error_count: failed_queries,
+                host: row.get("first_host").unwrap_or_default(),
+                target_id: row.get("first_target_id").ok(),
+            }))
+        },
+    )?;
+
+    if let Some(ntp_metric) = row {
+        let total_samples = ntp_metric.successful_queries + ntp_metric.failed_queries;
+        return Ok(Some(AggregatedMetrics::new(
+            task_name.to_string(),
+            TaskType::Ntp,
+            period_start,
+            period_end,
+            total_samples,
+            AggregatedMetricData::Ntp(ntp_metric),
+        )));
+    }
+
+    Ok(None)
+}
+
+/// Store aggregated NTP metrics
+pub(super) fn store_aggregated_metric(
+    conn: &Connection,
+    metrics: &AggregatedMetrics,
+    ntp_data: &AggregatedNtpMetric,
+) -> Result<i64> {
+    let labels = db_labels::column_value(&metrics.labels)?;
+    conn.execute(
+        r#"
+        INSERT OR REPLACE INTO agg_metric_ntp
+        (task_name, period_start, period_end, sample_count, avg_offset_ms, min_offset_ms, max_offset_ms, max_abs_offset_ms, avg_delay_ms, max_delay_ms, stratum, reference_id, leap_indicator, successful_queries, failed_queries, ok_count, warning_count, critical_count, error_count, host, target_id, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27)
+        "#,
+        params![
+            metrics.task_name,
+            metrics.period_start as i64,
+            metrics.period_end as i64,
+            metrics.sample_count,
+            ntp_data.avg_offset_ms,
+            ntp_data.min_offset_ms,
+            ntp_data.max_offset_ms,
+            ntp_data.max_abs_offset_ms,
+            ntp_data.avg_delay_ms,
+            ntp_data.max_delay_ms,
+            ntp_data.stratum,
+            ntp_data.reference_id,
+            ntp_data.leap_indicator,
+            ntp_data.successful_queries,
+            ntp_data.failed_queries,
+            ntp_data.ok_count,
+            ntp_data.warning_count,
+            ntp_data.critical_count,
+            ntp_data.error_count,
+            ntp_data.host,
+            ntp_data.target_id,
+            metrics.maintenance,
+            metrics.adaptive,
+            metrics.upstream_down,
+            metrics.avg_queue_wait_ms,
+            metrics.max_queue_wait_ms,
+            labels,
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Load aggregated NTP metric by row ID
+pub(super) fn load_aggregated_metric(
+    conn: &Connection,
+    row_id: i64,
+) -> Result<Option<AggregatedMetrics>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, period_start, period_end, sample_count,
+                avg_offset_ms, min_offset_ms, max_offset_ms, max_abs_offset_ms,
+                avg_delay_ms, max_delay_ms, stratum, reference_id, leap_indicator,
+                successful_queries, failed_queries, ok_count, warning_count,
+                critical_count, error_count, host, target_id, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels
+         FROM agg_metric_ntp WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        Ok(AggregatedMetrics {
+            task_name: row.get(0)?,
+            task_type: TaskType::Ntp,
+            period_start: row.get::<_, i64>(1)? as u64,
+            period_end: row.get::<_, i64>(2)? as u64,
+            sample_count: row.get(3)?,
+            maintenance: row.get(21)?,
+            adaptive: row.get(22)?,
+            upstream_down: row.get(23)?,
+            avg_queue_wait_ms: row.get(24)?,
+            max_queue_wait_ms: row.get(25)?,
+            labels: db_labels::read_column(row, 26)?,
+            data: AggregatedMetricData::Ntp(AggregatedNtpMetric {
+                avg_offset_ms: row.get(4)?,
+                min_offset_ms: row.get(5)?,
+                max_offset_ms: row.get(6)?,
+                max_abs_offset_ms: row.get(7)?,
+                avg_delay_ms: row.get(8)?,
+                max_delay_ms: row.get(9)?,
+                stratum: row.get(10)?,
+                reference_id: row.get(11)?,
+                leap_indicator: row.get(12)?,
+                successful_queries: row.get(13)?,
+                failed_queries: row.get(14)?,
+                ok_count: row.get(15)?,
+                warning_count: row.get(16)?,
+                critical_count: row.get(17)?,
+                error_count: row.get(18)?,
+                host: row.get(19).unwrap_or_default(),
+                target_id: row.get(20).ok(),
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Clean up old NTP metrics
+pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<(usize, usize)> {
+    let raw_deleted = conn.execute(
+        r#"
+        DELETE FROM raw_metric_ntp
+        WHERE timestamp < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'raw_ntp' AND status != 'sent'
+          )
+        "#,
+        params![cutoff_time],
+    )?;
+
+    let agg_deleted = conn.execute(
+        r#"
+        DELETE FROM agg_metric_ntp
+        WHERE period_end < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'ntp' AND status != 'sent'
+          )
+        "#,
+        params![cutoff_time],
+    )?;
+
+    Ok((raw_deleted, agg_deleted))
+}
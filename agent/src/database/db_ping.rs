@@ -10,10 +10,13 @@ use anyhow::{Context, Result};
 use rusqlite::{params, Connection};
 use shared::config::TaskType;
 use shared::metrics::{
-    AggregatedMetricData, AggregatedMetrics, AggregatedPingMetric, MetricData, RawPingMetric,
+    AggregatedMetricData, AggregatedMetrics, AggregatedPingMetric, MetricData, RawMetricData,
+    RawPingMetric,
 };
 use tracing::debug;
 
+use super::{add_columns, db_labels, db_latency, ANNOTATION_COLUMNS};
+
 /// Create ping-specific tables and indexes
 pub(super) fn create_tables(conn: &Connection) -> Result<()> {
     conn.execute(
@@ -27,7 +30,8 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
             error TEXT,
             ip_address TEXT NOT NULL,
             domain TEXT,
-            target_id TEXT
+            target_id TEXT,
+            labels TEXT
         )
         "#,
         [],
@@ -50,6 +54,17 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
             failed_pings INTEGER NOT NULL,
             domain TEXT,
             target_id TEXT,
+            p50_ms REAL,
+            p90_ms REAL,
+            p95_ms REAL,
+            p99_ms REAL,
+            latency_histogram TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
             UNIQUE(task_name, period_start, period_end)
         )
         "#,
@@ -70,6 +85,16 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Add latency distribution columns to existing tables (migration)
+    db_latency::add_columns(conn, "agg_metric_ping");
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_ping", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "raw_metric_ping");
+    db_labels::add_column(conn, "agg_metric_ping");
+
     Ok(())
 }
 
@@ -79,10 +104,11 @@ pub(super) fn store_raw_metric(
     metric: &MetricData,
     ping_data: &RawPingMetric,
 ) -> Result<i64> {
-    let row_id = conn.execute(
+    let labels = db_labels::column_value(&metric.labels)?;
+    conn.execute(
         r#"
-        INSERT INTO raw_metric_ping (task_name, timestamp, rtt_ms, success, error, ip_address, domain, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)
+        INSERT INTO raw_metric_ping (task_name, timestamp, rtt_ms, success, error, ip_address, domain, target_id, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
         "#,
         params![
             metric.task_name,
@@ -92,11 +118,45 @@ pub(super) fn store_raw_metric(
             ping_data.error,
             ping_data.ip_address,
             ping_data.domain,
-            ping_data.target_id
+            ping_data.target_id,
+            labels
         ],
     )?;
+    let row_id = conn.last_insert_rowid();
     debug!("Stored ping metric with ID: {}", row_id);
-    Ok(row_id as i64)
+    Ok(row_id)
+}
+
+/// Load a raw ping metric by row ID
+pub(super) fn load_raw_metric(conn: &Connection, row_id: i64) -> Result<Option<MetricData>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, timestamp, labels, rtt_ms, success, error, ip_address, domain, target_id
+         FROM raw_metric_ping WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        Ok(MetricData {
+            task_name: row.get(0)?,
+            task_type: TaskType::Ping,
+            timestamp: row.get::<_, i64>(1)? as u64,
+            labels: db_labels::read_column(row, 2)?,
+            row_id: Some(row_id),
+            data: RawMetricData::Ping(RawPingMetric {
+                rtt_ms: row.get(3)?,
+                success: row.get(4)?,
+                error: row.get(5)?,
+                ip_address: row.get(6)?,
+                domain: row.get(7)?,
+                target_id: row.get(8)?,
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
 }
 
 /// Generate aggregated ping metrics for a period
@@ -130,6 +190,18 @@ pub(super) fn generate_aggregated_metrics(
         "#,
     )?;
 
+    let latency = db_latency::query_distribution(
+        conn,
+        r#"
+        SELECT rtt_ms FROM raw_metric_ping
+        WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+        AND success = 1 AND rtt_ms IS NOT NULL
+        "#,
+        task_name,
+        period_start,
+        period_end,
+    )?;
+
     let row = stmt.query_row(
         params![task_name, period_start as i64, period_end as i64],
         |row| {
@@ -158,6 +230,7 @@ pub(super) fn generate_aggregated_metrics(
                 failed_pings: failed_pings as u32,
                 domain,
                 target_id,
+                latency,
             }))
         },
     )?;
@@ -183,11 +256,15 @@ pub(super) fn store_aggregated_metric(
     metrics: &AggregatedMetrics,
     ping_data: &AggregatedPingMetric,
 ) -> Result<i64> {
+    let labels = db_labels::column_value(&metrics.labels)?;
+    let (p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram) =
+        db_latency::column_values(&ping_data.latency)?;
     conn.execute(
         r#"
         INSERT OR REPLACE INTO agg_metric_ping
-        (task_name, period_start, period_end, sample_count, avg_latency_ms, max_latency_ms, min_latency_ms, packet_loss_percent, successful_pings, failed_pings, domain, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        (task_name, period_start, period_end, sample_count, avg_latency_ms, max_latency_ms, min_latency_ms, packet_loss_percent, successful_pings, failed_pings, domain, target_id,
+         p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23)
         "#,
         params![
             metrics.task_name,
@@ -201,7 +278,18 @@ pub(super) fn store_aggregated_metric(
             ping_data.successful_pings,
             ping_data.failed_pings,
             ping_data.domain,
-            ping_data.target_id
+            ping_data.target_id,
+            p50_ms,
+            p90_ms,
+            p95_ms,
+            p99_ms,
+            latency_histogram,
+            metrics.maintenance,
+            metrics.adaptive,
+            metrics.upstream_down,
+            metrics.avg_queue_wait_ms,
+            metrics.max_queue_wait_ms,
+            labels,
         ],
     )?;
     Ok(conn.last_insert_rowid())
@@ -216,7 +304,8 @@ pub(super) fn load_aggregated_metric(
         "SELECT task_name, period_start, period_end, sample_count,
                 avg_latency_ms, min_latency_ms, max_latency_ms,
                 packet_loss_percent, successful_pings, failed_pings,
-                domain, target_id
+                domain, target_id,
+                p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels
          FROM agg_metric_ping WHERE id = ?1",
     )?;
 
@@ -227,6 +316,12 @@ pub(super) fn load_aggregated_metric(
             period_start: row.get::<_, i64>(1)? as u64,
             period_end: row.get::<_, i64>(2)? as u64,
             sample_count: row.get(3)?,
+            maintenance: row.get(17)?,
+            adaptive: row.get(18)?,
+            upstream_down: row.get(19)?,
+            avg_queue_wait_ms: row.get(20)?,
+            max_queue_wait_ms: row.get(21)?,
+            labels: db_labels::read_column(row, 22)?,
             data: AggregatedMetricData::Ping(AggregatedPingMetric {
                 avg_latency_ms: row.get(4)?,
                 min_latency_ms: row.get(5)?,
@@ -236,6 +331,7 @@ pub(super) fn load_aggregated_metric(
                 failed_pings: row.get(9)?,
                 domain: row.get(10).ok(),
                 target_id: row.get(11).ok(),
+                latency: db_latency::read_columns(row, 12)?,
             }),
         })
     });
@@ -250,7 +346,14 @@ pub(super) fn load_aggregated_metric(
 /// Clean up old ping metrics
 pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<(usize, usize)> {
     let raw_deleted = conn.execute(
-        "DELETE FROM raw_metric_ping WHERE timestamp < ?1",
+        r#"
+        DELETE FROM raw_metric_ping
+        WHERE timestamp < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'raw_ping' AND status != 'sent'
+          )
+        "#,
         params![cutoff_time],
     )?;
 
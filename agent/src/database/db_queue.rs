@@ -2,13 +2,20 @@
 //!
 //! This module handles the send queue for aggregated metrics, including
 //! retry logic with exponential backoff and queue statistics.
+//!
+//! Raw metrics of tasks with `upload_raw` enabled go through the same queue.
+//! Their entries use the metric type prefixed with `raw_` (e.g. `raw_ping`)
+//! and point at a row of the raw metric table instead of the aggregated one.
 
 use anyhow::{Context, Result};
 use rusqlite::{params, Connection};
-use shared::metrics::{AggregatedMetricData, AggregatedMetrics};
+use shared::metrics::{AggregatedMetricData, AggregatedMetrics, MetricData, RawMetricData};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::{debug, info, warn};
 
+/// Most queue entries examined when filling one batch of raw metrics
+const RAW_BATCH_SCAN_LIMIT: usize = 10_000;
+
 /// Status of a metric in the send queue
 #[derive(Debug, Clone, PartialEq)]
 pub enum QueueStatus {
@@ -63,6 +70,15 @@ pub struct QueuedMetric {
     pub retry_count: i32,
 }
 
+/// Raw metric with queue metadata
+#[derive(Debug, Clone)]
+pub struct QueuedRawMetric {
+    pub queue_id: i64,
+    pub metric: MetricData,
+    #[allow(dead_code)]
+    pub retry_count: i32,
+}
+
 /// Queue statistics for monitoring
 #[derive(Debug, Default, Clone)]
 #[allow(dead_code)]
@@ -179,8 +195,14 @@ pub fn enqueue_metric_for_send(
         AggregatedMetricData::HttpContent(_) => "http_content",
         AggregatedMetricData::DnsQuery(_) => "dns",
         AggregatedMetricData::Bandwidth(_) => "bandwidth",
+        AggregatedMetricData::UdpProbe(_) => "udp_probe",
+        AggregatedMetricData::Twamp(_) => "twamp",
+        AggregatedMetricData::TcpSweep(_) => "tcp_sweep",
+        AggregatedMetricData::Ntp(_) => "ntp",
         AggregatedMetricData::Snmp(_) => "snmp",
+        AggregatedMetricData::SnmpTrap(_) => "snmp_trap",
         AggregatedMetricData::SqlQuery(_) => "sql_query",
+        AggregatedMetricData::Execution(_) => "execution",
         AggregatedMetricData::Unknown => {
             return Err(anyhow::anyhow!("Cannot enqueue unknown metric type"));
         }
@@ -213,8 +235,67 @@ pub fn enqueue_metric_for_send(
     Ok(())
 }
 
+/// Queue a raw metric for sending to the server
+pub fn enqueue_raw_metric_for_send(
+    conn: &Connection,
+    metric: &MetricData,
+    metric_row_id: i64,
+) -> Result<()> {
+    let now = current_timestamp();
+
+    let metric_type = match &metric.data {
+        RawMetricData::Ping(_) => "raw_ping",
+        RawMetricData::Tcp(_) => "raw_tcp",
+        RawMetricData::HttpGet(_) => "raw_http",
+        RawMetricData::TlsHandshake(_) => "raw_tls",
+        RawMetricData::HttpContent(_) => "raw_http_content",
+        RawMetricData::DnsQuery(_) => "raw_dns",
+        RawMetricData::Bandwidth(_) => "raw_bandwidth",
+        RawMetricData::UdpProbe(_) => "raw_udp_probe",
+        RawMetricData::Twamp(_) => "raw_twamp",
+        RawMetricData::TcpSweep(_) => "raw_tcp_sweep",
+        RawMetricData::Ntp(_) => "raw_ntp",
+        RawMetricData::Snmp(_) => "raw_snmp",
+        RawMetricData::SqlQuery(_) => "raw_sql_query",
+        RawMetricData::Unknown => {
+            return Err(anyhow::anyhow!("Cannot enqueue unknown metric type"));
+        }
+    };
+
+    // A raw metric covers a single instant, recorded as both period bounds
+    conn.execute(
+        r#"
+        INSERT OR IGNORE INTO metric_send_queue (
+            metric_type, metric_row_id, task_name, period_start, period_end,
+            status, created_at, next_retry_at
+        ) VALUES (?1, ?2, ?3, ?4, ?4, ?5, ?6, ?6)
+        "#,
+        params![
+            metric_type,
+            metric_row_id,
+            metric.task_name,
+            metric.timestamp as i64,
+            QueueStatus::Pending.as_str(),
+            now as i64,
+        ],
+    )?;
+
+    debug!(
+        "Enqueued {} metric {} (row_id={}) for sending",
+        metric_type, metric.task_name, metric_row_id
+    );
+
+    Ok(())
+}
+
 /// Get next batch of metrics to send, respecting exponential backoff
-pub fn get_pending_queue_entries(conn: &Connection, batch_size: usize) -> Result<Vec<QueueEntry>> {
+///
+/// Returns entries of raw metrics if `raw` is set, of aggregated metrics otherwise.
+pub fn get_pending_queue_entries(
+    conn: &Connection,
+    batch_size: usize,
+    raw: bool,
+) -> Result<Vec<QueueEntry>> {
     let now = current_timestamp();
 
     // Get queue entries that are pending and ready to retry
@@ -226,12 +307,13 @@ pub fn get_pending_queue_entries(conn: &Connection, batch_size: usize) -> Result
         FROM metric_send_queue
         WHERE status = 'pending'
           AND next_retry_at <= ?1
+          AND (metric_type LIKE 'raw\_%' ESCAPE '\') = ?3
         ORDER BY created_at ASC
         LIMIT ?2
         "#,
     )?;
 
-    let rows = stmt.query_map(params![now as i64, batch_size as i64], |row| {
+    let rows = stmt.query_map(params![now as i64, batch_size as i64, raw], |row| {
         Ok(QueueEntry {
             id: row.get(0)?,
             metric_type: row.get(1)?,
@@ -463,10 +545,43 @@ pub fn fetch_metric_by_type_and_id(
         "http_content" => super::db_http_content::load_aggregated_metric(conn, row_id),
         "dns" => super::db_dns::load_aggregated_metric(conn, row_id),
         "bandwidth" => super::db_bandwidth::load_aggregated_metric(conn, row_id),
+        "udp_probe" => super::db_udp::load_aggregated_metric(conn, row_id),
+        "twamp" => super::db_twamp::load_aggregated_metric(conn, row_id),
+        "tcp_sweep" => super::db_tcp_sweep::load_aggregated_metric(conn, row_id),
+        "ntp" => super::db_ntp::load_aggregated_metric(conn, row_id),
         #[cfg(feature = "snmp-tasks")]
         "snmp" => super::db_snmp::load_aggregated_metric(conn, row_id),
+        #[cfg(feature = "snmp-tasks")]
+        "snmp_trap" => super::db_snmp_trap::load_aggregated_metric(conn, row_id),
         #[cfg(feature = "sql-tasks")]
         "sql_query" => super::db_sql::load_aggregated_metric(conn, row_id),
+        "execution" => super::db_execution::load_aggregated_metric(conn, row_id),
+        _ => Err(anyhow::anyhow!("Unknown metric type: {}", metric_type)),
+    }
+}
+
+/// Fetch a specific raw metric from the appropriate table by type and row ID
+pub fn fetch_raw_metric_by_type_and_id(
+    conn: &Connection,
+    metric_type: &str,
+    row_id: i64,
+) -> Result<Option<MetricData>> {
+    match metric_type {
+        "raw_ping" => super::db_ping::load_raw_metric(conn, row_id),
+        "raw_tcp" => super::db_tcp::load_raw_metric(conn, row_id),
+        "raw_http" => super::db_http::load_raw_metric(conn, row_id),
+        "raw_tls" => super::db_tls::load_raw_metric(conn, row_id),
+        "raw_http_content" => super::db_http_content::load_raw_metric(conn, row_id),
+        "raw_dns" => super::db_dns::load_raw_metric(conn, row_id),
+        "raw_bandwidth" => super::db_bandwidth::load_raw_metric(conn, row_id),
+        "raw_udp_probe" => super::db_udp::load_raw_metric(conn, row_id),
+        "raw_twamp" => super::db_twamp::load_raw_metric(conn, row_id),
+        "raw_tcp_sweep" => super::db_tcp_sweep::load_raw_metric(conn, row_id),
+        "raw_ntp" => super::db_ntp::load_raw_metric(conn, row_id),
+        #[cfg(feature = "snmp-tasks")]
+        "raw_snmp" => super::db_snmp::load_raw_metric(conn, row_id),
+        #[cfg(feature = "sql-tasks")]
+        "raw_sql_query" => super::db_sql::load_raw_metric(conn, row_id),
         _ => Err(anyhow::anyhow!("Unknown metric type: {}", metric_type)),
     }
 }
@@ -480,7 +595,7 @@ fn current_timestamp() -> u64 {
 }
 
 pub fn get_queued_metrics(conn: &Connection, batch_size: usize) -> Result<Vec<QueuedMetric>> {
-    let queue_entries = get_pending_queue_entries(conn, batch_size)?;
+    let queue_entries = get_pending_queue_entries(conn, batch_size, false)?;
 
     // Now fetch the actual metric data for each queue entry
     let mut metrics = Vec::new();
@@ -506,3 +621,42 @@ pub fn get_queued_metrics(conn: &Connection, batch_size: usize) -> Result<Vec<Qu
 
     Ok(metrics)
 }
+
+/// Get next batch of raw metrics to send, up to `max_bytes` of serialized JSON
+///
+/// Raw metrics vary widely in size (a TCP sweep sample carries one result per
+/// target), so the batch is capped by size rather than by count. The first
+/// metric is always returned, however large.
+pub fn get_queued_raw_metrics(conn: &Connection, max_bytes: usize) -> Result<Vec<QueuedRawMetric>> {
+    let queue_entries = get_pending_queue_entries(conn, RAW_BATCH_SCAN_LIMIT, true)?;
+
+    let mut metrics = Vec::new();
+    let mut batch_bytes = 0;
+
+    for entry in queue_entries {
+        let metric =
+            fetch_raw_metric_by_type_and_id(conn, &entry.metric_type, entry.metric_row_id)?;
+
+        if let Some(metric) = metric {
+            let metric_bytes = serde_json::to_vec(&metric)?.len();
+            if !metrics.is_empty() && batch_bytes + metric_bytes > max_bytes {
+                break;
+            }
+            batch_bytes += metric_bytes;
+            metrics.push(QueuedRawMetric {
+                queue_id: entry.id,
+                metric,
+                retry_count: entry.retry_count,
+            });
+        } else {
+            // Metric was deleted, remove from queue
+            warn!(
+                "Metric {} {} not found, removing from queue",
+                entry.metric_type, entry.metric_row_id
+            );
+            remove_from_queue(conn, entry.id)?;
+        }
+    }
+
+    Ok(metrics)
+}
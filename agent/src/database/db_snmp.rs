@@ -5,11 +5,14 @@ use rusqlite::{params, Connection};
 use shared::{
     config::TaskType,
     metrics::{
-        AggregatedMetricData, AggregatedMetrics, AggregatedSnmpMetric, MetricData, RawSnmpMetric,
+        AggregatedMetricData, AggregatedMetrics, AggregatedSnmpMetric, MetricData, RawMetricData,
+        RawSnmpMetric,
     },
 };
 use tracing::debug;
 
+use super::{add_columns, db_labels, db_latency, ANNOTATION_COLUMNS};
+
 /// Create SNMP-specific tables and indexes
 pub(super) fn create_tables(conn: &Connection) -> Result<()> {
     conn.execute(
@@ -24,7 +27,9 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
             value_type TEXT,
             oid_queried TEXT NOT NULL,
             error TEXT,
-            target_id TEXT
+            target_id TEXT,
+            numeric_value REAL,
+            labels TEXT
         )
         "#,
         [],
@@ -47,6 +52,20 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
             first_value_type TEXT,
             oid_queried TEXT NOT NULL,
             target_id TEXT,
+            avg_value REAL,
+            min_value REAL,
+            max_value REAL,
+            p50_ms REAL,
+            p90_ms REAL,
+            p95_ms REAL,
+            p99_ms REAL,
+            latency_histogram TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
             UNIQUE(task_name, period_start, period_end)
         )
         "#,
@@ -67,6 +86,24 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Add numeric value and latency distribution columns to existing tables (migration)
+    // SQLite doesn't support IF NOT EXISTS for ALTER TABLE, so we ignore errors
+    let _ = conn.execute(
+        "ALTER TABLE raw_metric_snmp ADD COLUMN numeric_value REAL",
+        [],
+    );
+    let _ = conn.execute("ALTER TABLE agg_metric_snmp ADD COLUMN avg_value REAL", []);
+    let _ = conn.execute("ALTER TABLE agg_metric_snmp ADD COLUMN min_value REAL", []);
+    let _ = conn.execute("ALTER TABLE agg_metric_snmp ADD COLUMN max_value REAL", []);
+    db_latency::add_columns(conn, "agg_metric_snmp");
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_snmp", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "raw_metric_snmp");
+    db_labels::add_column(conn, "agg_metric_snmp");
+
     Ok(())
 }
 
@@ -76,10 +113,11 @@ pub(super) fn store_raw_metric(
     metric: &MetricData,
     snmp_data: &RawSnmpMetric,
 ) -> Result<i64> {
-    let row_id = conn.execute(
+    let labels = db_labels::column_value(&metric.labels)?;
+    conn.execute(
         r#"
-        INSERT INTO raw_metric_snmp (task_name, timestamp, response_time_ms, success, value, value_type, oid_queried, error, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        INSERT INTO raw_metric_snmp (task_name, timestamp, response_time_ms, success, value, value_type, oid_queried, error, target_id, numeric_value, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
         "#,
         params![
             metric.task_name,
@@ -90,16 +128,54 @@ pub(super) fn store_raw_metric(
             snmp_data.value_type,
             snmp_data.oid_queried,
             snmp_data.error,
-            snmp_data.target_id
+            snmp_data.target_id,
+            snmp_data.numeric_value,
+            labels
         ],
     )?;
+    let row_id = conn.last_insert_rowid();
     debug!("Stored SNMP metric with ID: {}", row_id);
-    Ok(row_id as i64)
+    Ok(row_id)
+}
+
+/// Load a raw SNMP metric by row ID
+pub(super) fn load_raw_metric(conn: &Connection, row_id: i64) -> Result<Option<MetricData>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, timestamp, labels, response_time_ms, success, value, value_type, oid_queried, error, target_id, numeric_value
+         FROM raw_metric_snmp WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        Ok(MetricData {
+            task_name: row.get(0)?,
+            task_type: TaskType::Snmp,
+            timestamp: row.get::<_, i64>(1)? as u64,
+            labels: db_labels::read_column(row, 2)?,
+            row_id: Some(row_id),
+            data: RawMetricData::Snmp(RawSnmpMetric {
+                response_time_ms: row.get(3)?,
+                success: row.get(4)?,
+                value: row.get(5)?,
+                value_type: row.get(6)?,
+                oid_queried: row.get(7)?,
+                error: row.get(8)?,
+                target_id: row.get(9)?,
+                numeric_value: row.get(10)?,
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
 }
 
 /// Generate aggregated SNMP metrics for a time period
 /// Since SNMP tasks run at minimum 60s intervals, aggregation typically contains 1 sample
-/// We use first-value strategy for value aggregation
+/// We use first-value strategy for the string value; numeric values are aggregated
+/// as avg/min/max, including samples that failed an expected value or range check
 pub(super) fn generate_aggregated_metrics(
     conn: &Connection,
     task_name: &str,
@@ -114,12 +190,15 @@ pub(super) fn generate_aggregated_metrics(
             SUM(CASE WHEN success = 1 THEN 1 ELSE 0 END) as successful_queries,
             SUM(CASE WHEN success = 0 THEN 1 ELSE 0 END) as failed_queries,
             MAX(oid_queried) as oid_queried,
+            AVG(numeric_value) as avg_value,
+            MIN(numeric_value) as min_value,
+            MAX(numeric_value) as max_value,
             (SELECT value FROM raw_metric_snmp
-             WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3 AND success = 1
+             WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3 AND value IS NOT NULL
              ORDER BY timestamp ASC
              LIMIT 1) as first_value,
             (SELECT value_type FROM raw_metric_snmp
-             WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3 AND success = 1
+             WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3 AND value IS NOT NULL
              ORDER BY timestamp ASC
              LIMIT 1) as first_value_type,
             (SELECT target_id FROM raw_metric_snmp
@@ -132,6 +211,18 @@ pub(super) fn generate_aggregated_metrics(
         "#,
     )?;
 
+    let latency = db_latency::query_distribution(
+        conn,
+        r#"
+        SELECT response_time_ms FROM raw_metric_snmp
+        WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+        AND success = 1 AND response_time_ms IS NOT NULL
+        "#,
+        task_name,
+        period_start,
+        period_end,
+    )?;
+
     let row = stmt.query_row(
         params![task_name, period_start as i64, period_end as i64],
         |row| {
@@ -165,6 +256,10 @@ pub(super) fn generate_aggregated_metrics(
                 first_value_type,
                 oid_queried,
                 target_id,
+                avg_value: row.get("avg_value").ok(),
+                min_value: row.get("min_value").ok(),
+                max_value: row.get("max_value").ok(),
+                latency,
             }))
         },
     )?;
@@ -190,11 +285,15 @@ pub(super) fn store_aggregated_metric(
     metrics: &AggregatedMetrics,
     snmp_data: &AggregatedSnmpMetric,
 ) -> Result<i64> {
+    let labels = db_labels::column_value(&metrics.labels)?;
+    let (p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram) =
+        db_latency::column_values(&snmp_data.latency)?;
     conn.execute(
         r#"
         INSERT OR REPLACE INTO agg_metric_snmp
-        (task_name, period_start, period_end, sample_count, success_rate_percent, avg_response_time_ms, successful_queries, failed_queries, first_value, first_value_type, oid_queried, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        (task_name, period_start, period_end, sample_count, success_rate_percent, avg_response_time_ms, successful_queries, failed_queries, first_value, first_value_type, oid_queried, target_id, avg_value, min_value, max_value,
+         p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26)
         "#,
         params![
             metrics.task_name,
@@ -208,7 +307,21 @@ pub(super) fn store_aggregated_metric(
             snmp_data.first_value,
             snmp_data.first_value_type,
             snmp_data.oid_queried,
-            snmp_data.target_id
+            snmp_data.target_id,
+            snmp_data.avg_value,
+            snmp_data.min_value,
+            snmp_data.max_value,
+            p50_ms,
+            p90_ms,
+            p95_ms,
+            p99_ms,
+            latency_histogram,
+            metrics.maintenance,
+            metrics.adaptive,
+            metrics.upstream_down,
+            metrics.avg_queue_wait_ms,
+            metrics.max_queue_wait_ms,
+            labels,
         ],
     )?;
     Ok(conn.last_insert_rowid())
@@ -224,7 +337,8 @@ pub(super) fn load_aggregated_metric(
         "SELECT task_name, period_start, period_end, sample_count,
                 success_rate_percent, avg_response_time_ms,
                 successful_queries, failed_queries, first_value, first_value_type,
-                oid_queried, target_id
+                oid_queried, target_id, avg_value, min_value, max_value,
+                p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels
          FROM agg_metric_snmp WHERE id = ?1",
     )?;
 
@@ -235,6 +349,12 @@ pub(super) fn load_aggregated_metric(
             period_start: row.get::<_, i64>(1)? as u64,
             period_end: row.get::<_, i64>(2)? as u64,
             sample_count: row.get(3)?,
+            maintenance: row.get(20)?,
+            adaptive: row.get(21)?,
+            upstream_down: row.get(22)?,
+            avg_queue_wait_ms: row.get(23)?,
+            max_queue_wait_ms: row.get(24)?,
+            labels: db_labels::read_column(row, 25)?,
             data: AggregatedMetricData::Snmp(AggregatedSnmpMetric {
                 success_rate_percent: row.get(4)?,
                 avg_response_time_ms: row.get(5)?,
@@ -244,6 +364,10 @@ pub(super) fn load_aggregated_metric(
                 first_value_type: row.get(9).ok(),
                 oid_queried: row.get(10)?,
                 target_id: row.get(11).ok(),
+                avg_value: row.get(12).ok(),
+                min_value: row.get(13).ok(),
+                max_value: row.get(14).ok(),
+                latency: db_latency::read_columns(row, 15)?,
             }),
         })
     });
@@ -258,7 +382,14 @@ pub(super) fn load_aggregated_metric(
 /// Clean up old SNMP metrics
 pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<(usize, usize)> {
     let raw_deleted = conn.execute(
-        "DELETE FROM raw_metric_snmp WHERE timestamp < ?1",
+        r#"
+        DELETE FROM raw_metric_snmp
+        WHERE timestamp < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'raw_snmp' AND status != 'sent'
+          )
+        "#,
         params![cutoff_time],
     )?;
 
@@ -0,0 +1,142 @@
+//! SNMP trap/inform event database operations
+//!
+//! Notifications received by the trap listener are stored one row per event
+//! and forwarded to the server through the send queue without aggregation.
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use shared::{
+    config::TaskType,
+    metrics::{AggregatedMetricData, AggregatedMetrics, SnmpTrapEventMetric, SnmpVarbind},
+};
+use std::collections::BTreeMap;
+use tracing::debug;
+
+/// Create SNMP trap event table and indexes
+pub(super) fn create_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS snmp_trap_event (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_name TEXT NOT NULL,
+            timestamp INTEGER NOT NULL,
+            source_address TEXT NOT NULL,
+            version TEXT NOT NULL,
+            pdu_type TEXT NOT NULL,
+            trap_oid TEXT NOT NULL,
+            uptime_ticks INTEGER,
+            varbinds TEXT NOT NULL,
+            target_id TEXT
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create snmp_trap_event table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_snmp_trap_timestamp ON snmp_trap_event(timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_snmp_trap_task ON snmp_trap_event(task_name, timestamp)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// Store an SNMP trap event
+pub(super) fn store_event(
+    conn: &Connection,
+    metrics: &AggregatedMetrics,
+    trap_data: &SnmpTrapEventMetric,
+) -> Result<i64> {
+    let varbinds_json =
+        serde_json::to_string(&trap_data.varbinds).context("Failed to serialize varbinds")?;
+
+    conn.execute(
+        r#"
+        INSERT INTO snmp_trap_event
+        (task_name, timestamp, source_address, version, pdu_type, trap_oid, uptime_ticks, varbinds, target_id)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        "#,
+        params![
+            metrics.task_name,
+            metrics.period_start as i64,
+            trap_data.source_address,
+            trap_data.version,
+            trap_data.pdu_type,
+            trap_data.trap_oid,
+            trap_data.uptime_ticks,
+            varbinds_json,
+            trap_data.target_id
+        ],
+    )?;
+    let row_id = conn.last_insert_rowid();
+    debug!("Stored SNMP trap event with ID: {}", row_id);
+    Ok(row_id)
+}
+
+/// Load an SNMP trap event by row ID
+pub(super) fn load_aggregated_metric(
+    conn: &Connection,
+    row_id: i64,
+) -> Result<Option<AggregatedMetrics>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, timestamp, source_address, version, pdu_type,
+                trap_oid, uptime_ticks, varbinds, target_id
+         FROM snmp_trap_event WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        let timestamp = row.get::<_, i64>(1)? as u64;
+        let varbinds_json: String = row.get(7)?;
+        Ok(AggregatedMetrics {
+            task_name: row.get(0)?,
+            task_type: TaskType::Snmp,
+            period_start: timestamp,
+            period_end: timestamp,
+            sample_count: 1,
+            maintenance: false,
+            adaptive: false,
+            upstream_down: false,
+            avg_queue_wait_ms: None,
+            max_queue_wait_ms: None,
+            labels: BTreeMap::new(),
+            data: AggregatedMetricData::SnmpTrap(SnmpTrapEventMetric {
+                event_id: row_id,
+                source_address: row.get(2)?,
+                version: row.get(3)?,
+                pdu_type: row.get(4)?,
+                trap_oid: row.get(5)?,
+                uptime_ticks: row.get(6).ok(),
+                varbinds: serde_json::from_str::<Vec<SnmpVarbind>>(&varbinds_json)
+                    .unwrap_or_default(),
+                target_id: row.get(8).ok(),
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Clean up old SNMP trap events that are no longer waiting to be sent
+pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        r#"
+        DELETE FROM snmp_trap_event
+        WHERE timestamp < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'snmp_trap' AND status != 'sent'
+          )
+        "#,
+        params![cutoff_time],
+    )?;
+
+    Ok(deleted)
+}
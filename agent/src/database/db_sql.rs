@@ -6,15 +6,18 @@ use anyhow::{Context, Result};
 use rusqlite::{params, Connection};
 #[cfg(feature = "sql-tasks")]
 use shared::{
-    config::TaskType,
+    config::{SqlQueryMode, TaskType},
     metrics::{
         AggregatedMetricData, AggregatedMetrics, AggregatedSqlQueryMetric, MetricData,
-        RawSqlQueryMetric,
+        RawMetricData, RawSqlQueryMetric, ThresholdStatus,
     },
 };
 #[cfg(feature = "sql-tasks")]
 use tracing::debug;
 
+#[cfg(feature = "sql-tasks")]
+use super::{add_columns, db_labels, db_latency, ANNOTATION_COLUMNS};
+
 /// Create SQL query-specific tables and indexes
 #[cfg(feature = "sql-tasks")]
 pub(super) fn create_tables(conn: &Connection) -> Result<()> {
@@ -33,7 +36,12 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
             value REAL,
             json_result TEXT,
             json_truncated BOOLEAN NOT NULL DEFAULT 0,
-            column_count INTEGER
+            column_count INTEGER,
+            connect_time_ms REAL,
+            query_time_ms REAL,
+            status TEXT NOT NULL DEFAULT 'ok',
+            status_message TEXT,
+            labels TEXT
         )
         "#,
         [],
@@ -60,6 +68,23 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
             min_value REAL,
             max_value REAL,
             json_truncated_count INTEGER NOT NULL DEFAULT 0,
+            avg_connect_time_ms REAL,
+            avg_query_time_ms REAL,
+            ok_count INTEGER NOT NULL DEFAULT 0,
+            warning_count INTEGER NOT NULL DEFAULT 0,
+            critical_count INTEGER NOT NULL DEFAULT 0,
+            error_count INTEGER NOT NULL DEFAULT 0,
+            p50_ms REAL,
+            p90_ms REAL,
+            p95_ms REAL,
+            p99_ms REAL,
+            latency_histogram TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
             UNIQUE(task_name, period_start, period_end)
         )
         "#,
@@ -112,6 +137,47 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
         [],
     );
     let _ = conn.execute("ALTER TABLE agg_metric_sql_query ADD COLUMN json_truncated_count INTEGER NOT NULL DEFAULT 0", []);
+    let _ = conn.execute(
+        "ALTER TABLE raw_metric_sql_query ADD COLUMN connect_time_ms REAL",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE raw_metric_sql_query ADD COLUMN query_time_ms REAL",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE agg_metric_sql_query ADD COLUMN avg_connect_time_ms REAL",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE agg_metric_sql_query ADD COLUMN avg_query_time_ms REAL",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE raw_metric_sql_query ADD COLUMN status TEXT NOT NULL DEFAULT 'ok'",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE raw_metric_sql_query ADD COLUMN status_message TEXT",
+        [],
+    );
+    for column in ["ok_count", "warning_count", "critical_count", "error_count"] {
+        let _ = conn.execute(
+            &format!(
+                "ALTER TABLE agg_metric_sql_query ADD COLUMN {} INTEGER NOT NULL DEFAULT 0",
+                column
+            ),
+            [],
+        );
+    }
+    db_latency::add_columns(conn, "agg_metric_sql_query");
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_sql_query", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "raw_metric_sql_query");
+    db_labels::add_column(conn, "agg_metric_sql_query");
 
     Ok(())
 }
@@ -123,12 +189,14 @@ pub(super) fn store_raw_metric(
     metric: &MetricData,
     sql_data: &RawSqlQueryMetric,
 ) -> Result<i64> {
+    let labels = db_labels::column_value(&metric.labels)?;
     conn.execute(
         r#"
         INSERT INTO raw_metric_sql_query
         (task_name, timestamp, total_time_ms, row_count, success, error, target_id,
-         mode, value, json_result, json_truncated, column_count)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+         mode, value, json_result, json_truncated, column_count, connect_time_ms, query_time_ms,
+         status, status_message, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
         "#,
         params![
             metric.task_name,
@@ -143,6 +211,11 @@ pub(super) fn store_raw_metric(
             sql_data.json_result,
             sql_data.json_truncated,
             sql_data.column_count,
+            sql_data.connect_time_ms,
+            sql_data.query_time_ms,
+            sql_data.status.as_str(),
+            sql_data.status_message,
+            labels,
         ],
     )?;
     let row_id = conn.last_insert_rowid();
@@ -150,6 +223,57 @@ pub(super) fn store_raw_metric(
     Ok(row_id)
 }
 
+/// Load a raw SQL query metric by row ID
+#[cfg(feature = "sql-tasks")]
+pub(super) fn load_raw_metric(conn: &Connection, row_id: i64) -> Result<Option<MetricData>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, timestamp, labels, total_time_ms, row_count, success, error, target_id, mode, value, json_result, json_truncated, column_count, connect_time_ms, query_time_ms, status, status_message
+         FROM raw_metric_sql_query WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        let mode = match row.get::<_, String>(8)?.as_str() {
+            "json" => SqlQueryMode::Json,
+            _ => SqlQueryMode::Value,
+        };
+        let status = match row.get::<_, String>(15)?.as_str() {
+            "warning" => ThresholdStatus::Warning,
+            "critical" => ThresholdStatus::Critical,
+            "error" => ThresholdStatus::Error,
+            _ => ThresholdStatus::Ok,
+        };
+        Ok(MetricData {
+            task_name: row.get(0)?,
+            task_type: TaskType::SqlQuery,
+            timestamp: row.get::<_, i64>(1)? as u64,
+            labels: db_labels::read_column(row, 2)?,
+            row_id: Some(row_id),
+            data: RawMetricData::SqlQuery(RawSqlQueryMetric {
+                total_time_ms: row.get(3)?,
+                row_count: row.get(4)?,
+                success: row.get(5)?,
+                error: row.get(6)?,
+                target_id: row.get(7)?,
+                mode,
+                value: row.get(9)?,
+                json_result: row.get(10)?,
+                json_truncated: row.get(11)?,
+                column_count: row.get(12)?,
+                connect_time_ms: row.get(13)?,
+                query_time_ms: row.get(14)?,
+                status,
+                status_message: row.get(16)?,
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
 /// Generate aggregated SQL query metrics for a time period
 #[cfg(feature = "sql-tasks")]
 pub(super) fn generate_aggregated_metrics(
@@ -176,12 +300,29 @@ pub(super) fn generate_aggregated_metrics(
             AVG(CASE WHEN success = 1 AND value IS NOT NULL THEN value END) as avg_value,
             MIN(CASE WHEN success = 1 AND value IS NOT NULL THEN value END) as min_value,
             MAX(CASE WHEN success = 1 AND value IS NOT NULL THEN value END) as max_value,
-            SUM(CASE WHEN json_truncated = 1 THEN 1 ELSE 0 END) as json_truncated_count
+            SUM(CASE WHEN json_truncated = 1 THEN 1 ELSE 0 END) as json_truncated_count,
+            AVG(connect_time_ms) as avg_connect_time,
+            AVG(CASE WHEN success = 1 AND query_time_ms IS NOT NULL THEN query_time_ms END) as avg_query_time,
+            SUM(CASE WHEN success = 1 AND status = 'ok' THEN 1 ELSE 0 END) as ok_count,
+            SUM(CASE WHEN success = 1 AND status = 'warning' THEN 1 ELSE 0 END) as warning_count,
+            SUM(CASE WHEN success = 1 AND status = 'critical' THEN 1 ELSE 0 END) as critical_count
         FROM raw_metric_sql_query
         WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
         "#,
     )?;
 
+    let latency = db_latency::query_distribution(
+        conn,
+        r#"
+        SELECT total_time_ms FROM raw_metric_sql_query
+        WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+        AND success = 1 AND total_time_ms IS NOT NULL
+        "#,
+        task_name,
+        period_start,
+        period_end,
+    )?;
+
     let row = stmt.query_row(
         params![task_name, period_start as i64, period_end as i64],
         |row| {
@@ -217,6 +358,14 @@ pub(super) fn generate_aggregated_metrics(
                 min_value,
                 max_value,
                 json_truncated_count: json_truncated_count as u32,
+                avg_connect_time_ms: row.get("avg_connect_time").ok(),
+                avg_query_time_ms: row.get("avg_query_time").ok(),
+                ok_count: row.get::<_, i64>("ok_count").unwrap_or(0) as u32,
+                warning_count: row.get::<_, i64>("warning_count").unwrap_or(0) as u32,
+                critical_count: row.get::<_, i64>("critical_count").unwrap_or(0) as u32,
+                // Every failed query is an error run
+                error_count: failed_queries as u32,
+                latency,
             }))
         },
     )?;
@@ -243,13 +392,19 @@ pub(super) fn store_aggregated_metric(
     metrics: &AggregatedMetrics,
     sql_data: &AggregatedSqlQueryMetric,
 ) -> Result<i64> {
+    let labels = db_labels::column_value(&metrics.labels)?;
+    let (p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram) =
+        db_latency::column_values(&sql_data.latency)?;
     conn.execute(
         r#"
         INSERT OR REPLACE INTO agg_metric_sql_query
         (task_name, period_start, period_end, sample_count, success_rate_percent,
          avg_total_time_ms, max_total_time_ms, avg_row_count, max_row_count,
-         successful_queries, failed_queries, target_id, avg_value, min_value, max_value, json_truncated_count)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+         successful_queries, failed_queries, target_id, avg_value, min_value, max_value, json_truncated_count,
+         avg_connect_time_ms, avg_query_time_ms, ok_count, warning_count, critical_count, error_count,
+         p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22,
+                ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32, ?33)
         "#,
         params![
             metrics.task_name,
@@ -268,6 +423,23 @@ pub(super) fn store_aggregated_metric(
             sql_data.min_value,
             sql_data.max_value,
             sql_data.json_truncated_count,
+            sql_data.avg_connect_time_ms,
+            sql_data.avg_query_time_ms,
+            sql_data.ok_count,
+            sql_data.warning_count,
+            sql_data.critical_count,
+            sql_data.error_count,
+            p50_ms,
+            p90_ms,
+            p95_ms,
+            p99_ms,
+            latency_histogram,
+            metrics.maintenance,
+            metrics.adaptive,
+            metrics.upstream_down,
+            metrics.avg_queue_wait_ms,
+            metrics.max_queue_wait_ms,
+            labels,
         ],
     )?;
     Ok(conn.last_insert_rowid())
@@ -283,7 +455,10 @@ pub(super) fn load_aggregated_metric(
         "SELECT task_name, period_start, period_end, sample_count,
                 success_rate_percent, avg_total_time_ms, max_total_time_ms,
                 avg_row_count, max_row_count, successful_queries,
-                failed_queries, target_id, avg_value, min_value, max_value, json_truncated_count
+                failed_queries, target_id, avg_value, min_value, max_value, json_truncated_count,
+                avg_connect_time_ms, avg_query_time_ms,
+                ok_count, warning_count, critical_count, error_count,
+                p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels
          FROM agg_metric_sql_query WHERE id = ?1",
     )?;
 
@@ -294,6 +469,12 @@ pub(super) fn load_aggregated_metric(
             period_start: row.get::<_, i64>(1)? as u64,
             period_end: row.get::<_, i64>(2)? as u64,
             sample_count: row.get(3)?,
+            maintenance: row.get(27)?,
+            adaptive: row.get(28)?,
+            upstream_down: row.get(29)?,
+            avg_queue_wait_ms: row.get(30)?,
+            max_queue_wait_ms: row.get(31)?,
+            labels: db_labels::read_column(row, 32)?,
             data: AggregatedMetricData::SqlQuery(AggregatedSqlQueryMetric {
                 success_rate_percent: row.get(4)?,
                 avg_total_time_ms: row.get(5)?,
@@ -307,6 +488,13 @@ pub(super) fn load_aggregated_metric(
                 min_value: row.get(13).ok(),
                 max_value: row.get(14).ok(),
                 json_truncated_count: row.get::<_, i64>(15).unwrap_or(0) as u32,
+                avg_connect_time_ms: row.get(16).ok(),
+                avg_query_time_ms: row.get(17).ok(),
+                ok_count: row.get(18)?,
+                warning_count: row.get(19)?,
+                critical_count: row.get(20)?,
+                error_count: row.get(21)?,
+                latency: db_latency::read_columns(row, 22)?,
             }),
         })
     });
@@ -322,7 +510,14 @@ pub(super) fn load_aggregated_metric(
 #[cfg(feature = "sql-tasks")]
 pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<(usize, usize)> {
     let raw_deleted = conn.execute(
-        "DELETE FROM raw_metric_sql_query WHERE timestamp < ?1",
+        r#"
+        DELETE FROM raw_metric_sql_query
+        WHERE timestamp < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'raw_sql_query' AND status != 'sent'
+          )
+        "#,
         params![cutoff_time],
     )?;
 
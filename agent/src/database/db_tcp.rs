@@ -10,10 +10,13 @@ use anyhow::{Context, Result};
 use rusqlite::{params, Connection};
 use shared::config::TaskType;
 use shared::metrics::{
-    AggregatedMetricData, AggregatedMetrics, AggregatedTcpMetric, MetricData, RawTcpMetric,
+    AggregatedMetricData, AggregatedMetrics, AggregatedTcpMetric, MetricData, RawMetricData,
+    RawTcpMetric,
 };
 use tracing::debug;
 
+use super::{add_columns, db_labels, db_latency, ANNOTATION_COLUMNS};
+
 /// Create TCP-specific tables and indexes
 pub(super) fn create_tables(conn: &Connection) -> Result<()> {
     conn.execute(
@@ -23,10 +26,13 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
             task_name TEXT NOT NULL,
             timestamp INTEGER NOT NULL,
             connect_time_ms REAL,
+            response_time_ms REAL,
+            expect_matched BOOLEAN,
             success BOOLEAN NOT NULL,
             error TEXT,
             host TEXT NOT NULL,
-            target_id TEXT
+            target_id TEXT,
+            labels TEXT
         )
         "#,
         [],
@@ -49,6 +55,17 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
             failed_connections INTEGER NOT NULL,
             host TEXT,
             target_id TEXT,
+            p50_ms REAL,
+            p90_ms REAL,
+            p95_ms REAL,
+            p99_ms REAL,
+            latency_histogram TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
             UNIQUE(task_name, period_start, period_end)
         )
         "#,
@@ -69,6 +86,23 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Add response check and latency distribution columns to existing tables (migration)
+    // SQLite doesn't support IF NOT EXISTS for ALTER TABLE, so we ignore errors
+    for column in ["response_time_ms REAL", "expect_matched BOOLEAN"] {
+        let _ = conn.execute(
+            &format!("ALTER TABLE raw_metric_tcp ADD COLUMN {}", column),
+            [],
+        );
+    }
+    db_latency::add_columns(conn, "agg_metric_tcp");
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_tcp", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "raw_metric_tcp");
+    db_labels::add_column(conn, "agg_metric_tcp");
+
     Ok(())
 }
 
@@ -78,26 +112,67 @@ pub(super) fn store_raw_metric(
     metric: &MetricData,
     tcp_data: &RawTcpMetric,
 ) -> Result<i64> {
-    let row_id = conn.execute(
+    let labels = db_labels::column_value(&metric.labels)?;
+    conn.execute(
         r#"
-        INSERT INTO raw_metric_tcp (task_name, timestamp, connect_time_ms, success, error, host, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+        INSERT INTO raw_metric_tcp (task_name, timestamp, connect_time_ms, response_time_ms, expect_matched, success, error, host, target_id, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
         "#,
         params![
             metric.task_name,
             metric.timestamp as i64,
             tcp_data.connect_time_ms,
+            tcp_data.response_time_ms,
+            tcp_data.expect_matched,
             tcp_data.success,
             tcp_data.error,
             tcp_data.host,
-            tcp_data.target_id
+            tcp_data.target_id,
+            labels
         ],
     )?;
+    let row_id = conn.last_insert_rowid();
     debug!("Stored TCP metric with ID: {}", row_id);
-    Ok(row_id as i64)
+    Ok(row_id)
+}
+
+/// Load a raw TCP metric by row ID
+pub(super) fn load_raw_metric(conn: &Connection, row_id: i64) -> Result<Option<MetricData>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, timestamp, labels, connect_time_ms, response_time_ms, expect_matched, success, error, host, target_id
+         FROM raw_metric_tcp WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        Ok(MetricData {
+            task_name: row.get(0)?,
+            task_type: TaskType::Tcp,
+            timestamp: row.get::<_, i64>(1)? as u64,
+            labels: db_labels::read_column(row, 2)?,
+            row_id: Some(row_id),
+            data: RawMetricData::Tcp(RawTcpMetric {
+                connect_time_ms: row.get(3)?,
+                response_time_ms: row.get(4)?,
+                expect_matched: row.get(5)?,
+                success: row.get(6)?,
+                error: row.get(7)?,
+                host: row.get(8)?,
+                target_id: row.get(9)?,
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
 }
 
 /// Generate aggregated TCP metrics for a period
+///
+/// Connection times include connections whose response check (`send`/`expect`)
+/// failed; those runs still count as failed connections.
 pub(super) fn generate_aggregated_metrics(
     conn: &Connection,
     task_name: &str,
@@ -108,9 +183,9 @@ pub(super) fn generate_aggregated_metrics(
         r#"
         SELECT
             COUNT(*) as total_count,
-            AVG(CASE WHEN success = 1 AND connect_time_ms IS NOT NULL THEN connect_time_ms END) as avg_connect_time,
-            MAX(CASE WHEN success = 1 AND connect_time_ms IS NOT NULL THEN connect_time_ms END) as max_connect_time,
-            MIN(CASE WHEN success = 1 AND connect_time_ms IS NOT NULL THEN connect_time_ms END) as min_connect_time,
+            AVG(CASE WHEN connect_time_ms IS NOT NULL THEN connect_time_ms END) as avg_connect_time,
+            MAX(CASE WHEN connect_time_ms IS NOT NULL THEN connect_time_ms END) as max_connect_time,
+            MIN(CASE WHEN connect_time_ms IS NOT NULL THEN connect_time_ms END) as min_connect_time,
             SUM(CASE WHEN success = 1 THEN 1 ELSE 0 END) as successful_connections,
             SUM(CASE WHEN success = 0 THEN 1 ELSE 0 END) as failed_connections,
             (SELECT host FROM raw_metric_tcp
@@ -127,6 +202,18 @@ pub(super) fn generate_aggregated_metrics(
         "#,
     )?;
 
+    let latency = db_latency::query_distribution(
+        conn,
+        r#"
+        SELECT connect_time_ms FROM raw_metric_tcp
+        WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+        AND connect_time_ms IS NOT NULL
+        "#,
+        task_name,
+        period_start,
+        period_end,
+    )?;
+
     let row = stmt.query_row(
         params![task_name, period_start as i64, period_end as i64],
         |row| {
@@ -155,6 +242,7 @@ pub(super) fn generate_aggregated_metrics(
                 failed_connections: failed_connections as u32,
                 host,
                 target_id,
+                latency,
             }))
         },
     )?;
@@ -180,11 +268,15 @@ pub(super) fn store_aggregated_metric(
     metrics: &AggregatedMetrics,
     tcp_data: &AggregatedTcpMetric,
 ) -> Result<i64> {
+    let labels = db_labels::column_value(&metrics.labels)?;
+    let (p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram) =
+        db_latency::column_values(&tcp_data.latency)?;
     conn.execute(
         r#"
         INSERT OR REPLACE INTO agg_metric_tcp
-        (task_name, period_start, period_end, sample_count, avg_connect_time_ms, max_connect_time_ms, min_connect_time_ms, failure_percent, successful_connections, failed_connections, host, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        (task_name, period_start, period_end, sample_count, avg_connect_time_ms, max_connect_time_ms, min_connect_time_ms, failure_percent, successful_connections, failed_connections, host, target_id,
+         p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23)
         "#,
         params![
             metrics.task_name,
@@ -198,7 +290,18 @@ pub(super) fn store_aggregated_metric(
             tcp_data.successful_connections,
             tcp_data.failed_connections,
             tcp_data.host,
-            tcp_data.target_id
+            tcp_data.target_id,
+            p50_ms,
+            p90_ms,
+            p95_ms,
+            p99_ms,
+            latency_histogram,
+            metrics.maintenance,
+            metrics.adaptive,
+            metrics.upstream_down,
+            metrics.avg_queue_wait_ms,
+            metrics.max_queue_wait_ms,
+            labels,
         ],
     )?;
     Ok(conn.last_insert_rowid())
@@ -213,7 +316,8 @@ pub(super) fn load_aggregated_metric(
         "SELECT task_name, period_start, period_end, sample_count,
                 avg_connect_time_ms, min_connect_time_ms, max_connect_time_ms,
                 failure_percent, successful_connections, failed_connections,
-                host, target_id
+                host, target_id,
+                p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels
          FROM agg_metric_tcp WHERE id = ?1",
     )?;
 
@@ -224,6 +328,12 @@ pub(super) fn load_aggregated_metric(
             period_start: row.get::<_, i64>(1)? as u64,
             period_end: row.get::<_, i64>(2)? as u64,
             sample_count: row.get(3)?,
+            maintenance: row.get(17)?,
+            adaptive: row.get(18)?,
+            upstream_down: row.get(19)?,
+            avg_queue_wait_ms: row.get(20)?,
+            max_queue_wait_ms: row.get(21)?,
+            labels: db_labels::read_column(row, 22)?,
             data: AggregatedMetricData::Tcp(AggregatedTcpMetric {
                 avg_connect_time_ms: row.get(4)?,
                 min_connect_time_ms: row.get(5)?,
@@ -233,6 +343,7 @@ pub(super) fn load_aggregated_metric(
                 failed_connections: row.get(9)?,
                 host: row.get(10).unwrap_or_default(),
                 target_id: row.get(11).ok(),
+                latency: db_latency::read_columns(row, 12)?,
             }),
         })
     });
@@ -247,7 +358,14 @@ pub(super) fn load_aggregated_metric(
 /// Clean up old TCP metrics
 pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<(usize, usize)> {
     let raw_deleted = conn.execute(
-        "DELETE FROM raw_metric_tcp WHERE timestamp < ?1",
+        r#"
+        DELETE FROM raw_metric_tcp
+        WHERE timestamp < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'raw_tcp' AND status != 'sent'
+          )
+        "#,
         params![cutoff_time],
     )?;
 
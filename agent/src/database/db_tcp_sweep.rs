@@ -0,0 +1,454 @@
+//! TCP sweep task database operations
+//!
+//! This module handles all database operations specific to TCP sweep monitoring:
+//! - Table creation and indexing
+//! - Raw metric storage (per-target results as JSON)
+//! - Aggregated metric generation (including per-target summaries) and storage
+//! - Loading aggregated metrics
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use shared::config::TaskType;
+use shared::metrics::{
+    AggregatedMetricData, AggregatedMetrics, AggregatedTcpSweepMetric, MetricData, RawMetricData,
+    RawTcpSweepMetric, TcpSweepState, TcpSweepTargetResult, TcpSweepTargetSummary,
+};
+use std::collections::HashMap;
+use tracing::debug;
+
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
+
+/// Create TCP sweep-specific tables and indexes
+pub(super) fn create_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS raw_metric_tcp_sweep (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_name TEXT NOT NULL,
+            timestamp INTEGER NOT NULL,
+            targets_total INTEGER NOT NULL,
+            open_count INTEGER NOT NULL,
+            closed_count INTEGER NOT NULL,
+            filtered_count INTEGER NOT NULL,
+            unresolved_count INTEGER NOT NULL,
+            avg_connect_time_ms REAL,
+            min_connect_time_ms REAL,
+            max_connect_time_ms REAL,
+            duration_ms REAL NOT NULL,
+            results TEXT NOT NULL,
+            success BOOLEAN NOT NULL,
+            error TEXT,
+            target_id TEXT,
+            labels TEXT
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create raw_metric_tcp_sweep table")?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS agg_metric_tcp_sweep (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_name TEXT NOT NULL,
+            period_start INTEGER NOT NULL,
+            period_end INTEGER NOT NULL,
+            sample_count INTEGER NOT NULL,
+            targets_total INTEGER NOT NULL,
+            avg_open_count REAL NOT NULL,
+            min_open_count INTEGER NOT NULL,
+            avg_closed_count REAL NOT NULL,
+            avg_filtered_count REAL NOT NULL,
+            avg_unresolved_count REAL NOT NULL,
+            avg_connect_time_ms REAL NOT NULL,
+            max_connect_time_ms REAL NOT NULL,
+            min_connect_time_ms REAL NOT NULL,
+            successful_sweeps INTEGER NOT NULL,
+            failed_sweeps INTEGER NOT NULL,
+            targets TEXT NOT NULL,
+            target_id TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
+            UNIQUE(task_name, period_start, period_end)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create agg_metric_tcp_sweep table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_tcp_sweep_timestamp ON raw_metric_tcp_sweep(timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_tcp_sweep_task ON raw_metric_tcp_sweep(task_name, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agg_tcp_sweep_period ON agg_metric_tcp_sweep(period_start, period_end)",
+        [],
+    )?;
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_tcp_sweep", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "raw_metric_tcp_sweep");
+    db_labels::add_column(conn, "agg_metric_tcp_sweep");
+
+    Ok(())
+}
+
+/// Store a raw TCP sweep metric
+pub(super) fn store_raw_metric(
+    conn: &Connection,
+    metric: &MetricData,
+    sweep_data: &RawTcpSweepMetric,
+) -> Result<i64> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    conn.execute(
+        r#"
+        INSERT INTO raw_metric_tcp_sweep (task_name, timestamp, targets_total, open_count, closed_count, filtered_count, unresolved_count, avg_connect_time_ms, min_connect_time_ms, max_connect_time_ms, duration_ms, results, success, error, target_id, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+        "#,
+        params![
+            metric.task_name,
+            metric.timestamp as i64,
+            sweep_data.targets_total,
+            sweep_data.open_count,
+            sweep_data.closed_count,
+            sweep_data.filtered_count,
+            sweep_data.unresolved_count,
+            sweep_data.avg_connect_time_ms,
+            sweep_data.min_connect_time_ms,
+            sweep_data.max_connect_time_ms,
+            sweep_data.duration_ms,
+            serde_json::to_string(&sweep_data.results)?,
+            sweep_data.success,
+            sweep_data.error,
+            sweep_data.target_id,
+            labels
+        ],
+    )?;
+    let row_id = conn.last_insert_rowid();
+    debug!("Stored TCP sweep metric with ID: {}", row_id);
+    Ok(row_id)
+}
+
+/// Load a raw TCP sweep metric by row ID
+pub(super) fn load_raw_metric(conn: &Connection, row_id: i64) -> Result<Option<MetricData>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, timestamp, labels, targets_total, open_count, closed_count, filtered_count, unresolved_count, avg_connect_time_ms, min_connect_time_ms, max_connect_time_ms, duration_ms, results, success, error, target_id
+         FROM raw_metric_tcp_sweep WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        let results: String = row.get(12)?;
+        let results = serde_json::from_str(&results).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(12, rusqlite::types::Type::Text, Box::new(e))
+        })?;
+        Ok(MetricData {
+            task_name: row.get(0)?,
+            task_type: TaskType::TcpSweep,
+            timestamp: row.get::<_, i64>(1)? as u64,
+            labels: db_labels::read_column(row, 2)?,
+            row_id: Some(row_id),
+            data: RawMetricData::TcpSweep(RawTcpSweepMetric {
+                targets_total: row.get(3)?,
+                open_count: row.get(4)?,
+                closed_count: row.get(5)?,
+                filtered_count: row.get(6)?,
+                unresolved_count: row.get(7)?,
+                avg_connect_time_ms: row.get(8)?,
+                min_connect_time_ms: row.get(9)?,
+                max_connect_time_ms: row.get(10)?,
+                duration_ms: row.get(11)?,
+                results,
+                success: row.get(13)?,
+                error: row.get(14)?,
+                target_id: row.get(15)?,
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Per-target accumulator used while aggregating a period
+#[derive(Default)]
+struct TargetTotals {
+    sweeps: u32,
+    open: u32,
+    connect_time_sum_ms: f64,
+    last_state: Option<TcpSweepState>,
+}
+
+/// Generate aggregated TCP sweep metrics for a period
+///
+/// Counts are averaged over sweeps and connection times over all open targets.
+/// Per-target summaries are built from the stored per-target results.
+pub(super) fn generate_aggregated_metrics(
+    conn: &Connection,
+    task_name: &str,
+    period_start: u64,
+    period_end: u64,
+) -> Result<Option<AggregatedMetrics>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT targets_total, open_count, closed_count, filtered_count, unresolved_count,
+               results, success, target_id
+        FROM raw_metric_tcp_sweep
+        WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+        ORDER BY timestamp ASC, id ASC
+        "#,
+    )?;
+
+    let rows = stmt
+        .query_map(
+            params![task_name, period_start as i64, period_end as i64],
+            |row| {
+                Ok((
+                    row.get::<_, u32>(0)?,
+                    [
+                        row.get::<_, u32>(1)?,
+                        row.get::<_, u32>(2)?,
+                        row.get::<_, u32>(3)?,
+                        row.get::<_, u32>(4)?,
+                    ],
+                    row.get::<_, String>(5)?,
+                    row.get::<_, bool>(6)?,
+                    row.get::<_, Option<String>>(7)?,
+                ))
+            },
+        )?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    if rows.is_empty() {
+        return Ok(None);
+    }
+
+    let sweeps = rows.len() as u32;
+    let mut targets_total = 0;
+    let mut count_sums = [0u64; 4];
+    let mut min_open_count = u32::MAX;
+    let mut successful_sweeps = 0;
+    let mut target_id = None;
+    let mut connect_times: Vec<f64> = Vec::new();
+    let mut totals: HashMap<String, TargetTotals> = HashMap::new();
+    let mut last_order: Vec<String> = Vec::new();
+
+    for (total, counts, results, success, row_target_id) in rows {
+        targets_total = total;
+        for (sum, count) in count_sums.iter_mut().zip(counts) {
+            *sum += count as u64;
+        }
+        min_open_count = min_open_count.min(counts[0]);
+        if success {
+            successful_sweeps += 1;
+        }
+        if target_id.is_none() {
+            target_id = row_target_id;
+        }
+
+        let results: Vec<TcpSweepTargetResult> =
+            serde_json::from_str(&results).context("Failed to parse stored TCP sweep results")?;
+        last_order = results.iter().map(|r| r.target.clone()).collect();
+        for result in results {
+            let entry = totals.entry(result.target).or_default();
+            entry.sweeps += 1;
+            if let Some(connect_time_ms) = result.connect_time_ms {
+                entry.open += 1;
+                entry.connect_time_sum_ms += connect_time_ms;
+                connect_times.push(connect_time_ms);
+            }
+            entry.last_state = Some(result.state);
+        }
+    }
+
+    let targets = last_order
+        .into_iter()
+        .filter_map(|target| {
+            let entry = totals.remove(&target)?;
+            Some(TcpSweepTargetSummary {
+                open_percent: entry.open as f64 / entry.sweeps as f64 * 100.0,
+                avg_connect_time_ms: (entry.open > 0)
+                    .then(|| entry.connect_time_sum_ms / entry.open as f64),
+                last_state: entry.last_state?,
+                target,
+            })
+        })
+        .collect();
+
+    let average = |sum: u64| sum as f64 / sweeps as f64;
+    let sweep_metric = AggregatedTcpSweepMetric {
+        targets_total,
+        avg_open_count: average(count_sums[0]),
+        min_open_count,
+        avg_closed_count: average(count_sums[1]),
+        avg_filtered_count: average(count_sums[2]),
+        avg_unresolved_count: average(count_sums[3]),
+        avg_connect_time_ms: if connect_times.is_empty() {
+            0.0
+        } else {
+            connect_times.iter().sum::<f64>() / connect_times.len() as f64
+        },
+        max_connect_time_ms: connect_times
+            .iter()
+            .copied()
+            .reduce(f64::max)
+            .unwrap_or(0.0),
+        min_connect_time_ms: connect_times
+            .iter()
+            .copied()
+            .reduce(f64::min)
+            .unwrap_or(0.0),
+        successful_sweeps,
+        failed_sweeps: sweeps - successful_sweeps,
+        targets,
+        target_id,
+    };
+
+    Ok(Some(AggregatedMetrics::new(
+        task_name.to_string(),
+        TaskType::TcpSweep,
+        period_start,
+        period_end,
+        sweeps,
+        AggregatedMetricData::TcpSweep(sweep_metric),
+    )))
+}
+
+/// Store aggregated TCP sweep metrics
+pub(super) fn store_aggregated_metric(
+    conn: &Connection,
+    metrics: &AggregatedMetrics,
+    sweep_data: &AggregatedTcpSweepMetric,
+) -> Result<i64> {
+    let labels = db_labels::column_value(&metrics.labels)?;
+    conn.execute(
+        r#"
+        INSERT OR REPLACE INTO agg_metric_tcp_sweep
+        (task_name, period_start, period_end, sample_count, targets_total, avg_open_count, min_open_count, avg_closed_count, avg_filtered_count, avg_unresolved_count, avg_connect_time_ms, max_connect_time_ms, min_connect_time_ms, successful_sweeps, failed_sweeps, targets, target_id, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23)
+        "#,
+        params![
+            metrics.task_name,
+            metrics.period_start as i64,
+            metrics.period_end as i64,
+            metrics.sample_count,
+            sweep_data.targets_total,
+            sweep_data.avg_open_count,
+            sweep_data.min_open_count,
+            sweep_data.avg_closed_count,
+            sweep_data.avg_filtered_count,
+            sweep_data.avg_unresolved_count,
+            sweep_data.avg_connect_time_ms,
+            sweep_data.max_connect_time_ms,
+            sweep_data.min_connect_time_ms,
+            sweep_data.successful_sweeps,
+            sweep_data.failed_sweeps,
+            serde_json::to_string(&sweep_data.targets)?,
+            sweep_data.target_id,
+            metrics.maintenance,
+            metrics.adaptive,
+            metrics.upstream_down,
+            metrics.avg_queue_wait_ms,
+            metrics.max_queue_wait_ms,
+            labels,
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Load aggregated TCP sweep metric by row ID
+pub(super) fn load_aggregated_metric(
+    conn: &Connection,
+    row_id: i64,
+) -> Result<Option<AggregatedMetrics>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, period_start, period_end, sample_count,
+                targets_total, avg_open_count, min_open_count, avg_closed_count,
+                avg_filtered_count, avg_unresolved_count, avg_connect_time_ms,
+                max_connect_time_ms, min_connect_time_ms, successful_sweeps, failed_sweeps,
+                targets, target_id, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels
+         FROM agg_metric_tcp_sweep WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        let targets: String = row.get(15)?;
+        let targets = serde_json::from_str(&targets).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(15, rusqlite::types::Type::Text, Box::new(e))
+        })?;
+        Ok(AggregatedMetrics {
+            task_name: row.get(0)?,
+            task_type: TaskType::TcpSweep,
+            period_start: row.get::<_, i64>(1)? as u64,
+            period_end: row.get::<_, i64>(2)? as u64,
+            sample_count: row.get(3)?,
+            maintenance: row.get(17)?,
+            adaptive: row.get(18)?,
+            upstream_down: row.get(19)?,
+            avg_queue_wait_ms: row.get(20)?,
+            max_queue_wait_ms: row.get(21)?,
+            labels: db_labels::read_column(row, 22)?,
+            data: AggregatedMetricData::TcpSweep(AggregatedTcpSweepMetric {
+                targets_total: row.get(4)?,
+                avg_open_count: row.get(5)?,
+                min_open_count: row.get(6)?,
+                avg_closed_count: row.get(7)?,
+                avg_filtered_count: row.get(8)?,
+                avg_unresolved_count: row.get(9)?,
+                avg_connect_time_ms: row.get(10)?,
+                max_connect_time_ms: row.get(11)?,
+                min_connect_time_ms: row.get(12)?,
+                successful_sweeps: row.get(13)?,
+                failed_sweeps: row.get(14)?,
+                targets,
+                target_id: row.get(16).ok(),
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Clean up old TCP sweep metrics
+pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<(usize, usize)> {
+    let raw_deleted = conn.execute(
+        r#"
+        DELETE FROM raw_metric_tcp_sweep
+        WHERE timestamp < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'raw_tcp_sweep' AND status != 'sent'
+          )
+        "#,
+        params![cutoff_time],
+    )?;
+
+    let agg_deleted = conn.execute(
+        r#"
+        DELETE FROM agg_metric_tcp_sweep
+        WHERE period_end < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'tcp_sweep' AND status != 'sent'
+          )
+        "#,
+        params![cutoff_time],
+    )?;
+
+    Ok((raw_deleted, agg_deleted))
+}
@@ -5,11 +5,14 @@ use rusqlite::{params, Connection};
 use shared::{
     config::TaskType,
     metrics::{
-        AggregatedMetricData, AggregatedMetrics, AggregatedTlsMetric, MetricData, RawTlsMetric,
+        AggregatedMetricData, AggregatedMetrics, AggregatedTlsMetric, MetricData, RawMetricData,
+        RawTlsMetric,
     },
 };
 use tracing::debug;
 
+use super::{add_columns, db_labels, db_latency, ANNOTATION_COLUMNS};
+
 /// Create TLS handshake-specific tables and indexes
 pub(super) fn create_tables(conn: &Connection) -> Result<()> {
     conn.execute(
@@ -24,7 +27,8 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
             ssl_cert_days_until_expiry INTEGER,
             success BOOLEAN NOT NULL,
             error TEXT,
-            target_id TEXT
+            target_id TEXT,
+            labels TEXT
         )
         "#,
         [],
@@ -47,6 +51,17 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
             ssl_valid_percent REAL,
             avg_ssl_cert_days_until_expiry REAL,
             target_id TEXT,
+            p50_ms REAL,
+            p90_ms REAL,
+            p95_ms REAL,
+            p99_ms REAL,
+            latency_histogram TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
             UNIQUE(task_name, period_start, period_end)
         )
         "#,
@@ -67,6 +82,16 @@ pub(super) fn create_tables(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Add latency distribution columns to existing tables (migration)
+    db_latency::add_columns(conn, "agg_metric_tls");
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_tls", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "raw_metric_tls");
+    db_labels::add_column(conn, "agg_metric_tls");
+
     Ok(())
 }
 
@@ -76,10 +101,11 @@ pub(super) fn store_raw_metric(
     metric: &MetricData,
     tls_data: &RawTlsMetric,
 ) -> Result<i64> {
-    let row_id = conn.execute(
+    let labels = db_labels::column_value(&metric.labels)?;
+    conn.execute(
         r#"
-        INSERT INTO raw_metric_tls (task_name, timestamp, tcp_timing_ms, tls_timing_ms, ssl_valid, ssl_cert_days_until_expiry, success, error, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        INSERT INTO raw_metric_tls (task_name, timestamp, tcp_timing_ms, tls_timing_ms, ssl_valid, ssl_cert_days_until_expiry, success, error, target_id, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
         "#,
         params![
             metric.task_name,
@@ -90,11 +116,46 @@ pub(super) fn store_raw_metric(
             tls_data.ssl_cert_days_until_expiry,
             tls_data.success,
             tls_data.error,
-            tls_data.target_id
+            tls_data.target_id,
+            labels
         ],
     )?;
+    let row_id = conn.last_insert_rowid();
     debug!("Stored TLS metric with ID: {}", row_id);
-    Ok(row_id as i64)
+    Ok(row_id)
+}
+
+/// Load a raw TLS metric by row ID
+pub(super) fn load_raw_metric(conn: &Connection, row_id: i64) -> Result<Option<MetricData>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, timestamp, labels, tcp_timing_ms, tls_timing_ms, ssl_valid, ssl_cert_days_until_expiry, success, error, target_id
+         FROM raw_metric_tls WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        Ok(MetricData {
+            task_name: row.get(0)?,
+            task_type: TaskType::TlsHandshake,
+            timestamp: row.get::<_, i64>(1)? as u64,
+            labels: db_labels::read_column(row, 2)?,
+            row_id: Some(row_id),
+            data: RawMetricData::TlsHandshake(RawTlsMetric {
+                tcp_timing_ms: row.get(3)?,
+                tls_timing_ms: row.get(4)?,
+                ssl_valid: row.get(5)?,
+                ssl_cert_days_until_expiry: row.get(6)?,
+                success: row.get(7)?,
+                error: row.get(8)?,
+                target_id: row.get(9)?,
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
 }
 
 /// Generate aggregated TLS metrics for a time period
@@ -124,6 +185,18 @@ pub(super) fn generate_aggregated_metrics(
         "#,
     )?;
 
+    let latency = db_latency::query_distribution(
+        conn,
+        r#"
+        SELECT tls_timing_ms FROM raw_metric_tls
+        WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+        AND success = 1 AND tls_timing_ms IS NOT NULL
+        "#,
+        task_name,
+        period_start,
+        period_end,
+    )?;
+
     let row = stmt.query_row(
         params![task_name, period_start as i64, period_end as i64],
         |row| {
@@ -154,6 +227,7 @@ pub(super) fn generate_aggregated_metrics(
                 ssl_valid_percent: ssl_valid_percent.unwrap_or(0.0),
                 avg_ssl_cert_days_until_expiry: avg_ssl_cert_days_until_expiry.unwrap_or(0.0),
                 target_id,
+                latency,
             }))
         },
     )?;
@@ -179,11 +253,15 @@ pub(super) fn store_aggregated_metric(
     metrics: &AggregatedMetrics,
     tls_data: &AggregatedTlsMetric,
 ) -> Result<i64> {
+    let labels = db_labels::column_value(&metrics.labels)?;
+    let (p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram) =
+        db_latency::column_values(&tls_data.latency)?;
     conn.execute(
         r#"
         INSERT OR REPLACE INTO agg_metric_tls
-        (task_name, period_start, period_end, sample_count, success_rate_percent, avg_tcp_timing_ms, avg_tls_timing_ms, successful_checks, failed_checks, ssl_valid_percent, avg_ssl_cert_days_until_expiry, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        (task_name, period_start, period_end, sample_count, success_rate_percent, avg_tcp_timing_ms, avg_tls_timing_ms, successful_checks, failed_checks, ssl_valid_percent, avg_ssl_cert_days_until_expiry, target_id,
+         p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23)
         "#,
         params![
             metrics.task_name,
@@ -197,7 +275,18 @@ pub(super) fn store_aggregated_metric(
             tls_data.failed_checks,
             tls_data.ssl_valid_percent,
             tls_data.avg_ssl_cert_days_until_expiry,
-            tls_data.target_id
+            tls_data.target_id,
+            p50_ms,
+            p90_ms,
+            p95_ms,
+            p99_ms,
+            latency_histogram,
+            metrics.maintenance,
+            metrics.adaptive,
+            metrics.upstream_down,
+            metrics.avg_queue_wait_ms,
+            metrics.max_queue_wait_ms,
+            labels,
         ],
     )?;
     Ok(conn.last_insert_rowid())
@@ -212,7 +301,8 @@ pub(super) fn load_aggregated_metric(
         "SELECT task_name, period_start, period_end, sample_count,
                 success_rate_percent, avg_tcp_timing_ms, avg_tls_timing_ms,
                 successful_checks, failed_checks, ssl_valid_percent,
-                avg_ssl_cert_days_until_expiry, target_id
+                avg_ssl_cert_days_until_expiry, target_id,
+                p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels
          FROM agg_metric_tls WHERE id = ?1",
     )?;
 
@@ -223,6 +313,12 @@ pub(super) fn load_aggregated_metric(
             period_start: row.get::<_, i64>(1)? as u64,
             period_end: row.get::<_, i64>(2)? as u64,
             sample_count: row.get(3)?,
+            maintenance: row.get(17)?,
+            adaptive: row.get(18)?,
+            upstream_down: row.get(19)?,
+            avg_queue_wait_ms: row.get(20)?,
+            max_queue_wait_ms: row.get(21)?,
+            labels: db_labels::read_column(row, 22)?,
             data: AggregatedMetricData::TlsHandshake(AggregatedTlsMetric {
                 success_rate_percent: row.get(4)?,
                 avg_tcp_timing_ms: row.get(5)?,
@@ -232,6 +328,7 @@ pub(super) fn load_aggregated_metric(
                 ssl_valid_percent: row.get(9)?,
                 avg_ssl_cert_days_until_expiry: row.get(10)?,
                 target_id: row.get(11).ok(),
+                latency: db_latency::read_columns(row, 12)?,
             }),
         })
     });
@@ -246,7 +343,14 @@ pub(super) fn load_aggregated_metric(
 /// Clean up old TLS metrics
 pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<(usize, usize)> {
     let raw_deleted = conn.execute(
-        "DELETE FROM raw_metric_tls WHERE timestamp < ?1",
+        r#"
+        DELETE FROM raw_metric_tls
+        WHERE timestamp < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'raw_tls' AND status != 'sent'
+          )
+        "#,
         params![cutoff_time],
     )?;
 
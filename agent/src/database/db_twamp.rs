@@ -0,0 +1,379 @@
+//! TWAMP-Light task database operations
+//!
+//! This module handles all database operations specific to TWAMP-Light monitoring:
+//! - Table creation and indexing
+//! - Raw metric storage
+//! - Aggregated metric generation and storage
+//! - Loading aggregated metrics
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use shared::config::TaskType;
+use shared::metrics::{
+    AggregatedMetricData, AggregatedMetrics, AggregatedTwampMetric, MetricData, RawMetricData,
+    RawTwampMetric,
+};
+use tracing::debug;
+
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
+
+/// Create TWAMP-specific tables and indexes
+pub(super) fn create_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS raw_metric_twamp (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_name TEXT NOT NULL,
+            timestamp INTEGER NOT NULL,
+            packets_sent INTEGER NOT NULL,
+            packets_received INTEGER NOT NULL,
+            packet_loss_percent REAL NOT NULL,
+            avg_latency_ms REAL,
+            min_latency_ms REAL,
+            max_latency_ms REAL,
+            jitter_ms REAL,
+            success BOOLEAN NOT NULL,
+            error TEXT,
+            host TEXT NOT NULL,
+            target_id TEXT,
+            labels TEXT
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create raw_metric_twamp table")?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS agg_metric_twamp (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_name TEXT NOT NULL,
+            period_start INTEGER NOT NULL,
+            period_end INTEGER NOT NULL,
+            sample_count INTEGER NOT NULL,
+            avg_latency_ms REAL NOT NULL,
+            max_latency_ms REAL NOT NULL,
+            min_latency_ms REAL NOT NULL,
+            packet_loss_percent REAL NOT NULL,
+            avg_jitter_ms REAL NOT NULL,
+            max_jitter_ms REAL NOT NULL,
+            packets_sent INTEGER NOT NULL,
+            packets_received INTEGER NOT NULL,
+            successful_tests INTEGER NOT NULL,
+            failed_tests INTEGER NOT NULL,
+            host TEXT,
+            target_id TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
+            UNIQUE(task_name, period_start, period_end)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create agg_metric_twamp table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_twamp_timestamp ON raw_metric_twamp(timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_twamp_task ON raw_metric_twamp(task_name, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agg_twamp_period ON agg_metric_twamp(period_start, period_end)",
+        [],
+    )?;
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_twamp", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "raw_metric_twamp");
+    db_labels::add_column(conn, "agg_metric_twamp");
+
+    Ok(())
+}
+
+/// Store a raw TWAMP metric
+pub(super) fn store_raw_metric(
+    conn: &Connection,
+    metric: &MetricData,
+    twamp_data: &RawTwampMetric,
+) -> Result<i64> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    conn.execute(
+        r#"
+        INSERT INTO raw_metric_twamp (task_name, timestamp, packets_sent, packets_received, packet_loss_percent, avg_latency_ms, min_latency_ms, max_latency_ms, jitter_ms, success, error, host, target_id, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+        "#,
+        params![
+            metric.task_name,
+            metric.timestamp as i64,
+            twamp_data.packets_sent,
+            twamp_data.packets_received,
+            twamp_data.packet_loss_percent,
+            twamp_data.avg_latency_ms,
+            twamp_data.min_latency_ms,
+            twamp_data.max_latency_ms,
+            twamp_data.jitter_ms,
+            twamp_data.success,
+            twamp_data.error,
+            twamp_data.host,
+            twamp_data.target_id,
+            labels
+        ],
+    )?;
+    let row_id = conn.last_insert_rowid();
+    debug!("Stored TWAMP metric with ID: {}", row_id);
+    Ok(row_id)
+}
+
+/// Load a raw TWAMP metric by row ID
+pub(super) fn load_raw_metric(conn: &Connection, row_id: i64) -> Result<Option<MetricData>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, timestamp, labels, packets_sent, packets_received, packet_loss_percent, avg_latency_ms, min_latency_ms, max_latency_ms, jitter_ms, success, error, host, target_id
+         FROM raw_metric_twamp WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        Ok(MetricData {
+            task_name: row.get(0)?,
+            task_type: TaskType::Twamp,
+            timestamp: row.get::<_, i64>(1)? as u64,
+            labels: db_labels::read_column(row, 2)?,
+            row_id: Some(row_id),
+            data: RawMetricData::Twamp(RawTwampMetric {
+                packets_sent: row.get(3)?,
+                packets_received: row.get(4)?,
+                packet_loss_percent: row.get(5)?,
+                avg_latency_ms: row.get(6)?,
+                min_latency_ms: row.get(7)?,
+                max_latency_ms: row.get(8)?,
+                jitter_ms: row.get(9)?,
+                success: row.get(10)?,
+                error: row.get(11)?,
+                host: row.get(12)?,
+                target_id: row.get(13)?,
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Generate aggregated TWAMP metrics for a period
+///
+/// Delay and jitter are aggregated over successful runs like ping latency;
+/// loss is computed over all test packets sent in the period.
+pub(super) fn generate_aggregated_metrics(
+    conn: &Connection,
+    task_name: &str,
+    period_start: u64,
+    period_end: u64,
+) -> Result<Option<AggregatedMetrics>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT
+            COUNT(*) as total_count,
+            AVG(CASE WHEN success = 1 THEN avg_latency_ms END) as avg_latency,
+            MAX(CASE WHEN success = 1 THEN max_latency_ms END) as max_latency,
+            MIN(CASE WHEN success = 1 THEN min_latency_ms END) as min_latency,
+            AVG(CASE WHEN success = 1 THEN jitter_ms END) as avg_jitter,
+            MAX(CASE WHEN success = 1 THEN jitter_ms END) as max_jitter,
+            SUM(packets_sent) as packets_sent,
+            SUM(packets_received) as packets_received,
+            SUM(CASE WHEN success = 1 THEN 1 ELSE 0 END) as successful_tests,
+            SUM(CASE WHEN success = 0 THEN 1 ELSE 0 END) as failed_tests,
+            (SELECT host FROM raw_metric_twamp
+             WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+             ORDER BY timestamp ASC
+             LIMIT 1) as first_host,
+            (SELECT target_id FROM raw_metric_twamp
+             WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+             AND target_id IS NOT NULL
+             ORDER BY timestamp ASC
+             LIMIT 1) as first_target_id
+        FROM raw_metric_twamp
+        WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+        "#,
+    )?;
+
+    let row = stmt.query_row(
+        params![task_name, period_start as i64, period_end as i64],
+        |row| {
+            let total_count: i64 = row.get("total_count")?;
+            if total_count == 0 {
+                return Ok(None);
+            }
+
+            let packets_sent: i64 = row.get("packets_sent")?;
+            let packets_received: i64 = row.get("packets_received")?;
+            let packet_loss_percent = if packets_sent > 0 {
+                (packets_sent - packets_received).max(0) as f64 / packets_sent as f64 * 100.0
+            } else {
+                100.0
+            };
+
+            let host: String = row.get("first_host").unwrap_or_default();
+            let target_id: Option<String> = row.get("first_target_id").ok();
+
+            Ok(Some(AggregatedTwampMetric {
+                avg_latency_ms: row.get("avg_latency").unwrap_or(0.0),
+                max_latency_ms: row.get("max_latency").unwrap_or(0.0),
+                min_latency_ms: row.get("min_latency").unwrap_or(0.0),
+                packet_loss_percent,
+                avg_jitter_ms: row.get("avg_jitter").unwrap_or(0.0),
+                max_jitter_ms: row.get("max_jitter").unwrap_or(0.0),
+                packets_sent: packets_sent as u32,
+                packets_received: packets_received as u32,
+                successful_tests: row.get::<_, i64>("successful_tests")? as u32,
+                failed_tests: row.get::<_, i64>("failed_tests")? as u32,
+                host,
+                target_id,
+            }))
+        },
+    )?;
+
+    if let Some(twamp_metric) = row {
+        let total_samples = twamp_metric.successful_tests + twamp_metric.failed_tests;
+        return Ok(Some(AggregatedMetrics::new(
+            task_name.to_string(),
+            TaskType::Twamp,
+            period_start,
+            period_end,
+            total_samples,
+            AggregatedMetricData::Twamp(twamp_metric),
+        )));
+    }
+
+    Ok(None)
+}
+
+/// Store aggregated TWAMP metrics
+pub(super) fn store_aggregated_metric(
+    conn: &Connection,
+    metrics: &AggregatedMetrics,
+    twamp_data: &AggregatedTwampMetric,
+) -> Result<i64> {
+    let labels = db_labels::column_value(&metrics.labels)?;
+    conn.execute(
+        r#"
+        INSERT OR REPLACE INTO agg_metric_twamp
+        (task_name, period_start, period_end, sample_count, avg_latency_ms, max_latency_ms, min_latency_ms, packet_loss_percent, avg_jitter_ms, max_jitter_ms, packets_sent, packets_received, successful_tests, failed_tests, host, target_id, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22)
+        "#,
+        params![
+            metrics.task_name,
+            metrics.period_start as i64,
+            metrics.period_end as i64,
+            metrics.sample_count,
+            twamp_data.avg_latency_ms,
+            twamp_data.max_latency_ms,
+            twamp_data.min_latency_ms,
+            twamp_data.packet_loss_percent,
+            twamp_data.avg_jitter_ms,
+            twamp_data.max_jitter_ms,
+            twamp_data.packets_sent,
+            twamp_data.packets_received,
+            twamp_data.successful_tests,
+            twamp_data.failed_tests,
+            twamp_data.host,
+            twamp_data.target_id,
+            metrics.maintenance,
+            metrics.adaptive,
+            metrics.upstream_down,
+            metrics.avg_queue_wait_ms,
+            metrics.max_queue_wait_ms,
+            labels,
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Load aggregated TWAMP metric by row ID
+pub(super) fn load_aggregated_metric(
+    conn: &Connection,
+    row_id: i64,
+) -> Result<Option<AggregatedMetrics>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, period_start, period_end, sample_count,
+                avg_latency_ms, max_latency_ms, min_latency_ms, packet_loss_percent,
+                avg_jitter_ms, max_jitter_ms, packets_sent, packets_received,
+                successful_tests, failed_tests, host, target_id, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels
+         FROM agg_metric_twamp WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        Ok(AggregatedMetrics {
+            task_name: row.get(0)?,
+            task_type: TaskType::Twamp,
+            period_start: row.get::<_, i64>(1)? as u64,
+            period_end: row.get::<_, i64>(2)? as u64,
+            sample_count: row.get(3)?,
+            maintenance: row.get(16)?,
+            adaptive: row.get(17)?,
+            upstream_down: row.get(18)?,
+            avg_queue_wait_ms: row.get(19)?,
+            max_queue_wait_ms: row.get(20)?,
+            labels: db_labels::read_column(row, 21)?,
+            data: AggregatedMetricData::Twamp(AggregatedTwampMetric {
+                avg_latency_ms: row.get(4)?,
+                max_latency_ms: row.get(5)?,
+                min_latency_ms: row.get(6)?,
+                packet_loss_percent: row.get(7)?,
+                avg_jitter_ms: row.get(8)?,
+                max_jitter_ms: row.get(9)?,
+                packets_sent: row.get(10)?,
+                packets_received: row.get(11)?,
+                successful_tests: row.get(12)?,
+                failed_tests: row.get(13)?,
+                host: row.get(14).unwrap_or_default(),
+                target_id: row.get(15).ok(),
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Clean up old TWAMP metrics
+pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<(usize, usize)> {
+    let raw_deleted = conn.execute(
+        r#"
+        DELETE FROM raw_metric_twamp
+        WHERE timestamp < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'raw_twamp' AND status != 'sent'
+          )
+        "#,
+        params![cutoff_time],
+    )?;
+
+    let agg_deleted = conn.execute(
+        r#"
+        DELETE FROM agg_metric_twamp
+        WHERE period_end < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'twamp' AND status != 'sent'
+          )
+        "#,
+        params![cutoff_time],
+    )?;
+
+    Ok((raw_deleted, agg_deleted))
+}
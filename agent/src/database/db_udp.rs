@@ -0,0 +1,429 @@
+//! UDP probe task database operations
+//!
+//! This module handles all database operations specific to UDP probe monitoring:
+//! - Table creation and indexing
+//! - Raw metric storage
+//! - Aggregated metric generation (including the estimated MOS) and storage
+//! - Loading aggregated metrics
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use shared::config::TaskType;
+use shared::metrics::{
+    AggregatedMetricData, AggregatedMetrics, AggregatedUdpProbeMetric, MetricData, RawMetricData,
+    RawUdpProbeMetric,
+};
+use tracing::debug;
+
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
+
+/// Create UDP probe-specific tables and indexes
+pub(super) fn create_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS raw_metric_udp_probe (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_name TEXT NOT NULL,
+            timestamp INTEGER NOT NULL,
+            packets_sent INTEGER NOT NULL,
+            packets_received INTEGER NOT NULL,
+            packets_reflected INTEGER,
+            loss_percent REAL NOT NULL,
+            forward_loss_percent REAL,
+            return_loss_percent REAL,
+            duplicate_packets INTEGER NOT NULL,
+            reordered_packets INTEGER NOT NULL,
+            avg_rtt_ms REAL,
+            min_rtt_ms REAL,
+            max_rtt_ms REAL,
+            jitter_ms REAL,
+            success BOOLEAN NOT NULL,
+            error TEXT,
+            host TEXT NOT NULL,
+            target_id TEXT,
+            labels TEXT
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create raw_metric_udp_probe table")?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS agg_metric_udp_probe (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            task_name TEXT NOT NULL,
+            period_start INTEGER NOT NULL,
+            period_end INTEGER NOT NULL,
+            sample_count INTEGER NOT NULL,
+            avg_rtt_ms REAL NOT NULL,
+            max_rtt_ms REAL NOT NULL,
+            min_rtt_ms REAL NOT NULL,
+            avg_jitter_ms REAL NOT NULL,
+            max_jitter_ms REAL NOT NULL,
+            loss_percent REAL NOT NULL,
+            forward_loss_percent REAL,
+            return_loss_percent REAL,
+            packets_sent INTEGER NOT NULL,
+            packets_received INTEGER NOT NULL,
+            duplicate_packets INTEGER NOT NULL,
+            reordered_packets INTEGER NOT NULL,
+            mos REAL NOT NULL,
+            successful_probes INTEGER NOT NULL,
+            failed_probes INTEGER NOT NULL,
+            host TEXT,
+            target_id TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
+            UNIQUE(task_name, period_start, period_end)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create agg_metric_udp_probe table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_udp_probe_timestamp ON raw_metric_udp_probe(timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_udp_probe_task ON raw_metric_udp_probe(task_name, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agg_udp_probe_period ON agg_metric_udp_probe(period_start, period_end)",
+        [],
+    )?;
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_udp_probe", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "raw_metric_udp_probe");
+    db_labels::add_column(conn, "agg_metric_udp_probe");
+
+    Ok(())
+}
+
+/// Store a raw UDP probe metric
+pub(super) fn store_raw_metric(
+    conn: &Connection,
+    metric: &MetricData,
+    udp_data: &RawUdpProbeMetric,
+) -> Result<i64> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    conn.execute(
+        r#"
+        INSERT INTO raw_metric_udp_probe (task_name, timestamp, packets_sent, packets_received, packets_reflected, loss_percent, forward_loss_percent, return_loss_percent, duplicate_packets, reordered_packets, avg_rtt_ms, min_rtt_ms, max_rtt_ms, jitter_ms, success, error, host, target_id, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)
+        "#,
+        params![
+            metric.task_name,
+            metric.timestamp as i64,
+            udp_data.packets_sent,
+            udp_data.packets_received,
+            udp_data.packets_reflected,
+            udp_data.loss_percent,
+            udp_data.forward_loss_percent,
+            udp_data.return_loss_percent,
+            udp_data.duplicate_packets,
+            udp_data.reordered_packets,
+            udp_data.avg_rtt_ms,
+            udp_data.min_rtt_ms,
+            udp_data.max_rtt_ms,
+            udp_data.jitter_ms,
+            udp_data.success,
+            udp_data.error,
+            udp_data.host,
+            udp_data.target_id,
+            labels
+        ],
+    )?;
+    let row_id = conn.last_insert_rowid();
+    debug!("Stored UDP probe metric with ID: {}", row_id);
+    Ok(row_id)
+}
+
+/// Load a raw UDP probe metric by row ID
+pub(super) fn load_raw_metric(conn: &Connection, row_id: i64) -> Result<Option<MetricData>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, timestamp, labels, packets_sent, packets_received, packets_reflected, loss_percent, forward_loss_percent, return_loss_percent, duplicate_packets, reordered_packets, avg_rtt_ms, min_rtt_ms, max_rtt_ms, jitter_ms, success, error, host, target_id
+         FROM raw_metric_udp_probe WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        Ok(MetricData {
+            task_name: row.get(0)?,
+            task_type: TaskType::UdpProbe,
+            timestamp: row.get::<_, i64>(1)? as u64,
+            labels: db_labels::read_column(row, 2)?,
+            row_id: Some(row_id),
+            data: RawMetricData::UdpProbe(RawUdpProbeMetric {
+                packets_sent: row.get(3)?,
+                packets_received: row.get(4)?,
+                packets_reflected: row.get(5)?,
+                loss_percent: row.get(6)?,
+                forward_loss_percent: row.get(7)?,
+                return_loss_percent: row.get(8)?,
+                duplicate_packets: row.get(9)?,
+                reordered_packets: row.get(10)?,
+                avg_rtt_ms: row.get(11)?,
+                min_rtt_ms: row.get(12)?,
+                max_rtt_ms: row.get(13)?,
+                jitter_ms: row.get(14)?,
+                success: row.get(15)?,
+                error: row.get(16)?,
+                host: row.get(17)?,
+                target_id: row.get(18)?,
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Generate aggregated UDP probe metrics for a period
+///
+/// Loss is computed over all packets sent in the period, so that runs with
+/// more packets weigh more. The MOS is estimated from the period's average
+/// round-trip time, average jitter and loss.
+pub(super) fn generate_aggregated_metrics(
+    conn: &Connection,
+    task_name: &str,
+    period_start: u64,
+    period_end: u64,
+) -> Result<Option<AggregatedMetrics>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT
+            COUNT(*) as total_count,
+            AVG(CASE WHEN success = 1 THEN avg_rtt_ms END) as avg_rtt,
+            MAX(CASE WHEN success = 1 THEN max_rtt_ms END) as max_rtt,
+            MIN(CASE WHEN success = 1 THEN min_rtt_ms END) as min_rtt,
+            AVG(CASE WHEN success = 1 THEN jitter_ms END) as avg_jitter,
+            MAX(CASE WHEN success = 1 THEN jitter_ms END) as max_jitter,
+            AVG(forward_loss_percent) as avg_forward_loss,
+            AVG(return_loss_percent) as avg_return_loss,
+            SUM(packets_sent) as packets_sent,
+            SUM(packets_received) as packets_received,
+            SUM(duplicate_packets) as duplicate_packets,
+            SUM(reordered_packets) as reordered_packets,
+            SUM(CASE WHEN success = 1 THEN 1 ELSE 0 END) as successful_probes,
+            SUM(CASE WHEN success = 0 THEN 1 ELSE 0 END) as failed_probes,
+            (SELECT host FROM raw_metric_udp_probe
+             WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+             ORDER BY timestamp ASC
+             LIMIT 1) as first_host,
+            (SELECT target_id FROM raw_metric_udp_probe
+             WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+             AND target_id IS NOT NULL
+             ORDER BY timestamp ASC
+             LIMIT 1) as first_target_id
+        FROM raw_metric_udp_probe
+        WHERE task_name = ?1 AND timestamp >= ?2 AND timestamp < ?3
+        "#,
+    )?;
+
+    let row = stmt.query_row(
+        params![task_name, period_start as i64, period_end as i64],
+        |row| {
+            let total_count: i64 = row.get("total_count")?;
+            if total_count == 0 {
+                return Ok(None);
+            }
+
+            let packets_sent: i64 = row.get("packets_sent")?;
+            let packets_received: i64 = row.get("packets_received")?;
+            let loss_percent = if packets_sent > 0 {
+                (packets_sent - packets_received).max(0) as f64 / packets_sent as f64 * 100.0
+            } else {
+                100.0
+            };
+
+            let successful_probes: i64 = row.get("successful_probes")?;
+            let avg_rtt_ms: f64 = row.get("avg_rtt").unwrap_or(0.0);
+            let avg_jitter_ms: f64 = row.get("avg_jitter").unwrap_or(0.0);
+            let mos = if successful_probes > 0 {
+                shared::utils::estimate_mos(avg_rtt_ms, avg_jitter_ms, loss_percent)
+            } else {
+                1.0
+            };
+
+            let host: String = row.get("first_host").unwrap_or_default();
+            let target_id: Option<String> = row.get("first_target_id").ok();
+
+            Ok(Some(AggregatedUdpProbeMetric {
+                avg_rtt_ms,
+                max_rtt_ms: row.get("max_rtt").unwrap_or(0.0),
+                min_rtt_ms: row.get("min_rtt").unwrap_or(0.0),
+                avg_jitter_ms,
+                max_jitter_ms: row.get("max_jitter").unwrap_or(0.0),
+                loss_percent,
+                forward_loss_percent: row.get("avg_forward_loss")?,
+                return_loss_percent: row.get("avg_return_loss")?,
+                packets_sent: packets_sent as u32,
+                packets_received: packets_received as u32,
+                duplicate_packets: row.get::<_, i64>("duplicate_packets")? as u32,
+                reordered_packets: row.get::<_, i64>("reordered_packets")? as u32,
+                mos,
+                successful_probes: successful_probes as u32,
+                failed_probes: row.get::<_, i64>("failed_probes")? as u32,
+                host,
+                target_id,
+            }))
+        },
+    )?;
+
+    if let Some(udp_metric) = row {
+        let total_samples = udp_metric.successful_probes + udp_metric.failed_probes;
+        return Ok(Some(AggregatedMetrics::new(
+            task_name.to_string(),
+            TaskType::UdpProbe,
+            period_start,
+            period_end,
+            total_samples,
+            AggregatedMetricData::UdpProbe(udp_metric),
+        )));
+    }
+
+    Ok(None)
+}
+
+/// Store aggregated UDP probe metrics
+pub(super) fn store_aggregated_metric(
+    conn: &Connection,
+    metrics: &AggregatedMetrics,
+    udp_data: &AggregatedUdpProbeMetric,
+) -> Result<i64> {
+    let labels = db_labels::column_value(&metrics.labels)?;
+    conn.execute(
+        r#"
+        INSERT OR REPLACE INTO agg_metric_udp_probe
+        (task_name, period_start, period_end, sample_count, avg_rtt_ms, max_rtt_ms, min_rtt_ms, avg_jitter_ms, max_jitter_ms, loss_percent, forward_loss_percent, return_loss_percent, packets_sent, packets_received, duplicate_packets, reordered_packets, mos, successful_probes, failed_probes, host, target_id, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27)
+        "#,
+        params![
+            metrics.task_name,
+            metrics.period_start as i64,
+            metrics.period_end as i64,
+            metrics.sample_count,
+            udp_data.avg_rtt_ms,
+            udp_data.max_rtt_ms,
+            udp_data.min_rtt_ms,
+            udp_data.avg_jitter_ms,
+            udp_data.max_jitter_ms,
+            udp_data.loss_percent,
+            udp_data.forward_loss_percent,
+            udp_data.return_loss_percent,
+            udp_data.packets_sent,
+            udp_data.packets_received,
+            udp_data.duplicate_packets,
+            udp_data.reordered_packets,
+            udp_data.mos,
+            udp_data.successful_probes,
+            udp_data.failed_probes,
+            udp_data.host,
+            udp_data.target_id,
+            metrics.maintenance,
+            metrics.adaptive,
+            metrics.upstream_down,
+            metrics.avg_queue_wait_ms,
+            metrics.max_queue_wait_ms,
+            labels,
+        ],
+    )?;
+    Ok(conn.last_insert_rowid())
+}
+
+/// Load aggregated UDP probe metric by row ID
+pub(super) fn load_aggregated_metric(
+    conn: &Connection,
+    row_id: i64,
+) -> Result<Option<AggregatedMetrics>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, period_start, period_end, sample_count,
+                avg_rtt_ms, max_rtt_ms, min_rtt_ms, avg_jitter_ms, max_jitter_ms,
+                loss_percent, forward_loss_percent, return_loss_percent,
+                packets_sent, packets_received, duplicate_packets, reordered_packets,
+                mos, successful_probes, failed_probes, host, target_id, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels
+         FROM agg_metric_udp_probe WHERE id = ?1",
+    )?;
+
+    let result = stmt.query_row(params![row_id], |row| {
+        Ok(AggregatedMetrics {
+            task_name: row.get(0)?,
+            task_type: TaskType::UdpProbe,
+            period_start: row.get::<_, i64>(1)? as u64,
+            period_end: row.get::<_, i64>(2)? as u64,
+            sample_count: row.get(3)?,
+            maintenance: row.get(21)?,
+            adaptive: row.get(22)?,
+            upstream_down: row.get(23)?,
+            avg_queue_wait_ms: row.get(24)?,
+            max_queue_wait_ms: row.get(25)?,
+            labels: db_labels::read_column(row, 26)?,
+            data: AggregatedMetricData::UdpProbe(AggregatedUdpProbeMetric {
+                avg_rtt_ms: row.get(4)?,
+                max_rtt_ms: row.get(5)?,
+                min_rtt_ms: row.get(6)?,
+                avg_jitter_ms: row.get(7)?,
+                max_jitter_ms: row.get(8)?,
+                loss_percent: row.get(9)?,
+                forward_loss_percent: row.get(10)?,
+                return_loss_percent: row.get(11)?,
+                packets_sent: row.get(12)?,
+                packets_received: row.get(13)?,
+                duplicate_packets: row.get(14)?,
+                reordered_packets: row.get(15)?,
+                mos: row.get(16)?,
+                successful_probes: row.get(17)?,
+                failed_probes: row.get(18)?,
+                host: row.get(19).unwrap_or_default(),
+                target_id: row.get(20).ok(),
+            }),
+        })
+    });
+
+    match result {
+        Ok(metric) => Ok(Some(metric)),
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Clean up old UDP probe metrics
+pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<(usize, usize)> {
+    let raw_deleted = conn.execute(
+        r#"
+        DELETE FROM raw_metric_udp_probe
+        WHERE timestamp < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'raw_udp_probe' AND status != 'sent'
+          )
+        "#,
+        params![cutoff_time],
+    )?;
+
+    let agg_deleted = conn.execute(
+        r#"
+        DELETE FROM agg_metric_udp_probe
+        WHERE period_end < ?1
+          AND id NOT IN (
+              SELECT metric_row_id FROM metric_send_queue
+              WHERE metric_type = 'udp_probe' AND status != 'sent'
+          )
+        "#,
+        params![cutoff_time],
+    )?;
+
+    Ok((raw_deleted, agg_deleted))
+}
@@ -0,0 +1,166 @@
+//! Host facts reported to the server with each metrics push
+//!
+//! The agent reports its hostname, operating system, kernel release, interface
+//! addresses, default gateway and (optionally) public IP address so that the
+//! server can keep an inventory of where each agent runs and notice when its
+//! network attachment changes.
+//!
+//! Kernel release and default gateway are read from `/proc` and are only known
+//! on Linux. So are the flags of IPv6 addresses: temporary (privacy extension)
+//! and deprecated addresses rotate every few hours and are left out of the
+//! interface addresses on Linux, so they do not show up as inventory changes.
+//! Other platforms report them.
+//!
+//! The public IP is looked up through `public_ip_url` in agent.toml, a service
+//! answering with the caller's address as plain text.
+
+use anyhow::{Context, Result};
+use shared::api::{HostFacts, InterfaceAddress};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use tracing::warn;
+
+/// Collects the local host facts. The public IP is left to [`lookup_public_ip`].
+pub fn collect() -> HostFacts {
+    HostFacts {
+        hostname: gethostname::gethostname().to_string_lossy().into_owned(),
+        os: os_name(),
+        kernel: kernel_release(),
+        interfaces: interface_addresses(),
+        default_gateway: default_gateway().map(|gateway| gateway.to_string()),
+        public_ip: None,
+    }
+}
+
+/// Looks up the agent's public (egress) IP address through `url`
+pub async fn lookup_public_ip(client: &reqwest::Client, url: &str) -> Result<IpAddr> {
+    let response = client
+        .get(url)
+        .send()
+        .await
+        .with_context(|| format!("Failed to query public IP from {}", url))?
+        .error_for_status()?;
+    let body = response.text().await?;
+    body.trim()
+        .parse()
+        .with_context(|| format!("Invalid public IP address returned by {}", url))
+}
+
+/// Operating system name from os-release, falling back to the platform name
+fn os_name() -> String {
+    std::fs::read_to_string("/etc/os-release")
+        .ok()
+        .and_then(|content| parse_os_release(&content))
+        .unwrap_or_else(|| std::env::consts::OS.to_string())
+}
+
+/// Kernel release on Linux
+fn kernel_release() -> Option<String> {
+    std::fs::read_to_string("/proc/sys/kernel/osrelease")
+        .ok()
+        .map(|release| release.trim().to_string())
+        .filter(|release| !release.is_empty())
+}
+
+/// IPv4 default gateway on Linux
+fn default_gateway() -> Option<Ipv4Addr> {
+    std::fs::read_to_string("/proc/net/route")
+        .ok()
+        .and_then(|content| parse_default_gateway(&content))
+}
+
+/// IPv6 addresses that are temporary or deprecated on Linux
+fn unstable_ipv6_addresses() -> Vec<Ipv6Addr> {
+    std::fs::read_to_string("/proc/net/if_inet6")
+        .map(|content| parse_unstable_ipv6_addresses(&content))
+        .unwrap_or_default()
+}
+
+/// Addresses of all non-loopback interfaces, except IPv6 link-local, temporary
+/// and deprecated ones
+fn interface_addresses() -> Vec<InterfaceAddress> {
+    let interfaces = match if_addrs::get_if_addrs() {
+        Ok(interfaces) => interfaces,
+        Err(e) => {
+            warn!("Failed to list network interfaces: {}", e);
+            return Vec::new();
+        }
+    };
+    let unstable = unstable_ipv6_addresses();
+
+    let mut addresses: Vec<InterfaceAddress> = interfaces
+        .into_iter()
+        .filter(|interface| {
+            !interface.is_loopback()
+                && match interface.ip() {
+                    IpAddr::V4(_) => true,
+                    IpAddr::V6(ip) => !interface.is_link_local() && !unstable.contains(&ip),
+                }
+        })
+        .map(|interface| {
+            let prefix_len = match &interface.addr {
+                if_addrs::IfAddr::V4(addr) => addr.prefixlen,
+                if_addrs::IfAddr::V6(addr) => addr.prefixlen,
+            };
+            InterfaceAddress {
+                address: interface.ip().to_string(),
+                name: interface.name,
+                prefix_len,
+            }
+        })
+        .collect();
+    // Sorted so that unchanged interfaces compare equal between reports
+    addresses.sort();
+    addresses
+}
+
+/// Extracts `PRETTY_NAME` (or `NAME`) from the contents of an os-release file
+pub fn parse_os_release(content: &str) -> Option<String> {
+    let value = |key: &str| {
+        content.lines().find_map(|line| {
+            line.strip_prefix(key)
+                .and_then(|rest| rest.strip_prefix('='))
+                .map(|value| value.trim().trim_matches('"').to_string())
+                .filter(|value| !value.is_empty())
+        })
+    };
+    value("PRETTY_NAME").or_else(|| value("NAME"))
+}
+
+/// Extracts the temporary and deprecated addresses from the contents of `/proc/net/if_inet6`
+///
+/// Each line holds the address as 32 hexadecimal digits, the interface index,
+/// prefix length, scope and flags (hexadecimal) and the interface name.
+pub fn parse_unstable_ipv6_addresses(if_inet6: &str) -> Vec<Ipv6Addr> {
+    // IFA_F_TEMPORARY and IFA_F_DEPRECATED from linux/if_addr.h
+    const UNSTABLE_FLAGS: u32 = 0x01 | 0x20;
+
+    if_inet6
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 6 {
+                return None;
+            }
+            let flags = u32::from_str_radix(fields[4], 16).ok()?;
+            if flags & UNSTABLE_FLAGS == 0 {
+                return None;
+            }
+            u128::from_str_radix(fields[0], 16).ok().map(Ipv6Addr::from)
+        })
+        .collect()
+}
+
+/// Extracts the default gateway from the contents of `/proc/net/route`
+///
+/// The table holds addresses as little-endian hexadecimal; the default route is
+/// the one with destination and mask 0. The first default route listed wins.
+pub fn parse_default_gateway(route_table: &str) -> Option<Ipv4Addr> {
+    route_table.lines().skip(1).find_map(|line| {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 8 || fields[1] != "00000000" || fields[7] != "00000000" {
+            return None;
+        }
+        let gateway = u32::from_str_radix(fields[2], 16).ok()?;
+        (gateway != 0).then(|| Ipv4Addr::from(gateway.to_le_bytes()))
+    })
+}
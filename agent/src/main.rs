@@ -24,33 +24,47 @@ use std::path::PathBuf;
 use tracing::{debug, error, info, warn};
 
 // The agent is organized into several modules, each with a distinct responsibility.
+mod bandwidth_reflector;
 mod config;
 mod database;
+mod host_facts;
+mod ntp_time;
 mod scheduler;
+mod secrets;
+#[cfg(feature = "snmp-tasks")]
+mod snmp_trap;
 mod task_bandwidth;
 mod task_dns;
 mod task_http;
 mod task_http_content;
+mod task_ntp;
 mod task_ping;
 #[cfg(feature = "snmp-tasks")]
 mod task_snmp;
 #[cfg(feature = "sql-tasks")]
 mod task_sql;
 mod task_tcp;
+mod task_tcp_sweep;
 mod task_tls;
+mod task_twamp;
+mod task_udp;
 mod tasks;
 #[cfg(test)]
 mod tests;
+mod twamp_responder;
+mod udp_reflector;
+mod udp_socket;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
 use config::ConfigManager;
 use database::AgentDatabase;
-use scheduler::TaskScheduler;
+use scheduler::{SchedulerOptions, TaskScheduler};
 use shared::api::{
-    endpoints, headers, ConfigUploadRequest, ConfigUploadResponse, MetricsRequest, MetricsResponse,
+    endpoints, headers, ConfigUploadRequest, ConfigUploadResponse, HostFacts, MetricsRequest,
+    MetricsResponse,
 };
-use shared::metrics::AggregatedMetrics;
+use shared::metrics::{AggregatedMetrics, MetricData};
 
 /// Command-line arguments for the agent
 #[derive(Parser, Debug)]
@@ -84,6 +98,65 @@ struct CliArgs {
     /// Override the local-only mode flag from config file
     #[arg(long = "local-only", value_name = "BOOL")]
     local_only: Option<bool>,
+
+    /// Store a secret in the encrypted secrets store (value is read from stdin) and exit
+    #[arg(long = "set-secret", value_name = "NAME")]
+    set_secret: Option<String>,
+
+    /// Remove a secret from the encrypted secrets store and exit
+    #[arg(long = "remove-secret", value_name = "NAME")]
+    remove_secret: Option<String>,
+
+    /// List the names of stored secrets and exit
+    #[arg(long = "list-secrets")]
+    list_secrets: bool,
+}
+
+/// Returns the data directory, which is expected to be a sibling of the config directory.
+fn data_dir_for(config_dir: &std::path::Path) -> PathBuf {
+    config_dir
+        .parent()
+        .map(|p| p.join("data"))
+        .unwrap_or_else(|| PathBuf::from("./data"))
+}
+
+/// Handles the secrets store management options.
+///
+/// Returns `Ok(true)` if a secrets command was run and the agent should exit.
+fn run_secrets_command(cli_args: &CliArgs) -> Result<bool> {
+    let store = secrets::SecretStore::new(data_dir_for(&cli_args.config_dir));
+
+    if let Some(name) = &cli_args.set_secret {
+        let mut value = String::new();
+        std::io::stdin()
+            .read_line(&mut value)
+            .context("Failed to read secret value from stdin")?;
+        let value = value.trim_end_matches(['\n', '\r']);
+        if value.is_empty() {
+            anyhow::bail!("Secret value read from stdin is empty");
+        }
+        store.set(name, value)?;
+        println!(
+            "Stored secret '{}'; reference it as \"secret:{}\"",
+            name, name
+        );
+        return Ok(true);
+    }
+    if let Some(name) = &cli_args.remove_secret {
+        if store.remove(name)? {
+            println!("Removed secret '{}'", name);
+        } else {
+            println!("Secret '{}' not found", name);
+        }
+        return Ok(true);
+    }
+    if cli_args.list_secrets {
+        for name in store.names()? {
+            println!("{}", name);
+        }
+        return Ok(true);
+    }
+    Ok(false)
 }
 
 /// The main application structure for the agent.
@@ -101,6 +174,18 @@ pub struct Agent {
     last_data_cleanup: u64,
     /// The last time HTTP clients were refreshed (as Unix timestamp)
     last_client_refresh: u64,
+    /// Host facts reported with each metrics push (None until first collected)
+    host_facts: Option<HostFacts>,
+    /// The last time host facts were collected (as Unix timestamp)
+    last_host_facts_refresh: u64,
+    /// SNMP task hosts used to attribute received traps, refreshed on config updates
+    #[cfg(feature = "snmp-tasks")]
+    snmp_trap_sources: Arc<RwLock<Vec<snmp_trap::TrapSource>>>,
+    /// Events from the SNMP trap receiver waiting to be stored (None if disabled)
+    #[cfg(feature = "snmp-tasks")]
+    snmp_trap_events: Option<tokio::sync::mpsc::Receiver<AggregatedMetrics>>,
+    /// Encrypted store for `secret:` references in task credentials
+    secret_store: secrets::SecretStore,
 }
 
 impl Agent {
@@ -111,11 +196,9 @@ impl Agent {
         info!("Starting Network Monitoring Agent");
 
         // The data directory is expected to be a sibling of the config directory.
-        let data_dir = config_dir
-            .parent()
-            .map(|p| p.join("data"))
-            .unwrap_or_else(|| PathBuf::from("./data"));
+        let data_dir = data_dir_for(&config_dir);
         info!("Data directory: {}", data_dir.display());
+        let secret_store = secrets::SecretStore::new(&data_dir);
 
         let mut config_manager = ConfigManager::new(config_dir)?;
         // Load the initial configuration first to get database timeout
@@ -126,6 +209,8 @@ impl Agent {
             .as_ref()
             .expect("Agent configuration not loaded. Call load_config() first.");
 
+        // AgentDatabase owns a rusqlite connection, which is Send but not Sync
+        #[allow(clippy::arc_with_non_send_sync)]
         let database = Arc::new(RwLock::new(AgentDatabase::new(
             data_dir,
             agent_config.database_busy_timeout_seconds,
@@ -177,31 +262,67 @@ impl Agent {
         // Create shutdown channel
         let (shutdown_tx, _) = tokio::sync::broadcast::channel(1);
 
-        // Extract server config for bandwidth tests (None if local-only mode)
-        let (server_url, api_key, agent_id) = if !agent_config.local_only {
-            (
-                Some(agent_config.central_server_url.clone()),
-                Some(agent_config.api_key.clone()),
-                Some(agent_config.agent_id.clone()),
-            )
-        } else {
-            (None, None, None)
-        };
-
-        // Create and start the task scheduler
+        // Create and start the task scheduler (server settings are None in local-only mode)
         let mut task_scheduler = TaskScheduler::new(
             tasks_config.clone(),
             database.clone(),
-            agent_config.metrics_flush_interval_seconds,
-            agent_config.graceful_shutdown_timeout_seconds,
-            agent_config.channel_buffer_size,
-            agent_config.queue_cleanup_interval_seconds,
-            server_url,
-            api_key,
-            agent_id,
-        )?;
+            SchedulerOptions::from_agent_config(agent_config, secret_store.clone()),
+        )?
+        .with_concurrency_limits(
+            agent_config.max_concurrent_tasks,
+            agent_config.max_concurrent_per_type.clone(),
+        );
         task_scheduler.start().await?;
 
+        // Start the SNMP trap receiver if configured
+        #[cfg(feature = "snmp-tasks")]
+        let snmp_trap_sources =
+            Arc::new(RwLock::new(snmp_trap::resolve_trap_sources(tasks_config)));
+        #[cfg(feature = "snmp-tasks")]
+        let snmp_trap_events = match &agent_config.snmp_trap_receiver {
+            Some(trap_config) => {
+                let (event_sender, event_receiver) =
+                    tokio::sync::mpsc::channel(agent_config.channel_buffer_size);
+                let receiver = snmp_trap::SnmpTrapReceiver::new(
+                    trap_config.clone(),
+                    &agent_config.agent_id,
+                    snmp_trap_sources.clone(),
+                    event_sender,
+                )?;
+                let socket = receiver.bind().await?;
+                tokio::spawn(receiver.run(socket, shutdown_tx.subscribe()));
+                Some(event_receiver)
+            }
+            None => None,
+        };
+
+        // Start the bandwidth reflector if configured
+        if let Some(reflector_config) = &agent_config.bandwidth_reflector {
+            let api_key = match &reflector_config.api_key {
+                Some(key) => secrets::resolve_secret(key, Some(&secret_store))
+                    .context("Failed to resolve bandwidth reflector API key")?,
+                None => agent_config.api_key.clone(),
+            };
+            let reflector =
+                bandwidth_reflector::BandwidthReflector::new(reflector_config, api_key)?;
+            let listener = reflector.bind().await?;
+            tokio::spawn(reflector.run(listener, shutdown_tx.subscribe()));
+        }
+
+        // Start the UDP reflector if configured
+        if let Some(reflector_config) = &agent_config.udp_reflector {
+            let reflector = udp_reflector::UdpReflector::new(reflector_config)?;
+            let socket = reflector.bind().await?;
+            tokio::spawn(reflector.run(socket, shutdown_tx.subscribe()));
+        }
+
+        // Start the TWAMP-Light responder if configured
+        if let Some(responder_config) = &agent_config.twamp_responder {
+            let responder = twamp_responder::TwampResponder::new(responder_config)?;
+            let socket = responder.bind().await?;
+            tokio::spawn(responder.run(socket, shutdown_tx.subscribe()));
+        }
+
         Ok(Self {
             config_manager,
             database,
@@ -210,6 +331,13 @@ impl Agent {
             last_metrics_send: 0,
             last_data_cleanup: 0,
             last_client_refresh: 0,
+            host_facts: None,
+            last_host_facts_refresh: 0,
+            #[cfg(feature = "snmp-tasks")]
+            snmp_trap_sources,
+            #[cfg(feature = "snmp-tasks")]
+            snmp_trap_events,
+            secret_store,
         })
     }
 
@@ -263,18 +391,32 @@ impl Agent {
                 scheduler.cleanup_sent_queue_if_needed().await?;
             }
 
+            self.refresh_host_facts_if_needed(&http_client).await;
             self.send_metrics_if_needed(&http_client).await?;
             self.cleanup_data_if_needed().await?;
             self.refresh_clients_if_needed().await?;
 
-            // Main event selection - only task execution events
+            // The trap receiver runs in its own task and cannot hold the database,
+            // so accepted notifications are stored here as soon as they arrive
+            #[cfg(feature = "snmp-tasks")]
+            let trap_events = self.snmp_trap_events.as_mut();
+            #[cfg(not(feature = "snmp-tasks"))]
+            let trap_events = None;
+
+            // Main event selection - task execution events and received SNMP traps
             if let Some(scheduler) = self.task_scheduler.as_mut() {
                 tokio::select! {
                     Some(result) = scheduler.result_receiver.recv() => {
                         scheduler.handle_task_result(result).await?;
                     },
-                    Some(task_name) = scheduler.ready_receiver.recv() => {
-                        scheduler.execute_single_task(&task_name).await?;
+                    Some(tick) = scheduler.ready_receiver.recv() => {
+                        scheduler.execute_single_task(&tick.task_name, tick.scheduled_at).await?;
+                    },
+                    event = next_snmp_trap_event(trap_events) => {
+                        let mut db = self.database.write().await;
+                        if let Err(e) = db.store_and_enqueue_aggregated_metrics(&event).await {
+                            error!("Failed to store SNMP trap event: {}", e);
+                        }
                     },
                 }
             }
@@ -290,6 +432,7 @@ impl Agent {
         client: &reqwest::Client,
         agent_config: &shared::config::AgentConfig,
         config_checksum: &str,
+        host_facts: Option<&HostFacts>,
     ) -> bool {
         let batch_size = agent_config.metrics_batch_size;
         let max_retries = agent_config.metrics_max_retries as i32;
@@ -305,6 +448,16 @@ impl Agent {
         };
 
         if queued_metrics.is_empty() {
+            // Nothing holds back the raw measurements
+            drop(db);
+            Self::send_queued_raw_metrics_static(
+                scheduler,
+                client,
+                agent_config,
+                config_checksum,
+                host_facts,
+            )
+            .await;
             return false;
         }
 
@@ -323,7 +476,16 @@ impl Agent {
         drop(db);
 
         // Attempt to send
-        match Self::send_metrics_batch(client, agent_config, config_checksum, &metrics).await {
+        match Self::send_metrics_batch(
+            client,
+            agent_config,
+            config_checksum,
+            &metrics,
+            &[],
+            host_facts,
+        )
+        .await
+        {
             Ok(config_status) => {
                 // Success! Mark as sent
                 let mut db = scheduler.database.write().await;
@@ -332,6 +494,18 @@ impl Agent {
                 } else {
                     info!("Successfully sent {} metrics to server", metrics.len());
                 }
+                drop(db);
+
+                // Raw measurements follow in their own requests, so they can
+                // never make the server refuse the aggregates
+                Self::send_queued_raw_metrics_static(
+                    scheduler,
+                    client,
+                    agent_config,
+                    config_checksum,
+                    host_facts,
+                )
+                .await;
 
                 // Check if config needs update
                 if config_status == shared::api::ConfigStatus::Stale {
@@ -360,6 +534,110 @@ impl Agent {
         }
     }
 
+    /// Sends queued raw measurements of `upload_raw` tasks to the central server
+    ///
+    /// One batch of up to `raw_metrics_batch_max_size_kb` is taken from the queue
+    /// per push. A batch the server refuses as too large is split in halves, and a
+    /// single measurement it refuses is dropped rather than retried.
+    async fn send_queued_raw_metrics_static(
+        scheduler: &mut TaskScheduler,
+        client: &reqwest::Client,
+        agent_config: &shared::config::AgentConfig,
+        config_checksum: &str,
+        host_facts: Option<&HostFacts>,
+    ) {
+        let max_bytes = agent_config.raw_metrics_batch_max_size_kb * 1024;
+        let max_retries = agent_config.metrics_max_retries as i32;
+
+        let mut db = scheduler.database.write().await;
+        let queued_raw_metrics = match db.get_raw_metrics_to_send(max_bytes).await {
+            Ok(metrics) => metrics,
+            Err(e) => {
+                error!("Failed to fetch raw metrics from queue: {}", e);
+                return;
+            }
+        };
+
+        if queued_raw_metrics.is_empty() {
+            return;
+        }
+
+        let queue_ids: Vec<i64> = queued_raw_metrics.iter().map(|m| m.queue_id).collect();
+        if let Err(e) = db.mark_as_sending(&queue_ids).await {
+            warn!("Failed to mark raw metrics as sending: {}", e);
+        }
+        drop(db);
+
+        // Batches still to send, the next one last
+        let mut batches = vec![queued_raw_metrics];
+        while let Some(batch) = batches.pop() {
+            let queue_ids: Vec<i64> = batch.iter().map(|m| m.queue_id).collect();
+            let raw_metrics: Vec<MetricData> = batch.iter().map(|m| m.metric.clone()).collect();
+
+            debug!(
+                "Attempting to send {} raw metrics to server",
+                raw_metrics.len()
+            );
+
+            let result = Self::send_metrics_batch(
+                client,
+                agent_config,
+                config_checksum,
+                &[],
+                &raw_metrics,
+                host_facts,
+            )
+            .await;
+
+            let mut db = scheduler.database.write().await;
+            match result {
+                Ok(_) => {
+                    if let Err(e) = db.mark_as_sent(&queue_ids).await {
+                        error!("Failed to mark raw metrics as sent: {}", e);
+                    } else {
+                        info!(
+                            "Successfully sent {} raw metrics to server",
+                            raw_metrics.len()
+                        );
+                    }
+                }
+                Err(e) if is_payload_too_large(&e) && batch.len() > 1 => {
+                    warn!(
+                        "Server refused {} raw metrics as too large, splitting the batch",
+                        batch.len()
+                    );
+                    let mut first_half = batch;
+                    let second_half = first_half.split_off(first_half.len() / 2);
+                    batches.push(second_half);
+                    batches.push(first_half);
+                }
+                Err(e) if is_payload_too_large(&e) => {
+                    // The server will never accept this measurement, do not retry it
+                    warn!(
+                        "Server refused the raw metric of task '{}' as too large, dropping it",
+                        raw_metrics[0].task_name
+                    );
+                    if let Err(e) = db.mark_as_failed(queue_ids[0], &e.to_string(), 0).await {
+                        error!("Failed to update queue entry {}: {}", queue_ids[0], e);
+                    }
+                }
+                Err(e) => {
+                    // Failed - mark this and the remaining batches for retry
+                    warn!("Failed to send raw metrics: {}", e);
+                    let remaining_ids = batches.drain(..).flatten().map(|m| m.queue_id);
+                    for queue_id in queue_ids.into_iter().chain(remaining_ids) {
+                        if let Err(e) = db
+                            .mark_as_failed(queue_id, &e.to_string(), max_retries)
+                            .await
+                        {
+                            error!("Failed to update queue entry {}: {}", queue_id, e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
     /// Sends aggregated metrics to the central server
     pub async fn send_metrics_to_server(&self, metrics: Vec<AggregatedMetrics>) -> Result<()> {
         let agent_config = self
@@ -382,7 +660,10 @@ impl Agent {
                 .get_tasks_config_hash()
                 .expect("Failed to calculate tasks config hash"),
             metrics,
+            raw_metrics: Vec::new(),
             agent_version: Some(AGENT_VERSION.to_string()),
+            agent_labels: agent_config.labels.clone(),
+            host_facts: self.host_facts.clone(),
         };
 
         let url = format!("{}{}", agent_config.central_server_url, endpoints::METRICS);
@@ -556,6 +837,7 @@ impl Agent {
                         client,
                         agent_config,
                         &config_checksum,
+                        self.host_facts.as_ref(),
                     )
                     .await
                 } else {
@@ -685,32 +967,29 @@ impl Agent {
                         .expect("Tasks config should be loaded after update")
                         .clone();
 
+                    // Re-resolve trap sources so notifications follow renamed or moved tasks
+                    #[cfg(feature = "snmp-tasks")]
+                    {
+                        *self.snmp_trap_sources.write().await =
+                            snmp_trap::resolve_trap_sources(&new_tasks_config);
+                    }
+
                     // Stop current scheduler
                     scheduler.stop().await?;
 
-                    // Extract server config for bandwidth tests (None if local-only mode)
-                    let (server_url, api_key, agent_id) = if !agent_config.local_only {
-                        (
-                            Some(agent_config.central_server_url.clone()),
-                            Some(agent_config.api_key.clone()),
-                            Some(agent_config.agent_id.clone()),
-                        )
-                    } else {
-                        (None, None, None)
-                    };
-
                     // Create new scheduler with new config
                     let new_scheduler = TaskScheduler::new(
                         new_tasks_config,
                         scheduler.database.clone(),
-                        agent_config.metrics_flush_interval_seconds,
-                        agent_config.graceful_shutdown_timeout_seconds,
-                        agent_config.channel_buffer_size,
-                        agent_config.queue_cleanup_interval_seconds,
-                        server_url,
-                        api_key,
-                        agent_id,
-                    )?;
+                        SchedulerOptions::from_agent_config(
+                            &agent_config,
+                            self.secret_store.clone(),
+                        ),
+                    )?
+                    .with_concurrency_limits(
+                        agent_config.max_concurrent_tasks,
+                        agent_config.max_concurrent_per_type.clone(),
+                    );
 
                     *scheduler = new_scheduler;
                     scheduler.start().await?;
@@ -821,6 +1100,55 @@ impl Agent {
         Ok(())
     }
 
+    /// Collects host facts if the configured refresh interval has passed.
+    ///
+    /// Facts are only sent to the server, so nothing is collected in local-only
+    /// mode. If the public IP lookup fails, the previously seen address is kept
+    /// so that a transient lookup error is not reported as an egress change.
+    async fn refresh_host_facts_if_needed(&mut self, http_client: &Option<reqwest::Client>) {
+        let Some(client) = http_client else {
+            return;
+        };
+        let agent_config = self
+            .config_manager
+            .agent_config
+            .as_ref()
+            .expect("Agent configuration not loaded");
+
+        let current_time = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+
+        if current_time
+            < self.last_host_facts_refresh + agent_config.host_facts_refresh_interval_seconds
+        {
+            return;
+        }
+
+        let mut facts = host_facts::collect();
+        if let Some(url) = &agent_config.public_ip_url {
+            facts.public_ip = match host_facts::lookup_public_ip(client, url).await {
+                Ok(ip) => Some(ip.to_string()),
+                Err(e) => {
+                    warn!("Failed to look up public IP: {:#}", e);
+                    self.host_facts
+                        .as_ref()
+                        .and_then(|previous| previous.public_ip.clone())
+                }
+            };
+        }
+        debug!(
+            hostname = %facts.hostname,
+            interfaces = facts.interfaces.len(),
+            public_ip = ?facts.public_ip,
+            "Host facts collected"
+        );
+
+        self.host_facts = Some(facts);
+        self.last_host_facts_refresh = current_time;
+    }
+
     /// Performs a graceful shutdown of the agent.
     /// This method is called when a shutdown signal is received. It should ensure
     /// that any pending data is saved and resources are released cleanly.
@@ -863,6 +1191,8 @@ impl Agent {
         agent_config: &shared::config::AgentConfig,
         config_checksum: &str,
         metrics: &[AggregatedMetrics],
+        raw_metrics: &[MetricData],
+        host_facts: Option<&HostFacts>,
     ) -> Result<shared::api::ConfigStatus> {
         use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -876,7 +1206,10 @@ impl Agent {
             timestamp_utc: timestamp.to_string(),
             config_checksum: config_checksum.to_string(),
             metrics: metrics.to_vec(),
+            raw_metrics: raw_metrics.to_vec(),
             agent_version: Some(AGENT_VERSION.to_string()),
+            agent_labels: agent_config.labels.clone(),
+            host_facts: host_facts.cloned(),
         };
 
         let url = format!("{}{}", agent_config.central_server_url, endpoints::METRICS);
@@ -898,11 +1231,11 @@ impl Agent {
                 .await
                 .unwrap_or_else(|_| "Unknown error".to_string());
 
-            return Err(anyhow::anyhow!(
-                "Server returned {}: {}",
+            return Err(ServerError {
                 status,
-                error_text
-            ));
+                message: error_text,
+            }
+            .into());
         }
 
         // Parse the response to get config status
@@ -915,6 +1248,37 @@ impl Agent {
     }
 }
 
+/// Error status returned by the server for a metrics push
+#[derive(thiserror::Error, Debug)]
+#[error("Server returned {status}: {message}")]
+struct ServerError {
+    status: reqwest::StatusCode,
+    message: String,
+}
+
+/// Whether a metrics push failed because the request body was too large
+fn is_payload_too_large(error: &anyhow::Error) -> bool {
+    error
+        .downcast_ref::<ServerError>()
+        .is_some_and(|e| e.status == reqwest::StatusCode::PAYLOAD_TOO_LARGE)
+}
+
+/// Waits for the next event from the SNMP trap receiver.
+///
+/// Never completes when no receiver is running or it has stopped, so the main loop
+/// keeps waiting on task events alone.
+async fn next_snmp_trap_event(
+    receiver: Option<&mut tokio::sync::mpsc::Receiver<AggregatedMetrics>>,
+) -> AggregatedMetrics {
+    match receiver {
+        Some(receiver) => match receiver.recv().await {
+            Some(event) => event,
+            None => std::future::pending().await,
+        },
+        None => std::future::pending().await,
+    }
+}
+
 // The `#[tokio::main]` attribute transforms the `async fn main` into a synchronous
 // `fn main` that initializes a tokio runtime and runs the async code.
 #[tokio::main]
@@ -941,6 +1305,11 @@ async fn main() -> Result<()> {
     // Parse command-line arguments
     let cli_args = CliArgs::parse();
 
+    // Secrets store management runs instead of the agent
+    if run_secrets_command(&cli_args)? {
+        return Ok(());
+    }
+
     info!("Network Monitoring Agent starting up");
     info!("Configuration directory: {}", cli_args.config_dir.display());
 
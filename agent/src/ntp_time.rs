@@ -0,0 +1,24 @@
+//! NTP timestamp helpers
+//!
+//! NTP timestamps are 64-bit values with 32 bits of seconds since 1900 and a
+//! 32-bit fraction. They are used on the wire by both TWAMP-Light and SNTP.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seconds between the NTP epoch (1900) and the Unix epoch (1970)
+const NTP_UNIX_OFFSET_SECONDS: u64 = 2_208_988_800;
+
+/// Returns the current time as a 64-bit NTP timestamp (32-bit seconds, 32-bit fraction)
+pub fn ntp_timestamp_now() -> u64 {
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let seconds = since_epoch.as_secs() + NTP_UNIX_OFFSET_SECONDS;
+    let fraction = (since_epoch.subsec_nanos() as u64) * (1u64 << 32) / 1_000_000_000;
+    (seconds << 32) | fraction
+}
+
+/// Difference `later - earlier` between two NTP timestamps in milliseconds
+pub fn ntp_difference_ms(later: u64, earlier: u64) -> f64 {
+    later.wrapping_sub(earlier) as i64 as f64 * 1000.0 / (1u64 << 32) as f64
+}
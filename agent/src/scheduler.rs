@@ -17,7 +17,11 @@
         -   `name`: The unique identifier for the task.
         -   `config`: A copy of the task's specific configuration (`TaskConfig`).
         -   `interval`: A `tokio::time::Interval` that fires whenever the task is due to be run, based on its schedule.
-        -   `is_running`: A boolean flag to prevent task overruns. If a task is still running when its next scheduled time arrives, the new execution is skipped.
+        -   `is_running`: A boolean flag to prevent task overruns. If a task is still running when its next scheduled time arrives, the new execution is skipped and counted as a skipped overrun.
+        -   `queued_at` / `due_at`: When the task started waiting for a concurrency slot, and when the waiting run was scheduled to start.
+        -   `executions`: Started, skipped, suppressed and timed-out runs since the last aggregation, summarized into a `TaskExecutionMetric` per period.
+        -   `last_failed` / `upstream_down_since` / `upstream_down_until`: Whether dependents of the task are suppressed (`depends_on`).
+        -   `adaptive` / `adaptive_sender`: Whether the ticker runs on the faster `on_failure_schedule_seconds` schedule.
 
     3.  `SchedulerState`: A simple enum (`Stopped`, `Starting`, `Running`, `Stopping`) to manage the lifecycle of the scheduler in a clear and predictable way.
 
@@ -28,7 +32,7 @@
     The intended lifecycle and flow of control are as follows:
 
     1.  **Initialization**:
-        -   `TaskScheduler::new(config, database)` is the entry point. It's called once when the agent starts.
+        -   `TaskScheduler::new(config, database, options)` is the entry point. It's called once when the agent starts.
         -   It creates the MPSC channel for results and initializes the `TaskExecutor`.
         -   The scheduler starts in the `SchedulerState::Stopped` state.
 
@@ -49,7 +53,8 @@
         -   **Execute Tasks**: For each ready task, it calls `execute_single_task`.
 
     5.  **Task Execution (`execute_single_task`)**:
-        -   It finds the corresponding `TaskHandle` and marks `is_running = true`.
+        -   Due tasks are not started directly: they are appended to the `pending` queue, and `dispatch_pending_tasks` starts them highest `priority` first as long as `max_concurrent_tasks` and `max_concurrent_per_type` allow. Every finished task frees its slot and triggers another dispatch.
+        -   Starting a task marks its `TaskHandle` with `is_running = true`.
         -   Crucially, it **spawns a new asynchronous Tokio task** to perform the actual work. This is vital to prevent a single long-running task from blocking the entire scheduler loop.
         -   Inside this new task, it would typically call `self.task_executor.execute(...)` (currently a placeholder `tokio::time::sleep` is used).
         -   The result of the execution is sent back to the scheduler via the `result_sender`.
@@ -83,18 +88,80 @@
 // tasks that can be updated at runtime.
 
 use anyhow::Result;
-use shared::config::{TaskConfig, TaskType, TasksConfig};
-use shared::metrics::MetricData;
-use std::collections::HashMap;
+use shared::config::{AgentConfig, TaskConfig, TaskType, TasksConfig};
+use shared::metrics::{AggregatedMetricData, AggregatedMetrics, MetricData, TaskExecutionMetric};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
-use tokio::sync::{mpsc, RwLock};
+use tokio::sync::{mpsc, watch, RwLock};
 use tokio::time::{Duration, Instant};
 use tracing::{debug, info, warn};
 
 use crate::database::AgentDatabase;
+use crate::secrets::SecretStore;
 use crate::tasks::TaskExecutor;
 
+/// Runs starting later than this after their scheduled time count as delayed starts
+const DELAYED_START_THRESHOLD: Duration = Duration::from_secs(1);
+
+/// Notification from a task's ticker that the task is due.
+#[derive(Debug, Clone)]
+pub struct TaskTick {
+    /// The name of the task that is due
+    pub task_name: String,
+    /// When the run was scheduled to start; ticks delayed by a stalled
+    /// runtime carry the time they were originally due
+    pub scheduled_at: Instant,
+}
+
+/// Agent settings used to construct a [`TaskScheduler`].
+pub struct SchedulerOptions {
+    /// Interval in seconds between database flushes
+    pub flush_interval_seconds: u32,
+    /// Maximum time to wait for in-flight tasks during shutdown
+    pub graceful_shutdown_timeout_secs: u64,
+    /// Size of MPSC channel buffers for task communication
+    pub channel_buffer_size: usize,
+    /// Interval in seconds between queue cleanup operations
+    pub queue_cleanup_interval_seconds: u64,
+    /// Optional server URL for bandwidth tests
+    pub server_url: Option<String>,
+    /// Optional API key for server authentication
+    pub api_key: Option<String>,
+    /// Optional agent ID for identification
+    pub agent_id: Option<String>,
+    /// Encrypted store for `secret:` references in task credentials
+    pub secret_store: SecretStore,
+}
+
+impl SchedulerOptions {
+    /// Builds the options from the agent configuration.
+    ///
+    /// The server URL and API key are only set when the agent is not in local-only mode.
+    pub fn from_agent_config(agent_config: &AgentConfig, secret_store: SecretStore) -> Self {
+        let (server_url, api_key) = if agent_config.local_only {
+            (None, None)
+        } else {
+            (
+                Some(agent_config.central_server_url.clone()),
+                Some(agent_config.api_key.clone()),
+            )
+        };
+
+        Self {
+            flush_interval_seconds: agent_config.metrics_flush_interval_seconds,
+            graceful_shutdown_timeout_secs: agent_config.graceful_shutdown_timeout_seconds,
+            channel_buffer_size: agent_config.channel_buffer_size,
+            queue_cleanup_interval_seconds: agent_config.queue_cleanup_interval_seconds,
+            server_url,
+            api_key,
+            agent_id: Some(agent_config.agent_id.clone()),
+            secret_store,
+        }
+    }
+}
+
 /// Manages the scheduling and execution of all monitoring tasks.
 pub struct TaskScheduler {
     /// The current task configuration, wrapped in `Arc<RwLock<>>` to allow
@@ -112,13 +179,19 @@ pub struct TaskScheduler {
     #[allow(dead_code)]
     result_sender: mpsc::Sender<crate::tasks::TaskResult>,
     /// A channel receiver for getting notifications that a task is ready to run.
-    pub ready_receiver: mpsc::Receiver<String>,
+    pub ready_receiver: mpsc::Receiver<TaskTick>,
     /// The sender part of the channel for ready notifications. It's cloned
     /// and given to each spawned ticker task.
-    ready_sender: mpsc::Sender<String>,
+    ready_sender: mpsc::Sender<TaskTick>,
     /// A map to keep track of the state of each scheduled task, including its
     /// timer and whether it's currently running.
     running_tasks: HashMap<String, TaskHandle>,
+    /// Names of due tasks waiting for a concurrency slot, in arrival order.
+    pending: Vec<String>,
+    /// Maximum number of tasks running at the same time
+    max_concurrent_tasks: usize,
+    /// Maximum number of tasks of a type running at the same time
+    max_concurrent_per_type: HashMap<TaskType, usize>,
     /// The overall state of the scheduler (e.g., Running, Stopped).
     pub state: SchedulerState,
     /// The last time aggregation was performed (as Unix timestamp)
@@ -153,9 +226,195 @@ struct TaskHandle {
     /// to prevent a task from being started again if its previous run hasn't
     /// finished yet (a condition known as "overrun").
     is_running: bool,
+    /// When the task started waiting for a concurrency slot, while it waits.
+    queued_at: Option<Instant>,
+    /// When the waiting run was scheduled to start, while it waits.
+    due_at: Option<Instant>,
+    /// Execution events not aggregated yet, as (Unix timestamp, event)
+    executions: Vec<(u64, ExecutionEvent)>,
     /// The handle to the spawned ticker task that sends notifications on the
     /// `ready_sender` channel when the task's interval fires.
     join_handle: tokio::task::JoinHandle<()>,
+    /// Whether the latest result of the task failed.
+    last_failed: bool,
+    /// When runs started being suppressed because a prerequisite was down
+    /// (Unix timestamp), while they are.
+    upstream_down_since: Option<u64>,
+    /// When runs were last suppressed before the prerequisites recovered (Unix timestamp)
+    upstream_down_until: Option<u64>,
+    /// Tells the ticker whether to run on the faster on-failure schedule.
+    adaptive_sender: watch::Sender<bool>,
+    /// Adaptive (on-failure) scheduling state of the task.
+    adaptive: AdaptiveState,
+}
+
+/// Scheduler-side information about a task's aggregation period, recorded on its aggregate.
+#[derive(Debug, Default, Clone, Copy)]
+struct PeriodAnnotations {
+    /// The period overlaps a maintenance window of the task
+    maintenance: bool,
+    /// The task ran on its faster on-failure schedule during the period
+    adaptive: bool,
+    /// Runs were suppressed during the period by a failing prerequisite
+    upstream_down: bool,
+    /// Average time runs started in the period waited for a concurrency slot
+    avg_queue_wait_ms: Option<f64>,
+    /// Longest time a run started in the period waited for a concurrency slot
+    max_queue_wait_ms: Option<f64>,
+}
+
+/// Something that happened to a scheduled run of a task.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ExecutionEvent {
+    /// The run started after waiting for a concurrency slot and after its scheduled time
+    Started {
+        queue_wait_ms: f64,
+        start_delay_ms: f64,
+    },
+    /// The run was skipped because the previous one was still running or waiting
+    SkippedOverrun,
+    /// The run was skipped because a prerequisite task was down
+    Suppressed,
+    /// The run was aborted by the task timeout
+    TimedOut,
+}
+
+/// Tracks when a task ran on its faster on-failure schedule.
+#[derive(Debug, Default)]
+struct AdaptiveState {
+    /// When the faster schedule was switched on (Unix timestamp), while it is active
+    active_since: Option<u64>,
+    /// When the faster schedule was last switched off (Unix timestamp)
+    active_until: Option<u64>,
+    /// Consecutive successful results seen while the faster schedule is active
+    consecutive_successes: u32,
+    /// Set once the maximum duration ran out; the faster schedule is not
+    /// re-entered until the task succeeds again
+    exhausted: bool,
+}
+
+impl AdaptiveState {
+    /// Returns true if the faster schedule was active at some point in `[start, end)`
+    fn active_during(&self, start: u64, end: u64) -> bool {
+        self.active_since.is_some_and(|since| since < end)
+            || self.active_until.is_some_and(|until| until > start)
+    }
+}
+
+impl TaskHandle {
+    /// Returns true if tasks depending on this one should be suppressed
+    fn is_down(&self) -> bool {
+        self.last_failed || self.upstream_down_since.is_some()
+    }
+
+    /// Removes the execution events recorded before `end` and summarizes those
+    /// recorded in `[start, end)`
+    ///
+    /// The queue wait times of the runs started in the period are written to
+    /// `annotations`; the returned metric counts the started, skipped,
+    /// suppressed, delayed and timed-out runs.
+    fn take_executions(
+        &mut self,
+        start: u64,
+        end: u64,
+        annotations: &mut PeriodAnnotations,
+    ) -> TaskExecutionMetric {
+        let mut execution = TaskExecutionMetric::default();
+        let mut waits = Vec::new();
+        for (_, event) in self
+            .executions
+            .iter()
+            .filter(|(timestamp, _)| (start..end).contains(timestamp))
+        {
+            match *event {
+                ExecutionEvent::Started {
+                    queue_wait_ms,
+                    start_delay_ms,
+                } => {
+                    execution.runs_started += 1;
+                    waits.push(queue_wait_ms);
+                    if start_delay_ms > DELAYED_START_THRESHOLD.as_secs_f64() * 1000.0 {
+                        execution.delayed_starts += 1;
+                    }
+                    execution.max_start_delay_ms = execution.max_start_delay_ms.max(start_delay_ms);
+                }
+                ExecutionEvent::SkippedOverrun => execution.skipped_overruns += 1,
+                ExecutionEvent::Suppressed => execution.suppressed_runs += 1,
+                ExecutionEvent::TimedOut => execution.timed_out_runs += 1,
+            }
+        }
+        self.executions.retain(|(timestamp, _)| *timestamp >= end);
+
+        if !waits.is_empty() {
+            annotations.avg_queue_wait_ms = Some(waits.iter().sum::<f64>() / waits.len() as f64);
+            annotations.max_queue_wait_ms = Some(waits.iter().copied().fold(0.0, f64::max));
+        }
+        execution
+    }
+
+    /// Returns true if runs of the task were suppressed at some point in `[start, end)`
+    fn upstream_down_during(&self, start: u64, end: u64) -> bool {
+        self.upstream_down_since.is_some_and(|since| since < end)
+            || self.upstream_down_until.is_some_and(|until| until > start)
+    }
+
+    /// Updates the adaptive scheduling state with the outcome of a run and
+    /// switches the ticker between the normal and the on-failure schedule.
+    ///
+    /// # Parameters
+    /// * `failed` - Whether the run failed (execution error or unsuccessful measurement)
+    /// * `now` - Current Unix timestamp
+    fn record_outcome(&mut self, failed: bool, now: u64) {
+        let Some(on_failure_seconds) = self.config.on_failure_schedule_seconds else {
+            return;
+        };
+
+        match (failed, self.adaptive.active_since) {
+            (true, None) if !self.adaptive.exhausted => {
+                info!(
+                    "Task '{}' is failing, probing every {}s until it recovers",
+                    self.name, on_failure_seconds
+                );
+                self.adaptive.active_since = Some(now);
+                self.adaptive.consecutive_successes = 0;
+                self.adaptive_sender.send_replace(true);
+            }
+            (true, Some(since)) => {
+                self.adaptive.consecutive_successes = 0;
+                let max_duration = self.config.on_failure_max_duration_seconds;
+                if max_duration.is_some_and(|max| now.saturating_sub(since) >= max as u64) {
+                    warn!(
+                        "Task '{}' is still failing after {}s, returning to its normal schedule",
+                        self.name,
+                        now.saturating_sub(since)
+                    );
+                    self.adaptive.exhausted = true;
+                    self.leave_adaptive_mode(now);
+                }
+            }
+            (false, Some(_)) => {
+                self.adaptive.consecutive_successes += 1;
+                if self.adaptive.consecutive_successes >= self.config.effective_recovery_successes()
+                {
+                    info!(
+                        "Task '{}' recovered, returning to its normal schedule",
+                        self.name
+                    );
+                    self.leave_adaptive_mode(now);
+                }
+            }
+            (false, None) => self.adaptive.exhausted = false,
+            (true, None) => {}
+        }
+    }
+
+    /// Switches the ticker back to the normal schedule.
+    fn leave_adaptive_mode(&mut self, now: u64) {
+        self.adaptive.active_since = None;
+        self.adaptive.active_until = Some(now);
+        self.adaptive.consecutive_successes = 0;
+        self.adaptive_sender.send_replace(false);
+    }
 }
 
 /// Represents the possible states of the scheduler.
@@ -172,27 +431,26 @@ impl TaskScheduler {
     /// # Parameters
     /// * `tasks_config` - Initial task configuration
     /// * `database` - Shared database handle for storing metrics
-    /// * `flush_interval_seconds` - Interval in seconds between database flushes
-    /// * `graceful_shutdown_timeout_secs` - Maximum time to wait for in-flight tasks during shutdown
-    /// * `channel_buffer_size` - Size of MPSC channel buffers for task communication
-    /// * `queue_cleanup_interval_seconds` - Interval in seconds between queue cleanup operations
-    /// * `server_url` - Optional server URL for bandwidth tests
-    /// * `api_key` - Optional API key for server authentication
-    /// * `agent_id` - Optional agent ID for identification
+    /// * `options` - Flush intervals, buffer sizes, server credentials and secret store
     ///
     /// # Returns
     /// `TaskScheduler` instance or error if initialization fails
     pub fn new(
         tasks_config: TasksConfig,
         database: Arc<RwLock<AgentDatabase>>,
-        flush_interval_seconds: u32,
-        graceful_shutdown_timeout_secs: u64,
-        channel_buffer_size: usize,
-        queue_cleanup_interval_seconds: u64,
-        server_url: Option<String>,
-        api_key: Option<String>,
-        agent_id: Option<String>,
+        options: SchedulerOptions,
     ) -> Result<Self> {
+        let SchedulerOptions {
+            flush_interval_seconds,
+            graceful_shutdown_timeout_secs,
+            channel_buffer_size,
+            queue_cleanup_interval_seconds,
+            server_url,
+            api_key,
+            agent_id,
+            secret_store,
+        } = options;
+
         // A MPSC (multi-producer, single-consumer) channel is used to communicate
         // task results from the executor back to the scheduler's main loop.
         let (result_sender, result_receiver) = mpsc::channel(channel_buffer_size);
@@ -200,7 +458,8 @@ impl TaskScheduler {
         // that a task is ready to be executed.
         let (ready_sender, ready_receiver) = mpsc::channel(channel_buffer_size);
         let task_executor =
-            TaskExecutor::new(result_sender.clone(), server_url, api_key, agent_id)?;
+            TaskExecutor::new(result_sender.clone(), server_url, api_key, agent_id)?
+                .with_secret_store(secret_store);
 
         // Calculate max buffer size: For high-frequency monitoring, we want to buffer
         // enough metrics to avoid frequent DB writes, but not so many that we risk OOM.
@@ -217,6 +476,9 @@ impl TaskScheduler {
             ready_receiver,
             ready_sender,
             running_tasks: HashMap::new(),
+            pending: Vec::new(),
+            max_concurrent_tasks: shared::defaults::default_max_concurrent_tasks(),
+            max_concurrent_per_type: HashMap::new(),
             state: SchedulerState::Stopped,
             last_aggregation: 0,
             metrics_buffer: Vec::new(),
@@ -230,6 +492,21 @@ impl TaskScheduler {
         })
     }
 
+    /// Sets the global and per-task-type concurrency limits.
+    ///
+    /// # Parameters
+    /// * `max_concurrent_tasks` - Maximum number of tasks running at the same time
+    /// * `max_concurrent_per_type` - Maximum number of running tasks per task type
+    pub fn with_concurrency_limits(
+        mut self,
+        max_concurrent_tasks: usize,
+        max_concurrent_per_type: HashMap<TaskType, usize>,
+    ) -> Self {
+        self.max_concurrent_tasks = max_concurrent_tasks.max(1);
+        self.max_concurrent_per_type = max_concurrent_per_type;
+        self
+    }
+
     /// Starts the scheduler.
     ///
     /// This initializes and spawns the ticker tasks for each configured task.
@@ -269,21 +546,70 @@ impl TaskScheduler {
     /// Spawns a dedicated ticker task for a given monitoring task.
     ///
     /// This ticker will send a notification on the `ready_sender` channel
-    /// every time the task's interval fires.
+    /// every time the task's interval fires, or at every minute matched by its
+    /// cron expression if one is set. While the task is failing and has an
+    /// `on_failure_schedule_seconds`, it fires at that faster pace instead.
+    /// Ticks outside the task's active windows are skipped.
     ///
     /// # Parameters
     /// * `task_config` - The configuration for the task to schedule
-    /// * `start_delay` - Initial delay before the first tick
+    /// * `start_delay` - Initial delay before the first tick (ignored for cron tasks)
     fn spawn_ticker_task(&mut self, task_config: &TaskConfig, start_delay: Duration) {
         let start_time = Instant::now() + start_delay;
         let mut interval = tokio::time::interval_at(start_time, task_config.schedule_duration());
+        let cron_schedule = task_config.cron_schedule();
+        let ticker_config = task_config.clone();
         let task_name = task_config.name.clone();
         let ready_sender = self.ready_sender.clone();
+        let (adaptive_sender, mut adaptive_receiver) = watch::channel(false);
 
         let join_handle = tokio::spawn(async move {
+            let mut adaptive = false;
             loop {
-                interval.tick().await;
-                if ready_sender.send(task_name.clone()).await.is_err() {
+                // Cron schedules are replaced by the fixed interval while adaptive
+                let cron = cron_schedule.as_ref().filter(|_| !adaptive);
+                let scheduled_at = tokio::select! {
+                    due = wait_for_next_tick(&mut interval, cron) => {
+                        let Some(scheduled_at) = due else {
+                            warn!(
+                                "Cron schedule of task '{}' has no upcoming run, stopping ticker.",
+                                task_name
+                            );
+                            break;
+                        };
+                        scheduled_at
+                    }
+                    changed = adaptive_receiver.changed() => {
+                        if changed.is_err() {
+                            break;
+                        }
+                        adaptive = *adaptive_receiver.borrow_and_update();
+                        let period = match ticker_config.on_failure_schedule_duration() {
+                            Some(on_failure) if adaptive => on_failure,
+                            _ => ticker_config.schedule_duration(),
+                        };
+                        debug!(
+                            "Task '{}' switching to a {:?} schedule (adaptive: {})",
+                            task_name, period, adaptive
+                        );
+                        interval = tokio::time::interval_at(Instant::now() + period, period);
+                        continue;
+                    }
+                };
+
+                if !ticker_config.is_active_at(current_unix_timestamp()) {
+                    debug!(
+                        "Skipping tick of task '{}' outside its active windows.",
+                        task_name
+                    );
+                    continue;
+                }
+
+                let tick = TaskTick {
+                    task_name: task_name.clone(),
+                    scheduled_at,
+                };
+                if ready_sender.send(tick).await.is_err() {
                     debug!(
                         "Task ticker for '{}' stopping as channel is closed.",
                         task_name
@@ -297,7 +623,15 @@ impl TaskScheduler {
             name: task_config.name.clone(),
             config: task_config.clone(),
             is_running: false,
+            queued_at: None,
+            due_at: None,
+            executions: Vec::new(),
             join_handle,
+            last_failed: false,
+            upstream_down_since: None,
+            upstream_down_until: None,
+            adaptive_sender,
+            adaptive: AdaptiveState::default(),
         };
         self.running_tasks.insert(task_config.name.clone(), handle);
     }
@@ -316,6 +650,12 @@ impl TaskScheduler {
         info!("Stopping task scheduler gracefully");
         self.state = SchedulerState::Stopped;
 
+        // Tasks still waiting for a concurrency slot are not started anymore
+        if !self.pending.is_empty() {
+            debug!("Dropping {} queued task runs", self.pending.len());
+            self.pending.clear();
+        }
+
         // Wait for in-flight tasks to complete with timeout
         let in_flight_count = self.running_tasks.values().filter(|h| h.is_running).count();
         if in_flight_count > 0 {
@@ -387,16 +727,58 @@ impl TaskScheduler {
 
     /// Executes a single task by its name.
     ///
-    /// Checks if the task is already running to prevent overruns, then spawns
-    /// a new async task to execute it without blocking the scheduler loop.
+    /// Checks if the task is already running or waiting to prevent overruns,
+    /// then queues it for `dispatch_pending_tasks`, which spawns it as soon as
+    /// the concurrency limits allow.
     ///
     /// # Parameters
     /// * `task_name` - The name of the task to execute
+    /// * `scheduled_at` - When the run was scheduled to start
     ///
     /// # Returns
     /// `Ok(())` on successful spawn, or an error if something goes wrong
-    pub async fn execute_single_task(&mut self, task_name: &str) -> Result<()> {
+    pub async fn execute_single_task(
+        &mut self,
+        task_name: &str,
+        scheduled_at: Instant,
+    ) -> Result<()> {
+        // A failing prerequisite explains failures of its dependents, so they
+        // are not run until it recovers.
+        let failing_prerequisite = self.running_tasks.get(task_name).and_then(|handle| {
+            handle.config.depends_on.iter().find(|dependency| {
+                self.running_tasks
+                    .get(dependency.as_str())
+                    .is_some_and(|prerequisite| prerequisite.is_down())
+            })
+        });
+        let failing_prerequisite = failing_prerequisite.cloned();
+        let now = self.get_current_timestamp();
+
         if let Some(handle) = self.running_tasks.get_mut(task_name) {
+            if let Some(prerequisite) = failing_prerequisite {
+                if handle.upstream_down_since.is_none() {
+                    info!(
+                        "Suppressing task '{}' while prerequisite task '{}' is down (suppressed-upstream-down)",
+                        handle.name, prerequisite
+                    );
+                    handle.upstream_down_since = Some(now);
+                } else {
+                    debug!(
+                        "Skipping task '{}': prerequisite task '{}' is down",
+                        handle.name, prerequisite
+                    );
+                }
+                handle.executions.push((now, ExecutionEvent::Suppressed));
+                return Ok(());
+            }
+            if handle.upstream_down_since.take().is_some() {
+                info!(
+                    "Prerequisites of task '{}' recovered, resuming runs",
+                    handle.name
+                );
+                handle.upstream_down_until = Some(now);
+            }
+
             if handle.is_running {
                 // If the task is already running, skip this execution.
                 // This prevents task overruns.
@@ -404,28 +786,142 @@ impl TaskScheduler {
                     "Skipping execution of task '{}' as it is already running.",
                     handle.name
                 );
+                handle
+                    .executions
+                    .push((now, ExecutionEvent::SkippedOverrun));
                 return Ok(());
             }
 
-            debug!("Executing task: {}", handle.name);
-            handle.is_running = true;
-
-            // The actual task execution is spawned onto a new tokio task.
-            // This allows the scheduler loop to remain responsive and not get
-            // blocked by a long-running task.
-            let task_executor = self.task_executor.clone();
-            let task_config = handle.config.clone();
+            if handle.queued_at.is_some() {
+                warn!(
+                    "Skipping execution of task '{}' as it is still waiting for a concurrency slot.",
+                    handle.name
+                );
+                handle
+                    .executions
+                    .push((now, ExecutionEvent::SkippedOverrun));
+                return Ok(());
+            }
 
-            tokio::spawn(async move {
-                if let Err(e) = task_executor.execute_task(&task_config).await {
-                    tracing::error!("Task execution failed: {}", e);
-                }
-            });
+            handle.queued_at = Some(Instant::now());
+            handle.due_at = Some(scheduled_at);
+            self.pending.push(task_name.to_string());
+            self.dispatch_pending_tasks();
         }
 
         Ok(())
     }
 
+    /// Starts queued tasks while the concurrency limits allow.
+    ///
+    /// Tasks are considered highest `priority` first and in arrival order
+    /// within a priority. A task whose type is at its `max_concurrent_per_type`
+    /// cap keeps waiting without blocking tasks of other types.
+    fn dispatch_pending_tasks(&mut self) {
+        if self.pending.is_empty() {
+            return;
+        }
+
+        let mut running_total = 0;
+        let mut running_per_type: HashMap<TaskType, usize> = HashMap::new();
+        for handle in self
+            .running_tasks
+            .values()
+            .filter(|handle| handle.is_running)
+        {
+            running_total += 1;
+            *running_per_type
+                .entry(handle.config.task_type.clone())
+                .or_default() += 1;
+        }
+
+        // The sort is stable, so tasks of equal priority keep their arrival order
+        let running_tasks = &self.running_tasks;
+        self.pending.sort_by_key(|name| {
+            Reverse(
+                running_tasks
+                    .get(name)
+                    .and_then(|handle| handle.config.priority)
+                    .unwrap_or_default(),
+            )
+        });
+
+        let mut waiting = Vec::new();
+        for task_name in std::mem::take(&mut self.pending) {
+            let Some(handle) = self.running_tasks.get(&task_name) else {
+                continue;
+            };
+            let task_type = handle.config.task_type.clone();
+            let running_of_type = running_per_type.entry(task_type.clone()).or_default();
+            let type_full = self
+                .max_concurrent_per_type
+                .get(&task_type)
+                .is_some_and(|limit| *running_of_type >= *limit);
+            if running_total >= self.max_concurrent_tasks || type_full {
+                waiting.push(task_name);
+                continue;
+            }
+
+            running_total += 1;
+            *running_of_type += 1;
+            self.start_task(&task_name);
+        }
+        self.pending = waiting;
+    }
+
+    /// Spawns the execution of a queued task and records how long it waited.
+    ///
+    /// # Parameters
+    /// * `task_name` - The name of the task to start
+    fn start_task(&mut self, task_name: &str) {
+        let now = self.get_current_timestamp();
+        let Some(handle) = self.running_tasks.get_mut(task_name) else {
+            return;
+        };
+
+        let wait_ms = handle
+            .queued_at
+            .take()
+            .map(|queued_at| queued_at.elapsed().as_secs_f64() * 1000.0)
+            .unwrap_or_default();
+        let delay_ms = handle
+            .due_at
+            .take()
+            .map(|due_at| due_at.elapsed().as_secs_f64() * 1000.0)
+            .unwrap_or_default();
+        handle.executions.push((
+            now,
+            ExecutionEvent::Started {
+                queue_wait_ms: wait_ms,
+                start_delay_ms: delay_ms,
+            },
+        ));
+        if delay_ms > DELAYED_START_THRESHOLD.as_secs_f64() * 1000.0 {
+            warn!(
+                "Task '{}' starting {:.0}ms after its scheduled time (waited {:.1}ms for a slot)",
+                handle.name, delay_ms, wait_ms
+            );
+        } else {
+            debug!(
+                "Executing task: {} (waited {:.1}ms for a slot)",
+                handle.name, wait_ms
+            );
+        }
+        handle.is_running = true;
+
+        // The actual task execution is spawned onto a new tokio task.
+        // This allows the scheduler loop to remain responsive and not get
+        // blocked by a long-running task.
+        let task_executor = self.task_executor.clone();
+        let task_config = handle.config.clone();
+
+        tokio::spawn(async move {
+            if let Err(e) = task_executor.execute_task(&task_config).await {
+                tracing::error!("Task execution failed: {}", e);
+            }
+        });
+    }
+
     /// Handles the result of a completed task.
     ///
     /// Updates task state, buffers metrics for database storage, and logs
@@ -439,9 +935,29 @@ impl TaskScheduler {
     pub async fn handle_task_result(&mut self, result: crate::tasks::TaskResult) -> Result<()> {
         debug!("Received task result for: {}", result.task_name);
 
-        // Update the task's state to indicate it's no longer running.
+        // Task functions report target failures inside successful results, so
+        // the measurement itself decides whether the run failed
+        let failed = !result.success
+            || result
+                .metric_data
+                .as_ref()
+                .is_some_and(|metric_data| !metric_data.is_successful());
+        let now = self.get_current_timestamp();
+
+        // Update the task's state to indicate it's no longer running, and
+        // adjust its schedule to the outcome.
         if let Some(handle) = self.running_tasks.get_mut(&result.task_name) {
             handle.is_running = false;
+            handle.last_failed = failed;
+            if result.timed_out {
+                handle.executions.push((now, ExecutionEvent::TimedOut));
+            }
+            handle.record_outcome(failed, now);
+        }
+
+        // The finished task freed a concurrency slot
+        if self.state == SchedulerState::Running {
+            self.dispatch_pending_tasks();
         }
 
         // If the task produced metric data, add it to the buffer.
@@ -549,7 +1065,11 @@ impl TaskScheduler {
 
             let mut db = self.database.write().await;
             for metric in metrics_to_write {
-                db.store_raw_metric(&metric).await?;
+                if self.uploads_raw(&metric.task_name) {
+                    db.store_and_enqueue_raw_metric(&metric).await?;
+                } else {
+                    db.store_raw_metric(&metric).await?;
+                }
             }
 
             self.last_db_write = current_time;
@@ -578,7 +1098,11 @@ impl TaskScheduler {
 
             let mut db = self.database.write().await;
             for metric in metrics_to_write {
-                db.store_raw_metric(&metric).await?;
+                if self.uploads_raw(&metric.task_name) {
+                    db.store_and_enqueue_raw_metric(&metric).await?;
+                } else {
+                    db.store_raw_metric(&metric).await?;
+                }
             }
 
             self.last_db_write = self.get_current_timestamp();
@@ -591,50 +1115,101 @@ impl TaskScheduler {
         Ok(())
     }
 
+    /// Returns true if the raw metrics of a task are uploaded alongside its aggregates
+    fn uploads_raw(&self, task_name: &str) -> bool {
+        self.running_tasks
+            .get(task_name)
+            .is_some_and(|handle| handle.config.upload_raw)
+    }
+
     /// Checks if aggregation should be performed and does it if needed.
     ///
-    /// Aggregation occurs once per minute boundary. When a new minute is
-    /// detected, this method flushes any buffered metrics and then aggregates
-    /// all raw metrics from the previous minute for each configured task.
+    /// Aggregation occurs once per aggregation window boundary (60 seconds
+    /// unless `aggregation_window_seconds` is set in the tasks configuration).
+    /// When a new window is detected, this method flushes any buffered metrics
+    /// and then aggregates all raw metrics from the previous window for each
+    /// configured task.
     ///
     /// # Returns
     /// `Ok(())` on success, error if aggregation or database operations fail
     pub async fn check_and_perform_aggregation(&mut self) -> Result<()> {
+        // Get the aggregation window, maintenance windows and all task names and types
+        // from our configuration
+        let tasks_config = self.tasks_config.read().await.clone();
+        let window_seconds = (tasks_config.aggregation_window_seconds as u64).max(1);
+        let task_configs = tasks_config.tasks.clone();
+
         let current_time = self.get_current_timestamp();
-        // Round down to full minute using saturating arithmetic to prevent overflow
-        let current_minute = current_time.saturating_div(60).saturating_mul(60);
+        // Round down to the window boundary using saturating arithmetic to prevent overflow
+        let current_window = current_time
+            .saturating_div(window_seconds)
+            .saturating_mul(window_seconds);
         debug!("Checking aggregator");
-        // Check if we've moved to a new minute
-        if current_minute > self.last_aggregation {
+        // Check if we've moved to a new window
+        if current_window > self.last_aggregation {
             // Flush any remaining buffered metrics before aggregation
             self.flush_metrics_now().await?;
 
-            let period_end = current_minute;
-            let period_start = period_end.saturating_sub(60); // Previous minute
+            let period_end = current_window;
+            // Previous window, but never reaching back before the end of the last
+            // aggregated period (the window may have grown since the last run)
+            let period_start = period_end
+                .saturating_sub(window_seconds)
+                .max(self.last_aggregation);
 
             debug!(
                 "Performing aggregation for period {}-{}",
                 period_start, period_end
             );
 
-            // Get all unique task names and types from our configuration
-            let task_configs = {
-                let config = self.tasks_config.read().await;
-                config.tasks.clone()
-            };
-
-            // Perform aggregation for each task
+            // Perform aggregation for each task, tagging periods that overlap
+            // one of its maintenance windows, its faster on-failure schedule or
+            // runs suppressed by a failing prerequisite, and reporting how long
+            // its runs waited for a concurrency slot and which runs were missed
             for task_config in task_configs {
+                let mut execution = TaskExecutionMetric::default();
+                let mut annotations = PeriodAnnotations {
+                    maintenance: tasks_config.in_maintenance(
+                        &task_config.name,
+                        period_start,
+                        period_end,
+                    ),
+                    ..Default::default()
+                };
+                if let Some(handle) = self.running_tasks.get_mut(&task_config.name) {
+                    annotations.adaptive = handle.adaptive.active_during(period_start, period_end);
+                    annotations.upstream_down =
+                        handle.upstream_down_during(period_start, period_end);
+                    execution = handle.take_executions(period_start, period_end, &mut annotations);
+                }
                 self.aggregate_task_metrics(
                     &task_config.name,
                     &task_config.task_type,
                     period_start,
                     period_end,
+                    annotations,
+                    &task_config.labels,
                 )
                 .await?;
+                if !execution.is_clean() {
+                    let mut execution_metrics = AggregatedMetrics::new(
+                        task_config.name.clone(),
+                        task_config.task_type.clone(),
+                        period_start,
+                        period_end,
+                        execution.runs_started,
+                        AggregatedMetricData::Execution(execution),
+                    );
+                    execution_metrics.labels = task_config.labels.clone();
+                    self.database
+                        .write()
+                        .await
+                        .store_and_enqueue_aggregated_metrics(&execution_metrics)
+                        .await?;
+                }
             }
 
-            self.last_aggregation = current_minute;
+            self.last_aggregation = current_window;
             info!(
                 "Completed aggregation for period {}-{}",
                 period_start, period_end
@@ -667,6 +1242,8 @@ impl TaskScheduler {
     /// * `task_type` - Type of the task (affects aggregation logic)
     /// * `period_start` - Start of the aggregation period (Unix timestamp)
     /// * `period_end` - End of the aggregation period (Unix timestamp)
+    /// * `annotations` - Scheduler-side information about the period to record on the aggregate
+    /// * `labels` - Labels of the task to record on the aggregate
     ///
     /// # Returns
     /// `Ok(())` on success, error if database operations fail
@@ -676,13 +1253,21 @@ impl TaskScheduler {
         task_type: &TaskType,
         period_start: u64,
         period_end: u64,
+        annotations: PeriodAnnotations,
+        labels: &BTreeMap<String, String>,
     ) -> Result<()> {
         let mut db = self.database.write().await;
 
-        if let Some(aggregated_metrics) = db
+        if let Some(mut aggregated_metrics) = db
             .generate_aggregated_metrics(task_name, task_type, period_start, period_end)
             .await?
         {
+            aggregated_metrics.maintenance = annotations.maintenance;
+            aggregated_metrics.adaptive = annotations.adaptive;
+            aggregated_metrics.upstream_down = annotations.upstream_down;
+            aggregated_metrics.avg_queue_wait_ms = annotations.avg_queue_wait_ms;
+            aggregated_metrics.max_queue_wait_ms = annotations.max_queue_wait_ms;
+            aggregated_metrics.labels = labels.clone();
             // Store and automatically enqueue for sending
             db.store_and_enqueue_aggregated_metrics(&aggregated_metrics)
                 .await?;
@@ -705,10 +1290,7 @@ impl TaskScheduler {
     /// # Returns
     /// Current time as seconds since Unix epoch
     pub fn get_current_timestamp(&self) -> u64 {
-        SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs()
+        current_unix_timestamp()
     }
 
     /// Checks if the scheduler is currently in the `Running` state.
@@ -764,3 +1346,31 @@ impl TaskScheduler {
         self.task_executor.refresh_clients()
     }
 }
+
+/// Current time as seconds since Unix epoch
+fn current_unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Waits for the next tick of a task, either its cron schedule or its interval.
+///
+/// # Returns
+/// When the tick was scheduled, or `None` if the cron schedule has no upcoming run
+async fn wait_for_next_tick(
+    interval: &mut tokio::time::Interval,
+    cron: Option<&shared::schedule::CronSchedule>,
+) -> Option<Instant> {
+    match cron {
+        Some(cron) => {
+            let now = current_unix_timestamp();
+            let next = cron.next_after(now)?;
+            let scheduled_at = Instant::now() + Duration::from_secs(next - now);
+            tokio::time::sleep_until(scheduled_at).await;
+            Some(scheduled_at)
+        }
+        None => Some(interval.tick().await),
+    }
+}
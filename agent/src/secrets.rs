@@ -0,0 +1,306 @@
+//! Secret references for task credentials
+//!
+//! Credential fields in tasks.toml may hold a reference instead of the secret
+//! itself, so the file uploaded to the server never contains credentials:
+//!
+//! - `env:NAME` - value of the environment variable `NAME`
+//! - `file:/path/to/file` - contents of the file (trailing newline removed)
+//! - `secret:NAME` - entry `NAME` of the agent-local encrypted secrets store
+//!
+//! Any other value is used as-is. References are resolved on the agent right
+//! before each task execution and the resolved values are never persisted.
+//!
+//! The encrypted store lives in the agent data directory: `secrets.json` holds
+//! AES-256-GCM encrypted entries and `secrets.key` the 256-bit key. Both files
+//! are created with owner-only permissions on Unix.
+
+use anyhow::{Context, Result};
+use aws_lc_rs::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM, NONCE_LEN};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use shared::config::{TaskConfig, TaskParams};
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// File holding the encrypted secrets
+const STORE_FILE: &str = "secrets.json";
+/// File holding the store encryption key
+const KEY_FILE: &str = "secrets.key";
+/// AES-256 key length in bytes
+const KEY_LEN: usize = 32;
+
+/// Reference prefixes
+const ENV_PREFIX: &str = "env:";
+const FILE_PREFIX: &str = "file:";
+const SECRET_PREFIX: &str = "secret:";
+
+/// On-disk format of the secrets store
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoreFile {
+    /// Secret name -> base64(nonce || ciphertext || tag)
+    secrets: BTreeMap<String, String>,
+}
+
+/// Agent-local encrypted secrets store
+#[derive(Debug, Clone)]
+pub struct SecretStore {
+    dir: PathBuf,
+}
+
+impl SecretStore {
+    /// Creates a store located in `dir` (usually the agent data directory)
+    ///
+    /// Files are only read or created when secrets are accessed.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Returns the decrypted value of a secret
+    pub fn get(&self, name: &str) -> Result<String> {
+        let store = self.load()?;
+        let encoded = store
+            .secrets
+            .get(name)
+            .ok_or_else(|| anyhow::anyhow!("Secret '{}' not found in secrets store", name))?;
+        let key = self.load_key()?;
+        decrypt(&key, name, encoded)
+    }
+
+    /// Adds or replaces a secret, creating the key and store files if needed
+    pub fn set(&self, name: &str, value: &str) -> Result<()> {
+        if name.is_empty() {
+            anyhow::bail!("Secret name cannot be empty");
+        }
+        let key = self.load_or_create_key()?;
+        let mut store = self.load()?;
+        store
+            .secrets
+            .insert(name.to_string(), encrypt(&key, name, value)?);
+        self.save(&store)
+    }
+
+    /// Removes a secret, returning whether it existed
+    pub fn remove(&self, name: &str) -> Result<bool> {
+        let mut store = self.load()?;
+        let existed = store.secrets.remove(name).is_some();
+        if existed {
+            self.save(&store)?;
+        }
+        Ok(existed)
+    }
+
+    /// Returns the names of all stored secrets
+    pub fn names(&self) -> Result<Vec<String>> {
+        Ok(self.load()?.secrets.into_keys().collect())
+    }
+
+    fn load(&self) -> Result<StoreFile> {
+        let path = self.dir.join(STORE_FILE);
+        if !path.exists() {
+            return Ok(StoreFile::default());
+        }
+        let content = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read secrets store: {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse secrets store: {}", path.display()))
+    }
+
+    fn save(&self, store: &StoreFile) -> Result<()> {
+        let content = serde_json::to_string_pretty(store)?;
+        write_private(&self.dir.join(STORE_FILE), content.as_bytes())
+    }
+
+    fn load_key(&self) -> Result<Vec<u8>> {
+        let path = self.dir.join(KEY_FILE);
+        let encoded = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read secrets key: {}", path.display()))?;
+        let key = base64::engine::general_purpose::STANDARD
+            .decode(encoded.trim())
+            .context("Secrets key is not valid base64")?;
+        if key.len() != KEY_LEN {
+            anyhow::bail!("Secrets key must be {} bytes, found {}", KEY_LEN, key.len());
+        }
+        Ok(key)
+    }
+
+    fn load_or_create_key(&self) -> Result<Vec<u8>> {
+        if self.dir.join(KEY_FILE).exists() {
+            return self.load_key();
+        }
+        std::fs::create_dir_all(&self.dir).with_context(|| {
+            format!("Failed to create secrets directory: {}", self.dir.display())
+        })?;
+        let key: [u8; KEY_LEN] = rand::random();
+        let encoded = base64::engine::general_purpose::STANDARD.encode(key);
+        write_private(&self.dir.join(KEY_FILE), encoded.as_bytes())?;
+        Ok(key.to_vec())
+    }
+}
+
+/// Writes a file readable only by its owner
+fn write_private(path: &Path, content: &[u8]) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .mode(0o600)
+            .open(path)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        file.write_all(content)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+    #[cfg(not(unix))]
+    {
+        std::fs::write(path, content).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+fn cipher(key: &[u8]) -> Result<LessSafeKey> {
+    let key =
+        UnboundKey::new(&AES_256_GCM, key).map_err(|_| anyhow::anyhow!("Invalid secrets key"))?;
+    Ok(LessSafeKey::new(key))
+}
+
+/// Encrypts a secret, binding it to its name so entries cannot be swapped
+fn encrypt(key: &[u8], name: &str, value: &str) -> Result<String> {
+    let nonce_bytes: [u8; NONCE_LEN] = rand::random();
+    let mut data = value.as_bytes().to_vec();
+    cipher(key)?
+        .seal_in_place_append_tag(
+            Nonce::assume_unique_for_key(nonce_bytes),
+            Aad::from(name.as_bytes()),
+            &mut data,
+        )
+        .map_err(|_| anyhow::anyhow!("Failed to encrypt secret '{}'", name))?;
+
+    let mut output = nonce_bytes.to_vec();
+    output.extend_from_slice(&data);
+    Ok(base64::engine::general_purpose::STANDARD.encode(output))
+}
+
+fn decrypt(key: &[u8], name: &str, encoded: &str) -> Result<String> {
+    let mut data = base64::engine::general_purpose::STANDARD
+        .decode(encoded)
+        .with_context(|| format!("Secret '{}' is not valid base64", name))?;
+    if data.len() < NONCE_LEN {
+        anyhow::bail!("Secret '{}' is truncated", name);
+    }
+    let mut ciphertext = data.split_off(NONCE_LEN);
+    let nonce = Nonce::try_assume_unique_for_key(&data)
+        .map_err(|_| anyhow::anyhow!("Secret '{}' has an invalid nonce", name))?;
+    let plaintext = cipher(key)?
+        .open_in_place(nonce, Aad::from(name.as_bytes()), &mut ciphertext)
+        .map_err(|_| {
+            anyhow::anyhow!(
+                "Failed to decrypt secret '{}' (wrong key or corrupted store)",
+                name
+            )
+        })?;
+    String::from_utf8(plaintext.to_vec())
+        .with_context(|| format!("Secret '{}' is not valid UTF-8", name))
+}
+
+/// Returns true if the value is a secret reference
+pub fn is_secret_reference(value: &str) -> bool {
+    value.starts_with(ENV_PREFIX)
+        || value.starts_with(FILE_PREFIX)
+        || value.starts_with(SECRET_PREFIX)
+}
+
+/// Resolves a single value, returning literals unchanged
+///
+/// `secret:` references fail when no store is available.
+pub fn resolve_secret(value: &str, store: Option<&SecretStore>) -> Result<String> {
+    if let Some(name) = value.strip_prefix(ENV_PREFIX) {
+        std::env::var(name).with_context(|| {
+            format!(
+                "Secret reference '{}': environment variable '{}' is not set",
+                value, name
+            )
+        })
+    } else if let Some(path) = value.strip_prefix(FILE_PREFIX) {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Secret reference '{}': failed to read file", value))?;
+        Ok(content.trim_end_matches(['\n', '\r']).to_string())
+    } else if let Some(name) = value.strip_prefix(SECRET_PREFIX) {
+        store
+            .ok_or_else(|| anyhow::anyhow!("No secrets store is configured"))?
+            .get(name)
+            .with_context(|| format!("Secret reference '{}' could not be resolved", value))
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+/// Resolves a field in place if it holds a reference
+fn resolve_field(field: &mut String, store: Option<&SecretStore>) -> Result<()> {
+    if is_secret_reference(field) {
+        *field = resolve_secret(field, store)?;
+    }
+    Ok(())
+}
+
+/// Returns the task configuration with all credential references resolved
+///
+/// Resolved fields are SQL `password`, SNMP `community` and `auth_password`,
+/// HTTP GET header values, and the bandwidth `reflector_api_key`. Tasks without references are borrowed as-is.
+pub fn resolve_task_secrets<'a>(
+    task_config: &'a TaskConfig,
+    store: Option<&SecretStore>,
+) -> Result<Cow<'a, TaskConfig>> {
+    let has_reference = match &task_config.params {
+        TaskParams::HttpGet(params) => params.headers.values().any(|v| is_secret_reference(v)),
+        TaskParams::Bandwidth(params) => params
+            .reflector_api_key
+            .as_deref()
+            .is_some_and(is_secret_reference),
+        #[cfg(feature = "sql-tasks")]
+        TaskParams::SqlQuery(params) => params.password.as_deref().is_some_and(is_secret_reference),
+        #[cfg(feature = "snmp-tasks")]
+        TaskParams::Snmp(params) => {
+            is_secret_reference(&params.community)
+                || params
+                    .auth_password
+                    .as_deref()
+                    .is_some_and(is_secret_reference)
+        }
+        _ => false,
+    };
+    if !has_reference {
+        return Ok(Cow::Borrowed(task_config));
+    }
+
+    let mut resolved = task_config.clone();
+    match &mut resolved.params {
+        TaskParams::HttpGet(params) => {
+            for value in params.headers.values_mut() {
+                resolve_field(value, store)?;
+            }
+        }
+        TaskParams::Bandwidth(params) => {
+            if let Some(key) = params.reflector_api_key.as_mut() {
+                resolve_field(key, store)?;
+            }
+        }
+        #[cfg(feature = "sql-tasks")]
+        TaskParams::SqlQuery(params) => {
+            if let Some(password) = params.password.as_mut() {
+                resolve_field(password, store)?;
+            }
+        }
+        #[cfg(feature = "snmp-tasks")]
+        TaskParams::Snmp(params) => {
+            resolve_field(&mut params.community, store)?;
+            if let Some(password) = params.auth_password.as_mut() {
+                resolve_field(password, store)?;
+            }
+        }
+        _ => {}
+    }
+    Ok(Cow::Owned(resolved))
+}
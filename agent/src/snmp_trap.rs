@@ -0,0 +1,1191 @@
+//! SNMP trap and inform receiver
+//!
+//! This module listens on a UDP socket for SNMP notifications: SNMPv1 traps,
+//! SNMPv2c traps and informs, and SNMPv3 traps and informs (noAuthNoPriv and
+//! authNoPriv). Accepted notifications are handed to the agent main loop, which
+//! stores them as events and forwards them through the regular send queue.
+//!
+//! The SNMP polling task only sees a device's state once per schedule interval,
+//! so short outages (e.g., a link flapping between two polls) are invisible to it.
+//! Devices report such changes through notifications, which this receiver captures.
+//!
+//! Notifications are decoded with a small BER parser rather than the polling
+//! library, because we need access to the raw message to verify SNMPv3
+//! authentication codes and to answer informs and engine discovery requests
+//! as the authoritative engine.
+
+use anyhow::{anyhow, bail, Context, Result};
+use openssl::hash::{Hasher, MessageDigest};
+use openssl::pkey::PKey;
+use openssl::sign::Signer;
+use shared::config::{
+    SnmpAuthProtocol, SnmpSecurityLevel, SnmpTrapReceiverConfig, SnmpTrapUser, TaskParams,
+    TasksConfig,
+};
+use shared::metrics::{AggregatedMetricData, AggregatedMetrics, SnmpTrapEventMetric, SnmpVarbind};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::net::UdpSocket;
+use tokio::sync::{mpsc, RwLock};
+use tracing::{debug, info, warn};
+
+/// Task name used for notifications whose source matches no configured SNMP task
+pub const UNMATCHED_TRAP_TASK_NAME: &str = "snmp_trap";
+
+/// Largest UDP payload we accept (maximum IPv4 UDP datagram size)
+const MAX_DATAGRAM_SIZE: usize = 65507;
+
+// BER universal and SNMP application tags
+const TAG_INTEGER: u8 = 0x02;
+const TAG_OCTET_STRING: u8 = 0x04;
+const TAG_NULL: u8 = 0x05;
+const TAG_OID: u8 = 0x06;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_IP_ADDRESS: u8 = 0x40;
+const TAG_COUNTER32: u8 = 0x41;
+const TAG_GAUGE32: u8 = 0x42;
+const TAG_TIMETICKS: u8 = 0x43;
+const TAG_OPAQUE: u8 = 0x44;
+const TAG_COUNTER64: u8 = 0x46;
+const TAG_NO_SUCH_OBJECT: u8 = 0x80;
+const TAG_NO_SUCH_INSTANCE: u8 = 0x81;
+const TAG_END_OF_MIB_VIEW: u8 = 0x82;
+
+// PDU tags
+const PDU_GET_REQUEST: u8 = 0xA0;
+const PDU_RESPONSE: u8 = 0xA2;
+const PDU_TRAP_V1: u8 = 0xA4;
+const PDU_INFORM_REQUEST: u8 = 0xA6;
+const PDU_TRAP_V2: u8 = 0xA7;
+const PDU_REPORT: u8 = 0xA8;
+
+// SNMPv3 message flags
+const FLAG_AUTH: u8 = 0x01;
+const FLAG_PRIV: u8 = 0x02;
+const FLAG_REPORTABLE: u8 = 0x04;
+
+/// User-based Security Model identifier
+const USM_SECURITY_MODEL: i64 = 3;
+
+/// snmpEngineBoots reported by our engine (the receiver keeps no state across restarts)
+const LOCAL_ENGINE_BOOTS: i64 = 1;
+/// Largest snmpEngineBoots value; an engine that reached it must be reconfigured
+const MAX_ENGINE_BOOTS: i64 = 2147483647;
+/// Accepted difference between a message's engine time and ours (RFC 3414 3.2 step 7)
+const TIME_WINDOW_SECONDS: i64 = 150;
+
+/// sysUpTime.0
+const OID_SYS_UPTIME: &[u32] = &[1, 3, 6, 1, 2, 1, 1, 3, 0];
+/// snmpTrapOID.0
+const OID_SNMP_TRAP_OID: &[u32] = &[1, 3, 6, 1, 6, 3, 1, 1, 4, 1, 0];
+/// snmpTraps (generic v1 traps are translated below this prefix, RFC 3584)
+const OID_SNMP_TRAPS: &[u32] = &[1, 3, 6, 1, 6, 3, 1, 1, 5];
+/// usmStatsNotInTimeWindows.0
+const OID_USM_NOT_IN_TIME_WINDOWS: &[u32] = &[1, 3, 6, 1, 6, 3, 15, 1, 1, 2, 0];
+/// usmStatsUnknownEngineIDs.0
+const OID_USM_UNKNOWN_ENGINE_IDS: &[u32] = &[1, 3, 6, 1, 6, 3, 15, 1, 1, 4, 0];
+
+/// A configured SNMP task whose host can originate notifications
+#[derive(Debug, Clone)]
+pub struct TrapSource {
+    pub ip: IpAddr,
+    pub task_name: String,
+    pub target_id: Option<String>,
+}
+
+/// Build the source list used to attribute notifications to SNMP tasks.
+/// Hosts that cannot be resolved are skipped with a warning.
+pub fn resolve_trap_sources(tasks_config: &TasksConfig) -> Vec<TrapSource> {
+    let mut sources = Vec::new();
+    for task in &tasks_config.tasks {
+        if let TaskParams::Snmp(params) = &task.params {
+            match crate::task_snmp::parse_host(&params.host) {
+                Ok(addr) => sources.push(TrapSource {
+                    ip: addr.ip().to_canonical(),
+                    task_name: task.name.clone(),
+                    target_id: params.target_id.clone(),
+                }),
+                Err(e) => warn!(
+                    task_name = %task.name,
+                    error = %e,
+                    "Could not resolve SNMP task host for trap attribution"
+                ),
+            }
+        }
+    }
+    sources
+}
+
+/// Latest boots and time received from an authoritative notification sender
+#[derive(Debug, Clone, Copy)]
+struct RemoteEngineTime {
+    boots: i64,
+    time: i64,
+    received_at: Instant,
+}
+
+/// Result of processing a single datagram
+#[derive(Debug, Default)]
+pub struct ProcessedDatagram {
+    /// Notification to store, if it passed all filters
+    pub event: Option<AggregatedMetrics>,
+    /// Datagram to send back to the sender (inform acknowledgement or report)
+    pub response: Option<Vec<u8>>,
+}
+
+/// SNMP notification receiver
+pub struct SnmpTrapReceiver {
+    config: SnmpTrapReceiverConfig,
+    allowed_sources: Vec<IpAddr>,
+    trap_oid_filters: Vec<Vec<u32>>,
+    /// Our authoritative engine ID (used for SNMPv3 informs)
+    engine_id: Vec<u8>,
+    started_at: Instant,
+    /// Number of reports sent for unknown engine IDs (usmStatsUnknownEngineIDs)
+    unknown_engine_ids: u32,
+    /// Number of reports sent for messages outside our time window (usmStatsNotInTimeWindows)
+    not_in_time_windows: u32,
+    /// Non-localized authentication keys per user (derived once from the password)
+    user_keys: HashMap<String, Vec<u8>>,
+    /// Engine boots and time of SNMPv3 trap senders, per engine ID
+    remote_engine_times: HashMap<Vec<u8>, RemoteEngineTime>,
+    sources: Arc<RwLock<Vec<TrapSource>>>,
+    /// Accepted events, stored by the agent main loop
+    event_sender: mpsc::Sender<AggregatedMetrics>,
+}
+
+impl SnmpTrapReceiver {
+    /// Create a new receiver from validated configuration
+    pub fn new(
+        config: SnmpTrapReceiverConfig,
+        agent_id: &str,
+        sources: Arc<RwLock<Vec<TrapSource>>>,
+        event_sender: mpsc::Sender<AggregatedMetrics>,
+    ) -> Result<Self> {
+        let allowed_sources = config
+            .allowed_sources
+            .iter()
+            .map(|s| {
+                s.parse::<IpAddr>()
+                    .map(|ip| ip.to_canonical())
+                    .with_context(|| format!("Invalid trap source address: {}", s))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let trap_oid_filters = config
+            .trap_oids
+            .iter()
+            .map(|oid| parse_oid(oid))
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            config,
+            allowed_sources,
+            trap_oid_filters,
+            engine_id: local_engine_id(agent_id),
+            started_at: Instant::now(),
+            unknown_engine_ids: 0,
+            not_in_time_windows: 0,
+            user_keys: HashMap::new(),
+            remote_engine_times: HashMap::new(),
+            sources,
+            event_sender,
+        })
+    }
+
+    /// Bind the listening socket
+    pub async fn bind(&self) -> Result<UdpSocket> {
+        let socket = UdpSocket::bind(&self.config.listen_address)
+            .await
+            .with_context(|| {
+                format!(
+                    "Failed to bind SNMP trap receiver to {} (ports below 1024 need elevated privileges)",
+                    self.config.listen_address
+                )
+            })?;
+        info!(
+            listen_address = %self.config.listen_address,
+            "SNMP trap receiver listening"
+        );
+        Ok(socket)
+    }
+
+    /// Receive notifications until a shutdown signal arrives
+    pub async fn run(
+        mut self,
+        socket: UdpSocket,
+        mut shutdown_rx: tokio::sync::broadcast::Receiver<()>,
+    ) {
+        let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+
+        loop {
+            let (len, src) = tokio::select! {
+                result = socket.recv_from(&mut buf) => match result {
+                    Ok(received) => received,
+                    Err(e) => {
+                        warn!("SNMP trap receiver failed to read datagram: {}", e);
+                        continue;
+                    }
+                },
+                _ = shutdown_rx.recv() => {
+                    info!("SNMP trap receiver shutting down");
+                    break;
+                }
+            };
+
+            let processed = {
+                let sources = self.sources.clone();
+                let sources = sources.read().await;
+                self.process_datagram(&buf[..len], src, &sources)
+            };
+
+            let processed = match processed {
+                Ok(processed) => processed,
+                Err(e) => {
+                    debug!(source = %src, "Dropped SNMP datagram: {}", e);
+                    continue;
+                }
+            };
+
+            if let Some(response) = processed.response {
+                if let Err(e) = socket.send_to(&response, src).await {
+                    warn!(source = %src, "Failed to answer SNMP notification: {}", e);
+                }
+            }
+
+            if let Some(event) = processed.event {
+                debug!(
+                    task_name = %event.task_name,
+                    source = %src,
+                    "Received SNMP notification"
+                );
+                if self.event_sender.send(event).await.is_err() {
+                    debug!("SNMP trap event channel closed, stopping receiver");
+                    break;
+                }
+            }
+        }
+    }
+
+    /// Decode, authenticate and filter a single datagram
+    pub fn process_datagram(
+        &mut self,
+        data: &[u8],
+        src: SocketAddr,
+        sources: &[TrapSource],
+    ) -> Result<ProcessedDatagram> {
+        let source_ip = src.ip().to_canonical();
+        if !self.allowed_sources.is_empty() && !self.allowed_sources.contains(&source_ip) {
+            bail!("source {} is not in allowed_sources", source_ip);
+        }
+
+        let message = parse_message(data)?;
+
+        let version = match &message.security {
+            Security::Community(community) => {
+                if !self.config.communities.is_empty()
+                    && !self
+                        .config
+                        .communities
+                        .iter()
+                        .any(|c| c.as_bytes() == *community)
+                {
+                    bail!("unknown community");
+                }
+                if message.version == 0 {
+                    "v1"
+                } else {
+                    "v2c"
+                }
+            }
+            Security::Usm(header) => {
+                if let Some(report) = self.check_engine_id(&message, header)? {
+                    return Ok(ProcessedDatagram {
+                        event: None,
+                        response: Some(report),
+                    });
+                }
+                self.authenticate(data, header)?;
+                if let Some(report) = self.check_timeliness(&message, header)? {
+                    return Ok(ProcessedDatagram {
+                        event: None,
+                        response: Some(report),
+                    });
+                }
+                "v3"
+            }
+        };
+
+        let notification = match (message.version, message.pdu_tag) {
+            (0, PDU_TRAP_V1) => parse_v1_trap(message.pdu)?,
+            (1 | 3, PDU_TRAP_V2 | PDU_INFORM_REQUEST) => {
+                parse_v2_notification(message.pdu_tag, message.pdu)?
+            }
+            (_, tag) => bail!("unsupported PDU type 0x{:02x} for {}", tag, version),
+        };
+
+        // Informs are acknowledged even when filtered out so the sender stops retrying
+        let response = if message.pdu_tag == PDU_INFORM_REQUEST {
+            Some(self.build_inform_response(&message, &notification)?)
+        } else {
+            None
+        };
+
+        if !self.trap_oid_filters.is_empty()
+            && !self
+                .trap_oid_filters
+                .iter()
+                .any(|prefix| notification.trap_oid.starts_with(prefix))
+        {
+            debug!(
+                trap_oid = %oid_to_string(&notification.trap_oid),
+                "SNMP notification does not match trap_oids filter"
+            );
+            return Ok(ProcessedDatagram {
+                event: None,
+                response,
+            });
+        }
+
+        let (task_name, target_id) = sources
+            .iter()
+            .find(|s| s.ip == source_ip)
+            .map(|s| (s.task_name.clone(), s.target_id.clone()))
+            .unwrap_or_else(|| (UNMATCHED_TRAP_TASK_NAME.to_string(), None));
+
+        let now = current_timestamp();
+        let event = AggregatedMetrics::new(
+            task_name,
+            shared::config::TaskType::Snmp,
+            now,
+            now,
+            1,
+            AggregatedMetricData::SnmpTrap(SnmpTrapEventMetric {
+                event_id: 0,
+                source_address: src.to_string(),
+                version: version.to_string(),
+                pdu_type: if message.pdu_tag == PDU_INFORM_REQUEST {
+                    "inform".to_string()
+                } else {
+                    "trap".to_string()
+                },
+                trap_oid: oid_to_string(&notification.trap_oid),
+                uptime_ticks: notification.uptime_ticks,
+                varbinds: notification.varbinds,
+                target_id,
+            }),
+        );
+
+        Ok(ProcessedDatagram {
+            event: Some(event),
+            response,
+        })
+    }
+
+    /// Answer engine discovery for informs, which must target our engine ID.
+    /// Returns a report to send back when the sender has to learn our engine ID.
+    fn check_engine_id(
+        &mut self,
+        message: &Message<'_>,
+        header: &UsmHeader<'_>,
+    ) -> Result<Option<Vec<u8>>> {
+        let needs_our_engine = header.engine_id.is_empty()
+            || message.pdu_tag == PDU_INFORM_REQUEST
+            || message.pdu_tag == PDU_GET_REQUEST;
+        if !needs_our_engine || header.engine_id == self.engine_id.as_slice() {
+            return Ok(None);
+        }
+        if header.flags & FLAG_REPORTABLE == 0 {
+            bail!("unknown engine ID in non-reportable message");
+        }
+
+        self.unknown_engine_ids = self.unknown_engine_ids.wrapping_add(1);
+        debug!("Answering SNMPv3 engine discovery");
+        Ok(Some(self.build_report(
+            message,
+            header,
+            OID_USM_UNKNOWN_ENGINE_IDS,
+            self.unknown_engine_ids,
+            None,
+        )?))
+    }
+
+    /// Check the user and, for authenticated messages, the message authentication code
+    fn authenticate(&mut self, data: &[u8], header: &UsmHeader<'_>) -> Result<()> {
+        let user = self
+            .config
+            .v3_users
+            .iter()
+            .find(|u| u.username.as_bytes() == header.user_name)
+            .cloned()
+            .ok_or_else(|| {
+                anyhow!(
+                    "unknown SNMPv3 user '{}'",
+                    String::from_utf8_lossy(header.user_name)
+                )
+            })?;
+
+        if header.flags & FLAG_AUTH == 0 {
+            if user.security_level == SnmpSecurityLevel::AuthNoPriv {
+                bail!("user '{}' requires authentication", user.username);
+            }
+            return Ok(());
+        }
+
+        let (digest, mac_len) = auth_digest(&user.auth_protocol).ok_or_else(|| {
+            anyhow!(
+                "user '{}' has no authentication protocol configured",
+                user.username
+            )
+        })?;
+        if header.auth_params.len() != mac_len {
+            bail!("invalid authentication parameter length");
+        }
+
+        let key = self.localized_key(&user, digest, header.engine_id)?;
+        let mut message = data[..header.message_len].to_vec();
+        message[header.auth_params_offset..header.auth_params_offset + mac_len].fill(0);
+        let mac = hmac(digest, &key, &message)?;
+
+        if !openssl::memcmp::eq(&mac[..mac_len], header.auth_params) {
+            bail!("authentication failed for user '{}'", user.username);
+        }
+        Ok(())
+    }
+
+    /// Reject authenticated messages outside the time window (RFC 3414 3.2 step 7),
+    /// so that captured messages cannot be replayed.
+    ///
+    /// Informs are sent to our engine and compared with our own boots and time; the
+    /// sender gets a report carrying both so it can resynchronize, e.g. after an agent
+    /// restart. Traps are sent by the notifying engine itself, so its latest boots and
+    /// time are tracked from the messages that passed this check.
+    fn check_timeliness(
+        &mut self,
+        message: &Message<'_>,
+        header: &UsmHeader<'_>,
+    ) -> Result<Option<Vec<u8>>> {
+        if header.flags & FLAG_AUTH == 0 {
+            return Ok(None);
+        }
+
+        if header.engine_id == self.engine_id.as_slice() {
+            if header.engine_boots == LOCAL_ENGINE_BOOTS
+                && (header.engine_time - self.engine_time()).abs() <= TIME_WINDOW_SECONDS
+            {
+                return Ok(None);
+            }
+            if header.flags & FLAG_REPORTABLE == 0 {
+                bail!("message is outside the time window of our engine");
+            }
+
+            self.not_in_time_windows = self.not_in_time_windows.wrapping_add(1);
+            let auth = self.response_auth(header)?;
+            debug!("Answering SNMPv3 message outside the time window");
+            return Ok(Some(self.build_report(
+                message,
+                header,
+                OID_USM_NOT_IN_TIME_WINDOWS,
+                self.not_in_time_windows,
+                auth,
+            )?));
+        }
+
+        if header.engine_boots >= MAX_ENGINE_BOOTS {
+            bail!("sending engine's boots counter has reached its maximum");
+        }
+        let received = RemoteEngineTime {
+            boots: header.engine_boots,
+            time: header.engine_time,
+            received_at: Instant::now(),
+        };
+        let Some(latest) = self.remote_engine_times.get_mut(header.engine_id) else {
+            self.remote_engine_times
+                .insert(header.engine_id.to_vec(), received);
+            return Ok(None);
+        };
+
+        let expected_time = latest.time + latest.received_at.elapsed().as_secs() as i64;
+        if received.boots < latest.boots
+            || (received.boots == latest.boots
+                && received.time < expected_time - TIME_WINDOW_SECONDS)
+        {
+            bail!("message is outside the time window of the sending engine");
+        }
+        if received.boots > latest.boots || received.time > latest.time {
+            *latest = received;
+        }
+        Ok(None)
+    }
+
+    /// Seconds since our engine started (snmpEngineTime)
+    fn engine_time(&self) -> i64 {
+        self.started_at.elapsed().as_secs() as i64
+    }
+
+    /// Key localized to an engine ID (RFC 3414 A.2)
+    fn localized_key(
+        &mut self,
+        user: &SnmpTrapUser,
+        digest: MessageDigest,
+        engine_id: &[u8],
+    ) -> Result<Vec<u8>> {
+        if !self.user_keys.contains_key(&user.username) {
+            let password = user
+                .auth_password
+                .as_deref()
+                .ok_or_else(|| anyhow!("user '{}' has no auth_password", user.username))?;
+            let key = password_to_key(digest, password.as_bytes())?;
+            self.user_keys.insert(user.username.clone(), key);
+        }
+        let key = &self.user_keys[&user.username];
+
+        let mut hasher = Hasher::new(digest)?;
+        hasher.update(key)?;
+        hasher.update(engine_id)?;
+        hasher.update(key)?;
+        Ok(hasher.finish()?.to_vec())
+    }
+
+    /// Build the Response PDU acknowledging an inform
+    fn build_inform_response(
+        &mut self,
+        message: &Message<'_>,
+        notification: &Notification<'_>,
+    ) -> Result<Vec<u8>> {
+        let pdu = encode_pdu(
+            PDU_RESPONSE,
+            notification.request_id,
+            &tlv(TAG_SEQUENCE, notification.varbind_list),
+        );
+
+        match &message.security {
+            Security::Community(community) => {
+                let mut content = encode_integer(message.version);
+                content.extend(tlv(TAG_OCTET_STRING, community));
+                content.extend(pdu);
+                Ok(tlv(TAG_SEQUENCE, &content))
+            }
+            Security::Usm(header) => {
+                let auth = self.response_auth(header)?;
+                self.encode_v3_message(
+                    header.msg_id,
+                    header.flags & FLAG_AUTH,
+                    header.user_name,
+                    header.context_engine_id,
+                    header.context_name,
+                    &pdu,
+                    auth,
+                )
+            }
+        }
+    }
+
+    /// Key for authenticating our response to an authenticated message, localized to our engine
+    fn response_auth(
+        &mut self,
+        header: &UsmHeader<'_>,
+    ) -> Result<Option<(MessageDigest, usize, Vec<u8>)>> {
+        if header.flags & FLAG_AUTH == 0 {
+            return Ok(None);
+        }
+        let user = self
+            .config
+            .v3_users
+            .iter()
+            .find(|u| u.username.as_bytes() == header.user_name)
+            .cloned()
+            .ok_or_else(|| anyhow!("unknown SNMPv3 user"))?;
+        let (digest, mac_len) = auth_digest(&user.auth_protocol)
+            .ok_or_else(|| anyhow!("missing authentication protocol"))?;
+        let key = self.localized_key(&user, digest, &self.engine_id.clone())?;
+        Ok(Some((digest, mac_len, key)))
+    }
+
+    /// Build a Report PDU carrying one of our USM statistics counters
+    fn build_report(
+        &self,
+        message: &Message<'_>,
+        header: &UsmHeader<'_>,
+        counter_oid: &[u32],
+        counter: u32,
+        auth: Option<(MessageDigest, usize, Vec<u8>)>,
+    ) -> Result<Vec<u8>> {
+        let request_id = read_request_id(message.pdu)?;
+
+        let mut varbind = encode_oid(counter_oid);
+        varbind.extend(encode_unsigned(TAG_COUNTER32, counter as u64));
+        let varbinds = tlv(TAG_SEQUENCE, &tlv(TAG_SEQUENCE, &varbind));
+        let pdu = encode_pdu(PDU_REPORT, request_id, &varbinds);
+
+        let flags = if auth.is_some() { FLAG_AUTH } else { 0 };
+        self.encode_v3_message(
+            header.msg_id,
+            flags,
+            header.user_name,
+            &self.engine_id,
+            b"",
+            &pdu,
+            auth,
+        )
+    }
+
+    /// Encode an SNMPv3 message sent by us as the authoritative engine
+    #[allow(clippy::too_many_arguments)]
+    fn encode_v3_message(
+        &self,
+        msg_id: i64,
+        flags: u8,
+        user_name: &[u8],
+        context_engine_id: &[u8],
+        context_name: &[u8],
+        pdu: &[u8],
+        auth: Option<(MessageDigest, usize, Vec<u8>)>,
+    ) -> Result<Vec<u8>> {
+        let mut global = encode_integer(msg_id);
+        global.extend(encode_integer(MAX_DATAGRAM_SIZE as i64));
+        global.extend(tlv(TAG_OCTET_STRING, &[flags]));
+        global.extend(encode_integer(USM_SECURITY_MODEL));
+
+        let mac_len = auth.as_ref().map_or(0, |(_, len, _)| *len);
+        let mut security = tlv(TAG_OCTET_STRING, &self.engine_id);
+        security.extend(encode_integer(LOCAL_ENGINE_BOOTS));
+        security.extend(encode_integer(self.engine_time()));
+        security.extend(tlv(TAG_OCTET_STRING, user_name));
+        security.extend(tlv(TAG_OCTET_STRING, &vec![0u8; mac_len]));
+        security.extend(tlv(TAG_OCTET_STRING, b""));
+
+        let mut scoped = tlv(TAG_OCTET_STRING, context_engine_id);
+        scoped.extend(tlv(TAG_OCTET_STRING, context_name));
+        scoped.extend_from_slice(pdu);
+
+        let mut content = encode_integer(3);
+        content.extend(tlv(TAG_SEQUENCE, &global));
+        content.extend(tlv(TAG_OCTET_STRING, &tlv(TAG_SEQUENCE, &security)));
+        content.extend(tlv(TAG_SEQUENCE, &scoped));
+        let mut message = tlv(TAG_SEQUENCE, &content);
+
+        if let Some((digest, mac_len, key)) = auth {
+            // Locate the zeroed placeholder by decoding our own message
+            let offset = match parse_message(&message)?.security {
+                Security::Usm(header) => header.auth_params_offset,
+                Security::Community(_) => bail!("encoded message is not SNMPv3"),
+            };
+            let mac = hmac(digest, &key, &message)?;
+            message[offset..offset + mac_len].copy_from_slice(&mac[..mac_len]);
+        }
+
+        Ok(message)
+    }
+}
+
+/// Security information carried by a message
+enum Security<'a> {
+    Community(&'a [u8]),
+    Usm(UsmHeader<'a>),
+}
+
+/// SNMPv3 header fields needed for authentication and responses
+struct UsmHeader<'a> {
+    msg_id: i64,
+    flags: u8,
+    engine_id: &'a [u8],
+    engine_boots: i64,
+    engine_time: i64,
+    user_name: &'a [u8],
+    auth_params: &'a [u8],
+    /// Offset of `auth_params` within the message
+    auth_params_offset: usize,
+    /// Length of the whole message (trailing bytes are ignored)
+    message_len: usize,
+    context_engine_id: &'a [u8],
+    context_name: &'a [u8],
+}
+
+/// A decoded SNMP message
+struct Message<'a> {
+    version: i64,
+    security: Security<'a>,
+    pdu_tag: u8,
+    pdu: &'a [u8],
+}
+
+/// A decoded notification PDU
+struct Notification<'a> {
+    request_id: i64,
+    trap_oid: Vec<u32>,
+    uptime_ticks: Option<u32>,
+    varbinds: Vec<SnmpVarbind>,
+    /// Raw variable binding list, echoed back in inform responses
+    varbind_list: &'a [u8],
+}
+
+/// Minimal BER reader over a received datagram
+struct BerReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> BerReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.pos >= self.data.len()
+    }
+
+    /// Read the next element, returning its tag and content
+    fn read_tlv(&mut self) -> Result<(u8, &'a [u8])> {
+        let truncated = || anyhow!("truncated BER element");
+        let tag = *self.data.get(self.pos).ok_or_else(truncated)?;
+        let first = *self.data.get(self.pos + 1).ok_or_else(truncated)?;
+        let mut pos = self.pos + 2;
+
+        let len = if first & 0x80 == 0 {
+            first as usize
+        } else {
+            let count = (first & 0x7f) as usize;
+            if count == 0 || count > 4 {
+                bail!("unsupported BER length encoding");
+            }
+            let bytes = self.data.get(pos..pos + count).ok_or_else(truncated)?;
+            pos += count;
+            bytes.iter().fold(0usize, |acc, b| (acc << 8) | *b as usize)
+        };
+
+        let end = pos
+            .checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| anyhow!("BER element length exceeds datagram"))?;
+        self.pos = end;
+        Ok((tag, &self.data[pos..end]))
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<&'a [u8]> {
+        let (tag, content) = self.read_tlv()?;
+        if tag != expected {
+            bail!(
+                "unexpected BER tag 0x{:02x} (expected 0x{:02x})",
+                tag,
+                expected
+            );
+        }
+        Ok(content)
+    }
+
+    fn read_integer(&mut self) -> Result<i64> {
+        decode_integer(self.expect(TAG_INTEGER)?)
+    }
+
+    fn read_octets(&mut self) -> Result<&'a [u8]> {
+        self.expect(TAG_OCTET_STRING)
+    }
+}
+
+/// Decode an SNMP message header, leaving the PDU undecoded
+fn parse_message(data: &[u8]) -> Result<Message<'_>> {
+    let mut top = BerReader::new(data);
+    let mut reader = BerReader::new(top.expect(TAG_SEQUENCE)?);
+    let message_len = top.pos;
+    let version = reader.read_integer()?;
+
+    match version {
+        0 | 1 => {
+            let community = reader.read_octets()?;
+            let (pdu_tag, pdu) = reader.read_tlv()?;
+            Ok(Message {
+                version,
+                security: Security::Community(community),
+                pdu_tag,
+                pdu,
+            })
+        }
+        3 => {
+            let mut global = BerReader::new(reader.expect(TAG_SEQUENCE)?);
+            let msg_id = global.read_integer()?;
+            let _max_size = global.read_integer()?;
+            let flags = *global
+                .read_octets()?
+                .first()
+                .ok_or_else(|| anyhow!("empty msgFlags"))?;
+            if global.read_integer()? != USM_SECURITY_MODEL {
+                bail!("unsupported SNMPv3 security model");
+            }
+
+            let mut security_params = BerReader::new(reader.read_octets()?);
+            let mut usm = BerReader::new(security_params.expect(TAG_SEQUENCE)?);
+            let engine_id = usm.read_octets()?;
+            let engine_boots = usm.read_integer()?;
+            let engine_time = usm.read_integer()?;
+            let user_name = usm.read_octets()?;
+            let auth_params = usm.read_octets()?;
+            let _priv_params = usm.read_octets()?;
+            let auth_params_offset = auth_params.as_ptr() as usize - data.as_ptr() as usize;
+
+            if flags & FLAG_PRIV != 0 {
+                bail!("encrypted SNMPv3 notifications are not supported");
+            }
+
+            let mut scoped = BerReader::new(reader.expect(TAG_SEQUENCE)?);
+            let context_engine_id = scoped.read_octets()?;
+            let context_name = scoped.read_octets()?;
+            let (pdu_tag, pdu) = scoped.read_tlv()?;
+
+            Ok(Message {
+                version,
+                security: Security::Usm(UsmHeader {
+                    msg_id,
+                    flags,
+                    engine_id,
+                    engine_boots,
+                    engine_time,
+                    user_name,
+                    auth_params,
+                    auth_params_offset,
+                    message_len,
+                    context_engine_id,
+                    context_name,
+                }),
+                pdu_tag,
+                pdu,
+            })
+        }
+        other => bail!("unsupported SNMP version {}", other),
+    }
+}
+
+/// Read the request ID of a v2-style PDU
+fn read_request_id(pdu: &[u8]) -> Result<i64> {
+    BerReader::new(pdu).read_integer()
+}
+
+/// Decode an SNMPv1 Trap-PDU, translating the trap OID as described in RFC 3584
+fn parse_v1_trap(pdu: &[u8]) -> Result<Notification<'_>> {
+    let mut reader = BerReader::new(pdu);
+    let enterprise = decode_oid(reader.expect(TAG_OID)?)?;
+    let _agent_addr = reader.expect(TAG_IP_ADDRESS)?;
+    let generic_trap = reader.read_integer()?;
+    let specific_trap = reader.read_integer()?;
+    let timestamp = decode_unsigned(reader.expect(TAG_TIMETICKS)?)?;
+    let varbind_list = reader.expect(TAG_SEQUENCE)?;
+
+    // RFC 1157 defines generic-trap values 0 to 6 only
+    let trap_oid = match generic_trap {
+        6 => {
+            let specific_trap = u32::try_from(specific_trap)
+                .map_err(|_| anyhow!("invalid specific-trap value {}", specific_trap))?;
+            let mut oid = enterprise;
+            oid.push(0);
+            oid.push(specific_trap);
+            oid
+        }
+        0..=5 => {
+            let mut oid = OID_SNMP_TRAPS.to_vec();
+            oid.push(generic_trap as u32 + 1);
+            oid
+        }
+        _ => bail!("invalid generic-trap value {}", generic_trap),
+    };
+
+    let varbinds = decode_varbinds(varbind_list)?
+        .into_iter()
+        .map(|(oid, tag, content)| to_snmp_varbind(&oid, tag, content))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(Notification {
+        request_id: 0,
+        trap_oid,
+        uptime_ticks: Some(timestamp as u32),
+        varbinds,
+        varbind_list,
+    })
+}
+
+/// Decode an SNMPv2-Trap or InformRequest PDU
+fn parse_v2_notification(pdu_tag: u8, pdu: &[u8]) -> Result<Notification<'_>> {
+    let mut reader = BerReader::new(pdu);
+    let request_id = reader.read_integer()?;
+    let _error_status = reader.read_integer()?;
+    let _error_index = reader.read_integer()?;
+    let varbind_list = reader.expect(TAG_SEQUENCE)?;
+
+    let mut trap_oid = None;
+    let mut uptime_ticks = None;
+    let mut varbinds = Vec::new();
+    for (oid, tag, content) in decode_varbinds(varbind_list)? {
+        if oid == OID_SYS_UPTIME && tag == TAG_TIMETICKS {
+            uptime_ticks = Some(decode_unsigned(content)? as u32);
+        } else if oid == OID_SNMP_TRAP_OID && tag == TAG_OID {
+            trap_oid = Some(decode_oid(content)?);
+        } else {
+            varbinds.push(to_snmp_varbind(&oid, tag, content)?);
+        }
+    }
+
+    let trap_oid = trap_oid.ok_or_else(|| {
+        anyhow!(
+            "notification PDU 0x{:02x} is missing snmpTrapOID.0",
+            pdu_tag
+        )
+    })?;
+
+    Ok(Notification {
+        request_id,
+        trap_oid,
+        uptime_ticks,
+        varbinds,
+        varbind_list,
+    })
+}
+
+/// A variable binding as (OID, value tag, value content)
+type RawVarbind<'a> = (Vec<u32>, u8, &'a [u8]);
+
+/// Split a variable binding list into its variable bindings
+fn decode_varbinds(list: &[u8]) -> Result<Vec<RawVarbind<'_>>> {
+    let mut reader = BerReader::new(list);
+    let mut varbinds = Vec::new();
+    while !reader.is_empty() {
+        let mut varbind = BerReader::new(reader.expect(TAG_SEQUENCE)?);
+        let oid = decode_oid(varbind.expect(TAG_OID)?)?;
+        let (tag, content) = varbind.read_tlv()?;
+        varbinds.push((oid, tag, content));
+    }
+    Ok(varbinds)
+}
+
+/// Convert a decoded variable binding into its string representation
+fn to_snmp_varbind(oid: &[u32], tag: u8, content: &[u8]) -> Result<SnmpVarbind> {
+    let (value, value_type) = match tag {
+        TAG_INTEGER => (decode_integer(content)?.to_string(), "Integer"),
+        TAG_OCTET_STRING => (octets_to_string(content), "OctetString"),
+        TAG_NULL => ("null".to_string(), "Null"),
+        TAG_OID => (oid_to_string(&decode_oid(content)?), "ObjectIdentifier"),
+        TAG_IP_ADDRESS if content.len() == 4 => (
+            format!(
+                "{}.{}.{}.{}",
+                content[0], content[1], content[2], content[3]
+            ),
+            "IpAddress",
+        ),
+        TAG_COUNTER32 => (decode_unsigned(content)?.to_string(), "Counter32"),
+        TAG_GAUGE32 => (decode_unsigned(content)?.to_string(), "Unsigned32"),
+        TAG_TIMETICKS => (
+            crate::task_snmp::format_timeticks(decode_unsigned(content)? as u32),
+            "Timeticks",
+        ),
+        TAG_OPAQUE => (crate::task_snmp::bytes_to_hex(content), "Opaque"),
+        TAG_COUNTER64 => (decode_unsigned(content)?.to_string(), "Counter64"),
+        TAG_NO_SUCH_OBJECT => ("noSuchObject".to_string(), "NoSuchObject"),
+        TAG_NO_SUCH_INSTANCE => ("noSuchInstance".to_string(), "NoSuchInstance"),
+        TAG_END_OF_MIB_VIEW => ("endOfMibView".to_string(), "EndOfMibView"),
+        _ => (crate::task_snmp::bytes_to_hex(content), "Unknown"),
+    };
+
+    Ok(SnmpVarbind {
+        oid: oid_to_string(oid),
+        value,
+        value_type: value_type.to_string(),
+    })
+}
+
+/// Render an OCTET STRING as text when printable, hex otherwise
+fn octets_to_string(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(s) if s.chars().all(|c| !c.is_control() || c == '\n' || c == '\t') => s.to_string(),
+        _ => crate::task_snmp::bytes_to_hex(bytes),
+    }
+}
+
+fn decode_integer(bytes: &[u8]) -> Result<i64> {
+    if bytes.is_empty() || bytes.len() > 8 {
+        bail!("invalid INTEGER length {}", bytes.len());
+    }
+    let initial: i64 = if bytes[0] & 0x80 != 0 { -1 } else { 0 };
+    Ok(bytes.iter().fold(initial, |acc, b| (acc << 8) | *b as i64))
+}
+
+fn decode_unsigned(bytes: &[u8]) -> Result<u64> {
+    let bytes = match bytes {
+        [0, rest @ ..] if !rest.is_empty() => rest,
+        _ => bytes,
+    };
+    if bytes.is_empty() || bytes.len() > 8 {
+        bail!("invalid unsigned integer length {}", bytes.len());
+    }
+    Ok(bytes.iter().fold(0u64, |acc, b| (acc << 8) | *b as u64))
+}
+
+fn decode_oid(bytes: &[u8]) -> Result<Vec<u32>> {
+    let mut subids = Vec::new();
+    let mut value: u64 = 0;
+    for (i, b) in bytes.iter().enumerate() {
+        value = (value << 7) | (b & 0x7f) as u64;
+        if value > u32::MAX as u64 {
+            bail!("OID sub-identifier too large");
+        }
+        if b & 0x80 == 0 {
+            if subids.is_empty() {
+                let first = (value / 40).min(2);
+                subids.push(first as u32);
+                subids.push((value - first * 40) as u32);
+            } else {
+                subids.push(value as u32);
+            }
+            value = 0;
+        } else if i == bytes.len() - 1 {
+            bail!("truncated OID");
+        }
+    }
+    if subids.is_empty() {
+        bail!("empty OID");
+    }
+    Ok(subids)
+}
+
+fn oid_to_string(oid: &[u32]) -> String {
+    oid.iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Parse a dotted OID string such as "1.3.6.1.6.3.1.1.5"
+fn parse_oid(oid: &str) -> Result<Vec<u32>> {
+    oid.strip_prefix('.')
+        .unwrap_or(oid)
+        .split('.')
+        .map(|s| {
+            s.parse::<u32>()
+                .with_context(|| format!("Invalid OID component: {}", s))
+        })
+        .collect()
+}
+
+/// Encode a BER element with definite length
+pub(crate) fn tlv(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut out = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        out.push(len as u8);
+    } else {
+        let bytes = (len as u32).to_be_bytes();
+        let skip = bytes.iter().take_while(|b| **b == 0).count();
+        out.push(0x80 | (4 - skip) as u8);
+        out.extend_from_slice(&bytes[skip..]);
+    }
+    out.extend_from_slice(content);
+    out
+}
+
+pub(crate) fn encode_integer(value: i64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let mut start = 0;
+    // Drop redundant leading bytes while keeping the sign bit intact
+    while start < 7
+        && ((bytes[start] == 0x00 && bytes[start + 1] & 0x80 == 0)
+            || (bytes[start] == 0xff && bytes[start + 1] & 0x80 != 0))
+    {
+        start += 1;
+    }
+    tlv(TAG_INTEGER, &bytes[start..])
+}
+
+pub(crate) fn encode_unsigned(tag: u8, value: u64) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let skip = bytes.iter().take_while(|b| **b == 0).count().min(7);
+    let mut content = Vec::with_capacity(9);
+    if bytes[skip] & 0x80 != 0 {
+        content.push(0);
+    }
+    content.extend_from_slice(&bytes[skip..]);
+    tlv(tag, &content)
+}
+
+pub(crate) fn encode_oid(oid: &[u32]) -> Vec<u8> {
+    let mut content = Vec::new();
+    let mut subids = Vec::with_capacity(oid.len());
+    match oid {
+        [first, second, rest @ ..] => {
+            subids.push(first * 40 + second);
+            subids.extend_from_slice(rest);
+        }
+        _ => subids.extend_from_slice(oid),
+    }
+    for subid in subids {
+        let mut chunk = vec![(subid & 0x7f) as u8];
+        let mut rest = subid >> 7;
+        while rest > 0 {
+            chunk.push(0x80 | (rest & 0x7f) as u8);
+            rest >>= 7;
+        }
+        chunk.reverse();
+        content.extend(chunk);
+    }
+    tlv(TAG_OID, &content)
+}
+
+/// Encode a v2-style PDU with no error
+pub(crate) fn encode_pdu(tag: u8, request_id: i64, varbind_list: &[u8]) -> Vec<u8> {
+    let mut content = encode_integer(request_id);
+    content.extend(encode_integer(0));
+    content.extend(encode_integer(0));
+    content.extend_from_slice(varbind_list);
+    tlv(tag, &content)
+}
+
+/// Digest and truncated MAC length for an authentication protocol
+/// (RFC 3414 for MD5/SHA-1, RFC 7860 for the SHA-2 family)
+pub(crate) fn auth_digest(protocol: &SnmpAuthProtocol) -> Option<(MessageDigest, usize)> {
+    match protocol {
+        SnmpAuthProtocol::None => None,
+        SnmpAuthProtocol::Md5 => Some((MessageDigest::md5(), 12)),
+        SnmpAuthProtocol::Sha1 => Some((MessageDigest::sha1(), 12)),
+        SnmpAuthProtocol::Sha224 => Some((MessageDigest::sha224(), 16)),
+        SnmpAuthProtocol::Sha256 => Some((MessageDigest::sha256(), 24)),
+        SnmpAuthProtocol::Sha384 => Some((MessageDigest::sha384(), 32)),
+        SnmpAuthProtocol::Sha512 => Some((MessageDigest::sha512(), 48)),
+    }
+}
+
+/// Derive the (non-localized) key from a password by hashing 1 MiB of it (RFC 3414 A.2)
+pub(crate) fn password_to_key(digest: MessageDigest, password: &[u8]) -> Result<Vec<u8>> {
+    if password.is_empty() {
+        bail!("authentication password cannot be empty");
+    }
+    let mut hasher = Hasher::new(digest)?;
+    let mut block = [0u8; 64];
+    let mut index = 0;
+    for _ in 0..(1_048_576 / block.len()) {
+        for byte in block.iter_mut() {
+            *byte = password[index % password.len()];
+            index += 1;
+        }
+        hasher.update(&block)?;
+    }
+    Ok(hasher.finish()?.to_vec())
+}
+
+pub(crate) fn hmac(digest: MessageDigest, key: &[u8], data: &[u8]) -> Result<Vec<u8>> {
+    let pkey = PKey::hmac(key)?;
+    let mut signer = Signer::new(digest, &pkey)?;
+    signer.update(data)?;
+    Ok(signer.sign_to_vec()?)
+}
+
+/// Engine ID derived from the agent ID (RFC 3411 text format, at most 32 bytes)
+pub(crate) fn local_engine_id(agent_id: &str) -> Vec<u8> {
+    let mut engine_id = vec![0x80, 0x00, 0x00, 0x00, 0x04];
+    engine_id.extend(agent_id.as_bytes().iter().take(27));
+    engine_id
+}
+
+fn current_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -2,36 +2,388 @@
 //!
 //! This module implements network bandwidth testing through server coordination.
 //! The agent requests permission from the server to perform a bandwidth test,
-//! then downloads test data to measure throughput.
+//! then downloads and/or uploads test data to measure throughput. When both
+//! directions are measured, each phase holds its own server test slot so that
+//! the two transfers never overlap with other agents' tests.
+//!
+//! Instead of the central server, a test can target a known file on a
+//! third-party HTTP(S) server or another agent running the bandwidth reflector
+//! (see `bandwidth_reflector`). Such tests only hold a server slot when
+//! `coordinate_with_server` is set, and release it explicitly afterwards.
+//!
+//! Each phase may use several parallel HTTP streams to saturate long fat pipes.
+//! Test data in both directions is pseudo-random so that compressing WAN
+//! optimizers cannot inflate the result. Besides the aggregate throughput, the
+//! per-stream throughput and a time series sampled during the transfer are
+//! reported.
 
 use anyhow::Result;
 use chrono::Utc;
-use shared::config::BandwidthParams;
+use futures_util::StreamExt;
+use shared::config::{BandwidthParams, BandwidthTarget};
 use shared::metrics::RawBandwidthMetric;
-use std::time::Duration;
-use tracing::debug;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, warn};
+
+/// Size of the chunks streamed during upload
+const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Interval between throughput samples of the time series
+pub(crate) const THROUGHPUT_SAMPLE_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Result of a single transfer phase across all streams
+struct TransferResult {
+    /// Aggregate throughput of all streams
+    mbps: f64,
+    duration_ms: f64,
+    bytes: u64,
+    stream_mbps: Vec<f64>,
+    series_mbps: Vec<f64>,
+}
+
+/// Result of one transfer stream
+struct StreamResult {
+    bytes: u64,
+    duration_ms: f64,
+}
+
+/// Central server connection used to coordinate bandwidth tests
+#[derive(Debug, Clone, Copy)]
+pub struct ServerConnection<'a> {
+    pub url: &'a str,
+    pub api_key: &'a str,
+    pub agent_id: &'a str,
+}
 
-/// Execute bandwidth measurement task with server coordination
+/// How the streams of one transfer phase reach the target
+struct PhaseEndpoint {
+    /// URL every stream is sent to
+    url: String,
+    /// Query parameters sent with every stream
+    query: Vec<(&'static str, String)>,
+    /// API key header sent with every stream, if any
+    api_key: Option<String>,
+    /// Whether the target reports the bytes it received in a `BandwidthUploadResponse`
+    reports_upload: bool,
+}
+
+/// Execute bandwidth measurement task
 ///
-/// Implements a retry loop to handle server queueing:
-/// 1. Requests permission from server to start test
-/// 2. If server says "Delay" → waits and retries
-/// 3. If server says "Proceed" → downloads test data and measures throughput
-/// 4. Respects task timeout and maximum retry attempts
+/// For each direction selected by `params.direction`:
+/// 1. If the test coordinates with the server, requests a test slot and waits
+///    while the server says "Delay", respecting the task timeout and maximum
+///    retry attempts
+/// 2. Transfers test data over `parallel_streams` streams to the target (the
+///    central server, a third-party HTTP(S) server or another agent's
+///    reflector) and measures throughput
+/// 3. Releases a server slot held for a test against another target
 ///
-/// Returns raw bandwidth metric with speed in Mbps.
+/// `server` is only required for coordinated tests. Returns raw bandwidth
+/// metric with download and/or upload speed in Mbps.
 pub async fn execute_bandwidth_task(
     params: &BandwidthParams,
-    server_url: &str,
-    api_key: &str,
+    server: Option<ServerConnection<'_>>,
     agent_id: &str,
 ) -> Result<RawBandwidthMetric> {
+    let start_time = Instant::now();
+    let client = reqwest::Client::new();
+
+    if params.coordinates_with_server() && server.is_none() {
+        return Err(anyhow::anyhow!(
+            "Bandwidth test coordinated with the server requires server configuration (not available in local-only mode)"
+        ));
+    }
+
+    let mut metric = RawBandwidthMetric {
+        bandwidth_mbps: None,
+        duration_ms: None,
+        bytes_downloaded: None,
+        upload_mbps: None,
+        upload_duration_ms: None,
+        bytes_uploaded: None,
+        parallel_streams: Some(params.parallel_streams),
+        stream_mbps: None,
+        throughput_series_mbps: None,
+        upload_stream_mbps: None,
+        upload_throughput_series_mbps: None,
+        target_url: params.target_url().map(str::to_string),
+        success: true,
+        error: None,
+        target_id: params.effective_target_id(),
+    };
+
+    if params.direction.includes_download() {
+        let download = run_phase(&client, params, server, agent_id, start_time, |size| {
+            download_endpoint(params, server, agent_id, size)
+        })
+        .await?;
+        metric.bandwidth_mbps = Some(download.mbps);
+        metric.duration_ms = Some(download.duration_ms);
+        metric.bytes_downloaded = Some(download.bytes);
+        metric.stream_mbps = Some(download.stream_mbps);
+        metric.throughput_series_mbps = Some(download.series_mbps);
+    }
+
+    if params.direction.includes_upload() {
+        let upload = run_phase(&client, params, server, agent_id, start_time, |size| {
+            upload_endpoint(params, server, agent_id, size)
+        })
+        .await;
+
+        match upload {
+            Ok(upload) => {
+                metric.upload_mbps = Some(upload.mbps);
+                metric.upload_duration_ms = Some(upload.duration_ms);
+                metric.bytes_uploaded = Some(upload.bytes);
+                metric.upload_stream_mbps = Some(upload.stream_mbps);
+                metric.upload_throughput_series_mbps = Some(upload.series_mbps);
+            }
+            // Keep the download measurement when only the upload phase failed
+            Err(e) if metric.bandwidth_mbps.is_some() => {
+                metric.success = false;
+                metric.error = Some(format!("Upload phase failed: {}", e));
+            }
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(metric)
+}
+
+/// Direction of a transfer phase, chosen by the endpoint builder passed to `run_phase`
+enum Phase {
+    /// Download whatever the target sends
+    Download(PhaseEndpoint),
+    /// Upload the given number of bytes in total
+    Upload(PhaseEndpoint, u64),
+}
+
+/// Runs one transfer phase, holding a server slot around it when coordinated
+///
+/// `endpoint` receives the test size granted by the server, if any.
+async fn run_phase(
+    client: &reqwest::Client,
+    params: &BandwidthParams,
+    server: Option<ServerConnection<'_>>,
+    agent_id: &str,
+    start_time: Instant,
+    endpoint: impl FnOnce(Option<u64>) -> Result<Phase>,
+) -> Result<TransferResult> {
+    let coordinated = params.coordinates_with_server();
+    let server_size = match server {
+        Some(server) if coordinated => {
+            Some(request_permission(client, params, server, start_time).await?)
+        }
+        _ => None,
+    };
+
+    let result = async {
+        match endpoint(server_size)? {
+            Phase::Download(endpoint) => run_download(client, params, &endpoint, start_time).await,
+            Phase::Upload(endpoint, size) => {
+                run_upload(client, params, &endpoint, size, start_time).await
+            }
+        }
+    }
+    .await;
+
+    // Transfers through the server release the slot themselves
+    if let Some(server) = server.filter(|_| coordinated && params.target_url().is_some()) {
+        if let Err(e) = release_slot(client, server).await {
+            warn!(
+                agent_id = %agent_id,
+                error = %e,
+                "Failed to release bandwidth test slot, the server will expire it"
+            );
+        }
+    }
+
+    result
+}
+
+/// Total test size for targets where the agent chooses it
+fn agent_chosen_size(params: &BandwidthParams, server_size: Option<u64>) -> u64 {
+    match (params.data_size_mb, server_size) {
+        (Some(size_mb), _) => size_mb as u64 * 1024 * 1024,
+        (None, Some(server_size)) => server_size,
+        (None, None) => shared::defaults::default_bandwidth_size() as u64 * 1024 * 1024,
+    }
+}
+
+/// API key sent to a reflector
+fn reflector_api_key(
+    params: &BandwidthParams,
+    server: Option<ServerConnection<'_>>,
+) -> Result<String> {
+    params
+        .reflector_api_key
+        .clone()
+        .or_else(|| server.map(|s| s.api_key.to_string()))
+        .filter(|key| !key.is_empty())
+        .ok_or_else(|| {
+            anyhow::anyhow!("Bandwidth test against a reflector requires 'reflector_api_key' in local-only mode")
+        })
+}
+
+fn reflector_endpoint(
+    params: &BandwidthParams,
+    server: Option<ServerConnection<'_>>,
+    agent_id: &str,
+    base_url: &str,
+    path: &str,
+    size: u64,
+) -> Result<PhaseEndpoint> {
+    let stream_size = shared::utils::bandwidth_stream_size(size, params.parallel_streams);
+    Ok(PhaseEndpoint {
+        url: format!("{}{}", base_url.trim_end_matches('/'), path),
+        query: vec![
+            ("agent_id", agent_id.to_string()),
+            (
+                crate::bandwidth_reflector::SIZE_QUERY_PARAM,
+                stream_size.to_string(),
+            ),
+        ],
+        api_key: Some(reflector_api_key(params, server)?),
+        reports_upload: true,
+    })
+}
+
+/// Builds the download phase for the configured target
+fn download_endpoint(
+    params: &BandwidthParams,
+    server: Option<ServerConnection<'_>>,
+    agent_id: &str,
+    server_size: Option<u64>,
+) -> Result<Phase> {
+    match params.target() {
+        // The server controls the download size via its configuration.
+        // We only send agent_id for identification, not size parameters.
+        BandwidthTarget::Server => {
+            let server = server.ok_or_else(|| anyhow::anyhow!("Server configuration missing"))?;
+            Ok(Phase::Download(PhaseEndpoint {
+                url: format!(
+                    "{}{}",
+                    server.url,
+                    shared::api::endpoints::BANDWIDTH_DOWNLOAD
+                ),
+                query: vec![("agent_id", server.agent_id.to_string())],
+                api_key: None,
+                reports_upload: false,
+            }))
+        }
+        // Every stream downloads the whole file
+        BandwidthTarget::Url { download, .. } => Ok(Phase::Download(PhaseEndpoint {
+            url: download
+                .ok_or_else(|| anyhow::anyhow!("Bandwidth download requires 'url'"))?
+                .to_string(),
+            query: Vec::new(),
+            api_key: None,
+            reports_upload: false,
+        })),
+        BandwidthTarget::Reflector(base_url) => Ok(Phase::Download(reflector_endpoint(
+            params,
+            server,
+            agent_id,
+            base_url,
+            shared::api::endpoints::BANDWIDTH_DOWNLOAD,
+            agent_chosen_size(params, server_size),
+        )?)),
+    }
+}
+
+/// Builds the upload phase for the configured target
+fn upload_endpoint(
+    params: &BandwidthParams,
+    server: Option<ServerConnection<'_>>,
+    agent_id: &str,
+    server_size: Option<u64>,
+) -> Result<Phase> {
+    match params.target() {
+        BandwidthTarget::Server => {
+            let server = server.ok_or_else(|| anyhow::anyhow!("Server configuration missing"))?;
+            let size = server_size
+                .ok_or_else(|| anyhow::anyhow!("Server did not provide test data size"))?;
+            Ok(Phase::Upload(
+                PhaseEndpoint {
+                    url: format!("{}{}", server.url, shared::api::endpoints::BANDWIDTH_UPLOAD),
+                    query: vec![("agent_id", server.agent_id.to_string())],
+                    api_key: Some(server.api_key.to_string()),
+                    reports_upload: true,
+                },
+                size,
+            ))
+        }
+        BandwidthTarget::Url { upload, .. } => Ok(Phase::Upload(
+            PhaseEndpoint {
+                url: upload
+                    .ok_or_else(|| anyhow::anyhow!("Bandwidth upload requires 'upload_url'"))?
+                    .to_string(),
+                query: Vec::new(),
+                api_key: None,
+                reports_upload: false,
+            },
+            agent_chosen_size(params, server_size),
+        )),
+        BandwidthTarget::Reflector(base_url) => {
+            let size = agent_chosen_size(params, server_size);
+            Ok(Phase::Upload(
+                reflector_endpoint(
+                    params,
+                    server,
+                    agent_id,
+                    base_url,
+                    shared::api::endpoints::BANDWIDTH_UPLOAD,
+                    size,
+                )?,
+                size,
+            ))
+        }
+    }
+}
+
+/// Tells the server the coordinated test is over so the slot is freed
+async fn release_slot(client: &reqwest::Client, server: ServerConnection<'_>) -> Result<()> {
+    let request = shared::api::BandwidthCompleteRequest {
+        agent_id: server.agent_id.to_string(),
+        timestamp_utc: Utc::now().to_rfc3339(),
+    };
+
+    let response = client
+        .post(format!(
+            "{}{}",
+            server.url,
+            shared::api::endpoints::BANDWIDTH_COMPLETE
+        ))
+        .header(shared::api::headers::API_KEY, server.api_key)
+        .json(&request)
+        .timeout(Duration::from_secs(10))
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Server rejected bandwidth completion: {}",
+            response.status()
+        ));
+    }
+    Ok(())
+}
+
+/// Waits for the server to grant a bandwidth test slot
+///
+/// Returns the test data size in bytes chosen by the server.
+async fn request_permission(
+    client: &reqwest::Client,
+    params: &BandwidthParams,
+    server: ServerConnection<'_>,
+    start_time: Instant,
+) -> Result<u64> {
     let total_timeout_secs = params.timeout_seconds as u64;
     let max_retries = params.max_retries;
-    let start_time = std::time::Instant::now();
     let permission_timeout = Duration::from_secs(10); // Fixed 10s timeout for permission requests
-
-    let client = reqwest::Client::new();
     let mut retry_count = 0;
 
     // Retry loop for requesting permission from server
@@ -61,17 +413,18 @@ pub async fn execute_bandwidth_task(
         );
 
         let test_request = shared::api::BandwidthTestRequest {
-            agent_id: agent_id.to_string(),
+            agent_id: server.agent_id.to_string(),
             timestamp_utc: Utc::now().to_rfc3339(),
+            parallel_streams: params.parallel_streams,
         };
 
         let response = client
             .post(format!(
                 "{}{}",
-                server_url,
+                server.url,
                 shared::api::endpoints::BANDWIDTH_TEST
             ))
-            .header(shared::api::headers::API_KEY, api_key)
+            .header(shared::api::headers::API_KEY, server.api_key)
             .header("Content-Type", "application/json")
             .json(&test_request)
             .timeout(permission_timeout)
@@ -121,30 +474,145 @@ pub async fn execute_bandwidth_task(
                     "Server granted bandwidth test permission after {} attempts",
                     retry_count + 1
                 );
-                break;
+                return test_response
+                    .data_size_bytes
+                    .ok_or_else(|| anyhow::anyhow!("Server did not provide test data size"));
+            }
+        }
+    }
+}
+
+/// Timeout left for a transfer, accounting for time already spent
+fn remaining_timeout(params: &BandwidthParams, start_time: Instant) -> Duration {
+    let elapsed = start_time.elapsed().as_secs();
+    Duration::from_secs((params.timeout_seconds as u64).saturating_sub(elapsed))
+}
+
+/// Calculate bandwidth in Mbps: (bytes * 8) / (seconds) / 1,000,000
+pub(crate) fn to_mbps(bytes: u64, duration_ms: f64) -> f64 {
+    if duration_ms > 0.0 {
+        (bytes as f64 * 8.0) / (duration_ms / 1000.0) / 1_000_000.0
+    } else {
+        0.0
+    }
+}
+
+/// Drives `transfer` to completion while sampling the shared byte counter
+///
+/// Returns the transfer output and the throughput in Mbps of every sample
+/// interval. The last sample covers the partial interval before completion.
+pub(crate) async fn sample_throughput<F: Future>(
+    transfer: F,
+    bytes_transferred: &AtomicU64,
+) -> (F::Output, Vec<f64>) {
+    let mut series = Vec::new();
+    let mut last_bytes = 0;
+    let mut last_sample = Instant::now();
+    let mut record_sample = |series: &mut Vec<f64>| {
+        let bytes = bytes_transferred.load(Ordering::Relaxed);
+        let elapsed_ms = last_sample.elapsed().as_secs_f64() * 1000.0;
+        series.push(to_mbps(bytes - last_bytes, elapsed_ms));
+        last_bytes = bytes;
+        last_sample = Instant::now();
+        bytes
+    };
+
+    let mut interval = tokio::time::interval(THROUGHPUT_SAMPLE_INTERVAL);
+    interval.tick().await; // First tick completes immediately
+    tokio::pin!(transfer);
+    let mut sampled_bytes = 0;
+
+    loop {
+        tokio::select! {
+            output = &mut transfer => {
+                if bytes_transferred.load(Ordering::Relaxed) > sampled_bytes {
+                    record_sample(&mut series);
+                }
+                return (output, series);
             }
+            _ = interval.tick() => sampled_bytes = record_sample(&mut series),
         }
     }
+}
 
-    // Step 2: Download test data and measure bandwidth
-    // Note: The server controls the download size via its configuration.
-    // We only send agent_id for identification, not size parameters.
+/// Combines per-stream results into the phase result
+fn combine_streams(
+    streams: Vec<StreamResult>,
+    duration_ms: f64,
+    series_mbps: Vec<f64>,
+) -> TransferResult {
+    let bytes = streams.iter().map(|s| s.bytes).sum();
+    TransferResult {
+        mbps: to_mbps(bytes, duration_ms),
+        duration_ms,
+        bytes,
+        stream_mbps: streams
+            .iter()
+            .map(|s| to_mbps(s.bytes, s.duration_ms))
+            .collect(),
+        series_mbps,
+    }
+}
 
-    // Calculate remaining timeout for download (accounting for time spent in retry loop)
-    let elapsed_in_retries = start_time.elapsed().as_secs();
-    let remaining_timeout_secs = total_timeout_secs.saturating_sub(elapsed_in_retries);
+/// Downloads test data over parallel streams and measures throughput
+async fn run_download(
+    client: &reqwest::Client,
+    params: &BandwidthParams,
+    endpoint: &PhaseEndpoint,
+    start_time: Instant,
+) -> Result<TransferResult> {
+    let download_timeout = remaining_timeout(params, start_time);
+    let bytes_transferred = AtomicU64::new(0);
+    let download_start = Instant::now();
 
-    let download_timeout = Duration::from_secs(remaining_timeout_secs);
-    let download_start = std::time::Instant::now();
+    let streams = (0..params.parallel_streams)
+        .map(|_| download_stream(client, endpoint, download_timeout, &bytes_transferred));
+    let (streams, series_mbps) = sample_throughput(
+        futures_util::future::try_join_all(streams),
+        &bytes_transferred,
+    )
+    .await;
+    let result = combine_streams(
+        streams?,
+        download_start.elapsed().as_millis() as f64,
+        series_mbps,
+    );
 
-    let download_response = client
-        .get(format!(
-            "{}{}",
-            server_url,
-            shared::api::endpoints::BANDWIDTH_DOWNLOAD
-        ))
-        .query(&[("agent_id", agent_id)])
-        .timeout(download_timeout)
+    debug!(
+        "Bandwidth download from {} completed: {} bytes over {} streams in {:.2}ms = {:.2} Mbps",
+        endpoint.url, result.bytes, params.parallel_streams, result.duration_ms, result.mbps
+    );
+
+    Ok(result)
+}
+
+/// Builds the request of one stream
+fn stream_request(
+    client: &reqwest::Client,
+    method: reqwest::Method,
+    endpoint: &PhaseEndpoint,
+    timeout: Duration,
+) -> reqwest::RequestBuilder {
+    let mut request = client
+        .request(method, &endpoint.url)
+        .query(&endpoint.query)
+        .timeout(timeout);
+    if let Some(api_key) = &endpoint.api_key {
+        request = request.header(shared::api::headers::API_KEY, api_key);
+    }
+    request
+}
+
+/// Downloads one stream of test data, adding received bytes to `bytes_transferred`
+async fn download_stream(
+    client: &reqwest::Client,
+    endpoint: &PhaseEndpoint,
+    timeout: Duration,
+    bytes_transferred: &AtomicU64,
+) -> Result<StreamResult> {
+    let stream_start = Instant::now();
+
+    let download_response = stream_request(client, reqwest::Method::GET, endpoint, timeout)
         .send()
         .await
         .map_err(|e| anyhow::anyhow!("Failed to start bandwidth download: {}", e))?;
@@ -158,8 +626,6 @@ pub async fn execute_bandwidth_task(
 
     // Stream bytes without buffering entire response in memory
     // This prevents memory leaks for large bandwidth test files (e.g., 100MB+)
-    use futures_util::StreamExt;
-
     let mut bytes_downloaded: u64 = 0;
     let mut stream = download_response.bytes_stream();
 
@@ -167,29 +633,114 @@ pub async fn execute_bandwidth_task(
         let chunk = chunk_result
             .map_err(|e| anyhow::anyhow!("Failed to download bandwidth test data: {}", e))?;
         bytes_downloaded += chunk.len() as u64;
+        bytes_transferred.fetch_add(chunk.len() as u64, Ordering::Relaxed);
     }
 
-    let duration = download_start.elapsed();
-    let duration_ms = duration.as_millis() as f64;
+    Ok(StreamResult {
+        bytes: bytes_downloaded,
+        duration_ms: stream_start.elapsed().as_millis() as f64,
+    })
+}
 
-    // Calculate bandwidth in Mbps: (bytes * 8) / (seconds) / 1,000,000
-    let bandwidth_mbps = if duration_ms > 0.0 {
-        (bytes_downloaded as f64 * 8.0) / (duration_ms / 1000.0) / 1_000_000.0
-    } else {
-        0.0
-    };
+/// Uploads `data_size_bytes` of test data over parallel streams
+///
+/// The data is split evenly between the streams, as the server does for
+/// downloads. The measured time of each stream ends when the target
+/// acknowledges receiving its whole body.
+async fn run_upload(
+    client: &reqwest::Client,
+    params: &BandwidthParams,
+    endpoint: &PhaseEndpoint,
+    data_size_bytes: u64,
+    start_time: Instant,
+) -> Result<TransferResult> {
+    let upload_timeout = remaining_timeout(params, start_time);
+    let stream_size =
+        shared::utils::bandwidth_stream_size(data_size_bytes, params.parallel_streams);
+    let bytes_transferred = Arc::new(AtomicU64::new(0));
+    let upload_start = Instant::now();
+
+    let streams = (0..params.parallel_streams).map(|_| {
+        upload_stream(
+            client,
+            endpoint,
+            stream_size,
+            upload_timeout,
+            bytes_transferred.clone(),
+        )
+    });
+    let (streams, series_mbps) = sample_throughput(
+        futures_util::future::try_join_all(streams),
+        &bytes_transferred,
+    )
+    .await;
+    let result = combine_streams(
+        streams?,
+        upload_start.elapsed().as_millis() as f64,
+        series_mbps,
+    );
 
     debug!(
-        "Bandwidth test completed: {} bytes in {:.2}ms = {:.2} Mbps",
-        bytes_downloaded, duration_ms, bandwidth_mbps
+        "Bandwidth upload to {} completed: {} bytes over {} streams in {:.2}ms = {:.2} Mbps",
+        endpoint.url, result.bytes, params.parallel_streams, result.duration_ms, result.mbps
     );
 
-    Ok(RawBandwidthMetric {
-        bandwidth_mbps: Some(bandwidth_mbps),
-        duration_ms: Some(duration_ms),
-        bytes_downloaded: Some(bytes_downloaded),
-        success: true,
-        error: None,
-        target_id: params.target_id.clone(),
+    Ok(result)
+}
+
+/// Uploads one stream of `size` bytes of incompressible test data
+///
+/// Chunks are generated on demand from a random seed so memory usage stays
+/// constant regardless of the test size. Bytes handed to the HTTP client are
+/// added to `bytes_transferred`. Targets that do not report the received byte
+/// count are credited with the full size once they answer with success.
+async fn upload_stream(
+    client: &reqwest::Client,
+    endpoint: &PhaseEndpoint,
+    size: u64,
+    timeout: Duration,
+    bytes_transferred: Arc<AtomicU64>,
+) -> Result<StreamResult> {
+    let total_size = size as usize;
+    let seed: u64 = rand::random();
+    let byte_stream =
+        futures_util::stream::iter((0..total_size.div_ceil(UPLOAD_CHUNK_SIZE)).map(move |i| {
+            let chunk_len = std::cmp::min(total_size - i * UPLOAD_CHUNK_SIZE, UPLOAD_CHUNK_SIZE);
+            let mut chunk = vec![0u8; chunk_len];
+            shared::utils::fill_incompressible(seed, i as u64, &mut chunk);
+            bytes_transferred.fetch_add(chunk_len as u64, Ordering::Relaxed);
+            Ok::<_, std::io::Error>(chunk)
+        }));
+
+    let stream_start = Instant::now();
+
+    let upload_response = stream_request(client, reqwest::Method::POST, endpoint, timeout)
+        .header("Content-Type", "application/octet-stream")
+        .header(reqwest::header::CONTENT_LENGTH, size)
+        .body(reqwest::Body::wrap_stream(byte_stream))
+        .send()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to upload bandwidth test data: {}", e))?;
+
+    if !upload_response.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Bandwidth upload failed: {}",
+            upload_response.status()
+        ));
+    }
+
+    let bytes = if endpoint.reports_upload {
+        let upload_result: shared::api::BandwidthUploadResponse = upload_response
+            .json()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to parse bandwidth upload response: {}", e))?;
+        upload_result.bytes_received
+    } else {
+        size
+    };
+
+    Ok(StreamResult {
+        bytes,
+        duration_ms: stream_start.elapsed().as_millis() as f64,
     })
 }
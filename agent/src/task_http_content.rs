@@ -22,7 +22,7 @@ static REGEX_CACHE: std::sync::LazyLock<RwLock<HashMap<String, Regex>>> =
     std::sync::LazyLock::new(|| RwLock::new(HashMap::new()));
 
 /// Get or compile a regex pattern from the cache
-fn get_or_compile_regex(pattern: &str) -> Result<Regex> {
+pub(crate) fn get_or_compile_regex(pattern: &str) -> Result<Regex> {
     // First, try to get from cache with a read lock (fast path)
     {
         let cache = REGEX_CACHE.read().unwrap();
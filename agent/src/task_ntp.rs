@@ -0,0 +1,238 @@
+//! NTP task implementation
+//!
+//! This module implements an SNTP client (RFC 4330). One client request is sent
+//! to the server, and the four timestamps of the exchange give the clock offset
+//! and round-trip delay:
+//!
+//! - T1: agent sends the request (copied back by the server as origin timestamp)
+//! - T2: server receives the request
+//! - T3: server sends the response
+//! - T4: agent receives the response
+//!
+//! offset = ((T2 - T1) + (T3 - T4)) / 2, delay = (T4 - T1) - (T3 - T2)
+
+use crate::ntp_time::{ntp_difference_ms, ntp_timestamp_now};
+use crate::udp_socket::connected_udp_socket;
+use anyhow::{anyhow, Result};
+use shared::config::NtpParams;
+use shared::metrics::{RawNtpMetric, ThresholdStatus};
+use std::time::Duration;
+use tracing::debug;
+
+/// Size of an NTP packet without extension fields
+pub const NTP_PACKET_SIZE: usize = 48;
+
+/// First byte of a client request: LI = 0, VN = 4, Mode = 3 (client)
+const CLIENT_REQUEST_HEADER: u8 = 0x23;
+
+/// Mode of a server response
+const MODE_SERVER: u8 = 4;
+
+/// Leap indicator of a server whose clock is not synchronized
+pub const LEAP_NOT_SYNCHRONIZED: u8 = 3;
+
+/// Builds a client request carrying `transmit_timestamp` (T1)
+pub fn client_request(transmit_timestamp: u64) -> [u8; NTP_PACKET_SIZE] {
+    let mut packet = [0u8; NTP_PACKET_SIZE];
+    packet[0] = CLIENT_REQUEST_HEADER;
+    packet[40..48].copy_from_slice(&transmit_timestamp.to_be_bytes());
+    packet
+}
+
+/// Fields of a server response used by the task
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NtpResponse {
+    pub leap_indicator: u8,
+    pub mode: u8,
+    pub stratum: u8,
+    pub reference_id: [u8; 4],
+    /// Transmit timestamp of the request, copied by the server (T1)
+    pub origin_timestamp: u64,
+    /// Time the server received the request (T2)
+    pub receive_timestamp: u64,
+    /// Time the server sent the response (T3)
+    pub transmit_timestamp: u64,
+}
+
+impl NtpResponse {
+    /// Parse a server response, returning None if the datagram is too short
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < NTP_PACKET_SIZE {
+            return None;
+        }
+        Some(Self {
+            leap_indicator: buf[0] >> 6,
+            mode: buf[0] & 0x07,
+            stratum: buf[1],
+            reference_id: buf[12..16].try_into().ok()?,
+            origin_timestamp: u64::from_be_bytes(buf[24..32].try_into().ok()?),
+            receive_timestamp: u64::from_be_bytes(buf[32..40].try_into().ok()?),
+            transmit_timestamp: u64::from_be_bytes(buf[40..48].try_into().ok()?),
+        })
+    }
+
+    /// Reference ID as text: a code of up to four ASCII characters for stratum 0
+    /// (kiss code) and 1 (reference clock), an IPv4 address for higher strata
+    pub fn reference_id_text(&self) -> String {
+        if self.stratum <= 1 {
+            self.reference_id
+                .iter()
+                .take_while(|b| **b != 0)
+                .map(|b| *b as char)
+                .collect()
+        } else {
+            std::net::Ipv4Addr::from(self.reference_id).to_string()
+        }
+    }
+
+    /// Clock offset and round-trip delay in milliseconds for a response received at `t4`
+    pub fn offset_and_delay_ms(&self, t4: u64) -> (f64, f64) {
+        let t1 = self.origin_timestamp;
+        let offset_ms = (ntp_difference_ms(self.receive_timestamp, t1)
+            + ntp_difference_ms(self.transmit_timestamp, t4))
+            / 2.0;
+        let delay_ms = (ntp_difference_ms(t4, t1)
+            - ntp_difference_ms(self.transmit_timestamp, self.receive_timestamp))
+        .max(0.0);
+        (offset_ms, delay_ms)
+    }
+}
+
+/// Execute an NTP query
+///
+/// # Arguments
+/// * `params` - NTP task parameters
+///
+/// # Returns
+/// * `RawNtpMetric` - Offset, delay, stratum, reference ID and leap indicator
+pub async fn execute_ntp_task(params: &NtpParams) -> RawNtpMetric {
+    let timeout_duration = Duration::from_secs(params.timeout_seconds as u64);
+    let result = match tokio::time::timeout(timeout_duration, query_server(params)).await {
+        Ok(result) => result,
+        Err(_) => Err(anyhow!(
+            "Timeout after {}s waiting for a response from {}",
+            params.timeout_seconds,
+            params.host
+        )),
+    };
+
+    match result {
+        Ok(metric) => metric,
+        Err(e) => RawNtpMetric {
+            offset_ms: None,
+            delay_ms: None,
+            stratum: None,
+            reference_id: None,
+            leap_indicator: None,
+            success: false,
+            error: Some(e.to_string()),
+            status: ThresholdStatus::Error,
+            status_message: None,
+            host: params.host.clone(),
+            target_id: params.target_id.clone(),
+        },
+    }
+}
+
+async fn query_server(params: &NtpParams) -> Result<RawNtpMetric> {
+    let address = params.server_address();
+    let socket = connected_udp_socket(&address).await?;
+
+    let t1 = ntp_timestamp_now();
+    socket
+        .send(&client_request(t1))
+        .await
+        .map_err(|e| anyhow!("Send error: {}", e))?;
+
+    let mut buf = [0u8; 1024];
+    let (response, t4) = loop {
+        let len = match socket.recv(&mut buf).await {
+            Ok(len) => len,
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                return Err(anyhow!("Port unreachable: {}", address));
+            }
+            Err(e) => return Err(anyhow!("Receive error: {}", e)),
+        };
+        let t4 = ntp_timestamp_now();
+        // Only accept server responses to this request
+        match NtpResponse::decode(&buf[..len]) {
+            Some(response) if response.mode == MODE_SERVER && response.origin_timestamp == t1 => {
+                break (response, t4);
+            }
+            _ => continue,
+        }
+    };
+
+    if response.stratum == 0 {
+        return Err(anyhow!(
+            "Kiss-o'-Death from {}: {}",
+            params.host,
+            response.reference_id_text()
+        ));
+    }
+
+    let (offset_ms, delay_ms) = response.offset_and_delay_ms(t4);
+    debug!(
+        host = %params.host,
+        offset_ms,
+        delay_ms,
+        stratum = response.stratum,
+        "NTP query finished"
+    );
+
+    let (success, error, (status, status_message)) =
+        if response.leap_indicator == LEAP_NOT_SYNCHRONIZED || response.transmit_timestamp == 0 {
+            (
+                false,
+                Some(format!("Server {} is not synchronized", params.host)),
+                (ThresholdStatus::Error, None),
+            )
+        } else {
+            (true, None, evaluate_status(params, offset_ms))
+        };
+
+    Ok(RawNtpMetric {
+        offset_ms: Some(offset_ms),
+        delay_ms: Some(delay_ms),
+        stratum: Some(response.stratum),
+        reference_id: Some(response.reference_id_text()),
+        leap_indicator: Some(response.leap_indicator),
+        success,
+        error,
+        status,
+        status_message,
+        host: params.host.clone(),
+        target_id: params.target_id.clone(),
+    })
+}
+
+/// Evaluate the absolute offset against the task's thresholds
+pub(crate) fn evaluate_status(
+    params: &NtpParams,
+    offset_ms: f64,
+) -> (ThresholdStatus, Option<String>) {
+    let abs_offset_ms = offset_ms.abs();
+    if let Some(crit) = params.crit_offset_ms {
+        if abs_offset_ms > crit {
+            return (
+                ThresholdStatus::Critical,
+                Some(format!(
+                    "Offset {:.3} ms exceeds crit_offset_ms ({} ms)",
+                    offset_ms, crit
+                )),
+            );
+        }
+    }
+    if let Some(warn) = params.warn_offset_ms {
+        if abs_offset_ms > warn {
+            return (
+                ThresholdStatus::Warning,
+                Some(format!(
+                    "Offset {:.3} ms exceeds warn_offset_ms ({} ms)",
+                    offset_ms, warn
+                )),
+            );
+        }
+    }
+    (ThresholdStatus::Ok, None)
+}
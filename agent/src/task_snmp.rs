@@ -14,6 +14,16 @@ use tracing::{debug, error};
 /// Default SNMP port
 const DEFAULT_SNMP_PORT: u16 = 161;
 
+/// Value retrieved by an SNMP query
+struct SnmpValue {
+    /// String representation of the value
+    value: String,
+    /// SNMP data type name
+    value_type: String,
+    /// Numeric value for numeric SNMP types
+    numeric_value: Option<f64>,
+}
+
 /// Execute an SNMP GET query and return the raw metric
 pub async fn execute_snmp_task(params: &SnmpParams) -> Result<RawSnmpMetric> {
     let start_time = Instant::now();
@@ -36,20 +46,30 @@ pub async fn execute_snmp_task(params: &SnmpParams) -> Result<RawSnmpMetric> {
     let result = tokio::time::timeout(timeout, execute_query(params, addr, &oid)).await;
 
     match result {
-        Ok(Ok((value, value_type))) => {
+        Ok(Ok(SnmpValue {
+            value,
+            value_type,
+            numeric_value,
+        })) => {
             let response_time_ms = start_time.elapsed().as_secs_f64() * 1000.0;
             debug!(
                 "SNMP query successful: {} = {} ({})",
                 params.oid, value, value_type
             );
+            // The query itself succeeded; expectations decide whether the value is acceptable
+            let error = check_expectations(params, &value, numeric_value);
+            if let Some(error) = &error {
+                debug!("SNMP value check for {} failed: {}", params.oid, error);
+            }
             Ok(RawSnmpMetric {
                 response_time_ms: Some(response_time_ms),
-                success: true,
+                success: error.is_none(),
                 value: Some(value),
                 value_type: Some(value_type),
                 oid_queried: params.oid.clone(),
-                error: None,
+                error,
                 target_id: params.target_id.clone(),
+                numeric_value,
             })
         }
         Ok(Err(e)) => {
@@ -62,6 +82,7 @@ pub async fn execute_snmp_task(params: &SnmpParams) -> Result<RawSnmpMetric> {
                 oid_queried: params.oid.clone(),
                 error: Some(e.to_string()),
                 target_id: params.target_id.clone(),
+                numeric_value: None,
             })
         }
         Err(_) => {
@@ -80,17 +101,14 @@ pub async fn execute_snmp_task(params: &SnmpParams) -> Result<RawSnmpMetric> {
                     params.timeout_seconds
                 )),
                 target_id: params.target_id.clone(),
+                numeric_value: None,
             })
         }
     }
 }
 
 /// Execute the actual SNMP query based on version
-async fn execute_query(
-    params: &SnmpParams,
-    addr: SocketAddr,
-    oid: &Oid<'_>,
-) -> Result<(String, String)> {
+async fn execute_query(params: &SnmpParams, addr: SocketAddr, oid: &Oid<'_>) -> Result<SnmpValue> {
     match params.version {
         SnmpVersion::V1 => execute_v1_query(addr, &params.community, oid).await,
         SnmpVersion::V2c => execute_v2c_query(addr, &params.community, oid).await,
@@ -99,11 +117,7 @@ async fn execute_query(
 }
 
 /// Execute SNMPv1 query
-async fn execute_v1_query(
-    addr: SocketAddr,
-    community: &str,
-    oid: &Oid<'_>,
-) -> Result<(String, String)> {
+async fn execute_v1_query(addr: SocketAddr, community: &str, oid: &Oid<'_>) -> Result<SnmpValue> {
     let mut session = AsyncSession::new_v1(addr, community.as_bytes(), 0)
         .await
         .context("Failed to create SNMPv1 session")?;
@@ -117,11 +131,7 @@ async fn execute_v1_query(
 }
 
 /// Execute SNMPv2c query
-async fn execute_v2c_query(
-    addr: SocketAddr,
-    community: &str,
-    oid: &Oid<'_>,
-) -> Result<(String, String)> {
+async fn execute_v2c_query(addr: SocketAddr, community: &str, oid: &Oid<'_>) -> Result<SnmpValue> {
     let mut session = AsyncSession::new_v2c(addr, community.as_bytes(), 0)
         .await
         .context("Failed to create SNMPv2c session")?;
@@ -139,7 +149,7 @@ async fn execute_v3_query(
     params: &SnmpParams,
     addr: SocketAddr,
     oid: &Oid<'_>,
-) -> Result<(String, String)> {
+) -> Result<SnmpValue> {
     use snmp2::v3::{Auth, AuthProtocol, Security};
 
     let username = params
@@ -196,13 +206,15 @@ async fn execute_v3_query(
 }
 
 /// Extract the value from SNMP response
-fn extract_value_from_response(response: Pdu<'_>) -> Result<(String, String)> {
+fn extract_value_from_response(response: Pdu<'_>) -> Result<SnmpValue> {
     let mut varbinds = response.varbinds;
 
     if let Some((_oid, value)) = varbinds.next() {
-        let value_str = value_to_string(&value);
-        let type_str = value_type_name(&value).to_string();
-        Ok((value_str, type_str))
+        Ok(SnmpValue {
+            value: value_to_string(&value),
+            value_type: value_type_name(&value).to_string(),
+            numeric_value: value_to_f64(&value),
+        })
     } else {
         Err(anyhow::anyhow!("No value returned in SNMP response"))
     }
@@ -227,16 +239,7 @@ fn value_to_string(value: &Value<'_>) -> String {
         Value::IpAddress(ip) => format!("{}.{}.{}.{}", ip[0], ip[1], ip[2], ip[3]),
         Value::Counter32(c) => c.to_string(),
         Value::Unsigned32(u) => u.to_string(),
-        Value::Timeticks(t) => {
-            // Convert to human-readable format (days, hours, minutes, seconds)
-            let hundredths = *t as u64;
-            let seconds = hundredths / 100;
-            let days = seconds / 86400;
-            let hours = (seconds % 86400) / 3600;
-            let minutes = (seconds % 3600) / 60;
-            let secs = seconds % 60;
-            format!("{}d {}h {}m {}s ({} ticks)", days, hours, minutes, secs, t)
-        }
+        Value::Timeticks(t) => format_timeticks(*t),
         Value::Opaque(bytes) => bytes_to_hex(bytes),
         Value::Counter64(c) => c.to_string(),
         Value::EndOfMibView => "endOfMibView".to_string(),
@@ -247,6 +250,18 @@ fn value_to_string(value: &Value<'_>) -> String {
     }
 }
 
+/// Convert numeric SNMP types to f64 (None for non-numeric types)
+fn value_to_f64(value: &Value<'_>) -> Option<f64> {
+    match value {
+        Value::Integer(i) => Some(*i as f64),
+        Value::Counter32(c) => Some(*c as f64),
+        Value::Unsigned32(u) => Some(*u as f64),
+        Value::Timeticks(t) => Some(*t as f64),
+        Value::Counter64(c) => Some(*c as f64),
+        _ => None,
+    }
+}
+
 /// Get the type name of an SNMP Value (using Rust enum variant names)
 fn value_type_name(value: &Value<'_>) -> &'static str {
     match value {
@@ -278,8 +293,60 @@ fn value_type_name(value: &Value<'_>) -> &'static str {
     }
 }
 
+/// Check the retrieved value against the task's expected value and range.
+/// Returns an error message if any check fails.
+pub(crate) fn check_expectations(
+    params: &SnmpParams,
+    value: &str,
+    numeric_value: Option<f64>,
+) -> Option<String> {
+    if let Some(expected) = &params.expected_value {
+        if value != expected {
+            return Some(format!(
+                "Value '{}' does not match expected value '{}'",
+                value, expected
+            ));
+        }
+    }
+
+    if params.min_value.is_none() && params.max_value.is_none() {
+        return None;
+    }
+
+    let Some(number) = numeric_value else {
+        return Some(format!(
+            "Value '{}' is not numeric and cannot be checked against min/max",
+            value
+        ));
+    };
+    if let Some(min) = params.min_value {
+        if number < min {
+            return Some(format!("Value {} is below min_value {}", number, min));
+        }
+    }
+    if let Some(max) = params.max_value {
+        if number > max {
+            return Some(format!("Value {} is above max_value {}", number, max));
+        }
+    }
+    None
+}
+
+/// Convert TimeTicks to human-readable format (days, hours, minutes, seconds)
+pub(crate) fn format_timeticks(ticks: u32) -> String {
+    let seconds = ticks as u64 / 100;
+    let days = seconds / 86400;
+    let hours = (seconds % 86400) / 3600;
+    let minutes = (seconds % 3600) / 60;
+    let secs = seconds % 60;
+    format!(
+        "{}d {}h {}m {}s ({} ticks)",
+        days, hours, minutes, secs, ticks
+    )
+}
+
 /// Convert bytes to hexadecimal string
-fn bytes_to_hex(bytes: &[u8]) -> String {
+pub(crate) fn bytes_to_hex(bytes: &[u8]) -> String {
     bytes
         .iter()
         .map(|b| format!("{:02x}", b))
@@ -288,7 +355,7 @@ fn bytes_to_hex(bytes: &[u8]) -> String {
 }
 
 /// Parse host string to SocketAddr, adding default port if not specified
-fn parse_host(host: &str) -> Result<SocketAddr> {
+pub(crate) fn parse_host(host: &str) -> Result<SocketAddr> {
     // Check if port is already specified
     let host_with_port = if host.contains(':') {
         // Check if it's an IPv6 address without port (contains multiple colons but no brackets)
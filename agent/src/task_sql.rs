@@ -1,9 +1,22 @@
 //! SQL query task execution with Value and JSON modes
+//!
+//! Connections are opened through one of three backends:
+//! - `rsql_drivers` for PostgreSQL, MySQL, MariaDB and SQL Server
+//! - the agent's bundled `rusqlite` for SQLite files (avoids a second libsqlite3 build)
+//! - the ClickHouse HTTP interface via `reqwest`
 
 use anyhow::{Context, Result};
 use shared::config::SqlQueryParams;
-use shared::metrics::RawSqlQueryMetric;
+use shared::metrics::{RawSqlQueryMetric, ThresholdStatus};
+
+#[cfg(feature = "sql-tasks")]
+use std::collections::HashMap;
+#[cfg(feature = "sql-tasks")]
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "sql-tasks")]
 use std::time::{Duration, Instant};
+#[cfg(feature = "sql-tasks")]
+use tracing::debug;
 
 #[cfg(feature = "sql-tasks")]
 use rsql_drivers::{DriverManager, Value};
@@ -11,6 +24,23 @@ use rsql_drivers::{DriverManager, Value};
 #[cfg(feature = "sql-tasks")]
 use shared::config::SqlQueryMode;
 
+/// Default ClickHouse HTTP interface port
+#[cfg(feature = "sql-tasks")]
+const DEFAULT_CLICKHOUSE_PORT: u16 = 8123;
+
+/// Default ClickHouse HTTPS interface port
+#[cfg(feature = "sql-tasks")]
+const DEFAULT_CLICKHOUSE_TLS_PORT: u16 = 8443;
+
+/// Errors raised while running a SQL query task
+#[cfg(feature = "sql-tasks")]
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum SqlTaskError {
+    /// The query did not finish within the task timeout
+    #[error("SQL query timeout after {0}s")]
+    QueryTimeout(u32),
+}
+
 /// Result from Value mode execution
 #[cfg(feature = "sql-tasks")]
 struct ValueModeResult {
@@ -28,66 +58,501 @@ struct JsonModeResult {
     truncated: bool,
 }
 
+/// Rows returned by a query, with cell values converted to JSON
+#[cfg(feature = "sql-tasks")]
+pub(crate) struct QueryOutput {
+    /// Column names in result order
+    pub columns: Vec<String>,
+    /// Up to `max_rows` rows
+    pub rows: Vec<Vec<serde_json::Value>>,
+    /// Total number of rows returned by the query
+    pub row_count: u64,
+}
+
+/// An open database connection
+#[cfg(feature = "sql-tasks")]
+pub(crate) enum SqlConnection {
+    /// Connection opened through `rsql_drivers`
+    Rsql(Box<dyn rsql_drivers::Connection>),
+    /// Read-only SQLite connection, shared with the blocking query thread
+    Sqlite(Arc<Mutex<rusqlite::Connection>>),
+    /// ClickHouse HTTP interface
+    ClickHouse(ClickHouseConnection),
+}
+
+/// ClickHouse HTTP interface endpoint
+#[cfg(feature = "sql-tasks")]
+pub(crate) struct ClickHouseConnection {
+    client: reqwest::Client,
+    /// Base URL of the HTTP interface (e.g., "http://localhost:8123/")
+    base_url: String,
+    /// Database to run queries against (None uses the server default)
+    database: Option<String>,
+    username: Option<String>,
+    password: Option<String>,
+}
+
+/// Response body of the ClickHouse `JSONCompact` output format
+#[cfg(feature = "sql-tasks")]
+#[derive(serde::Deserialize)]
+struct ClickHouseResponse {
+    meta: Vec<ClickHouseColumn>,
+    data: Vec<Vec<serde_json::Value>>,
+}
+
+#[cfg(feature = "sql-tasks")]
+#[derive(serde::Deserialize)]
+struct ClickHouseColumn {
+    name: String,
+}
+
+#[cfg(feature = "sql-tasks")]
+impl SqlConnection {
+    /// Open a new connection for the task's database type
+    pub(crate) async fn connect(params: &SqlQueryParams) -> Result<Self> {
+        match params.database_type.as_str() {
+            "sqlite" => {
+                let path = params
+                    .database_url
+                    .strip_prefix("sqlite://")
+                    .unwrap_or(&params.database_url);
+                // Monitoring queries never need to write, so the file is opened read-only
+                let connection = rusqlite::Connection::open_with_flags(
+                    path,
+                    rusqlite::OpenFlags::SQLITE_OPEN_READ_ONLY
+                        | rusqlite::OpenFlags::SQLITE_OPEN_URI
+                        | rusqlite::OpenFlags::SQLITE_OPEN_NO_MUTEX,
+                )
+                .with_context(|| format!("Failed to open SQLite database: {}", path))?;
+                connection
+                    .busy_timeout(Duration::from_secs(params.timeout_seconds as u64))
+                    .context("Failed to set SQLite busy timeout")?;
+                Ok(Self::Sqlite(Arc::new(Mutex::new(connection))))
+            }
+            "clickhouse" => Ok(Self::ClickHouse(
+                ClickHouseConnection::connect(params).await?,
+            )),
+            _ => {
+                let connection_url = build_connection_url(params);
+                DriverManager::initialize().context("Failed to initialize driver manager")?;
+                let connection = DriverManager::connect(&connection_url)
+                    .await
+                    .context("Failed to connect to database")?;
+                Ok(Self::Rsql(connection))
+            }
+        }
+    }
+
+    /// Run a query, keeping at most `max_rows` rows while counting all of them
+    ///
+    /// Fails with [`SqlTaskError::QueryTimeout`] if the query runs longer than `timeout`.
+    pub(crate) async fn query(
+        &mut self,
+        query: &str,
+        max_rows: usize,
+        timeout: Duration,
+    ) -> Result<QueryOutput> {
+        let timeout_error = || SqlTaskError::QueryTimeout(timeout.as_secs() as u32);
+        match self {
+            Self::Rsql(connection) => {
+                let read_rows = async {
+                    let mut query_result = connection
+                        .query(query, &[])
+                        .await
+                        .context("Failed to execute query")?;
+
+                    let columns = query_result.columns().to_vec();
+                    let mut rows = Vec::new();
+                    let mut row_count: u64 = 0;
+                    while let Some(row) = query_result.next().await {
+                        row_count += 1;
+                        if rows.len() < max_rows {
+                            rows.push(row.iter().cloned().map(value_to_json).collect());
+                        }
+                    }
+
+                    Ok(QueryOutput {
+                        columns,
+                        rows,
+                        row_count,
+                    })
+                };
+                tokio::time::timeout(timeout, read_rows)
+                    .await
+                    .map_err(|_| timeout_error())?
+            }
+            Self::Sqlite(connection) => {
+                // rusqlite blocks, so the query runs off the async runtime and is
+                // interrupted through its handle once the timeout expires
+                let interrupt = connection
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .get_interrupt_handle();
+                let connection = Arc::clone(connection);
+                let query = query.to_string();
+                let blocking = tokio::task::spawn_blocking(move || {
+                    let connection = connection.lock().unwrap_or_else(|e| e.into_inner());
+                    query_sqlite(&connection, &query, max_rows)
+                });
+                match tokio::time::timeout(timeout, blocking).await {
+                    Ok(joined) => joined.context("SQLite query task failed")?,
+                    Err(_) => {
+                        interrupt.interrupt();
+                        Err(timeout_error().into())
+                    }
+                }
+            }
+            Self::ClickHouse(connection) => {
+                tokio::time::timeout(timeout, connection.query(query, max_rows))
+                    .await
+                    .map_err(|_| timeout_error())?
+            }
+        }
+    }
+}
+
+#[cfg(feature = "sql-tasks")]
+impl ClickHouseConnection {
+    /// Build the HTTP client and check that the server answers
+    async fn connect(params: &SqlQueryParams) -> Result<Self> {
+        let (base_url, database) = clickhouse_endpoint(&params.database_url)?;
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(params.timeout_seconds as u64))
+            .build()
+            .context("Failed to create ClickHouse HTTP client")?;
+
+        // The ping opens the (kept-alive) TCP/TLS connection reused by the query
+        let response = client
+            .get(format!("{}ping", base_url))
+            .send()
+            .await
+            .context("Failed to connect to ClickHouse")?;
+        if !response.status().is_success() {
+            anyhow::bail!("ClickHouse ping failed with status {}", response.status());
+        }
+
+        Ok(Self {
+            client,
+            base_url,
+            database,
+            username: params.username.clone(),
+            password: params.password.clone(),
+        })
+    }
+
+    async fn query(&self, query: &str, max_rows: usize) -> Result<QueryOutput> {
+        let mut request = self
+            .client
+            .post(&self.base_url)
+            .query(&[
+                ("default_format", "JSONCompact"),
+                ("output_format_json_quote_64bit_integers", "0"),
+            ])
+            .body(query.to_string());
+        if let Some(database) = &self.database {
+            request = request.query(&[("database", database)]);
+        }
+        if let Some(username) = &self.username {
+            request = request.header("X-ClickHouse-User", username);
+        }
+        if let Some(password) = &self.password {
+            request = request.header("X-ClickHouse-Key", password);
+        }
+
+        let response = request.send().await.context("Failed to execute query")?;
+        let status = response.status();
+        if !status.is_success() {
+            let error_text = response.text().await.unwrap_or_default();
+            anyhow::bail!(
+                "ClickHouse returned error {}: {}",
+                status,
+                error_text.trim()
+            );
+        }
+
+        let body = response
+            .text()
+            .await
+            .context("Failed to read ClickHouse response")?;
+        parse_clickhouse_response(&body, max_rows)
+    }
+}
+
+/// Split a ClickHouse `database_url` into the HTTP interface base URL and the database
+///
+/// URLs without a scheme and `clickhouse://` URLs use plain HTTP. Without an explicit
+/// port the ClickHouse defaults (8123, or 8443 for HTTPS) are used rather than 80/443.
+#[cfg(feature = "sql-tasks")]
+pub(crate) fn clickhouse_endpoint(database_url: &str) -> Result<(String, Option<String>)> {
+    let (scheme, rest) = database_url
+        .split_once("://")
+        .unwrap_or(("http", database_url));
+    let (scheme, default_port) = match scheme.to_ascii_lowercase().as_str() {
+        "http" | "clickhouse" => ("http", DEFAULT_CLICKHOUSE_PORT),
+        "https" => ("https", DEFAULT_CLICKHOUSE_TLS_PORT),
+        other => anyhow::bail!(
+            "Unsupported ClickHouse URL scheme '{}': only the HTTP interface is supported, use http:// or https://",
+            other
+        ),
+    };
+    let url = reqwest::Url::parse(&format!("{}://{}", scheme, rest))
+        .with_context(|| format!("Invalid ClickHouse URL: {}", database_url))?;
+
+    // The parsed URL drops a port equal to the scheme default, so check the written authority
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    let host_port = authority
+        .rsplit_once('@')
+        .map_or(authority, |(_, host)| host);
+    let port_written = host_port
+        .rsplit_once(':')
+        .is_some_and(|(_, port)| !port.is_empty() && port.bytes().all(|b| b.is_ascii_digit()));
+    let port = match url.port_or_known_default() {
+        Some(port) if port_written => port,
+        _ => default_port,
+    };
+
+    let database = Some(url.path().trim_matches('/'))
+        .filter(|db| !db.is_empty())
+        .map(|db| db.to_string());
+    let base_url = format!(
+        "{}://{}:{}/",
+        scheme,
+        url.host_str()
+            .ok_or_else(|| anyhow::anyhow!("ClickHouse URL is missing a host"))?,
+        port
+    );
+    Ok((base_url, database))
+}
+
+/// Parse a ClickHouse `JSONCompact` response body
+#[cfg(feature = "sql-tasks")]
+pub(crate) fn parse_clickhouse_response(body: &str, max_rows: usize) -> Result<QueryOutput> {
+    // Statements without a result set (e.g., SET) return an empty body
+    if body.trim().is_empty() {
+        return Ok(QueryOutput {
+            columns: Vec::new(),
+            rows: Vec::new(),
+            row_count: 0,
+        });
+    }
+
+    let response: ClickHouseResponse =
+        serde_json::from_str(body).context("Failed to parse ClickHouse response")?;
+    let row_count = response.data.len() as u64;
+    Ok(QueryOutput {
+        columns: response.meta.into_iter().map(|c| c.name).collect(),
+        rows: response.data.into_iter().take(max_rows).collect(),
+        row_count,
+    })
+}
+
+/// Run a query on a SQLite connection
+#[cfg(feature = "sql-tasks")]
+pub(crate) fn query_sqlite(
+    connection: &rusqlite::Connection,
+    query: &str,
+    max_rows: usize,
+) -> Result<QueryOutput> {
+    use rusqlite::types::ValueRef;
+
+    let mut statement = connection
+        .prepare(query)
+        .context("Failed to execute query")?;
+    let columns: Vec<String> = statement
+        .column_names()
+        .into_iter()
+        .map(|c| c.to_string())
+        .collect();
+
+    let mut result_rows = statement.query([]).context("Failed to execute query")?;
+    let mut rows = Vec::new();
+    let mut row_count: u64 = 0;
+    while let Some(row) = result_rows.next().context("Failed to read query row")? {
+        row_count += 1;
+        if rows.len() >= max_rows {
+            continue;
+        }
+        let values = (0..columns.len())
+            .map(|i| {
+                Ok(match row.get_ref(i)? {
+                    ValueRef::Null => serde_json::Value::Null,
+                    ValueRef::Integer(v) => serde_json::json!(v),
+                    ValueRef::Real(v) => serde_json::json!(v),
+                    ValueRef::Text(t) => {
+                        serde_json::Value::String(String::from_utf8_lossy(t).into_owned())
+                    }
+                    ValueRef::Blob(b) => {
+                        use base64::Engine;
+                        serde_json::Value::String(
+                            base64::engine::general_purpose::STANDARD.encode(b),
+                        )
+                    }
+                })
+            })
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .context("Failed to read query row")?;
+        rows.push(values);
+    }
+
+    Ok(QueryOutput {
+        columns,
+        rows,
+        row_count,
+    })
+}
+
+/// Idle connections kept open between runs, per task
+///
+/// A task with `pool_size > 0` returns its connection here after a successful
+/// run so the next run skips connection setup. Pools are dropped together with
+/// the executor when the task configuration is reloaded.
+#[cfg(feature = "sql-tasks")]
+#[derive(Default)]
+pub struct SqlConnectionPools {
+    idle: std::sync::Mutex<HashMap<String, Vec<SqlConnection>>>,
+}
+
+#[cfg(feature = "sql-tasks")]
+impl SqlConnectionPools {
+    /// Take an idle connection for a task, if one is available
+    pub(crate) fn take(&self, task_name: &str) -> Option<SqlConnection> {
+        self.idle
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get_mut(task_name)
+            .and_then(|connections| connections.pop())
+    }
+
+    /// Return a connection to a task's pool, closing it if the pool is full
+    pub(crate) fn put(&self, task_name: &str, connection: SqlConnection, pool_size: u32) {
+        let mut idle = self.idle.lock().unwrap_or_else(|e| e.into_inner());
+        let connections = idle.entry(task_name.to_string()).or_default();
+        if connections.len() < pool_size as usize {
+            connections.push(connection);
+        }
+    }
+
+    /// Number of idle connections held for a task
+    #[cfg(test)]
+    pub(crate) fn idle_count(&self, task_name: &str) -> usize {
+        self.idle
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(task_name)
+            .map_or(0, |connections| connections.len())
+    }
+}
+
 /// Execute a SQL query task and return raw metrics
 #[cfg(feature = "sql-tasks")]
-pub async fn execute_sql_query(params: &SqlQueryParams) -> Result<RawSqlQueryMetric> {
+pub async fn execute_sql_query(
+    task_name: &str,
+    params: &SqlQueryParams,
+    pools: &SqlConnectionPools,
+) -> Result<RawSqlQueryMetric> {
     let start = Instant::now();
     let timeout = Duration::from_secs(params.timeout_seconds as u64);
 
-    // Build connection URL based on database type and credentials
-    let connection_url = build_connection_url(params);
-
-    // Initialize driver manager and connect
-    DriverManager::initialize().context("Failed to initialize driver manager")?;
-
-    let mut connection = DriverManager::connect(&connection_url)
-        .await
-        .context("Failed to connect to database")?;
-
-    // Execute based on mode with timeout
-    let result = match params.mode {
-        SqlQueryMode::Value => {
-            let value_result =
-                tokio::time::timeout(timeout, execute_value_mode(&mut *connection, &params.query))
-                    .await;
-
-            match value_result {
-                Ok(Ok(r)) => Ok(ModeResult::Value(r)),
-                Ok(Err(e)) => Err(e),
-                Err(_) => Err(anyhow::anyhow!(
-                    "SQL query timeout after {}s",
-                    params.timeout_seconds
-                )),
+    // Reuse a warm connection if the task keeps a pool
+    let pooled = if params.pool_size > 0 {
+        pools.take(task_name)
+    } else {
+        None
+    };
+    let reused = pooled.is_some();
+
+    let mut connection = match pooled {
+        Some(connection) => connection,
+        None => match tokio::time::timeout(timeout, SqlConnection::connect(params)).await {
+            Ok(Ok(connection)) => connection,
+            Ok(Err(e)) => return build_metric(Err(e), start, None, params),
+            Err(_) => {
+                return build_metric(
+                    Err(anyhow::anyhow!(
+                        "Database connection timeout after {}s",
+                        params.timeout_seconds
+                    )),
+                    start,
+                    None,
+                    params,
+                )
             }
-        }
-        SqlQueryMode::Json => {
-            let json_result = tokio::time::timeout(
-                timeout,
-                execute_json_mode(
-                    &mut *connection,
-                    &params.query,
-                    params.max_rows,
-                    params.max_json_size_bytes,
-                ),
-            )
-            .await;
-
-            match json_result {
-                Ok(Ok(r)) => Ok(ModeResult::Json(r)),
-                Ok(Err(e)) => Err(e),
-                Err(_) => Err(anyhow::anyhow!(
-                    "SQL query timeout after {}s",
-                    params.timeout_seconds
-                )),
+        },
+    };
+    let mut connect_time_ms = start.elapsed().as_secs_f64() * 1000.0;
+
+    let mut result = run_query(&mut connection, params, timeout).await;
+
+    // A pooled connection may have been closed by the server since the last run;
+    // retry once on a fresh connection unless the query itself timed out
+    if reused && result.as_ref().is_err_and(|e| !is_timeout(e)) {
+        debug!(
+            "Pooled connection for {} failed, reconnecting: {:?}",
+            task_name,
+            result.as_ref().err()
+        );
+        let reconnect_start = Instant::now();
+        match tokio::time::timeout(timeout, SqlConnection::connect(params)).await {
+            Ok(Ok(fresh)) => {
+                connection = fresh;
+                connect_time_ms = reconnect_start.elapsed().as_secs_f64() * 1000.0;
+                result = run_query(&mut connection, params, timeout).await;
+            }
+            Ok(Err(e)) => return build_metric(Err(e), start, None, params),
+            Err(_) => {
+                return build_metric(
+                    Err(anyhow::anyhow!(
+                        "Database connection timeout after {}s",
+                        params.timeout_seconds
+                    )),
+                    start,
+                    None,
+                    params,
+                )
             }
         }
-    };
+    }
 
-    let elapsed = start.elapsed();
-    let total_time_ms = elapsed.as_secs_f64() * 1000.0;
+    if params.pool_size > 0 && result.is_ok() {
+        pools.put(task_name, connection, params.pool_size);
+    }
 
     // Build metric from result
-    build_metric(result, total_time_ms, params)
+    build_metric(result, start, Some(connect_time_ms), params)
+}
+
+/// Run the task's query in the configured mode, bounded by the task timeout
+#[cfg(feature = "sql-tasks")]
+async fn run_query(
+    connection: &mut SqlConnection,
+    params: &SqlQueryParams,
+    timeout: Duration,
+) -> Result<(ModeResult, f64)> {
+    let query_start = Instant::now();
+    let max_rows = match params.mode {
+        SqlQueryMode::Value => 1,
+        SqlQueryMode::Json => params.max_rows,
+    };
+
+    let output = connection.query(&params.query, max_rows, timeout).await?;
+    let query_time_ms = query_start.elapsed().as_secs_f64() * 1000.0;
+
+    let mode_result = match params.mode {
+        SqlQueryMode::Value => ModeResult::Value(value_mode_result(output)),
+        SqlQueryMode::Json => {
+            ModeResult::Json(json_mode_result(output, params.max_json_size_bytes)?)
+        }
+    };
+    Ok((mode_result, query_time_ms))
+}
+
+#[cfg(feature = "sql-tasks")]
+fn is_timeout(error: &anyhow::Error) -> bool {
+    matches!(
+        error.downcast_ref::<SqlTaskError>(),
+        Some(SqlTaskError::QueryTimeout(_))
+    )
 }
 
 #[cfg(feature = "sql-tasks")]
@@ -98,52 +563,72 @@ enum ModeResult {
 
 #[cfg(feature = "sql-tasks")]
 fn build_connection_url(params: &SqlQueryParams) -> String {
+    // "mssql" is accepted as an alias for the rsql "sqlserver" driver
+    let scheme = match params.database_type.as_str() {
+        "mssql" => "sqlserver",
+        other => other,
+    };
+
     if let (Some(username), Some(password)) = (&params.username, &params.password) {
         format!(
             "{}://{}:{}@{}",
-            params.database_type, username, password, params.database_url
+            scheme, username, password, params.database_url
         )
     } else if let Some(username) = &params.username {
-        format!(
-            "{}://{}@{}",
-            params.database_type, username, params.database_url
-        )
+        format!("{}://{}@{}", scheme, username, params.database_url)
     } else {
-        format!("{}://{}", params.database_type, params.database_url)
+        format!("{}://{}", scheme, params.database_url)
     }
 }
 
 #[cfg(feature = "sql-tasks")]
 fn build_metric(
-    result: Result<ModeResult>,
-    total_time_ms: f64,
+    result: Result<(ModeResult, f64)>,
+    start: Instant,
+    connect_time_ms: Option<f64>,
     params: &SqlQueryParams,
 ) -> Result<RawSqlQueryMetric> {
+    let total_time_ms = start.elapsed().as_secs_f64() * 1000.0;
+
     match result {
-        Ok(ModeResult::Value(r)) => Ok(RawSqlQueryMetric {
-            total_time_ms: Some(total_time_ms),
-            row_count: Some(r.row_count),
-            success: true,
-            error: None,
-            target_id: params.target_id.clone(),
-            mode: params.mode.clone(),
-            value: r.value,
-            json_result: None,
-            json_truncated: false,
-            column_count: Some(r.column_count),
-        }),
-        Ok(ModeResult::Json(r)) => Ok(RawSqlQueryMetric {
-            total_time_ms: Some(total_time_ms),
-            row_count: Some(r.row_count),
-            success: true,
-            error: None,
-            target_id: params.target_id.clone(),
-            mode: params.mode.clone(),
-            value: None,
-            json_result: Some(r.json_result),
-            json_truncated: r.truncated,
-            column_count: Some(r.column_count),
-        }),
+        Ok((ModeResult::Value(r), query_time_ms)) => {
+            let (status, status_message) = evaluate_status(params, r.row_count, r.value);
+            Ok(RawSqlQueryMetric {
+                total_time_ms: Some(total_time_ms),
+                row_count: Some(r.row_count),
+                success: true,
+                error: None,
+                target_id: params.target_id.clone(),
+                mode: params.mode.clone(),
+                value: r.value,
+                json_result: None,
+                json_truncated: false,
+                column_count: Some(r.column_count),
+                connect_time_ms,
+                query_time_ms: Some(query_time_ms),
+                status,
+                status_message,
+            })
+        }
+        Ok((ModeResult::Json(r), query_time_ms)) => {
+            let (status, status_message) = evaluate_status(params, r.row_count, None);
+            Ok(RawSqlQueryMetric {
+                total_time_ms: Some(total_time_ms),
+                row_count: Some(r.row_count),
+                success: true,
+                error: None,
+                target_id: params.target_id.clone(),
+                mode: params.mode.clone(),
+                value: None,
+                json_result: Some(r.json_result),
+                json_truncated: r.truncated,
+                column_count: Some(r.column_count),
+                connect_time_ms,
+                query_time_ms: Some(query_time_ms),
+                status,
+                status_message,
+            })
+        }
         Err(e) => Ok(RawSqlQueryMetric {
             total_time_ms: Some(total_time_ms),
             row_count: None,
@@ -155,82 +640,115 @@ fn build_metric(
             json_result: None,
             json_truncated: false,
             column_count: None,
+            connect_time_ms,
+            query_time_ms: None,
+            status: ThresholdStatus::Error,
+            status_message: None,
         }),
     }
 }
 
-/// Execute query in Value mode - extract first row, first column as numeric
+/// Evaluate the row count and value of a successful run against the task's checks
+///
+/// Critical checks take precedence over warnings. A run with value thresholds
+/// but no numeric value is critical, since the check could not be made.
 #[cfg(feature = "sql-tasks")]
-async fn execute_value_mode(
-    connection: &mut dyn rsql_drivers::Connection,
-    query: &str,
-) -> Result<ValueModeResult> {
-    let mut query_result = connection
-        .query(query)
-        .await
-        .context("Failed to execute query")?;
+pub(crate) fn evaluate_status(
+    params: &SqlQueryParams,
+    row_count: u64,
+    value: Option<f64>,
+) -> (ThresholdStatus, Option<String>) {
+    if let Some(expected) = params.expected_row_count {
+        if row_count != expected {
+            return (
+                ThresholdStatus::Critical,
+                Some(format!(
+                    "Query returned {} rows, expected {}",
+                    row_count, expected
+                )),
+            );
+        }
+    }
 
-    let columns = query_result.columns().await;
-    let column_count = columns.len() as u32;
+    if !params.has_value_thresholds() {
+        return (ThresholdStatus::Ok, None);
+    }
 
-    // Get first row
-    let first_row = query_result.next().await;
+    let Some(value) = value else {
+        return (
+            ThresholdStatus::Critical,
+            Some("Query returned no numeric value to check".to_string()),
+        );
+    };
 
-    // Count all rows
-    let mut row_count: u64 = if first_row.is_some() { 1 } else { 0 };
-    while query_result.next().await.is_some() {
-        row_count += 1;
+    if let Some(limit) = params.crit_above.filter(|limit| value > *limit) {
+        return (
+            ThresholdStatus::Critical,
+            Some(format!(
+                "Value {} is above critical threshold {}",
+                value, limit
+            )),
+        );
+    }
+    if let Some(limit) = params.crit_below.filter(|limit| value < *limit) {
+        return (
+            ThresholdStatus::Critical,
+            Some(format!(
+                "Value {} is below critical threshold {}",
+                value, limit
+            )),
+        );
+    }
+    if let Some(limit) = params.warn_above.filter(|limit| value > *limit) {
+        return (
+            ThresholdStatus::Warning,
+            Some(format!(
+                "Value {} is above warning threshold {}",
+                value, limit
+            )),
+        );
+    }
+    if let Some(limit) = params.warn_below.filter(|limit| value < *limit) {
+        return (
+            ThresholdStatus::Warning,
+            Some(format!(
+                "Value {} is below warning threshold {}",
+                value, limit
+            )),
+        );
     }
 
-    // Extract numeric value from first cell
-    let value = first_row
-        .as_ref()
+    (ThresholdStatus::Ok, None)
+}
+
+/// Value mode - extract first row, first column as numeric
+#[cfg(feature = "sql-tasks")]
+fn value_mode_result(output: QueryOutput) -> ValueModeResult {
+    let value = output
+        .rows
+        .first()
         .and_then(|row| row.first())
-        .and_then(|v| value_to_f64(v));
+        .and_then(json_to_f64);
 
-    Ok(ValueModeResult {
-        row_count,
-        column_count,
+    ValueModeResult {
+        row_count: output.row_count,
+        column_count: output.columns.len() as u32,
         value,
-    })
+    }
 }
 
-/// Execute query in JSON mode - return results as JSON array of objects
+/// JSON mode - return results as JSON array of objects
 #[cfg(feature = "sql-tasks")]
-async fn execute_json_mode(
-    connection: &mut dyn rsql_drivers::Connection,
-    query: &str,
-    max_rows: usize,
-    max_json_size_bytes: usize,
-) -> Result<JsonModeResult> {
-    let mut query_result = connection
-        .query(query)
-        .await
-        .context("Failed to execute query")?;
-
-    let columns = query_result.columns().await;
-    let column_count = columns.len() as u32;
-
-    // Collect rows up to max_rows
-    let mut rows: Vec<Vec<Value>> = Vec::new();
-    let mut total_row_count: u64 = 0;
-
-    while let Some(row) = query_result.next().await {
-        total_row_count += 1;
-        if rows.len() < max_rows {
-            rows.push(row);
-        }
-    }
+fn json_mode_result(output: QueryOutput, max_json_size_bytes: usize) -> Result<JsonModeResult> {
+    let column_count = output.columns.len() as u32;
 
     // Convert to JSON array of objects
-    let json_rows: Vec<serde_json::Value> = rows
+    let json_rows: Vec<serde_json::Value> = output
+        .rows
         .into_iter()
         .map(|row| {
-            let obj: serde_json::Map<String, serde_json::Value> = columns
-                .iter()
-                .zip(row.into_iter())
-                .map(|(col, val)| (col.clone(), value_to_json(val)))
-                .collect();
+            let obj: serde_json::Map<String, serde_json::Value> =
+                output.columns.iter().cloned().zip(row).collect();
             serde_json::Value::Object(obj)
         })
         .collect();
@@ -245,41 +763,29 @@ async fn execute_json_mode(
     };
 
     Ok(JsonModeResult {
-        row_count: total_row_count,
+        row_count: output.row_count,
         column_count,
         json_result: final_json,
         truncated,
     })
 }
 
-/// Convert rsql_drivers Value to f64 for Value mode
+/// Convert a cell value to f64 for Value mode
+///
+/// Numeric strings are parsed, which covers decimals (MySQL returns SUM/AVG as
+/// Decimal) and 128-bit integers that are carried as strings.
 #[cfg(feature = "sql-tasks")]
-fn value_to_f64(value: &Value) -> Option<f64> {
-    use std::str::FromStr;
+pub(crate) fn json_to_f64(value: &serde_json::Value) -> Option<f64> {
     match value {
-        Value::I8(v) => Some(*v as f64),
-        Value::I16(v) => Some(*v as f64),
-        Value::I32(v) => Some(*v as f64),
-        Value::I64(v) => Some(*v as f64),
-        Value::I128(v) => Some(*v as f64),
-        Value::U8(v) => Some(*v as f64),
-        Value::U16(v) => Some(*v as f64),
-        Value::U32(v) => Some(*v as f64),
-        Value::U64(v) => Some(*v as f64),
-        Value::U128(v) => Some(*v as f64),
-        Value::F32(v) => Some(*v as f64),
-        Value::F64(v) => Some(*v),
-        Value::String(s) => s.parse().ok(),
-        Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
-        Value::Null => None,
-        // Decimal - MySQL returns SUM/AVG as Decimal type
-        Value::Decimal(d) => f64::from_str(&d.to_string()).ok(),
-        // Date, Time, DateTime, Uuid, Bytes, Array, Map - not convertible to simple f64
+        serde_json::Value::Number(n) => n.as_f64(),
+        serde_json::Value::String(s) => s.trim().parse().ok(),
+        serde_json::Value::Bool(b) => Some(if *b { 1.0 } else { 0.0 }),
+        // Null, arrays and objects are not convertible to a simple f64
         _ => None,
     }
 }
 
-/// Convert rsql_drivers Value to serde_json::Value for JSON mode
+/// Convert rsql_drivers Value to serde_json::Value
 #[cfg(feature = "sql-tasks")]
 fn value_to_json(value: Value) -> serde_json::Value {
     match value {
@@ -334,7 +840,7 @@ fn truncate_json_result(rows: &[serde_json::Value], max_size: usize) -> Result<(
     let mut high = rows.len();
 
     while low < high {
-        let mid = (low + high + 1) / 2;
+        let mid = (low + high).div_ceil(2);
         let subset: Vec<&serde_json::Value> = rows.iter().take(mid).collect();
         let json = serde_json::to_string(&subset)?;
         if json.len() <= max_size {
@@ -7,13 +7,32 @@
 //! - TCP (this module) - fundamental socket connection
 //! - TLS (imports from TCP) - adds TLS handshake
 //! - HTTP (imports from TLS) - adds HTTP protocol
+//!
+//! Optionally, the task also checks that the service answers correctly: it
+//! writes a `send` payload after connecting and/or reads the response until it
+//! matches the `expect` regex (e.g., an SSH or SMTP banner, or Redis `+PONG`).
 
 use shared::config::TcpParams;
 use shared::metrics::RawTcpMetric;
 use std::net::{SocketAddr, ToSocketAddrs};
 use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
-use tokio::time::timeout;
+use tokio::time::{timeout, timeout_at, Instant};
+
+/// Largest response read while waiting for `expect` to match
+const MAX_RESPONSE_BYTES: usize = 4096;
+
+/// Number of response characters quoted in mismatch errors
+const RESPONSE_EXCERPT_CHARS: usize = 100;
+
+/// Outcome of the response check after connecting
+#[derive(Debug, Default)]
+struct ResponseCheck {
+    response_time_ms: Option<f64>,
+    expect_matched: Option<bool>,
+    error: Option<String>,
+}
 
 /// Establish TCP connection and measure timing
 ///
@@ -54,6 +73,8 @@ pub async fn execute_tcp_task(params: &TcpParams) -> RawTcpMetric {
             } else {
                 return RawTcpMetric {
                     connect_time_ms: None,
+                    response_time_ms: None,
+                    expect_matched: None,
                     success: false,
                     error: Some("Failed to parse host: no addresses found".to_string()),
                     host: params.host.clone(),
@@ -64,6 +85,8 @@ pub async fn execute_tcp_task(params: &TcpParams) -> RawTcpMetric {
         Err(e) => {
             return RawTcpMetric {
                 connect_time_ms: None,
+                response_time_ms: None,
+                expect_matched: None,
                 success: false,
                 error: Some(format!("Failed to parse host: {}", e)),
                 host: params.host.clone(),
@@ -74,15 +97,23 @@ pub async fn execute_tcp_task(params: &TcpParams) -> RawTcpMetric {
 
     // Attempt TCP connection with timeout
     match timeout(timeout_duration, get_tcp_timing(&socket_addr)).await {
-        Ok(Ok((connect_time, stream))) => {
-            // Connection successful - explicitly drop the stream to ensure cleanup
+        Ok(Ok((connect_time, mut stream))) => {
+            let check = if params.checks_response() {
+                check_response(&mut stream, params).await
+            } else {
+                ResponseCheck::default()
+            };
+
+            // Explicitly drop the stream to ensure cleanup
             // This prevents potential file descriptor accumulation in high-frequency monitoring
             drop(stream);
 
             RawTcpMetric {
                 connect_time_ms: Some(connect_time.as_secs_f64() * 1000.0),
-                success: true,
-                error: None,
+                response_time_ms: check.response_time_ms,
+                expect_matched: check.expect_matched,
+                success: check.error.is_none(),
+                error: check.error,
                 host: params.host.clone(),
                 target_id: params.target_id.clone(),
             }
@@ -91,6 +122,8 @@ pub async fn execute_tcp_task(params: &TcpParams) -> RawTcpMetric {
             // Connection failed
             RawTcpMetric {
                 connect_time_ms: None,
+                response_time_ms: None,
+                expect_matched: None,
                 success: false,
                 error: Some(format!("Connection error: {}", e)),
                 host: params.host.clone(),
@@ -101,6 +134,8 @@ pub async fn execute_tcp_task(params: &TcpParams) -> RawTcpMetric {
             // Timeout
             RawTcpMetric {
                 connect_time_ms: None,
+                response_time_ms: None,
+                expect_matched: None,
                 success: false,
                 error: Some(format!(
                     "Connection timeout after {} seconds",
@@ -112,3 +147,96 @@ pub async fn execute_tcp_task(params: &TcpParams) -> RawTcpMetric {
         }
     }
 }
+
+/// Write the `send` payload and read the response until it matches `expect`
+///
+/// Without `expect`, any response passes. Reading stops when the pattern
+/// matches, the connection closes, `MAX_RESPONSE_BYTES` have been read or
+/// `read_timeout_seconds` have passed.
+async fn check_response(stream: &mut TcpStream, params: &TcpParams) -> ResponseCheck {
+    let expect = match params
+        .expect
+        .as_deref()
+        .map(crate::task_http_content::get_or_compile_regex)
+        .transpose()
+    {
+        Ok(expect) => expect,
+        Err(e) => {
+            return ResponseCheck {
+                expect_matched: Some(false),
+                error: Some(format!("Invalid expect pattern: {}", e)),
+                ..Default::default()
+            }
+        }
+    };
+    let expect_matched = expect.as_ref().map(|_| false);
+
+    if let Some(payload) = &params.send {
+        if let Err(e) = stream.write_all(payload.as_bytes()).await {
+            return ResponseCheck {
+                expect_matched,
+                error: Some(format!("Send error: {}", e)),
+                ..Default::default()
+            };
+        }
+    }
+
+    let start = Instant::now();
+    let deadline = start + Duration::from_secs(params.read_timeout_seconds as u64);
+    let mut response = Vec::new();
+    let mut chunk = [0u8; 1024];
+
+    let stop_reason = loop {
+        match timeout_at(deadline, stream.read(&mut chunk)).await {
+            Ok(Ok(0)) => break "connection closed",
+            Ok(Ok(len)) => {
+                let room = MAX_RESPONSE_BYTES - response.len();
+                response.extend_from_slice(&chunk[..len.min(room)]);
+                let matched = match &expect {
+                    Some(regex) => regex.is_match(&String::from_utf8_lossy(&response)),
+                    None => true,
+                };
+                if matched {
+                    return ResponseCheck {
+                        response_time_ms: Some(start.elapsed().as_secs_f64() * 1000.0),
+                        expect_matched: expect.as_ref().map(|_| true),
+                        error: None,
+                    };
+                }
+                if response.len() >= MAX_RESPONSE_BYTES {
+                    break "response limit reached";
+                }
+            }
+            Ok(Err(e)) => {
+                return ResponseCheck {
+                    expect_matched,
+                    error: Some(format!("Receive error: {}", e)),
+                    ..Default::default()
+                }
+            }
+            Err(_) => break "read timeout",
+        }
+    };
+
+    let error = if response.is_empty() && stop_reason == "connection closed" {
+        "Connection closed without a response".to_string()
+    } else if response.is_empty() {
+        format!("No response within {} seconds", params.read_timeout_seconds)
+    } else {
+        let excerpt: String = String::from_utf8_lossy(&response)
+            .chars()
+            .take(RESPONSE_EXCERPT_CHARS)
+            .collect();
+        format!(
+            "Response did not match expect pattern '{}' ({}): \"{}\"",
+            params.expect.as_deref().unwrap_or_default(),
+            stop_reason,
+            excerpt.escape_debug()
+        )
+    };
+    ResponseCheck {
+        expect_matched,
+        error: Some(error),
+        ..Default::default()
+    }
+}
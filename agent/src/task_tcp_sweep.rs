@@ -0,0 +1,121 @@
+//! TCP sweep task implementation
+//!
+//! This module connects to every host:port combination of a sweep (hosts,
+//! CIDR blocks and port ranges are expanded by `TcpSweepParams::targets`),
+//! running at most `concurrency` connects at once. Each target is classified
+//! as open, closed (connection refused), filtered (timeout or unreachable) or
+//! unresolved, and the whole sweep is reported as a single metric.
+
+use crate::task_tcp::get_tcp_timing;
+use futures_util::StreamExt;
+use shared::config::TcpSweepParams;
+use shared::metrics::{RawTcpSweepMetric, TcpSweepState, TcpSweepTargetResult};
+use std::time::{Duration, Instant};
+use tokio::time::timeout;
+use tracing::debug;
+
+/// Execute a TCP sweep
+///
+/// # Arguments
+/// * `params` - TCP sweep task parameters
+///
+/// # Returns
+/// * `RawTcpSweepMetric` - Per-target states and connect times, with totals
+pub async fn execute_tcp_sweep_task(params: &TcpSweepParams) -> RawTcpSweepMetric {
+    let start = Instant::now();
+    let targets = match params.targets() {
+        Ok(targets) => targets,
+        Err(e) => return build_metric(Vec::new(), start, params, Some(e.to_string())),
+    };
+
+    let connect_timeout = Duration::from_secs(params.timeout_seconds as u64);
+    let results: Vec<TcpSweepTargetResult> = futures_util::stream::iter(targets)
+        .map(|target| probe_target(target, connect_timeout))
+        .buffered(params.concurrency.max(1) as usize)
+        .collect()
+        .await;
+
+    debug!(
+        targets = results.len(),
+        duration_ms = start.elapsed().as_millis() as u64,
+        "TCP sweep finished"
+    );
+
+    build_metric(results, start, params, None)
+}
+
+/// Resolve and connect to one target within the connect timeout
+async fn probe_target(target: String, connect_timeout: Duration) -> TcpSweepTargetResult {
+    let attempt = async {
+        let address = match tokio::net::lookup_host(&target).await {
+            Ok(mut addresses) => match addresses.next() {
+                Some(address) => address,
+                None => return (TcpSweepState::Unresolved, None),
+            },
+            Err(_) => return (TcpSweepState::Unresolved, None),
+        };
+        match get_tcp_timing(&address).await {
+            Ok((connect_time, stream)) => {
+                drop(stream);
+                (
+                    TcpSweepState::Open,
+                    Some(connect_time.as_secs_f64() * 1000.0),
+                )
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                (TcpSweepState::Closed, None)
+            }
+            // Host or network unreachable: nothing answered on the port
+            Err(_) => (TcpSweepState::Filtered, None),
+        }
+    };
+
+    let (state, connect_time_ms) = timeout(connect_timeout, attempt)
+        .await
+        .unwrap_or((TcpSweepState::Filtered, None));
+    TcpSweepTargetResult {
+        target,
+        state,
+        connect_time_ms,
+    }
+}
+
+/// Count the per-target results of a sweep into its metric
+fn build_metric(
+    results: Vec<TcpSweepTargetResult>,
+    start: Instant,
+    params: &TcpSweepParams,
+    error: Option<String>,
+) -> RawTcpSweepMetric {
+    let count = |state: TcpSweepState| results.iter().filter(|r| r.state == state).count() as u32;
+    let connect_times: Vec<f64> = results.iter().filter_map(|r| r.connect_time_ms).collect();
+    let unresolved_count = count(TcpSweepState::Unresolved);
+
+    let error = error.or_else(|| {
+        let mut unresolved_hosts: Vec<&str> = results
+            .iter()
+            .filter(|r| r.state == TcpSweepState::Unresolved)
+            .filter_map(|r| r.target.rsplit_once(':').map(|(host, _)| host))
+            .collect();
+        unresolved_hosts.dedup();
+        (!unresolved_hosts.is_empty())
+            .then(|| format!("Failed to resolve: {}", unresolved_hosts.join(", ")))
+    });
+
+    RawTcpSweepMetric {
+        targets_total: results.len() as u32,
+        open_count: count(TcpSweepState::Open),
+        closed_count: count(TcpSweepState::Closed),
+        filtered_count: count(TcpSweepState::Filtered),
+        unresolved_count,
+        avg_connect_time_ms: (!connect_times.is_empty())
+            .then(|| connect_times.iter().sum::<f64>() / connect_times.len() as f64),
+        min_connect_time_ms: connect_times.iter().copied().reduce(f64::min),
+        max_connect_time_ms: connect_times.iter().copied().reduce(f64::max),
+        duration_ms: start.elapsed().as_secs_f64() * 1000.0,
+        success: error.is_none(),
+        error,
+        results,
+        target_id: params.target_id.clone(),
+    }
+}
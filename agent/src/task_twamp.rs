@@ -0,0 +1,303 @@
+//! TWAMP-Light task implementation
+//!
+//! This module implements the Session-Sender side of TWAMP-Light (RFC 5357,
+//! appendix I) in unauthenticated mode. Test packets are sent straight to a
+//! Session-Reflector's UDP port without a TWAMP-Control session, so any router,
+//! switch or test head with a TWAMP-Light reflector can be measured, as well as
+//! other agents running the `[twamp_responder]`.
+//!
+//! The two-way delay of every packet is the time between sending the packet and
+//! receiving its reflection, minus the time the reflector held the packet (its
+//! transmit timestamp minus its receive timestamp). Only the difference between
+//! the two reflector timestamps is used, so the clocks of sender and reflector
+//! need not be synchronized.
+
+use crate::ntp_time::{ntp_difference_ms, ntp_timestamp_now};
+use crate::udp_socket::connected_udp_socket;
+use anyhow::{anyhow, Result};
+use shared::config::TwampParams;
+use shared::metrics::RawTwampMetric;
+use std::time::Duration;
+use tokio::time::{Instant, MissedTickBehavior};
+use tracing::debug;
+
+/// Size of an unauthenticated sender test packet without padding
+pub const SENDER_PACKET_SIZE: usize = 14;
+
+/// Size of an unauthenticated reflector test packet without padding
+pub const REFLECTOR_PACKET_SIZE: usize = 41;
+
+/// Error estimate sent with every timestamp: clock not synchronized to UTC
+/// (S = 0), NTP format (Z = 0), scale 0, multiplier 1
+pub const ERROR_ESTIMATE: u16 = 0x0001;
+
+/// Sender test packet (RFC 5357 section 4.1.2, unauthenticated mode)
+///
+/// Layout (network byte order): sequence number (4), timestamp (8), error
+/// estimate (2), followed by zero padding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SenderPacket {
+    pub sequence: u32,
+    pub timestamp: u64,
+    pub error_estimate: u16,
+}
+
+impl SenderPacket {
+    /// Write the packet to the start of `buf`, which must hold at least `SENDER_PACKET_SIZE` bytes
+    pub fn encode(&self, buf: &mut [u8]) {
+        buf[0..4].copy_from_slice(&self.sequence.to_be_bytes());
+        buf[4..12].copy_from_slice(&self.timestamp.to_be_bytes());
+        buf[12..14].copy_from_slice(&self.error_estimate.to_be_bytes());
+        buf[SENDER_PACKET_SIZE..].fill(0);
+    }
+
+    /// Parse a sender test packet, returning None if the datagram is too short
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < SENDER_PACKET_SIZE {
+            return None;
+        }
+        Some(Self {
+            sequence: u32::from_be_bytes(buf[0..4].try_into().ok()?),
+            timestamp: u64::from_be_bytes(buf[4..12].try_into().ok()?),
+            error_estimate: u16::from_be_bytes(buf[12..14].try_into().ok()?),
+        })
+    }
+}
+
+/// Reflector test packet (RFC 5357 section 4.2.1, unauthenticated mode)
+///
+/// Layout (network byte order): sequence number (4), transmit timestamp (8),
+/// error estimate (2), MBZ (2), receive timestamp (8), sender sequence number
+/// (4), sender timestamp (8), sender error estimate (2), MBZ (2), sender TTL
+/// (1), followed by zero padding.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ReflectorPacket {
+    pub sequence: u32,
+    /// Time the reflector sent this packet (T3)
+    pub timestamp: u64,
+    pub error_estimate: u16,
+    /// Time the reflector received the sender's packet (T2)
+    pub receive_timestamp: u64,
+    pub sender_sequence: u32,
+    /// Timestamp of the sender's packet (T1)
+    pub sender_timestamp: u64,
+    pub sender_error_estimate: u16,
+    pub sender_ttl: u8,
+}
+
+impl ReflectorPacket {
+    /// Write the packet to the start of `buf`, which must hold at least `REFLECTOR_PACKET_SIZE` bytes
+    pub fn encode(&self, buf: &mut [u8]) {
+        buf[0..4].copy_from_slice(&self.sequence.to_be_bytes());
+        buf[4..12].copy_from_slice(&self.timestamp.to_be_bytes());
+        buf[12..14].copy_from_slice(&self.error_estimate.to_be_bytes());
+        buf[14..16].fill(0);
+        buf[16..24].copy_from_slice(&self.receive_timestamp.to_be_bytes());
+        buf[24..28].copy_from_slice(&self.sender_sequence.to_be_bytes());
+        buf[28..36].copy_from_slice(&self.sender_timestamp.to_be_bytes());
+        buf[36..38].copy_from_slice(&self.sender_error_estimate.to_be_bytes());
+        buf[38..40].fill(0);
+        buf[40] = self.sender_ttl;
+        buf[REFLECTOR_PACKET_SIZE..].fill(0);
+    }
+
+    /// Parse a reflector test packet, returning None if the datagram is too short
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < REFLECTOR_PACKET_SIZE {
+            return None;
+        }
+        Some(Self {
+            sequence: u32::from_be_bytes(buf[0..4].try_into().ok()?),
+            timestamp: u64::from_be_bytes(buf[4..12].try_into().ok()?),
+            error_estimate: u16::from_be_bytes(buf[12..14].try_into().ok()?),
+            receive_timestamp: u64::from_be_bytes(buf[16..24].try_into().ok()?),
+            sender_sequence: u32::from_be_bytes(buf[24..28].try_into().ok()?),
+            sender_timestamp: u64::from_be_bytes(buf[28..36].try_into().ok()?),
+            sender_error_estimate: u16::from_be_bytes(buf[36..38].try_into().ok()?),
+            sender_ttl: buf[40],
+        })
+    }
+
+    /// Time the reflector held the packet (T3 - T2) in milliseconds, never negative
+    pub fn processing_time_ms(&self) -> f64 {
+        ntp_difference_ms(self.timestamp, self.receive_timestamp).max(0.0)
+    }
+}
+
+/// Accumulates the reflected packets of one TWAMP run
+#[derive(Debug)]
+pub struct TwampStats {
+    received: Vec<bool>,
+    received_count: u32,
+    delay_sum_ms: f64,
+    min_delay_ms: Option<f64>,
+    max_delay_ms: Option<f64>,
+    last_delay_ms: Option<f64>,
+    jitter_ms: f64,
+}
+
+impl TwampStats {
+    pub fn new(packet_count: u32) -> Self {
+        Self {
+            received: vec![false; packet_count as usize],
+            received_count: 0,
+            delay_sum_ms: 0.0,
+            min_delay_ms: None,
+            max_delay_ms: None,
+            last_delay_ms: None,
+            jitter_ms: 0.0,
+        }
+    }
+
+    /// Returns true once every test packet has been reflected
+    pub fn is_complete(&self) -> bool {
+        self.received_count as usize == self.received.len()
+    }
+
+    /// Record the two-way delay of a reflected packet in arrival order
+    ///
+    /// Duplicates and sequence numbers outside the run are ignored.
+    pub fn record_reflection(&mut self, sequence: u32, delay_ms: f64) {
+        match self.received.get_mut(sequence as usize) {
+            Some(seen) if !*seen => *seen = true,
+            _ => return,
+        }
+        self.received_count += 1;
+
+        self.delay_sum_ms += delay_ms;
+        self.min_delay_ms = Some(self.min_delay_ms.map_or(delay_ms, |m| m.min(delay_ms)));
+        self.max_delay_ms = Some(self.max_delay_ms.map_or(delay_ms, |m| m.max(delay_ms)));
+
+        // RFC 3550 interarrival jitter over the two-way delay
+        if let Some(last_delay_ms) = self.last_delay_ms {
+            let difference = (delay_ms - last_delay_ms).abs();
+            self.jitter_ms += (difference - self.jitter_ms) / 16.0;
+        }
+        self.last_delay_ms = Some(delay_ms);
+    }
+
+    /// Build the metric for a run that sent `packets_sent` test packets
+    pub fn into_metric(
+        self,
+        packets_sent: u32,
+        params: &TwampParams,
+        error: Option<String>,
+    ) -> RawTwampMetric {
+        let received = self.received_count;
+        let packet_loss_percent = if packets_sent == 0 {
+            100.0
+        } else {
+            packets_sent.saturating_sub(received) as f64 / packets_sent as f64 * 100.0
+        };
+
+        let success = received > 0;
+        let error = if success {
+            None
+        } else {
+            Some(error.unwrap_or_else(|| format!("No test packets reflected by {}", params.host)))
+        };
+
+        RawTwampMetric {
+            packets_sent,
+            packets_received: received,
+            packet_loss_percent,
+            avg_latency_ms: success.then(|| self.delay_sum_ms / received as f64),
+            min_latency_ms: self.min_delay_ms,
+            max_latency_ms: self.max_delay_ms,
+            jitter_ms: success.then_some(self.jitter_ms),
+            success,
+            error,
+            host: params.host.clone(),
+            target_id: params.target_id.clone(),
+        }
+    }
+}
+
+/// Execute a TWAMP-Light run
+///
+/// # Arguments
+/// * `params` - TWAMP task parameters
+///
+/// # Returns
+/// * `RawTwampMetric` - Two-way delay, jitter and loss of the run
+pub async fn execute_twamp_task(params: &TwampParams) -> RawTwampMetric {
+    match run_session(params).await {
+        Ok(metric) => metric,
+        Err(e) => TwampStats::new(0).into_metric(0, params, Some(e.to_string())),
+    }
+}
+
+async fn run_session(params: &TwampParams) -> Result<RawTwampMetric> {
+    let socket = connected_udp_socket(&params.host).await?;
+
+    let mut stats = TwampStats::new(params.packet_count);
+    // Send time and sender timestamp of every packet, indexed by sequence number
+    let mut sent: Vec<(Instant, u64)> = Vec::with_capacity(params.packet_count as usize);
+    let mut packet = vec![0u8; params.packet_size as usize];
+    let mut reply = vec![0u8; (params.packet_size as usize).max(REFLECTOR_PACKET_SIZE) + 1];
+    let mut last_error: Option<String> = None;
+
+    let start = Instant::now();
+    let mut ticker = tokio::time::interval(Duration::from_millis(params.interval_ms as u64));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // Replaced by the reply deadline once the last packet is sent
+    let mut deadline = start + Duration::from_secs(params.run_duration_seconds() as u64);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick(), if (sent.len() as u32) < params.packet_count => {
+                let timestamp = ntp_timestamp_now();
+                SenderPacket {
+                    sequence: sent.len() as u32,
+                    timestamp,
+                    error_estimate: ERROR_ESTIMATE,
+                }
+                .encode(&mut packet);
+                sent.push((Instant::now(), timestamp));
+                if let Err(e) = socket.send(&packet).await {
+                    // The packet counts as lost
+                    debug!(host = %params.host, error = %e, "Failed to send TWAMP test packet");
+                    last_error = Some(format!("Send error: {}", e));
+                }
+                if sent.len() as u32 == params.packet_count {
+                    deadline = Instant::now() + Duration::from_secs(params.timeout_seconds as u64);
+                }
+            }
+            result = socket.recv(&mut reply) => match result {
+                Ok(len) => {
+                    let received_at = Instant::now();
+                    let Some(reflected) = ReflectorPacket::decode(&reply[..len]) else {
+                        continue;
+                    };
+                    // Only accept reflections of packets sent in this run
+                    let Some((sent_at, timestamp)) = sent.get(reflected.sender_sequence as usize)
+                    else {
+                        continue;
+                    };
+                    if *timestamp != reflected.sender_timestamp {
+                        continue;
+                    }
+                    let round_trip_ms = received_at.duration_since(*sent_at).as_secs_f64() * 1000.0;
+                    let delay_ms = (round_trip_ms - reflected.processing_time_ms()).max(0.0);
+                    stats.record_reflection(reflected.sender_sequence, delay_ms);
+                    if stats.is_complete() {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                    last_error = Some(format!("Port unreachable: {}", params.host));
+                }
+                Err(e) => return Err(anyhow!("Receive error: {}", e)),
+            },
+            _ = tokio::time::sleep_until(deadline) => break,
+        }
+    }
+
+    debug!(
+        host = %params.host,
+        packets_sent = sent.len(),
+        "TWAMP run finished"
+    );
+
+    Ok(stats.into_metric(sent.len() as u32, params, last_error))
+}
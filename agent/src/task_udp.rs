@@ -0,0 +1,297 @@
+//! UDP probe task implementation
+//!
+//! This module sends a paced stream of sequenced, timestamped UDP packets to a
+//! UDP reflector (another agent's `[udp_reflector]`) or a plain UDP echo server,
+//! and measures loss, duplication, reordering, round-trip time and jitter from
+//! the replies. This is the view of the network that voice and video traffic
+//! gets, which single-packet ICMP pings cannot give.
+//!
+//! Every packet starts with a fixed header (see `ProbeHeader`); the rest of the
+//! payload is padding. Echo servers return the packet unchanged, while the
+//! reflector marks it as a reply and adds the number of probes it has received
+//! in this run, which splits the loss into the forward and return directions.
+
+use crate::udp_socket::connected_udp_socket;
+use anyhow::{anyhow, Result};
+use shared::config::{UdpProbeParams, MIN_UDP_PROBE_PAYLOAD_SIZE};
+use shared::metrics::RawUdpProbeMetric;
+use std::time::Duration;
+use tokio::time::{Instant, MissedTickBehavior};
+use tracing::debug;
+
+/// Marks a datagram as a UDP probe packet
+pub const PROBE_MAGIC: &[u8; 4] = b"LSUP";
+
+/// Version of the probe packet format
+const PROBE_VERSION: u8 = 1;
+
+/// Size of the probe header at the start of every packet
+pub const PROBE_HEADER_SIZE: usize = MIN_UDP_PROBE_PAYLOAD_SIZE as usize;
+
+/// Whether a probe packet was sent by the prober or answered by a reflector
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProbeKind {
+    Request = 0,
+    Reply = 1,
+}
+
+/// Header of a UDP probe packet
+///
+/// Layout (network byte order): magic (4), version (1), kind (1), reserved (2),
+/// session ID (4), sequence number (4), send timestamp in microseconds since
+/// the start of the run (8), probes received by the reflector (4).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProbeHeader {
+    pub kind: ProbeKind,
+    /// Random identifier of the run, so that late replies to an earlier run are ignored
+    pub session_id: u32,
+    pub sequence: u32,
+    pub sent_at_us: u64,
+    /// Probes of this session received by the reflector (0 in requests)
+    pub reflected_count: u32,
+}
+
+impl ProbeHeader {
+    /// Write the header to the start of `buf`, which must hold at least `PROBE_HEADER_SIZE` bytes
+    pub fn encode(&self, buf: &mut [u8]) {
+        buf[0..4].copy_from_slice(PROBE_MAGIC);
+        buf[4] = PROBE_VERSION;
+        buf[5] = self.kind as u8;
+        buf[6..8].fill(0);
+        buf[8..12].copy_from_slice(&self.session_id.to_be_bytes());
+        buf[12..16].copy_from_slice(&self.sequence.to_be_bytes());
+        buf[16..24].copy_from_slice(&self.sent_at_us.to_be_bytes());
+        buf[24..28].copy_from_slice(&self.reflected_count.to_be_bytes());
+    }
+
+    /// Parse the header of a datagram, returning None if it is not a probe packet
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < PROBE_HEADER_SIZE || &buf[0..4] != PROBE_MAGIC || buf[4] != PROBE_VERSION {
+            return None;
+        }
+        let kind = match buf[5] {
+            0 => ProbeKind::Request,
+            1 => ProbeKind::Reply,
+            _ => return None,
+        };
+        Some(Self {
+            kind,
+            session_id: u32::from_be_bytes(buf[8..12].try_into().ok()?),
+            sequence: u32::from_be_bytes(buf[12..16].try_into().ok()?),
+            sent_at_us: u64::from_be_bytes(buf[16..24].try_into().ok()?),
+            reflected_count: u32::from_be_bytes(buf[24..28].try_into().ok()?),
+        })
+    }
+}
+
+/// Accumulates the replies of one probe run
+#[derive(Debug)]
+pub struct ProbeStats {
+    received: Vec<bool>,
+    received_count: u32,
+    highest_sequence: Option<u32>,
+    duplicates: u32,
+    reordered: u32,
+    rtt_sum_ms: f64,
+    min_rtt_ms: Option<f64>,
+    max_rtt_ms: Option<f64>,
+    last_rtt_ms: Option<f64>,
+    jitter_ms: f64,
+    reflected: Option<u32>,
+}
+
+impl ProbeStats {
+    pub fn new(packet_count: u32) -> Self {
+        Self {
+            received: vec![false; packet_count as usize],
+            received_count: 0,
+            highest_sequence: None,
+            duplicates: 0,
+            reordered: 0,
+            rtt_sum_ms: 0.0,
+            min_rtt_ms: None,
+            max_rtt_ms: None,
+            last_rtt_ms: None,
+            jitter_ms: 0.0,
+            reflected: None,
+        }
+    }
+
+    /// Returns true once a reply to every probe has arrived
+    pub fn is_complete(&self) -> bool {
+        self.received_count as usize == self.received.len()
+    }
+
+    /// Record a reply in arrival order
+    ///
+    /// `reflected_count` is the reflector's receive counter, or None if the
+    /// reply came from a plain echo server. Replies with a sequence number
+    /// outside the run are ignored.
+    pub fn record_reply(&mut self, sequence: u32, rtt_ms: f64, reflected_count: Option<u32>) {
+        let Some(seen) = self.received.get_mut(sequence as usize) else {
+            return;
+        };
+        if let Some(count) = reflected_count {
+            self.reflected = Some(self.reflected.map_or(count, |r| r.max(count)));
+        }
+        if *seen {
+            self.duplicates += 1;
+            return;
+        }
+        *seen = true;
+        self.received_count += 1;
+
+        match self.highest_sequence {
+            Some(highest) if sequence < highest => self.reordered += 1,
+            _ => self.highest_sequence = Some(sequence),
+        }
+
+        self.rtt_sum_ms += rtt_ms;
+        self.min_rtt_ms = Some(self.min_rtt_ms.map_or(rtt_ms, |m| m.min(rtt_ms)));
+        self.max_rtt_ms = Some(self.max_rtt_ms.map_or(rtt_ms, |m| m.max(rtt_ms)));
+
+        // RFC 3550 interarrival jitter, using round-trip time as the transit time
+        if let Some(last_rtt_ms) = self.last_rtt_ms {
+            let difference = (rtt_ms - last_rtt_ms).abs();
+            self.jitter_ms += (difference - self.jitter_ms) / 16.0;
+        }
+        self.last_rtt_ms = Some(rtt_ms);
+    }
+
+    /// Build the metric for a run that sent `packets_sent` probes
+    pub fn into_metric(
+        self,
+        packets_sent: u32,
+        params: &UdpProbeParams,
+        error: Option<String>,
+    ) -> RawUdpProbeMetric {
+        let received = self.received_count;
+        let percent_lost = |expected: u32, arrived: u32| {
+            if expected == 0 {
+                100.0
+            } else {
+                expected.saturating_sub(arrived) as f64 / expected as f64 * 100.0
+            }
+        };
+
+        // The reflector also counts probes whose reply was lost, so the
+        // difference between its counter and our replies is return loss
+        let packets_reflected = self.reflected.map(|count| count.min(packets_sent));
+        let forward_loss_percent = packets_reflected.map(|count| percent_lost(packets_sent, count));
+        let return_loss_percent = packets_reflected
+            .filter(|count| *count > 0)
+            .map(|count| percent_lost(count, received));
+
+        let success = received > 0;
+        let error = if success {
+            None
+        } else {
+            Some(error.unwrap_or_else(|| format!("No replies received from {}", params.host)))
+        };
+
+        RawUdpProbeMetric {
+            packets_sent,
+            packets_received: received,
+            packets_reflected,
+            loss_percent: percent_lost(packets_sent, received),
+            forward_loss_percent,
+            return_loss_percent,
+            duplicate_packets: self.duplicates,
+            reordered_packets: self.reordered,
+            avg_rtt_ms: success.then(|| self.rtt_sum_ms / received as f64),
+            min_rtt_ms: self.min_rtt_ms,
+            max_rtt_ms: self.max_rtt_ms,
+            jitter_ms: success.then_some(self.jitter_ms),
+            success,
+            error,
+            host: params.host.clone(),
+            target_id: params.target_id.clone(),
+        }
+    }
+}
+
+/// Execute a UDP probe run
+///
+/// # Arguments
+/// * `params` - UDP probe task parameters
+///
+/// # Returns
+/// * `RawUdpProbeMetric` - Loss, ordering and timing of the run
+pub async fn execute_udp_probe_task(params: &UdpProbeParams) -> RawUdpProbeMetric {
+    match run_probe(params).await {
+        Ok(metric) => metric,
+        Err(e) => ProbeStats::new(0).into_metric(0, params, Some(e.to_string())),
+    }
+}
+
+async fn run_probe(params: &UdpProbeParams) -> Result<RawUdpProbeMetric> {
+    let socket = connected_udp_socket(&params.host).await?;
+
+    let session_id: u32 = rand::random();
+    let mut stats = ProbeStats::new(params.packet_count);
+    let mut packet = vec![0u8; params.payload_size as usize];
+    let mut reply = vec![0u8; params.payload_size as usize + 1];
+    let mut packets_sent: u32 = 0;
+    let mut last_error: Option<String> = None;
+
+    let start = Instant::now();
+    let mut ticker = tokio::time::interval(Duration::from_millis(params.interval_ms as u64));
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    // Replaced by the reply deadline once the last packet is sent
+    let mut deadline = start + Duration::from_secs(params.run_duration_seconds() as u64);
+
+    loop {
+        tokio::select! {
+            _ = ticker.tick(), if packets_sent < params.packet_count => {
+                ProbeHeader {
+                    kind: ProbeKind::Request,
+                    session_id,
+                    sequence: packets_sent,
+                    sent_at_us: start.elapsed().as_micros() as u64,
+                    reflected_count: 0,
+                }
+                .encode(&mut packet);
+                if let Err(e) = socket.send(&packet).await {
+                    // The packet counts as lost
+                    debug!(host = %params.host, error = %e, "Failed to send UDP probe");
+                    last_error = Some(format!("Send error: {}", e));
+                }
+                packets_sent += 1;
+                if packets_sent == params.packet_count {
+                    deadline = Instant::now() + Duration::from_secs(params.timeout_seconds as u64);
+                }
+            }
+            result = socket.recv(&mut reply) => match result {
+                Ok(len) => {
+                    let received_at_us = start.elapsed().as_micros() as u64;
+                    let Some(header) = ProbeHeader::decode(&reply[..len]) else {
+                        continue;
+                    };
+                    if header.session_id != session_id || header.sent_at_us > received_at_us {
+                        continue;
+                    }
+                    let rtt_ms = (received_at_us - header.sent_at_us) as f64 / 1000.0;
+                    let reflected_count =
+                        (header.kind == ProbeKind::Reply).then_some(header.reflected_count);
+                    stats.record_reply(header.sequence, rtt_ms, reflected_count);
+                    if stats.is_complete() {
+                        break;
+                    }
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::ConnectionRefused => {
+                    last_error = Some(format!("Port unreachable: {}", params.host));
+                }
+                Err(e) => return Err(anyhow!("Receive error: {}", e)),
+            },
+            _ = tokio::time::sleep_until(deadline) => break,
+        }
+    }
+
+    debug!(
+        host = %params.host,
+        packets_sent = packets_sent,
+        "UDP probe run finished"
+    );
+
+    Ok(stats.into_metric(packets_sent, params, last_error))
+}
@@ -13,6 +13,7 @@
 //! Each task is implemented as a function that performs a network measurement
 //! and returns the result in a structured format.
 
+use crate::secrets::{resolve_task_secrets, SecretStore};
 use anyhow::{Context, Result};
 use shared::config::{TaskConfig, TaskParams, TaskType};
 use shared::metrics::{MetricData, RawMetricData};
@@ -38,6 +39,8 @@ pub struct TaskResult {
     pub error: Option<String>,
     /// The time it took to execute the task, in milliseconds.
     pub execution_time_ms: f64,
+    /// True if the run was aborted by the task timeout.
+    pub timed_out: bool,
 }
 
 /// The `TaskExecutor` is responsible for running individual monitoring tasks.
@@ -51,7 +54,7 @@ pub struct TaskExecutor {
     server_url: Option<String>,
     /// API key for server authentication (optional, only needed for server-connected agents)
     api_key: Option<String>,
-    /// Agent ID for identification (optional, only needed for server-connected agents)
+    /// Agent ID for identification in bandwidth tests
     agent_id: Option<String>,
     /// Shared HTTP client for HTTP content tasks (reused across all requests)
     http_content_client: reqwest::Client,
@@ -59,6 +62,11 @@ pub struct TaskExecutor {
     tls_connector_verify: TlsConnector,
     /// Shared TLS connector with verification disabled (reused across all TLS connections)
     tls_connector_no_verify: TlsConnector,
+    /// Idle SQL connections kept between runs of tasks with `pool_size > 0`
+    #[cfg(feature = "sql-tasks")]
+    sql_pools: Arc<crate::task_sql::SqlConnectionPools>,
+    /// Encrypted store used to resolve `secret:` references in task credentials
+    secret_store: Option<SecretStore>,
 }
 
 impl TaskExecutor {
@@ -96,9 +104,21 @@ impl TaskExecutor {
             http_content_client,
             tls_connector_verify,
             tls_connector_no_verify,
+            #[cfg(feature = "sql-tasks")]
+            sql_pools: Arc::new(crate::task_sql::SqlConnectionPools::default()),
+            secret_store: None,
         })
     }
 
+    /// Sets the secrets store used to resolve `secret:` references
+    ///
+    /// Without a store, `env:` and `file:` references still resolve but
+    /// `secret:` references fail the task.
+    pub fn with_secret_store(mut self, secret_store: SecretStore) -> Self {
+        self.secret_store = Some(secret_store);
+        self
+    }
+
     /// Refreshes HTTP clients and TLS connectors by dropping old instances and creating new ones
     ///
     /// This method explicitly drops the existing reqwest client and TLS connectors,
@@ -139,8 +159,12 @@ impl TaskExecutor {
         let timeout_duration = Duration::from_secs(task_config.get_effective_timeout() as u64);
 
         // Use tokio::select to handle timeout
-        let result = tokio::select! {
+        let (result, timed_out) = tokio::select! {
             task_result = async {
+                // Credential references are resolved per execution so that
+                // rotated secrets are picked up without a config reload
+                let resolved = resolve_task_secrets(task_config, self.secret_store.as_ref())?;
+                let task_config = &*resolved;
                 // A `match` statement is used to dispatch to the correct task function
                 // based on the `task_type` enum. This is a clean and type-safe way to
                 // handle different kinds of tasks.
@@ -153,14 +177,18 @@ impl TaskExecutor {
                     TaskType::DnsQuery => self.execute_dns_task(task_config).await,
                     TaskType::DnsQueryDoh => self.execute_dns_doh_task(task_config).await,
                     TaskType::Bandwidth => self.execute_bandwidth_task(task_config).await,
+                    TaskType::UdpProbe => self.execute_udp_probe_task(task_config).await,
+                    TaskType::Twamp => self.execute_twamp_task(task_config).await,
+                    TaskType::TcpSweep => self.execute_tcp_sweep_task(task_config).await,
+                    TaskType::Ntp => self.execute_ntp_task(task_config).await,
                     #[cfg(feature = "sql-tasks")]
                     TaskType::SqlQuery => self.execute_sql_query_task(task_config).await,
                     #[cfg(feature = "snmp-tasks")]
                     TaskType::Snmp => self.execute_snmp_task(task_config).await,
                 }
-            } => task_result,
+            } => (task_result, false),
             _ = tokio::time::sleep(timeout_duration) => {
-                Err(anyhow::anyhow!("Task timed out after {} seconds", task_config.get_effective_timeout()))
+                (Err(anyhow::anyhow!("Task timed out after {} seconds", task_config.get_effective_timeout())), true)
             }
         };
 
@@ -169,13 +197,18 @@ impl TaskExecutor {
         // The result of the task execution (either `Ok(MetricData)` or `Err(e)`)
         // is transformed into a `TaskResult` struct.
         let task_result = match result {
-            Ok(metric_data) => TaskResult {
-                task_name: task_config.name.clone(),
-                success: true,
-                metric_data: Some(metric_data),
-                error: None,
-                execution_time_ms: execution_time,
-            },
+            Ok(mut metric_data) => {
+                // Labels are attached here so that task modules don't need to know about them
+                metric_data.labels = task_config.labels.clone();
+                TaskResult {
+                    task_name: task_config.name.clone(),
+                    success: true,
+                    metric_data: Some(metric_data),
+                    error: None,
+                    execution_time_ms: execution_time,
+                    timed_out: false,
+                }
+            }
             Err(e) => {
                 error!("Task '{}' failed: {}", task_config.name, e);
                 TaskResult {
@@ -184,6 +217,7 @@ impl TaskExecutor {
                     metric_data: None,
                     error: Some(e.to_string()),
                     execution_time_ms: execution_time,
+                    timed_out,
                 }
             }
         };
@@ -426,6 +460,82 @@ impl TaskExecutor {
         }
     }
 
+    /// Executes a UDP probe task
+    async fn execute_udp_probe_task(&self, task_config: &TaskConfig) -> Result<MetricData> {
+        debug!("Executing UDP probe task: {}", task_config.name);
+
+        if let TaskParams::UdpProbe(params) = &task_config.params {
+            let result = crate::task_udp::execute_udp_probe_task(params).await;
+
+            let metric_data = MetricData::new(
+                task_config.name.clone(),
+                TaskType::UdpProbe,
+                RawMetricData::UdpProbe(result),
+            );
+
+            Ok(metric_data)
+        } else {
+            Err(anyhow::anyhow!("Invalid parameters for UDP probe task"))
+        }
+    }
+
+    /// Executes a TWAMP-Light task
+    async fn execute_twamp_task(&self, task_config: &TaskConfig) -> Result<MetricData> {
+        debug!("Executing TWAMP task: {}", task_config.name);
+
+        if let TaskParams::Twamp(params) = &task_config.params {
+            let result = crate::task_twamp::execute_twamp_task(params).await;
+
+            let metric_data = MetricData::new(
+                task_config.name.clone(),
+                TaskType::Twamp,
+                RawMetricData::Twamp(result),
+            );
+
+            Ok(metric_data)
+        } else {
+            Err(anyhow::anyhow!("Invalid parameters for TWAMP task"))
+        }
+    }
+
+    /// Executes a TCP sweep task
+    async fn execute_tcp_sweep_task(&self, task_config: &TaskConfig) -> Result<MetricData> {
+        debug!("Executing TCP sweep task: {}", task_config.name);
+
+        if let TaskParams::TcpSweep(params) = &task_config.params {
+            let result = crate::task_tcp_sweep::execute_tcp_sweep_task(params).await;
+
+            let metric_data = MetricData::new(
+                task_config.name.clone(),
+                TaskType::TcpSweep,
+                RawMetricData::TcpSweep(result),
+            );
+
+            Ok(metric_data)
+        } else {
+            Err(anyhow::anyhow!("Invalid parameters for TCP sweep task"))
+        }
+    }
+
+    /// Executes an NTP query task
+    async fn execute_ntp_task(&self, task_config: &TaskConfig) -> Result<MetricData> {
+        debug!("Executing NTP task: {}", task_config.name);
+
+        if let TaskParams::Ntp(params) = &task_config.params {
+            let result = crate::task_ntp::execute_ntp_task(params).await;
+
+            let metric_data = MetricData::new(
+                task_config.name.clone(),
+                TaskType::Ntp,
+                RawMetricData::Ntp(result),
+            );
+
+            Ok(metric_data)
+        } else {
+            Err(anyhow::anyhow!("Invalid parameters for NTP task"))
+        }
+    }
+
     /// Executes an HTTP content check task
     async fn execute_http_content_task(&self, task_config: &TaskConfig) -> Result<MetricData> {
         debug!("Executing HTTP content task: {}", task_config.name);
@@ -542,40 +652,26 @@ impl TaskExecutor {
 
     /// Executes a bandwidth test task
     ///
-    /// Coordinates with the server to ensure only one test runs at a time,
-    /// then downloads test data and measures the bandwidth.
+    /// Coordinates with the server when required so that tests do not overlap,
+    /// then transfers test data to the target and measures the bandwidth.
     async fn execute_bandwidth_task(&self, task_config: &TaskConfig) -> Result<MetricData> {
         debug!("Executing bandwidth task: {}", task_config.name);
 
         if let TaskParams::Bandwidth(params) = &task_config.params {
-            // Check if we have the required configuration for bandwidth tests
-            let (server_url, api_key, agent_id) = match (
-                self.server_url.as_ref(),
-                self.api_key.as_ref(),
-                self.agent_id.as_ref(),
-            ) {
-                (Some(url), Some(key), Some(id)) => (url.as_str(), key.as_str(), id.as_str()),
-                _ => {
-                    return Ok(MetricData::new(
-                        task_config.name.clone(),
-                        TaskType::Bandwidth,
-                        RawMetricData::Bandwidth(shared::metrics::RawBandwidthMetric {
-                            bandwidth_mbps: None,
-                            duration_ms: None,
-                            bytes_downloaded: None,
-                            success: false,
-                            error: Some(
-                                "Bandwidth test requires server configuration (not available in local-only mode)"
-                                    .to_string(),
-                            ),
-                            target_id: params.target_id.clone(),
-                        }),
-                    ));
-                }
+            // Server configuration is only needed for tests coordinated with the server
+            let server = match (self.server_url.as_ref(), self.api_key.as_ref()) {
+                (Some(url), Some(key)) => Some(crate::task_bandwidth::ServerConnection {
+                    url,
+                    api_key: key,
+                    agent_id: self.agent_id.as_deref().unwrap_or_default(),
+                }),
+                _ => None,
             };
 
             let metric_result = crate::task_bandwidth::execute_bandwidth_task(
-                params, server_url, api_key, agent_id,
+                params,
+                server,
+                self.agent_id.as_deref().unwrap_or_default(),
             )
             .await;
 
@@ -592,9 +688,18 @@ impl TaskExecutor {
                         bandwidth_mbps: None,
                         duration_ms: None,
                         bytes_downloaded: None,
+                        upload_mbps: None,
+                        upload_duration_ms: None,
+                        bytes_uploaded: None,
+                        parallel_streams: None,
+                        stream_mbps: None,
+                        throughput_series_mbps: None,
+                        upload_stream_mbps: None,
+                        upload_throughput_series_mbps: None,
+                        target_url: params.target_url().map(str::to_string),
                         success: false,
                         error: Some(e.to_string()),
-                        target_id: params.target_id.clone(),
+                        target_id: params.effective_target_id(),
                     }),
                 ),
             };
@@ -611,7 +716,9 @@ impl TaskExecutor {
         debug!("Executing SQL query task: {}", task_config.name);
 
         if let TaskParams::SqlQuery(params) = &task_config.params {
-            let metric_result = crate::task_sql::execute_sql_query(params).await;
+            let metric_result =
+                crate::task_sql::execute_sql_query(&task_config.name, params, &self.sql_pools)
+                    .await;
 
             let metric_data = match metric_result {
                 Ok(metric) => MetricData::new(
@@ -633,6 +740,10 @@ impl TaskExecutor {
                         json_result: None,
                         json_truncated: false,
                         column_count: None,
+                        connect_time_ms: None,
+                        query_time_ms: None,
+                        status: shared::metrics::ThresholdStatus::Error,
+                        status_message: None,
                     }),
                 ),
             };
@@ -668,6 +779,7 @@ impl TaskExecutor {
                         oid_queried: params.oid.clone(),
                         error: Some(e.to_string()),
                         target_id: params.target_id.clone(),
+                        numeric_value: None,
                     }),
                 ),
             };
@@ -0,0 +1,141 @@
+//! Tests for agent-to-agent and third-party bandwidth targets
+
+use crate::bandwidth_reflector::BandwidthReflector;
+use crate::task_bandwidth::execute_bandwidth_task;
+use axum::{
+    body::Body,
+    routing::{get, post},
+    Router,
+};
+use futures_util::StreamExt;
+use shared::config::{BandwidthDirection, BandwidthParams, BandwidthReflectorConfig};
+use std::net::SocketAddr;
+
+const REFLECTOR_KEY: &str = "reflector-key";
+
+fn bandwidth_params(direction: BandwidthDirection) -> BandwidthParams {
+    BandwidthParams {
+        timeout_seconds: 30,
+        max_retries: 1,
+        direction,
+        parallel_streams: 1,
+        url: None,
+        upload_url: None,
+        reflector_url: None,
+        reflector_api_key: Some(REFLECTOR_KEY.to_string()),
+        data_size_mb: Some(1),
+        coordinate_with_server: None,
+        target_id: None,
+    }
+}
+
+/// Starts a reflector on an ephemeral port, returning its address
+async fn start_reflector(max_test_size_mb: u32) -> SocketAddr {
+    let config = BandwidthReflectorConfig {
+        listen_address: "127.0.0.1:0".to_string(),
+        api_key: None,
+        max_test_size_mb,
+    };
+    let reflector = BandwidthReflector::new(&config, REFLECTOR_KEY.to_string()).unwrap();
+    let listener = reflector.bind().await.unwrap();
+    let address = listener.local_addr().unwrap();
+    let (shutdown_tx, shutdown_rx) = tokio::sync::broadcast::channel(1);
+    tokio::spawn(async move {
+        reflector.run(listener, shutdown_rx).await;
+        drop(shutdown_tx);
+    });
+    address
+}
+
+#[tokio::test]
+async fn test_reflector_both_directions_without_server() {
+    let address = start_reflector(10).await;
+    let mut params = bandwidth_params(BandwidthDirection::Both);
+    params.reflector_url = Some(format!("http://{}", address));
+    params.parallel_streams = 2;
+    assert!(!params.coordinates_with_server());
+
+    let metric = execute_bandwidth_task(&params, None, "branch-a")
+        .await
+        .unwrap();
+
+    assert!(metric.success, "error: {:?}", metric.error);
+    assert_eq!(metric.bytes_downloaded, Some(1024 * 1024));
+    assert_eq!(metric.bytes_uploaded, Some(1024 * 1024));
+    assert_eq!(metric.stream_mbps.as_ref().unwrap().len(), 2);
+    assert_eq!(
+        metric.target_url.as_deref(),
+        Some(format!("http://{}", address).as_str())
+    );
+    // Results are grouped by the reflector's address unless a target_id is set
+    assert_eq!(metric.target_id, Some(address.to_string()));
+}
+
+#[tokio::test]
+async fn test_reflector_rejects_wrong_key_and_oversized_tests() {
+    let address = start_reflector(1).await;
+
+    let mut params = bandwidth_params(BandwidthDirection::Download);
+    params.reflector_url = Some(format!("http://{}", address));
+    params.reflector_api_key = Some("wrong-key".to_string());
+    let error = execute_bandwidth_task(&params, None, "branch-a")
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("401"), "error: {}", error);
+
+    params.reflector_api_key = Some(REFLECTOR_KEY.to_string());
+    params.data_size_mb = Some(2);
+    let error = execute_bandwidth_task(&params, None, "branch-a")
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("400"), "error: {}", error);
+}
+
+#[tokio::test]
+async fn test_third_party_url_target() {
+    const FILE_SIZE: usize = 256 * 1024;
+
+    let app = Router::new()
+        .route("/file.bin", get(|| async { vec![7u8; FILE_SIZE] }))
+        .route(
+            "/upload",
+            post(|body: Body| async move {
+                let mut stream = body.into_data_stream();
+                while stream.next().await.is_some() {}
+                "ok"
+            }),
+        );
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move { axum::serve(listener, app).await });
+
+    let mut params = bandwidth_params(BandwidthDirection::Both);
+    params.url = Some(format!("http://{}/file.bin", address));
+    params.upload_url = Some(format!("http://{}/upload", address));
+    params.reflector_api_key = None;
+    params.parallel_streams = 2;
+    params.target_id = Some("cdn".to_string());
+
+    let metric = execute_bandwidth_task(&params, None, "branch-a")
+        .await
+        .unwrap();
+
+    assert!(metric.success, "error: {:?}", metric.error);
+    // Every stream downloads the whole file
+    assert_eq!(metric.bytes_downloaded, Some(2 * FILE_SIZE as u64));
+    // The upload is credited once the server accepts it
+    assert_eq!(metric.bytes_uploaded, Some(1024 * 1024));
+    assert_eq!(metric.target_id.as_deref(), Some("cdn"));
+}
+
+#[tokio::test]
+async fn test_coordinated_target_requires_server() {
+    let mut params = bandwidth_params(BandwidthDirection::Download);
+    params.reflector_url = Some("http://127.0.0.1:9".to_string());
+    params.coordinate_with_server = Some(true);
+
+    let error = execute_bandwidth_task(&params, None, "branch-a")
+        .await
+        .unwrap_err();
+    assert!(error.to_string().contains("local-only"), "error: {}", error);
+}
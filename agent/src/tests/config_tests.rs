@@ -1,7 +1,8 @@
 //! Tests for configuration management implementation
 
 use crate::config::ConfigManager;
-use shared::config::{AgentConfig, PingParams, TaskConfig, TaskParams, TaskType, TasksConfig};
+use shared::config::{AgentConfig, PingParams, TaskConfig, TaskParams, TasksConfig};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use tempfile::TempDir;
 use tokio::fs;
@@ -19,30 +20,40 @@ async fn create_test_config_files(dir: &std::path::Path) -> anyhow::Result<()> {
         metrics_flush_interval_seconds: 5,
         metrics_send_interval_seconds: 30,
         metrics_batch_size: 50,
+        raw_metrics_batch_max_size_kb: 1024,
         metrics_max_retries: 10,
         queue_cleanup_interval_seconds: 3600,
         data_cleanup_interval_seconds: 86400,
         max_concurrent_tasks: 50,
+        max_concurrent_per_type: HashMap::new(),
         http_response_max_size_mb: 100,
         http_client_timeout_seconds: 30,
         database_busy_timeout_seconds: 5,
         graceful_shutdown_timeout_seconds: 30,
         channel_buffer_size: 1000,
         http_client_refresh_interval_seconds: 3600,
+        labels: Default::default(),
+        public_ip_url: None,
+        host_facts_refresh_interval_seconds: 900,
+        bandwidth_reflector: None,
+        udp_reflector: None,
+        twamp_responder: None,
+        #[cfg(feature = "snmp-tasks")]
+        snmp_trap_receiver: None,
     };
 
     let tasks_config = TasksConfig {
-        tasks: vec![TaskConfig {
-            task_type: TaskType::Ping,
-            schedule_seconds: 10,
-            name: "Test Ping".to_string(),
-            timeout: None,
-            params: TaskParams::Ping(PingParams {
+        aggregation_window_seconds: 60,
+        maintenance_windows: Vec::new(),
+        tasks: vec![TaskConfig::new(
+            "Test Ping",
+            10,
+            TaskParams::Ping(PingParams {
                 host: "8.8.8.8".to_string(),
                 timeout_seconds: 1,
                 target_id: None,
             }),
-        }],
+        )],
     };
 
     let agent_toml = toml::to_string(&agent_config)?;
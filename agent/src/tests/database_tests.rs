@@ -117,15 +117,42 @@ async fn test_generate_ping_aggregated_metrics() {
     assert_eq!(agg.task_name, "test_ping");
     assert_eq!(agg.sample_count, 3);
 
-    if let AggregatedMetricData::Ping(ping_data) = agg.data {
+    if let AggregatedMetricData::Ping(ping_data) = &agg.data {
         assert_eq!(ping_data.successful_pings, 2);
         assert_eq!(ping_data.failed_pings, 1);
         assert_eq!(ping_data.avg_latency_ms, 15.0); // (10 + 20) / 2
         assert!((ping_data.packet_loss_percent - 33.333333333333336).abs() < 0.0001);
-    // 1 failed out of 3, with floating point tolerance
+        // 1 failed out of 3, with floating point tolerance
+
+        // The distribution only covers successful pings
+        let latency = ping_data.latency.as_ref().unwrap();
+        assert_eq!(latency.p50_ms, 10.0);
+        assert_eq!(latency.p95_ms, 20.0);
+        assert_eq!(latency.histogram, vec![0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0]);
     } else {
         panic!("Expected ping aggregated data");
     }
+
+    // The aggregate survives the send queue round trip
+    db.store_and_enqueue_aggregated_metrics(&agg).await.unwrap();
+    let queued = db.get_metrics_to_send(10).await.unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].metric, agg);
+
+    // So do the maintenance and adaptive scheduling tags and the task labels
+    let mut maintenance_agg = agg.clone();
+    maintenance_agg.period_start += 60;
+    maintenance_agg.period_end += 60;
+    maintenance_agg.maintenance = true;
+    maintenance_agg.adaptive = true;
+    maintenance_agg
+        .labels
+        .insert("site".to_string(), "paris".to_string());
+    db.store_and_enqueue_aggregated_metrics(&maintenance_agg)
+        .await
+        .unwrap();
+    let queued = db.get_metrics_to_send(10).await.unwrap();
+    assert!(queued.iter().any(|q| q.metric == maintenance_agg));
 }
 
 #[tokio::test]
@@ -233,6 +260,15 @@ async fn test_store_raw_bandwidth_metric() {
             bandwidth_mbps: Some(100.5),
             duration_ms: Some(5000.0),
             bytes_downloaded: Some(62_500_000),
+            upload_mbps: None,
+            upload_duration_ms: None,
+            bytes_uploaded: None,
+            parallel_streams: None,
+            stream_mbps: None,
+            throughput_series_mbps: None,
+            upload_stream_mbps: None,
+            upload_throughput_series_mbps: None,
+            target_url: None,
             success: true,
             error: None,
             target_id: None,
@@ -244,11 +280,394 @@ async fn test_store_raw_bandwidth_metric() {
     assert!(result.unwrap() > 0);
 }
 
+#[tokio::test]
+async fn test_generate_bandwidth_aggregated_upload() {
+    use shared::metrics::RawBandwidthMetric;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = AgentDatabase::new(temp_dir.path(), 5).unwrap();
+    db.initialize().await.unwrap();
+
+    // Two bidirectional runs and one download-only run
+    let runs = [
+        (Some(100.0), Some(20.0)),
+        (Some(80.0), Some(40.0)),
+        (Some(90.0), None),
+    ];
+    for (download, upload) in runs {
+        let metric = MetricData::new(
+            "test_bandwidth_upload".to_string(),
+            TaskType::Bandwidth,
+            RawMetricData::Bandwidth(RawBandwidthMetric {
+                bandwidth_mbps: download,
+                duration_ms: Some(1000.0),
+                bytes_downloaded: Some(12_500_000),
+                upload_mbps: upload,
+                upload_duration_ms: upload.map(|_| 2000.0),
+                bytes_uploaded: upload.map(|_| 5_000_000),
+                parallel_streams: None,
+                stream_mbps: None,
+                throughput_series_mbps: None,
+                upload_stream_mbps: None,
+                upload_throughput_series_mbps: None,
+                target_url: None,
+                success: true,
+                error: None,
+                target_id: None,
+            }),
+        );
+        db.store_raw_metric(&metric).await.unwrap();
+    }
+
+    let now = current_timestamp();
+    let agg = db
+        .generate_aggregated_metrics(
+            "test_bandwidth_upload",
+            &TaskType::Bandwidth,
+            now - 60,
+            now + 60,
+        )
+        .await
+        .unwrap()
+        .expect("aggregated bandwidth metric");
+
+    let AggregatedMetricData::Bandwidth(bandwidth) = agg.data else {
+        panic!("Expected bandwidth aggregated data");
+    };
+    assert_eq!(bandwidth.avg_bandwidth_mbps, 90.0);
+    assert_eq!(bandwidth.avg_upload_mbps, Some(30.0));
+    assert_eq!(bandwidth.max_upload_mbps, Some(40.0));
+    assert_eq!(bandwidth.min_upload_mbps, Some(20.0));
+    assert_eq!(bandwidth.successful_tests, 3);
+}
+
+#[tokio::test]
+async fn test_generate_udp_probe_aggregated_metrics() {
+    use shared::metrics::RawUdpProbeMetric;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = AgentDatabase::new(temp_dir.path(), 5).unwrap();
+    db.initialize().await.unwrap();
+
+    // (sent, received, reflected, avg rtt, jitter)
+    let runs = [
+        (50, 50, Some(50), Some(20.0), Some(1.0)),
+        (50, 45, Some(48), Some(30.0), Some(3.0)),
+        (50, 0, None, None, None),
+    ];
+    for (sent, received, reflected, rtt, jitter) in runs {
+        let metric = MetricData::new(
+            "test_udp_probe".to_string(),
+            TaskType::UdpProbe,
+            RawMetricData::UdpProbe(RawUdpProbeMetric {
+                packets_sent: sent,
+                packets_received: received,
+                packets_reflected: reflected,
+                loss_percent: (sent - received) as f64 / sent as f64 * 100.0,
+                forward_loss_percent: reflected.map(|r| (sent - r) as f64 / sent as f64 * 100.0),
+                return_loss_percent: reflected.map(|r| (r - received) as f64 / r as f64 * 100.0),
+                duplicate_packets: 1,
+                reordered_packets: 2,
+                avg_rtt_ms: rtt,
+                min_rtt_ms: rtt.map(|r| r - 5.0),
+                max_rtt_ms: rtt.map(|r| r + 5.0),
+                jitter_ms: jitter,
+                success: received > 0,
+                error: (received == 0).then(|| "No replies received".to_string()),
+                host: "10.2.0.5:8789".to_string(),
+                target_id: Some("branch-b".to_string()),
+            }),
+        );
+        db.store_raw_metric(&metric).await.unwrap();
+    }
+
+    let now = current_timestamp();
+    let agg = db
+        .generate_aggregated_metrics("test_udp_probe", &TaskType::UdpProbe, now - 60, now + 60)
+        .await
+        .unwrap()
+        .expect("aggregated UDP probe metric");
+
+    assert_eq!(agg.sample_count, 3);
+    let AggregatedMetricData::UdpProbe(udp) = &agg.data else {
+        panic!("Expected UDP probe aggregated data");
+    };
+    // Loss is computed over all packets, including the failed run
+    assert_eq!(udp.packets_sent, 150);
+    assert_eq!(udp.packets_received, 95);
+    assert!((udp.loss_percent - 55.0 / 150.0 * 100.0).abs() < 1e-9);
+    // Forward and return loss only come from runs against a reflector
+    assert_eq!(udp.forward_loss_percent, Some(2.0));
+    assert_eq!(udp.duplicate_packets, 3);
+    assert_eq!(udp.reordered_packets, 6);
+    assert_eq!(udp.avg_rtt_ms, 25.0);
+    assert_eq!(udp.min_rtt_ms, 15.0);
+    assert_eq!(udp.max_rtt_ms, 35.0);
+    assert_eq!(udp.avg_jitter_ms, 2.0);
+    assert_eq!(udp.max_jitter_ms, 3.0);
+    assert_eq!(udp.successful_probes, 2);
+    assert_eq!(udp.failed_probes, 1);
+    assert_eq!(udp.target_id.as_deref(), Some("branch-b"));
+    assert_eq!(
+        udp.mos,
+        shared::utils::estimate_mos(25.0, 2.0, udp.loss_percent)
+    );
+
+    // The aggregate survives the send queue round trip
+    db.store_and_enqueue_aggregated_metrics(&agg).await.unwrap();
+    let queued = db.get_metrics_to_send(10).await.unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].metric, agg);
+}
+
+#[tokio::test]
+async fn test_generate_twamp_aggregated_metrics() {
+    use shared::metrics::RawTwampMetric;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = AgentDatabase::new(temp_dir.path(), 5).unwrap();
+    db.initialize().await.unwrap();
+
+    // (sent, received, avg delay, jitter)
+    let runs = [
+        (10, 10, Some(2.0), Some(0.2)),
+        (10, 8, Some(4.0), Some(0.6)),
+        (10, 0, None, None),
+    ];
+    for (sent, received, delay, jitter) in runs {
+        let metric = MetricData::new(
+            "test_twamp".to_string(),
+            TaskType::Twamp,
+            RawMetricData::Twamp(RawTwampMetric {
+                packets_sent: sent,
+                packets_received: received,
+                packet_loss_percent: (sent - received) as f64 / sent as f64 * 100.0,
+                avg_latency_ms: delay,
+                min_latency_ms: delay.map(|d| d - 1.0),
+                max_latency_ms: delay.map(|d| d + 1.0),
+                jitter_ms: jitter,
+                success: received > 0,
+                error: (received == 0).then(|| "No test packets reflected".to_string()),
+                host: "10.0.0.1:862".to_string(),
+                target_id: Some("core-1".to_string()),
+            }),
+        );
+        db.store_raw_metric(&metric).await.unwrap();
+    }
+
+    let now = current_timestamp();
+    let agg = db
+        .generate_aggregated_metrics("test_twamp", &TaskType::Twamp, now - 60, now + 60)
+        .await
+        .unwrap()
+        .expect("aggregated TWAMP metric");
+
+    assert_eq!(agg.sample_count, 3);
+    let AggregatedMetricData::Twamp(twamp) = &agg.data else {
+        panic!("Expected TWAMP aggregated data");
+    };
+    // Loss is computed over all test packets, including the failed run
+    assert_eq!(twamp.packets_sent, 30);
+    assert_eq!(twamp.packets_received, 18);
+    assert_eq!(twamp.packet_loss_percent, 40.0);
+    assert_eq!(twamp.avg_latency_ms, 3.0);
+    assert_eq!(twamp.min_latency_ms, 1.0);
+    assert_eq!(twamp.max_latency_ms, 5.0);
+    assert!((twamp.avg_jitter_ms - 0.4).abs() < 1e-9);
+    assert_eq!(twamp.max_jitter_ms, 0.6);
+    assert_eq!(twamp.successful_tests, 2);
+    assert_eq!(twamp.failed_tests, 1);
+    assert_eq!(twamp.host, "10.0.0.1:862");
+    assert_eq!(twamp.target_id.as_deref(), Some("core-1"));
+
+    // The aggregate survives the send queue round trip
+    db.store_and_enqueue_aggregated_metrics(&agg).await.unwrap();
+    let queued = db.get_metrics_to_send(10).await.unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].metric, agg);
+}
+
+#[tokio::test]
+async fn test_generate_tcp_sweep_aggregated_metrics() {
+    use shared::metrics::{RawTcpSweepMetric, TcpSweepState, TcpSweepTargetResult};
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = AgentDatabase::new(temp_dir.path(), 5).unwrap();
+    db.initialize().await.unwrap();
+
+    // Two targets per sweep; the second target goes down in the second sweep
+    let sweeps = [
+        [
+            (TcpSweepState::Open, Some(2.0)),
+            (TcpSweepState::Open, Some(4.0)),
+        ],
+        [
+            (TcpSweepState::Open, Some(6.0)),
+            (TcpSweepState::Filtered, None),
+        ],
+    ];
+    for sweep in sweeps {
+        let results: Vec<TcpSweepTargetResult> = ["10.0.1.1:443", "10.0.1.2:443"]
+            .iter()
+            .zip(sweep)
+            .map(|(target, (state, connect_time_ms))| TcpSweepTargetResult {
+                target: target.to_string(),
+                state,
+                connect_time_ms,
+            })
+            .collect();
+        let open_count = results
+            .iter()
+            .filter(|r| r.state == TcpSweepState::Open)
+            .count();
+        let metric = MetricData::new(
+            "test_tcp_sweep".to_string(),
+            TaskType::TcpSweep,
+            RawMetricData::TcpSweep(RawTcpSweepMetric {
+                targets_total: 2,
+                open_count: open_count as u32,
+                closed_count: 0,
+                filtered_count: 2 - open_count as u32,
+                unresolved_count: 0,
+                avg_connect_time_ms: None,
+                min_connect_time_ms: None,
+                max_connect_time_ms: None,
+                duration_ms: 1000.0,
+                results,
+                success: true,
+                error: None,
+                target_id: Some("farm".to_string()),
+            }),
+        );
+        db.store_raw_metric(&metric).await.unwrap();
+    }
+
+    let now = current_timestamp();
+    let agg = db
+        .generate_aggregated_metrics("test_tcp_sweep", &TaskType::TcpSweep, now - 60, now + 60)
+        .await
+        .unwrap()
+        .expect("aggregated TCP sweep metric");
+
+    assert_eq!(agg.sample_count, 2);
+    let AggregatedMetricData::TcpSweep(sweep) = &agg.data else {
+        panic!("Expected TCP sweep aggregated data");
+    };
+    assert_eq!(sweep.targets_total, 2);
+    assert_eq!(sweep.avg_open_count, 1.5);
+    assert_eq!(sweep.min_open_count, 1);
+    assert_eq!(sweep.avg_filtered_count, 0.5);
+    assert_eq!(sweep.avg_connect_time_ms, 4.0);
+    assert_eq!(sweep.min_connect_time_ms, 2.0);
+    assert_eq!(sweep.max_connect_time_ms, 6.0);
+    assert_eq!(sweep.successful_sweeps, 2);
+    assert_eq!(sweep.failed_sweeps, 0);
+    assert_eq!(sweep.target_id.as_deref(), Some("farm"));
+
+    assert_eq!(sweep.targets.len(), 2);
+    assert_eq!(sweep.targets[0].target, "10.0.1.1:443");
+    assert_eq!(sweep.targets[0].open_percent, 100.0);
+    assert_eq!(sweep.targets[0].avg_connect_time_ms, Some(4.0));
+    assert_eq!(sweep.targets[1].open_percent, 50.0);
+    assert_eq!(sweep.targets[1].avg_connect_time_ms, Some(4.0));
+    assert_eq!(sweep.targets[1].last_state, TcpSweepState::Filtered);
+
+    // The aggregate survives the send queue round trip
+    db.store_and_enqueue_aggregated_metrics(&agg).await.unwrap();
+    let queued = db.get_metrics_to_send(10).await.unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].metric, agg);
+}
+
+#[tokio::test]
+async fn test_generate_ntp_aggregated_metrics() {
+    use shared::metrics::{RawNtpMetric, ThresholdStatus};
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = AgentDatabase::new(temp_dir.path(), 5).unwrap();
+    db.initialize().await.unwrap();
+
+    // (offset, delay, stratum, reference ID, status)
+    let queries = [
+        (
+            Some(12.0),
+            Some(4.0),
+            Some(2),
+            Some("10.0.0.1"),
+            ThresholdStatus::Ok,
+        ),
+        (
+            Some(-30.0),
+            Some(8.0),
+            Some(3),
+            Some("10.0.0.2"),
+            ThresholdStatus::Warning,
+        ),
+        (None, None, None, None, ThresholdStatus::Error),
+    ];
+    for (offset, delay, stratum, reference_id, status) in queries {
+        let success = status != ThresholdStatus::Error;
+        let metric = MetricData::new(
+            "test_ntp".to_string(),
+            TaskType::Ntp,
+            RawMetricData::Ntp(RawNtpMetric {
+                offset_ms: offset,
+                delay_ms: delay,
+                stratum,
+                reference_id: reference_id.map(String::from),
+                leap_indicator: success.then_some(0),
+                success,
+                error: (!success).then(|| "Timeout".to_string()),
+                status,
+                status_message: None,
+                host: "pool.ntp.org".to_string(),
+                target_id: Some("ntp-1".to_string()),
+            }),
+        );
+        db.store_raw_metric(&metric).await.unwrap();
+    }
+
+    let now = current_timestamp();
+    let agg = db
+        .generate_aggregated_metrics("test_ntp", &TaskType::Ntp, now - 60, now + 60)
+        .await
+        .unwrap()
+        .expect("aggregated NTP metric");
+
+    assert_eq!(agg.sample_count, 3);
+    let AggregatedMetricData::Ntp(ntp) = &agg.data else {
+        panic!("Expected NTP aggregated data");
+    };
+    assert_eq!(ntp.avg_offset_ms, -9.0);
+    assert_eq!(ntp.min_offset_ms, -30.0);
+    assert_eq!(ntp.max_offset_ms, 12.0);
+    assert_eq!(ntp.max_abs_offset_ms, 30.0);
+    assert_eq!(ntp.avg_delay_ms, 6.0);
+    assert_eq!(ntp.max_delay_ms, 8.0);
+    // Stratum and reference ID of the last successful query
+    assert_eq!(ntp.stratum, Some(3));
+    assert_eq!(ntp.reference_id.as_deref(), Some("10.0.0.2"));
+    assert_eq!(ntp.successful_queries, 2);
+    assert_eq!(ntp.failed_queries, 1);
+    assert_eq!(ntp.ok_count, 1);
+    assert_eq!(ntp.warning_count, 1);
+    assert_eq!(ntp.critical_count, 0);
+    assert_eq!(ntp.error_count, 1);
+    assert_eq!(ntp.host, "pool.ntp.org");
+    assert_eq!(ntp.target_id.as_deref(), Some("ntp-1"));
+
+    // The aggregate survives the send queue round trip
+    db.store_and_enqueue_aggregated_metrics(&agg).await.unwrap();
+    let queued = db.get_metrics_to_send(10).await.unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].metric, agg);
+}
+
 #[tokio::test]
 #[cfg(feature = "sql-tasks")]
 async fn test_store_raw_sql_query_metric() {
     use shared::config::SqlQueryMode;
-    use shared::metrics::RawSqlQueryMetric;
+    use shared::metrics::{RawSqlQueryMetric, ThresholdStatus};
 
     let temp_dir = TempDir::new().unwrap();
     let mut db = AgentDatabase::new(temp_dir.path(), 5).unwrap();
@@ -268,6 +687,10 @@ async fn test_store_raw_sql_query_metric() {
             json_result: None,
             json_truncated: false,
             column_count: Some(1),
+            connect_time_ms: Some(25.3),
+            query_time_ms: Some(100.4),
+            status: ThresholdStatus::Ok,
+            status_message: None,
         }),
     );
 
@@ -276,6 +699,112 @@ async fn test_store_raw_sql_query_metric() {
     assert!(result.unwrap() > 0);
 }
 
+#[tokio::test]
+#[cfg(feature = "sql-tasks")]
+async fn test_generate_sql_aggregated_status_distribution() {
+    use shared::config::SqlQueryMode;
+    use shared::metrics::{RawSqlQueryMetric, ThresholdStatus};
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = AgentDatabase::new(temp_dir.path(), 5).unwrap();
+    db.initialize().await.unwrap();
+
+    let runs = [
+        (ThresholdStatus::Ok, Some(10.0)),
+        (ThresholdStatus::Ok, Some(20.0)),
+        (ThresholdStatus::Warning, Some(400.0)),
+        (ThresholdStatus::Critical, Some(900.0)),
+        (ThresholdStatus::Error, None),
+    ];
+    for (status, value) in runs {
+        let success = status != ThresholdStatus::Error;
+        let metric = MetricData::new(
+            "test_sql_status".to_string(),
+            TaskType::SqlQuery,
+            RawMetricData::SqlQuery(RawSqlQueryMetric {
+                total_time_ms: Some(10.0),
+                row_count: success.then_some(1),
+                success,
+                error: (!success).then(|| "connection refused".to_string()),
+                target_id: None,
+                mode: SqlQueryMode::Value,
+                value,
+                json_result: None,
+                json_truncated: false,
+                column_count: success.then_some(1),
+                connect_time_ms: Some(2.0),
+                query_time_ms: success.then_some(8.0),
+                status,
+                status_message: None,
+            }),
+        );
+        db.store_raw_metric(&metric).await.unwrap();
+    }
+
+    let now = current_timestamp();
+    let agg = db
+        .generate_aggregated_metrics("test_sql_status", &TaskType::SqlQuery, now - 60, now + 60)
+        .await
+        .unwrap()
+        .expect("aggregated SQL metric");
+
+    let AggregatedMetricData::SqlQuery(sql_data) = agg.data else {
+        panic!("Expected SQL aggregated data");
+    };
+    assert_eq!(sql_data.ok_count, 2);
+    assert_eq!(sql_data.warning_count, 1);
+    assert_eq!(sql_data.critical_count, 1);
+    assert_eq!(sql_data.error_count, 1);
+    // Threshold breaches are still successful queries
+    assert_eq!(sql_data.successful_queries, 4);
+    assert_eq!(sql_data.max_value, Some(900.0));
+}
+
+#[tokio::test]
+#[cfg(feature = "snmp-tasks")]
+async fn test_generate_snmp_aggregated_numeric_values() {
+    use shared::metrics::RawSnmpMetric;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = AgentDatabase::new(temp_dir.path(), 5).unwrap();
+    db.initialize().await.unwrap();
+
+    // Two in-range readings and one that failed its range check
+    for (value, success) in [(40.0, true), (60.0, true), (95.0, false)] {
+        let metric = MetricData::new(
+            "test_snmp".to_string(),
+            TaskType::Snmp,
+            RawMetricData::Snmp(RawSnmpMetric {
+                response_time_ms: Some(5.0),
+                success,
+                value: Some(format!("{}", value)),
+                value_type: Some("Unsigned32".to_string()),
+                oid_queried: "1.3.6.1.4.1.2021.10.1.5.1".to_string(),
+                error: (!success).then(|| "Value 95 is above max_value 90".to_string()),
+                target_id: None,
+                numeric_value: Some(value),
+            }),
+        );
+        db.store_raw_metric(&metric).await.unwrap();
+    }
+
+    let now = current_timestamp();
+    let agg = db
+        .generate_aggregated_metrics("test_snmp", &TaskType::Snmp, now - 60, now + 60)
+        .await
+        .unwrap()
+        .expect("aggregated SNMP metric");
+
+    let AggregatedMetricData::Snmp(snmp_data) = agg.data else {
+        panic!("Expected SNMP aggregated data");
+    };
+    assert_eq!(snmp_data.successful_queries, 2);
+    assert_eq!(snmp_data.failed_queries, 1);
+    assert_eq!(snmp_data.min_value, Some(40.0));
+    assert_eq!(snmp_data.max_value, Some(95.0));
+    assert!((snmp_data.avg_value.unwrap() - 65.0).abs() < 0.001);
+}
+
 #[tokio::test]
 async fn test_generate_http_aggregated_metrics() {
     use shared::metrics::RawHttpMetric;
@@ -447,3 +976,147 @@ async fn test_cleanup_old_data() {
         );
     } // Drop connection
 }
+
+#[tokio::test]
+async fn test_task_execution_metric_round_trip() {
+    use shared::metrics::{AggregatedMetrics, TaskExecutionMetric};
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = AgentDatabase::new(temp_dir.path(), 5).unwrap();
+    db.initialize().await.unwrap();
+
+    let now = current_timestamp();
+    let execution = TaskExecutionMetric {
+        runs_started: 4,
+        skipped_overruns: 2,
+        delayed_starts: 1,
+        max_start_delay_ms: 2500.0,
+        timed_out_runs: 1,
+        suppressed_runs: 3,
+    };
+    assert!(!execution.is_clean());
+    let metrics = AggregatedMetrics::new(
+        "test_ping".to_string(),
+        TaskType::Ping,
+        now - 60,
+        now,
+        execution.runs_started,
+        AggregatedMetricData::Execution(execution),
+    );
+
+    // The execution statistics are queued like any other aggregate
+    db.store_and_enqueue_aggregated_metrics(&metrics)
+        .await
+        .unwrap();
+    let queued = db.get_metrics_to_send(10).await.unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].metric, metrics);
+}
+
+#[tokio::test]
+async fn test_raw_metric_upload_queue_round_trip() {
+    use shared::metrics::{AggregatedMetrics, AggregatedPingMetric, RawDnsMetric};
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = AgentDatabase::new(temp_dir.path(), 5).unwrap();
+    db.initialize().await.unwrap();
+
+    let mut ping = MetricData::new(
+        "test_ping".to_string(),
+        TaskType::Ping,
+        RawMetricData::Ping(RawPingMetric {
+            rtt_ms: None,
+            success: false,
+            error: Some("Request timed out".to_string()),
+            ip_address: "192.0.2.1".to_string(),
+            domain: Some("example.com".to_string()),
+            target_id: None,
+        }),
+    );
+    ping.labels.insert("site".to_string(), "ams1".to_string());
+    let dns = MetricData::new(
+        "test_dns".to_string(),
+        TaskType::DnsQuery,
+        RawMetricData::DnsQuery(RawDnsMetric {
+            query_time_ms: Some(12.5),
+            success: true,
+            record_count: Some(2),
+            resolved_addresses: Some(vec!["192.0.2.10".to_string(), "192.0.2.11".to_string()]),
+            domain_queried: "example.com".to_string(),
+            error: None,
+            expected_ip: None,
+            resolved_ip: Some("192.0.2.10".to_string()),
+            correct_resolution: true,
+            target_id: None,
+        }),
+    );
+
+    // Only metrics of tasks with upload_raw are enqueued
+    let ping_row_id = db.store_and_enqueue_raw_metric(&ping).await.unwrap();
+    let dns_row_id = db.store_and_enqueue_raw_metric(&dns).await.unwrap();
+    db.store_raw_metric(&ping).await.unwrap();
+
+    let now = current_timestamp();
+    let aggregate = AggregatedMetrics::new(
+        "test_ping".to_string(),
+        TaskType::Ping,
+        now - 60,
+        now,
+        1,
+        AggregatedMetricData::Ping(AggregatedPingMetric {
+            avg_latency_ms: 0.0,
+            max_latency_ms: 0.0,
+            min_latency_ms: 0.0,
+            packet_loss_percent: 100.0,
+            successful_pings: 0,
+            failed_pings: 1,
+            domain: Some("example.com".to_string()),
+            target_id: None,
+            latency: None,
+        }),
+    );
+    db.store_and_enqueue_aggregated_metrics(&aggregate)
+        .await
+        .unwrap();
+
+    // Raw and aggregated entries share the queue but are fetched separately
+    let queued = db.get_metrics_to_send(10).await.unwrap();
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].metric, aggregate);
+
+    let queued_raw = db.get_raw_metrics_to_send(1024 * 1024).await.unwrap();
+    assert_eq!(queued_raw.len(), 2);
+    // Uploaded samples carry their row ID so the server can discard duplicates
+    assert_eq!(
+        queued_raw[0].metric,
+        MetricData {
+            row_id: Some(ping_row_id),
+            ..ping
+        }
+    );
+    assert_eq!(
+        queued_raw[1].metric,
+        MetricData {
+            row_id: Some(dns_row_id),
+            ..dns
+        }
+    );
+
+    // The batch is capped by serialized size, but always holds at least one metric
+    let ping_bytes = serde_json::to_vec(&queued_raw[0].metric).unwrap().len();
+    assert_eq!(
+        db.get_raw_metrics_to_send(ping_bytes).await.unwrap().len(),
+        1
+    );
+    assert_eq!(db.get_raw_metrics_to_send(1).await.unwrap().len(), 1);
+
+    let queue_ids: Vec<i64> = queued_raw.iter().map(|m| m.queue_id).collect();
+    db.mark_as_sending(&queue_ids).await.unwrap();
+    db.mark_as_sent(&queue_ids).await.unwrap();
+    assert!(db
+        .get_raw_metrics_to_send(1024 * 1024)
+        .await
+        .unwrap()
+        .is_empty());
+    assert_eq!(db.get_metrics_to_send(10).await.unwrap().len(), 1);
+}
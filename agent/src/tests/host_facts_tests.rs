@@ -0,0 +1,66 @@
+//! Tests for host facts parsing
+
+use crate::host_facts::{parse_default_gateway, parse_os_release, parse_unstable_ipv6_addresses};
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+#[test]
+fn test_parse_default_gateway() {
+    let route_table = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+eth0\t0000A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0
+eth0\t00000000\t0100A8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0
+wlan0\t00000000\t0101A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0
+";
+    assert_eq!(
+        parse_default_gateway(route_table),
+        Some(Ipv4Addr::new(192, 168, 0, 1))
+    );
+
+    // Only on-link routes, no default route
+    let no_default = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+eth0\t0000A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0
+";
+    assert_eq!(parse_default_gateway(no_default), None);
+    assert_eq!(parse_default_gateway(""), None);
+}
+
+#[test]
+fn test_parse_unstable_ipv6_addresses() {
+    // Stable global (permanent), temporary, deprecated and link-local addresses
+    let if_inet6 = "\
+20010db8000000000000000000000001 02 40 00 80     eth0
+20010db8000000001c2d3e4f5a6b7c8d 02 40 00 01     eth0
+20010db8000000000000000000000002 02 40 00 20     eth0
+fe800000000000000000000000000001 02 40 20 80     eth0
+00000000000000000000000000000001 01 80 10 80       lo
+";
+    assert_eq!(
+        parse_unstable_ipv6_addresses(if_inet6),
+        vec![
+            "2001:db8::1c2d:3e4f:5a6b:7c8d".parse::<Ipv6Addr>().unwrap(),
+            "2001:db8::2".parse::<Ipv6Addr>().unwrap(),
+        ]
+    );
+    assert!(parse_unstable_ipv6_addresses("").is_empty());
+}
+
+#[test]
+fn test_parse_os_release() {
+    let debian = r#"PRETTY_NAME="Debian GNU/Linux 12 (bookworm)"
+NAME="Debian GNU/Linux"
+VERSION_ID="12"
+ID=debian
+"#;
+    assert_eq!(
+        parse_os_release(debian).as_deref(),
+        Some("Debian GNU/Linux 12 (bookworm)")
+    );
+
+    // Falls back to NAME without PRETTY_NAME
+    assert_eq!(
+        parse_os_release("NAME=Alpine\nID=alpine\n").as_deref(),
+        Some("Alpine")
+    );
+    assert_eq!(parse_os_release("ID=custom\n"), None);
+}
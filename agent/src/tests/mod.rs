@@ -1,11 +1,25 @@
 //! Test modules for the agent crate
 
+mod bandwidth_reflector_tests;
 mod config_tests;
 mod database_tests;
+mod host_facts_tests;
 mod scheduler_tests;
+mod secrets_tests;
+#[cfg(feature = "snmp-tasks")]
+mod snmp_trap_tests;
+mod task_bandwidth_tests;
 mod task_dns_tests;
 mod task_http_content_tests;
 mod task_http_tests;
+mod task_ntp_tests;
+#[cfg(feature = "snmp-tasks")]
+mod task_snmp_tests;
+#[cfg(feature = "sql-tasks")]
+mod task_sql_tests;
+mod task_tcp_sweep_tests;
 mod task_tcp_tests;
 mod task_tls_tests;
+mod task_twamp_tests;
+mod task_udp_tests;
 mod tasks_tests;
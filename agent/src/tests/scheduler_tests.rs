@@ -1,51 +1,74 @@
 //! Tests for task scheduler implementation
 
 use crate::database::AgentDatabase;
-use crate::scheduler::{SchedulerState, TaskScheduler};
-use shared::config::{PingParams, TaskConfig, TaskParams, TaskType, TasksConfig};
+use crate::scheduler::{SchedulerOptions, SchedulerState, TaskScheduler};
+use crate::secrets::SecretStore;
+use crate::tasks::TaskResult;
+use shared::config::{PingParams, TaskConfig, TaskParams, TaskType, TasksConfig, TcpParams};
+use shared::metrics::{MetricData, RawMetricData, RawPingMetric};
+use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
 use tempfile::TempDir;
 use tokio::sync::RwLock;
+use tokio::time::Instant;
 
 /// Helper function to create a test `TasksConfig`.
 fn create_test_config() -> TasksConfig {
     TasksConfig {
-        tasks: vec![TaskConfig {
-            task_type: TaskType::Ping,
-            schedule_seconds: 10,
-            name: "Test Ping".to_string(),
-            timeout: None,
-            params: TaskParams::Ping(PingParams {
+        aggregation_window_seconds: 60,
+        maintenance_windows: Vec::new(),
+        tasks: vec![TaskConfig::new(
+            "Test Ping",
+            10,
+            TaskParams::Ping(PingParams {
                 host: "8.8.8.8".to_string(),
                 timeout_seconds: 1,
                 target_id: None,
             }),
-        }],
+        )],
+    }
+}
+
+/// Open and initialize an agent database in `temp_dir`
+#[allow(clippy::arc_with_non_send_sync)]
+async fn test_database(temp_dir: &TempDir) -> Arc<RwLock<AgentDatabase>> {
+    let mut db = AgentDatabase::new(temp_dir.path(), 5).unwrap();
+    db.initialize().await.unwrap();
+    Arc::new(RwLock::new(db))
+}
+
+/// Scheduler options for a local-only agent keeping secrets in `temp_dir`
+fn test_options(temp_dir: &TempDir) -> SchedulerOptions {
+    SchedulerOptions {
+        flush_interval_seconds: 5,
+        graceful_shutdown_timeout_secs: 30,
+        channel_buffer_size: 1000,
+        queue_cleanup_interval_seconds: 3600,
+        server_url: None,
+        api_key: None,
+        agent_id: None,
+        secret_store: SecretStore::new(temp_dir.path()),
     }
 }
 
 #[tokio::test]
 async fn test_scheduler_creation() {
     let temp_dir = TempDir::new().unwrap();
-    let mut db = AgentDatabase::new(temp_dir.path(), 5).unwrap();
-    db.initialize().await.unwrap();
-    let db = Arc::new(RwLock::new(db));
+    let db = test_database(&temp_dir).await;
 
     let config = create_test_config();
-    let scheduler = TaskScheduler::new(config, db, 5, 30, 1000, 3600, None, None, None);
+    let scheduler = TaskScheduler::new(config, db, test_options(&temp_dir));
     assert!(scheduler.is_ok());
 }
 
 #[tokio::test]
 async fn test_scheduler_start_stop() {
     let temp_dir = TempDir::new().unwrap();
-    let mut db = AgentDatabase::new(temp_dir.path(), 5).unwrap();
-    db.initialize().await.unwrap();
-    let db = Arc::new(RwLock::new(db));
+    let db = test_database(&temp_dir).await;
 
     let config = create_test_config();
-    let mut scheduler =
-        TaskScheduler::new(config, db, 5, 30, 1000, 3600, None, None, None).unwrap();
+    let mut scheduler = TaskScheduler::new(config, db, test_options(&temp_dir)).unwrap();
 
     assert_eq!(scheduler.state, SchedulerState::Stopped);
 
@@ -55,3 +78,189 @@ async fn test_scheduler_start_stop() {
     scheduler.stop().await.unwrap();
     assert_eq!(scheduler.state, SchedulerState::Stopped);
 }
+
+/// Builds a ping result whose measurement succeeded or failed.
+fn ping_result(success: bool) -> TaskResult {
+    named_ping_result("Test Ping", success)
+}
+
+/// Builds a ping result of the named task whose measurement succeeded or failed.
+fn named_ping_result(task_name: &str, success: bool) -> TaskResult {
+    TaskResult {
+        task_name: task_name.to_string(),
+        success: true,
+        metric_data: Some(MetricData::new(
+            task_name.to_string(),
+            TaskType::Ping,
+            RawMetricData::Ping(RawPingMetric {
+                rtt_ms: success.then_some(10.0),
+                success,
+                error: (!success).then(|| "timeout".to_string()),
+                ip_address: "8.8.8.8".to_string(),
+                domain: None,
+                target_id: None,
+            }),
+        )),
+        error: None,
+        execution_time_ms: 10.0,
+        timed_out: false,
+    }
+}
+
+#[tokio::test]
+async fn test_adaptive_schedule_while_failing() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = test_database(&temp_dir).await;
+
+    let mut config = create_test_config();
+    config.tasks[0].schedule_seconds = 3600;
+    config.tasks[0].on_failure_schedule_seconds = Some(1);
+    let mut scheduler = TaskScheduler::new(config, db, test_options(&temp_dir)).unwrap();
+    scheduler.start().await.unwrap();
+
+    // The first tick fires immediately, the next one only after an hour
+    let first = tokio::time::timeout(Duration::from_secs(1), scheduler.ready_receiver.recv()).await;
+    assert_eq!(first.unwrap().unwrap().task_name, "Test Ping");
+
+    // A failed measurement switches the ticker to the faster schedule
+    scheduler
+        .handle_task_result(ping_result(false))
+        .await
+        .unwrap();
+    let fast = tokio::time::timeout(Duration::from_secs(3), scheduler.ready_receiver.recv()).await;
+    assert_eq!(fast.unwrap().unwrap().task_name, "Test Ping");
+
+    // Two consecutive successes restore the normal schedule
+    scheduler
+        .handle_task_result(ping_result(true))
+        .await
+        .unwrap();
+    scheduler
+        .handle_task_result(ping_result(true))
+        .await
+        .unwrap();
+    while scheduler.ready_receiver.try_recv().is_ok() {}
+    let slow =
+        tokio::time::timeout(Duration::from_millis(1500), scheduler.ready_receiver.recv()).await;
+    assert!(slow.is_err());
+
+    scheduler.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_dependents_suppressed_while_prerequisite_down() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = test_database(&temp_dir).await;
+
+    // "Test Ping" depends on "Gateway", which has not reported yet
+    let mut config = create_test_config();
+    let mut gateway = config.tasks[0].clone();
+    gateway.name = "Gateway".to_string();
+    config.tasks[0].schedule_seconds = 3600;
+    config.tasks[0].depends_on = vec!["Gateway".to_string()];
+    if let TaskParams::Ping(params) = &mut config.tasks[0].params {
+        params.host = "127.0.0.1".to_string();
+    }
+    config.tasks.push(gateway);
+    let mut scheduler = TaskScheduler::new(config, db, test_options(&temp_dir)).unwrap();
+    scheduler.start().await.unwrap();
+
+    // While the prerequisite's latest result failed, the dependent is not run
+    scheduler
+        .handle_task_result(named_ping_result("Gateway", false))
+        .await
+        .unwrap();
+    scheduler
+        .execute_single_task("Test Ping", Instant::now())
+        .await
+        .unwrap();
+    let suppressed = tokio::time::timeout(
+        Duration::from_millis(1500),
+        scheduler.result_receiver.recv(),
+    )
+    .await;
+    assert!(suppressed.is_err());
+
+    // Once it recovers, the dependent runs again and reports a result
+    scheduler
+        .handle_task_result(named_ping_result("Gateway", true))
+        .await
+        .unwrap();
+    scheduler
+        .execute_single_task("Test Ping", Instant::now())
+        .await
+        .unwrap();
+    let result = tokio::time::timeout(Duration::from_secs(5), scheduler.result_receiver.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(result.task_name, "Test Ping");
+
+    scheduler.stop().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_dispatch_respects_type_limits_and_priorities() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = test_database(&temp_dir).await;
+
+    // Three local pings sharing a single ping slot, and a TCP connect
+    let mut config = create_test_config();
+    config.tasks[0].schedule_seconds = 3600;
+    if let TaskParams::Ping(params) = &mut config.tasks[0].params {
+        params.host = "127.0.0.1".to_string();
+    }
+    let mut first = config.tasks[0].clone();
+    first.name = "First".to_string();
+    let mut low = config.tasks[0].clone();
+    low.name = "Low".to_string();
+    let mut high = config.tasks[0].clone();
+    high.name = "High".to_string();
+    high.priority = Some(5);
+    let mut tcp = config.tasks[0].clone();
+    tcp.name = "Tcp".to_string();
+    tcp.task_type = TaskType::Tcp;
+    tcp.params = TaskParams::Tcp(TcpParams {
+        host: "127.0.0.1:1".to_string(),
+        timeout_seconds: 1,
+        send: None,
+        expect: None,
+        read_timeout_seconds: 1,
+        target_id: None,
+    });
+    config.tasks = vec![first, low, high, tcp];
+
+    let mut scheduler = TaskScheduler::new(config, db, test_options(&temp_dir))
+        .unwrap()
+        .with_concurrency_limits(10, HashMap::from([(TaskType::Ping, 1)]));
+    scheduler.start().await.unwrap();
+    // Drop the initial ticks, runs are triggered explicitly below
+    while scheduler.ready_receiver.try_recv().is_ok() {}
+
+    for task_name in ["First", "Low", "High", "Tcp"] {
+        scheduler
+            .execute_single_task(task_name, Instant::now())
+            .await
+            .unwrap();
+    }
+
+    let mut finished = Vec::new();
+    while finished.len() < 4 {
+        let result =
+            tokio::time::timeout(Duration::from_secs(10), scheduler.result_receiver.recv())
+                .await
+                .unwrap()
+                .unwrap();
+        finished.push(result.task_name.clone());
+        scheduler.handle_task_result(result).await.unwrap();
+    }
+
+    // The TCP task is not held back by the ping cap, and the queued pings
+    // run one at a time with the higher priority first
+    let position = |name: &str| finished.iter().position(|task| task == name).unwrap();
+    assert!(position("Tcp") < position("Low"));
+    assert!(position("First") < position("High"));
+    assert!(position("High") < position("Low"));
+
+    scheduler.stop().await.unwrap();
+}
@@ -0,0 +1,143 @@
+//! Tests for secret references and the encrypted secrets store
+
+use crate::secrets::{resolve_secret, resolve_task_secrets, SecretStore};
+use shared::config::{HttpGetParams, TaskConfig, TaskParams};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+fn http_task(headers: HashMap<String, String>) -> TaskConfig {
+    TaskConfig::new(
+        "API health",
+        60,
+        TaskParams::HttpGet(HttpGetParams {
+            url: "https://example.com/health".to_string(),
+            timeout_seconds: 10,
+            headers,
+            verify_ssl: true,
+            target_id: None,
+        }),
+    )
+}
+
+#[test]
+fn test_store_round_trip() {
+    let temp_dir = TempDir::new().unwrap();
+    let store = SecretStore::new(temp_dir.path());
+
+    store.set("db_password", "s3cr3t!").unwrap();
+    store.set("api_token", "token-value").unwrap();
+    assert_eq!(store.get("db_password").unwrap(), "s3cr3t!");
+    assert_eq!(store.names().unwrap(), vec!["api_token", "db_password"]);
+
+    // The plaintext never appears in the store file
+    let content = std::fs::read_to_string(temp_dir.path().join("secrets.json")).unwrap();
+    assert!(!content.contains("s3cr3t!"));
+
+    // A new handle on the same directory reads the same secrets
+    let reopened = SecretStore::new(temp_dir.path());
+    assert_eq!(reopened.get("api_token").unwrap(), "token-value");
+
+    assert!(store.remove("api_token").unwrap());
+    assert!(!store.remove("api_token").unwrap());
+    assert!(store.get("api_token").is_err());
+}
+
+#[cfg(unix)]
+#[test]
+fn test_store_files_are_private() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let temp_dir = TempDir::new().unwrap();
+    let store = SecretStore::new(temp_dir.path());
+    store.set("name", "value").unwrap();
+
+    for file in ["secrets.json", "secrets.key"] {
+        let mode = std::fs::metadata(temp_dir.path().join(file))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600, "{} has mode {:o}", file, mode);
+    }
+}
+
+#[test]
+fn test_swapped_entries_fail_to_decrypt() {
+    let temp_dir = TempDir::new().unwrap();
+    let store = SecretStore::new(temp_dir.path());
+    store.set("a", "first").unwrap();
+    store.set("b", "second").unwrap();
+
+    // Entries are bound to their names, so moving ciphertext between names is detected
+    let path = temp_dir.path().join("secrets.json");
+    let mut json: serde_json::Value =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    let first = json["secrets"]["a"].clone();
+    json["secrets"]["b"] = first;
+    std::fs::write(&path, json.to_string()).unwrap();
+
+    assert_eq!(store.get("a").unwrap(), "first");
+    assert!(store.get("b").is_err());
+}
+
+#[test]
+fn test_resolve_references() {
+    let temp_dir = TempDir::new().unwrap();
+    let store = SecretStore::new(temp_dir.path());
+    store.set("snmp_auth", "authpass123").unwrap();
+
+    // Literals are returned unchanged
+    assert_eq!(resolve_secret("plain", Some(&store)).unwrap(), "plain");
+
+    std::env::set_var("LINKSENSE_TEST_SECRET_REF", "from-env");
+    assert_eq!(
+        resolve_secret("env:LINKSENSE_TEST_SECRET_REF", Some(&store)).unwrap(),
+        "from-env"
+    );
+    assert!(resolve_secret("env:LINKSENSE_TEST_SECRET_MISSING", Some(&store)).is_err());
+
+    let file = temp_dir.path().join("token");
+    std::fs::write(&file, "from-file\n").unwrap();
+    assert_eq!(
+        resolve_secret(&format!("file:{}", file.display()), Some(&store)).unwrap(),
+        "from-file"
+    );
+
+    assert_eq!(
+        resolve_secret("secret:snmp_auth", Some(&store)).unwrap(),
+        "authpass123"
+    );
+    assert!(resolve_secret("secret:unknown", Some(&store)).is_err());
+    assert!(resolve_secret("secret:snmp_auth", None).is_err());
+}
+
+#[test]
+fn test_resolve_task_secrets() {
+    let temp_dir = TempDir::new().unwrap();
+    let store = SecretStore::new(temp_dir.path());
+    store.set("api_token", "Bearer abc123").unwrap();
+
+    // Tasks without references are not copied
+    let plain = http_task(HashMap::from([("Accept".to_string(), "*/*".to_string())]));
+    assert!(matches!(
+        resolve_task_secrets(&plain, Some(&store)).unwrap(),
+        Cow::Borrowed(_)
+    ));
+
+    let task = http_task(HashMap::from([
+        ("Authorization".to_string(), "secret:api_token".to_string()),
+        ("Accept".to_string(), "*/*".to_string()),
+    ]));
+    let resolved = resolve_task_secrets(&task, Some(&store)).unwrap();
+    let TaskParams::HttpGet(params) = &resolved.params else {
+        panic!("Expected HttpGet params");
+    };
+    assert_eq!(params.headers["Authorization"], "Bearer abc123");
+    assert_eq!(params.headers["Accept"], "*/*");
+
+    // The original configuration keeps the reference
+    let TaskParams::HttpGet(original) = &task.params else {
+        panic!("Expected HttpGet params");
+    };
+    assert_eq!(original.headers["Authorization"], "secret:api_token");
+}
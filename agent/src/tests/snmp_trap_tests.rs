@@ -0,0 +1,581 @@
+//! Tests for the SNMP trap and inform receiver
+
+use crate::snmp_trap::{
+    auth_digest, encode_integer, encode_oid, encode_pdu, encode_unsigned, hmac, local_engine_id,
+    password_to_key, tlv, SnmpTrapReceiver, TrapSource, UNMATCHED_TRAP_TASK_NAME,
+};
+use openssl::hash::Hasher;
+use shared::config::{SnmpAuthProtocol, SnmpSecurityLevel, SnmpTrapReceiverConfig, SnmpTrapUser};
+use shared::metrics::AggregatedMetricData;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tempfile::TempDir;
+use tokio::sync::{mpsc, RwLock};
+
+const AGENT_ID: &str = "trap-test-agent";
+const LINK_DOWN: &[u32] = &[1, 3, 6, 1, 6, 3, 1, 1, 5, 3];
+const IF_INDEX: &[u32] = &[1, 3, 6, 1, 2, 1, 2, 2, 1, 1, 2];
+
+fn receiver_config() -> SnmpTrapReceiverConfig {
+    SnmpTrapReceiverConfig {
+        listen_address: "127.0.0.1:0".to_string(),
+        communities: vec!["public".to_string()],
+        v3_users: vec![],
+        allowed_sources: vec![],
+        trap_oids: vec![],
+    }
+}
+
+fn create_receiver(config: SnmpTrapReceiverConfig) -> SnmpTrapReceiver {
+    let (event_sender, _event_receiver) = mpsc::channel(16);
+    SnmpTrapReceiver::new(
+        config,
+        AGENT_ID,
+        Arc::new(RwLock::new(Vec::new())),
+        event_sender,
+    )
+    .unwrap()
+}
+
+fn source() -> SocketAddr {
+    "192.0.2.10:50000".parse().unwrap()
+}
+
+fn varbind(oid: &[u32], value: Vec<u8>) -> Vec<u8> {
+    let mut content = encode_oid(oid);
+    content.extend(value);
+    tlv(0x30, &content)
+}
+
+/// Varbind list of an SNMPv2 linkDown notification
+fn link_down_varbinds() -> Vec<u8> {
+    let mut list = varbind(&[1, 3, 6, 1, 2, 1, 1, 3, 0], encode_unsigned(0x43, 12345));
+    list.extend(varbind(
+        &[1, 3, 6, 1, 6, 3, 1, 1, 4, 1, 0],
+        encode_oid(LINK_DOWN),
+    ));
+    list.extend(varbind(IF_INDEX, encode_integer(2)));
+    list.extend(varbind(
+        &[1, 3, 6, 1, 2, 1, 2, 2, 1, 2, 2],
+        tlv(0x04, b"GigabitEthernet0/2"),
+    ));
+    tlv(0x30, &list)
+}
+
+fn community_message(version: i64, community: &[u8], pdu: Vec<u8>) -> Vec<u8> {
+    let mut content = encode_integer(version);
+    content.extend(tlv(0x04, community));
+    content.extend(pdu);
+    tlv(0x30, &content)
+}
+
+fn v2c_trap(community: &[u8]) -> Vec<u8> {
+    community_message(1, community, encode_pdu(0xA7, 42, &link_down_varbinds()))
+}
+
+/// Encode an SNMPv3 message, optionally signing it with the given localized key
+fn v3_message(
+    flags: u8,
+    engine_id: &[u8],
+    user: &[u8],
+    pdu: Vec<u8>,
+    auth: Option<(&SnmpAuthProtocol, &[u8])>,
+) -> Vec<u8> {
+    v3_message_at(flags, engine_id, user, pdu, auth, (1, 100))
+}
+
+/// Encode an SNMPv3 message carrying the given engine boots and time
+fn v3_message_at(
+    flags: u8,
+    engine_id: &[u8],
+    user: &[u8],
+    pdu: Vec<u8>,
+    auth: Option<(&SnmpAuthProtocol, &[u8])>,
+    (engine_boots, engine_time): (i64, i64),
+) -> Vec<u8> {
+    let mac_len = auth.map_or(0, |(protocol, _)| auth_digest(protocol).unwrap().1);
+
+    let mut global = encode_integer(7);
+    global.extend(encode_integer(65507));
+    global.extend(tlv(0x04, &[flags]));
+    global.extend(encode_integer(3));
+
+    let mut security = tlv(0x04, engine_id);
+    security.extend(encode_integer(engine_boots));
+    security.extend(encode_integer(engine_time));
+    security.extend(tlv(0x04, user));
+    security.extend(tlv(0x04, &vec![0u8; mac_len]));
+    security.extend(tlv(0x04, b""));
+
+    let mut scoped = tlv(0x04, engine_id);
+    scoped.extend(tlv(0x04, b""));
+    scoped.extend(pdu);
+
+    let mut content = encode_integer(3);
+    content.extend(tlv(0x30, &global));
+    content.extend(tlv(0x04, &tlv(0x30, &security)));
+    content.extend(tlv(0x30, &scoped));
+    let mut message = tlv(0x30, &content);
+
+    if let Some((protocol, key)) = auth {
+        let (digest, _) = auth_digest(protocol).unwrap();
+        let placeholder = vec![0u8; mac_len];
+        let offset = message
+            .windows(mac_len)
+            .position(|w| w == placeholder.as_slice())
+            .unwrap();
+        let mac = hmac(digest, key, &message).unwrap();
+        message[offset..offset + mac_len].copy_from_slice(&mac[..mac_len]);
+    }
+    message
+}
+
+fn localized_key(protocol: &SnmpAuthProtocol, password: &str, engine_id: &[u8]) -> Vec<u8> {
+    let (digest, _) = auth_digest(protocol).unwrap();
+    let key = password_to_key(digest, password.as_bytes()).unwrap();
+    let mut hasher = Hasher::new(digest).unwrap();
+    hasher.update(&key).unwrap();
+    hasher.update(engine_id).unwrap();
+    hasher.update(&key).unwrap();
+    hasher.finish().unwrap().to_vec()
+}
+
+fn v3_user() -> SnmpTrapUser {
+    SnmpTrapUser {
+        username: "trapuser".to_string(),
+        security_level: SnmpSecurityLevel::AuthNoPriv,
+        auth_protocol: SnmpAuthProtocol::Sha1,
+        auth_password: Some("maplesyrup".to_string()),
+    }
+}
+
+#[test]
+fn test_password_to_key_rfc3414_vector() {
+    // RFC 3414 A.3.2: SHA-1 key for "maplesyrup" localized to engine 00..02
+    let engine_id = [0u8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+    let key = localized_key(&SnmpAuthProtocol::Sha1, "maplesyrup", &engine_id);
+    let hex: String = key.iter().map(|b| format!("{:02x}", b)).collect();
+    assert_eq!(hex, "6695febc9288e36282235fc7151f128497b38f3f");
+}
+
+#[test]
+fn test_v2c_trap_is_decoded() {
+    let mut receiver = create_receiver(receiver_config());
+
+    let processed = receiver
+        .process_datagram(&v2c_trap(b"public"), source(), &[])
+        .unwrap();
+    assert!(processed.response.is_none());
+
+    let event = processed.event.expect("trap should be accepted");
+    assert_eq!(event.task_name, UNMATCHED_TRAP_TASK_NAME);
+    assert_eq!(event.sample_count, 1);
+    assert_eq!(event.period_start, event.period_end);
+
+    let AggregatedMetricData::SnmpTrap(trap) = event.data else {
+        panic!("Expected SNMP trap data");
+    };
+    assert_eq!(trap.version, "v2c");
+    assert_eq!(trap.pdu_type, "trap");
+    assert_eq!(trap.trap_oid, "1.3.6.1.6.3.1.1.5.3");
+    assert_eq!(trap.uptime_ticks, Some(12345));
+    assert_eq!(trap.source_address, "192.0.2.10:50000");
+    assert_eq!(trap.varbinds.len(), 2);
+    assert_eq!(trap.varbinds[0].oid, "1.3.6.1.2.1.2.2.1.1.2");
+    assert_eq!(trap.varbinds[0].value, "2");
+    assert_eq!(trap.varbinds[0].value_type, "Integer");
+    assert_eq!(trap.varbinds[1].value, "GigabitEthernet0/2");
+    assert_eq!(trap.varbinds[1].value_type, "OctetString");
+}
+
+#[test]
+fn test_trap_attributed_to_matching_task() {
+    let mut receiver = create_receiver(receiver_config());
+    let sources = vec![TrapSource {
+        ip: "192.0.2.10".parse().unwrap(),
+        task_name: "core-switch".to_string(),
+        target_id: Some("dc1".to_string()),
+    }];
+
+    let event = receiver
+        .process_datagram(&v2c_trap(b"public"), source(), &sources)
+        .unwrap()
+        .event
+        .unwrap();
+    assert_eq!(event.task_name, "core-switch");
+    let AggregatedMetricData::SnmpTrap(trap) = event.data else {
+        panic!("Expected SNMP trap data");
+    };
+    assert_eq!(trap.target_id.as_deref(), Some("dc1"));
+}
+
+#[test]
+fn test_v1_generic_trap_is_translated() {
+    let mut receiver = create_receiver(receiver_config());
+
+    let mut pdu = encode_oid(&[1, 3, 6, 1, 4, 1, 9]);
+    pdu.extend(tlv(0x40, &[192, 0, 2, 10]));
+    pdu.extend(encode_integer(2)); // linkDown
+    pdu.extend(encode_integer(0));
+    pdu.extend(encode_unsigned(0x43, 500));
+    pdu.extend(tlv(0x30, &varbind(IF_INDEX, encode_integer(2))));
+    let datagram = community_message(0, b"public", tlv(0xA4, &pdu));
+
+    let event = receiver
+        .process_datagram(&datagram, source(), &[])
+        .unwrap()
+        .event
+        .unwrap();
+    let AggregatedMetricData::SnmpTrap(trap) = event.data else {
+        panic!("Expected SNMP trap data");
+    };
+    assert_eq!(trap.version, "v1");
+    assert_eq!(trap.trap_oid, "1.3.6.1.6.3.1.1.5.3");
+    assert_eq!(trap.uptime_ticks, Some(500));
+    assert_eq!(trap.varbinds.len(), 1);
+}
+
+#[test]
+fn test_v1_enterprise_specific_trap_is_translated() {
+    let mut receiver = create_receiver(receiver_config());
+
+    let mut pdu = encode_oid(&[1, 3, 6, 1, 4, 1, 9]);
+    pdu.extend(tlv(0x40, &[192, 0, 2, 10]));
+    pdu.extend(encode_integer(6));
+    pdu.extend(encode_integer(17));
+    pdu.extend(encode_unsigned(0x43, 500));
+    pdu.extend(tlv(0x30, &[]));
+    let datagram = community_message(0, b"public", tlv(0xA4, &pdu));
+
+    let event = receiver
+        .process_datagram(&datagram, source(), &[])
+        .unwrap()
+        .event
+        .unwrap();
+    let AggregatedMetricData::SnmpTrap(trap) = event.data else {
+        panic!("Expected SNMP trap data");
+    };
+    assert_eq!(trap.trap_oid, "1.3.6.1.4.1.9.0.17");
+}
+
+#[test]
+fn test_v1_trap_with_invalid_generic_trap_is_rejected() {
+    let mut receiver = create_receiver(receiver_config());
+
+    for generic_trap in [-1, 7, u32::MAX as i64] {
+        let mut pdu = encode_oid(&[1, 3, 6, 1, 4, 1, 9]);
+        pdu.extend(tlv(0x40, &[192, 0, 2, 10]));
+        pdu.extend(encode_integer(generic_trap));
+        pdu.extend(encode_integer(0));
+        pdu.extend(encode_unsigned(0x43, 500));
+        pdu.extend(tlv(0x30, &[]));
+        let datagram = community_message(0, b"public", tlv(0xA4, &pdu));
+
+        assert!(receiver.process_datagram(&datagram, source(), &[]).is_err());
+    }
+}
+
+#[test]
+fn test_unknown_community_is_rejected() {
+    let mut receiver = create_receiver(receiver_config());
+    assert!(receiver
+        .process_datagram(&v2c_trap(b"private"), source(), &[])
+        .is_err());
+}
+
+#[test]
+fn test_source_not_allowed_is_rejected() {
+    let mut config = receiver_config();
+    config.allowed_sources = vec!["192.0.2.99".to_string()];
+    let mut receiver = create_receiver(config);
+
+    assert!(receiver
+        .process_datagram(&v2c_trap(b"public"), source(), &[])
+        .is_err());
+
+    let allowed: SocketAddr = "192.0.2.99:162".parse().unwrap();
+    assert!(receiver
+        .process_datagram(&v2c_trap(b"public"), allowed, &[])
+        .unwrap()
+        .event
+        .is_some());
+}
+
+#[test]
+fn test_trap_oid_filter_drops_non_matching() {
+    let mut config = receiver_config();
+    config.trap_oids = vec!["1.3.6.1.4.1.9".to_string()];
+    let mut receiver = create_receiver(config);
+    assert!(receiver
+        .process_datagram(&v2c_trap(b"public"), source(), &[])
+        .unwrap()
+        .event
+        .is_none());
+
+    let mut config = receiver_config();
+    config.trap_oids = vec!["1.3.6.1.6.3.1.1.5".to_string()];
+    let mut receiver = create_receiver(config);
+    assert!(receiver
+        .process_datagram(&v2c_trap(b"public"), source(), &[])
+        .unwrap()
+        .event
+        .is_some());
+}
+
+#[test]
+fn test_inform_is_acknowledged() {
+    let mut config = receiver_config();
+    // Filtered informs must still be acknowledged
+    config.trap_oids = vec!["1.3.6.1.4.1.9".to_string()];
+    let mut receiver = create_receiver(config);
+
+    let varbinds = link_down_varbinds();
+    let datagram = community_message(1, b"public", encode_pdu(0xA6, 4242, &varbinds));
+    let processed = receiver.process_datagram(&datagram, source(), &[]).unwrap();
+
+    assert!(processed.event.is_none());
+    let expected = community_message(1, b"public", encode_pdu(0xA2, 4242, &varbinds));
+    assert_eq!(processed.response, Some(expected));
+}
+
+#[test]
+fn test_v3_inform_with_unknown_engine_gets_report() {
+    let mut config = receiver_config();
+    config.v3_users = vec![v3_user()];
+    let mut receiver = create_receiver(config);
+
+    let datagram = v3_message(
+        0x04,
+        b"",
+        b"",
+        encode_pdu(0xA6, 99, &link_down_varbinds()),
+        None,
+    );
+    let processed = receiver.process_datagram(&datagram, source(), &[]).unwrap();
+
+    assert!(processed.event.is_none());
+    let report = processed.response.expect("discovery should be answered");
+    let engine_id = local_engine_id(AGENT_ID);
+    assert!(report
+        .windows(engine_id.len())
+        .any(|w| w == engine_id.as_slice()));
+    // Report PDU carrying usmStatsUnknownEngineIDs.0
+    assert!(report.contains(&0xA8));
+    let usm_oid = encode_oid(&[1, 3, 6, 1, 6, 3, 15, 1, 1, 4, 0]);
+    assert!(report
+        .windows(usm_oid.len())
+        .any(|w| w == usm_oid.as_slice()));
+}
+
+#[test]
+fn test_v3_authenticated_trap() {
+    let mut config = receiver_config();
+    config.v3_users = vec![v3_user()];
+    let mut receiver = create_receiver(config);
+
+    let sender_engine = [0x80, 0, 0, 9, 3, 1, 2, 3, 4];
+    let key = localized_key(&SnmpAuthProtocol::Sha1, "maplesyrup", &sender_engine);
+    let pdu = encode_pdu(0xA7, 7, &link_down_varbinds());
+
+    let signed = v3_message(
+        0x01,
+        &sender_engine,
+        b"trapuser",
+        pdu.clone(),
+        Some((&SnmpAuthProtocol::Sha1, &key)),
+    );
+    let event = receiver
+        .process_datagram(&signed, source(), &[])
+        .unwrap()
+        .event
+        .unwrap();
+    let AggregatedMetricData::SnmpTrap(trap) = event.data else {
+        panic!("Expected SNMP trap data");
+    };
+    assert_eq!(trap.version, "v3");
+    assert_eq!(trap.trap_oid, "1.3.6.1.6.3.1.1.5.3");
+
+    // Wrong password
+    let bad_key = localized_key(&SnmpAuthProtocol::Sha1, "wrongpassword", &sender_engine);
+    let forged = v3_message(
+        0x01,
+        &sender_engine,
+        b"trapuser",
+        pdu.clone(),
+        Some((&SnmpAuthProtocol::Sha1, &bad_key)),
+    );
+    assert!(receiver.process_datagram(&forged, source(), &[]).is_err());
+
+    // authNoPriv user sending without authentication
+    let unauthenticated = v3_message(0x00, &sender_engine, b"trapuser", pdu.clone(), None);
+    assert!(receiver
+        .process_datagram(&unauthenticated, source(), &[])
+        .is_err());
+
+    // Unknown user
+    let unknown = v3_message(0x00, &sender_engine, b"nobody", pdu, None);
+    assert!(receiver.process_datagram(&unknown, source(), &[]).is_err());
+}
+
+#[test]
+fn test_v3_replayed_trap_is_rejected() {
+    let mut config = receiver_config();
+    config.v3_users = vec![v3_user()];
+    let mut receiver = create_receiver(config);
+
+    let sender_engine = [0x80, 0, 0, 9, 3, 1, 2, 3, 4];
+    let key = localized_key(&SnmpAuthProtocol::Sha1, "maplesyrup", &sender_engine);
+    let trap_at = |engine_clock| {
+        v3_message_at(
+            0x01,
+            &sender_engine,
+            b"trapuser",
+            encode_pdu(0xA7, 7, &link_down_varbinds()),
+            Some((&SnmpAuthProtocol::Sha1, &key)),
+            engine_clock,
+        )
+    };
+    let accepted = |receiver: &mut SnmpTrapReceiver, datagram: &[u8]| {
+        receiver
+            .process_datagram(datagram, source(), &[])
+            .map(|processed| processed.event.is_some())
+            .unwrap_or(false)
+    };
+
+    let captured = trap_at((1, 100));
+    assert!(accepted(&mut receiver, &captured));
+    assert!(accepted(&mut receiver, &trap_at((1, 1000))));
+
+    // The captured trap is older than the sender's engine time allows
+    assert!(!accepted(&mut receiver, &captured));
+    // Traps delayed by less than the time window are still accepted
+    assert!(accepted(&mut receiver, &trap_at((1, 900))));
+
+    // After the sender rebooted, traps from its previous boot are stale
+    assert!(accepted(&mut receiver, &trap_at((2, 5))));
+    assert!(!accepted(&mut receiver, &trap_at((1, 1000))));
+}
+
+#[test]
+fn test_v3_inform_outside_time_window_is_rejected() {
+    let mut config = receiver_config();
+    config.v3_users = vec![v3_user()];
+    let mut receiver = create_receiver(config);
+
+    // Informs are sent to our engine, whose time starts when the receiver is created
+    let engine_id = local_engine_id(AGENT_ID);
+    let key = localized_key(&SnmpAuthProtocol::Sha1, "maplesyrup", &engine_id);
+    let inform_at = |flags, engine_clock| {
+        v3_message_at(
+            flags,
+            &engine_id,
+            b"trapuser",
+            encode_pdu(0xA6, 11, &link_down_varbinds()),
+            Some((&SnmpAuthProtocol::Sha1, &key)),
+            engine_clock,
+        )
+    };
+
+    let processed = receiver
+        .process_datagram(&inform_at(0x05, (1, 0)), source(), &[])
+        .unwrap();
+    assert!(processed.event.is_some());
+    assert!(processed.response.is_some());
+
+    assert!(receiver
+        .process_datagram(&inform_at(0x01, (1, 1000)), source(), &[])
+        .is_err());
+    assert!(receiver
+        .process_datagram(&inform_at(0x01, (2, 0)), source(), &[])
+        .is_err());
+
+    // A reportable inform is answered with usmStatsNotInTimeWindows so the sender can resync
+    let processed = receiver
+        .process_datagram(&inform_at(0x05, (1, 1000)), source(), &[])
+        .unwrap();
+    assert!(processed.event.is_none());
+    let report = processed
+        .response
+        .expect("sender should be told our engine time");
+    let usm_oid = encode_oid(&[1, 3, 6, 1, 6, 3, 15, 1, 1, 2, 0]);
+    assert!(report
+        .windows(usm_oid.len())
+        .any(|w| w == usm_oid.as_slice()));
+}
+
+#[test]
+fn test_truncated_datagram_is_rejected() {
+    let mut receiver = create_receiver(receiver_config());
+    let datagram = v2c_trap(b"public");
+    assert!(receiver
+        .process_datagram(&datagram[..datagram.len() - 5], source(), &[])
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_trap_stored_without_task_ticks() {
+    let temp_dir = TempDir::new().unwrap();
+    let config_dir = temp_dir.path().join("config");
+    std::fs::create_dir(&config_dir).unwrap();
+    std::fs::write(
+        config_dir.join("agent.toml"),
+        "agent_id = \"trap-test-agent\"\nlocal_data_retention_days = 7\nlocal_only = true\n",
+    )
+    .unwrap();
+    // The only task runs once a year, so the scheduler produces no events
+    std::fs::write(
+        config_dir.join("tasks.toml"),
+        r#"
+[[tasks]]
+type = "ping"
+name = "Yearly Ping"
+schedule_seconds = 60
+cron = "0 0 1 1 *"
+host = "127.0.0.1"
+"#,
+    )
+    .unwrap();
+
+    let mut agent = crate::Agent::new(config_dir).await.unwrap();
+    let (event_sender, event_receiver) = mpsc::channel(16);
+    agent.snmp_trap_events = Some(event_receiver);
+    let database = agent.database.clone();
+
+    let event = create_receiver(receiver_config())
+        .process_datagram(&v2c_trap(b"public"), source(), &[])
+        .unwrap()
+        .event
+        .unwrap();
+
+    let stored = async {
+        // Let the main loop settle into waiting for events before the trap arrives
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        event_sender.send(event).await.unwrap();
+        loop {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            let queued = database
+                .write()
+                .await
+                .get_metrics_to_send(10)
+                .await
+                .unwrap();
+            if !queued.is_empty() {
+                return queued;
+            }
+        }
+    };
+
+    let queued = tokio::select! {
+        result = agent.run() => panic!("agent main loop exited: {:?}", result),
+        queued = tokio::time::timeout(Duration::from_secs(5), stored) => {
+            queued.expect("trap event was not stored")
+        }
+    };
+    assert_eq!(queued.len(), 1);
+    assert_eq!(queued[0].metric.task_name, UNMATCHED_TRAP_TASK_NAME);
+    assert!(matches!(
+        queued[0].metric.data,
+        AggregatedMetricData::SnmpTrap(_)
+    ));
+}
@@ -0,0 +1,56 @@
+//! Tests for bandwidth throughput calculation and sampling
+
+use crate::task_bandwidth::{sample_throughput, to_mbps, THROUGHPUT_SAMPLE_INTERVAL};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+#[test]
+fn test_to_mbps() {
+    // 1,000,000 bytes in one second = 8 Mbps
+    assert_eq!(to_mbps(1_000_000, 1000.0), 8.0);
+    assert_eq!(to_mbps(1_000_000, 500.0), 16.0);
+    assert_eq!(to_mbps(1_000_000, 0.0), 0.0);
+}
+
+#[tokio::test]
+async fn test_sample_throughput_series() {
+    let counter = AtomicU64::new(0);
+
+    // Transfer 125,000 bytes per sample interval for four intervals
+    let transfer = async {
+        for _ in 0..4 {
+            counter.fetch_add(125_000, Ordering::Relaxed);
+            tokio::time::sleep(THROUGHPUT_SAMPLE_INTERVAL).await;
+        }
+        "done"
+    };
+
+    let (output, series) = sample_throughput(transfer, &counter).await;
+    assert_eq!(output, "done");
+    // Timer jitter may add a short trailing sample
+    assert!((4..=5).contains(&series.len()), "samples: {:?}", series);
+    // 125,000 bytes per 250 ms = 4 Mbps
+    let total_bytes: f64 = series
+        .iter()
+        .map(|mbps| mbps * 1_000_000.0 / 8.0 * THROUGHPUT_SAMPLE_INTERVAL.as_secs_f64())
+        .sum();
+    assert!(
+        (total_bytes - 500_000.0).abs() < 100_000.0,
+        "samples: {:?}",
+        series
+    );
+}
+
+#[tokio::test]
+async fn test_sample_throughput_records_partial_interval() {
+    let counter = AtomicU64::new(0);
+
+    // Finishes well before the first sample interval elapses
+    let transfer = async {
+        counter.fetch_add(10_000, Ordering::Relaxed);
+        tokio::task::yield_now().await;
+    };
+
+    let (_, series) = sample_throughput(transfer, &counter).await;
+    assert_eq!(series.len(), 1);
+    assert!(series[0] > 0.0);
+}
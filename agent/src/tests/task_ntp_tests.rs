@@ -0,0 +1,181 @@
+//! Tests for the NTP task
+
+use crate::ntp_time::ntp_timestamp_now;
+use crate::task_ntp::{
+    client_request, evaluate_status, execute_ntp_task, NtpResponse, NTP_PACKET_SIZE,
+};
+use shared::config::NtpParams;
+use shared::metrics::ThresholdStatus;
+use std::net::SocketAddr;
+use tokio::net::UdpSocket;
+
+fn ntp_params(host: String) -> NtpParams {
+    NtpParams {
+        host,
+        timeout_seconds: 1,
+        warn_offset_ms: None,
+        crit_offset_ms: None,
+        target_id: Some("ntp-1".to_string()),
+    }
+}
+
+/// Converts milliseconds to the NTP timestamp format (32.32 fixed point)
+fn ntp_ms(ms: f64) -> i64 {
+    (ms / 1000.0 * (1u64 << 32) as f64) as i64
+}
+
+/// Starts a fake NTP server that answers one request, returning its address
+///
+/// The server's clock runs `offset_ms` ahead of the agent's. `header` and
+/// `stratum` are the first two bytes of the response.
+async fn start_server(offset_ms: f64, header: u8, stratum: u8) -> SocketAddr {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let address = socket.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buf = [0u8; 1024];
+        let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+        assert_eq!(len, NTP_PACKET_SIZE);
+        let now = ntp_timestamp_now().wrapping_add(ntp_ms(offset_ms) as u64);
+
+        let mut response = [0u8; NTP_PACKET_SIZE];
+        response[0] = header;
+        response[1] = stratum;
+        if stratum == 0 {
+            response[12..16].copy_from_slice(b"RATE");
+        } else if stratum == 1 {
+            response[12..16].copy_from_slice(b"GPS\0");
+        } else {
+            response[12..16].copy_from_slice(&[192, 0, 2, 10]);
+        }
+        // Origin timestamp is the client's transmit timestamp
+        response[24..32].copy_from_slice(&buf[40..48]);
+        response[32..40].copy_from_slice(&now.to_be_bytes());
+        response[40..48].copy_from_slice(&now.to_be_bytes());
+        socket.send_to(&response, peer).await.unwrap();
+    });
+    address
+}
+
+#[test]
+fn test_client_request_layout() {
+    let request = client_request(0x0102_0304_0506_0708);
+    // LI = 0, VN = 4, Mode = 3
+    assert_eq!(request[0], 0x23);
+    assert!(request[1..40].iter().all(|b| *b == 0));
+    assert_eq!(&request[40..48], &[1, 2, 3, 4, 5, 6, 7, 8]);
+}
+
+#[test]
+fn test_offset_and_delay_from_timestamps() {
+    let t1 = ntp_timestamp_now();
+    // Server clock 100 ms ahead, 10 ms each way, 2 ms in the server
+    let response = NtpResponse {
+        leap_indicator: 0,
+        mode: 4,
+        stratum: 2,
+        reference_id: [10, 0, 0, 1],
+        origin_timestamp: t1,
+        receive_timestamp: t1.wrapping_add(ntp_ms(110.0) as u64),
+        transmit_timestamp: t1.wrapping_add(ntp_ms(112.0) as u64),
+    };
+    let t4 = t1.wrapping_add(ntp_ms(22.0) as u64);
+
+    let (offset_ms, delay_ms) = response.offset_and_delay_ms(t4);
+    assert!((offset_ms - 100.0).abs() < 0.001, "offset {}", offset_ms);
+    assert!((delay_ms - 20.0).abs() < 0.001, "delay {}", delay_ms);
+    assert_eq!(response.reference_id_text(), "10.0.0.1");
+
+    // Server clock behind the agent gives a negative offset
+    let behind = NtpResponse {
+        receive_timestamp: t1.wrapping_sub(ntp_ms(40.0) as u64),
+        transmit_timestamp: t1.wrapping_sub(ntp_ms(38.0) as u64),
+        ..response
+    };
+    let (offset_ms, _) = behind.offset_and_delay_ms(t4);
+    assert!((offset_ms + 50.0).abs() < 0.001, "offset {}", offset_ms);
+}
+
+#[test]
+fn test_decode_rejects_short_packets() {
+    assert_eq!(NtpResponse::decode(&[0u8; NTP_PACKET_SIZE - 1]), None);
+    let mut packet = [0u8; NTP_PACKET_SIZE];
+    packet[0] = 0xE4; // LI = 3, VN = 4, Mode = 4
+    packet[1] = 1;
+    packet[12..16].copy_from_slice(b"PPS\0");
+    let response = NtpResponse::decode(&packet).unwrap();
+    assert_eq!(response.leap_indicator, 3);
+    assert_eq!(response.mode, 4);
+    assert_eq!(response.reference_id_text(), "PPS");
+}
+
+#[test]
+fn test_offset_thresholds() {
+    let mut params = ntp_params("pool.ntp.org".to_string());
+    assert_eq!(
+        evaluate_status(&params, 5000.0),
+        (ThresholdStatus::Ok, None)
+    );
+
+    params.warn_offset_ms = Some(100.0);
+    params.crit_offset_ms = Some(1000.0);
+    assert_eq!(evaluate_status(&params, 50.0).0, ThresholdStatus::Ok);
+    assert_eq!(evaluate_status(&params, 150.0).0, ThresholdStatus::Warning);
+    // Thresholds apply to the absolute offset
+    let (status, message) = evaluate_status(&params, -1500.0);
+    assert_eq!(status, ThresholdStatus::Critical);
+    assert!(message.unwrap().contains("crit_offset_ms"));
+}
+
+#[tokio::test]
+async fn test_ntp_query_against_local_server() {
+    let address = start_server(250.0, 0x24, 2).await;
+    let mut params = ntp_params(address.to_string());
+    params.warn_offset_ms = Some(100.0);
+    let result = execute_ntp_task(&params).await;
+
+    assert!(result.success, "{:?}", result.error);
+    let offset_ms = result.offset_ms.unwrap();
+    assert!((offset_ms - 250.0).abs() < 50.0, "offset {}", offset_ms);
+    assert!(result.delay_ms.unwrap() >= 0.0);
+    assert_eq!(result.stratum, Some(2));
+    assert_eq!(result.reference_id.as_deref(), Some("192.0.2.10"));
+    assert_eq!(result.leap_indicator, Some(0));
+    assert_eq!(result.status, ThresholdStatus::Warning);
+    assert_eq!(result.host, address.to_string());
+    assert_eq!(result.target_id.as_deref(), Some("ntp-1"));
+}
+
+#[tokio::test]
+async fn test_ntp_unsynchronized_server() {
+    // LI = 3 (alarm), VN = 4, Mode = 4
+    let address = start_server(0.0, 0xE4, 1).await;
+    let result = execute_ntp_task(&ntp_params(address.to_string())).await;
+
+    assert!(!result.success);
+    assert_eq!(result.status, ThresholdStatus::Error);
+    assert_eq!(result.leap_indicator, Some(3));
+    assert_eq!(result.reference_id.as_deref(), Some("GPS"));
+    assert!(result.error.unwrap().contains("not synchronized"));
+}
+
+#[tokio::test]
+async fn test_ntp_kiss_of_death() {
+    let address = start_server(0.0, 0x24, 0).await;
+    let result = execute_ntp_task(&ntp_params(address.to_string())).await;
+
+    assert!(!result.success);
+    assert!(result.offset_ms.is_none());
+    assert!(result.error.unwrap().contains("RATE"));
+}
+
+#[tokio::test]
+async fn test_ntp_timeout() {
+    // A bound socket that never answers
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let params = ntp_params(socket.local_addr().unwrap().to_string());
+    let result = execute_ntp_task(&params).await;
+
+    assert!(!result.success);
+    assert_eq!(result.status, ThresholdStatus::Error);
+    assert!(result.error.unwrap().contains("Timeout"));
+}
@@ -0,0 +1,65 @@
+//! Tests for SNMP value checks
+
+use crate::task_snmp::check_expectations;
+use shared::config::{SnmpAuthProtocol, SnmpParams, SnmpSecurityLevel, SnmpVersion};
+
+fn snmp_params() -> SnmpParams {
+    SnmpParams {
+        host: "192.0.2.1".to_string(),
+        oid: "1.3.6.1.2.1.2.2.1.8.1".to_string(),
+        version: SnmpVersion::V2c,
+        community: "public".to_string(),
+        username: None,
+        security_level: SnmpSecurityLevel::NoAuthNoPriv,
+        auth_protocol: SnmpAuthProtocol::None,
+        auth_password: None,
+        timeout_seconds: 5,
+        target_id: None,
+        expected_value: None,
+        min_value: None,
+        max_value: None,
+    }
+}
+
+#[test]
+fn test_no_expectations_always_pass() {
+    let params = snmp_params();
+    assert!(check_expectations(&params, "Cisco IOS", None).is_none());
+    assert!(check_expectations(&params, "42", Some(42.0)).is_none());
+}
+
+#[test]
+fn test_expected_value_match() {
+    let mut params = snmp_params();
+    // ifOperStatus up(1)
+    params.expected_value = Some("1".to_string());
+    assert!(check_expectations(&params, "1", Some(1.0)).is_none());
+
+    let error = check_expectations(&params, "2", Some(2.0)).unwrap();
+    assert!(error.contains("does not match expected value"));
+}
+
+#[test]
+fn test_value_range() {
+    let mut params = snmp_params();
+    params.min_value = Some(10.0);
+    params.max_value = Some(90.0);
+
+    assert!(check_expectations(&params, "10", Some(10.0)).is_none());
+    assert!(check_expectations(&params, "90", Some(90.0)).is_none());
+    assert!(check_expectations(&params, "5", Some(5.0))
+        .unwrap()
+        .contains("below min_value"));
+    assert!(check_expectations(&params, "95", Some(95.0))
+        .unwrap()
+        .contains("above max_value"));
+}
+
+#[test]
+fn test_range_requires_numeric_value() {
+    let mut params = snmp_params();
+    params.max_value = Some(90.0);
+
+    let error = check_expectations(&params, "Cisco IOS", None).unwrap();
+    assert!(error.contains("not numeric"));
+}
@@ -0,0 +1,111 @@
+//! Tests for the TCP sweep task
+
+use crate::task_tcp_sweep::execute_tcp_sweep_task;
+use shared::config::TcpSweepParams;
+use shared::metrics::TcpSweepState;
+use tokio::net::TcpListener;
+
+fn sweep_params(hosts: Vec<&str>, ports: String) -> TcpSweepParams {
+    TcpSweepParams {
+        hosts: hosts.into_iter().map(String::from).collect(),
+        ports,
+        timeout_seconds: 2,
+        concurrency: 4,
+        target_id: Some("farm".to_string()),
+    }
+}
+
+/// Returns a port on localhost with nothing listening on it
+async fn closed_port() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    listener.local_addr().unwrap().port()
+}
+
+#[tokio::test]
+async fn test_tcp_sweep_open_and_closed_targets() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let open_port = listener.local_addr().unwrap().port();
+    let closed_port = closed_port().await;
+
+    let params = sweep_params(vec!["127.0.0.1"], format!("{},{}", open_port, closed_port));
+    let result = execute_tcp_sweep_task(&params).await;
+
+    assert!(result.success, "{:?}", result.error);
+    assert_eq!(result.targets_total, 2);
+    assert_eq!(result.open_count, 1);
+    assert_eq!(result.closed_count, 1);
+    assert_eq!(result.filtered_count, 0);
+    assert_eq!(result.unresolved_count, 0);
+    assert_eq!(result.target_id.as_deref(), Some("farm"));
+
+    // Results keep the order of the configuration
+    assert_eq!(result.results[0].target, format!("127.0.0.1:{}", open_port));
+    assert_eq!(result.results[0].state, TcpSweepState::Open);
+    assert!(result.results[0].connect_time_ms.is_some());
+    assert_eq!(
+        result.results[1].target,
+        format!("127.0.0.1:{}", closed_port)
+    );
+    assert_eq!(result.results[1].state, TcpSweepState::Closed);
+    assert!(result.results[1].connect_time_ms.is_none());
+    assert_eq!(
+        result.avg_connect_time_ms,
+        result.results[0].connect_time_ms
+    );
+}
+
+#[tokio::test]
+async fn test_tcp_sweep_many_targets_with_bounded_concurrency() {
+    let mut listeners = Vec::new();
+    for _ in 0..10 {
+        listeners.push(TcpListener::bind("127.0.0.1:0").await.unwrap());
+    }
+    let ports: Vec<String> = listeners
+        .iter()
+        .map(|l| l.local_addr().unwrap().port().to_string())
+        .collect();
+
+    let mut params = sweep_params(vec!["127.0.0.1"], ports.join(","));
+    params.concurrency = 3;
+    let result = execute_tcp_sweep_task(&params).await;
+
+    assert!(result.success);
+    assert_eq!(result.targets_total, 10);
+    assert_eq!(result.open_count, 10);
+    let min = result.min_connect_time_ms.unwrap();
+    let max = result.max_connect_time_ms.unwrap();
+    assert!(min <= result.avg_connect_time_ms.unwrap());
+    assert!(result.avg_connect_time_ms.unwrap() <= max);
+}
+
+#[tokio::test]
+async fn test_tcp_sweep_unresolved_host() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let open_port = listener.local_addr().unwrap().port();
+
+    let params = sweep_params(
+        vec!["127.0.0.1", "invalid-host-that-does-not-exist.invalid"],
+        open_port.to_string(),
+    );
+    let result = execute_tcp_sweep_task(&params).await;
+
+    // Resolvable hosts are still swept
+    assert!(!result.success);
+    assert_eq!(result.open_count, 1);
+    assert_eq!(result.unresolved_count, 1);
+    assert_eq!(result.results[1].state, TcpSweepState::Unresolved);
+    assert!(result
+        .error
+        .unwrap()
+        .contains("invalid-host-that-does-not-exist.invalid"));
+}
+
+#[tokio::test]
+async fn test_tcp_sweep_invalid_ports() {
+    let params = sweep_params(vec!["127.0.0.1"], "80-70".to_string());
+    let result = execute_tcp_sweep_task(&params).await;
+
+    assert!(!result.success);
+    assert_eq!(result.targets_total, 0);
+    assert!(result.error.unwrap().contains("80-70"));
+}
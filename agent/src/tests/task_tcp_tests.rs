@@ -9,6 +9,9 @@ async fn test_tcp_task_success() {
     let params = TcpParams {
         host: "8.8.8.8:53".to_string(),
         timeout_seconds: 5,
+        send: None,
+        expect: None,
+        read_timeout_seconds: 5,
         target_id: Some("google-dns".to_string()),
     };
 
@@ -26,6 +29,9 @@ async fn test_tcp_task_connection_refused() {
     let params = TcpParams {
         host: "127.0.0.1:65534".to_string(),
         timeout_seconds: 2,
+        send: None,
+        expect: None,
+        read_timeout_seconds: 5,
         target_id: None,
     };
 
@@ -41,6 +47,9 @@ async fn test_tcp_task_invalid_host() {
     let params = TcpParams {
         host: "invalid-host-that-does-not-exist.invalid:80".to_string(),
         timeout_seconds: 2,
+        send: None,
+        expect: None,
+        read_timeout_seconds: 5,
         target_id: Some("invalid".to_string()),
     };
 
@@ -61,6 +70,9 @@ async fn test_tcp_task_timeout() {
     let params = TcpParams {
         host: "192.0.2.1:80".to_string(),
         timeout_seconds: 1,
+        send: None,
+        expect: None,
+        read_timeout_seconds: 5,
         target_id: None,
     };
 
@@ -70,3 +82,110 @@ async fn test_tcp_task_timeout() {
     assert!(result.error.is_some());
     assert!(result.error.as_ref().unwrap().contains("timeout"));
 }
+
+/// Starts a one-connection server that writes `banner`, then answers every read with `reply`
+async fn start_line_server(banner: &'static [u8], reply: &'static [u8]) -> String {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let address = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        stream.write_all(banner).await.unwrap();
+        let mut buf = [0u8; 256];
+        while let Ok(len) = stream.read(&mut buf).await {
+            if len == 0 || stream.write_all(reply).await.is_err() {
+                break;
+            }
+        }
+    });
+    address.to_string()
+}
+
+fn response_check_params(host: String, send: Option<&str>, expect: Option<&str>) -> TcpParams {
+    TcpParams {
+        host,
+        timeout_seconds: 2,
+        send: send.map(str::to_string),
+        expect: expect.map(str::to_string),
+        read_timeout_seconds: 1,
+        target_id: None,
+    }
+}
+
+#[tokio::test]
+async fn test_tcp_task_banner_match() {
+    let host = start_line_server(b"SSH-2.0-OpenSSH_9.6\r\n", b"").await;
+
+    let result = execute_tcp_task(&response_check_params(host, None, Some(r"^SSH-2\.0-"))).await;
+
+    assert!(result.success, "error: {:?}", result.error);
+    assert!(result.connect_time_ms.is_some());
+    assert!(result.response_time_ms.is_some());
+    assert_eq!(result.expect_matched, Some(true));
+}
+
+#[tokio::test]
+async fn test_tcp_task_send_and_expect() {
+    // Redis answers PING with +PONG
+    let host = start_line_server(b"", b"+PONG\r\n").await;
+
+    let result = execute_tcp_task(&response_check_params(
+        host,
+        Some("PING\r\n"),
+        Some(r"^\+PONG"),
+    ))
+    .await;
+
+    assert!(result.success, "error: {:?}", result.error);
+    assert_eq!(result.expect_matched, Some(true));
+    assert!(result.response_time_ms.is_some());
+}
+
+#[tokio::test]
+async fn test_tcp_task_expect_mismatch() {
+    let host = start_line_server(b"", b"-NOAUTH Authentication required.\r\n").await;
+
+    let result = execute_tcp_task(&response_check_params(
+        host,
+        Some("PING\r\n"),
+        Some(r"^\+PONG"),
+    ))
+    .await;
+
+    // The port is open, but the service does not answer correctly
+    assert!(!result.success);
+    assert!(result.connect_time_ms.is_some());
+    assert_eq!(result.response_time_ms, None);
+    assert_eq!(result.expect_matched, Some(false));
+    let error = result.error.unwrap();
+    assert!(error.contains("did not match"), "{}", error);
+    assert!(error.contains("-NOAUTH"), "{}", error);
+}
+
+#[tokio::test]
+async fn test_tcp_task_no_response() {
+    // The server never writes anything
+    let host = start_line_server(b"", b"").await;
+
+    let result = execute_tcp_task(&response_check_params(host, None, Some("^220 "))).await;
+
+    assert!(!result.success);
+    assert_eq!(result.expect_matched, Some(false));
+    assert!(result
+        .error
+        .unwrap()
+        .contains("No response within 1 seconds"));
+}
+
+#[tokio::test]
+async fn test_tcp_task_send_without_expect() {
+    let host = start_line_server(b"", b"anything\r\n").await;
+
+    let result = execute_tcp_task(&response_check_params(host, Some("HELLO\r\n"), None)).await;
+
+    // Any response passes, and there is no match status
+    assert!(result.success, "error: {:?}", result.error);
+    assert!(result.response_time_ms.is_some());
+    assert_eq!(result.expect_matched, None);
+}
@@ -0,0 +1,209 @@
+//! Tests for the TWAMP-Light task and the TWAMP responder
+
+use crate::ntp_time::{ntp_difference_ms, ntp_timestamp_now};
+use crate::task_twamp::{
+    execute_twamp_task, ReflectorPacket, SenderPacket, TwampStats, ERROR_ESTIMATE,
+    REFLECTOR_PACKET_SIZE, SENDER_PACKET_SIZE,
+};
+use crate::twamp_responder::TwampResponder;
+use shared::config::{TwampParams, TwampResponderConfig};
+use std::net::SocketAddr;
+use tokio::net::UdpSocket;
+
+fn twamp_params(host: String) -> TwampParams {
+    TwampParams {
+        host,
+        packet_count: 10,
+        interval_ms: 1,
+        packet_size: 41,
+        timeout_seconds: 1,
+        target_id: Some("core-1".to_string()),
+    }
+}
+
+fn responder(allowed_sources: Vec<String>) -> TwampResponder {
+    TwampResponder::new(&TwampResponderConfig {
+        listen_address: "127.0.0.1:0".to_string(),
+        allowed_sources,
+    })
+    .unwrap()
+}
+
+/// Starts a responder on an ephemeral port, returning its address
+async fn start_responder(allowed_sources: Vec<String>) -> SocketAddr {
+    let responder = responder(allowed_sources);
+    let socket = responder.bind().await.unwrap();
+    let address = socket.local_addr().unwrap();
+    let (shutdown_tx, shutdown_rx) = tokio::sync::broadcast::channel(1);
+    tokio::spawn(async move {
+        responder.run(socket, shutdown_rx).await;
+        drop(shutdown_tx);
+    });
+    address
+}
+
+#[test]
+fn test_packet_layout_matches_rfc_5357() {
+    let sender = SenderPacket {
+        sequence: 7,
+        timestamp: 0x0102_0304_0506_0708,
+        error_estimate: ERROR_ESTIMATE,
+    };
+    let mut packet = vec![0xFFu8; 41];
+    sender.encode(&mut packet);
+    assert_eq!(&packet[0..4], &[0, 0, 0, 7]);
+    assert_eq!(&packet[4..12], &[1, 2, 3, 4, 5, 6, 7, 8]);
+    assert_eq!(&packet[12..14], &[0, 1]);
+    // Padding is zeroed
+    assert!(packet[SENDER_PACKET_SIZE..].iter().all(|b| *b == 0));
+    assert_eq!(SenderPacket::decode(&packet), Some(sender));
+    assert_eq!(SenderPacket::decode(&packet[..13]), None);
+
+    let reflected = ReflectorPacket {
+        sequence: 7,
+        timestamp: 30,
+        error_estimate: ERROR_ESTIMATE,
+        receive_timestamp: 20,
+        sender_sequence: 7,
+        sender_timestamp: 10,
+        sender_error_estimate: ERROR_ESTIMATE,
+        sender_ttl: 255,
+    };
+    let mut packet = vec![0xFFu8; REFLECTOR_PACKET_SIZE];
+    reflected.encode(&mut packet);
+    // MBZ fields are zeroed
+    assert_eq!(&packet[14..16], &[0, 0]);
+    assert_eq!(&packet[38..40], &[0, 0]);
+    assert_eq!(packet[40], 255);
+    assert_eq!(ReflectorPacket::decode(&packet), Some(reflected));
+    assert_eq!(ReflectorPacket::decode(&packet[..40]), None);
+}
+
+#[test]
+fn test_ntp_timestamps() {
+    // Half a second is 2^31 in the fraction field
+    let earlier = 3_900_000_000u64 << 32;
+    let later = earlier + (1u64 << 31);
+    assert_eq!(ntp_difference_ms(later, earlier), 500.0);
+    assert_eq!(ntp_difference_ms(earlier, later), -500.0);
+
+    let reflected = ReflectorPacket {
+        sequence: 0,
+        timestamp: earlier,
+        error_estimate: ERROR_ESTIMATE,
+        receive_timestamp: later,
+        sender_sequence: 0,
+        sender_timestamp: 0,
+        sender_error_estimate: ERROR_ESTIMATE,
+        sender_ttl: 255,
+    };
+    // A reflector clock stepping backwards never inflates the delay
+    assert_eq!(reflected.processing_time_ms(), 0.0);
+
+    // The NTP era started in 1900, 2208988800 seconds before the Unix epoch
+    assert!(ntp_timestamp_now() >> 32 > 2_208_988_800 + 1_700_000_000);
+}
+
+#[test]
+fn test_twamp_stats() {
+    let params = twamp_params("10.0.0.1:862".to_string());
+    let mut stats = TwampStats::new(4);
+    stats.record_reflection(0, 2.0);
+    stats.record_reflection(1, 4.0);
+    // Duplicates and packets outside the run are ignored
+    stats.record_reflection(1, 100.0);
+    stats.record_reflection(9, 100.0);
+    assert!(!stats.is_complete());
+
+    let metric = stats.into_metric(4, &params, None);
+    assert!(metric.success);
+    assert_eq!(metric.packets_received, 2);
+    assert_eq!(metric.packet_loss_percent, 50.0);
+    assert_eq!(metric.avg_latency_ms, Some(3.0));
+    assert_eq!(metric.min_latency_ms, Some(2.0));
+    assert_eq!(metric.max_latency_ms, Some(4.0));
+    assert_eq!(metric.jitter_ms, Some(2.0 / 16.0));
+
+    let metric = TwampStats::new(4).into_metric(4, &params, None);
+    assert!(!metric.success);
+    assert_eq!(metric.packet_loss_percent, 100.0);
+    assert_eq!(metric.avg_latency_ms, None);
+    assert!(metric.error.unwrap().contains("No test packets reflected"));
+}
+
+#[test]
+fn test_responder_reflects_sender_packet() {
+    let responder = responder(vec![]);
+    let src: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+
+    // Minimum-size sender packets get a full reflector packet
+    let mut request = vec![0u8; SENDER_PACKET_SIZE];
+    let sent_at = ntp_timestamp_now();
+    SenderPacket {
+        sequence: 3,
+        timestamp: sent_at,
+        error_estimate: ERROR_ESTIMATE,
+    }
+    .encode(&mut request);
+    let reply = responder.reflect(&request, src).unwrap();
+    assert_eq!(reply.len(), REFLECTOR_PACKET_SIZE);
+    let reflected = ReflectorPacket::decode(&reply).unwrap();
+    assert_eq!(reflected.sequence, 3);
+    assert_eq!(reflected.sender_sequence, 3);
+    assert_eq!(reflected.sender_timestamp, sent_at);
+    assert_eq!(reflected.sender_error_estimate, ERROR_ESTIMATE);
+    assert!(reflected.timestamp >= reflected.receive_timestamp);
+
+    // Padded packets are reflected at the same size
+    request.resize(200, 0);
+    assert_eq!(responder.reflect(&request, src).unwrap().len(), 200);
+
+    // Datagrams too short to be test packets are dropped
+    assert!(responder.reflect(&request[..10], src).is_none());
+}
+
+#[tokio::test]
+async fn test_twamp_against_responder() {
+    let address = start_responder(vec![]).await;
+
+    let metric = execute_twamp_task(&twamp_params(address.to_string())).await;
+
+    assert!(metric.success, "error: {:?}", metric.error);
+    assert_eq!(metric.packets_sent, 10);
+    assert_eq!(metric.packets_received, 10);
+    assert_eq!(metric.packet_loss_percent, 0.0);
+    assert!(metric.avg_latency_ms.unwrap() >= 0.0);
+    assert!(metric.jitter_ms.is_some());
+    assert_eq!(metric.target_id.as_deref(), Some("core-1"));
+}
+
+#[tokio::test]
+async fn test_twamp_without_reflector() {
+    // Bind and release a port so that nothing is listening on it
+    let address = UdpSocket::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let mut params = twamp_params(address.to_string());
+    params.packet_count = 3;
+
+    let metric = execute_twamp_task(&params).await;
+
+    assert!(!metric.success);
+    assert_eq!(metric.packets_sent, 3);
+    assert_eq!(metric.packet_loss_percent, 100.0);
+    assert!(metric.error.is_some());
+}
+
+#[tokio::test]
+async fn test_responder_ignores_sources_not_allowed() {
+    let address = start_responder(vec!["192.0.2.1".to_string()]).await;
+    let mut params = twamp_params(address.to_string());
+    params.packet_count = 3;
+
+    let metric = execute_twamp_task(&params).await;
+
+    assert!(!metric.success);
+    assert_eq!(metric.packets_received, 0);
+}
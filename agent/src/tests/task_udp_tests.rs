@@ -0,0 +1,214 @@
+//! Tests for the UDP probe task and the UDP reflector
+
+use crate::task_udp::{execute_udp_probe_task, ProbeHeader, ProbeKind, ProbeStats};
+use crate::udp_reflector::UdpReflector;
+use shared::config::{UdpProbeParams, UdpReflectorConfig};
+use std::net::SocketAddr;
+use tokio::net::UdpSocket;
+
+fn probe_params(host: String) -> UdpProbeParams {
+    UdpProbeParams {
+        host,
+        packet_count: 20,
+        interval_ms: 1,
+        payload_size: 160,
+        timeout_seconds: 1,
+        target_id: Some("branch-b".to_string()),
+    }
+}
+
+/// Starts a reflector on an ephemeral port, returning its address
+async fn start_reflector(allowed_sources: Vec<String>) -> SocketAddr {
+    let config = UdpReflectorConfig {
+        listen_address: "127.0.0.1:0".to_string(),
+        allowed_sources,
+    };
+    let reflector = UdpReflector::new(&config).unwrap();
+    let socket = reflector.bind().await.unwrap();
+    let address = socket.local_addr().unwrap();
+    let (shutdown_tx, shutdown_rx) = tokio::sync::broadcast::channel(1);
+    tokio::spawn(async move {
+        reflector.run(socket, shutdown_rx).await;
+        drop(shutdown_tx);
+    });
+    address
+}
+
+#[test]
+fn test_probe_header_round_trip() {
+    let header = ProbeHeader {
+        kind: ProbeKind::Reply,
+        session_id: 0xDEAD_BEEF,
+        sequence: 42,
+        sent_at_us: 1_234_567,
+        reflected_count: 40,
+    };
+    let mut packet = vec![0u8; 160];
+    header.encode(&mut packet);
+    assert_eq!(ProbeHeader::decode(&packet), Some(header));
+
+    // Truncated packets and other traffic are not probes
+    assert_eq!(ProbeHeader::decode(&packet[..20]), None);
+    assert_eq!(
+        ProbeHeader::decode(b"GET / HTTP/1.1\r\n\r\n0123456789"),
+        None
+    );
+}
+
+#[test]
+fn test_probe_stats_loss_duplicates_and_reordering() {
+    let params = probe_params("127.0.0.1:8789".to_string());
+    let mut stats = ProbeStats::new(10);
+
+    // Probe 2 arrives after 3, probe 4 is duplicated, probes 6-9 are lost
+    for (sequence, rtt_ms) in [
+        (0, 10.0),
+        (1, 12.0),
+        (3, 10.0),
+        (2, 14.0),
+        (4, 10.0),
+        (4, 10.0),
+    ] {
+        stats.record_reply(sequence, rtt_ms, Some(sequence + 1));
+    }
+    stats.record_reply(5, 10.0, Some(8));
+    // Replies outside the run are ignored
+    stats.record_reply(99, 1.0, Some(99));
+
+    let metric = stats.into_metric(10, &params, None);
+    assert!(metric.success);
+    assert_eq!(metric.packets_received, 6);
+    assert_eq!(metric.loss_percent, 40.0);
+    assert_eq!(metric.duplicate_packets, 1);
+    assert_eq!(metric.reordered_packets, 1);
+    // The reflector saw 8 probes: 2 were lost on the way out, 2 on the way back
+    assert_eq!(metric.packets_reflected, Some(8));
+    assert_eq!(metric.forward_loss_percent, Some(20.0));
+    assert_eq!(metric.return_loss_percent, Some(25.0));
+    assert_eq!(metric.min_rtt_ms, Some(10.0));
+    assert_eq!(metric.max_rtt_ms, Some(14.0));
+    assert!((metric.avg_rtt_ms.unwrap() - 66.0 / 6.0).abs() < 1e-9);
+    let jitter = metric.jitter_ms.unwrap();
+    assert!(jitter > 0.0 && jitter < 4.0, "jitter: {}", jitter);
+}
+
+#[test]
+fn test_probe_stats_without_replies() {
+    let params = probe_params("127.0.0.1:8789".to_string());
+    let metric = ProbeStats::new(5).into_metric(5, &params, None);
+
+    assert!(!metric.success);
+    assert_eq!(metric.loss_percent, 100.0);
+    assert_eq!(metric.avg_rtt_ms, None);
+    assert_eq!(metric.jitter_ms, None);
+    assert_eq!(metric.forward_loss_percent, None);
+    assert!(metric.error.unwrap().contains("No replies"));
+}
+
+#[tokio::test]
+async fn test_udp_probe_against_reflector() {
+    let address = start_reflector(vec![]).await;
+    let params = probe_params(address.to_string());
+
+    let metric = execute_udp_probe_task(&params).await;
+
+    assert!(metric.success, "error: {:?}", metric.error);
+    assert_eq!(metric.packets_sent, 20);
+    assert_eq!(metric.packets_received, 20);
+    assert_eq!(metric.loss_percent, 0.0);
+    assert_eq!(metric.duplicate_packets, 0);
+    assert_eq!(metric.packets_reflected, Some(20));
+    assert_eq!(metric.forward_loss_percent, Some(0.0));
+    assert_eq!(metric.return_loss_percent, Some(0.0));
+    assert!(metric.avg_rtt_ms.is_some());
+    assert!(metric.jitter_ms.is_some());
+    assert_eq!(metric.target_id.as_deref(), Some("branch-b"));
+}
+
+#[tokio::test]
+async fn test_udp_probe_against_echo_server() {
+    let echo = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let address = echo.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut buf = [0u8; 2048];
+        while let Ok((len, src)) = echo.recv_from(&mut buf).await {
+            let _ = echo.send_to(&buf[..len], src).await;
+        }
+    });
+
+    let metric = execute_udp_probe_task(&probe_params(address.to_string())).await;
+
+    assert!(metric.success, "error: {:?}", metric.error);
+    assert_eq!(metric.packets_received, 20);
+    // Echo servers cannot tell which direction lost a packet
+    assert_eq!(metric.packets_reflected, None);
+    assert_eq!(metric.forward_loss_percent, None);
+    assert_eq!(metric.return_loss_percent, None);
+}
+
+#[tokio::test]
+async fn test_udp_probe_without_listener() {
+    // Bind and release a port so that nothing is listening on it
+    let address = UdpSocket::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap();
+    let mut params = probe_params(address.to_string());
+    params.packet_count = 3;
+
+    let metric = execute_udp_probe_task(&params).await;
+
+    assert!(!metric.success);
+    assert_eq!(metric.packets_sent, 3);
+    assert_eq!(metric.loss_percent, 100.0);
+    assert!(metric.error.is_some());
+}
+
+#[tokio::test]
+async fn test_reflector_ignores_sources_not_allowed() {
+    let address = start_reflector(vec!["192.0.2.1".to_string()]).await;
+    let mut params = probe_params(address.to_string());
+    params.packet_count = 3;
+
+    let metric = execute_udp_probe_task(&params).await;
+
+    assert!(!metric.success);
+    assert_eq!(metric.packets_received, 0);
+}
+
+#[test]
+fn test_reflector_counts_probes_per_run() {
+    let config = UdpReflectorConfig {
+        listen_address: "127.0.0.1:0".to_string(),
+        allowed_sources: vec![],
+    };
+    let mut reflector = UdpReflector::new(&config).unwrap();
+    let src: SocketAddr = "127.0.0.1:40000".parse().unwrap();
+
+    let mut request = |session_id, sequence| {
+        let mut packet = vec![0u8; 64];
+        ProbeHeader {
+            kind: ProbeKind::Request,
+            session_id,
+            sequence,
+            sent_at_us: 0,
+            reflected_count: 0,
+        }
+        .encode(&mut packet);
+        assert!(reflector.reflect(&mut packet, src));
+        ProbeHeader::decode(&packet).unwrap()
+    };
+
+    assert_eq!(request(1, 0).reflected_count, 1);
+    assert_eq!(request(1, 1).reflected_count, 2);
+    // A new run starts counting from one
+    let reply = request(2, 0);
+    assert_eq!(reply.kind, ProbeKind::Reply);
+    assert_eq!(reply.reflected_count, 1);
+
+    // Replies are never reflected again
+    let mut packet = vec![0u8; 64];
+    reply.encode(&mut packet);
+    assert!(!reflector.reflect(&mut packet, src));
+}
@@ -2,8 +2,9 @@
 
 use crate::tasks::TaskExecutor;
 use shared::config::{
-    BandwidthParams, DnsQueryDohParams, DnsQueryParams, DnsRecordType, HttpContentParams,
-    HttpGetParams, PingParams, TaskConfig, TaskParams, TaskType, TcpParams, TlsHandshakeParams,
+    BandwidthDirection, BandwidthParams, DnsQueryDohParams, DnsQueryParams, DnsRecordType,
+    HttpContentParams, HttpGetParams, PingParams, TaskConfig, TaskParams, TcpParams,
+    TlsHandshakeParams,
 };
 use std::collections::HashMap;
 use tokio::sync::mpsc;
@@ -20,17 +21,15 @@ async fn test_ping_task_execution() {
     let (sender, mut receiver) = mpsc::channel(100);
     let executor = TaskExecutor::new(sender, None, None, None).unwrap();
 
-    let task_config = TaskConfig {
-        task_type: TaskType::Ping,
-        schedule_seconds: 10,
-        name: "Test Ping".to_string(),
-        timeout: None,
-        params: TaskParams::Ping(PingParams {
+    let task_config = TaskConfig::new(
+        "Test Ping",
+        10,
+        TaskParams::Ping(PingParams {
             host: "8.8.8.8".to_string(),
             timeout_seconds: 1,
             target_id: None,
         }),
-    };
+    );
 
     let result = executor.execute_task(&task_config).await;
     assert!(result.is_ok());
@@ -47,19 +46,17 @@ async fn test_http_task_execution() {
     let (sender, mut receiver) = mpsc::channel(100);
     let executor = TaskExecutor::new(sender, None, None, None).unwrap();
 
-    let task_config = TaskConfig {
-        task_type: TaskType::HttpGet,
-        schedule_seconds: 30,
-        name: "Test HTTP".to_string(),
-        timeout: None,
-        params: TaskParams::HttpGet(HttpGetParams {
+    let task_config = TaskConfig::new(
+        "Test HTTP",
+        30,
+        TaskParams::HttpGet(HttpGetParams {
             url: "https://example.com".to_string(),
             timeout_seconds: 10,
             headers: HashMap::new(),
             verify_ssl: false,
             target_id: None,
         }),
-    };
+    );
 
     let result = executor.execute_task(&task_config).await;
     assert!(result.is_ok());
@@ -74,12 +71,10 @@ async fn test_dns_task_execution() {
     let (sender, mut receiver) = mpsc::channel(100);
     let executor = TaskExecutor::new(sender, None, None, None).unwrap();
 
-    let task_config = TaskConfig {
-        task_type: TaskType::DnsQuery,
-        schedule_seconds: 60,
-        name: "Test DNS".to_string(),
-        timeout: None,
-        params: TaskParams::DnsQuery(DnsQueryParams {
+    let task_config = TaskConfig::new(
+        "Test DNS",
+        60,
+        TaskParams::DnsQuery(DnsQueryParams {
             server: "1.1.1.1:53".to_string(),
             domain: "google.com".to_string(),
             record_type: DnsRecordType::A,
@@ -87,7 +82,7 @@ async fn test_dns_task_execution() {
             expected_ip: None,
             target_id: None,
         }),
-    };
+    );
 
     let result = executor.execute_task(&task_config).await;
     assert!(result.is_ok());
@@ -109,17 +104,23 @@ async fn test_bandwidth_task_execution() {
     let (sender, mut receiver) = mpsc::channel(100);
     let executor = TaskExecutor::new(sender, None, None, None).unwrap();
 
-    let task_config = TaskConfig {
-        task_type: TaskType::Bandwidth,
-        schedule_seconds: 60, // Use minimum valid value
-        name: "Test Bandwidth".to_string(),
-        timeout: None,
-        params: TaskParams::Bandwidth(BandwidthParams {
+    let task_config = TaskConfig::new(
+        "Test Bandwidth",
+        60, // Use minimum valid value
+        TaskParams::Bandwidth(BandwidthParams {
             timeout_seconds: 60,
             max_retries: 10,
+            direction: BandwidthDirection::Download,
+            parallel_streams: 1,
+            url: None,
+            upload_url: None,
+            reflector_url: None,
+            reflector_api_key: None,
+            data_size_mb: None,
+            coordinate_with_server: None,
             target_id: None,
         }),
-    };
+    );
 
     // Validate the task config first
     assert!(task_config.validate().is_ok());
@@ -136,32 +137,44 @@ async fn test_bandwidth_task_execution() {
 #[tokio::test]
 async fn test_bandwidth_task_validation() {
     // Test that bandwidth tasks with schedule < 60 seconds fail validation
-    let invalid_task_config = TaskConfig {
-        task_type: TaskType::Bandwidth,
-        schedule_seconds: 30, // Less than minimum 60 seconds
-        name: "Invalid Bandwidth Test".to_string(),
-        timeout: None,
-        params: TaskParams::Bandwidth(BandwidthParams {
+    let invalid_task_config = TaskConfig::new(
+        "Invalid Bandwidth Test",
+        30, // Less than minimum 60 seconds
+        TaskParams::Bandwidth(BandwidthParams {
             timeout_seconds: 60,
             max_retries: 10,
+            direction: BandwidthDirection::Download,
+            parallel_streams: 1,
+            url: None,
+            upload_url: None,
+            reflector_url: None,
+            reflector_api_key: None,
+            data_size_mb: None,
+            coordinate_with_server: None,
             target_id: None,
         }),
-    };
+    );
 
     assert!(invalid_task_config.validate().is_err());
 
     // Test that bandwidth tasks with schedule >= 60 seconds pass validation
-    let valid_task_config = TaskConfig {
-        task_type: TaskType::Bandwidth,
-        schedule_seconds: 60,
-        name: "Valid Bandwidth Test".to_string(),
-        timeout: None,
-        params: TaskParams::Bandwidth(BandwidthParams {
+    let valid_task_config = TaskConfig::new(
+        "Valid Bandwidth Test",
+        60,
+        TaskParams::Bandwidth(BandwidthParams {
             timeout_seconds: 60,
             max_retries: 10,
+            direction: BandwidthDirection::Download,
+            parallel_streams: 1,
+            url: None,
+            upload_url: None,
+            reflector_url: None,
+            reflector_api_key: None,
+            data_size_mb: None,
+            coordinate_with_server: None,
             target_id: None,
         }),
-    };
+    );
 
     assert!(valid_task_config.validate().is_ok());
 }
@@ -172,17 +185,15 @@ async fn test_ping_timeout() {
     let executor = TaskExecutor::new(sender, None, None, None).unwrap();
 
     // Use a non-routable IP address that should timeout
-    let task_config = TaskConfig {
-        task_type: TaskType::Ping,
-        schedule_seconds: 10,
-        name: "Test Ping Timeout".to_string(),
-        timeout: None,
-        params: TaskParams::Ping(PingParams {
+    let task_config = TaskConfig::new(
+        "Test Ping Timeout",
+        10,
+        TaskParams::Ping(PingParams {
             host: "192.0.2.1".to_string(), // TEST-NET-1, reserved for documentation
             timeout_seconds: 1,
             target_id: None,
         }),
-    };
+    );
 
     let start = std::time::Instant::now();
     let result = executor.execute_task(&task_config).await;
@@ -210,19 +221,17 @@ async fn test_http_timeout() {
     let executor = TaskExecutor::new(sender, None, None, None).unwrap();
 
     // Use a non-routable IP that will timeout
-    let task_config = TaskConfig {
-        task_type: TaskType::HttpGet,
-        schedule_seconds: 30,
-        name: "Test HTTP Timeout".to_string(),
-        timeout: None,
-        params: TaskParams::HttpGet(HttpGetParams {
+    let task_config = TaskConfig::new(
+        "Test HTTP Timeout",
+        30,
+        TaskParams::HttpGet(HttpGetParams {
             url: "http://192.0.2.1/test".to_string(), // TEST-NET-1
             timeout_seconds: 2,
             headers: HashMap::new(),
             verify_ssl: false,
             target_id: None,
         }),
-    };
+    );
 
     let start = std::time::Instant::now();
     let result = executor.execute_task(&task_config).await;
@@ -250,12 +259,10 @@ async fn test_dns_timeout() {
     let executor = TaskExecutor::new(sender, None, None, None).unwrap();
 
     // Use an IP that doesn't respond to DNS queries
-    let task_config = TaskConfig {
-        task_type: TaskType::DnsQuery,
-        schedule_seconds: 60,
-        name: "Test DNS Timeout".to_string(),
-        timeout: None,
-        params: TaskParams::DnsQuery(DnsQueryParams {
+    let task_config = TaskConfig::new(
+        "Test DNS Timeout",
+        60,
+        TaskParams::DnsQuery(DnsQueryParams {
             server: "192.0.2.1:53".to_string(), // TEST-NET-1
             domain: "example.com".to_string(),
             record_type: DnsRecordType::A,
@@ -263,7 +270,7 @@ async fn test_dns_timeout() {
             expected_ip: None,
             target_id: None,
         }),
-    };
+    );
 
     let start = std::time::Instant::now();
     let result = executor.execute_task(&task_config).await;
@@ -290,19 +297,17 @@ async fn test_http_task_failed_domain() {
     let (sender, mut receiver) = mpsc::channel(100);
     let executor = TaskExecutor::new(sender, None, None, None).unwrap();
 
-    let task_config = TaskConfig {
-        task_type: TaskType::HttpGet,
-        schedule_seconds: 30,
-        name: "Test HTTP Failure".to_string(),
-        timeout: None,
-        params: TaskParams::HttpGet(HttpGetParams {
+    let task_config = TaskConfig::new(
+        "Test HTTP Failure",
+        30,
+        TaskParams::HttpGet(HttpGetParams {
             url: "https://non-existent-domain-12345.com".to_string(),
             timeout_seconds: 5,
             headers: HashMap::new(),
             verify_ssl: false,
             target_id: None,
         }),
-    };
+    );
 
     let result = executor.execute_task(&task_config).await;
     assert!(result.is_ok());
@@ -318,12 +323,10 @@ async fn test_dns_task_expected_ip_match() {
     let (sender, mut receiver) = mpsc::channel(100);
     let executor = TaskExecutor::new(sender, None, None, None).unwrap();
 
-    let task_config = TaskConfig {
-        task_type: TaskType::DnsQuery,
-        schedule_seconds: 60,
-        name: "Test DNS Expected IP Match".to_string(),
-        timeout: None,
-        params: TaskParams::DnsQuery(DnsQueryParams {
+    let task_config = TaskConfig::new(
+        "Test DNS Expected IP Match",
+        60,
+        TaskParams::DnsQuery(DnsQueryParams {
             server: "1.1.1.1:53".to_string(),
             domain: "one.one.one.one".to_string(),
             record_type: DnsRecordType::A,
@@ -331,7 +334,7 @@ async fn test_dns_task_expected_ip_match() {
             expected_ip: Some("1.1.1.1".to_string()),
             target_id: None,
         }),
-    };
+    );
 
     let result = executor.execute_task(&task_config).await;
     assert!(result.is_ok());
@@ -350,12 +353,10 @@ async fn test_dns_task_expected_ip_mismatch() {
     let (sender, mut receiver) = mpsc::channel(100);
     let executor = TaskExecutor::new(sender, None, None, None).unwrap();
 
-    let task_config = TaskConfig {
-        task_type: TaskType::DnsQuery,
-        schedule_seconds: 60,
-        name: "Test DNS Expected IP Mismatch".to_string(),
-        timeout: None,
-        params: TaskParams::DnsQuery(DnsQueryParams {
+    let task_config = TaskConfig::new(
+        "Test DNS Expected IP Mismatch",
+        60,
+        TaskParams::DnsQuery(DnsQueryParams {
             server: "1.1.1.1:53".to_string(),
             domain: "one.one.one.one".to_string(),
             record_type: DnsRecordType::A,
@@ -363,7 +364,7 @@ async fn test_dns_task_expected_ip_mismatch() {
             expected_ip: Some("1.2.3.4".to_string()),
             target_id: None,
         }),
-    };
+    );
 
     let result = executor.execute_task(&task_config).await;
     assert!(result.is_ok());
@@ -383,17 +384,18 @@ async fn test_tcp_task_execution() {
     let (sender, mut receiver) = mpsc::channel(100);
     let executor = TaskExecutor::new(sender, None, None, None).unwrap();
 
-    let task_config = TaskConfig {
-        task_type: TaskType::Tcp,
-        schedule_seconds: 10,
-        name: "Test TCP".to_string(),
-        timeout: None,
-        params: TaskParams::Tcp(TcpParams {
+    let task_config = TaskConfig::new(
+        "Test TCP",
+        10,
+        TaskParams::Tcp(TcpParams {
             host: "google.com:80".to_string(),
             timeout_seconds: 5,
+            send: None,
+            expect: None,
+            read_timeout_seconds: 5,
             target_id: None,
         }),
-    };
+    );
 
     let result = executor.execute_task(&task_config).await;
     assert!(result.is_ok());
@@ -409,17 +411,15 @@ async fn test_tls_task_execution() {
     let executor = TaskExecutor::new(sender, None, None, None).unwrap();
 
     // Test with SSL verification enabled (should fail)
-    let task_config_verify = TaskConfig {
-        task_type: TaskType::TlsHandshake,
-        schedule_seconds: 30,
-        name: "Test TLS Verify".to_string(),
-        timeout: None,
-        params: TaskParams::TlsHandshake(TlsHandshakeParams {
+    let task_config_verify = TaskConfig::new(
+        "Test TLS Verify",
+        30,
+        TaskParams::TlsHandshake(TlsHandshakeParams {
             host: "expired.badssl.com:443".to_string(),
             verify_ssl: true,
             target_id: None,
         }),
-    };
+    );
 
     let result = executor.execute_task(&task_config_verify).await;
     assert!(result.is_ok());
@@ -429,17 +429,15 @@ async fn test_tls_task_execution() {
     assert!(!task_result.success);
 
     // Test with SSL verification disabled (should succeed)
-    let task_config_no_verify = TaskConfig {
-        task_type: TaskType::TlsHandshake,
-        schedule_seconds: 30,
-        name: "Test TLS No Verify".to_string(),
-        timeout: None,
-        params: TaskParams::TlsHandshake(TlsHandshakeParams {
+    let task_config_no_verify = TaskConfig::new(
+        "Test TLS No Verify",
+        30,
+        TaskParams::TlsHandshake(TlsHandshakeParams {
             host: "expired.badssl.com:443".to_string(),
             verify_ssl: false,
             target_id: None,
         }),
-    };
+    );
 
     let result = executor.execute_task(&task_config_no_verify).await;
     assert!(result.is_ok());
@@ -454,18 +452,16 @@ async fn test_http_content_task_execution() {
     let (sender, mut receiver) = mpsc::channel(100);
     let executor = TaskExecutor::new(sender, None, None, None).unwrap();
 
-    let task_config = TaskConfig {
-        task_type: TaskType::HttpContent,
-        schedule_seconds: 30,
-        name: "Test HTTP Content".to_string(),
-        timeout: None,
-        params: TaskParams::HttpContent(HttpContentParams {
+    let task_config = TaskConfig::new(
+        "Test HTTP Content",
+        30,
+        TaskParams::HttpContent(HttpContentParams {
             url: "https://example.com".to_string(),
             regexp: "Example Domain".to_string(),
             timeout_seconds: 10,
             target_id: None,
         }),
-    };
+    );
 
     let result = executor.execute_task(&task_config).await;
     assert!(result.is_ok());
@@ -480,12 +476,10 @@ async fn test_dns_doh_task_execution() {
     let (sender, mut receiver) = mpsc::channel(100);
     let executor = TaskExecutor::new(sender, None, None, None).unwrap();
 
-    let task_config = TaskConfig {
-        task_type: TaskType::DnsQueryDoh,
-        schedule_seconds: 60,
-        name: "Test DoH".to_string(),
-        timeout: None,
-        params: TaskParams::DnsQueryDoh(DnsQueryDohParams {
+    let task_config = TaskConfig::new(
+        "Test DoH",
+        60,
+        TaskParams::DnsQueryDoh(DnsQueryDohParams {
             server_url: "https://chrome.cloudflare-dns.com/dns-query".to_string(),
             domain: "google.com".to_string(),
             record_type: DnsRecordType::A,
@@ -493,7 +487,7 @@ async fn test_dns_doh_task_execution() {
             expected_ip: None,
             target_id: None,
         }),
-    };
+    );
 
     let result = executor.execute_task(&task_config).await;
     assert!(result.is_ok());
@@ -509,19 +503,17 @@ async fn test_http_get_ssl_verification() {
     let executor = TaskExecutor::new(sender, None, None, None).unwrap();
 
     // Test with SSL verification enabled (should fail)
-    let task_config_verify = TaskConfig {
-        task_type: TaskType::HttpGet,
-        schedule_seconds: 30,
-        name: "Test HTTP Verify SSL".to_string(),
-        timeout: None,
-        params: TaskParams::HttpGet(HttpGetParams {
+    let task_config_verify = TaskConfig::new(
+        "Test HTTP Verify SSL",
+        30,
+        TaskParams::HttpGet(HttpGetParams {
             url: "https://expired.badssl.com/".to_string(),
             timeout_seconds: 10,
             headers: HashMap::new(),
             verify_ssl: true,
             target_id: None,
         }),
-    };
+    );
 
     let result = executor.execute_task(&task_config_verify).await;
     assert!(result.is_ok());
@@ -531,19 +523,17 @@ async fn test_http_get_ssl_verification() {
     assert!(!task_result.success);
 
     // Test with SSL verification disabled (should succeed)
-    let task_config_no_verify = TaskConfig {
-        task_type: TaskType::HttpGet,
-        schedule_seconds: 30,
-        name: "Test HTTP No Verify SSL".to_string(),
-        timeout: None,
-        params: TaskParams::HttpGet(HttpGetParams {
+    let task_config_no_verify = TaskConfig::new(
+        "Test HTTP No Verify SSL",
+        30,
+        TaskParams::HttpGet(HttpGetParams {
             url: "https://expired.badssl.com/".to_string(),
             timeout_seconds: 10,
             headers: HashMap::new(),
             verify_ssl: false,
             target_id: None,
         }),
-    };
+    );
 
     let result = executor.execute_task(&task_config_no_verify).await;
     assert!(result.is_ok());
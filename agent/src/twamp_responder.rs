@@ -0,0 +1,123 @@
+//! TWAMP-Light responder
+//!
+//! When `[twamp_responder]` is configured, the agent acts as a stateless
+//! TWAMP-Light Session-Reflector (RFC 5357, unauthenticated mode). Every test
+//! packet is answered with a reflector test packet carrying the receive and
+//! transmit timestamps, so that other agents' `twamp` tasks and third-party
+//! Session-Senders can measure two-way delay to this agent.
+// Being stateless, the responder copies the sender's sequence number into the
+// reflector sequence number. The IP TTL of the test packet is not available
+// from the socket, so the sender TTL field is always 255.
+
+use crate::ntp_time::ntp_timestamp_now;
+use crate::task_twamp::{ReflectorPacket, SenderPacket, ERROR_ESTIMATE, REFLECTOR_PACKET_SIZE};
+use anyhow::{Context, Result};
+use shared::config::{TwampResponderConfig, MAX_UDP_PROBE_PAYLOAD_SIZE};
+use std::net::{IpAddr, SocketAddr};
+use tokio::net::UdpSocket;
+use tracing::{debug, info, warn};
+
+/// Value of the sender TTL field in reflected packets
+const SENDER_TTL: u8 = 255;
+
+/// UDP listener answering TWAMP-Light test packets
+pub struct TwampResponder {
+    listen_address: String,
+    allowed_sources: Vec<IpAddr>,
+}
+
+impl TwampResponder {
+    /// Create a new responder from validated configuration
+    pub fn new(config: &TwampResponderConfig) -> Result<Self> {
+        let allowed_sources = config
+            .allowed_sources
+            .iter()
+            .map(|s| {
+                s.parse::<IpAddr>()
+                    .map(|ip| ip.to_canonical())
+                    .with_context(|| format!("Invalid TWAMP responder source address: {}", s))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            listen_address: config.listen_address.clone(),
+            allowed_sources,
+        })
+    }
+
+    /// Bind the listening socket
+    pub async fn bind(&self) -> Result<UdpSocket> {
+        let socket = UdpSocket::bind(&self.listen_address)
+            .await
+            .with_context(|| {
+                format!("Failed to bind TWAMP responder to {}", self.listen_address)
+            })?;
+        info!(
+            listen_address = %self.listen_address,
+            "TWAMP responder listening"
+        );
+        Ok(socket)
+    }
+
+    /// Answer test packets until a shutdown signal arrives
+    pub async fn run(
+        self,
+        socket: UdpSocket,
+        mut shutdown_rx: tokio::sync::broadcast::Receiver<()>,
+    ) {
+        let mut buf = vec![0u8; MAX_UDP_PROBE_PAYLOAD_SIZE as usize];
+
+        loop {
+            let (len, src) = tokio::select! {
+                result = socket.recv_from(&mut buf) => match result {
+                    Ok(received) => received,
+                    Err(e) => {
+                        // ICMP errors for earlier replies surface here; keep serving
+                        debug!("TWAMP responder failed to read datagram: {}", e);
+                        continue;
+                    }
+                },
+                _ = shutdown_rx.recv() => {
+                    info!("TWAMP responder shutting down");
+                    break;
+                }
+            };
+
+            if let Some(reply) = self.reflect(&buf[..len], src) {
+                if let Err(e) = socket.send_to(&reply, src).await {
+                    warn!(source = %src, "TWAMP responder failed to send reply: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Build the reflector test packet answering a sender test packet
+    ///
+    /// Returns None if the datagram must be dropped: it is too short to be a
+    /// test packet or its source is not allowed. The reply is as large as the
+    /// request, but at least `REFLECTOR_PACKET_SIZE` bytes.
+    pub fn reflect(&self, datagram: &[u8], src: SocketAddr) -> Option<Vec<u8>> {
+        let receive_timestamp = ntp_timestamp_now();
+        if !self.allowed_sources.is_empty()
+            && !self.allowed_sources.contains(&src.ip().to_canonical())
+        {
+            debug!(source = %src, "TWAMP responder dropped packet from source not in allowed_sources");
+            return None;
+        }
+        let request = SenderPacket::decode(datagram)?;
+
+        let mut reply = vec![0u8; datagram.len().max(REFLECTOR_PACKET_SIZE)];
+        ReflectorPacket {
+            sequence: request.sequence,
+            timestamp: ntp_timestamp_now(),
+            error_estimate: ERROR_ESTIMATE,
+            receive_timestamp,
+            sender_sequence: request.sequence,
+            sender_timestamp: request.timestamp,
+            sender_error_estimate: request.error_estimate,
+            sender_ttl: SENDER_TTL,
+        }
+        .encode(&mut reply);
+        Some(reply)
+    }
+}
@@ -0,0 +1,143 @@
+//! UDP reflector for agent-to-agent UDP probes
+//!
+//! When `[udp_reflector]` is configured, the agent answers UDP probe packets
+//! from other agents' `udp_probe` tasks. Each reply is the probe itself, marked
+//! as a reply and stamped with the number of probes received from that run, so
+//! the sender can tell loss on the way out from loss on the way back.
+// Replies are never larger than the request, so the reflector cannot be used
+// to amplify traffic. Datagrams that are not probe requests are dropped.
+
+use crate::task_udp::{ProbeHeader, ProbeKind};
+use anyhow::{Context, Result};
+use shared::config::{UdpReflectorConfig, MAX_UDP_PROBE_PAYLOAD_SIZE};
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use tokio::net::UdpSocket;
+use tracing::{debug, info, warn};
+
+/// Number of probe runs tracked at once before idle runs are forgotten
+const MAX_SESSIONS: usize = 1024;
+
+/// Runs without probes for this long are forgotten
+const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Per-run receive counter
+struct Session {
+    received: u32,
+    last_seen: Instant,
+}
+
+/// UDP listener answering probe packets
+pub struct UdpReflector {
+    listen_address: String,
+    allowed_sources: Vec<IpAddr>,
+    sessions: HashMap<(SocketAddr, u32), Session>,
+}
+
+impl UdpReflector {
+    /// Create a new reflector from validated configuration
+    pub fn new(config: &UdpReflectorConfig) -> Result<Self> {
+        let allowed_sources = config
+            .allowed_sources
+            .iter()
+            .map(|s| {
+                s.parse::<IpAddr>()
+                    .map(|ip| ip.to_canonical())
+                    .with_context(|| format!("Invalid UDP reflector source address: {}", s))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Self {
+            listen_address: config.listen_address.clone(),
+            allowed_sources,
+            sessions: HashMap::new(),
+        })
+    }
+
+    /// Bind the listening socket
+    pub async fn bind(&self) -> Result<UdpSocket> {
+        let socket = UdpSocket::bind(&self.listen_address)
+            .await
+            .with_context(|| format!("Failed to bind UDP reflector to {}", self.listen_address))?;
+        info!(
+            listen_address = %self.listen_address,
+            "UDP reflector listening"
+        );
+        Ok(socket)
+    }
+
+    /// Answer probes until a shutdown signal arrives
+    pub async fn run(
+        mut self,
+        socket: UdpSocket,
+        mut shutdown_rx: tokio::sync::broadcast::Receiver<()>,
+    ) {
+        let mut buf = vec![0u8; MAX_UDP_PROBE_PAYLOAD_SIZE as usize];
+
+        loop {
+            let (len, src) = tokio::select! {
+                result = socket.recv_from(&mut buf) => match result {
+                    Ok(received) => received,
+                    Err(e) => {
+                        // ICMP errors for earlier replies surface here; keep serving
+                        debug!("UDP reflector failed to read datagram: {}", e);
+                        continue;
+                    }
+                },
+                _ = shutdown_rx.recv() => {
+                    info!("UDP reflector shutting down");
+                    break;
+                }
+            };
+
+            if self.reflect(&mut buf[..len], src) {
+                if let Err(e) = socket.send_to(&buf[..len], src).await {
+                    warn!(source = %src, "UDP reflector failed to send reply: {}", e);
+                }
+            }
+        }
+    }
+
+    /// Turn a probe request into its reply in place
+    ///
+    /// Returns false if the datagram must be dropped: it is not a probe
+    /// request or its source is not allowed.
+    pub fn reflect(&mut self, datagram: &mut [u8], src: SocketAddr) -> bool {
+        if !self.allowed_sources.is_empty()
+            && !self.allowed_sources.contains(&src.ip().to_canonical())
+        {
+            debug!(source = %src, "UDP reflector dropped probe from source not in allowed_sources");
+            return false;
+        }
+        let Some(mut header) = ProbeHeader::decode(datagram) else {
+            return false;
+        };
+        if header.kind != ProbeKind::Request {
+            return false;
+        }
+
+        let now = Instant::now();
+        if self.sessions.len() >= MAX_SESSIONS {
+            self.sessions
+                .retain(|_, session| now.duration_since(session.last_seen) < SESSION_IDLE_TIMEOUT);
+            if self.sessions.len() >= MAX_SESSIONS {
+                self.sessions.clear();
+            }
+        }
+        let session = self
+            .sessions
+            .entry((src, header.session_id))
+            .or_insert(Session {
+                received: 0,
+                last_seen: now,
+            });
+        session.received = session.received.saturating_add(1);
+        session.last_seen = now;
+
+        header.kind = ProbeKind::Reply;
+        header.reflected_count = session.received;
+        header.encode(datagram);
+        true
+    }
+}
@@ -0,0 +1,32 @@
+//! UDP socket setup shared by the UDP probe, TWAMP and NTP tasks
+
+use anyhow::{anyhow, Context, Result};
+use std::net::SocketAddr;
+use tokio::net::UdpSocket;
+
+/// Resolve `host` ("host:port") and return a UDP socket connected to it
+///
+/// The socket is bound to an ephemeral port of the target's address family.
+pub async fn connected_udp_socket(host: &str) -> Result<UdpSocket> {
+    let target = tokio::net::lookup_host(host)
+        .await
+        .with_context(|| format!("Failed to parse host '{}'", host))?
+        .next()
+        .ok_or_else(|| anyhow!("Failed to parse host '{}': no addresses found", host))?;
+
+    let bind_address: SocketAddr = if target.is_ipv4() {
+        "0.0.0.0:0".parse()?
+    } else {
+        "[::]:0".parse()?
+    };
+    let socket = UdpSocket::bind(bind_address)
+        .await
+        .context("Failed to bind UDP socket")?;
+    // Connecting filters out datagrams from other sources and reports ICMP
+    // port unreachable messages as errors
+    socket
+        .connect(target)
+        .await
+        .with_context(|| format!("Failed to connect UDP socket to {}", target))?;
+    Ok(socket)
+}
@@ -8,6 +8,7 @@
 // incoming requests, interacting with other parts of the server (like the
 // database), and returning appropriate responses.
 
+use crate::database::db_agent_inventory::{AgentInventory, InventoryChange};
 use axum::{
     extract::{DefaultBodyLimit, Query, State},
     http::{HeaderMap, StatusCode},
@@ -21,8 +22,11 @@ use shared::{
         // Importing the data structures for API requests and responses from the `shared` crate.
         endpoints,
         headers,
+        BandwidthCompleteRequest,
+        BandwidthCompleteResponse,
         BandwidthTestRequest,
         BandwidthTestResponse,
+        BandwidthUploadResponse,
         ConfigErrorRequest,
         ConfigStatus,
         ConfigUploadRequest,
@@ -200,6 +204,14 @@ pub fn create_router(state: AppState) -> Router {
             endpoints::BANDWIDTH_DOWNLOAD,
             get(handle_bandwidth_download),
         )
+        // The body limit layer below covers this route too, but it only applies
+        // to buffering extractors. The upload handler reads the raw body as a
+        // stream and enforces the test's own size limit while streaming.
+        .route(endpoints::BANDWIDTH_UPLOAD, post(handle_bandwidth_upload))
+        .route(
+            endpoints::BANDWIDTH_COMPLETE,
+            post(handle_bandwidth_complete),
+        )
         .layer(DefaultBodyLimit::max(MAX_REQUEST_SIZE))
         .with_state(state)
 }
@@ -331,6 +343,71 @@ fn validate_agent_whitelist(agent_id: &str, whitelist: &[String]) -> Result<(),
     }
 }
 
+/// Parses the `timestamp_utc` field sent by an agent.
+///
+/// Agents send Unix seconds; RFC 3339 timestamps are accepted as well.
+///
+/// # Returns
+/// * `Some(seconds)` - Unix timestamp of the agent's clock
+/// * `None` - The field is in neither format
+pub(crate) fn parse_agent_timestamp(timestamp_utc: &str) -> Option<i64> {
+    let timestamp_utc = timestamp_utc.trim();
+    if let Ok(seconds) = timestamp_utc.parse::<i64>() {
+        return Some(seconds);
+    }
+    chrono::DateTime::parse_from_rfc3339(timestamp_utc)
+        .ok()
+        .map(|dt| dt.timestamp())
+}
+
+/// Computes an agent's clock offset (agent clock minus server clock) in seconds.
+///
+/// Agents stamp each request just before sending it, so the offset also includes
+/// the request's transit time, which is negligible next to the skew threshold.
+pub(crate) fn agent_clock_offset(timestamp_utc: &str, server_time: i64) -> Option<i64> {
+    parse_agent_timestamp(timestamp_utc).map(|agent_time| agent_time - server_time)
+}
+
+/// Logs how an agent's reported inventory differs from the stored one.
+///
+/// A changed public IP or labels (e.g. a new ISP or site) is a warning, since it
+/// changes how the agent's results compare with its own history. Other host fact
+/// changes such as a renewed DHCP lease are informational.
+fn log_inventory_change(request: &MetricsRequest, previous: &AgentInventory) {
+    let previous_public_ip = previous
+        .host_facts
+        .as_ref()
+        .and_then(|facts| facts.public_ip.as_deref());
+    let public_ip = request
+        .host_facts
+        .as_ref()
+        .and_then(|facts| facts.public_ip.as_deref());
+
+    if previous_public_ip != public_ip {
+        warn!(
+            agent_id = %request.agent_id,
+            previous_public_ip = ?previous_public_ip,
+            public_ip = ?public_ip,
+            "Agent public IP changed"
+        );
+    }
+    if previous.labels != request.agent_labels {
+        warn!(
+            agent_id = %request.agent_id,
+            previous_labels = ?previous.labels,
+            labels = ?request.agent_labels,
+            "Agent labels changed"
+        );
+    }
+    if previous_public_ip == public_ip && previous.labels == request.agent_labels {
+        info!(
+            agent_id = %request.agent_id,
+            host_facts = ?request.host_facts,
+            "Agent host facts changed"
+        );
+    }
+}
+
 /// The handler for the `/health` endpoint.
 /// It returns a simple JSON response indicating the server's status.
 async fn health_check() -> impl IntoResponse {
@@ -375,7 +452,32 @@ async fn handle_metrics(
         "Received metrics from agent"
     );
 
-    // Upsert agent in database to track last seen time and config checksum
+    // Compare the agent's clock to ours. A skewed agent still gets its metrics
+    // stored; the health monitor flags it and compensates when counting entries.
+    let clock_offset_seconds =
+        agent_clock_offset(&request.timestamp_utc, chrono::Utc::now().timestamp());
+    match clock_offset_seconds {
+        Some(offset)
+            if offset.unsigned_abs() > state.config.health_check_max_clock_skew_seconds =>
+        {
+            warn!(
+                agent_id = %request.agent_id,
+                clock_offset_seconds = offset,
+                max_clock_skew_seconds = state.config.health_check_max_clock_skew_seconds,
+                "Agent clock is skewed"
+            );
+        }
+        Some(_) => {}
+        None => {
+            warn!(
+                agent_id = %request.agent_id,
+                timestamp_utc = %request.timestamp_utc,
+                "Could not parse agent timestamp, clock offset unknown"
+            );
+        }
+    }
+
+    // Upsert agent in database to track last seen time, config checksum and clock offset
     {
         let mut db = state.database.lock().await;
         if let Err(e) = db
@@ -383,6 +485,7 @@ async fn handle_metrics(
                 &request.agent_id,
                 &request.config_checksum,
                 request.agent_version.as_deref(),
+                clock_offset_seconds,
             )
             .await
         {
@@ -398,13 +501,47 @@ async fn handle_metrics(
         }
     }
 
-    // Store metrics in database
-    if !request.metrics.is_empty() {
+    // Record the agent's labels and host facts, noticing egress and ISP changes
+    if !request.agent_labels.is_empty() || request.host_facts.is_some() {
+        let mut db = state.database.lock().await;
+        match db
+            .update_agent_inventory(
+                &request.agent_id,
+                &request.agent_labels,
+                request.host_facts.as_ref(),
+            )
+            .await
+        {
+            Ok(InventoryChange::Changed(previous)) => {
+                log_inventory_change(&request, &previous);
+            }
+            Ok(InventoryChange::New) => {
+                info!(agent_id = %request.agent_id, "Recorded agent inventory");
+            }
+            Ok(InventoryChange::Unchanged) => {}
+            // Inventory is informational, the metrics are still stored
+            Err(e) => {
+                error!(
+                    agent_id = %request.agent_id,
+                    error = %e,
+                    "Failed to update agent inventory"
+                );
+            }
+        }
+    }
+
+    // Store aggregated metrics and the raw samples of tasks with upload_raw
+    // enabled together, so a failed push leaves nothing behind
+    if !request.metrics.is_empty() || !request.raw_metrics.is_empty() {
         let mut db = state.database.lock().await;
-        if let Err(e) = db.store_metrics(&request.agent_id, &request.metrics).await {
+        if let Err(e) = db
+            .store_metrics(&request.agent_id, &request.metrics, &request.raw_metrics)
+            .await
+        {
             error!(
                 agent_id = %request.agent_id,
                 metric_count = request.metrics.len(),
+                raw_metric_count = request.raw_metrics.len(),
                 error = %e,
                 "Failed to store metrics in database"
             );
@@ -417,6 +554,7 @@ async fn handle_metrics(
         info!(
             agent_id = %request.agent_id,
             metric_count = request.metrics.len(),
+            raw_metric_count = request.raw_metrics.len(),
             "Successfully stored metrics in database"
         );
     }
@@ -463,20 +601,87 @@ async fn handle_metrics(
     Ok(Json(response))
 }
 
+/// Registers a transfer stream for the agent holding the bandwidth test slot
+///
+/// Returns the per-stream data size in bytes. Fails if the agent has no active
+/// test or has already opened every stream it announced.
+async fn start_bandwidth_stream(state: &AppState, agent_id: &str) -> Result<u64, ApiError> {
+    let bandwidth_manager = state.bandwidth_manager.lock().await;
+
+    match bandwidth_manager.start_stream(agent_id).await {
+        Some(streams) => {
+            let total_size = (state.config.bandwidth_test_size_mb as u64) * 1024 * 1024;
+            Ok(shared::utils::bandwidth_stream_size(total_size, streams))
+        }
+        None => {
+            let status = bandwidth_manager.get_status().await;
+            warn!(
+                agent_id = %agent_id,
+                active_agents = ?status.active_tests.iter().map(|(agent, _)| agent).collect::<Vec<_>>(),
+                queued_agents = status.queued_agents.len(),
+                "Agent attempted bandwidth transfer without permission"
+            );
+            Err(ApiError::BadRequest(
+                "No active bandwidth test for this agent".to_string(),
+            ))
+        }
+    }
+}
+
+/// Finishes a download stream's bandwidth slot exactly once
+///
+/// The response body calls [`finish`](Self::finish) after its last chunk. If
+/// the body is dropped before that, the stream is finished on drop instead.
+struct StreamRelease {
+    bandwidth_manager: Arc<tokio::sync::Mutex<crate::bandwidth_state::BandwidthTestManager>>,
+    agent_id: Option<String>,
+}
+
+impl StreamRelease {
+    fn new(state: &AppState, agent_id: &str) -> Self {
+        Self {
+            bandwidth_manager: Arc::clone(&state.bandwidth_manager),
+            agent_id: Some(agent_id.to_string()),
+        }
+    }
+
+    async fn finish(mut self) {
+        if let Some(agent_id) = self.agent_id.take() {
+            let bandwidth_manager = self.bandwidth_manager.lock().await;
+            bandwidth_manager.finish_stream(&agent_id).await;
+        }
+    }
+}
+
+impl Drop for StreamRelease {
+    fn drop(&mut self) {
+        if let Some(agent_id) = self.agent_id.take() {
+            let bandwidth_manager = Arc::clone(&self.bandwidth_manager);
+            tokio::spawn(async move {
+                let bandwidth_manager = bandwidth_manager.lock().await;
+                bandwidth_manager.finish_stream(&agent_id).await;
+            });
+        }
+    }
+}
+
 /// The handler for the bandwidth download endpoint.
 /// Agents download test data from this endpoint to measure their network throughput.
 /// This should only be called after receiving permission via the bandwidth_test endpoint.
 ///
 /// IMPORTANT: The download size is controlled ONLY by server configuration.
 /// The agent cannot influence the size - any size_mb parameter is ignored.
+/// With parallel streams, the configured size is split evenly between them.
 ///
 /// This endpoint uses streaming to avoid allocating the entire response in memory,
 /// which prevents memory exhaustion attacks with large bandwidth test sizes.
+/// The data is pseudo-random and generated per request from a seed, so WAN
+/// optimizers cannot compress it.
 async fn handle_bandwidth_download(
     State(state): State<AppState>,
     Query(params): Query<HashMap<String, String>>,
 ) -> Result<impl IntoResponse, ApiError> {
-    use futures_util::stream;
+    use futures_util::{stream, StreamExt};
 
     let agent_id = params.get("agent_id").map_or("", |s| s.as_str());
 
@@ -486,77 +691,46 @@ async fn handle_bandwidth_download(
     // Validate agent against whitelist
     validate_agent_whitelist(agent_id, &state.config.agent_id_whitelist)?;
 
-    // Validate that this agent has an active bandwidth test
-    {
-        let bandwidth_manager = state.bandwidth_manager.lock().await;
-        let status = bandwidth_manager.get_status().await;
-
-        if let Some((current_agent, _start_time)) = status.current_test {
-            if current_agent != agent_id {
-                warn!(
-                    agent_id = %agent_id,
-                    current_agent = %current_agent,
-                    "Agent attempted bandwidth download without permission"
-                );
-                return Err(ApiError::BadRequest(
-                    "No active bandwidth test for this agent".to_string(),
-                ));
-            }
-        } else {
-            warn!(
-                agent_id = %agent_id,
-                "Agent attempted bandwidth download without active test"
-            );
-            return Err(ApiError::BadRequest("No active bandwidth test".to_string()));
-        }
-    }
-
-    // Use server configuration for bandwidth test size
-    // Agent cannot influence this value - it comes from server.toml
-    let size_mb = state.config.bandwidth_test_size_mb;
+    // Validate that this agent has an active bandwidth test.
+    // Size comes from server configuration - agent cannot influence it
+    let total_size = start_bandwidth_stream(&state, agent_id).await? as usize;
 
     debug!(
         agent_id = %agent_id,
-        size_mb = size_mb,
+        total_size = total_size,
         "Serving bandwidth test data (server-controlled size)"
     );
 
     // Use streaming to avoid allocating entire response in memory
     // This prevents memory exhaustion with large bandwidth tests (e.g., 100MB+)
     const CHUNK_SIZE: usize = 64 * 1024; // 64 KB chunks - good balance for network efficiency
-    let total_size = (size_mb as usize).saturating_mul(1024 * 1024);
-    let chunks_needed = total_size.saturating_add(CHUNK_SIZE - 1) / CHUNK_SIZE;
+    let chunks_needed = total_size.div_ceil(CHUNK_SIZE);
+    let seed: u64 = rand::random();
 
-    // Create a single zero-filled chunk that will be reused in the stream
+    // Create a stream that generates each chunk on demand
     // This keeps memory usage constant at ~64KB regardless of total download size
-    let zero_chunk = axum::body::Bytes::from(vec![0u8; CHUNK_SIZE]);
-
-    // Create a stream that yields chunks without allocating the full response
     let byte_stream = stream::iter((0..chunks_needed).map(move |i| {
         let offset = i * CHUNK_SIZE;
-        let remaining = total_size.saturating_sub(offset);
-        let chunk_len = std::cmp::min(remaining, CHUNK_SIZE);
-
-        if chunk_len == CHUNK_SIZE {
-            // Full chunk - reuse the pre-allocated buffer
-            Ok::<_, std::io::Error>(zero_chunk.clone())
-        } else {
-            // Last chunk may be smaller
-            Ok(axum::body::Bytes::from(vec![0u8; chunk_len]))
-        }
+        let chunk_len = std::cmp::min(total_size - offset, CHUNK_SIZE);
+        let mut chunk = vec![0u8; chunk_len];
+        shared::utils::fill_incompressible(seed, i as u64, &mut chunk);
+        Ok::<_, std::io::Error>(axum::body::Bytes::from(chunk))
     }));
 
-    // Mark the test as completed so queued tests can proceed
-    // We do this before streaming starts so other agents don't wait unnecessarily
-    {
-        let bandwidth_manager = state.bandwidth_manager.lock().await;
-        bandwidth_manager.complete_test(agent_id).await;
-    }
+    // Mark the stream as finished once the last chunk has been sent, or when the
+    // body is dropped early because the agent disconnected
+    let release = StreamRelease::new(&state, agent_id);
+    let finished = stream::once(async move {
+        release.finish().await;
+        None
+    })
+    .filter_map(std::future::ready);
+    let byte_stream = byte_stream.chain(finished);
 
     info!(
         agent_id = %agent_id,
         total_size = total_size,
-        "Bandwidth test streaming started, queued agents can proceed"
+        "Bandwidth test streaming started"
     );
 
     let response = Response::builder()
@@ -569,6 +743,90 @@ async fn handle_bandwidth_download(
     Ok(response)
 }
 
+/// The handler for the bandwidth upload endpoint.
+/// Agents stream test data to this endpoint to measure their upload throughput.
+/// This should only be called after receiving permission via the bandwidth_test endpoint.
+///
+/// The body is read chunk by chunk and discarded, so memory usage stays constant.
+/// Uploads larger than the per-stream share of the configured bandwidth test
+/// size are rejected. Unlike
+/// downloads, the test slot is held until the whole body has been received,
+/// because the transfer happens while the request is being handled.
+async fn handle_bandwidth_upload(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Query(params): Query<HashMap<String, String>>,
+    body: axum::body::Body,
+) -> Result<Json<BandwidthUploadResponse>, ApiError> {
+    use futures_util::StreamExt;
+
+    // Validate API key against configured value
+    validate_api_key(&headers, &state.config.api_key)?;
+
+    let agent_id = params.get("agent_id").map_or("", |s| s.as_str());
+
+    // Validate agent ID
+    validate_agent_id(agent_id)?;
+
+    // Validate agent against whitelist
+    validate_agent_whitelist(agent_id, &state.config.agent_id_whitelist)?;
+
+    // Validate that this agent has an active bandwidth test
+    let max_size = start_bandwidth_stream(&state, agent_id).await?;
+    let mut bytes_received: u64 = 0;
+    let mut stream = body.into_data_stream();
+    let mut result = Ok(());
+
+    while let Some(chunk) = stream.next().await {
+        match chunk {
+            Ok(chunk) => {
+                bytes_received += chunk.len() as u64;
+                if bytes_received > max_size {
+                    result = Err(ApiError::BadRequest(format!(
+                        "Upload exceeds bandwidth test size of {} bytes",
+                        max_size
+                    )));
+                    break;
+                }
+            }
+            Err(e) => {
+                result = Err(ApiError::BadRequest(format!(
+                    "Failed to read upload body: {}",
+                    e
+                )));
+                break;
+            }
+        }
+    }
+
+    // Release the stream whether or not the upload succeeded
+    {
+        let bandwidth_manager = state.bandwidth_manager.lock().await;
+        bandwidth_manager.finish_stream(agent_id).await;
+    }
+
+    if let Err(e) = result {
+        warn!(
+            agent_id = %agent_id,
+            bytes_received = bytes_received,
+            error = %e,
+            "Bandwidth upload failed"
+        );
+        return Err(e);
+    }
+
+    info!(
+        agent_id = %agent_id,
+        bytes_received = bytes_received,
+        "Bandwidth upload stream completed"
+    );
+
+    Ok(Json(BandwidthUploadResponse {
+        status: "ok".to_string(),
+        bytes_received,
+    }))
+}
+
 /// The handler for the configuration retrieval endpoint.
 /// Agents call this endpoint to download their configuration files.
 // `Query(params)` is an extractor for URL query parameters.
@@ -920,6 +1178,15 @@ async fn handle_bandwidth_test(
         "Received bandwidth test request from agent"
     );
 
+    if request.parallel_streams == 0
+        || request.parallel_streams > shared::config::MAX_BANDWIDTH_PARALLEL_STREAMS
+    {
+        return Err(ApiError::BadRequest(format!(
+            "parallel_streams must be between 1 and {}",
+            shared::config::MAX_BANDWIDTH_PARALLEL_STREAMS
+        )));
+    }
+
     // Get data size from server configuration
     let data_size_bytes = (state.config.bandwidth_test_size_mb as u64) * 1024 * 1024;
 
@@ -927,7 +1194,11 @@ async fn handle_bandwidth_test(
     let response = {
         let bandwidth_manager = state.bandwidth_manager.lock().await;
         bandwidth_manager
-            .request_test(request.agent_id.clone(), data_size_bytes)
+            .request_test(
+                request.agent_id.clone(),
+                data_size_bytes,
+                request.parallel_streams,
+            )
             .await
     };
 
@@ -940,6 +1211,36 @@ async fn handle_bandwidth_test(
     Ok(Json(response))
 }
 
+/// The handler for releasing a coordinated bandwidth test slot.
+/// Agents that measured against a third-party server or another agent while
+/// holding a slot call this endpoint when done, since no transfer through the
+/// server marks their test as finished.
+async fn handle_bandwidth_complete(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(request): Json<BandwidthCompleteRequest>,
+) -> Result<Json<BandwidthCompleteResponse>, ApiError> {
+    validate_api_key(&headers, &state.config.api_key)?;
+    validate_agent_id(&request.agent_id)?;
+    validate_agent_whitelist(&request.agent_id, &state.config.agent_id_whitelist)?;
+
+    let released = {
+        let bandwidth_manager = state.bandwidth_manager.lock().await;
+        bandwidth_manager.complete_test(&request.agent_id).await
+    };
+
+    debug!(
+        agent_id = %request.agent_id,
+        released = released,
+        "Bandwidth test completion processed"
+    );
+
+    Ok(Json(BandwidthCompleteResponse {
+        status: "success".to_string(),
+        released,
+    }))
+}
+
 /// Custom error types for the API.
 /// Using a dedicated enum for API errors allows for consistent error handling
 /// and response formatting.
@@ -1,27 +1,83 @@
-//! Bandwidth test state management for preventing concurrent tests
+//! Bandwidth test state management for coordinating concurrent tests
 //!
-//! This module manages the state of bandwidth tests to ensure only one test
-//! runs at a time per server, coordinating test execution between agents.
+//! This module manages the state of bandwidth tests so that agents sharing an
+//! uplink do not measure each other's traffic. A configurable number of tests
+//! may run at once server-wide, and agents can be assigned to groups (for
+//! example a site) with a separate per-group limit. Agents outside any group
+//! form a group of their own.
+//!
+//! A test may use several parallel transfer streams; its slot is released
+//! once every announced stream has finished. Waiting agents are served in
+//! request order, skipping only those whose group is at capacity, and the
+//! queue can be persisted so it survives a server restart. The state file is
+//! written by a background task so request handlers never wait on disk I/O.
 
+use serde::{Deserialize, Serialize};
 use shared::api::{BandwidthTestAction, BandwidthTestResponse};
 
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::{mpsc, oneshot, RwLock};
 use tracing::{debug, info, warn};
 
-/// Manages bandwidth test state to prevent concurrent tests
+/// Manages bandwidth test slots and the waiting queue
 #[derive(Clone)]
 pub struct BandwidthTestManager {
     state: Arc<RwLock<BandwidthTestState>>,
 }
 
+/// A bandwidth test holding a slot
+struct ActiveTest {
+    agent_id: String,
+    /// Group whose concurrency limit this test counts against
+    group: String,
+    start_time: Instant,
+    /// Number of transfer streams announced by the agent
+    streams: u32,
+    /// Streams that have started transferring
+    streams_started: u32,
+    /// Streams that have finished transferring
+    streams_finished: u32,
+}
+
+impl ActiveTest {
+    fn new(agent_id: String, group: String, streams: u32) -> Self {
+        Self {
+            agent_id,
+            group,
+            start_time: Instant::now(),
+            streams: streams.max(1),
+            streams_started: 0,
+            streams_finished: 0,
+        }
+    }
+}
+
+/// An agent waiting for a slot
+struct QueueEntry {
+    agent_id: String,
+    /// When the agent first asked for a test; kept across retries
+    first_requested: SystemTime,
+    /// When the agent last asked; entries not refreshed in time expire
+    last_seen: Instant,
+}
+
 /// Internal state for bandwidth test management
 struct BandwidthTestState {
-    /// Current test in progress (agent_id and start time)
-    current_test: Option<(String, Instant)>,
-    /// Queue of agents waiting to run tests (agent_id and request time)
-    waiting_queue: Vec<(String, Instant)>,
+    /// Tests currently holding a slot
+    active_tests: Vec<ActiveTest>,
+    /// Agents waiting to run tests, in request order
+    waiting_queue: Vec<QueueEntry>,
+    /// Maximum number of tests running at once
+    max_concurrent: usize,
+    /// Maximum number of tests running at once within one group
+    group_max_concurrent: usize,
+    /// Agent ID to group name for grouped agents
+    agent_groups: HashMap<String, String>,
+    /// Channel to the task writing the state file, if persistence is enabled
+    state_writer: Option<mpsc::UnboundedSender<StateWrite>>,
     /// Test timeout duration
     test_timeout: Duration,
     /// Maximum delay to suggest to agents
@@ -34,8 +90,43 @@ struct BandwidthTestState {
     position_multiplier_seconds: u64,
 }
 
+/// Message for the state file writer task
+enum StateWrite {
+    /// Serialized queue snapshot to write
+    Snapshot(Vec<u8>),
+    /// Reply once every earlier snapshot has been written
+    Flush(oneshot::Sender<()>),
+}
+
+/// On-disk snapshot of the queue
+#[derive(Serialize, Deserialize, Default)]
+struct PersistedQueue {
+    /// Tests that held a slot, with the unix time they started
+    active: Vec<PersistedTest>,
+    /// Waiting agents, with the unix time they first requested a test
+    waiting: Vec<PersistedTest>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PersistedTest {
+    agent_id: String,
+    since: u64,
+    /// Whether any transfer stream had started
+    #[serde(default)]
+    transferring: bool,
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
 impl BandwidthTestManager {
     /// Create a new bandwidth test manager with configurable timeouts
+    ///
+    /// Only one test runs at a time until [`with_concurrency`](Self::with_concurrency)
+    /// is applied.
     pub fn new(
         test_timeout_seconds: u64,
         max_delay_seconds: u64,
@@ -45,8 +136,12 @@ impl BandwidthTestManager {
     ) -> Self {
         Self {
             state: Arc::new(RwLock::new(BandwidthTestState {
-                current_test: None,
+                active_tests: Vec::new(),
                 waiting_queue: Vec::new(),
+                max_concurrent: 1,
+                group_max_concurrent: 1,
+                agent_groups: HashMap::new(),
+                state_writer: None,
                 test_timeout: Duration::from_secs(test_timeout_seconds),
                 max_delay: Duration::from_secs(max_delay_seconds),
                 base_delay_seconds,
@@ -56,82 +151,221 @@ impl BandwidthTestManager {
         }
     }
 
+    /// Set the server-wide and per-group concurrency limits and the agent groups
+    ///
+    /// `groups` maps a group name (such as a site or uplink) to its agent IDs.
+    pub fn with_concurrency(
+        mut self,
+        max_concurrent: u32,
+        group_max_concurrent: u32,
+        groups: &HashMap<String, Vec<String>>,
+    ) -> Self {
+        {
+            let state = self.state_mut();
+            state.max_concurrent = max_concurrent.max(1) as usize;
+            state.group_max_concurrent = group_max_concurrent.max(1) as usize;
+            state.agent_groups = groups
+                .iter()
+                .flat_map(|(group, agents)| {
+                    agents
+                        .iter()
+                        .map(move |agent| (agent.clone(), group.clone()))
+                })
+                .collect();
+        }
+        self
+    }
+
+    /// Persist the queue to `path`, restoring any queue saved there earlier
+    ///
+    /// Tests that were transferring when the server stopped have lost their
+    /// connection, so those agents are put back at the front of the queue.
+    pub fn with_state_file(mut self, path: impl Into<PathBuf>) -> Self {
+        {
+            let state = self.state_mut();
+            let path = path.into();
+            match Self::load_queue(&path) {
+                Ok(Some(saved)) => state.restore(saved),
+                Ok(None) => {}
+                Err(e) => warn!(
+                    path = %path.display(),
+                    error = %e,
+                    "Failed to load bandwidth test queue, starting empty"
+                ),
+            }
+            state.state_writer = Some(spawn_state_writer(path));
+            state.persist();
+        }
+        self
+    }
+
+    fn state_mut(&mut self) -> &mut BandwidthTestState {
+        Arc::get_mut(&mut self.state)
+            .expect("bandwidth test manager is configured before it is shared")
+            .get_mut()
+    }
+
+    fn load_queue(path: &Path) -> anyhow::Result<Option<PersistedQueue>> {
+        match std::fs::read_to_string(path) {
+            Ok(contents) => Ok(Some(serde_json::from_str(&contents)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Wait until every queue change so far has been written to the state file
+    pub async fn flush_state(&self) {
+        let reply = {
+            let state = self.state.read().await;
+            let Some(writer) = &state.state_writer else {
+                return;
+            };
+            let (reply_tx, reply_rx) = oneshot::channel();
+            if writer.send(StateWrite::Flush(reply_tx)).is_err() {
+                return;
+            }
+            reply_rx
+        };
+        let _ = reply.await;
+    }
+
     /// Request to start a bandwidth test for the given agent
+    ///
+    /// `streams` is the number of parallel transfers the agent will open.
     pub async fn request_test(
         &self,
         agent_id: String,
         data_size_bytes: u64,
+        streams: u32,
     ) -> BandwidthTestResponse {
         let mut state = self.state.write().await;
 
-        // Clean up any expired tests first
-        self.cleanup_expired_tests(&mut state);
-
-        // Check if this agent is already in queue or running a test
-        if let Some((current_agent, _)) = &state.current_test {
-            if current_agent == &agent_id {
-                debug!("Agent {} already running bandwidth test", agent_id);
-                return BandwidthTestResponse {
-                    status: "success".to_string(),
-                    action: BandwidthTestAction::Proceed,
-                    delay_seconds: None,
-                    data_size_bytes: Some(data_size_bytes),
-                };
-            }
-        }
-
-        // Remove agent from waiting queue if already present
-        state.waiting_queue.retain(|(id, _)| id != &agent_id);
+        // Clean up any expired tests first, then fill freed slots
+        state.cleanup_expired_tests();
+        state.promote_waiting();
 
-        // If no test is currently running, start this one
-        if state.current_test.is_none() {
-            state.current_test = Some((agent_id.clone(), Instant::now()));
-            info!("Starting bandwidth test for agent {}", agent_id);
+        let proceed = BandwidthTestResponse {
+            status: "success".to_string(),
+            action: BandwidthTestAction::Proceed,
+            delay_seconds: None,
+            data_size_bytes: Some(data_size_bytes),
+        };
 
-            BandwidthTestResponse {
-                status: "success".to_string(),
-                action: BandwidthTestAction::Proceed,
-                delay_seconds: None,
-                data_size_bytes: Some(data_size_bytes),
+        // Check if this agent is already running a test
+        if let Some(test) = state
+            .active_tests
+            .iter_mut()
+            .find(|test| test.agent_id == agent_id)
+        {
+            debug!("Agent {} already running bandwidth test", agent_id);
+            // Tests auto-started from the queue learn the stream count here
+            if test.streams_started == 0 {
+                test.streams = streams.max(1);
             }
-        } else {
-            // Test is running, add to queue and suggest delay
-            let delay_seconds = self.calculate_delay(&state);
-            state.waiting_queue.push((agent_id.clone(), Instant::now()));
+            return proceed;
+        }
 
+        // A queued agent keeps its place and is told how long to wait
+        if let Some(position) = state
+            .waiting_queue
+            .iter()
+            .position(|entry| entry.agent_id == agent_id)
+        {
+            state.waiting_queue[position].last_seen = Instant::now();
+            let delay_seconds = state.calculate_delay(position);
+            debug!(
+                "Agent {} still queued for bandwidth test at position {}",
+                agent_id,
+                position + 1
+            );
+            return Self::delay_response(delay_seconds);
+        }
+
+        let group = state.group_of(&agent_id);
+        // Agents still waiting after promotion are blocked by their group,
+        // so a free slot may go to an agent from another group
+        if state.active_tests.len() < state.max_concurrent && state.group_has_capacity(&group) {
             info!(
-                "Bandwidth test already running, queuing agent {} with {}s delay",
-                agent_id, delay_seconds
+                "Starting bandwidth test for agent {} in group {} ({} streams)",
+                agent_id, group, streams
             );
+            state
+                .active_tests
+                .push(ActiveTest::new(agent_id, group, streams));
+            state.persist();
+            return proceed;
+        }
 
-            BandwidthTestResponse {
-                status: "success".to_string(),
-                action: BandwidthTestAction::Delay,
-                delay_seconds: Some(delay_seconds),
-                data_size_bytes: None,
-            }
+        // No slot free for this agent's group, join the back of the queue
+        let position = state.waiting_queue.len();
+        state.waiting_queue.push(QueueEntry {
+            agent_id: agent_id.clone(),
+            first_requested: SystemTime::now(),
+            last_seen: Instant::now(),
+        });
+        let delay_seconds = state.calculate_delay(position);
+        state.persist();
+
+        info!(
+            "No bandwidth test slot available, queuing agent {} with {}s delay",
+            agent_id, delay_seconds
+        );
+
+        Self::delay_response(delay_seconds)
+    }
+
+    fn delay_response(delay_seconds: u32) -> BandwidthTestResponse {
+        BandwidthTestResponse {
+            status: "success".to_string(),
+            action: BandwidthTestAction::Delay,
+            delay_seconds: Some(delay_seconds),
+            data_size_bytes: None,
         }
     }
 
-    /// Mark a test as completed for the given agent
-    pub async fn complete_test(&self, agent_id: &str) {
+    /// Mark a test as completed for the given agent, regardless of open streams
+    ///
+    /// Returns true if the agent held a slot.
+    pub async fn complete_test(&self, agent_id: &str) -> bool {
         let mut state = self.state.write().await;
+        state.complete(agent_id)
+    }
 
-        if let Some((current_agent, _)) = &state.current_test {
-            if current_agent == agent_id {
-                state.current_test = None;
-                info!("Completed bandwidth test for agent {}", agent_id);
-
-                // Start next test from queue if available
-                if let Some((next_agent, _)) = state.waiting_queue.first().cloned() {
-                    state.waiting_queue.remove(0);
-                    state.current_test = Some((next_agent.clone(), Instant::now()));
-                    info!(
-                        "Auto-starting queued bandwidth test for agent {}",
-                        next_agent
-                    );
-                }
+    /// Register the start of a transfer stream for the given agent
+    ///
+    /// Returns the number of streams in the test, or None if the agent does
+    /// not hold a slot or has already started every announced stream.
+    pub async fn start_stream(&self, agent_id: &str) -> Option<u32> {
+        let mut state = self.state.write().await;
+        let test = state
+            .active_tests
+            .iter_mut()
+            .find(|test| test.agent_id == agent_id)?;
+        if test.streams_started >= test.streams {
+            return None;
+        }
+        test.streams_started += 1;
+        let streams = test.streams;
+        state.persist();
+        Some(streams)
+    }
+
+    /// Register the end of a transfer stream, completing the test after the last one
+    pub async fn finish_stream(&self, agent_id: &str) {
+        let mut state = self.state.write().await;
+        let finished = match state
+            .active_tests
+            .iter_mut()
+            .find(|test| test.agent_id == agent_id)
+        {
+            Some(test) => {
+                test.streams_finished += 1;
+                test.streams_finished >= test.streams
             }
+            None => false,
+        };
+        if finished {
+            state.complete(agent_id);
         }
     }
 
@@ -139,67 +373,272 @@ impl BandwidthTestManager {
     pub async fn get_status(&self) -> BandwidthTestStatus {
         let state = self.state.read().await;
         BandwidthTestStatus {
-            current_test: state
-                .current_test
-                .as_ref()
-                .map(|(agent, start)| (agent.clone(), start.elapsed().as_secs())),
+            active_tests: state
+                .active_tests
+                .iter()
+                .map(|test| (test.agent_id.clone(), test.start_time.elapsed().as_secs()))
+                .collect(),
+            queued_agents: state
+                .waiting_queue
+                .iter()
+                .map(|entry| entry.agent_id.clone())
+                .collect(),
+        }
+    }
+}
+
+impl BandwidthTestState {
+    /// Group name used for concurrency limits; ungrouped agents stand alone
+    fn group_of(&self, agent_id: &str) -> String {
+        self.agent_groups
+            .get(agent_id)
+            .cloned()
+            .unwrap_or_else(|| format!("agent:{}", agent_id))
+    }
+
+    fn group_has_capacity(&self, group: &str) -> bool {
+        self.active_tests
+            .iter()
+            .filter(|test| test.group == group)
+            .count()
+            < self.group_max_concurrent
+    }
+
+    /// Release the agent's slot and hand free slots to waiting agents
+    fn complete(&mut self, agent_id: &str) -> bool {
+        let before = self.active_tests.len();
+        self.active_tests.retain(|test| test.agent_id != agent_id);
+        if self.active_tests.len() == before {
+            return false;
+        }
+        info!("Completed bandwidth test for agent {}", agent_id);
+        self.persist();
+        self.promote_waiting();
+        true
+    }
+
+    /// Start tests for waiting agents while slots are free
+    ///
+    /// Agents are taken in request order; an agent whose group is at capacity
+    /// keeps its place while agents behind it from other groups go ahead. The
+    /// stream count is unknown until the agent requests the test again.
+    fn promote_waiting(&mut self) {
+        let initial_len = self.waiting_queue.len();
+        let mut index = 0;
+        while index < self.waiting_queue.len() && self.active_tests.len() < self.max_concurrent {
+            let group = self.group_of(&self.waiting_queue[index].agent_id);
+            if self.group_has_capacity(&group) {
+                let entry = self.waiting_queue.remove(index);
+                info!(
+                    "Auto-starting queued bandwidth test for agent {}",
+                    entry.agent_id
+                );
+                self.active_tests
+                    .push(ActiveTest::new(entry.agent_id, group, 1));
+            } else {
+                index += 1;
+            }
+        }
+        if self.waiting_queue.len() < initial_len {
+            self.persist();
         }
     }
 
-    /// Remove timed-out current test and expired queue entries
+    /// Remove timed-out tests and abandoned queue entries
     ///
-    /// Removes current test if it exceeds timeout, and removes waiting queue
-    /// entries older than max_delay.
-    fn cleanup_expired_tests(&self, state: &mut BandwidthTestState) {
-        if let Some((agent, start_time)) = &state.current_test {
-            if start_time.elapsed() > state.test_timeout {
+    /// Removes tests that exceed the timeout, and queue entries whose agent
+    /// has not asked again within twice the maximum suggested delay.
+    fn cleanup_expired_tests(&mut self) {
+        let timeout = self.test_timeout;
+        let initial_active = self.active_tests.len();
+        self.active_tests.retain(|test| {
+            let expired = test.start_time.elapsed() > timeout;
+            if expired {
                 warn!(
                     "Bandwidth test for agent {} timed out after {:?}, removing",
-                    agent, state.test_timeout
+                    test.agent_id, timeout
                 );
-                state.current_test = None;
             }
-        }
+            !expired
+        });
 
-        // Remove old entries from waiting queue (older than max_delay)
-        let cutoff = Instant::now() - state.max_delay;
-        let initial_len = state.waiting_queue.len();
-        state.waiting_queue.retain(|(_, time)| *time > cutoff);
+        // Agents retry after at most max_delay; allow the same again for jitter
+        let stale_after = self.max_delay * 2;
+        let initial_len = self.waiting_queue.len();
+        self.waiting_queue
+            .retain(|entry| entry.last_seen.elapsed() <= stale_after);
 
-        if state.waiting_queue.len() < initial_len {
+        if self.waiting_queue.len() < initial_len {
             debug!(
                 "Removed {} expired entries from bandwidth test queue",
-                initial_len - state.waiting_queue.len()
+                initial_len - self.waiting_queue.len()
             );
         }
+        if self.active_tests.len() < initial_active || self.waiting_queue.len() < initial_len {
+            self.persist();
+        }
     }
 
-    /// Calculate suggested delay for agent based on queue state
+    /// Calculate suggested delay for the agent at the given queue position
     ///
-    /// Returns delay in seconds based on whether a test is running and the
+    /// Returns delay in seconds based on whether tests are running and the
     /// agent's position in queue, capped at max_delay.
-    fn calculate_delay(&self, state: &BandwidthTestState) -> u32 {
-        let base_delay = if state.current_test.is_some() {
-            // If test is running, suggest waiting for it to complete plus buffer
-            state.current_test_delay_seconds as u32
+    fn calculate_delay(&self, position: usize) -> u32 {
+        let base_delay = if self.active_tests.is_empty() {
+            self.base_delay_seconds as u32
         } else {
-            state.base_delay_seconds as u32
+            // If tests are running, suggest waiting for one to complete plus buffer
+            self.current_test_delay_seconds as u32
         };
 
-        // Add delay based on queue position
-        let queue_delay =
-            state.waiting_queue.len() as u32 * state.position_multiplier_seconds as u32;
+        // Agents ahead in the queue share the available slots
+        let slots = self.max_concurrent as u32;
+        let queue_delay = (position as u32 / slots) * self.position_multiplier_seconds as u32;
 
         let total_delay = base_delay + queue_delay;
 
         // Cap at max_delay
-        std::cmp::min(total_delay, state.max_delay.as_secs() as u32)
+        std::cmp::min(total_delay, self.max_delay.as_secs() as u32)
+    }
+
+    /// Rebuild the queue from a snapshot taken before a restart
+    fn restore(&mut self, saved: PersistedQueue) {
+        let now = SystemTime::now();
+        let mut requeued = Vec::new();
+
+        for test in saved.active {
+            if test.transferring {
+                // The transfer died with the old process; retry first
+                requeued.push(test);
+                continue;
+            }
+            let elapsed = now
+                .duration_since(UNIX_EPOCH + Duration::from_secs(test.since))
+                .unwrap_or_default();
+            if elapsed > self.test_timeout {
+                continue;
+            }
+            let group = self.group_of(&test.agent_id);
+            let mut active = ActiveTest::new(test.agent_id, group, 1);
+            active.start_time = Instant::now()
+                .checked_sub(elapsed)
+                .unwrap_or(active.start_time);
+            self.active_tests.push(active);
+        }
+
+        for test in requeued.into_iter().chain(saved.waiting) {
+            if self
+                .waiting_queue
+                .iter()
+                .any(|entry| entry.agent_id == test.agent_id)
+            {
+                continue;
+            }
+            self.waiting_queue.push(QueueEntry {
+                agent_id: test.agent_id,
+                first_requested: UNIX_EPOCH + Duration::from_secs(test.since),
+                // Give every agent a full retry window after the restart
+                last_seen: Instant::now(),
+            });
+        }
+
+        info!(
+            "Restored bandwidth test queue: {} active, {} waiting",
+            self.active_tests.len(),
+            self.waiting_queue.len()
+        );
+        self.promote_waiting();
+    }
+
+    /// Hand a snapshot of the queue to the state file writer, if one is configured
+    fn persist(&self) {
+        let Some(writer) = &self.state_writer else {
+            return;
+        };
+
+        let snapshot = PersistedQueue {
+            active: self
+                .active_tests
+                .iter()
+                .map(|test| PersistedTest {
+                    agent_id: test.agent_id.clone(),
+                    since: unix_seconds(SystemTime::now() - test.start_time.elapsed()),
+                    transferring: test.streams_started > 0,
+                })
+                .collect(),
+            waiting: self
+                .waiting_queue
+                .iter()
+                .map(|entry| PersistedTest {
+                    agent_id: entry.agent_id.clone(),
+                    since: unix_seconds(entry.first_requested),
+                    transferring: false,
+                })
+                .collect(),
+        };
+
+        match serde_json::to_vec_pretty(&snapshot) {
+            Ok(json) => {
+                let _ = writer.send(StateWrite::Snapshot(json));
+            }
+            Err(e) => warn!(error = %e, "Failed to serialize bandwidth test queue"),
+        }
     }
 }
 
+/// Spawn the task that writes queue snapshots to `path`
+///
+/// Snapshots are written in order; when several are waiting only the latest
+/// is written. The task ends once the manager is dropped.
+fn spawn_state_writer(path: PathBuf) -> mpsc::UnboundedSender<StateWrite> {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        while let Some(message) = receiver.recv().await {
+            let mut latest = None;
+            let mut flushes = Vec::new();
+            let mut next = Some(message);
+            while let Some(message) = next {
+                match message {
+                    StateWrite::Snapshot(json) => latest = Some(json),
+                    StateWrite::Flush(reply) => flushes.push(reply),
+                }
+                next = receiver.try_recv().ok();
+            }
+
+            if let Some(json) = latest {
+                let file_path = path.clone();
+                let result =
+                    tokio::task::spawn_blocking(move || write_state_file(&file_path, &json))
+                        .await
+                        .unwrap_or_else(|e| Err(std::io::Error::other(e)));
+                if let Err(e) = result {
+                    warn!(
+                        path = %path.display(),
+                        error = %e,
+                        "Failed to persist bandwidth test queue"
+                    );
+                }
+            }
+            for reply in flushes {
+                let _ = reply.send(());
+            }
+        }
+    });
+    sender
+}
+
+/// Write the state file through a temporary file so a crash never leaves a partial file
+fn write_state_file(path: &Path, json: &[u8]) -> std::io::Result<()> {
+    let tmp_path = path.with_extension("json.tmp");
+    std::fs::write(&tmp_path, json)?;
+    std::fs::rename(&tmp_path, path)
+}
+
 /// Status information for bandwidth tests
 #[derive(Debug)]
 pub struct BandwidthTestStatus {
-    /// Current running test (agent_id, elapsed_seconds)
-    pub current_test: Option<(String, u64)>,
+    /// Running tests (agent_id, elapsed_seconds)
+    pub active_tests: Vec<(String, u64)>,
+    /// Agents waiting for a slot, in queue order
+    pub queued_agents: Vec<String>,
 }
@@ -166,6 +166,15 @@ impl ConfigManager {
             "  bandwidth_max_delay_seconds: {}",
             server_config.bandwidth_max_delay_seconds
         );
+        debug!(
+            "  bandwidth_max_concurrent_tests: {}",
+            server_config.bandwidth_max_concurrent_tests
+        );
+        debug!(
+            "  bandwidth_group_max_concurrent: {}",
+            server_config.bandwidth_group_max_concurrent
+        );
+        debug!("  bandwidth_groups: {:?}", server_config.bandwidth_groups);
         debug!(
             "  initial_cleanup_delay_seconds: {}",
             server_config.initial_cleanup_delay_seconds
@@ -12,19 +12,32 @@
 // Note: All metric types are always enabled on the server to ensure it can
 // receive metrics from agents compiled with any combination of features.
 pub mod db_agent_health;
+pub mod db_agent_inventory;
 mod db_bandwidth;
 mod db_dns;
+mod db_execution;
 mod db_http;
 mod db_http_content;
+mod db_labels;
+mod db_latency;
+mod db_ntp;
 mod db_ping;
+mod db_rollup;
 mod db_snmp;
+mod db_snmp_trap;
 mod db_sql;
 mod db_tcp;
+mod db_tcp_sweep;
 mod db_tls;
+mod db_twamp;
+mod db_udp;
 
 use anyhow::{Context, Result};
+use db_agent_inventory::{AgentInventory, InventoryChange};
 use rusqlite::{params, Connection};
-use shared::metrics::{AggregatedMetricData, AggregatedMetrics};
+use shared::api::HostFacts;
+use shared::metrics::{AggregatedMetricData, AggregatedMetrics, MetricData, RawMetricData};
+use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 use tracing::{debug, info, warn};
@@ -32,6 +45,43 @@ use tracing::{debug, info, warn};
 /// The default name for the server's database file.
 const DATABASE_FILE: &str = "server_metrics.db";
 
+/// Columns recording how a task ran during an aggregation period: in a maintenance
+/// window, adaptively rescheduled, suppressed by a down prerequisite, and how long
+/// its runs waited for a concurrency slot
+const ANNOTATION_COLUMNS: [&str; 5] = [
+    "maintenance BOOLEAN NOT NULL DEFAULT 0",
+    "adaptive BOOLEAN NOT NULL DEFAULT 0",
+    "upstream_down BOOLEAN NOT NULL DEFAULT 0",
+    "avg_queue_wait_ms REAL",
+    "max_queue_wait_ms REAL",
+];
+
+/// Add columns to a table created before they existed (migration)
+fn add_columns(conn: &Connection, table: &str, columns: &[&str]) {
+    // SQLite doesn't support IF NOT EXISTS for ALTER TABLE, so we ignore errors
+    for column in columns {
+        let _ = conn.execute(&format!("ALTER TABLE {} ADD COLUMN {}", table, column), []);
+    }
+}
+
+/// Aggregated metrics tables that are rolled up into 5-minute, hourly and daily tiers,
+/// with how each of their columns is combined
+const ROLLUP_TABLES: [(&str, &[(&str, db_rollup::Rollup)]); 13] = [
+    ("agg_metric_ping", db_ping::ROLLUP_COLUMNS),
+    ("agg_metric_tcp", db_tcp::ROLLUP_COLUMNS),
+    ("agg_metric_http", db_http::ROLLUP_COLUMNS),
+    ("agg_metric_tls", db_tls::ROLLUP_COLUMNS),
+    ("agg_metric_http_content", db_http_content::ROLLUP_COLUMNS),
+    ("agg_metric_dns", db_dns::ROLLUP_COLUMNS),
+    ("agg_metric_bandwidth", db_bandwidth::ROLLUP_COLUMNS),
+    ("agg_metric_udp_probe", db_udp::ROLLUP_COLUMNS),
+    ("agg_metric_twamp", db_twamp::ROLLUP_COLUMNS),
+    ("agg_metric_tcp_sweep", db_tcp_sweep::ROLLUP_COLUMNS),
+    ("agg_metric_ntp", db_ntp::ROLLUP_COLUMNS),
+    ("agg_metric_sql_query", db_sql::ROLLUP_COLUMNS),
+    ("agg_metric_snmp", db_snmp::ROLLUP_COLUMNS),
+];
+
 /// Manages the SQLite database for the server.
 /// This struct encapsulates the database connection and all related operations,
 /// providing a clean, high-level API to the rest of the server application.
@@ -81,6 +131,7 @@ impl ServerDatabase {
                 last_config_checksum TEXT,
                 total_metrics_received INTEGER DEFAULT 0,
                 agent_version TEXT,
+                clock_offset_seconds INTEGER,
                 created_at INTEGER DEFAULT (strftime('%s', 'now'))
             )
             "#,
@@ -88,6 +139,13 @@ impl ServerDatabase {
         )
         .context("Failed to create agents table")?;
 
+        // Databases created before clock offset tracking lack the column.
+        // SQLite doesn't support IF NOT EXISTS for ALTER TABLE, so we ignore errors
+        let _ = conn.execute(
+            "ALTER TABLE agents ADD COLUMN clock_offset_seconds INTEGER",
+            [],
+        );
+
         // Create task-specific aggregated metrics tables using submodules
         db_ping::create_table(conn)?;
         db_tcp::create_table(conn)?;
@@ -96,12 +154,42 @@ impl ServerDatabase {
         db_http_content::create_table(conn)?;
         db_dns::create_table(conn)?;
         db_bandwidth::create_table(conn)?;
+        db_udp::create_table(conn)?;
+        db_twamp::create_table(conn)?;
+        db_tcp_sweep::create_table(conn)?;
+        db_ntp::create_table(conn)?;
         db_sql::create_table(conn)?;
         db_snmp::create_table(conn)?;
+        db_snmp_trap::create_table(conn)?;
+        db_execution::create_table(conn)?;
+        db_labels::create_table(conn)?;
+
+        // Create raw metrics tables for tasks uploading individual samples
+        db_ping::create_raw_table(conn)?;
+        db_tcp::create_raw_table(conn)?;
+        db_http::create_raw_table(conn)?;
+        db_tls::create_raw_table(conn)?;
+        db_http_content::create_raw_table(conn)?;
+        db_dns::create_raw_table(conn)?;
+        db_bandwidth::create_raw_table(conn)?;
+        db_udp::create_raw_table(conn)?;
+        db_twamp::create_raw_table(conn)?;
+        db_tcp_sweep::create_raw_table(conn)?;
+        db_ntp::create_raw_table(conn)?;
+        db_sql::create_raw_table(conn)?;
+        db_snmp::create_raw_table(conn)?;
+
+        // Create rollup tier tables (after the base tables they copy)
+        for (table, _) in ROLLUP_TABLES {
+            db_rollup::create_tables(conn, table)?;
+        }
 
         // Create agent health checks table
         db_agent_health::create_table(conn)?;
 
+        // Create agent inventory tables (labels and host facts with history)
+        db_agent_inventory::create_tables(conn)?;
+
         // The `config_errors` table is used to log any time an agent reports
         // a problem with its configuration. This is useful for debugging.
         conn.execute(
@@ -176,11 +264,15 @@ impl ServerDatabase {
 
     /// Registers a new agent or updates the status of an existing one.
     /// This is an "upsert" operation: it updates if the agent exists, or inserts if not.
+    ///
+    /// `clock_offset_seconds` is the agent's clock minus the server's at the time of
+    /// the push, or None if the agent's timestamp could not be parsed.
     pub async fn upsert_agent(
         &mut self,
         agent_id: &str,
         config_checksum: &str,
         agent_version: Option<&str>,
+        clock_offset_seconds: Option<i64>,
     ) -> Result<()> {
         debug!("Upserting agent: {}", agent_id);
 
@@ -192,20 +284,20 @@ impl ServerDatabase {
         let updated_rows = conn.execute(
             r#"
             UPDATE agents
-            SET last_seen = ?1, last_config_checksum = ?2, agent_version = ?3, total_metrics_received = total_metrics_received + 1
-            WHERE agent_id = ?4
+            SET last_seen = ?1, last_config_checksum = ?2, agent_version = ?3, clock_offset_seconds = ?4, total_metrics_received = total_metrics_received + 1
+            WHERE agent_id = ?5
             "#,
-            params![current_time, config_checksum, agent_version, agent_id],
+            params![current_time, config_checksum, agent_version, clock_offset_seconds, agent_id],
         )?;
 
         // If `execute` returns 0, no rows were updated, which means the agent is new.
         if updated_rows == 0 {
             conn.execute(
                 r#"
-                INSERT INTO agents (agent_id, first_seen, last_seen, last_config_checksum, agent_version, total_metrics_received)
-                VALUES (?1, ?2, ?3, ?4, ?5, 1)
+                INSERT INTO agents (agent_id, first_seen, last_seen, last_config_checksum, agent_version, clock_offset_seconds, total_metrics_received)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, 1)
                 "#,
-                params![agent_id, current_time, current_time, config_checksum, agent_version],
+                params![agent_id, current_time, current_time, config_checksum, agent_version, clock_offset_seconds],
             ).with_context(|| format!("Failed to insert new agent: {}", agent_id))?;
 
             info!("Registered new agent: {}", agent_id);
@@ -216,20 +308,96 @@ impl ServerDatabase {
         Ok(())
     }
 
-    /// Stores a batch of aggregated metrics from an agent into task-specific tables.
-    /// This operation is performed within a database transaction to ensure that
-    /// all metrics in the batch are inserted atomically.
+    /// Stores the labels and host facts an agent reported with a metrics push.
+    ///
+    /// The inventory is only written, and a history row added, when it differs
+    /// from the stored one. The agent must have been upserted first.
+    pub async fn update_agent_inventory(
+        &mut self,
+        agent_id: &str,
+        labels: &BTreeMap<String, String>,
+        host_facts: Option<&HostFacts>,
+    ) -> Result<InventoryChange> {
+        let conn = self.get_connection()?;
+        db_agent_inventory::update_inventory(
+            conn,
+            agent_id,
+            labels,
+            host_facts,
+            current_timestamp() as i64,
+        )
+        .with_context(|| format!("Failed to update inventory of agent {}", agent_id))
+    }
+
+    /// Retrieves the current labels and host facts of an agent.
+    #[allow(dead_code)]
+    pub async fn get_agent_inventory(&mut self, agent_id: &str) -> Result<Option<AgentInventory>> {
+        let conn = self.get_connection()?;
+        db_agent_inventory::load_inventory(conn, agent_id)
+    }
+
+    /// Retrieves every recorded change of an agent's labels and host facts, oldest first.
+    #[allow(dead_code)]
+    pub async fn get_agent_inventory_history(
+        &mut self,
+        agent_id: &str,
+    ) -> Result<Vec<AgentInventory>> {
+        let conn = self.get_connection()?;
+        db_agent_inventory::load_history(conn, agent_id)
+    }
+
+    /// Retrieves the periods of an agent starting in `[from, to)` in which every
+    /// due run of a task was suppressed by a down prerequisite, as
+    /// `(task_name, period_start)` pairs in the agent's clock.
+    pub async fn get_suppressed_periods(
+        &mut self,
+        agent_id: &str,
+        from: i64,
+        to: i64,
+    ) -> Result<Vec<(String, i64)>> {
+        let conn = self.get_connection()?;
+        db_execution::load_suppressed_periods(conn, agent_id, from, to)
+    }
+
+    /// Stores a push of aggregated and raw metrics from an agent into the
+    /// task-specific and raw metrics tables.
+    /// This operation is performed within a single database transaction so that
+    /// a push is stored completely or not at all. Raw samples carry the agent's
+    /// row ID, and a sample delivered again is ignored.
     pub async fn store_metrics(
         &mut self,
         agent_id: &str,
         metrics: &[AggregatedMetrics],
+        raw_metrics: &[MetricData],
     ) -> Result<()> {
-        debug!("Storing {} metrics from agent: {}", metrics.len(), agent_id);
+        debug!(
+            "Storing {} metrics and {} raw metrics from agent: {}",
+            metrics.len(),
+            raw_metrics.len(),
+            agent_id
+        );
 
         let conn = self.get_connection()?;
         let tx = conn.transaction()?;
 
+        // Labels of each task's latest period in the batch, as (period end, labels),
+        // indexed after the metrics are stored
+        let mut task_labels: BTreeMap<&str, (u64, &BTreeMap<String, String>)> = BTreeMap::new();
+
         for metric in metrics {
+            // Trap events are not produced by a task and carry no labels
+            if !matches!(
+                metric.data,
+                AggregatedMetricData::SnmpTrap(_) | AggregatedMetricData::Unknown
+            ) {
+                let latest = task_labels
+                    .entry(metric.task_name.as_str())
+                    .or_insert((metric.period_end, &metric.labels));
+                if metric.period_end >= latest.0 {
+                    *latest = (metric.period_end, &metric.labels);
+                }
+            }
+
             match &metric.data {
                 AggregatedMetricData::Ping(ping_data) => {
                     db_ping::store_metric(&tx, agent_id, metric, ping_data)?;
@@ -252,12 +420,30 @@ impl ServerDatabase {
                 AggregatedMetricData::Bandwidth(bandwidth_data) => {
                     db_bandwidth::store_metric(&tx, agent_id, metric, bandwidth_data)?;
                 }
+                AggregatedMetricData::UdpProbe(udp_data) => {
+                    db_udp::store_metric(&tx, agent_id, metric, udp_data)?;
+                }
+                AggregatedMetricData::Twamp(twamp_data) => {
+                    db_twamp::store_metric(&tx, agent_id, metric, twamp_data)?;
+                }
+                AggregatedMetricData::TcpSweep(sweep_data) => {
+                    db_tcp_sweep::store_metric(&tx, agent_id, metric, sweep_data)?;
+                }
+                AggregatedMetricData::Ntp(ntp_data) => {
+                    db_ntp::store_metric(&tx, agent_id, metric, ntp_data)?;
+                }
                 AggregatedMetricData::SqlQuery(sql_data) => {
                     db_sql::store_metric(&tx, agent_id, metric, sql_data)?;
                 }
                 AggregatedMetricData::Snmp(snmp_data) => {
                     db_snmp::store_metric(&tx, agent_id, metric, snmp_data)?;
                 }
+                AggregatedMetricData::SnmpTrap(trap_data) => {
+                    db_snmp_trap::store_metric(&tx, agent_id, metric, trap_data)?;
+                }
+                AggregatedMetricData::Execution(execution) => {
+                    db_execution::store_metric(&tx, agent_id, metric, execution)?;
+                }
                 AggregatedMetricData::Unknown => {
                     warn!(
                         "Received unknown metric type from agent {}, skipping",
@@ -267,10 +453,77 @@ impl ServerDatabase {
             }
         }
 
+        let updated_at = current_timestamp() as i64;
+        for (task_name, (period_end, labels)) in task_labels {
+            db_labels::update_task_labels(
+                &tx,
+                agent_id,
+                task_name,
+                labels,
+                period_end as i64,
+                updated_at,
+            )?;
+        }
+
+        for metric in raw_metrics {
+            match &metric.data {
+                RawMetricData::Ping(data) => {
+                    db_ping::store_raw_metric(&tx, agent_id, metric, data)?;
+                }
+                RawMetricData::Tcp(data) => {
+                    db_tcp::store_raw_metric(&tx, agent_id, metric, data)?;
+                }
+                RawMetricData::HttpGet(data) => {
+                    db_http::store_raw_metric(&tx, agent_id, metric, data)?;
+                }
+                RawMetricData::TlsHandshake(data) => {
+                    db_tls::store_raw_metric(&tx, agent_id, metric, data)?;
+                }
+                RawMetricData::HttpContent(data) => {
+                    db_http_content::store_raw_metric(&tx, agent_id, metric, data)?;
+                }
+                RawMetricData::DnsQuery(data) => {
+                    db_dns::store_raw_metric(&tx, agent_id, metric, data)?;
+                }
+                RawMetricData::Bandwidth(data) => {
+                    db_bandwidth::store_raw_metric(&tx, agent_id, metric, data)?;
+                }
+                RawMetricData::UdpProbe(data) => {
+                    db_udp::store_raw_metric(&tx, agent_id, metric, data)?;
+                }
+                RawMetricData::Twamp(data) => {
+                    db_twamp::store_raw_metric(&tx, agent_id, metric, data)?;
+                }
+                RawMetricData::TcpSweep(data) => {
+                    db_tcp_sweep::store_raw_metric(&tx, agent_id, metric, data)?;
+                }
+                RawMetricData::Ntp(data) => {
+                    db_ntp::store_raw_metric(&tx, agent_id, metric, data)?;
+                }
+                RawMetricData::SqlQuery(data) => {
+                    db_sql::store_raw_metric(&tx, agent_id, metric, data)?;
+                }
+                RawMetricData::Snmp(data) => {
+                    db_snmp::store_raw_metric(&tx, agent_id, metric, data)?;
+                }
+                RawMetricData::Unknown => {
+                    warn!(
+                        "Received unknown raw metric type from agent {}, skipping",
+                        agent_id
+                    );
+                }
+            }
+        }
+
         tx.commit()
             .context("Failed to commit metrics transaction")?;
 
-        debug!("Stored {} metrics for agent: {}", metrics.len(), agent_id);
+        debug!(
+            "Stored {} metrics and {} raw metrics for agent: {}",
+            metrics.len(),
+            raw_metrics.len(),
+            agent_id
+        );
         Ok(())
     }
 
@@ -313,7 +566,7 @@ impl ServerDatabase {
 
         let mut stmt = conn.prepare(
             r#"
-            SELECT agent_id, first_seen, last_seen, last_config_checksum, total_metrics_received, agent_version
+            SELECT agent_id, first_seen, last_seen, last_config_checksum, total_metrics_received, agent_version, clock_offset_seconds
             FROM agents
             WHERE agent_id = ?1
             "#,
@@ -328,6 +581,7 @@ impl ServerDatabase {
                 last_config_checksum: row.get(3)?,
                 total_metrics_received: row.get::<_, i64>(4)? as u64,
                 agent_version: row.get(5)?,
+                clock_offset_seconds: row.get(6)?,
             })
         });
 
@@ -348,7 +602,7 @@ impl ServerDatabase {
 
         let mut stmt = conn.prepare(
             r#"
-            SELECT agent_id, first_seen, last_seen, last_config_checksum, total_metrics_received, agent_version
+            SELECT agent_id, first_seen, last_seen, last_config_checksum, total_metrics_received, agent_version, clock_offset_seconds
             FROM agents
             ORDER BY last_seen DESC
             "#,
@@ -362,6 +616,7 @@ impl ServerDatabase {
                 last_config_checksum: row.get(3)?,
                 total_metrics_received: row.get::<_, i64>(4)? as u64,
                 agent_version: row.get(5)?,
+                clock_offset_seconds: row.get(6)?,
             })
         })?;
 
@@ -398,9 +653,15 @@ impl ServerDatabase {
         let agg_http_content_deleted = db_http_content::cleanup_old_data(conn, cutoff_time as i64)?;
         let agg_dns_deleted = db_dns::cleanup_old_data(conn, cutoff_time as i64)?;
         let agg_bandwidth_deleted = db_bandwidth::cleanup_old_data(conn, cutoff_time as i64)?;
+        let agg_udp_probe_deleted = db_udp::cleanup_old_data(conn, cutoff_time as i64)?;
+        let agg_twamp_deleted = db_twamp::cleanup_old_data(conn, cutoff_time as i64)?;
+        let agg_tcp_sweep_deleted = db_tcp_sweep::cleanup_old_data(conn, cutoff_time as i64)?;
+        let agg_ntp_deleted = db_ntp::cleanup_old_data(conn, cutoff_time as i64)?;
 
         let agg_sql_query_deleted = db_sql::cleanup_old_data(conn, cutoff_time as i64)?;
         let agg_snmp_deleted = db_snmp::cleanup_old_data(conn, cutoff_time as i64)?;
+        let snmp_trap_deleted = db_snmp_trap::cleanup_old_data(conn, cutoff_time as i64)?;
+        let execution_deleted = db_execution::cleanup_old_data(conn, cutoff_time as i64)?;
 
         let total_metrics_deleted = agg_ping_deleted
             + agg_tcp_deleted
@@ -409,7 +670,13 @@ impl ServerDatabase {
             + agg_http_content_deleted
             + agg_dns_deleted
             + agg_bandwidth_deleted
+            + agg_udp_probe_deleted
+            + agg_twamp_deleted
+            + agg_tcp_sweep_deleted
+            + agg_ntp_deleted
             + agg_snmp_deleted
+            + snmp_trap_deleted
+            + execution_deleted
             + agg_sql_query_deleted;
 
         // Delete old config errors.
@@ -418,6 +685,14 @@ impl ServerDatabase {
             params![cutoff_time as i64],
         )?;
 
+        // Delete labels of tasks that stopped reporting.
+        let labels_deleted = db_labels::cleanup_old_data(conn, cutoff_time as i64)?;
+        debug!("Deleted {} stale task labels", labels_deleted);
+
+        // Delete old inventory history and the inventory of agents about to be deleted.
+        let inventory_deleted = db_agent_inventory::cleanup_old_data(conn, cutoff_time as i64)?;
+        debug!("Deleted {} agent inventory rows", inventory_deleted);
+
         // Optionally, delete records of agents that have been inactive for a long time.
         let agents_deleted = conn.execute(
             "DELETE FROM agents WHERE last_seen < ?1",
@@ -441,6 +716,106 @@ impl ServerDatabase {
         Ok(())
     }
 
+    /// Deletes raw metrics older than the raw data retention period.
+    /// Raw samples are kept for a shorter time than aggregated metrics.
+    pub async fn cleanup_old_raw_data(&mut self, raw_retention_days: u32) -> Result<usize> {
+        // Use saturating arithmetic to prevent overflow with large retention values
+        let cutoff_time = current_timestamp()
+            .saturating_sub((raw_retention_days as u64).saturating_mul(86400))
+            as i64;
+        let conn = self.get_connection()?;
+
+        let mut deleted = 0;
+        deleted += db_ping::cleanup_old_raw_data(conn, cutoff_time)?;
+        deleted += db_tcp::cleanup_old_raw_data(conn, cutoff_time)?;
+        deleted += db_http::cleanup_old_raw_data(conn, cutoff_time)?;
+        deleted += db_tls::cleanup_old_raw_data(conn, cutoff_time)?;
+        deleted += db_http_content::cleanup_old_raw_data(conn, cutoff_time)?;
+        deleted += db_dns::cleanup_old_raw_data(conn, cutoff_time)?;
+        deleted += db_bandwidth::cleanup_old_raw_data(conn, cutoff_time)?;
+        deleted += db_udp::cleanup_old_raw_data(conn, cutoff_time)?;
+        deleted += db_twamp::cleanup_old_raw_data(conn, cutoff_time)?;
+        deleted += db_tcp_sweep::cleanup_old_raw_data(conn, cutoff_time)?;
+        deleted += db_ntp::cleanup_old_raw_data(conn, cutoff_time)?;
+        deleted += db_sql::cleanup_old_raw_data(conn, cutoff_time)?;
+        deleted += db_snmp::cleanup_old_raw_data(conn, cutoff_time)?;
+
+        info!("Raw data cleanup complete: {} raw metrics deleted", deleted);
+        Ok(deleted)
+    }
+
+    /// Rolls aggregated metrics up into the 5-minute, hourly and daily tiers.
+    ///
+    /// Each tier period starting within the last `lookback_seconds` is recomputed,
+    /// so metrics that arrive late (e.g. from an agent that was offline) are included.
+    /// Tiers that are still empty are backfilled from all existing rows. Periods that
+    /// the retention of the table a tier is computed from may have partly purged are
+    /// never recomputed, so complete tier rows are not replaced with partial ones.
+    ///
+    /// Synchronous, since a backfill can take long: the rollup task runs it on a
+    /// blocking thread with a connection of its own.
+    ///
+    /// Returns the number of tier rows written.
+    pub fn perform_rollups(
+        &mut self,
+        lookback_seconds: u64,
+        data_retention_days: u32,
+        five_minute_retention_days: u32,
+        hourly_retention_days: u32,
+    ) -> Result<usize> {
+        let now = current_timestamp();
+        let since = now.saturating_sub(lookback_seconds) as i64;
+        let conn = self.get_connection()?;
+
+        let mut written = 0;
+        for (table, columns) in ROLLUP_TABLES {
+            for tier in db_rollup::RollupTier::ALL {
+                // Retention of the table this tier is computed from
+                let source_retention_days = match tier {
+                    db_rollup::RollupTier::FiveMinutes => data_retention_days,
+                    db_rollup::RollupTier::Hourly => five_minute_retention_days,
+                    db_rollup::RollupTier::Daily => hourly_retention_days,
+                };
+                let source_cutoff =
+                    now.saturating_sub((source_retention_days as u64).saturating_mul(86400));
+                written +=
+                    db_rollup::rollup_tier(conn, table, columns, tier, since, source_cutoff as i64)
+                        .with_context(|| format!("Failed to roll up {}", tier.table_name(table)))?;
+            }
+        }
+
+        debug!("Rollup complete: {} tier rows written", written);
+        Ok(written)
+    }
+
+    /// Deletes rollup tier rows older than each tier's retention period.
+    pub async fn cleanup_old_rollups(
+        &mut self,
+        five_minute_retention_days: u32,
+        hourly_retention_days: u32,
+        daily_retention_days: u32,
+    ) -> Result<usize> {
+        let now = current_timestamp();
+        let conn = self.get_connection()?;
+
+        let mut deleted = 0;
+        for tier in db_rollup::RollupTier::ALL {
+            let retention_days = match tier {
+                db_rollup::RollupTier::FiveMinutes => five_minute_retention_days,
+                db_rollup::RollupTier::Hourly => hourly_retention_days,
+                db_rollup::RollupTier::Daily => daily_retention_days,
+            };
+            // Use saturating arithmetic to prevent overflow with large retention values
+            let cutoff_time = now.saturating_sub((retention_days as u64).saturating_mul(86400));
+            for (table, _) in ROLLUP_TABLES {
+                deleted += db_rollup::cleanup_old_data(conn, table, tier, cutoff_time as i64)?;
+            }
+        }
+
+        info!("Rollup cleanup complete: {} tier rows deleted", deleted);
+        Ok(deleted)
+    }
+
     /// Performs a WAL checkpoint to merge WAL file changes back into the main database.
     /// This method should be called periodically to prevent unbounded WAL file growth.
     ///
@@ -515,6 +890,20 @@ impl ServerDatabase {
             tx.query_row("SELECT COUNT(*) FROM agg_metric_bandwidth", [], |row| {
                 row.get(0)
             })?;
+        let agg_udp_probe_count: i64 =
+            tx.query_row("SELECT COUNT(*) FROM agg_metric_udp_probe", [], |row| {
+                row.get(0)
+            })?;
+        let agg_twamp_count: i64 =
+            tx.query_row("SELECT COUNT(*) FROM agg_metric_twamp", [], |row| {
+                row.get(0)
+            })?;
+        let agg_tcp_sweep_count: i64 =
+            tx.query_row("SELECT COUNT(*) FROM agg_metric_tcp_sweep", [], |row| {
+                row.get(0)
+            })?;
+        let agg_ntp_count: i64 =
+            tx.query_row("SELECT COUNT(*) FROM agg_metric_ntp", [], |row| row.get(0))?;
 
         let agg_sql_query_count: i64 =
             tx.query_row("SELECT COUNT(*) FROM agg_metric_sql_query", [], |row| {
@@ -522,6 +911,8 @@ impl ServerDatabase {
             })?;
         let agg_snmp_count: i64 =
             tx.query_row("SELECT COUNT(*) FROM agg_metric_snmp", [], |row| row.get(0))?;
+        let snmp_trap_count: i64 =
+            tx.query_row("SELECT COUNT(*) FROM snmp_trap_event", [], |row| row.get(0))?;
 
         let total_metrics = agg_ping_count
             + agg_tcp_count
@@ -530,8 +921,13 @@ impl ServerDatabase {
             + agg_http_content_count
             + agg_dns_count
             + agg_bandwidth_count
+            + agg_udp_probe_count
+            + agg_twamp_count
+            + agg_tcp_sweep_count
+            + agg_ntp_count
             + agg_sql_query_count
-            + agg_snmp_count;
+            + agg_snmp_count
+            + snmp_trap_count;
 
         let config_errors_count: i64 =
             tx.query_row("SELECT COUNT(*) FROM config_errors", [], |row| row.get(0))?;
@@ -573,6 +969,8 @@ pub struct AgentInfo {
     pub last_config_checksum: Option<String>,
     pub total_metrics_received: u64,
     pub agent_version: Option<String>,
+    /// Agent clock minus server clock in seconds, measured on the last push
+    pub clock_offset_seconds: Option<i64>,
 }
 
 /// A struct to hold statistics about the server's database.
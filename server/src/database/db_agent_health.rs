@@ -18,6 +18,8 @@ pub struct AgentHealthCheck {
     pub received_entries: i64,
     pub success_ratio: f64,
     pub is_problematic: bool,
+    /// Agent clock minus server clock in seconds, None if unknown
+    pub clock_offset_seconds: Option<i64>,
 }
 
 /// Creates the agent_health_checks table and related indexes
@@ -35,6 +37,7 @@ pub fn create_table(conn: &Connection) -> Result<()> {
             received_entries INTEGER NOT NULL,
             success_ratio REAL NOT NULL,
             is_problematic INTEGER NOT NULL,
+            clock_offset_seconds INTEGER,
             FOREIGN KEY (agent_id) REFERENCES agents (agent_id)
         )
         "#,
@@ -42,6 +45,12 @@ pub fn create_table(conn: &Connection) -> Result<()> {
     )
     .context("Failed to create agent_health_checks table")?;
 
+    // SQLite doesn't support IF NOT EXISTS for ALTER TABLE, so we ignore errors
+    let _ = conn.execute(
+        "ALTER TABLE agent_health_checks ADD COLUMN clock_offset_seconds INTEGER",
+        [],
+    );
+
     // Create indexes for efficient querying
     conn.execute(
         "CREATE INDEX IF NOT EXISTS idx_agent_health_agent_id ON agent_health_checks(agent_id)",
@@ -75,8 +84,9 @@ pub fn store_health_check(tx: &Transaction, health_check: &AgentHealthCheck) ->
             expected_entries,
             received_entries,
             success_ratio,
-            is_problematic
-        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+            is_problematic,
+            clock_offset_seconds
+        ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
         "#,
         params![
             health_check.agent_id,
@@ -88,6 +98,7 @@ pub fn store_health_check(tx: &Transaction, health_check: &AgentHealthCheck) ->
             health_check.received_entries,
             health_check.success_ratio,
             health_check.is_problematic as i32,
+            health_check.clock_offset_seconds,
         ],
     )
     .with_context(|| {
@@ -114,7 +125,8 @@ pub fn get_recent_health_checks(conn: &Connection) -> Result<Vec<AgentHealthChec
             expected_entries,
             received_entries,
             success_ratio,
-            is_problematic
+            is_problematic,
+            clock_offset_seconds
         FROM agent_health_checks
         WHERE check_timestamp = (
             SELECT MAX(check_timestamp) FROM agent_health_checks
@@ -135,6 +147,7 @@ pub fn get_recent_health_checks(conn: &Connection) -> Result<Vec<AgentHealthChec
                 received_entries: row.get(6)?,
                 success_ratio: row.get(7)?,
                 is_problematic: row.get::<_, i32>(8)? != 0,
+                clock_offset_seconds: row.get(9)?,
             })
         })?
         .collect::<Result<Vec<_>, _>>()?;
@@ -155,7 +168,8 @@ pub fn get_problematic_agents(conn: &Connection) -> Result<Vec<AgentHealthCheck>
             expected_entries,
             received_entries,
             success_ratio,
-            is_problematic
+            is_problematic,
+            clock_offset_seconds
         FROM agent_health_checks
         WHERE check_timestamp = (
             SELECT MAX(check_timestamp) FROM agent_health_checks
@@ -177,6 +191,7 @@ pub fn get_problematic_agents(conn: &Connection) -> Result<Vec<AgentHealthCheck>
                 received_entries: row.get(6)?,
                 success_ratio: row.get(7)?,
                 is_problematic: row.get::<_, i32>(8)? != 0,
+                clock_offset_seconds: row.get(9)?,
             })
         })?
         .collect::<Result<Vec<_>, _>>()?;
@@ -202,7 +217,8 @@ pub fn get_agent_health_history(
             expected_entries,
             received_entries,
             success_ratio,
-            is_problematic
+            is_problematic,
+            clock_offset_seconds
         FROM agent_health_checks
         WHERE agent_id = ?1
         ORDER BY check_timestamp DESC
@@ -222,6 +238,7 @@ pub fn get_agent_health_history(
                 received_entries: row.get(6)?,
                 success_ratio: row.get(7)?,
                 is_problematic: row.get::<_, i32>(8)? != 0,
+                clock_offset_seconds: row.get(9)?,
             })
         })?
         .collect::<Result<Vec<_>, _>>()?;
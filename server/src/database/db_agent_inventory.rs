@@ -0,0 +1,300 @@
+//! Agent inventory database operations for server
+//!
+//! Agents report their configured labels (site, region, ISP, role, ...) and host
+//! facts (hostname, OS, kernel, interface addresses, default gateway, public IP)
+//! with each metrics push. The latest report of every agent is kept one row per
+//! agent in `agent_inventory`, with the labels also indexed one row per label in
+//! `agent_labels` so that results can be grouped by site or region with a join.
+//! A row is added to `agent_inventory_history` only when something changed, so
+//! the history records e.g. when an agent's egress IP or ISP changed.
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, OptionalExtension};
+use shared::api::{HostFacts, InterfaceAddress};
+use std::collections::BTreeMap;
+
+/// An agent's labels and host facts as stored on the server
+#[derive(Debug, Clone, PartialEq)]
+pub struct AgentInventory {
+    pub labels: BTreeMap<String, String>,
+    /// None if the agent does not report host facts (older agent versions)
+    pub host_facts: Option<HostFacts>,
+    /// When the inventory was first reported in this form (Unix timestamp)
+    pub changed_at: u64,
+}
+
+/// Outcome of storing an agent's inventory
+#[derive(Debug, Clone, PartialEq)]
+pub enum InventoryChange {
+    /// First inventory report of the agent
+    New,
+    /// Same labels and host facts as the stored inventory
+    Unchanged,
+    /// Labels or host facts differ; holds the previously stored inventory
+    Changed(AgentInventory),
+}
+
+/// Create the agent inventory, history and labels tables
+pub(super) fn create_tables(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS agent_inventory (
+            agent_id TEXT PRIMARY KEY,
+            labels TEXT,
+            hostname TEXT,
+            os TEXT,
+            kernel TEXT,
+            interfaces TEXT,
+            default_gateway TEXT,
+            public_ip TEXT,
+            changed_at INTEGER NOT NULL,
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create agent_inventory table")?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS agent_inventory_history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            labels TEXT,
+            hostname TEXT,
+            os TEXT,
+            kernel TEXT,
+            interfaces TEXT,
+            default_gateway TEXT,
+            public_ip TEXT,
+            recorded_at INTEGER NOT NULL,
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create agent_inventory_history table")?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS agent_labels (
+            agent_id TEXT NOT NULL,
+            label_key TEXT NOT NULL,
+            label_value TEXT NOT NULL,
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            PRIMARY KEY (agent_id, label_key)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create agent_labels table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agent_inventory_history_agent_time ON agent_inventory_history(agent_id, recorded_at)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agent_labels_key_value ON agent_labels(label_key, label_value)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// Column values of an inventory: labels, hostname, os, kernel, interfaces, gateway, public IP
+type InventoryColumns = (
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+    Option<String>,
+);
+
+fn to_columns(
+    labels: &BTreeMap<String, String>,
+    host_facts: Option<&HostFacts>,
+) -> Result<InventoryColumns> {
+    let labels = if labels.is_empty() {
+        None
+    } else {
+        Some(serde_json::to_string(labels)?)
+    };
+    Ok(match host_facts {
+        Some(facts) => (
+            labels,
+            Some(facts.hostname.clone()),
+            Some(facts.os.clone()),
+            facts.kernel.clone(),
+            Some(serde_json::to_string(&facts.interfaces)?),
+            facts.default_gateway.clone(),
+            facts.public_ip.clone(),
+        ),
+        None => (labels, None, None, None, None, None, None),
+    })
+}
+
+fn from_columns(columns: InventoryColumns, changed_at: i64) -> Result<AgentInventory> {
+    let (labels, hostname, os, kernel, interfaces, default_gateway, public_ip) = columns;
+    let labels = match labels {
+        Some(json) => serde_json::from_str(&json)?,
+        None => BTreeMap::new(),
+    };
+    let host_facts = match hostname {
+        Some(hostname) => {
+            let interfaces: Vec<InterfaceAddress> = match interfaces {
+                Some(json) => serde_json::from_str(&json)?,
+                None => Vec::new(),
+            };
+            Some(HostFacts {
+                hostname,
+                os: os.unwrap_or_default(),
+                kernel,
+                interfaces,
+                default_gateway,
+                public_ip,
+            })
+        }
+        None => None,
+    };
+    Ok(AgentInventory {
+        labels,
+        host_facts,
+        changed_at: changed_at as u64,
+    })
+}
+
+/// Load the current inventory of an agent
+pub(super) fn load_inventory(conn: &Connection, agent_id: &str) -> Result<Option<AgentInventory>> {
+    let row = conn
+        .query_row(
+            r#"
+            SELECT labels, hostname, os, kernel, interfaces, default_gateway, public_ip, changed_at
+            FROM agent_inventory
+            WHERE agent_id = ?1
+            "#,
+            params![agent_id],
+            |row| {
+                Ok((
+                    (
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                        row.get(6)?,
+                    ),
+                    row.get::<_, i64>(7)?,
+                ))
+            },
+        )
+        .optional()?;
+
+    row.map(|(columns, changed_at)| from_columns(columns, changed_at))
+        .transpose()
+}
+
+/// Load the inventory history of an agent, oldest first
+pub(super) fn load_history(conn: &Connection, agent_id: &str) -> Result<Vec<AgentInventory>> {
+    let mut stmt = conn.prepare(
+        r#"
+        SELECT labels, hostname, os, kernel, interfaces, default_gateway, public_ip, recorded_at
+        FROM agent_inventory_history
+        WHERE agent_id = ?1
+        ORDER BY recorded_at, id
+        "#,
+    )?;
+    let rows = stmt.query_map(params![agent_id], |row| {
+        Ok((
+            (
+                row.get(0)?,
+                row.get(1)?,
+                row.get(2)?,
+                row.get(3)?,
+                row.get(4)?,
+                row.get(5)?,
+                row.get(6)?,
+            ),
+            row.get::<_, i64>(7)?,
+        ))
+    })?;
+
+    let mut history = Vec::new();
+    for row in rows {
+        let (columns, recorded_at) = row?;
+        history.push(from_columns(columns, recorded_at)?);
+    }
+    Ok(history)
+}
+
+/// Store an agent's reported inventory, recording it in the history if it changed
+pub(super) fn update_inventory(
+    conn: &mut Connection,
+    agent_id: &str,
+    labels: &BTreeMap<String, String>,
+    host_facts: Option<&HostFacts>,
+    reported_at: i64,
+) -> Result<InventoryChange> {
+    let previous = load_inventory(conn, agent_id)?;
+    if let Some(previous) = &previous {
+        if previous.labels == *labels && previous.host_facts.as_ref() == host_facts {
+            return Ok(InventoryChange::Unchanged);
+        }
+    }
+
+    let (labels_json, hostname, os, kernel, interfaces, default_gateway, public_ip) =
+        to_columns(labels, host_facts)?;
+
+    let tx = conn.transaction()?;
+    tx.execute(
+        r#"
+        INSERT OR REPLACE INTO agent_inventory (agent_id, labels, hostname, os, kernel, interfaces, default_gateway, public_ip, changed_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        "#,
+        params![agent_id, labels_json, hostname, os, kernel, interfaces, default_gateway, public_ip, reported_at],
+    )?;
+    tx.execute(
+        r#"
+        INSERT INTO agent_inventory_history (agent_id, labels, hostname, os, kernel, interfaces, default_gateway, public_ip, recorded_at)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+        "#,
+        params![agent_id, labels_json, hostname, os, kernel, interfaces, default_gateway, public_ip, reported_at],
+    )?;
+    tx.execute(
+        "DELETE FROM agent_labels WHERE agent_id = ?1",
+        params![agent_id],
+    )?;
+    for (key, value) in labels {
+        tx.execute(
+            "INSERT INTO agent_labels (agent_id, label_key, label_value) VALUES (?1, ?2, ?3)",
+            params![agent_id, key, value],
+        )?;
+    }
+    tx.commit()?;
+
+    Ok(match previous {
+        Some(previous) => InventoryChange::Changed(previous),
+        None => InventoryChange::New,
+    })
+}
+
+/// Delete inventory history recorded before the cutoff, and all inventory of
+/// agents that have not been seen since then (before the agents themselves are deleted)
+pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let mut deleted = conn.execute(
+        "DELETE FROM agent_inventory_history WHERE recorded_at < ?1",
+        params![cutoff_time],
+    )?;
+    for table in ["agent_inventory", "agent_labels"] {
+        deleted += conn.execute(
+            &format!(
+                "DELETE FROM {} WHERE agent_id IN (SELECT agent_id FROM agents WHERE last_seen < ?1)",
+                table
+            ),
+            params![cutoff_time],
+        )?;
+    }
+    Ok(deleted)
+}
@@ -3,9 +3,33 @@
 //! This module handles all database operations specific to bandwidth testing
 //! on the server side, including table creation, metric storage, and cleanup.
 
+use super::db_rollup::Rollup;
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
 use anyhow::{Context, Result};
 use rusqlite::{params, Connection, Transaction};
-use shared::metrics::{AggregatedBandwidthMetric, AggregatedMetrics};
+use shared::metrics::{
+    AggregatedBandwidthMetric, AggregatedMetrics, MetricData, RawBandwidthMetric,
+};
+
+/// How each bandwidth metric column is combined in rollup tiers
+pub(super) const ROLLUP_COLUMNS: &[(&str, Rollup)] = &[
+    ("sample_count", Rollup::Sum),
+    ("avg_bandwidth_mbps", Rollup::Avg("successful_tests")),
+    ("max_bandwidth_mbps", Rollup::Max),
+    ("min_bandwidth_mbps", Rollup::Min),
+    ("successful_tests", Rollup::Sum),
+    ("failed_tests", Rollup::Sum),
+    ("target_id", Rollup::Any),
+    ("avg_upload_mbps", Rollup::Avg("successful_tests")),
+    ("max_upload_mbps", Rollup::Max),
+    ("min_upload_mbps", Rollup::Min),
+    ("maintenance", Rollup::Max),
+    ("adaptive", Rollup::Max),
+    ("upstream_down", Rollup::Max),
+    ("avg_queue_wait_ms", Rollup::Avg("sample_count")),
+    ("max_queue_wait_ms", Rollup::Max),
+    ("labels", Rollup::Any),
+];
 
 /// Create bandwidth aggregated metrics table
 pub(super) fn create_table(conn: &Connection) -> Result<()> {
@@ -24,6 +48,15 @@ pub(super) fn create_table(conn: &Connection) -> Result<()> {
             successful_tests INTEGER NOT NULL,
             failed_tests INTEGER NOT NULL,
             target_id TEXT,
+            avg_upload_mbps REAL,
+            max_upload_mbps REAL,
+            min_upload_mbps REAL,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
             received_at INTEGER DEFAULT (strftime('%s', 'now')),
             FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
             UNIQUE(agent_id, task_name, period_start, period_end)
@@ -46,6 +79,71 @@ pub(super) fn create_table(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Add upload columns to existing tables (migration)
+    // SQLite doesn't support IF NOT EXISTS for ALTER TABLE, so we ignore errors
+    for column in ["avg_upload_mbps", "max_upload_mbps", "min_upload_mbps"] {
+        let _ = conn.execute(
+            &format!(
+                "ALTER TABLE agg_metric_bandwidth ADD COLUMN {} REAL",
+                column
+            ),
+            [],
+        );
+    }
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_bandwidth", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "agg_metric_bandwidth");
+
+    Ok(())
+}
+
+/// Create bandwidth raw metrics table
+pub(super) fn create_raw_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS raw_metric_bandwidth (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            agent_row_id INTEGER,
+            timestamp INTEGER NOT NULL,
+            bandwidth_mbps REAL,
+            duration_ms REAL,
+            bytes_downloaded INTEGER,
+            success BOOLEAN NOT NULL,
+            error TEXT,
+            target_id TEXT,
+            upload_mbps REAL,
+            upload_duration_ms REAL,
+            bytes_uploaded INTEGER,
+            parallel_streams INTEGER,
+            stream_mbps TEXT,
+            throughput_series_mbps TEXT,
+            upload_stream_mbps TEXT,
+            upload_throughput_series_mbps TEXT,
+            target_url TEXT,
+            labels TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, timestamp, agent_row_id)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create raw_metric_bandwidth table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_bandwidth_agent_task ON raw_metric_bandwidth(agent_id, task_name, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_bandwidth_timestamp ON raw_metric_bandwidth(timestamp)",
+        [],
+    )?;
+
     Ok(())
 }
 
@@ -56,10 +154,11 @@ pub(super) fn store_metric(
     metric: &AggregatedMetrics,
     bandwidth_data: &AggregatedBandwidthMetric,
 ) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
     tx.execute(
         r#"
-        INSERT INTO agg_metric_bandwidth (agent_id, task_name, period_start, period_end, sample_count, avg_bandwidth_mbps, max_bandwidth_mbps, min_bandwidth_mbps, successful_tests, failed_tests, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+        INSERT INTO agg_metric_bandwidth (agent_id, task_name, period_start, period_end, sample_count, avg_bandwidth_mbps, max_bandwidth_mbps, min_bandwidth_mbps, successful_tests, failed_tests, target_id, avg_upload_mbps, max_upload_mbps, min_upload_mbps, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)
         "#,
         params![
             agent_id,
@@ -73,6 +172,57 @@ pub(super) fn store_metric(
             bandwidth_data.successful_tests,
             bandwidth_data.failed_tests,
             bandwidth_data.target_id,
+            bandwidth_data.avg_upload_mbps,
+            bandwidth_data.max_upload_mbps,
+            bandwidth_data.min_upload_mbps,
+            metric.maintenance,
+            metric.adaptive,
+            metric.upstream_down,
+            metric.avg_queue_wait_ms,
+            metric.max_queue_wait_ms,
+            labels,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Store raw bandwidth metric within a transaction
+pub(super) fn store_raw_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &MetricData,
+    bandwidth_data: &RawBandwidthMetric,
+) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    let to_json = |values: &Option<Vec<f64>>| -> Result<Option<String>> {
+        Ok(values.as_ref().map(serde_json::to_string).transpose()?)
+    };
+    tx.execute(
+        r#"
+        INSERT OR IGNORE INTO raw_metric_bandwidth (agent_id, task_name, timestamp, bandwidth_mbps, duration_ms, bytes_downloaded, success, error, target_id, upload_mbps, upload_duration_ms, bytes_uploaded, parallel_streams, stream_mbps, throughput_series_mbps, upload_stream_mbps, upload_throughput_series_mbps, target_url, labels, agent_row_id)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)
+        "#,
+        params![
+            agent_id,
+            metric.task_name,
+            metric.timestamp as i64,
+            bandwidth_data.bandwidth_mbps,
+            bandwidth_data.duration_ms,
+            bandwidth_data.bytes_downloaded.map(|b| b as i64),
+            bandwidth_data.success,
+            bandwidth_data.error,
+            bandwidth_data.target_id,
+            bandwidth_data.upload_mbps,
+            bandwidth_data.upload_duration_ms,
+            bandwidth_data.bytes_uploaded.map(|b| b as i64),
+            bandwidth_data.parallel_streams,
+            to_json(&bandwidth_data.stream_mbps)?,
+            to_json(&bandwidth_data.throughput_series_mbps)?,
+            to_json(&bandwidth_data.upload_stream_mbps)?,
+            to_json(&bandwidth_data.upload_throughput_series_mbps)?,
+            bandwidth_data.target_url,
+            labels,
+            metric.row_id
         ],
     )?;
     Ok(())
@@ -86,3 +236,12 @@ pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<us
     )?;
     Ok(deleted)
 }
+
+/// Delete old raw bandwidth metrics
+pub(super) fn cleanup_old_raw_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM raw_metric_bandwidth WHERE timestamp < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
@@ -3,9 +3,37 @@
 //! This module handles all database operations specific to DNS query monitoring
 //! on the server side, including table creation, metric storage, and cleanup.
 
+use super::db_latency;
+use super::db_rollup::Rollup;
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
 use anyhow::{Context, Result};
 use rusqlite::{params, Connection, Transaction};
-use shared::metrics::{AggregatedDnsMetric, AggregatedMetrics};
+use shared::metrics::{AggregatedDnsMetric, AggregatedMetrics, MetricData, RawDnsMetric};
+
+/// How each DNS metric column is combined in rollup tiers
+pub(super) const ROLLUP_COLUMNS: &[(&str, Rollup)] = &[
+    ("sample_count", Rollup::Sum),
+    ("success_rate_percent", Rollup::Avg("sample_count")),
+    ("avg_query_time_ms", Rollup::Avg("successful_queries")),
+    ("max_query_time_ms", Rollup::Max),
+    ("successful_queries", Rollup::Sum),
+    ("failed_queries", Rollup::Sum),
+    ("all_resolved_addresses", Rollup::StringSet),
+    ("domain_queried", Rollup::Any),
+    ("correct_resolution_percent", Rollup::Avg("sample_count")),
+    ("target_id", Rollup::Any),
+    ("p50_ms", Rollup::HistogramPercentile(50.0)),
+    ("p90_ms", Rollup::HistogramPercentile(90.0)),
+    ("p95_ms", Rollup::HistogramPercentile(95.0)),
+    ("p99_ms", Rollup::HistogramPercentile(99.0)),
+    ("latency_histogram", Rollup::CountArray),
+    ("maintenance", Rollup::Max),
+    ("adaptive", Rollup::Max),
+    ("upstream_down", Rollup::Max),
+    ("avg_queue_wait_ms", Rollup::Avg("sample_count")),
+    ("max_queue_wait_ms", Rollup::Max),
+    ("labels", Rollup::Any),
+];
 
 /// Create DNS aggregated metrics table
 pub(super) fn create_table(conn: &Connection) -> Result<()> {
@@ -27,6 +55,17 @@ pub(super) fn create_table(conn: &Connection) -> Result<()> {
             domain_queried TEXT NOT NULL,
             correct_resolution_percent REAL NOT NULL DEFAULT 100.0,
             target_id TEXT,
+            p50_ms REAL,
+            p90_ms REAL,
+            p95_ms REAL,
+            p99_ms REAL,
+            latency_histogram TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
             received_at INTEGER DEFAULT (strftime('%s', 'now')),
             FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
             UNIQUE(agent_id, task_name, period_start, period_end)
@@ -49,6 +88,57 @@ pub(super) fn create_table(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Add latency distribution columns to existing tables (migration)
+    db_latency::add_columns(conn, "agg_metric_dns");
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_dns", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "agg_metric_dns");
+
+    Ok(())
+}
+
+/// Create DNS raw metrics table
+pub(super) fn create_raw_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS raw_metric_dns (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            agent_row_id INTEGER,
+            timestamp INTEGER NOT NULL,
+            query_time_ms REAL,
+            success BOOLEAN NOT NULL,
+            record_count INTEGER,
+            resolved_addresses TEXT,
+            domain_queried TEXT NOT NULL,
+            error TEXT,
+            expected_ip TEXT,
+            resolved_ip TEXT,
+            correct_resolution BOOLEAN NOT NULL DEFAULT 1,
+            target_id TEXT,
+            labels TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, timestamp, agent_row_id)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create raw_metric_dns table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_dns_agent_task ON raw_metric_dns(agent_id, task_name, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_dns_timestamp ON raw_metric_dns(timestamp)",
+        [],
+    )?;
+
     Ok(())
 }
 
@@ -59,11 +149,15 @@ pub(super) fn store_metric(
     metric: &AggregatedMetrics,
     dns_data: &AggregatedDnsMetric,
 ) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
     let addresses_json = serde_json::to_string(&dns_data.all_resolved_addresses)?;
+    let (p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram) =
+        db_latency::column_values(&dns_data.latency)?;
+
     tx.execute(
         r#"
-        INSERT INTO agg_metric_dns (agent_id, task_name, period_start, period_end, sample_count, success_rate_percent, avg_query_time_ms, max_query_time_ms, successful_queries, failed_queries, all_resolved_addresses, domain_queried, correct_resolution_percent, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+        INSERT INTO agg_metric_dns (agent_id, task_name, period_start, period_end, sample_count, success_rate_percent, avg_query_time_ms, max_query_time_ms, successful_queries, failed_queries, all_resolved_addresses, domain_queried, correct_resolution_percent, target_id, p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25)
         "#,
         params![
             agent_id,
@@ -80,6 +174,56 @@ pub(super) fn store_metric(
             dns_data.domain_queried,
             dns_data.correct_resolution_percent,
             dns_data.target_id,
+            p50_ms,
+            p90_ms,
+            p95_ms,
+            p99_ms,
+            latency_histogram,
+            metric.maintenance,
+            metric.adaptive,
+            metric.upstream_down,
+            metric.avg_queue_wait_ms,
+            metric.max_queue_wait_ms,
+            labels,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Store raw DNS metric within a transaction
+pub(super) fn store_raw_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &MetricData,
+    dns_data: &RawDnsMetric,
+) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    let resolved_addresses_json = dns_data
+        .resolved_addresses
+        .as_ref()
+        .map(|addrs| serde_json::to_string(addrs).unwrap_or_else(|_| "[]".to_string()));
+
+    tx.execute(
+        r#"
+        INSERT OR IGNORE INTO raw_metric_dns (agent_id, task_name, timestamp, query_time_ms, success, record_count, resolved_addresses, domain_queried, error, expected_ip, resolved_ip, correct_resolution, target_id, labels, agent_row_id)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15)
+        "#,
+        params![
+            agent_id,
+            metric.task_name,
+            metric.timestamp as i64,
+            dns_data.query_time_ms,
+            dns_data.success,
+            dns_data.record_count.map(|c| c as i64),
+            resolved_addresses_json,
+            dns_data.domain_queried,
+            dns_data.error,
+            dns_data.expected_ip,
+            dns_data.resolved_ip,
+            dns_data.correct_resolution,
+            dns_data.target_id,
+            labels,
+            metric.row_id
         ],
     )?;
     Ok(())
@@ -93,3 +237,12 @@ pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<us
     )?;
     Ok(deleted)
 }
+
+/// Delete old raw DNS metrics
+pub(super) fn cleanup_old_raw_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM raw_metric_dns WHERE timestamp < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
@@ -0,0 +1,129 @@
+//! Task execution statistics database operations for server
+//!
+//! This module stores the per-period counts of skipped, suppressed, delayed and
+//! timed-out task runs reported by agents, so a missing sample in an aggregated table can
+//! be told apart from a failed probe.
+
+use super::db_labels;
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, Transaction};
+use shared::metrics::{AggregatedMetrics, TaskExecutionMetric};
+
+/// Create task execution table
+pub(super) fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS task_execution (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            task_type TEXT NOT NULL,
+            period_start INTEGER NOT NULL,
+            period_end INTEGER NOT NULL,
+            runs_started INTEGER NOT NULL,
+            skipped_overruns INTEGER NOT NULL,
+            delayed_starts INTEGER NOT NULL,
+            max_start_delay_ms REAL NOT NULL,
+            timed_out_runs INTEGER NOT NULL,
+            suppressed_runs INTEGER NOT NULL DEFAULT 0,
+            labels TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, period_start, period_end)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create task_execution table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_task_execution_agent_task ON task_execution(agent_id, task_name, period_start)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_task_execution_period ON task_execution(period_start)",
+        [],
+    )?;
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "task_execution");
+
+    // Add suppressed_runs column to existing tables (migration)
+    let _ = conn.execute(
+        "ALTER TABLE task_execution ADD COLUMN suppressed_runs INTEGER NOT NULL DEFAULT 0",
+        [],
+    );
+
+    Ok(())
+}
+
+/// Store task execution statistics within a transaction
+///
+/// A repeated delivery for the same agent, task and period replaces the row.
+pub(super) fn store_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &AggregatedMetrics,
+    execution: &TaskExecutionMetric,
+) -> Result<()> {
+    let task_type = serde_json::to_value(&metric.task_type)
+        .context("Failed to serialize task type")?
+        .as_str()
+        .unwrap_or_default()
+        .to_string();
+    let labels = db_labels::column_value(&metric.labels)?;
+
+    tx.execute(
+        r#"
+        INSERT OR REPLACE INTO task_execution (agent_id, task_name, task_type, period_start, period_end, runs_started, skipped_overruns, delayed_starts, max_start_delay_ms, timed_out_runs, suppressed_runs, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        "#,
+        params![
+            agent_id,
+            metric.task_name,
+            task_type,
+            metric.period_start as i64,
+            metric.period_end as i64,
+            execution.runs_started,
+            execution.skipped_overruns,
+            execution.delayed_starts,
+            execution.max_start_delay_ms,
+            execution.timed_out_runs,
+            execution.suppressed_runs,
+            labels,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Periods in which every due run of a task was suppressed by a down prerequisite
+///
+/// Returns `(task_name, period_start)` pairs for the periods of an agent
+/// starting in `[from, to)`. Such periods have no aggregated entry.
+pub(super) fn load_suppressed_periods(
+    conn: &Connection,
+    agent_id: &str,
+    from: i64,
+    to: i64,
+) -> Result<Vec<(String, i64)>> {
+    let mut stmt = conn.prepare(
+        "SELECT task_name, period_start FROM task_execution
+         WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3
+           AND suppressed_runs > 0 AND runs_started = 0",
+    )?;
+    let periods = stmt
+        .query_map(params![agent_id, from, to], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(periods)
+}
+
+/// Delete old task execution statistics
+pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM task_execution WHERE period_end < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
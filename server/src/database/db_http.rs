@@ -3,9 +3,47 @@
 //! This module handles all database operations specific to HTTP GET monitoring
 //! on the server side, including table creation, metric storage, and cleanup.
 
+use super::db_latency;
+use super::db_rollup::Rollup;
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
 use anyhow::{Context, Result};
 use rusqlite::{params, Connection, Transaction};
-use shared::metrics::{AggregatedHttpMetric, AggregatedMetrics};
+use shared::metrics::{AggregatedHttpMetric, AggregatedMetrics, MetricData, RawHttpMetric};
+
+/// How each HTTP metric column is combined in rollup tiers
+pub(super) const ROLLUP_COLUMNS: &[(&str, Rollup)] = &[
+    ("sample_count", Rollup::Sum),
+    ("success_rate_percent", Rollup::Avg("sample_count")),
+    ("avg_tcp_timing_ms", Rollup::Avg("successful_requests")),
+    ("avg_tls_timing_ms", Rollup::Avg("successful_requests")),
+    ("avg_ttfb_timing_ms", Rollup::Avg("successful_requests")),
+    (
+        "avg_content_download_timing_ms",
+        Rollup::Avg("successful_requests"),
+    ),
+    ("avg_total_time_ms", Rollup::Avg("successful_requests")),
+    ("max_total_time_ms", Rollup::Max),
+    ("successful_requests", Rollup::Sum),
+    ("failed_requests", Rollup::Sum),
+    ("status_code_distribution", Rollup::CountPairs),
+    ("ssl_valid_percent", Rollup::Avg("sample_count")),
+    (
+        "avg_ssl_cert_days_until_expiry",
+        Rollup::Avg("successful_requests"),
+    ),
+    ("target_id", Rollup::Any),
+    ("p50_ms", Rollup::HistogramPercentile(50.0)),
+    ("p90_ms", Rollup::HistogramPercentile(90.0)),
+    ("p95_ms", Rollup::HistogramPercentile(95.0)),
+    ("p99_ms", Rollup::HistogramPercentile(99.0)),
+    ("latency_histogram", Rollup::CountArray),
+    ("maintenance", Rollup::Max),
+    ("adaptive", Rollup::Max),
+    ("upstream_down", Rollup::Max),
+    ("avg_queue_wait_ms", Rollup::Avg("sample_count")),
+    ("max_queue_wait_ms", Rollup::Max),
+    ("labels", Rollup::Any),
+];
 
 /// Create HTTP aggregated metrics table
 pub(super) fn create_table(conn: &Connection) -> Result<()> {
@@ -31,6 +69,17 @@ pub(super) fn create_table(conn: &Connection) -> Result<()> {
             ssl_valid_percent REAL,
             avg_ssl_cert_days_until_expiry REAL,
             target_id TEXT,
+            p50_ms REAL,
+            p90_ms REAL,
+            p95_ms REAL,
+            p99_ms REAL,
+            latency_histogram TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
             received_at INTEGER DEFAULT (strftime('%s', 'now')),
             FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
             UNIQUE(agent_id, task_name, period_start, period_end)
@@ -53,6 +102,58 @@ pub(super) fn create_table(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Add latency distribution columns to existing tables (migration)
+    db_latency::add_columns(conn, "agg_metric_http");
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_http", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "agg_metric_http");
+
+    Ok(())
+}
+
+/// Create HTTP raw metrics table
+pub(super) fn create_raw_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS raw_metric_http (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            agent_row_id INTEGER,
+            timestamp INTEGER NOT NULL,
+            status_code INTEGER,
+            tcp_timing_ms REAL,
+            tls_timing_ms REAL,
+            ttfb_timing_ms REAL,
+            content_download_timing_ms REAL,
+            total_time_ms REAL,
+            success BOOLEAN NOT NULL,
+            error TEXT,
+            ssl_valid BOOLEAN,
+            ssl_cert_days_until_expiry INTEGER,
+            target_id TEXT,
+            labels TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, timestamp, agent_row_id)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create raw_metric_http table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_http_agent_task ON raw_metric_http(agent_id, task_name, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_http_timestamp ON raw_metric_http(timestamp)",
+        [],
+    )?;
+
     Ok(())
 }
 
@@ -63,6 +164,7 @@ pub(super) fn store_metric(
     metric: &AggregatedMetrics,
     http_data: &AggregatedHttpMetric,
 ) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
     // Convert HashMap to Vec of tuples for proper JSON serialization
     // JSON only supports string keys, so we serialize as array of [code, count] pairs
     let status_code_vec: Vec<(u16, u32)> = http_data
@@ -71,10 +173,13 @@ pub(super) fn store_metric(
         .map(|(&k, &v)| (k, v))
         .collect();
     let status_code_json = serde_json::to_string(&status_code_vec)?;
+    let (p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram) =
+        db_latency::column_values(&http_data.latency)?;
+
     tx.execute(
         r#"
-        INSERT INTO agg_metric_http (agent_id, task_name, period_start, period_end, sample_count, success_rate_percent, avg_tcp_timing_ms, avg_tls_timing_ms, avg_ttfb_timing_ms, avg_content_download_timing_ms, avg_total_time_ms, max_total_time_ms, successful_requests, failed_requests, status_code_distribution, ssl_valid_percent, avg_ssl_cert_days_until_expiry, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)
+        INSERT INTO agg_metric_http (agent_id, task_name, period_start, period_end, sample_count, success_rate_percent, avg_tcp_timing_ms, avg_tls_timing_ms, avg_ttfb_timing_ms, avg_content_download_timing_ms, avg_total_time_ms, max_total_time_ms, successful_requests, failed_requests, status_code_distribution, ssl_valid_percent, avg_ssl_cert_days_until_expiry, target_id, p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29)
         "#,
         params![
             agent_id,
@@ -95,6 +200,52 @@ pub(super) fn store_metric(
             http_data.ssl_valid_percent,
             http_data.avg_ssl_cert_days_until_expiry,
             http_data.target_id,
+            p50_ms,
+            p90_ms,
+            p95_ms,
+            p99_ms,
+            latency_histogram,
+            metric.maintenance,
+            metric.adaptive,
+            metric.upstream_down,
+            metric.avg_queue_wait_ms,
+            metric.max_queue_wait_ms,
+            labels,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Store raw HTTP metric within a transaction
+pub(super) fn store_raw_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &MetricData,
+    http_data: &RawHttpMetric,
+) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    tx.execute(
+        r#"
+        INSERT OR IGNORE INTO raw_metric_http (agent_id, task_name, timestamp, status_code, tcp_timing_ms, tls_timing_ms, ttfb_timing_ms, content_download_timing_ms, total_time_ms, success, error, ssl_valid, ssl_cert_days_until_expiry, target_id, labels, agent_row_id)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+        "#,
+        params![
+            agent_id,
+            metric.task_name,
+            metric.timestamp as i64,
+            http_data.status_code.map(|s| s as i64),
+            http_data.tcp_timing_ms,
+            http_data.tls_timing_ms,
+            http_data.ttfb_timing_ms,
+            http_data.content_download_timing_ms,
+            http_data.total_time_ms,
+            http_data.success,
+            http_data.error,
+            http_data.ssl_valid,
+            http_data.ssl_cert_days_until_expiry,
+            http_data.target_id,
+            labels,
+            metric.row_id
         ],
     )?;
     Ok(())
@@ -108,3 +259,12 @@ pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<us
     )?;
     Ok(deleted)
 }
+
+/// Delete old raw HTTP metrics
+pub(super) fn cleanup_old_raw_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM raw_metric_http WHERE timestamp < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
@@ -3,9 +3,33 @@
 //! This module handles all database operations specific to HTTP content checking
 //! on the server side, including table creation, metric storage, and cleanup.
 
+use super::db_rollup::Rollup;
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
 use anyhow::{Context, Result};
 use rusqlite::{params, Connection, Transaction};
-use shared::metrics::{AggregatedHttpContentMetric, AggregatedMetrics};
+use shared::metrics::{
+    AggregatedHttpContentMetric, AggregatedMetrics, MetricData, RawHttpContentMetric,
+};
+
+/// How each HTTP content metric column is combined in rollup tiers
+pub(super) const ROLLUP_COLUMNS: &[(&str, Rollup)] = &[
+    ("sample_count", Rollup::Sum),
+    ("success_rate_percent", Rollup::Avg("sample_count")),
+    ("avg_total_time_ms", Rollup::Avg("successful_requests")),
+    ("max_total_time_ms", Rollup::Max),
+    ("avg_total_size", Rollup::Avg("successful_requests")),
+    ("regexp_match_rate_percent", Rollup::Avg("sample_count")),
+    ("successful_requests", Rollup::Sum),
+    ("failed_requests", Rollup::Sum),
+    ("regexp_matched_count", Rollup::Sum),
+    ("target_id", Rollup::Any),
+    ("maintenance", Rollup::Max),
+    ("adaptive", Rollup::Max),
+    ("upstream_down", Rollup::Max),
+    ("avg_queue_wait_ms", Rollup::Avg("sample_count")),
+    ("max_queue_wait_ms", Rollup::Max),
+    ("labels", Rollup::Any),
+];
 
 /// Create HTTP content aggregated metrics table
 pub(super) fn create_table(conn: &Connection) -> Result<()> {
@@ -27,6 +51,12 @@ pub(super) fn create_table(conn: &Connection) -> Result<()> {
             failed_requests INTEGER NOT NULL,
             regexp_matched_count INTEGER NOT NULL,
             target_id TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
             received_at INTEGER DEFAULT (strftime('%s', 'now')),
             FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
             UNIQUE(agent_id, task_name, period_start, period_end)
@@ -49,6 +79,51 @@ pub(super) fn create_table(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_http_content", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "agg_metric_http_content");
+
+    Ok(())
+}
+
+/// Create HTTP content raw metrics table
+pub(super) fn create_raw_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS raw_metric_http_content (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            agent_row_id INTEGER,
+            timestamp INTEGER NOT NULL,
+            status_code INTEGER,
+            total_time_ms REAL,
+            total_size INTEGER,
+            regexp_match BOOLEAN,
+            success BOOLEAN NOT NULL,
+            error TEXT,
+            target_id TEXT,
+            labels TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, timestamp, agent_row_id)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create raw_metric_http_content table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_http_content_agent_task ON raw_metric_http_content(agent_id, task_name, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_http_content_timestamp ON raw_metric_http_content(timestamp)",
+        [],
+    )?;
+
     Ok(())
 }
 
@@ -59,10 +134,11 @@ pub(super) fn store_metric(
     metric: &AggregatedMetrics,
     http_content_data: &AggregatedHttpContentMetric,
 ) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
     tx.execute(
         r#"
-        INSERT INTO agg_metric_http_content (agent_id, task_name, period_start, period_end, sample_count, success_rate_percent, avg_total_time_ms, max_total_time_ms, avg_total_size, regexp_match_rate_percent, successful_requests, failed_requests, regexp_matched_count, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+        INSERT INTO agg_metric_http_content (agent_id, task_name, period_start, period_end, sample_count, success_rate_percent, avg_total_time_ms, max_total_time_ms, avg_total_size, regexp_match_rate_percent, successful_requests, failed_requests, regexp_matched_count, target_id, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20)
         "#,
         params![
             agent_id,
@@ -79,6 +155,43 @@ pub(super) fn store_metric(
             http_content_data.failed_requests,
             http_content_data.regexp_matched_count,
             http_content_data.target_id,
+            metric.maintenance,
+            metric.adaptive,
+            metric.upstream_down,
+            metric.avg_queue_wait_ms,
+            metric.max_queue_wait_ms,
+            labels,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Store raw HTTP content metric within a transaction
+pub(super) fn store_raw_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &MetricData,
+    http_content_data: &RawHttpContentMetric,
+) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    tx.execute(
+        r#"
+        INSERT OR IGNORE INTO raw_metric_http_content (agent_id, task_name, timestamp, status_code, total_time_ms, total_size, regexp_match, success, error, target_id, labels, agent_row_id)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        "#,
+        params![
+            agent_id,
+            metric.task_name,
+            metric.timestamp as i64,
+            http_content_data.status_code.map(|s| s as i64),
+            http_content_data.total_time_ms,
+            http_content_data.total_size.map(|s| s as i64),
+            http_content_data.regexp_match,
+            http_content_data.success,
+            http_content_data.error,
+            http_content_data.target_id,
+            labels,
+            metric.row_id
         ],
     )?;
     Ok(())
@@ -92,3 +205,12 @@ pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<us
     )?;
     Ok(deleted)
 }
+
+/// Delete old raw HTTP content metrics
+pub(super) fn cleanup_old_raw_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM raw_metric_http_content WHERE timestamp < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
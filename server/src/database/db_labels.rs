@@ -0,0 +1,125 @@
+//! Task label database operations for server
+//!
+//! Aggregated tables carry each task's labels in a `labels` column (JSON object,
+//! NULL without labels). The current labels of every agent's tasks are also kept
+//! one row per label in the `task_labels` table, indexed by name and value, so
+//! that metrics can be filtered by label with a join instead of relying on
+//! task-name conventions. `task_label_periods` records the end of the period
+//! the indexed labels of each task were reported with, so a batch delivered
+//! late does not replace labels reported for a later period.
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, Transaction};
+use std::collections::BTreeMap;
+
+/// Create the task labels table
+pub(super) fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS task_labels (
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            label_key TEXT NOT NULL,
+            label_value TEXT NOT NULL,
+            updated_at INTEGER NOT NULL,
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            PRIMARY KEY (agent_id, task_name, label_key)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create task_labels table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_task_labels_key_value ON task_labels(label_key, label_value)",
+        [],
+    )?;
+
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS task_label_periods (
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            updated_period INTEGER NOT NULL,
+            updated_at INTEGER NOT NULL,
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            PRIMARY KEY (agent_id, task_name)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create task_label_periods table")?;
+
+    Ok(())
+}
+
+/// Add the labels column to an aggregated table created before it existed
+pub(super) fn add_column(conn: &Connection, table: &str) {
+    super::add_columns(conn, table, &["labels TEXT"]);
+}
+
+/// Column value for storing a task's labels
+pub(super) fn column_value(labels: &BTreeMap<String, String>) -> Result<Option<String>> {
+    if labels.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(serde_json::to_string(labels)?))
+}
+
+/// Replace the indexed labels of an agent's task within a transaction
+///
+/// `period_end` is the end of the period the labels were reported with. The
+/// labels are left alone when labels of a later period are already indexed.
+pub(super) fn update_task_labels(
+    tx: &Transaction,
+    agent_id: &str,
+    task_name: &str,
+    labels: &BTreeMap<String, String>,
+    period_end: i64,
+    updated_at: i64,
+) -> Result<()> {
+    let superseded: bool = tx.query_row(
+        "SELECT EXISTS(SELECT 1 FROM task_label_periods
+                       WHERE agent_id = ?1 AND task_name = ?2 AND updated_period > ?3)",
+        params![agent_id, task_name, period_end],
+        |row| row.get(0),
+    )?;
+    if superseded {
+        return Ok(());
+    }
+
+    tx.execute(
+        r#"
+        INSERT OR REPLACE INTO task_label_periods (agent_id, task_name, updated_period, updated_at)
+        VALUES (?1, ?2, ?3, ?4)
+        "#,
+        params![agent_id, task_name, period_end, updated_at],
+    )?;
+    tx.execute(
+        "DELETE FROM task_labels WHERE agent_id = ?1 AND task_name = ?2",
+        params![agent_id, task_name],
+    )?;
+    for (key, value) in labels {
+        tx.execute(
+            r#"
+            INSERT INTO task_labels (agent_id, task_name, label_key, label_value, updated_at)
+            VALUES (?1, ?2, ?3, ?4, ?5)
+            "#,
+            params![agent_id, task_name, key, value, updated_at],
+        )?;
+    }
+    Ok(())
+}
+
+/// Delete labels of tasks that have not reported metrics since the cutoff
+pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM task_labels WHERE updated_at < ?1",
+        params![cutoff_time],
+    )?;
+    conn.execute(
+        "DELETE FROM task_label_periods WHERE updated_at < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
@@ -0,0 +1,77 @@
+//! Latency distribution database operations for server
+//!
+//! Helpers shared by the task modules whose aggregated metrics carry a
+//! `LatencyDistribution` (percentiles and histogram):
+//! - Adding the distribution columns to existing tables
+//! - Converting the distribution to its columns
+//! - Estimating percentiles from a merged histogram for rollup tiers
+
+use anyhow::Result;
+use rusqlite::Connection;
+use shared::metrics::{LatencyDistribution, LATENCY_HISTOGRAM_BOUNDS_MS};
+
+/// Columns of a latency distribution, in storage order
+const LATENCY_COLUMNS: [&str; 5] = [
+    "p50_ms REAL",
+    "p90_ms REAL",
+    "p95_ms REAL",
+    "p99_ms REAL",
+    "latency_histogram TEXT",
+];
+
+/// Values of the latency distribution columns, in storage order
+pub(super) type LatencyColumnValues = (
+    Option<f64>,
+    Option<f64>,
+    Option<f64>,
+    Option<f64>,
+    Option<String>,
+);
+
+/// Add the latency distribution columns to an aggregated table created before they existed
+pub(super) fn add_columns(conn: &Connection, table: &str) {
+    super::add_columns(conn, table, &LATENCY_COLUMNS);
+}
+
+/// Column values for storing a latency distribution
+pub(super) fn column_values(latency: &Option<LatencyDistribution>) -> Result<LatencyColumnValues> {
+    Ok(match latency {
+        Some(latency) => (
+            Some(latency.p50_ms),
+            Some(latency.p90_ms),
+            Some(latency.p95_ms),
+            Some(latency.p99_ms),
+            Some(serde_json::to_string(&latency.histogram)?),
+        ),
+        None => (None, None, None, None, None),
+    })
+}
+
+/// Estimate a percentile from histogram counts per `LATENCY_HISTOGRAM_BOUNDS_MS` bucket
+///
+/// The nearest-rank sample is located in its bucket and interpolated linearly
+/// between the bucket's bounds. Samples in the overflow bucket are reported as
+/// the last bound. Returns None for an empty histogram.
+pub(super) fn histogram_percentile(histogram: &[u64], percentile: f64) -> Option<f64> {
+    let total: u64 = histogram.iter().sum();
+    if total == 0 {
+        return None;
+    }
+    let rank = ((percentile / 100.0 * total as f64).ceil() as u64).clamp(1, total);
+
+    let mut below = 0u64;
+    for (bucket, &count) in histogram.iter().enumerate() {
+        if below + count >= rank {
+            let Some(&upper) = LATENCY_HISTOGRAM_BOUNDS_MS.get(bucket) else {
+                return LATENCY_HISTOGRAM_BOUNDS_MS.last().copied();
+            };
+            let lower = bucket
+                .checked_sub(1)
+                .map_or(0.0, |previous| LATENCY_HISTOGRAM_BOUNDS_MS[previous]);
+            let fraction = (rank - below) as f64 / count as f64;
+            return Some(lower + (upper - lower) * fraction);
+        }
+        below += count;
+    }
+    None
+}
@@ -0,0 +1,246 @@
+//! NTP task database operations for server
+//!
+//! This module handles all database operations specific to NTP monitoring
+//! on the server side, including table creation, metric storage, and cleanup.
+
+use super::db_rollup::Rollup;
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, Transaction};
+use shared::metrics::{AggregatedMetrics, AggregatedNtpMetric, MetricData, RawNtpMetric};
+
+/// How each NTP metric column is combined in rollup tiers
+pub(super) const ROLLUP_COLUMNS: &[(&str, Rollup)] = &[
+    ("sample_count", Rollup::Sum),
+    ("avg_offset_ms", Rollup::Avg("successful_queries")),
+    ("min_offset_ms", Rollup::Min),
+    ("max_offset_ms", Rollup::Max),
+    ("max_abs_offset_ms", Rollup::Max),
+    ("avg_delay_ms", Rollup::Avg("successful_queries")),
+    ("max_delay_ms", Rollup::Max),
+    ("stratum", Rollup::Any),
+    ("reference_id", Rollup::Any),
+    ("leap_indicator", Rollup::Max),
+    ("successful_queries", Rollup::Sum),
+    ("failed_queries", Rollup::Sum),
+    ("ok_count", Rollup::Sum),
+    ("warning_count", Rollup::Sum),
+    ("critical_count", Rollup::Sum),
+    ("error_count", Rollup::Sum),
+    ("host", Rollup::Any),
+    ("target_id", Rollup::Any),
+    ("maintenance", Rollup::Max),
+    ("adaptive", Rollup::Max),
+    ("upstream_down", Rollup::Max),
+    ("avg_queue_wait_ms", Rollup::Avg("sample_count")),
+    ("max_queue_wait_ms", Rollup::Max),
+    ("labels", Rollup::Any),
+];
+
+/// Create NTP aggregated metrics table
+pub(super) fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS agg_metric_ntp (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            period_start INTEGER NOT NULL,
+            period_end INTEGER NOT NULL,
+            sample_count INTEGER NOT NULL,
+            avg_offset_ms REAL NOT NULL,
+            min_offset_ms REAL NOT NULL,
+            max_offset_ms REAL NOT NULL,
+            max_abs_offset_ms REAL NOT NULL,
+            avg_delay_ms REAL NOT NULL,
+            max_delay_ms REAL NOT NULL,
+            stratum INTEGER,
+            reference_id TEXT,
+            leap_indicator INTEGER,
+            successful_queries INTEGER NOT NULL,
+            failed_queries INTEGER NOT NULL,
+            ok_count INTEGER NOT NULL,
+            warning_count INTEGER NOT NULL,
+            critical_count INTEGER NOT NULL,
+            error_count INTEGER NOT NULL,
+            host TEXT,
+            target_id TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, period_start, period_end)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create agg_metric_ntp table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agg_ntp_agent_id ON agg_metric_ntp(agent_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agg_ntp_period ON agg_metric_ntp(period_start, period_end)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agg_ntp_task ON agg_metric_ntp(task_name, period_start)",
+        [],
+    )?;
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_ntp", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "agg_metric_ntp");
+
+    Ok(())
+}
+
+/// Create NTP raw metrics table
+pub(super) fn create_raw_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS raw_metric_ntp (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            agent_row_id INTEGER,
+            timestamp INTEGER NOT NULL,
+            offset_ms REAL,
+            delay_ms REAL,
+            stratum INTEGER,
+            reference_id TEXT,
+            leap_indicator INTEGER,
+            success BOOLEAN NOT NULL,
+            error TEXT,
+            status TEXT NOT NULL DEFAULT 'ok',
+            status_message TEXT,
+            host TEXT NOT NULL,
+            target_id TEXT,
+            labels TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, timestamp, agent_row_id)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create raw_metric_ntp table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_ntp_agent_task ON raw_metric_ntp(agent_id, task_name, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_ntp_timestamp ON raw_metric_ntp(timestamp)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// Store aggregated NTP metric within a transaction
+pub(super) fn store_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &AggregatedMetrics,
+    ntp_data: &AggregatedNtpMetric,
+) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    tx.execute(
+        r#"
+        INSERT INTO agg_metric_ntp (agent_id, task_name, period_start, period_end, sample_count, avg_offset_ms, min_offset_ms, max_offset_ms, max_abs_offset_ms, avg_delay_ms, max_delay_ms, stratum, reference_id, leap_indicator, successful_queries, failed_queries, ok_count, warning_count, critical_count, error_count, host, target_id, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28)
+        "#,
+        params![
+            agent_id,
+            metric.task_name,
+            metric.period_start as i64,
+            metric.period_end as i64,
+            metric.sample_count,
+            ntp_data.avg_offset_ms,
+            ntp_data.min_offset_ms,
+            ntp_data.max_offset_ms,
+            ntp_data.max_abs_offset_ms,
+            ntp_data.avg_delay_ms,
+            ntp_data.max_delay_ms,
+            ntp_data.stratum,
+            ntp_data.reference_id,
+            ntp_data.leap_indicator,
+            ntp_data.successful_queries,
+            ntp_data.failed_queries,
+            ntp_data.ok_count,
+            ntp_data.warning_count,
+            ntp_data.critical_count,
+            ntp_data.error_count,
+            ntp_data.host,
+            ntp_data.target_id,
+            metric.maintenance,
+            metric.adaptive,
+            metric.upstream_down,
+            metric.avg_queue_wait_ms,
+            metric.max_queue_wait_ms,
+            labels,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Store raw NTP metric within a transaction
+pub(super) fn store_raw_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &MetricData,
+    ntp_data: &RawNtpMetric,
+) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    tx.execute(
+        r#"
+        INSERT OR IGNORE INTO raw_metric_ntp (agent_id, task_name, timestamp, offset_ms, delay_ms, stratum, reference_id, leap_indicator, success, error, status, status_message, host, target_id, labels, agent_row_id)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+        "#,
+        params![
+            agent_id,
+            metric.task_name,
+            metric.timestamp as i64,
+            ntp_data.offset_ms,
+            ntp_data.delay_ms,
+            ntp_data.stratum,
+            ntp_data.reference_id,
+            ntp_data.leap_indicator,
+            ntp_data.success,
+            ntp_data.error,
+            ntp_data.status.as_str(),
+            ntp_data.status_message,
+            ntp_data.host,
+            ntp_data.target_id,
+            labels,
+            metric.row_id
+        ],
+    )?;
+    Ok(())
+}
+
+/// Delete old NTP metrics
+pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM agg_metric_ntp WHERE period_end < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
+
+/// Delete old raw NTP metrics
+pub(super) fn cleanup_old_raw_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM raw_metric_ntp WHERE timestamp < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
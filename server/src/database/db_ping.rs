@@ -3,9 +3,36 @@
 //! This module handles all database operations specific to ICMP ping monitoring
 //! on the server side, including table creation, metric storage, and cleanup.
 
+use super::db_latency;
+use super::db_rollup::Rollup;
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
 use anyhow::{Context, Result};
 use rusqlite::{params, Connection, Transaction};
-use shared::metrics::{AggregatedMetrics, AggregatedPingMetric};
+use shared::metrics::{AggregatedMetrics, AggregatedPingMetric, MetricData, RawPingMetric};
+
+/// How each ping metric column is combined in rollup tiers
+pub(super) const ROLLUP_COLUMNS: &[(&str, Rollup)] = &[
+    ("sample_count", Rollup::Sum),
+    ("avg_latency_ms", Rollup::Avg("successful_pings")),
+    ("max_latency_ms", Rollup::Max),
+    ("min_latency_ms", Rollup::Min),
+    ("packet_loss_percent", Rollup::Avg("sample_count")),
+    ("successful_pings", Rollup::Sum),
+    ("failed_pings", Rollup::Sum),
+    ("domain", Rollup::Any),
+    ("target_id", Rollup::Any),
+    ("p50_ms", Rollup::HistogramPercentile(50.0)),
+    ("p90_ms", Rollup::HistogramPercentile(90.0)),
+    ("p95_ms", Rollup::HistogramPercentile(95.0)),
+    ("p99_ms", Rollup::HistogramPercentile(99.0)),
+    ("latency_histogram", Rollup::CountArray),
+    ("maintenance", Rollup::Max),
+    ("adaptive", Rollup::Max),
+    ("upstream_down", Rollup::Max),
+    ("avg_queue_wait_ms", Rollup::Avg("sample_count")),
+    ("max_queue_wait_ms", Rollup::Max),
+    ("labels", Rollup::Any),
+];
 
 /// Create ping aggregated metrics table
 pub(super) fn create_table(conn: &Connection) -> Result<()> {
@@ -26,6 +53,17 @@ pub(super) fn create_table(conn: &Connection) -> Result<()> {
             failed_pings INTEGER NOT NULL,
             domain TEXT,
             target_id TEXT,
+            p50_ms REAL,
+            p90_ms REAL,
+            p95_ms REAL,
+            p99_ms REAL,
+            latency_histogram TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
             received_at INTEGER DEFAULT (strftime('%s', 'now')),
             FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
             UNIQUE(agent_id, task_name, period_start, period_end)
@@ -48,6 +86,53 @@ pub(super) fn create_table(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Add latency distribution columns to existing tables (migration)
+    db_latency::add_columns(conn, "agg_metric_ping");
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_ping", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "agg_metric_ping");
+
+    Ok(())
+}
+
+/// Create ping raw metrics table
+pub(super) fn create_raw_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS raw_metric_ping (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            agent_row_id INTEGER,
+            timestamp INTEGER NOT NULL,
+            rtt_ms REAL,
+            success BOOLEAN NOT NULL,
+            error TEXT,
+            ip_address TEXT NOT NULL,
+            domain TEXT,
+            target_id TEXT,
+            labels TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, timestamp, agent_row_id)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create raw_metric_ping table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_ping_agent_task ON raw_metric_ping(agent_id, task_name, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_ping_timestamp ON raw_metric_ping(timestamp)",
+        [],
+    )?;
+
     Ok(())
 }
 
@@ -58,10 +143,14 @@ pub(super) fn store_metric(
     metric: &AggregatedMetrics,
     ping_data: &AggregatedPingMetric,
 ) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    let (p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram) =
+        db_latency::column_values(&ping_data.latency)?;
+
     tx.execute(
         r#"
-        INSERT INTO agg_metric_ping (agent_id, task_name, period_start, period_end, sample_count, avg_latency_ms, max_latency_ms, min_latency_ms, packet_loss_percent, successful_pings, failed_pings, domain, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+        INSERT INTO agg_metric_ping (agent_id, task_name, period_start, period_end, sample_count, avg_latency_ms, max_latency_ms, min_latency_ms, packet_loss_percent, successful_pings, failed_pings, domain, target_id, p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24)
         "#,
         params![
             agent_id,
@@ -77,6 +166,47 @@ pub(super) fn store_metric(
             ping_data.failed_pings,
             ping_data.domain,
             ping_data.target_id,
+            p50_ms,
+            p90_ms,
+            p95_ms,
+            p99_ms,
+            latency_histogram,
+            metric.maintenance,
+            metric.adaptive,
+            metric.upstream_down,
+            metric.avg_queue_wait_ms,
+            metric.max_queue_wait_ms,
+            labels,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Store raw ping metric within a transaction
+pub(super) fn store_raw_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &MetricData,
+    ping_data: &RawPingMetric,
+) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    tx.execute(
+        r#"
+        INSERT OR IGNORE INTO raw_metric_ping (agent_id, task_name, timestamp, rtt_ms, success, error, ip_address, domain, target_id, labels, agent_row_id)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+        "#,
+        params![
+            agent_id,
+            metric.task_name,
+            metric.timestamp as i64,
+            ping_data.rtt_ms,
+            ping_data.success,
+            ping_data.error,
+            ping_data.ip_address,
+            ping_data.domain,
+            ping_data.target_id,
+            labels,
+            metric.row_id
         ],
     )?;
     Ok(())
@@ -90,3 +220,12 @@ pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<us
     )?;
     Ok(deleted)
 }
+
+/// Delete old raw ping metrics
+pub(super) fn cleanup_old_raw_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM raw_metric_ping WHERE timestamp < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
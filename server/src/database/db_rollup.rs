@@ -0,0 +1,390 @@
+//! Rollup tier database operations for server
+//!
+//! Aggregated metrics arrive at the agent's aggregation window (60 seconds by
+//! default). To keep long-term trends without keeping every per-window row, each
+//! `agg_metric_*` table is rolled up into 5-minute, hourly and daily tiers
+//! (`agg_metric_ping_5m`, `agg_metric_ping_1h`, `agg_metric_ping_1d`, ...):
+//! - 5-minute rows are computed from the base table
+//! - Hourly rows are computed from 5-minute rows
+//! - Daily rows are computed from hourly rows
+//!
+//! Tier tables have the same columns as their base table. How each column is
+//! combined is declared by the task module as a list of `(column, Rollup)` pairs.
+//! Latency percentiles cannot be averaged, so tiers derive them from the merged
+//! latency histogram.
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use super::db_latency;
+
+/// Columns identifying a row rather than carrying a measurement
+const KEY_COLUMNS: [&str; 6] = [
+    "id",
+    "agent_id",
+    "task_name",
+    "period_start",
+    "period_end",
+    "received_at",
+];
+
+/// How a column is combined when rows are rolled up into a longer period
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Rollup {
+    /// Sum of the values (counters)
+    Sum,
+    /// Largest value
+    Max,
+    /// Smallest value
+    Min,
+    /// Average weighted by the named column, ignoring NULL values.
+    /// The weight column must itself be rolled up with `Sum`.
+    Avg(&'static str),
+    /// Any value of the period (for columns that are constant per task)
+    Any,
+    /// Element-wise sum of JSON arrays of counts (latency histograms)
+    CountArray,
+    /// Merge of JSON arrays of `[key, count]` pairs (status code distributions)
+    CountPairs,
+    /// Union of JSON arrays of strings (resolved addresses)
+    StringSet,
+    /// Percentile estimated from the merged `latency_histogram` column
+    HistogramPercentile(f64),
+}
+
+/// Rollup tiers, from finest to coarsest
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum RollupTier {
+    FiveMinutes,
+    Hourly,
+    Daily,
+}
+
+impl RollupTier {
+    /// All tiers in rollup order (each tier is computed from the previous one)
+    pub(super) const ALL: [RollupTier; 3] = [
+        RollupTier::FiveMinutes,
+        RollupTier::Hourly,
+        RollupTier::Daily,
+    ];
+
+    /// Length of a tier period in seconds
+    pub(super) fn period_seconds(self) -> i64 {
+        match self {
+            RollupTier::FiveMinutes => 300,
+            RollupTier::Hourly => 3600,
+            RollupTier::Daily => 86400,
+        }
+    }
+
+    /// Suffix appended to the base table name
+    pub(super) fn suffix(self) -> &'static str {
+        match self {
+            RollupTier::FiveMinutes => "5m",
+            RollupTier::Hourly => "1h",
+            RollupTier::Daily => "1d",
+        }
+    }
+
+    /// Name of this tier's table for a base table
+    pub(super) fn table_name(self, base_table: &str) -> String {
+        format!("{}_{}", base_table, self.suffix())
+    }
+
+    /// Table this tier is computed from
+    fn source_table(self, base_table: &str) -> String {
+        match self {
+            RollupTier::FiveMinutes => base_table.to_string(),
+            RollupTier::Hourly => RollupTier::FiveMinutes.table_name(base_table),
+            RollupTier::Daily => RollupTier::Hourly.table_name(base_table),
+        }
+    }
+}
+
+/// Names and declared types of a table's columns
+fn table_columns(conn: &Connection, table: &str) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let columns = stmt
+        .query_map([], |row| Ok((row.get(1)?, row.get(2)?)))?
+        .collect::<rusqlite::Result<Vec<(String, String)>>>()?;
+    Ok(columns)
+}
+
+/// Create the tier tables of a base table
+///
+/// Must run after the base table is created and migrated: tier tables copy its
+/// columns, and columns the base table gained later are added to existing tiers.
+pub(super) fn create_tables(conn: &Connection, base_table: &str) -> Result<()> {
+    let value_columns: Vec<(String, String)> = table_columns(conn, base_table)?
+        .into_iter()
+        .filter(|(name, _)| !KEY_COLUMNS.contains(&name.as_str()))
+        .collect();
+
+    for tier in RollupTier::ALL {
+        let table = tier.table_name(base_table);
+
+        // Values are nullable in tiers: an average over a period without
+        // successful samples has no value
+        let column_definitions: String = value_columns
+            .iter()
+            .map(|(name, column_type)| format!("            {} {},\n", name, column_type))
+            .collect();
+        conn.execute(
+            &format!(
+                r#"
+        CREATE TABLE IF NOT EXISTS {table} (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            period_start INTEGER NOT NULL,
+            period_end INTEGER NOT NULL,
+{column_definitions}            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            UNIQUE(agent_id, task_name, period_start, period_end)
+        )
+        "#
+            ),
+            [],
+        )
+        .with_context(|| format!("Failed to create {} table", table))?;
+
+        // Add columns the base table gained after this tier was created (migration)
+        let existing: Vec<String> = table_columns(conn, &table)?
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        for (name, column_type) in &value_columns {
+            if !existing.contains(name) {
+                conn.execute(
+                    &format!("ALTER TABLE {} ADD COLUMN {} {}", table, name, column_type),
+                    [],
+                )?;
+            }
+        }
+
+        conn.execute(
+            &format!("CREATE INDEX IF NOT EXISTS idx_{table}_agent_id ON {table}(agent_id)"),
+            [],
+        )?;
+        conn.execute(
+            &format!(
+                "CREATE INDEX IF NOT EXISTS idx_{table}_period ON {table}(period_start, period_end)"
+            ),
+            [],
+        )?;
+        conn.execute(
+            &format!(
+                "CREATE INDEX IF NOT EXISTS idx_{table}_task ON {table}(task_name, period_start)"
+            ),
+            [],
+        )?;
+    }
+
+    Ok(())
+}
+
+/// SQL expression combining a column over a rollup period
+///
+/// JSON columns are merged in Rust after the rows are written.
+fn rollup_expression(column: &str, rollup: Rollup) -> String {
+    match rollup {
+        Rollup::Sum => format!("SUM({})", column),
+        Rollup::Max | Rollup::Any => format!("MAX({})", column),
+        Rollup::Min => format!("MIN({})", column),
+        Rollup::Avg(weight) => format!(
+            "CAST(SUM({column} * {weight}) AS REAL) / NULLIF(SUM(CASE WHEN {column} IS NOT NULL THEN {weight} END), 0)"
+        ),
+        Rollup::CountArray
+        | Rollup::CountPairs
+        | Rollup::StringSet
+        | Rollup::HistogramPercentile(_) => "NULL".to_string(),
+    }
+}
+
+/// Merge JSON column values of the rows in a rollup period
+fn merge_json(rollup: Rollup, values: &[String]) -> Result<Option<String>> {
+    let merged = match rollup {
+        Rollup::CountArray => {
+            let mut totals: Vec<u64> = Vec::new();
+            for value in values {
+                let counts: Vec<u64> = serde_json::from_str(value)?;
+                if totals.len() < counts.len() {
+                    totals.resize(counts.len(), 0);
+                }
+                for (total, count) in totals.iter_mut().zip(counts) {
+                    *total += count;
+                }
+            }
+            serde_json::to_string(&totals)?
+        }
+        Rollup::CountPairs => {
+            let mut totals: BTreeMap<i64, u64> = BTreeMap::new();
+            for value in values {
+                let pairs: Vec<(i64, u64)> = serde_json::from_str(value)?;
+                for (key, count) in pairs {
+                    *totals.entry(key).or_insert(0) += count;
+                }
+            }
+            serde_json::to_string(&totals.into_iter().collect::<Vec<_>>())?
+        }
+        Rollup::StringSet => {
+            let mut union: BTreeSet<String> = BTreeSet::new();
+            for value in values {
+                let strings: Vec<String> = serde_json::from_str(value)?;
+                union.extend(strings);
+            }
+            serde_json::to_string(&union)?
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(merged))
+}
+
+/// Roll up one tier of a base table, recomputing every period starting at or after `since`
+///
+/// When the tier table is still empty, all source rows are rolled up instead so
+/// existing history is backfilled. Source rows whose period ended before
+/// `source_cutoff` may already have been purged, so periods starting before it are
+/// left as they are. Returns the number of tier rows written.
+pub(super) fn rollup_tier(
+    conn: &mut Connection,
+    base_table: &str,
+    columns: &[(&str, Rollup)],
+    tier: RollupTier,
+    since: i64,
+    source_cutoff: i64,
+) -> Result<usize> {
+    let table = tier.table_name(base_table);
+    let source = tier.source_table(base_table);
+    let period = tier.period_seconds();
+
+    let tier_is_empty: bool = conn.query_row(
+        &format!("SELECT NOT EXISTS (SELECT 1 FROM {})", table),
+        [],
+        |row| row.get(0),
+    )?;
+    let since = if tier_is_empty {
+        let oldest: Option<i64> = conn.query_row(
+            &format!("SELECT MIN(period_start) FROM {}", source),
+            [],
+            |row| row.get(0),
+        )?;
+        match oldest {
+            Some(oldest) => oldest.min(since),
+            None => return Ok(0),
+        }
+    } else {
+        since
+    };
+    // Only whole periods are recomputed, and only while all their source rows are kept:
+    // rolling up a partly purged period would replace its row with partial sums
+    let first_complete = (source_cutoff + period - 1).div_euclid(period) * period;
+    let since = (since.div_euclid(period) * period).max(first_complete);
+
+    let column_names: Vec<&str> = columns.iter().map(|(name, _)| *name).collect();
+    let expressions: Vec<String> = columns
+        .iter()
+        .map(|(name, rollup)| rollup_expression(name, *rollup))
+        .collect();
+
+    let tx = conn.transaction()?;
+    let written = tx.execute(
+        &format!(
+            r#"
+        INSERT OR REPLACE INTO {table} (agent_id, task_name, period_start, period_end, {names})
+        SELECT agent_id, task_name, (period_start / ?1) * ?1 AS rollup_start, (period_start / ?1) * ?1 + ?1, {expressions}
+        FROM {source}
+        WHERE period_start >= ?2
+        GROUP BY agent_id, task_name, rollup_start
+        "#,
+            names = column_names.join(", "),
+            expressions = expressions.join(", "),
+        ),
+        params![period, since],
+    )?;
+
+    for (column, rollup) in columns {
+        if !matches!(
+            rollup,
+            Rollup::CountArray | Rollup::CountPairs | Rollup::StringSet
+        ) {
+            continue;
+        }
+
+        let mut grouped: HashMap<(String, String, i64), Vec<String>> = HashMap::new();
+        {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT agent_id, task_name, (period_start / ?1) * ?1, {column} FROM {source} WHERE period_start >= ?2 AND {column} IS NOT NULL"
+            ))?;
+            let rows = stmt.query_map(params![period, since], |row| {
+                Ok((
+                    (row.get(0)?, row.get(1)?, row.get(2)?),
+                    row.get::<_, String>(3)?,
+                ))
+            })?;
+            for row in rows {
+                let (key, value) = row?;
+                grouped.entry(key).or_default().push(value);
+            }
+        }
+
+        let mut update = tx.prepare(&format!(
+            "UPDATE {table} SET {column} = ?1 WHERE agent_id = ?2 AND task_name = ?3 AND period_start = ?4"
+        ))?;
+        for ((agent_id, task_name, period_start), values) in grouped {
+            let merged = merge_json(*rollup, &values)
+                .with_context(|| format!("Failed to merge {}.{} for rollup", source, column))?;
+            update.execute(params![merged, agent_id, task_name, period_start])?;
+        }
+    }
+
+    let percentiles: Vec<(&str, f64)> = columns
+        .iter()
+        .filter_map(|(column, rollup)| match rollup {
+            Rollup::HistogramPercentile(percentile) => Some((*column, *percentile)),
+            _ => None,
+        })
+        .collect();
+    if !percentiles.is_empty() {
+        let histograms: Vec<(i64, String)> = {
+            let mut stmt = tx.prepare(&format!(
+                "SELECT id, latency_histogram FROM {table} WHERE period_start >= ?1 AND latency_histogram IS NOT NULL"
+            ))?;
+            let rows = stmt.query_map(params![since], |row| Ok((row.get(0)?, row.get(1)?)))?;
+            rows.collect::<rusqlite::Result<_>>()?
+        };
+
+        for (column, percentile) in percentiles {
+            let mut update =
+                tx.prepare(&format!("UPDATE {table} SET {column} = ?1 WHERE id = ?2"))?;
+            for (id, histogram) in &histograms {
+                let counts: Vec<u64> = serde_json::from_str(histogram)
+                    .with_context(|| format!("Failed to parse {}.latency_histogram", table))?;
+                update.execute(params![
+                    db_latency::histogram_percentile(&counts, percentile),
+                    id
+                ])?;
+            }
+        }
+    }
+
+    tx.commit()?;
+    Ok(written)
+}
+
+/// Delete tier rows whose period ended before the cutoff
+pub(super) fn cleanup_old_data(
+    conn: &Connection,
+    base_table: &str,
+    tier: RollupTier,
+    cutoff_time: i64,
+) -> Result<usize> {
+    let deleted = conn.execute(
+        &format!(
+            "DELETE FROM {} WHERE period_end < ?1",
+            tier.table_name(base_table)
+        ),
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
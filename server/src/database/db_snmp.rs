@@ -3,9 +3,39 @@
 //! This module handles all database operations specific to SNMP query monitoring
 //! on the server side, including table creation, metric storage, and cleanup.
 
+use super::db_latency;
+use super::db_rollup::Rollup;
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
 use anyhow::{Context, Result};
 use rusqlite::{params, Connection, Transaction};
-use shared::metrics::{AggregatedMetrics, AggregatedSnmpMetric};
+use shared::metrics::{AggregatedMetrics, AggregatedSnmpMetric, MetricData, RawSnmpMetric};
+
+/// How each SNMP metric column is combined in rollup tiers
+pub(super) const ROLLUP_COLUMNS: &[(&str, Rollup)] = &[
+    ("sample_count", Rollup::Sum),
+    ("success_rate_percent", Rollup::Avg("sample_count")),
+    ("avg_response_time_ms", Rollup::Avg("successful_queries")),
+    ("successful_queries", Rollup::Sum),
+    ("failed_queries", Rollup::Sum),
+    ("first_value", Rollup::Any),
+    ("first_value_type", Rollup::Any),
+    ("oid_queried", Rollup::Any),
+    ("target_id", Rollup::Any),
+    ("avg_value", Rollup::Avg("successful_queries")),
+    ("min_value", Rollup::Min),
+    ("max_value", Rollup::Max),
+    ("p50_ms", Rollup::HistogramPercentile(50.0)),
+    ("p90_ms", Rollup::HistogramPercentile(90.0)),
+    ("p95_ms", Rollup::HistogramPercentile(95.0)),
+    ("p99_ms", Rollup::HistogramPercentile(99.0)),
+    ("latency_histogram", Rollup::CountArray),
+    ("maintenance", Rollup::Max),
+    ("adaptive", Rollup::Max),
+    ("upstream_down", Rollup::Max),
+    ("avg_queue_wait_ms", Rollup::Avg("sample_count")),
+    ("max_queue_wait_ms", Rollup::Max),
+    ("labels", Rollup::Any),
+];
 
 /// Create SNMP aggregated metrics table
 pub(super) fn create_table(conn: &Connection) -> Result<()> {
@@ -26,6 +56,20 @@ pub(super) fn create_table(conn: &Connection) -> Result<()> {
             first_value_type TEXT,
             oid_queried TEXT NOT NULL,
             target_id TEXT,
+            avg_value REAL,
+            min_value REAL,
+            max_value REAL,
+            p50_ms REAL,
+            p90_ms REAL,
+            p95_ms REAL,
+            p99_ms REAL,
+            latency_histogram TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
             received_at INTEGER DEFAULT (strftime('%s', 'now')),
             FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
             UNIQUE(agent_id, task_name, period_start, period_end)
@@ -48,6 +92,61 @@ pub(super) fn create_table(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Add numeric value columns to existing tables (migration)
+    // SQLite doesn't support IF NOT EXISTS for ALTER TABLE, so we ignore errors
+    let _ = conn.execute("ALTER TABLE agg_metric_snmp ADD COLUMN avg_value REAL", []);
+    let _ = conn.execute("ALTER TABLE agg_metric_snmp ADD COLUMN min_value REAL", []);
+    let _ = conn.execute("ALTER TABLE agg_metric_snmp ADD COLUMN max_value REAL", []);
+
+    // Add latency distribution columns to existing tables (migration)
+    db_latency::add_columns(conn, "agg_metric_snmp");
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_snmp", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "agg_metric_snmp");
+
+    Ok(())
+}
+
+/// Create SNMP raw metrics table
+pub(super) fn create_raw_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS raw_metric_snmp (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            agent_row_id INTEGER,
+            timestamp INTEGER NOT NULL,
+            response_time_ms REAL,
+            success BOOLEAN NOT NULL,
+            value TEXT,
+            value_type TEXT,
+            oid_queried TEXT NOT NULL,
+            error TEXT,
+            target_id TEXT,
+            numeric_value REAL,
+            labels TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, timestamp, agent_row_id)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create raw_metric_snmp table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_snmp_agent_task ON raw_metric_snmp(agent_id, task_name, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_snmp_timestamp ON raw_metric_snmp(timestamp)",
+        [],
+    )?;
+
     Ok(())
 }
 
@@ -58,10 +157,14 @@ pub(super) fn store_metric(
     metric: &AggregatedMetrics,
     snmp_data: &AggregatedSnmpMetric,
 ) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    let (p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram) =
+        db_latency::column_values(&snmp_data.latency)?;
+
     tx.execute(
         r#"
-        INSERT INTO agg_metric_snmp (agent_id, task_name, period_start, period_end, sample_count, success_rate_percent, avg_response_time_ms, successful_queries, failed_queries, first_value, first_value_type, oid_queried, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+        INSERT INTO agg_metric_snmp (agent_id, task_name, period_start, period_end, sample_count, success_rate_percent, avg_response_time_ms, successful_queries, failed_queries, first_value, first_value_type, oid_queried, target_id, avg_value, min_value, max_value, p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27)
         "#,
         params![
             agent_id,
@@ -77,6 +180,52 @@ pub(super) fn store_metric(
             snmp_data.first_value_type,
             snmp_data.oid_queried,
             snmp_data.target_id,
+            snmp_data.avg_value,
+            snmp_data.min_value,
+            snmp_data.max_value,
+            p50_ms,
+            p90_ms,
+            p95_ms,
+            p99_ms,
+            latency_histogram,
+            metric.maintenance,
+            metric.adaptive,
+            metric.upstream_down,
+            metric.avg_queue_wait_ms,
+            metric.max_queue_wait_ms,
+            labels,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Store raw SNMP metric within a transaction
+pub(super) fn store_raw_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &MetricData,
+    snmp_data: &RawSnmpMetric,
+) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    tx.execute(
+        r#"
+        INSERT OR IGNORE INTO raw_metric_snmp (agent_id, task_name, timestamp, response_time_ms, success, value, value_type, oid_queried, error, target_id, numeric_value, labels, agent_row_id)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+        "#,
+        params![
+            agent_id,
+            metric.task_name,
+            metric.timestamp as i64,
+            snmp_data.response_time_ms,
+            snmp_data.success,
+            snmp_data.value,
+            snmp_data.value_type,
+            snmp_data.oid_queried,
+            snmp_data.error,
+            snmp_data.target_id,
+            snmp_data.numeric_value,
+            labels,
+            metric.row_id
         ],
     )?;
     Ok(())
@@ -90,3 +239,12 @@ pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<us
     )?;
     Ok(deleted)
 }
+
+/// Delete old raw SNMP metrics
+pub(super) fn cleanup_old_raw_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM raw_metric_snmp WHERE timestamp < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
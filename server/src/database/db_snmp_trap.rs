@@ -0,0 +1,94 @@
+//! SNMP trap/inform event database operations for server
+//!
+//! This module handles all database operations specific to SNMP notifications
+//! received by agent trap listeners, including table creation, event storage,
+//! and cleanup.
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, Transaction};
+use shared::metrics::{AggregatedMetrics, SnmpTrapEventMetric};
+
+/// Create SNMP trap events table
+pub(super) fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS snmp_trap_event (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            event_id INTEGER NOT NULL,
+            task_name TEXT NOT NULL,
+            timestamp INTEGER NOT NULL,
+            source_address TEXT NOT NULL,
+            version TEXT NOT NULL,
+            pdu_type TEXT NOT NULL,
+            trap_oid TEXT NOT NULL,
+            uptime_ticks INTEGER,
+            varbinds TEXT NOT NULL,
+            target_id TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, timestamp, event_id)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create snmp_trap_event table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_snmp_trap_agent_id ON snmp_trap_event(agent_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_snmp_trap_timestamp ON snmp_trap_event(timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_snmp_trap_oid ON snmp_trap_event(trap_oid, timestamp)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// Store an SNMP trap event within a transaction
+///
+/// Duplicate deliveries of the same agent event are ignored.
+pub(super) fn store_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &AggregatedMetrics,
+    trap_data: &SnmpTrapEventMetric,
+) -> Result<()> {
+    let varbinds_json =
+        serde_json::to_string(&trap_data.varbinds).context("Failed to serialize varbinds")?;
+
+    tx.execute(
+        r#"
+        INSERT OR IGNORE INTO snmp_trap_event (agent_id, event_id, task_name, timestamp, source_address, version, pdu_type, trap_oid, uptime_ticks, varbinds, target_id)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
+        "#,
+        params![
+            agent_id,
+            trap_data.event_id,
+            metric.task_name,
+            metric.period_start as i64,
+            trap_data.source_address,
+            trap_data.version,
+            trap_data.pdu_type,
+            trap_data.trap_oid,
+            trap_data.uptime_ticks,
+            varbinds_json,
+            trap_data.target_id,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Delete old SNMP trap events
+pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM snmp_trap_event WHERE timestamp < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
@@ -5,9 +5,46 @@
 //! Note: This module is always compiled on the server to accept metrics from
 //! agents that have the sql-tasks feature enabled.
 
+use super::db_latency;
+use super::db_rollup::Rollup;
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
 use anyhow::{Context, Result};
 use rusqlite::{params, Connection, Transaction};
-use shared::metrics::{AggregatedMetrics, AggregatedSqlQueryMetric};
+use shared::metrics::{AggregatedMetrics, AggregatedSqlQueryMetric, MetricData, RawSqlQueryMetric};
+
+/// How each SQL query metric column is combined in rollup tiers
+pub(super) const ROLLUP_COLUMNS: &[(&str, Rollup)] = &[
+    ("sample_count", Rollup::Sum),
+    ("success_rate_percent", Rollup::Avg("sample_count")),
+    ("avg_total_time_ms", Rollup::Avg("successful_queries")),
+    ("max_total_time_ms", Rollup::Max),
+    ("avg_row_count", Rollup::Avg("successful_queries")),
+    ("max_row_count", Rollup::Max),
+    ("successful_queries", Rollup::Sum),
+    ("failed_queries", Rollup::Sum),
+    ("target_id", Rollup::Any),
+    ("avg_value", Rollup::Avg("successful_queries")),
+    ("min_value", Rollup::Min),
+    ("max_value", Rollup::Max),
+    ("json_truncated_count", Rollup::Sum),
+    ("avg_connect_time_ms", Rollup::Avg("successful_queries")),
+    ("avg_query_time_ms", Rollup::Avg("successful_queries")),
+    ("ok_count", Rollup::Sum),
+    ("warning_count", Rollup::Sum),
+    ("critical_count", Rollup::Sum),
+    ("error_count", Rollup::Sum),
+    ("p50_ms", Rollup::HistogramPercentile(50.0)),
+    ("p90_ms", Rollup::HistogramPercentile(90.0)),
+    ("p95_ms", Rollup::HistogramPercentile(95.0)),
+    ("p99_ms", Rollup::HistogramPercentile(99.0)),
+    ("latency_histogram", Rollup::CountArray),
+    ("maintenance", Rollup::Max),
+    ("adaptive", Rollup::Max),
+    ("upstream_down", Rollup::Max),
+    ("avg_queue_wait_ms", Rollup::Avg("sample_count")),
+    ("max_queue_wait_ms", Rollup::Max),
+    ("labels", Rollup::Any),
+];
 
 /// Create SQL query aggregated metrics table
 pub(super) fn create_table(conn: &Connection) -> Result<()> {
@@ -32,6 +69,23 @@ pub(super) fn create_table(conn: &Connection) -> Result<()> {
             min_value REAL,
             max_value REAL,
             json_truncated_count INTEGER NOT NULL DEFAULT 0,
+            avg_connect_time_ms REAL,
+            avg_query_time_ms REAL,
+            ok_count INTEGER NOT NULL DEFAULT 0,
+            warning_count INTEGER NOT NULL DEFAULT 0,
+            critical_count INTEGER NOT NULL DEFAULT 0,
+            error_count INTEGER NOT NULL DEFAULT 0,
+            p50_ms REAL,
+            p90_ms REAL,
+            p95_ms REAL,
+            p99_ms REAL,
+            latency_histogram TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
             received_at INTEGER DEFAULT (strftime('%s', 'now')),
             FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
             UNIQUE(agent_id, task_name, period_start, period_end)
@@ -68,6 +122,78 @@ pub(super) fn create_table(conn: &Connection) -> Result<()> {
         [],
     );
     let _ = conn.execute("ALTER TABLE agg_metric_sql_query ADD COLUMN json_truncated_count INTEGER NOT NULL DEFAULT 0", []);
+    let _ = conn.execute(
+        "ALTER TABLE agg_metric_sql_query ADD COLUMN avg_connect_time_ms REAL",
+        [],
+    );
+    let _ = conn.execute(
+        "ALTER TABLE agg_metric_sql_query ADD COLUMN avg_query_time_ms REAL",
+        [],
+    );
+    for column in ["ok_count", "warning_count", "critical_count", "error_count"] {
+        let _ = conn.execute(
+            &format!(
+                "ALTER TABLE agg_metric_sql_query ADD COLUMN {} INTEGER NOT NULL DEFAULT 0",
+                column
+            ),
+            [],
+        );
+    }
+
+    // Add latency distribution columns to existing tables (migration)
+    db_latency::add_columns(conn, "agg_metric_sql_query");
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_sql_query", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "agg_metric_sql_query");
+
+    Ok(())
+}
+
+/// Create SQL query raw metrics table
+pub(super) fn create_raw_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS raw_metric_sql_query (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            agent_row_id INTEGER,
+            timestamp INTEGER NOT NULL,
+            total_time_ms REAL,
+            row_count INTEGER,
+            success BOOLEAN NOT NULL,
+            error TEXT,
+            target_id TEXT,
+            mode TEXT NOT NULL DEFAULT 'value',
+            value REAL,
+            json_result TEXT,
+            json_truncated BOOLEAN NOT NULL DEFAULT 0,
+            column_count INTEGER,
+            connect_time_ms REAL,
+            query_time_ms REAL,
+            status TEXT NOT NULL DEFAULT 'ok',
+            status_message TEXT,
+            labels TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, timestamp, agent_row_id)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create raw_metric_sql_query table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_sql_query_agent_task ON raw_metric_sql_query(agent_id, task_name, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_sql_query_timestamp ON raw_metric_sql_query(timestamp)",
+        [],
+    )?;
 
     Ok(())
 }
@@ -79,13 +205,19 @@ pub(super) fn store_metric(
     metric: &AggregatedMetrics,
     sql_data: &AggregatedSqlQueryMetric,
 ) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    let (p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram) =
+        db_latency::column_values(&sql_data.latency)?;
+
     tx.execute(
         r#"
         INSERT INTO agg_metric_sql_query
         (agent_id, task_name, period_start, period_end, sample_count, success_rate_percent,
          avg_total_time_ms, max_total_time_ms, avg_row_count, max_row_count,
-         successful_queries, failed_queries, target_id, avg_value, min_value, max_value, json_truncated_count)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
+         successful_queries, failed_queries, target_id, avg_value, min_value, max_value, json_truncated_count,
+         avg_connect_time_ms, avg_query_time_ms, ok_count, warning_count, critical_count, error_count,
+         p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28, ?29, ?30, ?31, ?32, ?33, ?34)
         "#,
         params![
             agent_id,
@@ -105,6 +237,61 @@ pub(super) fn store_metric(
             sql_data.min_value,
             sql_data.max_value,
             sql_data.json_truncated_count,
+            sql_data.avg_connect_time_ms,
+            sql_data.avg_query_time_ms,
+            sql_data.ok_count,
+            sql_data.warning_count,
+            sql_data.critical_count,
+            sql_data.error_count,
+            p50_ms,
+            p90_ms,
+            p95_ms,
+            p99_ms,
+            latency_histogram,
+            metric.maintenance,
+            metric.adaptive,
+            metric.upstream_down,
+            metric.avg_queue_wait_ms,
+            metric.max_queue_wait_ms,
+            labels,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Store raw SQL query metric within a transaction
+pub(super) fn store_raw_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &MetricData,
+    sql_data: &RawSqlQueryMetric,
+) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    tx.execute(
+        r#"
+        INSERT OR IGNORE INTO raw_metric_sql_query (agent_id, task_name, timestamp, total_time_ms, row_count, success, error, target_id, mode, value, json_result, json_truncated, column_count, connect_time_ms, query_time_ms, status, status_message, labels, agent_row_id)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19)
+        "#,
+        params![
+            agent_id,
+            metric.task_name,
+            metric.timestamp as i64,
+            sql_data.total_time_ms,
+            sql_data.row_count.map(|c| c as i64),
+            sql_data.success,
+            sql_data.error,
+            sql_data.target_id,
+            sql_data.mode.as_str(),
+            sql_data.value,
+            sql_data.json_result,
+            sql_data.json_truncated,
+            sql_data.column_count,
+            sql_data.connect_time_ms,
+            sql_data.query_time_ms,
+            sql_data.status.as_str(),
+            sql_data.status_message,
+            labels,
+            metric.row_id,
         ],
     )?;
     Ok(())
@@ -118,3 +305,12 @@ pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<us
     )?;
     Ok(deleted)
 }
+
+/// Delete old raw SQL query metrics
+pub(super) fn cleanup_old_raw_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM raw_metric_sql_query WHERE timestamp < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
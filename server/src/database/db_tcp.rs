@@ -3,9 +3,36 @@
 //! This module handles all database operations specific to TCP connection monitoring
 //! on the server side, including table creation, metric storage, and cleanup.
 
+use super::db_latency;
+use super::db_rollup::Rollup;
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
 use anyhow::{Context, Result};
 use rusqlite::{params, Connection, Transaction};
-use shared::metrics::{AggregatedMetrics, AggregatedTcpMetric};
+use shared::metrics::{AggregatedMetrics, AggregatedTcpMetric, MetricData, RawTcpMetric};
+
+/// How each TCP metric column is combined in rollup tiers
+pub(super) const ROLLUP_COLUMNS: &[(&str, Rollup)] = &[
+    ("sample_count", Rollup::Sum),
+    ("avg_connect_time_ms", Rollup::Avg("sample_count")),
+    ("max_connect_time_ms", Rollup::Max),
+    ("min_connect_time_ms", Rollup::Min),
+    ("failure_percent", Rollup::Avg("sample_count")),
+    ("successful_connections", Rollup::Sum),
+    ("failed_connections", Rollup::Sum),
+    ("host", Rollup::Any),
+    ("target_id", Rollup::Any),
+    ("p50_ms", Rollup::HistogramPercentile(50.0)),
+    ("p90_ms", Rollup::HistogramPercentile(90.0)),
+    ("p95_ms", Rollup::HistogramPercentile(95.0)),
+    ("p99_ms", Rollup::HistogramPercentile(99.0)),
+    ("latency_histogram", Rollup::CountArray),
+    ("maintenance", Rollup::Max),
+    ("adaptive", Rollup::Max),
+    ("upstream_down", Rollup::Max),
+    ("avg_queue_wait_ms", Rollup::Avg("sample_count")),
+    ("max_queue_wait_ms", Rollup::Max),
+    ("labels", Rollup::Any),
+];
 
 /// Create TCP aggregated metrics table
 pub(super) fn create_table(conn: &Connection) -> Result<()> {
@@ -26,6 +53,17 @@ pub(super) fn create_table(conn: &Connection) -> Result<()> {
             failed_connections INTEGER NOT NULL,
             host TEXT,
             target_id TEXT,
+            p50_ms REAL,
+            p90_ms REAL,
+            p95_ms REAL,
+            p99_ms REAL,
+            latency_histogram TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
             received_at INTEGER DEFAULT (strftime('%s', 'now')),
             FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
             UNIQUE(agent_id, task_name, period_start, period_end)
@@ -48,6 +86,54 @@ pub(super) fn create_table(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Add latency distribution columns to existing tables (migration)
+    db_latency::add_columns(conn, "agg_metric_tcp");
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_tcp", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "agg_metric_tcp");
+
+    Ok(())
+}
+
+/// Create TCP raw metrics table
+pub(super) fn create_raw_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS raw_metric_tcp (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            agent_row_id INTEGER,
+            timestamp INTEGER NOT NULL,
+            connect_time_ms REAL,
+            response_time_ms REAL,
+            expect_matched BOOLEAN,
+            success BOOLEAN NOT NULL,
+            error TEXT,
+            host TEXT NOT NULL,
+            target_id TEXT,
+            labels TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, timestamp, agent_row_id)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create raw_metric_tcp table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_tcp_agent_task ON raw_metric_tcp(agent_id, task_name, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_tcp_timestamp ON raw_metric_tcp(timestamp)",
+        [],
+    )?;
+
     Ok(())
 }
 
@@ -58,10 +144,14 @@ pub(super) fn store_metric(
     metric: &AggregatedMetrics,
     tcp_data: &AggregatedTcpMetric,
 ) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    let (p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram) =
+        db_latency::column_values(&tcp_data.latency)?;
+
     tx.execute(
         r#"
-        INSERT INTO agg_metric_tcp (agent_id, task_name, period_start, period_end, sample_count, avg_connect_time_ms, max_connect_time_ms, min_connect_time_ms, failure_percent, successful_connections, failed_connections, host, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+        INSERT INTO agg_metric_tcp (agent_id, task_name, period_start, period_end, sample_count, avg_connect_time_ms, max_connect_time_ms, min_connect_time_ms, failure_percent, successful_connections, failed_connections, host, target_id, p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24)
         "#,
         params![
             agent_id,
@@ -77,6 +167,48 @@ pub(super) fn store_metric(
             tcp_data.failed_connections,
             tcp_data.host,
             tcp_data.target_id,
+            p50_ms,
+            p90_ms,
+            p95_ms,
+            p99_ms,
+            latency_histogram,
+            metric.maintenance,
+            metric.adaptive,
+            metric.upstream_down,
+            metric.avg_queue_wait_ms,
+            metric.max_queue_wait_ms,
+            labels,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Store raw TCP metric within a transaction
+pub(super) fn store_raw_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &MetricData,
+    tcp_data: &RawTcpMetric,
+) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    tx.execute(
+        r#"
+        INSERT OR IGNORE INTO raw_metric_tcp (agent_id, task_name, timestamp, connect_time_ms, response_time_ms, expect_matched, success, error, host, target_id, labels, agent_row_id)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        "#,
+        params![
+            agent_id,
+            metric.task_name,
+            metric.timestamp as i64,
+            tcp_data.connect_time_ms,
+            tcp_data.response_time_ms,
+            tcp_data.expect_matched,
+            tcp_data.success,
+            tcp_data.error,
+            tcp_data.host,
+            tcp_data.target_id,
+            labels,
+            metric.row_id
         ],
     )?;
     Ok(())
@@ -90,3 +222,12 @@ pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<us
     )?;
     Ok(deleted)
 }
+
+/// Delete old raw TCP metrics
+pub(super) fn cleanup_old_raw_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM raw_metric_tcp WHERE timestamp < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
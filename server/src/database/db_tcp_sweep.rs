@@ -0,0 +1,238 @@
+//! TCP sweep task database operations for server
+//!
+//! This module handles all database operations specific to TCP sweep monitoring
+//! on the server side, including table creation, metric storage, and cleanup.
+
+use super::db_rollup::Rollup;
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, Transaction};
+use shared::metrics::{AggregatedMetrics, AggregatedTcpSweepMetric, MetricData, RawTcpSweepMetric};
+
+/// How each TCP sweep metric column is combined in rollup tiers
+pub(super) const ROLLUP_COLUMNS: &[(&str, Rollup)] = &[
+    ("sample_count", Rollup::Sum),
+    ("targets_total", Rollup::Max),
+    ("avg_open_count", Rollup::Avg("successful_sweeps")),
+    ("min_open_count", Rollup::Min),
+    ("avg_closed_count", Rollup::Avg("successful_sweeps")),
+    ("avg_filtered_count", Rollup::Avg("successful_sweeps")),
+    ("avg_unresolved_count", Rollup::Avg("successful_sweeps")),
+    ("avg_connect_time_ms", Rollup::Avg("successful_sweeps")),
+    ("max_connect_time_ms", Rollup::Max),
+    ("min_connect_time_ms", Rollup::Min),
+    ("successful_sweeps", Rollup::Sum),
+    ("failed_sweeps", Rollup::Sum),
+    ("targets", Rollup::Any),
+    ("target_id", Rollup::Any),
+    ("maintenance", Rollup::Max),
+    ("adaptive", Rollup::Max),
+    ("upstream_down", Rollup::Max),
+    ("avg_queue_wait_ms", Rollup::Avg("sample_count")),
+    ("max_queue_wait_ms", Rollup::Max),
+    ("labels", Rollup::Any),
+];
+
+/// Create TCP sweep aggregated metrics table
+pub(super) fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS agg_metric_tcp_sweep (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            period_start INTEGER NOT NULL,
+            period_end INTEGER NOT NULL,
+            sample_count INTEGER NOT NULL,
+            targets_total INTEGER NOT NULL,
+            avg_open_count REAL NOT NULL,
+            min_open_count INTEGER NOT NULL,
+            avg_closed_count REAL NOT NULL,
+            avg_filtered_count REAL NOT NULL,
+            avg_unresolved_count REAL NOT NULL,
+            avg_connect_time_ms REAL NOT NULL,
+            max_connect_time_ms REAL NOT NULL,
+            min_connect_time_ms REAL NOT NULL,
+            successful_sweeps INTEGER NOT NULL,
+            failed_sweeps INTEGER NOT NULL,
+            targets TEXT NOT NULL,
+            target_id TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, period_start, period_end)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create agg_metric_tcp_sweep table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agg_tcp_sweep_agent_id ON agg_metric_tcp_sweep(agent_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agg_tcp_sweep_period ON agg_metric_tcp_sweep(period_start, period_end)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agg_tcp_sweep_task ON agg_metric_tcp_sweep(task_name, period_start)",
+        [],
+    )?;
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_tcp_sweep", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "agg_metric_tcp_sweep");
+
+    Ok(())
+}
+
+/// Create TCP sweep raw metrics table
+pub(super) fn create_raw_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS raw_metric_tcp_sweep (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            agent_row_id INTEGER,
+            timestamp INTEGER NOT NULL,
+            targets_total INTEGER NOT NULL,
+            open_count INTEGER NOT NULL,
+            closed_count INTEGER NOT NULL,
+            filtered_count INTEGER NOT NULL,
+            unresolved_count INTEGER NOT NULL,
+            avg_connect_time_ms REAL,
+            min_connect_time_ms REAL,
+            max_connect_time_ms REAL,
+            duration_ms REAL NOT NULL,
+            results TEXT NOT NULL,
+            success BOOLEAN NOT NULL,
+            error TEXT,
+            target_id TEXT,
+            labels TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, timestamp, agent_row_id)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create raw_metric_tcp_sweep table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_tcp_sweep_agent_task ON raw_metric_tcp_sweep(agent_id, task_name, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_tcp_sweep_timestamp ON raw_metric_tcp_sweep(timestamp)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// Store aggregated TCP sweep metric within a transaction
+pub(super) fn store_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &AggregatedMetrics,
+    sweep_data: &AggregatedTcpSweepMetric,
+) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    tx.execute(
+        r#"
+        INSERT INTO agg_metric_tcp_sweep (agent_id, task_name, period_start, period_end, sample_count, targets_total, avg_open_count, min_open_count, avg_closed_count, avg_filtered_count, avg_unresolved_count, avg_connect_time_ms, max_connect_time_ms, min_connect_time_ms, successful_sweeps, failed_sweeps, targets, target_id, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24)
+        "#,
+        params![
+            agent_id,
+            metric.task_name,
+            metric.period_start as i64,
+            metric.period_end as i64,
+            metric.sample_count,
+            sweep_data.targets_total,
+            sweep_data.avg_open_count,
+            sweep_data.min_open_count,
+            sweep_data.avg_closed_count,
+            sweep_data.avg_filtered_count,
+            sweep_data.avg_unresolved_count,
+            sweep_data.avg_connect_time_ms,
+            sweep_data.max_connect_time_ms,
+            sweep_data.min_connect_time_ms,
+            sweep_data.successful_sweeps,
+            sweep_data.failed_sweeps,
+            serde_json::to_string(&sweep_data.targets)?,
+            sweep_data.target_id,
+            metric.maintenance,
+            metric.adaptive,
+            metric.upstream_down,
+            metric.avg_queue_wait_ms,
+            metric.max_queue_wait_ms,
+            labels,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Store raw TCP sweep metric within a transaction
+pub(super) fn store_raw_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &MetricData,
+    sweep_data: &RawTcpSweepMetric,
+) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    tx.execute(
+        r#"
+        INSERT OR IGNORE INTO raw_metric_tcp_sweep (agent_id, task_name, timestamp, targets_total, open_count, closed_count, filtered_count, unresolved_count, avg_connect_time_ms, min_connect_time_ms, max_connect_time_ms, duration_ms, results, success, error, target_id, labels, agent_row_id)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)
+        "#,
+        params![
+            agent_id,
+            metric.task_name,
+            metric.timestamp as i64,
+            sweep_data.targets_total,
+            sweep_data.open_count,
+            sweep_data.closed_count,
+            sweep_data.filtered_count,
+            sweep_data.unresolved_count,
+            sweep_data.avg_connect_time_ms,
+            sweep_data.min_connect_time_ms,
+            sweep_data.max_connect_time_ms,
+            sweep_data.duration_ms,
+            serde_json::to_string(&sweep_data.results)?,
+            sweep_data.success,
+            sweep_data.error,
+            sweep_data.target_id,
+            labels,
+            metric.row_id
+        ],
+    )?;
+    Ok(())
+}
+
+/// Delete old TCP sweep metrics
+pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM agg_metric_tcp_sweep WHERE period_end < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
+
+/// Delete old raw TCP sweep metrics
+pub(super) fn cleanup_old_raw_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM raw_metric_tcp_sweep WHERE timestamp < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
@@ -3,9 +3,39 @@
 //! This module handles all database operations specific to TLS handshake monitoring
 //! on the server side, including table creation, metric storage, and cleanup.
 
+use super::db_latency;
+use super::db_rollup::Rollup;
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
 use anyhow::{Context, Result};
 use rusqlite::{params, Connection, Transaction};
-use shared::metrics::{AggregatedMetrics, AggregatedTlsMetric};
+use shared::metrics::{AggregatedMetrics, AggregatedTlsMetric, MetricData, RawTlsMetric};
+
+/// How each TLS metric column is combined in rollup tiers
+pub(super) const ROLLUP_COLUMNS: &[(&str, Rollup)] = &[
+    ("sample_count", Rollup::Sum),
+    ("success_rate_percent", Rollup::Avg("sample_count")),
+    ("avg_tcp_timing_ms", Rollup::Avg("successful_checks")),
+    ("avg_tls_timing_ms", Rollup::Avg("successful_checks")),
+    ("successful_checks", Rollup::Sum),
+    ("failed_checks", Rollup::Sum),
+    ("ssl_valid_percent", Rollup::Avg("sample_count")),
+    (
+        "avg_ssl_cert_days_until_expiry",
+        Rollup::Avg("successful_checks"),
+    ),
+    ("target_id", Rollup::Any),
+    ("p50_ms", Rollup::HistogramPercentile(50.0)),
+    ("p90_ms", Rollup::HistogramPercentile(90.0)),
+    ("p95_ms", Rollup::HistogramPercentile(95.0)),
+    ("p99_ms", Rollup::HistogramPercentile(99.0)),
+    ("latency_histogram", Rollup::CountArray),
+    ("maintenance", Rollup::Max),
+    ("adaptive", Rollup::Max),
+    ("upstream_down", Rollup::Max),
+    ("avg_queue_wait_ms", Rollup::Avg("sample_count")),
+    ("max_queue_wait_ms", Rollup::Max),
+    ("labels", Rollup::Any),
+];
 
 /// Create TLS handshake aggregated metrics table
 pub(super) fn create_table(conn: &Connection) -> Result<()> {
@@ -26,6 +56,17 @@ pub(super) fn create_table(conn: &Connection) -> Result<()> {
             ssl_valid_percent REAL,
             avg_ssl_cert_days_until_expiry REAL,
             target_id TEXT,
+            p50_ms REAL,
+            p90_ms REAL,
+            p95_ms REAL,
+            p99_ms REAL,
+            latency_histogram TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
             received_at INTEGER DEFAULT (strftime('%s', 'now')),
             FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
             UNIQUE(agent_id, task_name, period_start, period_end)
@@ -48,6 +89,54 @@ pub(super) fn create_table(conn: &Connection) -> Result<()> {
         [],
     )?;
 
+    // Add latency distribution columns to existing tables (migration)
+    db_latency::add_columns(conn, "agg_metric_tls");
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_tls", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "agg_metric_tls");
+
+    Ok(())
+}
+
+/// Create TLS raw metrics table
+pub(super) fn create_raw_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS raw_metric_tls (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            agent_row_id INTEGER,
+            timestamp INTEGER NOT NULL,
+            tcp_timing_ms REAL,
+            tls_timing_ms REAL,
+            ssl_valid BOOLEAN,
+            ssl_cert_days_until_expiry INTEGER,
+            success BOOLEAN NOT NULL,
+            error TEXT,
+            target_id TEXT,
+            labels TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, timestamp, agent_row_id)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create raw_metric_tls table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_tls_agent_task ON raw_metric_tls(agent_id, task_name, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_tls_timestamp ON raw_metric_tls(timestamp)",
+        [],
+    )?;
+
     Ok(())
 }
 
@@ -58,10 +147,14 @@ pub(super) fn store_metric(
     metric: &AggregatedMetrics,
     tls_data: &AggregatedTlsMetric,
 ) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    let (p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram) =
+        db_latency::column_values(&tls_data.latency)?;
+
     tx.execute(
         r#"
-        INSERT INTO agg_metric_tls (agent_id, task_name, period_start, period_end, sample_count, success_rate_percent, avg_tcp_timing_ms, avg_tls_timing_ms, successful_checks, failed_checks, ssl_valid_percent, avg_ssl_cert_days_until_expiry, target_id)
-        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+        INSERT INTO agg_metric_tls (agent_id, task_name, period_start, period_end, sample_count, success_rate_percent, avg_tcp_timing_ms, avg_tls_timing_ms, successful_checks, failed_checks, ssl_valid_percent, avg_ssl_cert_days_until_expiry, target_id, p50_ms, p90_ms, p95_ms, p99_ms, latency_histogram, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24)
         "#,
         params![
             agent_id,
@@ -77,6 +170,48 @@ pub(super) fn store_metric(
             tls_data.ssl_valid_percent,
             tls_data.avg_ssl_cert_days_until_expiry,
             tls_data.target_id,
+            p50_ms,
+            p90_ms,
+            p95_ms,
+            p99_ms,
+            latency_histogram,
+            metric.maintenance,
+            metric.adaptive,
+            metric.upstream_down,
+            metric.avg_queue_wait_ms,
+            metric.max_queue_wait_ms,
+            labels,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Store raw TLS metric within a transaction
+pub(super) fn store_raw_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &MetricData,
+    tls_data: &RawTlsMetric,
+) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    tx.execute(
+        r#"
+        INSERT OR IGNORE INTO raw_metric_tls (agent_id, task_name, timestamp, tcp_timing_ms, tls_timing_ms, ssl_valid, ssl_cert_days_until_expiry, success, error, target_id, labels, agent_row_id)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+        "#,
+        params![
+            agent_id,
+            metric.task_name,
+            metric.timestamp as i64,
+            tls_data.tcp_timing_ms,
+            tls_data.tls_timing_ms,
+            tls_data.ssl_valid,
+            tls_data.ssl_cert_days_until_expiry,
+            tls_data.success,
+            tls_data.error,
+            tls_data.target_id,
+            labels,
+            metric.row_id
         ],
     )?;
     Ok(())
@@ -90,3 +225,12 @@ pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<us
     )?;
     Ok(deleted)
 }
+
+/// Delete old raw TLS metrics
+pub(super) fn cleanup_old_raw_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM raw_metric_tls WHERE timestamp < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
@@ -0,0 +1,231 @@
+//! TWAMP-Light task database operations for server
+//!
+//! This module handles all database operations specific to TWAMP-Light monitoring
+//! on the server side, including table creation, metric storage, and cleanup.
+
+use super::db_rollup::Rollup;
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, Transaction};
+use shared::metrics::{AggregatedMetrics, AggregatedTwampMetric, MetricData, RawTwampMetric};
+
+/// How each TWAMP metric column is combined in rollup tiers
+pub(super) const ROLLUP_COLUMNS: &[(&str, Rollup)] = &[
+    ("sample_count", Rollup::Sum),
+    ("avg_latency_ms", Rollup::Avg("successful_tests")),
+    ("max_latency_ms", Rollup::Max),
+    ("min_latency_ms", Rollup::Min),
+    ("packet_loss_percent", Rollup::Avg("packets_sent")),
+    ("avg_jitter_ms", Rollup::Avg("successful_tests")),
+    ("max_jitter_ms", Rollup::Max),
+    ("packets_sent", Rollup::Sum),
+    ("packets_received", Rollup::Sum),
+    ("successful_tests", Rollup::Sum),
+    ("failed_tests", Rollup::Sum),
+    ("host", Rollup::Any),
+    ("target_id", Rollup::Any),
+    ("maintenance", Rollup::Max),
+    ("adaptive", Rollup::Max),
+    ("upstream_down", Rollup::Max),
+    ("avg_queue_wait_ms", Rollup::Avg("sample_count")),
+    ("max_queue_wait_ms", Rollup::Max),
+    ("labels", Rollup::Any),
+];
+
+/// Create TWAMP aggregated metrics table
+pub(super) fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS agg_metric_twamp (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            period_start INTEGER NOT NULL,
+            period_end INTEGER NOT NULL,
+            sample_count INTEGER NOT NULL,
+            avg_latency_ms REAL NOT NULL,
+            max_latency_ms REAL NOT NULL,
+            min_latency_ms REAL NOT NULL,
+            packet_loss_percent REAL NOT NULL,
+            avg_jitter_ms REAL NOT NULL,
+            max_jitter_ms REAL NOT NULL,
+            packets_sent INTEGER NOT NULL,
+            packets_received INTEGER NOT NULL,
+            successful_tests INTEGER NOT NULL,
+            failed_tests INTEGER NOT NULL,
+            host TEXT,
+            target_id TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, period_start, period_end)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create agg_metric_twamp table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agg_twamp_agent_id ON agg_metric_twamp(agent_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agg_twamp_period ON agg_metric_twamp(period_start, period_end)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agg_twamp_task ON agg_metric_twamp(task_name, period_start)",
+        [],
+    )?;
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_twamp", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "agg_metric_twamp");
+
+    Ok(())
+}
+
+/// Create TWAMP raw metrics table
+pub(super) fn create_raw_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS raw_metric_twamp (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            agent_row_id INTEGER,
+            timestamp INTEGER NOT NULL,
+            packets_sent INTEGER NOT NULL,
+            packets_received INTEGER NOT NULL,
+            packet_loss_percent REAL NOT NULL,
+            avg_latency_ms REAL,
+            min_latency_ms REAL,
+            max_latency_ms REAL,
+            jitter_ms REAL,
+            success BOOLEAN NOT NULL,
+            error TEXT,
+            host TEXT NOT NULL,
+            target_id TEXT,
+            labels TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, timestamp, agent_row_id)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create raw_metric_twamp table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_twamp_agent_task ON raw_metric_twamp(agent_id, task_name, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_twamp_timestamp ON raw_metric_twamp(timestamp)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// Store aggregated TWAMP metric within a transaction
+pub(super) fn store_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &AggregatedMetrics,
+    twamp_data: &AggregatedTwampMetric,
+) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    tx.execute(
+        r#"
+        INSERT INTO agg_metric_twamp (agent_id, task_name, period_start, period_end, sample_count, avg_latency_ms, max_latency_ms, min_latency_ms, packet_loss_percent, avg_jitter_ms, max_jitter_ms, packets_sent, packets_received, successful_tests, failed_tests, host, target_id, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23)
+        "#,
+        params![
+            agent_id,
+            metric.task_name,
+            metric.period_start as i64,
+            metric.period_end as i64,
+            metric.sample_count,
+            twamp_data.avg_latency_ms,
+            twamp_data.max_latency_ms,
+            twamp_data.min_latency_ms,
+            twamp_data.packet_loss_percent,
+            twamp_data.avg_jitter_ms,
+            twamp_data.max_jitter_ms,
+            twamp_data.packets_sent,
+            twamp_data.packets_received,
+            twamp_data.successful_tests,
+            twamp_data.failed_tests,
+            twamp_data.host,
+            twamp_data.target_id,
+            metric.maintenance,
+            metric.adaptive,
+            metric.upstream_down,
+            metric.avg_queue_wait_ms,
+            metric.max_queue_wait_ms,
+            labels,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Store raw TWAMP metric within a transaction
+pub(super) fn store_raw_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &MetricData,
+    twamp_data: &RawTwampMetric,
+) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    tx.execute(
+        r#"
+        INSERT OR IGNORE INTO raw_metric_twamp (agent_id, task_name, timestamp, packets_sent, packets_received, packet_loss_percent, avg_latency_ms, min_latency_ms, max_latency_ms, jitter_ms, success, error, host, target_id, labels, agent_row_id)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
+        "#,
+        params![
+            agent_id,
+            metric.task_name,
+            metric.timestamp as i64,
+            twamp_data.packets_sent,
+            twamp_data.packets_received,
+            twamp_data.packet_loss_percent,
+            twamp_data.avg_latency_ms,
+            twamp_data.min_latency_ms,
+            twamp_data.max_latency_ms,
+            twamp_data.jitter_ms,
+            twamp_data.success,
+            twamp_data.error,
+            twamp_data.host,
+            twamp_data.target_id,
+            labels,
+            metric.row_id
+        ],
+    )?;
+    Ok(())
+}
+
+/// Delete old TWAMP metrics
+pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM agg_metric_twamp WHERE period_end < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
+
+/// Delete old raw TWAMP metrics
+pub(super) fn cleanup_old_raw_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM raw_metric_twamp WHERE timestamp < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
@@ -0,0 +1,256 @@
+//! UDP probe task database operations for server
+//!
+//! This module handles all database operations specific to UDP probe monitoring
+//! on the server side, including table creation, metric storage, and cleanup.
+
+use super::db_rollup::Rollup;
+use super::{add_columns, db_labels, ANNOTATION_COLUMNS};
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection, Transaction};
+use shared::metrics::{AggregatedMetrics, AggregatedUdpProbeMetric, MetricData, RawUdpProbeMetric};
+
+/// How each UDP probe metric column is combined in rollup tiers
+pub(super) const ROLLUP_COLUMNS: &[(&str, Rollup)] = &[
+    ("sample_count", Rollup::Sum),
+    ("avg_rtt_ms", Rollup::Avg("successful_probes")),
+    ("max_rtt_ms", Rollup::Max),
+    ("min_rtt_ms", Rollup::Min),
+    ("avg_jitter_ms", Rollup::Avg("successful_probes")),
+    ("max_jitter_ms", Rollup::Max),
+    ("loss_percent", Rollup::Avg("packets_sent")),
+    ("forward_loss_percent", Rollup::Avg("packets_sent")),
+    ("return_loss_percent", Rollup::Avg("packets_sent")),
+    ("packets_sent", Rollup::Sum),
+    ("packets_received", Rollup::Sum),
+    ("duplicate_packets", Rollup::Sum),
+    ("reordered_packets", Rollup::Sum),
+    ("mos", Rollup::Avg("successful_probes")),
+    ("successful_probes", Rollup::Sum),
+    ("failed_probes", Rollup::Sum),
+    ("host", Rollup::Any),
+    ("target_id", Rollup::Any),
+    ("maintenance", Rollup::Max),
+    ("adaptive", Rollup::Max),
+    ("upstream_down", Rollup::Max),
+    ("avg_queue_wait_ms", Rollup::Avg("sample_count")),
+    ("max_queue_wait_ms", Rollup::Max),
+    ("labels", Rollup::Any),
+];
+
+/// Create UDP probe aggregated metrics table
+pub(super) fn create_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS agg_metric_udp_probe (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            period_start INTEGER NOT NULL,
+            period_end INTEGER NOT NULL,
+            sample_count INTEGER NOT NULL,
+            avg_rtt_ms REAL NOT NULL,
+            max_rtt_ms REAL NOT NULL,
+            min_rtt_ms REAL NOT NULL,
+            avg_jitter_ms REAL NOT NULL,
+            max_jitter_ms REAL NOT NULL,
+            loss_percent REAL NOT NULL,
+            forward_loss_percent REAL,
+            return_loss_percent REAL,
+            packets_sent INTEGER NOT NULL,
+            packets_received INTEGER NOT NULL,
+            duplicate_packets INTEGER NOT NULL,
+            reordered_packets INTEGER NOT NULL,
+            mos REAL NOT NULL,
+            successful_probes INTEGER NOT NULL,
+            failed_probes INTEGER NOT NULL,
+            host TEXT,
+            target_id TEXT,
+            maintenance BOOLEAN NOT NULL DEFAULT 0,
+            adaptive BOOLEAN NOT NULL DEFAULT 0,
+            upstream_down BOOLEAN NOT NULL DEFAULT 0,
+            avg_queue_wait_ms REAL,
+            max_queue_wait_ms REAL,
+            labels TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, period_start, period_end)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create agg_metric_udp_probe table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agg_udp_probe_agent_id ON agg_metric_udp_probe(agent_id)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agg_udp_probe_period ON agg_metric_udp_probe(period_start, period_end)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_agg_udp_probe_task ON agg_metric_udp_probe(task_name, period_start)",
+        [],
+    )?;
+
+    // Add task run annotation columns to existing tables (migration)
+    add_columns(conn, "agg_metric_udp_probe", &ANNOTATION_COLUMNS);
+
+    // Add labels column to existing tables (migration)
+    db_labels::add_column(conn, "agg_metric_udp_probe");
+
+    Ok(())
+}
+
+/// Create UDP probe raw metrics table
+pub(super) fn create_raw_table(conn: &Connection) -> Result<()> {
+    conn.execute(
+        r#"
+        CREATE TABLE IF NOT EXISTS raw_metric_udp_probe (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            agent_id TEXT NOT NULL,
+            task_name TEXT NOT NULL,
+            agent_row_id INTEGER,
+            timestamp INTEGER NOT NULL,
+            packets_sent INTEGER NOT NULL,
+            packets_received INTEGER NOT NULL,
+            packets_reflected INTEGER,
+            loss_percent REAL NOT NULL,
+            forward_loss_percent REAL,
+            return_loss_percent REAL,
+            duplicate_packets INTEGER NOT NULL,
+            reordered_packets INTEGER NOT NULL,
+            avg_rtt_ms REAL,
+            min_rtt_ms REAL,
+            max_rtt_ms REAL,
+            jitter_ms REAL,
+            success BOOLEAN NOT NULL,
+            error TEXT,
+            host TEXT NOT NULL,
+            target_id TEXT,
+            labels TEXT,
+            received_at INTEGER DEFAULT (strftime('%s', 'now')),
+            FOREIGN KEY (agent_id) REFERENCES agents (agent_id),
+            UNIQUE(agent_id, task_name, timestamp, agent_row_id)
+        )
+        "#,
+        [],
+    )
+    .context("Failed to create raw_metric_udp_probe table")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_udp_probe_agent_task ON raw_metric_udp_probe(agent_id, task_name, timestamp)",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_raw_udp_probe_timestamp ON raw_metric_udp_probe(timestamp)",
+        [],
+    )?;
+
+    Ok(())
+}
+
+/// Store aggregated UDP probe metric within a transaction
+pub(super) fn store_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &AggregatedMetrics,
+    udp_data: &AggregatedUdpProbeMetric,
+) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    tx.execute(
+        r#"
+        INSERT INTO agg_metric_udp_probe (agent_id, task_name, period_start, period_end, sample_count, avg_rtt_ms, max_rtt_ms, min_rtt_ms, avg_jitter_ms, max_jitter_ms, loss_percent, forward_loss_percent, return_loss_percent, packets_sent, packets_received, duplicate_packets, reordered_packets, mos, successful_probes, failed_probes, host, target_id, maintenance, adaptive, upstream_down, avg_queue_wait_ms, max_queue_wait_ms, labels)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24, ?25, ?26, ?27, ?28)
+        "#,
+        params![
+            agent_id,
+            metric.task_name,
+            metric.period_start as i64,
+            metric.period_end as i64,
+            metric.sample_count,
+            udp_data.avg_rtt_ms,
+            udp_data.max_rtt_ms,
+            udp_data.min_rtt_ms,
+            udp_data.avg_jitter_ms,
+            udp_data.max_jitter_ms,
+            udp_data.loss_percent,
+            udp_data.forward_loss_percent,
+            udp_data.return_loss_percent,
+            udp_data.packets_sent,
+            udp_data.packets_received,
+            udp_data.duplicate_packets,
+            udp_data.reordered_packets,
+            udp_data.mos,
+            udp_data.successful_probes,
+            udp_data.failed_probes,
+            udp_data.host,
+            udp_data.target_id,
+            metric.maintenance,
+            metric.adaptive,
+            metric.upstream_down,
+            metric.avg_queue_wait_ms,
+            metric.max_queue_wait_ms,
+            labels,
+        ],
+    )?;
+    Ok(())
+}
+
+/// Store raw UDP probe metric within a transaction
+pub(super) fn store_raw_metric(
+    tx: &Transaction,
+    agent_id: &str,
+    metric: &MetricData,
+    udp_data: &RawUdpProbeMetric,
+) -> Result<()> {
+    let labels = db_labels::column_value(&metric.labels)?;
+    tx.execute(
+        r#"
+        INSERT OR IGNORE INTO raw_metric_udp_probe (agent_id, task_name, timestamp, packets_sent, packets_received, packets_reflected, loss_percent, forward_loss_percent, return_loss_percent, duplicate_packets, reordered_packets, avg_rtt_ms, min_rtt_ms, max_rtt_ms, jitter_ms, success, error, host, target_id, labels, agent_row_id)
+        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21)
+        "#,
+        params![
+            agent_id,
+            metric.task_name,
+            metric.timestamp as i64,
+            udp_data.packets_sent,
+            udp_data.packets_received,
+            udp_data.packets_reflected,
+            udp_data.loss_percent,
+            udp_data.forward_loss_percent,
+            udp_data.return_loss_percent,
+            udp_data.duplicate_packets,
+            udp_data.reordered_packets,
+            udp_data.avg_rtt_ms,
+            udp_data.min_rtt_ms,
+            udp_data.max_rtt_ms,
+            udp_data.jitter_ms,
+            udp_data.success,
+            udp_data.error,
+            udp_data.host,
+            udp_data.target_id,
+            labels,
+            metric.row_id
+        ],
+    )?;
+    Ok(())
+}
+
+/// Delete old UDP probe metrics
+pub(super) fn cleanup_old_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM agg_metric_udp_probe WHERE period_end < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
+
+/// Delete old raw UDP probe metrics
+pub(super) fn cleanup_old_raw_data(conn: &Connection, cutoff_time: i64) -> Result<usize> {
+    let deleted = conn.execute(
+        "DELETE FROM raw_metric_udp_probe WHERE timestamp < ?1",
+        params![cutoff_time],
+    )?;
+    Ok(deleted)
+}
@@ -8,7 +8,8 @@ use crate::config::ConfigManager;
 use crate::database::db_agent_health::{store_health_check, AgentHealthCheck};
 use crate::database::{AgentInfo, ServerDatabase};
 use anyhow::{Context, Result};
-use shared::config::ServerConfig;
+use shared::config::{ServerConfig, TasksConfig};
+use shared::defaults::default_aggregation_window;
 use std::path::PathBuf;
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
@@ -34,6 +35,9 @@ pub struct AgentHealthMetrics {
     pub is_problematic: bool,
     pub agent_version: Option<String>,
     pub version_outdated: bool,
+    /// Agent clock minus server clock in seconds, None if unknown
+    pub clock_offset_seconds: Option<i64>,
+    pub clock_skewed: bool,
 }
 
 impl HealthMonitor {
@@ -77,27 +81,9 @@ impl HealthMonitor {
         let config = self.get_server_config().await?;
         let check_period_seconds = config.health_check_interval_seconds;
         let threshold = config.health_check_success_ratio_threshold;
+        let max_clock_skew_seconds = config.health_check_max_clock_skew_seconds;
 
-        // Agents aggregate metrics at minute boundaries (e.g., period_start = 720, 780, 840...)
-        // We must align our query period to minute boundaries to match.
-        //
-        // We exclude TWO minutes from the end:
-        // 1. The current incomplete minute (not yet aggregated by agents)
-        // 2. The previous minute (aggregated but may not be sent/received yet due to network delay)
-        //
-        // Example at real time 1065:
-        //   current_minute = 1020 (floor to minute)
-        //   period_end = 1020 - 60 = 960 (exclude current + transmission buffer)
-        //   period_start = 960 - 300 = 660 (for 5-minute check interval)
-        //   Query matches entries with period_start: 660, 720, 780, 840, 900 (5 entries)
-        const AGGREGATION_WINDOW_SECONDS: u64 = 60;
         let now = current_timestamp();
-        let current_minute = (now / AGGREGATION_WINDOW_SECONDS) * AGGREGATION_WINDOW_SECONDS;
-        let period_end = current_minute - AGGREGATION_WINDOW_SECONDS; // Exclude current + buffer minute
-        let period_start = period_end - check_period_seconds;
-
-        // Use period_end as the effective "current_time" for calculations
-        let current_time = period_end;
 
         // Get all registered agents
         let agents = {
@@ -118,19 +104,35 @@ impl HealthMonitor {
         let mut health_checks = Vec::new();
 
         for agent in agents {
+            // Each agent's period is aligned to its own aggregation window
+            let window_seconds = match self.load_agent_tasks_config(&agent.agent_id).await {
+                Ok(tasks_config) => tasks_config.aggregation_window_seconds as u64,
+                Err(_) => default_aggregation_window() as u64,
+            };
+            let (period_start, period_end) =
+                health_check_period(now, check_period_seconds, window_seconds);
+
             match self
-                .calculate_health_metrics(&agent, period_start, current_time, threshold)
+                .calculate_health_metrics(
+                    &agent,
+                    period_start,
+                    period_end,
+                    threshold,
+                    max_clock_skew_seconds,
+                )
                 .await
             {
                 Ok(metrics) => {
                     if metrics.is_problematic {
                         problematic_count += 1;
                         warn!(
-                            "Agent {} is problematic: ratio={:.2}, expected={}, received={}",
+                            "Agent {} is problematic: ratio={:.2}, expected={}, received={}, clock_offset={:?}s, clock_skewed={}",
                             metrics.agent_id,
                             metrics.success_ratio,
                             metrics.expected_entries,
-                            metrics.received_entries
+                            metrics.received_entries,
+                            metrics.clock_offset_seconds,
+                            metrics.clock_skewed
                         );
                     } else {
                         debug!(
@@ -141,14 +143,15 @@ impl HealthMonitor {
 
                     health_checks.push(AgentHealthCheck {
                         agent_id: metrics.agent_id,
-                        check_timestamp: current_time as i64,
+                        check_timestamp: period_end as i64,
                         period_start: period_start as i64,
-                        period_end: current_time as i64,
+                        period_end: period_end as i64,
                         seconds_since_last_push: metrics.seconds_since_last_push,
                         expected_entries: metrics.expected_entries,
                         received_entries: metrics.received_entries,
                         success_ratio: metrics.success_ratio,
                         is_problematic: metrics.is_problematic,
+                        clock_offset_seconds: metrics.clock_offset_seconds,
                     });
                 }
                 Err(e) => {
@@ -200,18 +203,33 @@ impl HealthMonitor {
         period_start: u64,
         period_end: u64,
         threshold: f64,
+        max_clock_skew_seconds: u64,
     ) -> Result<AgentHealthMetrics> {
         let current_time = current_timestamp();
         let seconds_since_last_push = (current_time - agent.last_seen) as i64;
 
+        // Agents stamp periods with their own clock, so the windows looked up
+        // in the database are shifted by the agent's clock offset.
+        let clock_offset_seconds = agent.clock_offset_seconds.unwrap_or(0);
+
         // Calculate expected entries based on agent's task configuration
         let expected_entries = self
-            .calculate_expected_entries(&agent.agent_id, period_start, period_end)
+            .calculate_expected_entries(
+                &agent.agent_id,
+                period_start,
+                period_end,
+                clock_offset_seconds,
+            )
             .await?;
 
         // Calculate received entries from database
         let received_entries = self
-            .calculate_received_entries(&agent.agent_id, period_start, period_end)
+            .calculate_received_entries(
+                &agent.agent_id,
+                period_start,
+                period_end,
+                clock_offset_seconds,
+            )
             .await?;
 
         // Calculate success ratio
@@ -224,8 +242,11 @@ impl HealthMonitor {
         // Check if agent version is outdated
         let version_outdated = self.is_agent_version_outdated(agent);
 
-        // Agent is problematic if success ratio is below threshold OR version is outdated
-        let is_problematic = success_ratio < threshold || version_outdated;
+        let clock_skewed = clock_offset_seconds.unsigned_abs() > max_clock_skew_seconds;
+
+        // Agent is problematic if success ratio is below threshold, version is outdated
+        // or its clock is skewed
+        let is_problematic = success_ratio < threshold || version_outdated || clock_skewed;
 
         Ok(AgentHealthMetrics {
             agent_id: agent.agent_id.clone(),
@@ -236,6 +257,8 @@ impl HealthMonitor {
             is_problematic,
             agent_version: agent.agent_version.clone(),
             version_outdated,
+            clock_offset_seconds: agent.clock_offset_seconds,
+            clock_skewed,
         })
     }
 
@@ -287,21 +310,8 @@ impl HealthMonitor {
         )
     }
 
-    /// Calculates the expected number of metric entries based on agent's task configuration
-    ///
-    /// Note: Agents aggregate metrics every 60 seconds (1 minute) and send those aggregated
-    /// entries to the server. Each task produces one aggregated entry per minute if it runs
-    /// at least once during that minute.
-    pub(crate) async fn calculate_expected_entries(
-        &self,
-        agent_id: &str,
-        period_start: u64,
-        period_end: u64,
-    ) -> Result<i64> {
-        // Agents aggregate metrics every 60 seconds
-        const AGGREGATION_WINDOW_SECONDS: u64 = 60;
-
-        // Get agent's tasks configuration (from cache or disk)
+    /// Loads and parses an agent's tasks configuration (from cache or disk)
+    async fn load_agent_tasks_config(&self, agent_id: &str) -> Result<TasksConfig> {
         let tasks_toml = {
             let config_manager = self.config_manager.lock().await;
             match config_manager.get_agent_config(agent_id).await {
@@ -316,17 +326,56 @@ impl HealthMonitor {
         };
 
         // Parse tasks configuration
-        let tasks_config: shared::config::TasksConfig =
-            toml::from_str(&tasks_toml).context("Failed to parse agent tasks configuration")?;
+        toml::from_str(&tasks_toml).context("Failed to parse agent tasks configuration")
+    }
+
+    /// Calculates the expected number of metric entries based on agent's task configuration
+    ///
+    /// Note: Agents aggregate metrics every aggregation window (60 seconds unless
+    /// `aggregation_window_seconds` is set in their tasks configuration) and send those
+    /// aggregated entries to the server. Each task produces one aggregated entry per window
+    /// if it runs at least once during that window.
+    ///
+    /// Windows in which a task is outside its active windows or overlaps one of its
+    /// maintenance windows are not expected, and cron tasks are only expected in
+    /// windows containing one of their runs. Neither are windows in which the agent
+    /// reported every run of the task as suppressed by a down prerequisite.
+    /// `clock_offset_seconds` (agent clock minus server clock) maps the reported
+    /// periods to server time.
+    pub(crate) async fn calculate_expected_entries(
+        &self,
+        agent_id: &str,
+        period_start: u64,
+        period_end: u64,
+        clock_offset_seconds: i64,
+    ) -> Result<i64> {
+        let tasks_config = self.load_agent_tasks_config(agent_id).await?;
+        let aggregation_window_seconds = tasks_config.aggregation_window_seconds as u64;
+
+        // Fully suppressed periods leave only an execution entry behind,
+        // as (task name, period start in server time)
+        let suppressed_periods: Vec<(String, u64)> = self
+            .database
+            .lock()
+            .await
+            .get_suppressed_periods(
+                agent_id,
+                period_start as i64 + clock_offset_seconds,
+                period_end as i64 + clock_offset_seconds,
+            )
+            .await?
+            .into_iter()
+            .map(|(task_name, start)| (task_name, (start - clock_offset_seconds).max(0) as u64))
+            .collect();
 
         let period_duration = period_end - period_start;
 
         // Calculate how many aggregation windows fit in the health check period
-        let num_aggregation_windows = period_duration / AGGREGATION_WINDOW_SECONDS;
+        let num_aggregation_windows = period_duration / aggregation_window_seconds;
 
-        // Each task produces one aggregated entry per minute (if it runs at least once in that minute)
-        // Tasks that run faster than once per minute still only produce one aggregated entry per minute
-        // Tasks that run slower than once per minute may not produce an entry in every minute
+        // Each task produces one aggregated entry per window (if it runs at least once in that window)
+        // Tasks that run faster than once per window still only produce one aggregated entry per window
+        // Tasks that run slower than once per window may not produce an entry in every window
         let mut total_expected = 0i64;
 
         for task in &tasks_config.tasks {
@@ -336,17 +385,62 @@ impl HealthMonitor {
                 continue; // Skip invalid tasks
             }
 
-            // For tasks that run more frequently than the aggregation window (< 60s):
-            // They produce 1 aggregated entry per minute
-            // For tasks that run less frequently (>= 60s):
+            // Windows the task is expected to report in: overlapping one of its active
+            // windows and not overlapping a maintenance window (those entries are
+            // tagged and not counted as received)
+            let expected_windows: Vec<u64> = (0..num_aggregation_windows)
+                .map(|index| period_start + index * aggregation_window_seconds)
+                .filter(|&window_start| {
+                    task.is_active_during(window_start, window_start + aggregation_window_seconds)
+                        && !tasks_config.in_maintenance(
+                            &task.name,
+                            window_start,
+                            window_start + aggregation_window_seconds,
+                        )
+                })
+                .collect();
+
+            // Expected windows in which every run of the task was suppressed
+            let suppressed_windows = expected_windows
+                .iter()
+                .filter(|&&window_start| {
+                    suppressed_periods.iter().any(|(task_name, start)| {
+                        *task_name == task.name
+                            && (window_start..window_start + aggregation_window_seconds)
+                                .contains(start)
+                    })
+                })
+                .count() as u64;
+
+            // For tasks that run more frequently than the aggregation window:
+            // They produce 1 aggregated entry per window
+            // For tasks that run less frequently:
             // They produce fewer entries based on their schedule
-            let expected_entries_for_task = if schedule_seconds < AGGREGATION_WINDOW_SECONDS {
-                // Fast tasks: one aggregated entry per minute
-                num_aggregation_windows
+            let expected_entries_for_task = if let Some(cron) = task.cron_schedule() {
+                // Cron tasks: one aggregated entry per window containing an active run
+                let runs: Vec<u64> = cron
+                    .occurrences(period_start, period_end)
+                    .into_iter()
+                    .filter(|&run| task.is_active_at(run))
+                    .collect();
+                expected_windows
+                    .iter()
+                    .filter(|&&window_start| {
+                        runs.iter().any(|&run| {
+                            run >= window_start && run < window_start + aggregation_window_seconds
+                        })
+                    })
+                    .count() as u64
+            } else if schedule_seconds < aggregation_window_seconds {
+                // Fast tasks: one aggregated entry per expected window
+                expected_windows.len() as u64
             } else {
-                // Slow tasks: one entry per execution
-                period_duration / schedule_seconds
-            };
+                // Slow tasks: one entry per execution during the expected windows
+                let excluded_windows = num_aggregation_windows - expected_windows.len() as u64;
+                period_duration.saturating_sub(excluded_windows * aggregation_window_seconds)
+                    / schedule_seconds
+            }
+            .saturating_sub(suppressed_windows);
 
             total_expected += expected_entries_for_task as i64;
 
@@ -365,17 +459,22 @@ impl HealthMonitor {
     }
 
     /// Calculates the actual number of metric entries received from an agent
+    ///
+    /// `period_start` and `period_end` are in server time; `clock_offset_seconds`
+    /// (agent clock minus server clock) maps them to the agent's clock. Entries
+    /// tagged as maintenance are not counted.
     async fn calculate_received_entries(
         &self,
         agent_id: &str,
         period_start: u64,
         period_end: u64,
+        clock_offset_seconds: i64,
     ) -> Result<i64> {
         let mut db = self.database.lock().await;
         let conn = db.get_connection()?;
 
-        let period_start_i64 = period_start as i64;
-        let period_end_i64 = period_end as i64;
+        let period_start_i64 = period_start as i64 + clock_offset_seconds;
+        let period_end_i64 = period_end as i64 + clock_offset_seconds;
 
         // Use a read transaction to ensure consistent snapshot across all queries
         // This is critical in WAL mode to see all committed data
@@ -383,55 +482,79 @@ impl HealthMonitor {
 
         // Count entries from all aggregated metrics tables
         let count_ping: i64 = tx.query_row(
-            "SELECT COUNT(*) FROM agg_metric_ping WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3",
+            "SELECT COUNT(*) FROM agg_metric_ping WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3 AND maintenance = 0",
             rusqlite::params![agent_id, period_start_i64, period_end_i64],
             |row| row.get(0),
         )?;
 
         let count_tcp: i64 = tx.query_row(
-            "SELECT COUNT(*) FROM agg_metric_tcp WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3",
+            "SELECT COUNT(*) FROM agg_metric_tcp WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3 AND maintenance = 0",
             rusqlite::params![agent_id, period_start_i64, period_end_i64],
             |row| row.get(0),
         )?;
 
         let count_http: i64 = tx.query_row(
-            "SELECT COUNT(*) FROM agg_metric_http WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3",
+            "SELECT COUNT(*) FROM agg_metric_http WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3 AND maintenance = 0",
             rusqlite::params![agent_id, period_start_i64, period_end_i64],
             |row| row.get(0),
         )?;
 
         let count_tls: i64 = tx.query_row(
-            "SELECT COUNT(*) FROM agg_metric_tls WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3",
+            "SELECT COUNT(*) FROM agg_metric_tls WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3 AND maintenance = 0",
             rusqlite::params![agent_id, period_start_i64, period_end_i64],
             |row| row.get(0),
         )?;
 
         let count_http_content: i64 = tx.query_row(
-            "SELECT COUNT(*) FROM agg_metric_http_content WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3",
+            "SELECT COUNT(*) FROM agg_metric_http_content WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3 AND maintenance = 0",
             rusqlite::params![agent_id, period_start_i64, period_end_i64],
             |row| row.get(0),
         )?;
 
         let count_dns: i64 = tx.query_row(
-            "SELECT COUNT(*) FROM agg_metric_dns WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3",
+            "SELECT COUNT(*) FROM agg_metric_dns WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3 AND maintenance = 0",
             rusqlite::params![agent_id, period_start_i64, period_end_i64],
             |row| row.get(0),
         )?;
 
         let count_bandwidth: i64 = tx.query_row(
-            "SELECT COUNT(*) FROM agg_metric_bandwidth WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3",
+            "SELECT COUNT(*) FROM agg_metric_bandwidth WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3 AND maintenance = 0",
+            rusqlite::params![agent_id, period_start_i64, period_end_i64],
+            |row| row.get(0),
+        )?;
+
+        let count_udp_probe: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM agg_metric_udp_probe WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3 AND maintenance = 0",
+            rusqlite::params![agent_id, period_start_i64, period_end_i64],
+            |row| row.get(0),
+        )?;
+
+        let count_twamp: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM agg_metric_twamp WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3 AND maintenance = 0",
+            rusqlite::params![agent_id, period_start_i64, period_end_i64],
+            |row| row.get(0),
+        )?;
+
+        let count_tcp_sweep: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM agg_metric_tcp_sweep WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3 AND maintenance = 0",
+            rusqlite::params![agent_id, period_start_i64, period_end_i64],
+            |row| row.get(0),
+        )?;
+
+        let count_ntp: i64 = tx.query_row(
+            "SELECT COUNT(*) FROM agg_metric_ntp WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3 AND maintenance = 0",
             rusqlite::params![agent_id, period_start_i64, period_end_i64],
             |row| row.get(0),
         )?;
 
         let count_sql: i64 = tx.query_row(
-            "SELECT COUNT(*) FROM agg_metric_sql_query WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3",
+            "SELECT COUNT(*) FROM agg_metric_sql_query WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3 AND maintenance = 0",
             rusqlite::params![agent_id, period_start_i64, period_end_i64],
             |row| row.get(0),
         )?;
 
         let count_snmp: i64 = tx.query_row(
-            "SELECT COUNT(*) FROM agg_metric_snmp WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3",
+            "SELECT COUNT(*) FROM agg_metric_snmp WHERE agent_id = ?1 AND period_start >= ?2 AND period_start < ?3 AND maintenance = 0",
             rusqlite::params![agent_id, period_start_i64, period_end_i64],
             |row| row.get(0),
         )?;
@@ -446,6 +569,10 @@ impl HealthMonitor {
             + count_http_content
             + count_dns
             + count_bandwidth
+            + count_udp_probe
+            + count_twamp
+            + count_tcp_sweep
+            + count_ntp
             + count_sql
             + count_snmp;
 
@@ -463,6 +590,11 @@ impl HealthMonitor {
 
         let output_path = self.output_dir.join("problematic_agents.txt");
 
+        let max_clock_skew_seconds = self
+            .get_server_config()
+            .await?
+            .health_check_max_clock_skew_seconds;
+
         let problematic = {
             let mut db = self.database.lock().await;
             let conn = db.get_connection()?;
@@ -512,6 +644,19 @@ impl HealthMonitor {
                     self.server_version
                 )?;
             }
+            match agent.clock_offset_seconds {
+                Some(offset) => {
+                    writeln!(file, "  Clock Offset: {:+} seconds", offset)?;
+                    if offset.unsigned_abs() > max_clock_skew_seconds {
+                        writeln!(
+                            file,
+                            "  Clock Status: SKEWED (max: {} seconds)",
+                            max_clock_skew_seconds
+                        )?;
+                    }
+                }
+                None => writeln!(file, "  Clock Offset: unknown")?,
+            }
             writeln!(
                 file,
                 "  Last Push: {} seconds ago",
@@ -593,6 +738,37 @@ impl HealthMonitor {
     }
 }
 
+/// Computes the health check period `(period_start, period_end)` for an agent
+///
+/// Agents aggregate metrics at window boundaries (e.g., period_start = 720, 780, 840...
+/// for 60-second windows). We must align our query period to the same boundaries.
+///
+/// We exclude TWO windows from the end:
+/// 1. The current incomplete window (not yet aggregated by the agent)
+/// 2. The previous window (aggregated but may not be sent/received yet due to network delay)
+///
+/// Example at real time 1065 with 60-second windows:
+///   current_window = 1020 (floor to window)
+///   period_end = 1020 - 60 = 960 (exclude current + transmission buffer)
+///   period_start = 960 - 300 = 660 (for 5-minute check interval)
+///   Query matches entries with period_start: 660, 720, 780, 840, 900 (5 entries)
+///
+/// The period is rounded up to whole windows, so an agent whose windows are
+/// longer than the check interval (e.g., 3600 seconds with a 5-minute interval)
+/// is still checked over its last complete window.
+pub(crate) fn health_check_period(
+    now: u64,
+    check_period_seconds: u64,
+    window_seconds: u64,
+) -> (u64, u64) {
+    let window_seconds = window_seconds.max(1);
+    let period_seconds = check_period_seconds.div_ceil(window_seconds).max(1) * window_seconds;
+    let current_window = (now / window_seconds) * window_seconds;
+    let period_end = current_window.saturating_sub(window_seconds);
+    let period_start = period_end.saturating_sub(period_seconds);
+    (period_start, period_end)
+}
+
 /// Helper function to get current Unix timestamp
 fn current_timestamp() -> u64 {
     SystemTime::now()
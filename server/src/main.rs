@@ -87,12 +87,16 @@ pub struct Server {
     reconfigure_manager: Arc<Mutex<ReconfigureManager>>,
     /// Database handle for metrics storage. Wrapped in Arc<Mutex<>> for sharing.
     database: Option<Arc<tokio::sync::Mutex<crate::database::ServerDatabase>>>,
+    /// Bandwidth test manager, kept to flush the persisted queue on shutdown.
+    bandwidth_manager: Option<crate::bandwidth_state::BandwidthTestManager>,
     /// Handle to the reconfigure background task for graceful shutdown.
     reconfigure_task_handle: Option<JoinHandle<()>>,
     /// Handle to the database cleanup task for graceful shutdown.
     cleanup_task_handle: Option<JoinHandle<()>>,
     /// Handle to the WAL checkpoint task for graceful shutdown.
     wal_checkpoint_task_handle: Option<JoinHandle<()>>,
+    /// Handle to the metric rollup task for graceful shutdown.
+    rollup_task_handle: Option<JoinHandle<()>>,
     /// Handle to the health monitoring task for graceful shutdown.
     health_monitor_task_handle: Option<JoinHandle<()>>,
     /// Handle to the rate limiter cleanup task for graceful shutdown.
@@ -144,9 +148,11 @@ impl Server {
             listen_address,
             reconfigure_manager,
             database: None,
+            bandwidth_manager: None,
             reconfigure_task_handle: None,
             cleanup_task_handle: None,
             wal_checkpoint_task_handle: None,
+            rollup_task_handle: None,
             health_monitor_task_handle: None,
             rate_limiter_cleanup_task_handle: None,
             config_cache_updater_handle: None,
@@ -194,8 +200,19 @@ impl Server {
             server_config.bandwidth_queue_base_delay_seconds,
             server_config.bandwidth_queue_current_test_delay_seconds,
             server_config.bandwidth_queue_position_multiplier_seconds,
+        )
+        .with_concurrency(
+            server_config.bandwidth_max_concurrent_tests,
+            server_config.bandwidth_group_max_concurrent,
+            &server_config.bandwidth_groups,
+        )
+        .with_state_file(data_dir.join("bandwidth_queue.json"));
+        info!(
+            max_concurrent = server_config.bandwidth_max_concurrent_tests,
+            group_max_concurrent = server_config.bandwidth_group_max_concurrent,
+            groups = server_config.bandwidth_groups.len(),
+            "Bandwidth test manager initialized"
         );
-        info!("Bandwidth test manager initialized");
 
         // Load all agent configurations into cache
         let agent_configs_dir = PathBuf::from(&server_config.agent_configs_dir);
@@ -233,6 +250,15 @@ impl Server {
         // Start periodic cleanup task for old data
         let cleanup_interval_hours = server_config.cleanup_interval_hours;
         let retention_days = server_config.data_retention_days;
+        // Raw samples are never kept longer than the aggregates
+        let raw_retention_days = server_config
+            .raw_data_retention_days
+            .min(server_config.data_retention_days);
+        let rollup_retention_days = (
+            server_config.rollup_5m_retention_days,
+            server_config.rollup_1h_retention_days,
+            server_config.rollup_1d_retention_days,
+        );
         let db_for_cleanup = std::sync::Arc::new(tokio::sync::Mutex::new(
             crate::database::ServerDatabase::new(&data_dir)
                 .context("Failed to create database manager for cleanup task")?,
@@ -255,6 +281,16 @@ impl Server {
                     _ = interval.tick() => {
                         info!("Running periodic database cleanup");
                         let mut db = db_for_cleanup.lock().await;
+                        let (five_minute_days, hourly_days, daily_days) = rollup_retention_days;
+                        if let Err(e) = db
+                            .cleanup_old_rollups(five_minute_days, hourly_days, daily_days)
+                            .await
+                        {
+                            error!("Rollup cleanup failed: {}", e);
+                        }
+                        if let Err(e) = db.cleanup_old_raw_data(raw_retention_days).await {
+                            error!("Raw data cleanup failed: {}", e);
+                        }
                         if let Err(e) = db.cleanup_old_data(retention_days).await {
                             error!("Database cleanup failed: {}", e);
                         } else {
@@ -303,6 +339,64 @@ impl Server {
             }
         });
 
+        // Start periodic metric rollup task
+        if server_config.rollups_enabled {
+            let rollup_interval_secs = server_config.rollup_interval_seconds;
+            let rollup_lookback_secs = (server_config.rollup_lookback_hours as u64) * 3600;
+            let (five_minute_days, hourly_days, _) = rollup_retention_days;
+            // Rollups use their own connection so that a long backfill does not
+            // hold the database handle that agent pushes wait on
+            let db_for_rollup = Arc::new(tokio::sync::Mutex::new(
+                crate::database::ServerDatabase::new(&data_dir)
+                    .context("Failed to create database manager for rollup task")?,
+            ));
+            let mut rollup_shutdown_rx = shutdown_tx.subscribe();
+            let rollup_task = tokio::spawn(async move {
+                let mut interval =
+                    tokio::time::interval(std::time::Duration::from_secs(rollup_interval_secs));
+
+                loop {
+                    tokio::select! {
+                        _ = interval.tick() => {
+                            debug!("Running periodic metric rollup");
+                            let db = Arc::clone(&db_for_rollup);
+                            let result = tokio::task::spawn_blocking(move || {
+                                db.blocking_lock().perform_rollups(
+                                    rollup_lookback_secs,
+                                    retention_days,
+                                    five_minute_days,
+                                    hourly_days,
+                                )
+                            })
+                            .await;
+                            match result {
+                                Ok(Ok(rows)) => {
+                                    debug!("Metric rollup completed: {} tier rows written", rows);
+                                }
+                                Ok(Err(e)) => {
+                                    error!("Metric rollup failed: {}", e);
+                                }
+                                Err(e) => {
+                                    error!("Metric rollup task panicked: {}", e);
+                                }
+                            }
+                        }
+                        _ = rollup_shutdown_rx.recv() => {
+                            info!("Rollup task received shutdown signal");
+                            break;
+                        }
+                    }
+                }
+            });
+            self.rollup_task_handle = Some(rollup_task);
+            info!(
+                "Metric rollup task started (interval: {}s)",
+                rollup_interval_secs
+            );
+        }
+
+        self.bandwidth_manager = Some(bandwidth_manager.clone());
+
         // Create application state with all dependencies
         let app_state = crate::api::AppState::new(
             server_config.clone(),
@@ -561,6 +655,31 @@ impl Server {
             }
         }
 
+        // Wait for rollup task to complete
+        if let Some(handle) = self.rollup_task_handle.take() {
+            info!(
+                "Waiting for rollup task to complete (timeout: {}s)",
+                shutdown_timeout_secs
+            );
+
+            match tokio::time::timeout(
+                std::time::Duration::from_secs(shutdown_timeout_secs),
+                handle,
+            )
+            .await
+            {
+                Ok(Ok(())) => {
+                    info!("Rollup task completed successfully");
+                }
+                Ok(Err(e)) => {
+                    warn!("Rollup task panicked: {}", e);
+                }
+                Err(_) => {
+                    warn!("Rollup task shutdown timeout reached, aborting");
+                }
+            }
+        }
+
         // Wait for health monitor task to complete
         if let Some(handle) = self.health_monitor_task_handle.take() {
             info!(
@@ -597,6 +716,11 @@ impl Server {
             info!("Config file watcher stopped");
         }
 
+        // Make sure the last bandwidth queue change reaches the state file
+        if let Some(bandwidth_manager) = self.bandwidth_manager.take() {
+            bandwidth_manager.flush_state().await;
+        }
+
         // Close database connection
         if let Some(database_arc) = &self.database {
             info!("Closing database connection");
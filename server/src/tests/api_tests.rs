@@ -1,12 +1,15 @@
 //! Tests for the REST API module
 
-use crate::api::{create_router, AgentRateLimiter, ApiError, AppState};
+use crate::api::{
+    agent_clock_offset, create_router, parse_agent_timestamp, AgentRateLimiter, ApiError, AppState,
+};
 use axum::body::Body;
 use axum::http::{Method, Request, StatusCode};
 use shared::api::{
-    endpoints, headers, BandwidthTestAction, BandwidthTestRequest, BandwidthTestResponse,
-    ConfigErrorRequest, ConfigStatus, ConfigUploadRequest, ConfigUploadResponse,
-    ConfigVerifyRequest, ConfigVerifyResponse, MetricsRequest, MetricsResponse,
+    endpoints, headers, BandwidthCompleteRequest, BandwidthCompleteResponse, BandwidthTestAction,
+    BandwidthTestRequest, BandwidthTestResponse, BandwidthUploadResponse, ConfigErrorRequest,
+    ConfigStatus, ConfigUploadRequest, ConfigUploadResponse, ConfigVerifyRequest,
+    ConfigVerifyResponse, MetricsRequest, MetricsResponse,
 };
 use shared::config::ServerConfig;
 use std::sync::Arc;
@@ -62,6 +65,7 @@ reconfigure_check_interval_seconds = 10
         listen_address: "127.0.0.1:8787".to_string(),
         api_key: "test-api-key".to_string(),
         data_retention_days: 30,
+        raw_data_retention_days: 3,
         agent_configs_dir: config_dir.to_string_lossy().to_string(),
         bandwidth_test_size_mb: 10,
         reconfigure_check_interval_seconds: 10,
@@ -75,6 +79,8 @@ reconfigure_check_interval_seconds = 10
         bandwidth_queue_current_test_delay_seconds: 60,
         bandwidth_queue_position_multiplier_seconds: 30,
         bandwidth_max_delay_seconds: 300,
+        bandwidth_max_concurrent_tests: 1,
+        bandwidth_group_max_concurrent: 1,
         initial_cleanup_delay_seconds: 3600,
         graceful_shutdown_timeout_seconds: 30,
         wal_checkpoint_interval_seconds: 60,
@@ -82,6 +88,14 @@ reconfigure_check_interval_seconds = 10
         health_check_interval_seconds: 300,
         health_check_success_ratio_threshold: 0.9,
         health_check_retention_days: 30,
+        health_check_max_clock_skew_seconds: 30,
+        rollups_enabled: true,
+        rollup_interval_seconds: 300,
+        rollup_lookback_hours: 2,
+        rollup_5m_retention_days: 90,
+        rollup_1h_retention_days: 365,
+        rollup_1d_retention_days: 1825,
+        bandwidth_groups: Default::default(),
     };
 
     // Initialize database for testing
@@ -128,7 +142,10 @@ async fn test_metrics_endpoint() {
         timestamp_utc: "2023-01-01T00:00:00Z".to_string(),
         config_checksum: "checksum123".to_string(),
         metrics: vec![],
+        raw_metrics: vec![],
         agent_version: Some("0.7.6".to_string()),
+        agent_labels: Default::default(),
+        host_facts: None,
     };
 
     let request = Request::builder()
@@ -191,6 +208,7 @@ async fn test_bandwidth_test_endpoint() {
     let test_request = BandwidthTestRequest {
         agent_id: "test-agent".to_string(),
         timestamp_utc: "2023-01-01T00:00:00Z".to_string(),
+        parallel_streams: 1,
     };
 
     let request = Request::builder()
@@ -224,6 +242,266 @@ async fn test_bandwidth_download_endpoint() {
     assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 }
 
+/// Helper to request a bandwidth test slot for an agent
+async fn request_bandwidth_slot(
+    app: &axum::Router,
+    agent_id: &str,
+    parallel_streams: u32,
+) -> BandwidthTestResponse {
+    let test_request = BandwidthTestRequest {
+        agent_id: agent_id.to_string(),
+        timestamp_utc: "2023-01-01T00:00:00Z".to_string(),
+        parallel_streams,
+    };
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(endpoints::BANDWIDTH_TEST)
+        .header(headers::API_KEY, "test-api-key")
+        .header("content-type", "application/json")
+        .body(Body::from(serde_json::to_string(&test_request).unwrap()))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+fn bandwidth_upload_request(agent_id: &str, size: usize) -> Request<Body> {
+    Request::builder()
+        .method(Method::POST)
+        .uri(format!(
+            "{}?agent_id={}",
+            endpoints::BANDWIDTH_UPLOAD,
+            agent_id
+        ))
+        .header(headers::API_KEY, "test-api-key")
+        .header("content-type", "application/octet-stream")
+        .body(Body::from(vec![0u8; size]))
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_bandwidth_upload_without_active_test() {
+    let (app, _temp_dir) = create_test_app().await;
+
+    let response = app
+        .oneshot(bandwidth_upload_request("test-agent", 1024))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_bandwidth_upload_requires_api_key() {
+    let (app, _temp_dir) = create_test_app().await;
+
+    let mut request = bandwidth_upload_request("test-agent", 1024);
+    request.headers_mut().remove(headers::API_KEY);
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_bandwidth_upload_releases_slot() {
+    let (app, _temp_dir) = create_test_app().await;
+
+    let granted = request_bandwidth_slot(&app, "agent-a", 1).await;
+    assert_eq!(granted.action, BandwidthTestAction::Proceed);
+    // Another agent must wait while the slot is held
+    let queued = request_bandwidth_slot(&app, "agent-b", 1).await;
+    assert_eq!(queued.action, BandwidthTestAction::Delay);
+
+    let size = 1024 * 1024;
+    let response = app
+        .clone()
+        .oneshot(bandwidth_upload_request("agent-a", size))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let upload: BandwidthUploadResponse = serde_json::from_slice(&body).unwrap();
+    assert_eq!(upload.bytes_received, size as u64);
+
+    // The slot is released once the upload completes
+    let next = request_bandwidth_slot(&app, "agent-b", 1).await;
+    assert_eq!(next.action, BandwidthTestAction::Proceed);
+}
+
+#[tokio::test]
+async fn test_bandwidth_complete_releases_slot() {
+    let (app, _temp_dir) = create_test_app().await;
+
+    // A coordinated test against another target holds the slot without
+    // transferring data through the server
+    let granted = request_bandwidth_slot(&app, "agent-a", 1).await;
+    assert_eq!(granted.action, BandwidthTestAction::Proceed);
+    let queued = request_bandwidth_slot(&app, "agent-b", 1).await;
+    assert_eq!(queued.action, BandwidthTestAction::Delay);
+
+    let complete = |agent_id: &str, api_key: &str| {
+        let body = BandwidthCompleteRequest {
+            agent_id: agent_id.to_string(),
+            timestamp_utc: "2023-01-01T00:00:00Z".to_string(),
+        };
+        Request::builder()
+            .method(Method::POST)
+            .uri(endpoints::BANDWIDTH_COMPLETE)
+            .header(headers::API_KEY, api_key)
+            .header("content-type", "application/json")
+            .body(Body::from(serde_json::to_string(&body).unwrap()))
+            .unwrap()
+    };
+
+    let response = app
+        .clone()
+        .oneshot(complete("agent-a", "wrong-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = app
+        .clone()
+        .oneshot(complete("agent-a", "test-api-key"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let released: BandwidthCompleteResponse = serde_json::from_slice(&body).unwrap();
+    assert!(released.released);
+
+    // agent-b was promoted, so a repeated completion by agent-a is a no-op
+    let next = request_bandwidth_slot(&app, "agent-b", 1).await;
+    assert_eq!(next.action, BandwidthTestAction::Proceed);
+    let response = app
+        .clone()
+        .oneshot(complete("agent-a", "test-api-key"))
+        .await
+        .unwrap();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let released: BandwidthCompleteResponse = serde_json::from_slice(&body).unwrap();
+    assert!(!released.released);
+}
+
+#[tokio::test]
+async fn test_bandwidth_upload_exceeding_test_size() {
+    let (app, _temp_dir) = create_test_app().await;
+
+    request_bandwidth_slot(&app, "agent-a", 1).await;
+    let response = app
+        .clone()
+        .oneshot(bandwidth_upload_request("agent-a", 10 * 1024 * 1024 + 1))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    // A rejected upload still releases the slot
+    let next = request_bandwidth_slot(&app, "agent-b", 1).await;
+    assert_eq!(next.action, BandwidthTestAction::Proceed);
+}
+
+#[tokio::test]
+async fn test_bandwidth_download_parallel_incompressible() {
+    use std::io::Write;
+
+    let (app, _temp_dir) = create_test_app().await;
+
+    let granted = request_bandwidth_slot(&app, "agent-a", 2).await;
+    assert_eq!(granted.action, BandwidthTestAction::Proceed);
+
+    let download = || {
+        Request::builder()
+            .method(Method::GET)
+            .uri(format!(
+                "{}?agent_id=agent-a",
+                endpoints::BANDWIDTH_DOWNLOAD
+            ))
+            .body(Body::empty())
+            .unwrap()
+    };
+
+    // The configured 10 MB are split between the two streams
+    let mut bodies = Vec::new();
+    for _ in 0..2 {
+        let response = app.clone().oneshot(download()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(body.len(), 5 * 1024 * 1024);
+        bodies.push(body);
+    }
+
+    // No further streams, and the slot is free once both have been served
+    let response = app.clone().oneshot(download()).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let next = request_bandwidth_slot(&app, "agent-b", 1).await;
+    assert_eq!(next.action, BandwidthTestAction::Proceed);
+
+    // Each stream uses its own seed and the data does not compress
+    assert_ne!(bodies[0][..1024], bodies[1][..1024]);
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&bodies[0]).unwrap();
+    let compressed = encoder.finish().unwrap();
+    assert!(compressed.len() as f64 > bodies[0].len() as f64 * 0.99);
+}
+
+#[tokio::test]
+async fn test_bandwidth_download_holds_slot_until_body_sent() {
+    let (app, _temp_dir) = create_test_app().await;
+
+    let granted = request_bandwidth_slot(&app, "agent-a", 1).await;
+    assert_eq!(granted.action, BandwidthTestAction::Proceed);
+
+    let request = Request::builder()
+        .method(Method::GET)
+        .uri(format!(
+            "{}?agent_id=agent-a",
+            endpoints::BANDWIDTH_DOWNLOAD
+        ))
+        .body(Body::empty())
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The slot stays taken while the body is still being streamed
+    let queued = request_bandwidth_slot(&app, "agent-b", 1).await;
+    assert_eq!(queued.action, BandwidthTestAction::Delay);
+
+    axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let next = request_bandwidth_slot(&app, "agent-b", 1).await;
+    assert_eq!(next.action, BandwidthTestAction::Proceed);
+}
+
+#[tokio::test]
+async fn test_bandwidth_test_rejects_invalid_stream_count() {
+    let (app, _temp_dir) = create_test_app().await;
+
+    let test_request = BandwidthTestRequest {
+        agent_id: "agent-a".to_string(),
+        timestamp_utc: "2023-01-01T00:00:00Z".to_string(),
+        parallel_streams: 0,
+    };
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(endpoints::BANDWIDTH_TEST)
+        .header(headers::API_KEY, "test-api-key")
+        .header("content-type", "application/json")
+        .body(Body::from(serde_json::to_string(&test_request).unwrap()))
+        .unwrap();
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
 #[tokio::test]
 async fn test_bandwidth_queue_five_concurrent_agents() {
     let (app, _temp_dir) = create_test_app().await;
@@ -237,6 +515,7 @@ async fn test_bandwidth_queue_five_concurrent_agents() {
         let test_request = BandwidthTestRequest {
             agent_id: agent_id.to_string(),
             timestamp_utc: "2023-01-01T00:00:00Z".to_string(),
+            parallel_streams: 1,
         };
 
         let request = Request::builder()
@@ -303,7 +582,10 @@ async fn test_metrics_endpoint_invalid_agent_id_empty() {
         timestamp_utc: "2023-01-01T00:00:00Z".to_string(),
         config_checksum: "checksum123".to_string(),
         metrics: vec![],
+        raw_metrics: vec![],
         agent_version: Some("0.7.6".to_string()),
+        agent_labels: Default::default(),
+        host_facts: None,
     };
 
     let request = Request::builder()
@@ -328,7 +610,10 @@ async fn test_metrics_endpoint_invalid_agent_id_special_chars() {
         timestamp_utc: "2023-01-01T00:00:00Z".to_string(),
         config_checksum: "checksum123".to_string(),
         metrics: vec![],
+        raw_metrics: vec![],
         agent_version: Some("0.7.6".to_string()),
+        agent_labels: Default::default(),
+        host_facts: None,
     };
 
     let request = Request::builder()
@@ -353,7 +638,10 @@ async fn test_metrics_endpoint_invalid_agent_id_starts_with_hyphen() {
         timestamp_utc: "2023-01-01T00:00:00Z".to_string(),
         config_checksum: "checksum123".to_string(),
         metrics: vec![],
+        raw_metrics: vec![],
         agent_version: Some("0.7.6".to_string()),
+        agent_labels: Default::default(),
+        host_facts: None,
     };
 
     let request = Request::builder()
@@ -398,6 +686,7 @@ async fn test_bandwidth_test_invalid_agent_id_too_long() {
     let test_request = BandwidthTestRequest {
         agent_id: long_id,
         timestamp_utc: "2023-01-01T00:00:00Z".to_string(),
+        parallel_streams: 1,
     };
 
     let request = Request::builder()
@@ -487,6 +776,7 @@ reconfigure_check_interval_seconds = 10
         listen_address: "127.0.0.1:8787".to_string(),
         api_key: "test-api-key".to_string(),
         data_retention_days: 30,
+        raw_data_retention_days: 3,
         agent_configs_dir: config_dir.to_string_lossy().to_string(),
         bandwidth_test_size_mb: 10,
         reconfigure_check_interval_seconds: 10,
@@ -500,6 +790,8 @@ reconfigure_check_interval_seconds = 10
         bandwidth_queue_current_test_delay_seconds: 60,
         bandwidth_queue_position_multiplier_seconds: 30,
         bandwidth_max_delay_seconds: 300,
+        bandwidth_max_concurrent_tests: 1,
+        bandwidth_group_max_concurrent: 1,
         initial_cleanup_delay_seconds: 3600,
         graceful_shutdown_timeout_seconds: 30,
         wal_checkpoint_interval_seconds: 60,
@@ -507,6 +799,14 @@ reconfigure_check_interval_seconds = 10
         health_check_interval_seconds: 300,
         health_check_success_ratio_threshold: 0.9,
         health_check_retention_days: 30,
+        health_check_max_clock_skew_seconds: 30,
+        rollups_enabled: true,
+        rollup_interval_seconds: 300,
+        rollup_lookback_hours: 2,
+        rollup_5m_retention_days: 90,
+        rollup_1h_retention_days: 365,
+        rollup_1d_retention_days: 1825,
+        bandwidth_groups: Default::default(),
     };
 
     let mut database = crate::database::ServerDatabase::new(&data_dir).unwrap();
@@ -536,7 +836,10 @@ async fn test_whitelist_empty_allows_all_agents() {
         timestamp_utc: "2023-01-01T00:00:00Z".to_string(),
         config_checksum: "abc123".to_string(),
         metrics: vec![],
+        raw_metrics: vec![],
         agent_version: Some("0.7.6".to_string()),
+        agent_labels: Default::default(),
+        host_facts: None,
     };
 
     let request = Request::builder()
@@ -562,7 +865,10 @@ async fn test_whitelist_allows_listed_agent() {
         timestamp_utc: "2023-01-01T00:00:00Z".to_string(),
         config_checksum: "abc123".to_string(),
         metrics: vec![],
+        raw_metrics: vec![],
         agent_version: Some("0.7.6".to_string()),
+        agent_labels: Default::default(),
+        host_facts: None,
     };
 
     let request = Request::builder()
@@ -588,7 +894,10 @@ async fn test_whitelist_blocks_unlisted_agent() {
         timestamp_utc: "2023-01-01T00:00:00Z".to_string(),
         config_checksum: "abc123".to_string(),
         metrics: vec![],
+        raw_metrics: vec![],
         agent_version: Some("0.7.6".to_string()),
+        agent_labels: Default::default(),
+        host_facts: None,
     };
 
     let request = Request::builder()
@@ -653,6 +962,7 @@ async fn test_whitelist_applies_to_bandwidth_test() {
     let test_request = BandwidthTestRequest {
         agent_id: "blocked-agent".to_string(),
         timestamp_utc: "2023-01-01T00:00:00Z".to_string(),
+        parallel_streams: 1,
     };
 
     let request = Request::builder()
@@ -812,7 +1122,10 @@ async fn test_metrics_endpoint_requests_config_upload_when_server_has_no_config(
         timestamp_utc: "2023-01-01T00:00:00Z".to_string(),
         config_checksum: "some-checksum-123".to_string(),
         metrics: vec![],
+        raw_metrics: vec![],
         agent_version: Some("0.7.6".to_string()),
+        agent_labels: Default::default(),
+        host_facts: None,
     };
 
     let request = Request::builder()
@@ -836,6 +1149,60 @@ async fn test_metrics_endpoint_requests_config_upload_when_server_has_no_config(
     assert_eq!(metrics_response.config_status, ConfigStatus::Stale);
 }
 
+#[test]
+fn test_parse_agent_timestamp() {
+    assert_eq!(parse_agent_timestamp("1700000000"), Some(1_700_000_000));
+    assert_eq!(
+        parse_agent_timestamp("2023-01-01T00:00:00Z"),
+        Some(1_672_531_200)
+    );
+    assert_eq!(
+        parse_agent_timestamp("2023-01-01T01:00:00+01:00"),
+        Some(1_672_531_200)
+    );
+    assert_eq!(parse_agent_timestamp("yesterday"), None);
+
+    // Positive offset means the agent's clock is ahead of the server's
+    assert_eq!(agent_clock_offset("1700000090", 1_700_000_000), Some(90));
+    assert_eq!(agent_clock_offset("1699999970", 1_700_000_000), Some(-30));
+    assert_eq!(agent_clock_offset("", 1_700_000_000), None);
+}
+
+#[tokio::test]
+async fn test_metrics_endpoint_records_clock_offset() {
+    let (app, temp_dir) = create_test_app().await;
+
+    // Agent clock two minutes ahead of the server's
+    let agent_time = chrono::Utc::now().timestamp() + 120;
+    let test_request = MetricsRequest {
+        agent_id: "skewed-agent".to_string(),
+        timestamp_utc: agent_time.to_string(),
+        config_checksum: "checksum123".to_string(),
+        metrics: vec![],
+        raw_metrics: vec![],
+        agent_version: Some("0.7.6".to_string()),
+        agent_labels: Default::default(),
+        host_facts: None,
+    };
+
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri(endpoints::METRICS)
+        .header("content-type", "application/json")
+        .header(headers::API_KEY, "test-api-key")
+        .body(Body::from(serde_json::to_string(&test_request).unwrap()))
+        .unwrap();
+
+    // A skewed agent is not rejected
+    let response = app.oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let mut db = crate::database::ServerDatabase::new(temp_dir.path().join("data")).unwrap();
+    let agent = db.get_agent_info("skewed-agent").await.unwrap().unwrap();
+    let offset = agent.clock_offset_seconds.unwrap();
+    assert!((118..=120).contains(&offset), "offset {}", offset);
+}
+
 #[tokio::test]
 async fn test_config_verify_requests_upload_when_server_has_no_config() {
     let (app, _temp_dir) = create_test_app().await;
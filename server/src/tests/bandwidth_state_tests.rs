@@ -2,19 +2,48 @@
 
 use crate::bandwidth_state::BandwidthTestManager;
 use shared::api::BandwidthTestAction;
+use std::collections::HashMap;
+use tempfile::TempDir;
+
+fn site_groups() -> HashMap<String, Vec<String>> {
+    HashMap::from([
+        (
+            "site-a".to_string(),
+            vec!["a1".to_string(), "a2".to_string(), "a3".to_string()],
+        ),
+        ("site-b".to_string(), vec!["b1".to_string()]),
+    ])
+}
+
+async fn request(manager: &BandwidthTestManager, agent: &str) -> BandwidthTestAction {
+    manager
+        .request_test(agent.to_string(), 1024 * 1024, 1)
+        .await
+        .action
+}
+
+fn active_agents(status: &crate::bandwidth_state::BandwidthTestStatus) -> Vec<&str> {
+    let mut agents: Vec<&str> = status
+        .active_tests
+        .iter()
+        .map(|(agent, _)| agent.as_str())
+        .collect();
+    agents.sort();
+    agents
+}
 
 #[tokio::test]
 async fn test_bandwidth_manager_single_test() {
     let manager = BandwidthTestManager::new(120, 300, 30, 60, 30);
 
     let response = manager
-        .request_test("agent1".to_string(), 1024 * 1024)
+        .request_test("agent1".to_string(), 1024 * 1024, 1)
         .await;
     assert_eq!(response.action, BandwidthTestAction::Proceed);
     assert!(response.data_size_bytes.is_some());
 
     let status = manager.get_status().await;
-    assert!(status.current_test.is_some());
+    assert_eq!(status.active_tests.len(), 1);
 }
 
 #[tokio::test]
@@ -23,19 +52,19 @@ async fn test_bandwidth_manager_concurrent_tests() {
 
     // Start first test
     let response1 = manager
-        .request_test("agent1".to_string(), 1024 * 1024)
+        .request_test("agent1".to_string(), 1024 * 1024, 1)
         .await;
     assert_eq!(response1.action, BandwidthTestAction::Proceed);
 
     // Try to start second test - should be delayed
     let response2 = manager
-        .request_test("agent2".to_string(), 1024 * 1024)
+        .request_test("agent2".to_string(), 1024 * 1024, 1)
         .await;
     assert_eq!(response2.action, BandwidthTestAction::Delay);
     assert!(response2.delay_seconds.is_some());
 
     let status = manager.get_status().await;
-    assert!(status.current_test.is_some());
+    assert_eq!(status.active_tests.len(), 1);
 }
 
 #[tokio::test]
@@ -44,20 +73,20 @@ async fn test_bandwidth_manager_test_completion() {
 
     // Start first test
     manager
-        .request_test("agent1".to_string(), 1024 * 1024)
+        .request_test("agent1".to_string(), 1024 * 1024, 1)
         .await;
 
     // Queue second test
     manager
-        .request_test("agent2".to_string(), 1024 * 1024)
+        .request_test("agent2".to_string(), 1024 * 1024, 1)
         .await;
 
     // Complete first test
     manager.complete_test("agent1").await;
 
     let status = manager.get_status().await;
-    assert!(status.current_test.is_some());
-    assert_eq!(status.current_test.as_ref().unwrap().0, "agent2");
+    assert_eq!(status.active_tests.len(), 1);
+    assert_eq!(status.active_tests[0].0, "agent2");
 }
 
 #[tokio::test]
@@ -66,32 +95,32 @@ async fn test_bandwidth_manager_queue_ordering() {
 
     // Start first test
     manager
-        .request_test("agent1".to_string(), 1024 * 1024)
+        .request_test("agent1".to_string(), 1024 * 1024, 1)
         .await;
 
     // Queue multiple tests
     manager
-        .request_test("agent2".to_string(), 1024 * 1024)
+        .request_test("agent2".to_string(), 1024 * 1024, 1)
         .await;
     manager
-        .request_test("agent3".to_string(), 1024 * 1024)
+        .request_test("agent3".to_string(), 1024 * 1024, 1)
         .await;
     manager
-        .request_test("agent4".to_string(), 1024 * 1024)
+        .request_test("agent4".to_string(), 1024 * 1024, 1)
         .await;
 
     // Complete tests and verify FIFO ordering
     manager.complete_test("agent1").await;
     let status = manager.get_status().await;
-    assert_eq!(status.current_test.as_ref().unwrap().0, "agent2");
+    assert_eq!(status.active_tests[0].0, "agent2");
 
     manager.complete_test("agent2").await;
     let status = manager.get_status().await;
-    assert_eq!(status.current_test.as_ref().unwrap().0, "agent3");
+    assert_eq!(status.active_tests[0].0, "agent3");
 
     manager.complete_test("agent3").await;
     let status = manager.get_status().await;
-    assert_eq!(status.current_test.as_ref().unwrap().0, "agent4");
+    assert_eq!(status.active_tests[0].0, "agent4");
 }
 
 #[tokio::test]
@@ -100,27 +129,27 @@ async fn test_bandwidth_manager_get_status_accuracy() {
 
     // Initially empty
     let status = manager.get_status().await;
-    assert!(status.current_test.is_none());
+    assert!(status.active_tests.is_empty());
 
     // With active test
     manager
-        .request_test("agent1".to_string(), 1024 * 1024)
+        .request_test("agent1".to_string(), 1024 * 1024, 1)
         .await;
     let status = manager.get_status().await;
-    assert!(status.current_test.is_some());
-    assert_eq!(status.current_test.as_ref().unwrap().0, "agent1");
+    assert_eq!(status.active_tests.len(), 1);
+    assert_eq!(status.active_tests[0].0, "agent1");
     // Check elapsed time is very small (just started)
-    assert!(status.current_test.as_ref().unwrap().1 < 2);
+    assert!(status.active_tests[0].1 < 2);
 
     // With queued tests
     manager
-        .request_test("agent2".to_string(), 2 * 1024 * 1024)
+        .request_test("agent2".to_string(), 2 * 1024 * 1024, 1)
         .await;
     manager
-        .request_test("agent3".to_string(), 3 * 1024 * 1024)
+        .request_test("agent3".to_string(), 3 * 1024 * 1024, 1)
         .await;
     let status = manager.get_status().await;
-    assert_eq!(status.current_test.as_ref().unwrap().0, "agent1");
+    assert_eq!(status.active_tests[0].0, "agent1");
 }
 
 #[tokio::test]
@@ -132,7 +161,7 @@ async fn test_bandwidth_complete_test_nonexistent_agent() {
 
     // Verify status unchanged
     let status = manager.get_status().await;
-    assert!(status.current_test.is_none());
+    assert!(status.active_tests.is_empty());
 }
 
 #[tokio::test]
@@ -141,12 +170,12 @@ async fn test_bandwidth_manager_complete_wrong_agent() {
 
     // Start test for agent1
     manager
-        .request_test("agent1".to_string(), 1024 * 1024)
+        .request_test("agent1".to_string(), 1024 * 1024, 1)
         .await;
 
     // Queue agent2
     manager
-        .request_test("agent2".to_string(), 1024 * 1024)
+        .request_test("agent2".to_string(), 1024 * 1024, 1)
         .await;
 
     // Try to complete agent2 (not current test) - should have no effect
@@ -154,5 +183,199 @@ async fn test_bandwidth_manager_complete_wrong_agent() {
 
     // agent1 should still be the current test
     let status = manager.get_status().await;
-    assert_eq!(status.current_test.as_ref().unwrap().0, "agent1");
+    assert_eq!(status.active_tests[0].0, "agent1");
+}
+
+#[tokio::test]
+async fn test_bandwidth_parallel_streams_release_slot_after_last() {
+    let manager = BandwidthTestManager::new(120, 300, 30, 60, 30);
+
+    let response = manager
+        .request_test("agent1".to_string(), 1024 * 1024, 3)
+        .await;
+    assert_eq!(response.action, BandwidthTestAction::Proceed);
+
+    // Only the announced number of streams may start
+    for _ in 0..3 {
+        assert_eq!(manager.start_stream("agent1").await, Some(3));
+    }
+    assert_eq!(manager.start_stream("agent1").await, None);
+    assert_eq!(manager.start_stream("agent2").await, None);
+
+    manager.finish_stream("agent1").await;
+    manager.finish_stream("agent1").await;
+    assert_eq!(manager.get_status().await.active_tests.len(), 1);
+
+    manager.finish_stream("agent1").await;
+    assert!(manager.get_status().await.active_tests.is_empty());
+}
+
+#[tokio::test]
+async fn test_bandwidth_queued_agent_sets_stream_count() {
+    let manager = BandwidthTestManager::new(120, 300, 30, 60, 30);
+
+    manager
+        .request_test("agent1".to_string(), 1024 * 1024, 1)
+        .await;
+    let queued = manager
+        .request_test("agent2".to_string(), 1024 * 1024, 4)
+        .await;
+    assert_eq!(queued.action, BandwidthTestAction::Delay);
+
+    // agent2 is auto-started when agent1's only stream finishes
+    assert_eq!(manager.start_stream("agent1").await, Some(1));
+    manager.finish_stream("agent1").await;
+    let status = manager.get_status().await;
+    assert_eq!(status.active_tests[0].0, "agent2");
+
+    // Its stream count is taken from the retried request
+    let response = manager
+        .request_test("agent2".to_string(), 1024 * 1024, 4)
+        .await;
+    assert_eq!(response.action, BandwidthTestAction::Proceed);
+    assert_eq!(manager.start_stream("agent2").await, Some(4));
+}
+
+#[tokio::test]
+async fn test_bandwidth_concurrent_slots_for_unrelated_agents() {
+    let manager =
+        BandwidthTestManager::new(120, 300, 30, 60, 30).with_concurrency(2, 1, &HashMap::new());
+
+    assert_eq!(
+        request(&manager, "agent1").await,
+        BandwidthTestAction::Proceed
+    );
+    assert_eq!(
+        request(&manager, "agent2").await,
+        BandwidthTestAction::Proceed
+    );
+    assert_eq!(
+        request(&manager, "agent3").await,
+        BandwidthTestAction::Delay
+    );
+
+    let status = manager.get_status().await;
+    assert_eq!(active_agents(&status), vec!["agent1", "agent2"]);
+    assert_eq!(status.queued_agents, vec!["agent3"]);
+
+    manager.complete_test("agent2").await;
+    let status = manager.get_status().await;
+    assert_eq!(active_agents(&status), vec!["agent1", "agent3"]);
+    assert!(status.queued_agents.is_empty());
+}
+
+#[tokio::test]
+async fn test_bandwidth_group_limit_lets_other_sites_run() {
+    let manager =
+        BandwidthTestManager::new(120, 300, 30, 60, 30).with_concurrency(4, 1, &site_groups());
+
+    assert_eq!(request(&manager, "a1").await, BandwidthTestAction::Proceed);
+    // Same uplink as a1
+    assert_eq!(request(&manager, "a2").await, BandwidthTestAction::Delay);
+    // Other site and an ungrouped agent run alongside
+    assert_eq!(request(&manager, "b1").await, BandwidthTestAction::Proceed);
+    assert_eq!(request(&manager, "x1").await, BandwidthTestAction::Proceed);
+
+    let status = manager.get_status().await;
+    assert_eq!(active_agents(&status), vec!["a1", "b1", "x1"]);
+    assert_eq!(status.queued_agents, vec!["a2"]);
+
+    // a2 takes over once a1 releases the site's slot
+    manager.complete_test("a1").await;
+    let status = manager.get_status().await;
+    assert_eq!(active_agents(&status), vec!["a2", "b1", "x1"]);
+}
+
+#[tokio::test]
+async fn test_bandwidth_queue_skips_blocked_group_in_order() {
+    let manager =
+        BandwidthTestManager::new(120, 300, 30, 60, 30).with_concurrency(2, 1, &site_groups());
+
+    request(&manager, "a1").await;
+    request(&manager, "x1").await;
+    // Queue: a2 (site-a), x2, x3
+    request(&manager, "a2").await;
+    request(&manager, "x2").await;
+    request(&manager, "x3").await;
+
+    // site-a is still busy, so the freed slot goes to x2, not a2 or x3
+    manager.complete_test("x1").await;
+    let status = manager.get_status().await;
+    assert_eq!(active_agents(&status), vec!["a1", "x2"]);
+    assert_eq!(status.queued_agents, vec!["a2", "x3"]);
+
+    // a2 keeps its place ahead of x3 once its group frees up
+    manager.complete_test("a1").await;
+    let status = manager.get_status().await;
+    assert_eq!(active_agents(&status), vec!["a2", "x2"]);
+    assert_eq!(status.queued_agents, vec!["x3"]);
+}
+
+#[tokio::test]
+async fn test_bandwidth_retry_keeps_queue_position() {
+    let manager = BandwidthTestManager::new(120, 300, 30, 60, 30);
+
+    request(&manager, "agent1").await;
+    request(&manager, "agent2").await;
+    request(&manager, "agent3").await;
+
+    // agent2 retrying must not lose its place to agent3
+    let retry = manager
+        .request_test("agent2".to_string(), 1024 * 1024, 1)
+        .await;
+    assert_eq!(retry.action, BandwidthTestAction::Delay);
+    assert_eq!(retry.delay_seconds, Some(60));
+    let status = manager.get_status().await;
+    assert_eq!(status.queued_agents, vec!["agent2", "agent3"]);
+
+    // Later positions are told to wait longer
+    let retry = manager
+        .request_test("agent3".to_string(), 1024 * 1024, 1)
+        .await;
+    assert_eq!(retry.delay_seconds, Some(90));
+}
+
+#[tokio::test]
+async fn test_bandwidth_queue_survives_restart() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_file = temp_dir.path().join("bandwidth_queue.json");
+
+    {
+        let manager = BandwidthTestManager::new(120, 300, 30, 60, 30)
+            .with_concurrency(2, 1, &HashMap::new())
+            .with_state_file(&state_file);
+        request(&manager, "idle").await;
+        request(&manager, "transferring").await;
+        request(&manager, "waiting1").await;
+        request(&manager, "waiting2").await;
+        assert_eq!(manager.start_stream("transferring").await, Some(1));
+        manager.flush_state().await;
+    }
+    assert!(state_file.exists());
+
+    let manager = BandwidthTestManager::new(120, 300, 30, 60, 30)
+        .with_concurrency(2, 1, &HashMap::new())
+        .with_state_file(&state_file);
+    let status = manager.get_status().await;
+
+    // The test that had not started keeps its slot. The interrupted transfer
+    // is retried first and takes the other slot ahead of the waiting agents.
+    assert_eq!(active_agents(&status), vec!["idle", "transferring"]);
+    assert_eq!(status.queued_agents, vec!["waiting1", "waiting2"]);
+}
+
+#[tokio::test]
+async fn test_bandwidth_corrupt_state_file_starts_empty() {
+    let temp_dir = TempDir::new().unwrap();
+    let state_file = temp_dir.path().join("bandwidth_queue.json");
+    std::fs::write(&state_file, "not json").unwrap();
+
+    let manager = BandwidthTestManager::new(120, 300, 30, 60, 30).with_state_file(&state_file);
+    let status = manager.get_status().await;
+    assert!(status.active_tests.is_empty());
+    assert!(status.queued_agents.is_empty());
+    assert_eq!(
+        request(&manager, "agent1").await,
+        BandwidthTestAction::Proceed
+    );
 }
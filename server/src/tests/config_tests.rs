@@ -16,6 +16,7 @@ fn create_test_server_config() -> ServerConfig {
         listen_address: "127.0.0.1:8787".to_string(),
         api_key: "test-api-key".to_string(),
         data_retention_days: 30,
+        raw_data_retention_days: 3,
         agent_configs_dir: "/tmp/test-configs".to_string(),
         bandwidth_test_size_mb: 10,
         reconfigure_check_interval_seconds: 10,
@@ -29,6 +30,8 @@ fn create_test_server_config() -> ServerConfig {
         bandwidth_queue_current_test_delay_seconds: 60,
         bandwidth_queue_position_multiplier_seconds: 30,
         bandwidth_max_delay_seconds: 300,
+        bandwidth_max_concurrent_tests: 1,
+        bandwidth_group_max_concurrent: 1,
         initial_cleanup_delay_seconds: 3600,
         graceful_shutdown_timeout_seconds: 30,
         wal_checkpoint_interval_seconds: 60,
@@ -36,6 +39,14 @@ fn create_test_server_config() -> ServerConfig {
         health_check_interval_seconds: 300,
         health_check_success_ratio_threshold: 0.9,
         health_check_retention_days: 30,
+        health_check_max_clock_skew_seconds: 30,
+        rollups_enabled: true,
+        rollup_interval_seconds: 300,
+        rollup_lookback_hours: 2,
+        rollup_5m_retention_days: 90,
+        rollup_1h_retention_days: 365,
+        rollup_1d_retention_days: 1825,
+        bandwidth_groups: Default::default(),
     }
 }
 
@@ -155,6 +166,67 @@ fn test_agent_configs_dir_validation() {
     assert!(agent_configs_dir.is_dir());
 }
 
+#[test]
+fn test_bandwidth_groups_round_trip_and_validation() {
+    let temp_file = NamedTempFile::new().unwrap();
+    let mut config = create_test_server_config();
+    config.bandwidth_max_concurrent_tests = 4;
+    config.bandwidth_groups.insert(
+        "site-a".to_string(),
+        vec!["agent1".to_string(), "agent2".to_string()],
+    );
+    write_config_to_file(&config, temp_file.path()).unwrap();
+
+    let config_manager = ConfigManager::new(temp_file.path().to_path_buf()).unwrap();
+    let loaded = config_manager.server_config.as_ref().unwrap();
+    assert_eq!(loaded.bandwidth_max_concurrent_tests, 4);
+    assert_eq!(loaded.bandwidth_groups["site-a"].len(), 2);
+
+    // An agent can only share one uplink
+    config
+        .bandwidth_groups
+        .insert("site-b".to_string(), vec!["agent2".to_string()]);
+    assert!(config.validate().is_err());
+
+    config.bandwidth_groups.remove("site-b");
+    config.bandwidth_max_concurrent_tests = 0;
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_rollup_settings_validation() {
+    let mut config = create_test_server_config();
+    assert!(config.validate().is_ok());
+
+    config.rollup_interval_seconds = 0;
+    assert!(config.validate().is_err());
+    config.rollup_interval_seconds = 300;
+
+    // The lookback must stay within the base retention
+    config.rollup_lookback_hours = 30 * 24;
+    assert!(config.validate().is_err());
+    config.rollup_lookback_hours = 0;
+    assert!(config.validate().is_err());
+    config.rollup_lookback_hours = 2;
+
+    config.rollup_1h_retention_days = 0;
+    assert!(config.validate().is_err());
+}
+
+#[test]
+fn test_raw_data_retention_validation() {
+    let mut config = create_test_server_config();
+    assert!(config.validate().is_ok());
+
+    config.raw_data_retention_days = 0;
+    assert!(config.validate().is_err());
+
+    // The default stays valid with a shorter base retention
+    config.raw_data_retention_days = 3;
+    config.data_retention_days = 1;
+    assert!(config.validate().is_ok());
+}
+
 #[tokio::test]
 async fn test_get_agent_config_existing() {
     let temp_dir = TempDir::new().unwrap();
@@ -1,8 +1,11 @@
 //! Tests for the server database management module
 
+use crate::database::db_agent_inventory::InventoryChange;
 use crate::database::ServerDatabase;
+use shared::api::{HostFacts, InterfaceAddress};
 use shared::config::TaskType;
 use shared::metrics::{AggregatedMetricData, AggregatedMetrics, AggregatedPingMetric};
+use std::collections::BTreeMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 use tempfile::TempDir;
 
@@ -31,7 +34,7 @@ async fn test_agent_registration() {
     db.initialize().await.unwrap();
 
     let result = db
-        .upsert_agent("test-agent-01", "checksum123", Some("0.7.6"))
+        .upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), None)
         .await;
     assert!(result.is_ok());
 
@@ -40,6 +43,66 @@ async fn test_agent_registration() {
     assert_eq!(agent_info.unwrap().agent_id, "test-agent-01");
 }
 
+#[tokio::test]
+async fn test_agent_clock_offset_tracking() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = ServerDatabase::new(temp_dir.path()).unwrap();
+    db.initialize().await.unwrap();
+
+    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), Some(-45))
+        .await
+        .unwrap();
+    let agent_info = db.get_agent_info("test-agent-01").await.unwrap().unwrap();
+    assert_eq!(agent_info.clock_offset_seconds, Some(-45));
+
+    // Every push replaces the offset, including with an unknown one
+    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), Some(3))
+        .await
+        .unwrap();
+    let agents = db.get_all_agents().await.unwrap();
+    assert_eq!(agents[0].clock_offset_seconds, Some(3));
+
+    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), None)
+        .await
+        .unwrap();
+    let agent_info = db.get_agent_info("test-agent-01").await.unwrap().unwrap();
+    assert_eq!(agent_info.clock_offset_seconds, None);
+}
+
+#[tokio::test]
+async fn test_agents_table_migration_adds_clock_offset() {
+    let temp_dir = TempDir::new().unwrap();
+
+    // Agents table as created before clock offset tracking
+    {
+        let conn = rusqlite::Connection::open(temp_dir.path().join("server_metrics.db")).unwrap();
+        conn.execute(
+            r#"
+            CREATE TABLE agents (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                agent_id TEXT UNIQUE NOT NULL,
+                first_seen INTEGER NOT NULL,
+                last_seen INTEGER NOT NULL,
+                last_config_checksum TEXT,
+                total_metrics_received INTEGER DEFAULT 0,
+                agent_version TEXT,
+                created_at INTEGER DEFAULT (strftime('%s', 'now'))
+            )
+            "#,
+            [],
+        )
+        .unwrap();
+    }
+
+    let mut db = ServerDatabase::new(temp_dir.path()).unwrap();
+    db.initialize().await.unwrap();
+    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), Some(12))
+        .await
+        .unwrap();
+    let agent_info = db.get_agent_info("test-agent-01").await.unwrap().unwrap();
+    assert_eq!(agent_info.clock_offset_seconds, Some(12));
+}
+
 #[tokio::test]
 async fn test_metrics_storage() {
     let temp_dir = TempDir::new().unwrap();
@@ -47,7 +110,7 @@ async fn test_metrics_storage() {
     db.initialize().await.unwrap();
 
     // An agent must be registered before its metrics can be stored, due to the foreign key constraint.
-    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"))
+    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), None)
         .await
         .unwrap();
 
@@ -57,6 +120,12 @@ async fn test_metrics_storage() {
         period_start: 1640995200,
         period_end: 1640995260,
         sample_count: 60,
+        maintenance: false,
+        adaptive: false,
+        upstream_down: false,
+        avg_queue_wait_ms: None,
+        max_queue_wait_ms: None,
+        labels: Default::default(),
         data: AggregatedMetricData::Ping(AggregatedPingMetric {
             avg_latency_ms: 15.0,
             max_latency_ms: 25.0,
@@ -66,20 +135,733 @@ async fn test_metrics_storage() {
             failed_pings: 0,
             domain: None,
             target_id: None,
+            latency: None,
         }),
     };
 
-    let result = db.store_metrics("test-agent-01", &[metric]).await;
+    let result = db.store_metrics("test-agent-01", &[metric], &[]).await;
     assert!(result.is_ok());
 }
 
+#[tokio::test]
+async fn test_latency_distribution_storage() {
+    use shared::metrics::LatencyDistribution;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = ServerDatabase::new(temp_dir.path()).unwrap();
+    db.initialize().await.unwrap();
+
+    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), None)
+        .await
+        .unwrap();
+
+    let samples: Vec<f64> = (1..=100).map(|ms| ms as f64).collect();
+    let metric = AggregatedMetrics {
+        task_name: "Test Ping".to_string(),
+        task_type: TaskType::Ping,
+        period_start: 1640995200,
+        period_end: 1640995260,
+        sample_count: 100,
+        maintenance: false,
+        adaptive: false,
+        upstream_down: false,
+        avg_queue_wait_ms: None,
+        max_queue_wait_ms: None,
+        labels: Default::default(),
+        data: AggregatedMetricData::Ping(AggregatedPingMetric {
+            avg_latency_ms: 50.5,
+            max_latency_ms: 100.0,
+            min_latency_ms: 1.0,
+            packet_loss_percent: 0.0,
+            successful_pings: 100,
+            failed_pings: 0,
+            domain: None,
+            target_id: None,
+            latency: LatencyDistribution::from_samples(&samples),
+        }),
+    };
+
+    db.store_metrics("test-agent-01", &[metric], &[])
+        .await
+        .unwrap();
+
+    let conn = db.get_connection().unwrap();
+    let (p95, histogram): (f64, String) = conn
+        .query_row(
+            "SELECT p95_ms, latency_histogram FROM agg_metric_ping WHERE agent_id = 'test-agent-01'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(p95, 95.0);
+    let histogram: Vec<u32> = serde_json::from_str(&histogram).unwrap();
+    assert_eq!(histogram, vec![1, 1, 3, 5, 10, 30, 50, 0, 0, 0, 0, 0]);
+}
+
+#[tokio::test]
+async fn test_rollups_combine_periods_into_tiers() {
+    use shared::metrics::LatencyDistribution;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = ServerDatabase::new(temp_dir.path()).unwrap();
+    db.initialize().await.unwrap();
+
+    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), None)
+        .await
+        .unwrap();
+
+    // Five one-minute periods filling a 5-minute period, plus one in the next
+    let base = 1_699_999_800; // Aligned to 5 minutes
+    let metrics: Vec<AggregatedMetrics> = (0..6u64)
+        .map(|minute| {
+            let latency_ms = 10.0 * (minute % 5 + 1) as f64;
+            AggregatedMetrics {
+                task_name: "Test Ping".to_string(),
+                task_type: TaskType::Ping,
+                period_start: base + minute * 60,
+                period_end: base + (minute + 1) * 60,
+                sample_count: 10,
+                maintenance: false,
+                adaptive: false,
+                upstream_down: false,
+                avg_queue_wait_ms: None,
+                max_queue_wait_ms: None,
+                labels: Default::default(),
+                data: AggregatedMetricData::Ping(AggregatedPingMetric {
+                    avg_latency_ms: latency_ms,
+                    max_latency_ms: latency_ms + 5.0,
+                    min_latency_ms: latency_ms - 5.0,
+                    packet_loss_percent: 0.0,
+                    successful_pings: 10,
+                    failed_pings: 0,
+                    domain: None,
+                    target_id: Some("dc-1".to_string()),
+                    latency: LatencyDistribution::from_samples(&[latency_ms]),
+                }),
+            }
+        })
+        .collect();
+    db.store_metrics("test-agent-01", &metrics, &[])
+        .await
+        .unwrap();
+
+    // Empty tiers are backfilled regardless of the lookback; a second run is idempotent.
+    // Retention is long enough that none of the 2023 source rows count as purged.
+    db.perform_rollups(3600, 36500, 36500, 36500).unwrap();
+    db.perform_rollups(3600, 36500, 36500, 36500).unwrap();
+
+    {
+        let conn = db.get_connection().unwrap();
+        let (period_end, sample_count, avg, max, min, p95, histogram, target_id): (
+            i64,
+            i64,
+            f64,
+            f64,
+            f64,
+            f64,
+            String,
+            String,
+        ) = conn
+            .query_row(
+                "SELECT period_end, sample_count, avg_latency_ms, max_latency_ms, min_latency_ms, p95_ms, latency_histogram, target_id
+                 FROM agg_metric_ping_5m WHERE period_start = ?1",
+                [base as i64],
+                |row| {
+                    Ok((
+                        row.get(0)?,
+                        row.get(1)?,
+                        row.get(2)?,
+                        row.get(3)?,
+                        row.get(4)?,
+                        row.get(5)?,
+                        row.get(6)?,
+                        row.get(7)?,
+                    ))
+                },
+            )
+            .unwrap();
+        assert_eq!(period_end, base as i64 + 300);
+        assert_eq!(sample_count, 50);
+        assert_eq!(avg, 30.0);
+        assert_eq!(max, 55.0);
+        assert_eq!(min, 5.0);
+        // The 95th percentile of 10..50 ms falls at the top of the 20-50 ms bucket
+        assert_eq!(p95, 50.0);
+        assert_eq!(histogram, "[0,0,0,1,1,3,0,0,0,0,0,0]");
+        assert_eq!(target_id, "dc-1");
+
+        let five_minute_rows: i64 = conn
+            .query_row("SELECT COUNT(*) FROM agg_metric_ping_5m", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(five_minute_rows, 2);
+
+        // Hourly and daily tiers are computed from the finer tier
+        for table in ["agg_metric_ping_1h", "agg_metric_ping_1d"] {
+            let (rows, sample_count, histogram): (i64, i64, String) = conn
+                .query_row(
+                    &format!(
+                        "SELECT COUNT(*), SUM(sample_count), MAX(latency_histogram) FROM {}",
+                        table
+                    ),
+                    [],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )
+                .unwrap();
+            assert_eq!(rows, 1, "{}", table);
+            assert_eq!(sample_count, 60, "{}", table);
+            assert_eq!(histogram, "[0,0,0,2,1,3,0,0,0,0,0,0]", "{}", table);
+        }
+    }
+
+    // Each tier has its own retention: the 2023 daily row outlives the finer tiers
+    let deleted = db.cleanup_old_rollups(90, 365, 36500).await.unwrap();
+    assert_eq!(deleted, 3);
+    let conn = db.get_connection().unwrap();
+    let daily_rows: i64 = conn
+        .query_row("SELECT COUNT(*) FROM agg_metric_ping_1d", [], |row| {
+            row.get(0)
+        })
+        .unwrap();
+    assert_eq!(daily_rows, 1);
+}
+
+#[tokio::test]
+async fn test_rollups_keep_periods_of_purged_source() {
+    use shared::metrics::LatencyDistribution;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = ServerDatabase::new(temp_dir.path()).unwrap();
+    db.initialize().await.unwrap();
+
+    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), None)
+        .await
+        .unwrap();
+
+    // One-minute periods filling the hour that a one-day retention cutoff falls into
+    let hour = (current_timestamp() - 86400) / 3600 * 3600;
+    let metrics: Vec<AggregatedMetrics> = (0..60u64)
+        .map(|minute| AggregatedMetrics {
+            task_name: "Test Ping".to_string(),
+            task_type: TaskType::Ping,
+            period_start: hour + minute * 60,
+            period_end: hour + (minute + 1) * 60,
+            sample_count: 1,
+            maintenance: false,
+            adaptive: false,
+            upstream_down: false,
+            avg_queue_wait_ms: None,
+            max_queue_wait_ms: None,
+            labels: Default::default(),
+            data: AggregatedMetricData::Ping(AggregatedPingMetric {
+                avg_latency_ms: 10.0,
+                max_latency_ms: 10.0,
+                min_latency_ms: 10.0,
+                packet_loss_percent: 0.0,
+                successful_pings: 1,
+                failed_pings: 0,
+                domain: None,
+                target_id: None,
+                latency: LatencyDistribution::from_samples(&[10.0]),
+            }),
+        })
+        .collect();
+    db.store_metrics("test-agent-01", &metrics, &[])
+        .await
+        .unwrap();
+
+    let lookback = 3 * 86400;
+    db.perform_rollups(lookback, 7, 7, 365).unwrap();
+
+    let hourly_samples = |db: &mut ServerDatabase| -> i64 {
+        db.get_connection()
+            .unwrap()
+            .query_row(
+                "SELECT sample_count FROM agg_metric_ping_1h WHERE period_start = ?1",
+                [hour as i64],
+                |row| row.get(0),
+            )
+            .unwrap()
+    };
+    assert_eq!(hourly_samples(&mut db), 60);
+
+    // Purge the start of the hour from the base table and the 5-minute tier
+    db.cleanup_old_data(1).await.unwrap();
+    db.cleanup_old_rollups(1, 365, 1825).await.unwrap();
+
+    // A rollup within the lookback must not rebuild the hour from what is left
+    db.perform_rollups(lookback, 1, 1, 365).unwrap();
+    assert_eq!(hourly_samples(&mut db), 60);
+
+    let daily_samples: i64 = db
+        .get_connection()
+        .unwrap()
+        .query_row(
+            "SELECT SUM(sample_count) FROM agg_metric_ping_1d",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(daily_samples, 60);
+}
+
+#[tokio::test]
+async fn test_udp_probe_metrics_storage() {
+    use shared::metrics::AggregatedUdpProbeMetric;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = ServerDatabase::new(temp_dir.path()).unwrap();
+    db.initialize().await.unwrap();
+
+    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), None)
+        .await
+        .unwrap();
+
+    let metric = AggregatedMetrics {
+        task_name: "Voice to Branch B".to_string(),
+        task_type: TaskType::UdpProbe,
+        period_start: 1640995200,
+        period_end: 1640995260,
+        sample_count: 2,
+        maintenance: false,
+        adaptive: false,
+        upstream_down: false,
+        avg_queue_wait_ms: None,
+        max_queue_wait_ms: None,
+        labels: Default::default(),
+        data: AggregatedMetricData::UdpProbe(AggregatedUdpProbeMetric {
+            avg_rtt_ms: 24.5,
+            max_rtt_ms: 40.0,
+            min_rtt_ms: 18.0,
+            avg_jitter_ms: 2.1,
+            max_jitter_ms: 3.4,
+            loss_percent: 1.0,
+            forward_loss_percent: Some(1.0),
+            return_loss_percent: None,
+            packets_sent: 100,
+            packets_received: 99,
+            duplicate_packets: 0,
+            reordered_packets: 1,
+            mos: 4.3,
+            successful_probes: 2,
+            failed_probes: 0,
+            host: "10.2.0.5:8789".to_string(),
+            target_id: Some("branch-b".to_string()),
+        }),
+    };
+
+    db.store_metrics("test-agent-01", &[metric], &[])
+        .await
+        .unwrap();
+
+    let conn = db.get_connection().unwrap();
+    let (mos, return_loss): (f64, Option<f64>) = conn
+        .query_row(
+            "SELECT mos, return_loss_percent FROM agg_metric_udp_probe WHERE agent_id = 'test-agent-01'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(mos, 4.3);
+    assert_eq!(return_loss, None);
+}
+
+#[tokio::test]
+async fn test_twamp_metrics_storage() {
+    use shared::metrics::AggregatedTwampMetric;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = ServerDatabase::new(temp_dir.path()).unwrap();
+    db.initialize().await.unwrap();
+
+    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), None)
+        .await
+        .unwrap();
+
+    let metric = AggregatedMetrics {
+        task_name: "Core router".to_string(),
+        task_type: TaskType::Twamp,
+        period_start: 1640995200,
+        period_end: 1640995260,
+        sample_count: 2,
+        maintenance: false,
+        adaptive: false,
+        upstream_down: false,
+        avg_queue_wait_ms: None,
+        max_queue_wait_ms: None,
+        labels: Default::default(),
+        data: AggregatedMetricData::Twamp(AggregatedTwampMetric {
+            avg_latency_ms: 1.8,
+            max_latency_ms: 3.2,
+            min_latency_ms: 1.1,
+            packet_loss_percent: 5.0,
+            avg_jitter_ms: 0.3,
+            max_jitter_ms: 0.5,
+            packets_sent: 20,
+            packets_received: 19,
+            successful_tests: 2,
+            failed_tests: 0,
+            host: "10.0.0.1:862".to_string(),
+            target_id: None,
+        }),
+    };
+
+    db.store_metrics("test-agent-01", &[metric], &[])
+        .await
+        .unwrap();
+
+    let conn = db.get_connection().unwrap();
+    let (avg_latency, loss): (f64, f64) = conn
+        .query_row(
+            "SELECT avg_latency_ms, packet_loss_percent FROM agg_metric_twamp WHERE agent_id = 'test-agent-01'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(avg_latency, 1.8);
+    assert_eq!(loss, 5.0);
+}
+
+#[tokio::test]
+async fn test_tcp_sweep_metrics_storage() {
+    use shared::metrics::{AggregatedTcpSweepMetric, TcpSweepState, TcpSweepTargetSummary};
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = ServerDatabase::new(temp_dir.path()).unwrap();
+    db.initialize().await.unwrap();
+
+    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), None)
+        .await
+        .unwrap();
+
+    let metric = AggregatedMetrics {
+        task_name: "Web farm".to_string(),
+        task_type: TaskType::TcpSweep,
+        period_start: 1640995200,
+        period_end: 1640995260,
+        sample_count: 1,
+        maintenance: false,
+        adaptive: false,
+        upstream_down: false,
+        avg_queue_wait_ms: None,
+        max_queue_wait_ms: None,
+        labels: Default::default(),
+        data: AggregatedMetricData::TcpSweep(AggregatedTcpSweepMetric {
+            targets_total: 2,
+            avg_open_count: 1.0,
+            min_open_count: 1,
+            avg_closed_count: 1.0,
+            avg_filtered_count: 0.0,
+            avg_unresolved_count: 0.0,
+            avg_connect_time_ms: 1.5,
+            max_connect_time_ms: 1.5,
+            min_connect_time_ms: 1.5,
+            successful_sweeps: 1,
+            failed_sweeps: 0,
+            targets: vec![TcpSweepTargetSummary {
+                target: "10.0.1.1:443".to_string(),
+                open_percent: 100.0,
+                avg_connect_time_ms: Some(1.5),
+                last_state: TcpSweepState::Open,
+            }],
+            target_id: None,
+        }),
+    };
+
+    db.store_metrics("test-agent-01", &[metric], &[])
+        .await
+        .unwrap();
+
+    let conn = db.get_connection().unwrap();
+    let (open_count, targets): (f64, String) = conn
+        .query_row(
+            "SELECT avg_open_count, targets FROM agg_metric_tcp_sweep WHERE agent_id = 'test-agent-01'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(open_count, 1.0);
+    assert!(targets.contains("\"last_state\":\"open\""));
+}
+
+#[tokio::test]
+async fn test_ntp_metrics_storage() {
+    use shared::metrics::AggregatedNtpMetric;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = ServerDatabase::new(temp_dir.path()).unwrap();
+    db.initialize().await.unwrap();
+
+    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), None)
+        .await
+        .unwrap();
+
+    let metric = AggregatedMetrics {
+        task_name: "Pool NTP".to_string(),
+        task_type: TaskType::Ntp,
+        period_start: 1640995200,
+        period_end: 1640995260,
+        sample_count: 1,
+        maintenance: false,
+        adaptive: false,
+        upstream_down: false,
+        avg_queue_wait_ms: None,
+        max_queue_wait_ms: None,
+        labels: Default::default(),
+        data: AggregatedMetricData::Ntp(AggregatedNtpMetric {
+            avg_offset_ms: -3.5,
+            min_offset_ms: -3.5,
+            max_offset_ms: -3.5,
+            max_abs_offset_ms: 3.5,
+            avg_delay_ms: 12.0,
+            max_delay_ms: 12.0,
+            stratum: Some(2),
+            reference_id: Some("10.0.0.1".to_string()),
+            leap_indicator: Some(0),
+            successful_queries: 1,
+            failed_queries: 0,
+            ok_count: 1,
+            warning_count: 0,
+            critical_count: 0,
+            error_count: 0,
+            host: "pool.ntp.org".to_string(),
+            target_id: None,
+        }),
+    };
+
+    db.store_metrics("test-agent-01", &[metric], &[])
+        .await
+        .unwrap();
+
+    let conn = db.get_connection().unwrap();
+    let (offset, stratum): (f64, i64) = conn
+        .query_row(
+            "SELECT avg_offset_ms, stratum FROM agg_metric_ntp WHERE agent_id = 'test-agent-01'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(offset, -3.5);
+    assert_eq!(stratum, 2);
+}
+
+#[tokio::test]
+async fn test_raw_metrics_storage_and_cleanup() {
+    use shared::metrics::{MetricData, RawHttpMetric, RawMetricData};
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = ServerDatabase::new(temp_dir.path()).unwrap();
+    db.initialize().await.unwrap();
+
+    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), None)
+        .await
+        .unwrap();
+
+    let sample = |timestamp: u64, error: Option<&str>| MetricData {
+        task_name: "Homepage".to_string(),
+        task_type: TaskType::HttpGet,
+        timestamp,
+        labels: BTreeMap::from([("site".to_string(), "ams1".to_string())]),
+        row_id: Some(timestamp as i64),
+        data: RawMetricData::HttpGet(RawHttpMetric {
+            status_code: error.is_none().then_some(200),
+            tcp_timing_ms: Some(4.5),
+            tls_timing_ms: Some(12.0),
+            ttfb_timing_ms: error.is_none().then_some(80.0),
+            content_download_timing_ms: None,
+            total_time_ms: Some(96.5),
+            success: error.is_none(),
+            error: error.map(str::to_string),
+            ssl_valid: Some(true),
+            ssl_cert_days_until_expiry: Some(42),
+            target_id: None,
+        }),
+    };
+    let now = current_timestamp();
+    let old = now - 5 * 24 * 60 * 60;
+    let metrics = vec![
+        sample(now - 20, None),
+        sample(now - 10, Some("Connection reset by peer")),
+        sample(old, None),
+        MetricData {
+            data: RawMetricData::Unknown,
+            ..sample(now, None)
+        },
+    ];
+
+    // Unknown raw metrics are skipped, every other sample is kept individually
+    db.store_metrics("test-agent-01", &[], &metrics)
+        .await
+        .unwrap();
+    {
+        let conn = db.get_connection().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT ttfb_timing_ms, error, labels FROM raw_metric_http WHERE agent_id = 'test-agent-01' ORDER BY timestamp DESC")
+            .unwrap();
+        let rows: Vec<(Option<f64>, Option<String>, Option<String>)> = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(rows.len(), 3);
+        assert_eq!(rows[0].0, None);
+        assert_eq!(rows[0].1.as_deref(), Some("Connection reset by peer"));
+        assert_eq!(rows[1].0, Some(80.0));
+        assert_eq!(rows[1].2.as_deref(), Some(r#"{"site":"ams1"}"#));
+    }
+
+    // A push delivered again does not duplicate its raw samples
+    db.store_metrics("test-agent-01", &[], &metrics)
+        .await
+        .unwrap();
+    {
+        let conn = db.get_connection().unwrap();
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM raw_metric_http", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 3);
+    }
+
+    // Raw samples older than the raw retention are deleted
+    assert_eq!(db.cleanup_old_raw_data(3).await.unwrap(), 1);
+    let conn = db.get_connection().unwrap();
+    let count: i64 = conn
+        .query_row("SELECT COUNT(*) FROM raw_metric_http", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(count, 2);
+}
+
+#[tokio::test]
+async fn test_failed_push_stores_no_raw_samples() {
+    use shared::metrics::{MetricData, RawMetricData, RawPingMetric};
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = ServerDatabase::new(temp_dir.path()).unwrap();
+    db.initialize().await.unwrap();
+
+    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), None)
+        .await
+        .unwrap();
+
+    let aggregate = AggregatedMetrics::new(
+        "Test Ping".to_string(),
+        TaskType::Ping,
+        1640995200,
+        1640995260,
+        1,
+        AggregatedMetricData::Ping(AggregatedPingMetric {
+            avg_latency_ms: 15.0,
+            max_latency_ms: 15.0,
+            min_latency_ms: 15.0,
+            packet_loss_percent: 0.0,
+            successful_pings: 1,
+            failed_pings: 0,
+            domain: None,
+            target_id: None,
+            latency: None,
+        }),
+    );
+    let sample = MetricData {
+        timestamp: 1640995230,
+        row_id: Some(7),
+        ..MetricData::new(
+            "Test Ping".to_string(),
+            TaskType::Ping,
+            RawMetricData::Ping(RawPingMetric {
+                rtt_ms: Some(15.0),
+                success: true,
+                error: None,
+                ip_address: "192.0.2.1".to_string(),
+                domain: None,
+                target_id: None,
+            }),
+        )
+    };
+    db.store_metrics("test-agent-01", std::slice::from_ref(&aggregate), &[])
+        .await
+        .unwrap();
+
+    // The aggregate is already stored, so the push fails as a whole
+    let result = db
+        .store_metrics("test-agent-01", &[aggregate], &[sample])
+        .await;
+    assert!(result.is_err());
+    let conn = db.get_connection().unwrap();
+    let count: i64 = conn
+        .query_row("SELECT COUNT(*) FROM raw_metric_ping", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(count, 0);
+}
+
+#[cfg(feature = "snmp-tasks")]
+#[tokio::test]
+async fn test_snmp_trap_event_storage_ignores_duplicates() {
+    use shared::metrics::{SnmpTrapEventMetric, SnmpVarbind};
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = ServerDatabase::new(temp_dir.path()).unwrap();
+    db.initialize().await.unwrap();
+
+    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), None)
+        .await
+        .unwrap();
+
+    let event = AggregatedMetrics {
+        task_name: "Core Switch".to_string(),
+        task_type: TaskType::Snmp,
+        period_start: 1640995200,
+        period_end: 1640995200,
+        sample_count: 1,
+        maintenance: false,
+        adaptive: false,
+        upstream_down: false,
+        avg_queue_wait_ms: None,
+        max_queue_wait_ms: None,
+        labels: Default::default(),
+        data: AggregatedMetricData::SnmpTrap(SnmpTrapEventMetric {
+            event_id: 42,
+            source_address: "192.0.2.10:50162".to_string(),
+            version: "v2c".to_string(),
+            pdu_type: "trap".to_string(),
+            trap_oid: "1.3.6.1.6.3.1.1.5.3".to_string(),
+            uptime_ticks: Some(123456),
+            varbinds: vec![SnmpVarbind {
+                oid: "1.3.6.1.2.1.2.2.1.1.3".to_string(),
+                value: "3".to_string(),
+                value_type: "Integer".to_string(),
+            }],
+            target_id: Some("core".to_string()),
+        }),
+    };
+
+    // A re-delivered event (e.g. after a lost response) must not fail the batch
+    db.store_metrics("test-agent-01", std::slice::from_ref(&event), &[])
+        .await
+        .unwrap();
+    db.store_metrics("test-agent-01", &[event], &[])
+        .await
+        .unwrap();
+
+    let conn = db.get_connection().unwrap();
+    let (count, varbinds): (i64, String) = conn
+        .query_row(
+            "SELECT COUNT(*), MAX(varbinds) FROM snmp_trap_event WHERE agent_id = 'test-agent-01'",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .unwrap();
+    assert_eq!(count, 1);
+    assert!(varbinds.contains("1.3.6.1.2.1.2.2.1.1.3"));
+}
+
 #[tokio::test]
 async fn test_config_error_logging() {
     let temp_dir = TempDir::new().unwrap();
     let mut db = ServerDatabase::new(temp_dir.path()).unwrap();
     db.initialize().await.unwrap();
 
-    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"))
+    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), None)
         .await
         .unwrap();
 
@@ -112,10 +894,10 @@ async fn test_get_all_agents() {
     let mut db = ServerDatabase::new(temp_dir.path()).unwrap();
     db.initialize().await.unwrap();
 
-    db.upsert_agent("agent-01", "checksum1", Some("0.7.6"))
+    db.upsert_agent("agent-01", "checksum1", Some("0.7.6"), None)
         .await
         .unwrap();
-    db.upsert_agent("agent-02", "checksum2", Some("0.7.5"))
+    db.upsert_agent("agent-02", "checksum2", Some("0.7.5"), None)
         .await
         .unwrap();
 
@@ -130,7 +912,7 @@ async fn test_cleanup_old_data_deletes_expired_metrics() {
     db.initialize().await.unwrap();
 
     // Register agent first
-    db.upsert_agent("test-agent", "hash", Some("0.7.6"))
+    db.upsert_agent("test-agent", "hash", Some("0.7.6"), None)
         .await
         .unwrap();
 
@@ -184,7 +966,7 @@ async fn test_cleanup_old_data_deletes_inactive_agents() {
     }
 
     // Insert recent agent
-    db.upsert_agent("recent-agent", "hash", Some("0.7.6"))
+    db.upsert_agent("recent-agent", "hash", Some("0.7.6"), None)
         .await
         .unwrap();
 
@@ -204,7 +986,7 @@ async fn test_cleanup_old_data_preserves_recent_data() {
     db.initialize().await.unwrap();
 
     // Register agent and insert metrics within retention period
-    db.upsert_agent("test-agent", "hash", Some("0.7.6"))
+    db.upsert_agent("test-agent", "hash", Some("0.7.6"), None)
         .await
         .unwrap();
 
@@ -214,6 +996,12 @@ async fn test_cleanup_old_data_preserves_recent_data() {
         period_start: current_timestamp() - (5 * 24 * 60 * 60), // 5 days ago
         period_end: current_timestamp() - (5 * 24 * 60 * 60),
         sample_count: 10,
+        maintenance: false,
+        adaptive: false,
+        upstream_down: false,
+        avg_queue_wait_ms: None,
+        max_queue_wait_ms: None,
+        labels: Default::default(),
         data: AggregatedMetricData::Ping(AggregatedPingMetric {
             avg_latency_ms: 15.0,
             max_latency_ms: 25.0,
@@ -223,10 +1011,13 @@ async fn test_cleanup_old_data_preserves_recent_data() {
             failed_pings: 0,
             domain: None,
             target_id: None,
+            latency: None,
         }),
     };
 
-    db.store_metrics("test-agent", &[metric]).await.unwrap();
+    db.store_metrics("test-agent", &[metric], &[])
+        .await
+        .unwrap();
 
     // Run cleanup with 30 day retention
     db.cleanup_old_data(30).await.unwrap();
@@ -243,7 +1034,7 @@ async fn test_cleanup_old_data_deletes_old_config_errors() {
     let mut db = ServerDatabase::new(temp_dir.path()).unwrap();
     db.initialize().await.unwrap();
 
-    db.upsert_agent("test-agent", "hash", Some("0.7.6"))
+    db.upsert_agent("test-agent", "hash", Some("0.7.6"), None)
         .await
         .unwrap();
 
@@ -277,7 +1068,7 @@ async fn test_get_agent_info_existing_agent() {
     let mut db = ServerDatabase::new(temp_dir.path()).unwrap();
     db.initialize().await.unwrap();
 
-    db.upsert_agent("test-agent", "checksum123", Some("0.7.6"))
+    db.upsert_agent("test-agent", "checksum123", Some("0.7.6"), None)
         .await
         .unwrap();
 
@@ -309,3 +1100,198 @@ async fn test_database_close() {
     // Connection should be None after close
     assert!(db.connection.is_none());
 }
+
+#[tokio::test]
+async fn test_task_labels_storage() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = ServerDatabase::new(temp_dir.path()).unwrap();
+    db.initialize().await.unwrap();
+
+    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), None)
+        .await
+        .unwrap();
+
+    let mut metric = AggregatedMetrics::new(
+        "Test Ping".to_string(),
+        TaskType::Ping,
+        1640995200,
+        1640995260,
+        60,
+        AggregatedMetricData::Ping(AggregatedPingMetric {
+            avg_latency_ms: 15.0,
+            max_latency_ms: 25.0,
+            min_latency_ms: 10.0,
+            packet_loss_percent: 0.0,
+            successful_pings: 60,
+            failed_pings: 0,
+            domain: None,
+            target_id: None,
+            latency: None,
+        }),
+    );
+    metric.labels = [("site", "paris"), ("environment", "prod")]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    db.store_metrics("test-agent-01", &[metric.clone()], &[])
+        .await
+        .unwrap();
+
+    {
+        let conn = db.get_connection().unwrap();
+        let labels: String = conn
+            .query_row("SELECT labels FROM agg_metric_ping", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(labels, r#"{"environment":"prod","site":"paris"}"#);
+
+        // Metrics can be filtered by label through the index table
+        let matching: i64 = conn
+            .query_row(
+                "SELECT COUNT(*) FROM agg_metric_ping m
+                 JOIN task_labels l ON l.agent_id = m.agent_id AND l.task_name = m.task_name
+                 WHERE l.label_key = 'site' AND l.label_value = 'paris'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(matching, 1);
+    }
+
+    // Later metrics replace the indexed labels of the task
+    metric.period_start += 60;
+    metric.period_end += 60;
+    metric.labels.remove("environment");
+    metric.labels.insert("site".to_string(), "lyon".to_string());
+    db.store_metrics("test-agent-01", &[metric.clone()], &[])
+        .await
+        .unwrap();
+
+    let indexed_labels = |db: &mut ServerDatabase| -> Vec<(String, String)> {
+        let conn = db.get_connection().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT label_key, label_value FROM task_labels ORDER BY label_key")
+            .unwrap();
+        let labels = stmt
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        labels
+    };
+    let lyon = vec![("site".to_string(), "lyon".to_string())];
+    assert_eq!(indexed_labels(&mut db), lyon);
+
+    // A batch delivered late with an earlier period keeps the newer labels,
+    // and within a batch the latest period wins regardless of order
+    let mut earlier = metric.clone();
+    earlier.period_start -= 120;
+    earlier.period_end -= 120;
+    earlier
+        .labels
+        .insert("site".to_string(), "nice".to_string());
+    db.store_metrics("test-agent-01", &[earlier.clone()], &[])
+        .await
+        .unwrap();
+    assert_eq!(indexed_labels(&mut db), lyon);
+
+    metric.period_start += 60;
+    metric.period_end += 60;
+    earlier.period_start -= 60;
+    earlier.period_end -= 60;
+    db.store_metrics("test-agent-01", &[metric, earlier], &[])
+        .await
+        .unwrap();
+    assert_eq!(indexed_labels(&mut db), lyon);
+}
+
+#[tokio::test]
+async fn test_agent_inventory_history() {
+    let temp_dir = TempDir::new().unwrap();
+    let mut db = ServerDatabase::new(temp_dir.path()).unwrap();
+    db.initialize().await.unwrap();
+
+    db.upsert_agent("test-agent-01", "checksum123", Some("0.7.6"), None)
+        .await
+        .unwrap();
+
+    let mut labels: BTreeMap<String, String> = [("site", "paris"), ("isp", "orange")]
+        .into_iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect();
+    let mut facts = HostFacts {
+        hostname: "probe-paris".to_string(),
+        os: "Debian GNU/Linux 12 (bookworm)".to_string(),
+        kernel: Some("6.1.0-18-amd64".to_string()),
+        interfaces: vec![InterfaceAddress {
+            name: "eth0".to_string(),
+            address: "192.168.0.10".to_string(),
+            prefix_len: 24,
+        }],
+        default_gateway: Some("192.168.0.1".to_string()),
+        public_ip: Some("203.0.113.7".to_string()),
+    };
+
+    let change = db
+        .update_agent_inventory("test-agent-01", &labels, Some(&facts))
+        .await
+        .unwrap();
+    assert_eq!(change, InventoryChange::New);
+
+    // The same report again does not add history
+    let change = db
+        .update_agent_inventory("test-agent-01", &labels, Some(&facts))
+        .await
+        .unwrap();
+    assert_eq!(change, InventoryChange::Unchanged);
+
+    // A new egress IP and ISP are recorded, returning the previous inventory
+    facts.public_ip = Some("198.51.100.20".to_string());
+    labels.insert("isp".to_string(), "free".to_string());
+    let change = db
+        .update_agent_inventory("test-agent-01", &labels, Some(&facts))
+        .await
+        .unwrap();
+    match change {
+        InventoryChange::Changed(previous) => {
+            assert_eq!(
+                previous.host_facts.unwrap().public_ip.as_deref(),
+                Some("203.0.113.7")
+            );
+            assert_eq!(
+                previous.labels.get("isp").map(String::as_str),
+                Some("orange")
+            );
+        }
+        other => panic!("Expected a changed inventory, got {:?}", other),
+    }
+
+    let current = db
+        .get_agent_inventory("test-agent-01")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(current.labels, labels);
+    assert_eq!(current.host_facts.as_ref(), Some(&facts));
+
+    let history = db
+        .get_agent_inventory_history("test-agent-01")
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 2);
+    assert_eq!(
+        history[0].host_facts.as_ref().unwrap().public_ip.as_deref(),
+        Some("203.0.113.7")
+    );
+    assert_eq!(history[1], current);
+
+    // Agents can be grouped by label through the index table
+    let conn = db.get_connection().unwrap();
+    let isp: String = conn
+        .query_row(
+            "SELECT label_value FROM agent_labels WHERE agent_id = 'test-agent-01' AND label_key = 'isp'",
+            [],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(isp, "free");
+}
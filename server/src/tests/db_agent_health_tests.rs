@@ -71,6 +71,7 @@ fn test_store_and_retrieve_health_check() {
         received_entries: 85,
         success_ratio: 0.85,
         is_problematic: true,
+        clock_offset_seconds: None,
     };
 
     let tx = conn.transaction().unwrap();
@@ -100,6 +101,7 @@ fn test_get_problematic_agents() {
         received_entries: 95,
         success_ratio: 0.95,
         is_problematic: false,
+        clock_offset_seconds: None,
     };
 
     let sick = AgentHealthCheck {
@@ -112,6 +114,7 @@ fn test_get_problematic_agents() {
         received_entries: 50,
         success_ratio: 0.50,
         is_problematic: true,
+        clock_offset_seconds: None,
     };
 
     let tx = conn.transaction().unwrap();
@@ -141,6 +144,7 @@ fn test_get_agent_health_history() {
             received_entries: 90 + i,
             success_ratio: (90 + i) as f64 / 100.0,
             is_problematic: false,
+            clock_offset_seconds: None,
         };
 
         let tx = conn.transaction().unwrap();
@@ -172,6 +176,7 @@ fn test_cleanup_old_data() {
         received_entries: 90,
         success_ratio: 0.90,
         is_problematic: false,
+        clock_offset_seconds: None,
     };
 
     let recent_check = AgentHealthCheck {
@@ -184,6 +189,7 @@ fn test_cleanup_old_data() {
         received_entries: 95,
         success_ratio: 0.95,
         is_problematic: false,
+        clock_offset_seconds: None,
     };
 
     let tx = conn.transaction().unwrap();
@@ -218,6 +224,7 @@ fn test_get_recent_health_checks_multiple_agents() {
             received_entries: 90,
             success_ratio: 0.90,
             is_problematic: false,
+            clock_offset_seconds: None,
         };
 
         let tx = conn.transaction().unwrap();
@@ -237,6 +244,7 @@ fn test_get_recent_health_checks_multiple_agents() {
             received_entries: 95,
             success_ratio: 0.95,
             is_problematic: false,
+            clock_offset_seconds: None,
         };
 
         let tx = conn.transaction().unwrap();
@@ -85,40 +85,36 @@ async fn test_calculate_expected_entries() {
 
     // Create agent config with 3 tasks: fast (<60s), exact (60s), slow (>60s)
     let tasks_config = TasksConfig {
+        aggregation_window_seconds: 60,
+        maintenance_windows: Vec::new(),
         tasks: vec![
-            TaskConfig {
-                task_type: TaskType::Ping,
-                schedule_seconds: 30, // Runs every 30s (faster than aggregation window)
-                name: "ping-fast".to_string(),
-                timeout: None,
-                params: TaskParams::Ping(PingParams {
+            TaskConfig::new(
+                "ping-fast",
+                30, // Runs every 30s (faster than aggregation window)
+                TaskParams::Ping(PingParams {
                     host: "8.8.8.8".to_string(),
                     timeout_seconds: 5,
                     target_id: None,
                 }),
-            },
-            TaskConfig {
-                task_type: TaskType::Ping,
-                schedule_seconds: 60, // Runs every 60s (equals aggregation window)
-                name: "ping-normal".to_string(),
-                timeout: None,
-                params: TaskParams::Ping(PingParams {
+            ),
+            TaskConfig::new(
+                "ping-normal",
+                60, // Runs every 60s (equals aggregation window)
+                TaskParams::Ping(PingParams {
                     host: "1.1.1.1".to_string(),
                     timeout_seconds: 5,
                     target_id: None,
                 }),
-            },
-            TaskConfig {
-                task_type: TaskType::Ping,
-                schedule_seconds: 120, // Runs every 2 minutes (slower than aggregation window)
-                name: "ping-slow".to_string(),
-                timeout: None,
-                params: TaskParams::Ping(PingParams {
+            ),
+            TaskConfig::new(
+                "ping-slow",
+                120, // Runs every 2 minutes (slower than aggregation window)
+                TaskParams::Ping(PingParams {
                     host: "9.9.9.9".to_string(),
                     timeout_seconds: 5,
                     target_id: None,
                 }),
-            },
+            ),
         ],
     };
 
@@ -151,9 +147,537 @@ async fn test_calculate_expected_entries() {
     let current_time = current_timestamp();
     let period_start = current_time - 300;
     let expected = monitor
-        .calculate_expected_entries("test-agent", period_start, current_time)
+        .calculate_expected_entries("test-agent", period_start, current_time, 0)
         .await
         .unwrap();
 
     assert_eq!(expected, 12);
 }
+
+#[tokio::test]
+async fn test_calculate_expected_entries_with_aggregation_window() {
+    let (db, config_manager, temp_dir) = setup_test_environment().await;
+    let output_dir = temp_dir.path().join("output");
+
+    // Agent aggregating over 5-minute windows
+    let ping_task = |name: &str, schedule_seconds: u32| {
+        TaskConfig::new(
+            name,
+            schedule_seconds,
+            TaskParams::Ping(PingParams {
+                host: "8.8.8.8".to_string(),
+                timeout_seconds: 5,
+                target_id: None,
+            }),
+        )
+    };
+    let tasks_config = TasksConfig {
+        aggregation_window_seconds: 300,
+        maintenance_windows: Vec::new(),
+        tasks: vec![ping_task("ping-fast", 60), ping_task("ping-slow", 600)],
+    };
+
+    let tasks_toml = toml::to_string(&tasks_config).unwrap();
+    let agent_config_path = {
+        let cm = config_manager.lock().await;
+        let sc = cm.server_config.as_ref().unwrap();
+        PathBuf::from(&sc.agent_configs_dir).join("test-agent.toml")
+    };
+    std::fs::write(&agent_config_path, tasks_toml).unwrap();
+    {
+        let cm = config_manager.lock().await;
+        cm.reload_agent_config("test-agent").await.unwrap();
+    }
+
+    let monitor = HealthMonitor::new(db, config_manager, output_dir, "0.7.6".to_string()).unwrap();
+
+    // For a 15-minute (900s) period there are 3 windows:
+    // the 60s task produces one entry per window (3), the 600s task 900/600 = 1
+    let expected = monitor
+        .calculate_expected_entries("test-agent", 1_800, 2_700, 0)
+        .await
+        .unwrap();
+    assert_eq!(expected, 4);
+}
+
+#[tokio::test]
+async fn test_calculate_expected_entries_with_schedules() {
+    use shared::schedule::{MaintenanceWindow, TimeWindow};
+
+    let (db, config_manager, temp_dir) = setup_test_environment().await;
+    let output_dir = temp_dir.path().join("output");
+
+    let ping_task = |name: &str, schedule_seconds: u32| {
+        TaskConfig::new(
+            name,
+            schedule_seconds,
+            TaskParams::Ping(PingParams {
+                host: "8.8.8.8".to_string(),
+                timeout_seconds: 5,
+                target_id: None,
+            }),
+        )
+    };
+
+    // Only active from 00:30 to 00:35 UTC
+    let mut windowed = ping_task("ping-windowed", 30);
+    windowed.active_windows = vec![TimeWindow {
+        days: Vec::new(),
+        start: "00:30".to_string(),
+        end: "00:35".to_string(),
+    }];
+    // Runs every five minutes by cron
+    let mut cron = ping_task("ping-cron", 300);
+    cron.cron = Some("*/5 * * * *".to_string());
+
+    let tasks_config = TasksConfig {
+        aggregation_window_seconds: 60,
+        // Daily maintenance from 00:40 to 00:45 UTC for one task only
+        maintenance_windows: vec![MaintenanceWindow {
+            name: "nightly".to_string(),
+            tasks: vec!["ping-maintained".to_string()],
+            starts_at: None,
+            ends_at: None,
+            days: Vec::new(),
+            start: Some("00:40".to_string()),
+            end: Some("00:45".to_string()),
+        }],
+        tasks: vec![windowed, ping_task("ping-maintained", 30), cron],
+    };
+
+    let tasks_toml = toml::to_string(&tasks_config).unwrap();
+    let agent_config_path = {
+        let cm = config_manager.lock().await;
+        let sc = cm.server_config.as_ref().unwrap();
+        PathBuf::from(&sc.agent_configs_dir).join("test-agent.toml")
+    };
+    std::fs::write(&agent_config_path, tasks_toml).unwrap();
+    {
+        let cm = config_manager.lock().await;
+        cm.reload_agent_config("test-agent").await.unwrap();
+    }
+
+    let monitor =
+        HealthMonitor::new(db, config_manager.clone(), output_dir, "0.7.6".to_string()).unwrap();
+
+    // Period 00:30 to 00:45 UTC (15 one-minute windows):
+    // - ping-windowed is active in 5 windows
+    // - ping-maintained is expected in the 10 windows outside maintenance
+    // - ping-cron runs at 00:30, 00:35 and 00:40
+    let expected = monitor
+        .calculate_expected_entries("test-agent", 1_800, 2_700, 0)
+        .await
+        .unwrap();
+    assert_eq!(expected, 18);
+
+    // With five-minute windows, a task whose active window opens at 00:32 is
+    // expected in the 00:30 window as well as the 00:35 window
+    let mut mid_window = ping_task("ping-mid-window", 30);
+    mid_window.active_windows = vec![TimeWindow {
+        days: Vec::new(),
+        start: "00:32".to_string(),
+        end: "00:38".to_string(),
+    }];
+    let tasks_config = TasksConfig {
+        aggregation_window_seconds: 300,
+        maintenance_windows: Vec::new(),
+        tasks: vec![mid_window],
+    };
+    std::fs::write(&agent_config_path, toml::to_string(&tasks_config).unwrap()).unwrap();
+    {
+        let cm = config_manager.lock().await;
+        cm.reload_agent_config("test-agent").await.unwrap();
+    }
+    let expected = monitor
+        .calculate_expected_entries("test-agent", 1_800, 2_700, 0)
+        .await
+        .unwrap();
+    assert_eq!(expected, 2);
+}
+
+#[test]
+fn test_health_check_period_alignment() {
+    use crate::health_monitor::health_check_period;
+
+    // One-minute windows: current and previous minute are excluded
+    assert_eq!(health_check_period(1065, 300, 60), (660, 960));
+
+    // Five-minute windows: aligned to window boundaries
+    assert_eq!(health_check_period(1065, 300, 300), (300, 600));
+    assert_eq!(health_check_period(1200, 900, 300), (0, 900));
+
+    // Windows longer than the check interval: the period covers one whole window
+    assert_eq!(health_check_period(11_000, 300, 3600), (3600, 7200));
+    // Partial windows are rounded up to whole windows
+    assert_eq!(health_check_period(1065, 90, 60), (840, 960));
+}
+
+#[tokio::test]
+async fn test_calculate_expected_entries_with_hourly_window() {
+    use crate::health_monitor::health_check_period;
+
+    let (db, config_manager, temp_dir) = setup_test_environment().await;
+    let output_dir = temp_dir.path().join("output");
+
+    // Agent aggregating over one-hour windows, longer than the 5-minute check interval
+    let tasks_config = TasksConfig {
+        aggregation_window_seconds: 3600,
+        maintenance_windows: Vec::new(),
+        tasks: vec![TaskConfig::new(
+            "ping-hourly-window",
+            60,
+            TaskParams::Ping(PingParams {
+                host: "8.8.8.8".to_string(),
+                timeout_seconds: 5,
+                target_id: None,
+            }),
+        )],
+    };
+
+    let tasks_toml = toml::to_string(&tasks_config).unwrap();
+    let agent_config_path = {
+        let cm = config_manager.lock().await;
+        let sc = cm.server_config.as_ref().unwrap();
+        PathBuf::from(&sc.agent_configs_dir).join("test-agent.toml")
+    };
+    std::fs::write(&agent_config_path, tasks_toml).unwrap();
+    {
+        let cm = config_manager.lock().await;
+        cm.reload_agent_config("test-agent").await.unwrap();
+    }
+
+    let monitor = HealthMonitor::new(db, config_manager, output_dir, "0.7.6".to_string()).unwrap();
+
+    // The period spans the last complete hour, in which one entry is expected
+    let (period_start, period_end) = health_check_period(11_000, 300, 3600);
+    let expected = monitor
+        .calculate_expected_entries("test-agent", period_start, period_end, 0)
+        .await
+        .unwrap();
+    assert_eq!(expected, 1);
+}
+
+#[tokio::test]
+async fn test_skewed_agent_entries_counted_and_flagged() {
+    use crate::database::db_agent_health::get_recent_health_checks;
+    use shared::metrics::{AggregatedMetricData, AggregatedMetrics, AggregatedPingMetric};
+
+    let (db, config_manager, temp_dir) = setup_test_environment().await;
+    let output_dir = temp_dir.path().join("output");
+
+    // One task producing one aggregated entry per minute
+    let tasks_config = TasksConfig {
+        aggregation_window_seconds: 60,
+        maintenance_windows: Vec::new(),
+        tasks: vec![TaskConfig::new(
+            "ping",
+            60,
+            TaskParams::Ping(PingParams {
+                host: "8.8.8.8".to_string(),
+                timeout_seconds: 5,
+                target_id: None,
+            }),
+        )],
+    };
+    let tasks_toml = toml::to_string(&tasks_config).unwrap();
+    let configs_dir = {
+        let cm = config_manager.lock().await;
+        PathBuf::from(&cm.server_config.as_ref().unwrap().agent_configs_dir)
+    };
+
+    // Agent clocks: in sync, and ten minutes ahead of the server
+    let agents = [("synced-agent", 0i64), ("skewed-agent", 600i64)];
+    let now = current_timestamp() as i64;
+    let current_minute = now / 60 * 60;
+    for (agent_id, offset) in agents {
+        std::fs::write(configs_dir.join(format!("{}.toml", agent_id)), &tasks_toml).unwrap();
+        config_manager
+            .lock()
+            .await
+            .reload_agent_config(agent_id)
+            .await
+            .unwrap();
+
+        // Entries for every minute around the check period, stamped with the agent's clock
+        let metrics: Vec<AggregatedMetrics> = (-10..=2)
+            .map(|minute| {
+                let period_start = current_minute + minute * 60 + offset;
+                AggregatedMetrics {
+                    task_name: "ping".to_string(),
+                    task_type: TaskType::Ping,
+                    period_start: period_start as u64,
+                    period_end: (period_start + 60) as u64,
+                    sample_count: 1,
+                    maintenance: false,
+                    adaptive: false,
+                    upstream_down: false,
+                    avg_queue_wait_ms: None,
+                    max_queue_wait_ms: None,
+                    labels: Default::default(),
+                    data: AggregatedMetricData::Ping(AggregatedPingMetric {
+                        avg_latency_ms: 10.0,
+                        max_latency_ms: 10.0,
+                        min_latency_ms: 10.0,
+                        packet_loss_percent: 0.0,
+                        successful_pings: 1,
+                        failed_pings: 0,
+                        domain: None,
+                        target_id: None,
+                        latency: None,
+                    }),
+                }
+            })
+            .collect();
+
+        let mut db = db.lock().await;
+        db.upsert_agent(agent_id, "checksum", Some("0.7.6"), Some(offset))
+            .await
+            .unwrap();
+        db.store_metrics(agent_id, &metrics, &[]).await.unwrap();
+    }
+
+    let monitor = HealthMonitor::new(
+        db.clone(),
+        config_manager,
+        output_dir.clone(),
+        "0.7.6".to_string(),
+    )
+    .unwrap();
+    let problematic = monitor.check_all_agents().await.unwrap();
+    assert_eq!(problematic, 1);
+
+    let checks = {
+        let mut db = db.lock().await;
+        get_recent_health_checks(db.get_connection().unwrap()).unwrap()
+    };
+    assert_eq!(checks.len(), 2);
+
+    // The skewed agent's entries are not mistaken for gaps, but its clock is flagged
+    let skewed = checks
+        .iter()
+        .find(|c| c.agent_id == "skewed-agent")
+        .unwrap();
+    assert_eq!(skewed.expected_entries, 5);
+    assert_eq!(skewed.received_entries, 5);
+    assert_eq!(skewed.clock_offset_seconds, Some(600));
+    assert!(skewed.is_problematic);
+
+    let synced = checks
+        .iter()
+        .find(|c| c.agent_id == "synced-agent")
+        .unwrap();
+    assert_eq!(synced.received_entries, 5);
+    assert!(!synced.is_problematic);
+
+    let report = std::fs::read_to_string(output_dir.join("problematic_agents.txt")).unwrap();
+    assert!(report.contains("agent_id: skewed-agent"));
+    assert!(report.contains("Clock Offset: +600 seconds"));
+    assert!(report.contains("Clock Status: SKEWED (max: 30 seconds)"));
+    assert!(!report.contains("synced-agent"));
+}
+
+#[tokio::test]
+async fn test_maintenance_entries_excluded_from_health() {
+    use crate::database::db_agent_health::get_recent_health_checks;
+    use shared::metrics::{AggregatedMetricData, AggregatedMetrics, AggregatedPingMetric};
+    use shared::schedule::MaintenanceWindow;
+
+    let (db, config_manager, temp_dir) = setup_test_environment().await;
+    let output_dir = temp_dir.path().join("output");
+
+    let now = current_timestamp() as i64;
+    let current_minute = now / 60 * 60;
+    let rfc3339 = |timestamp: i64| {
+        chrono::DateTime::from_timestamp(timestamp, 0)
+            .unwrap()
+            .to_rfc3339()
+    };
+
+    let mut tasks_config = TasksConfig {
+        aggregation_window_seconds: 60,
+        maintenance_windows: Vec::new(),
+        tasks: vec![TaskConfig::new(
+            "ping",
+            60,
+            TaskParams::Ping(PingParams {
+                host: "8.8.8.8".to_string(),
+                timeout_seconds: 5,
+                target_id: None,
+            }),
+        )],
+    };
+    let configs_dir = {
+        let cm = config_manager.lock().await;
+        PathBuf::from(&cm.server_config.as_ref().unwrap().agent_configs_dir)
+    };
+
+    // Both agents only send maintenance-tagged entries, but only one of them
+    // has a maintenance window covering the check period
+    for agent_id in ["maintained-agent", "untagged-agent"] {
+        tasks_config.maintenance_windows = if agent_id == "maintained-agent" {
+            vec![MaintenanceWindow {
+                name: "upgrade".to_string(),
+                tasks: Vec::new(),
+                starts_at: Some(rfc3339(current_minute - 3600)),
+                ends_at: Some(rfc3339(current_minute + 3600)),
+                days: Vec::new(),
+                start: None,
+                end: None,
+            }]
+        } else {
+            Vec::new()
+        };
+        std::fs::write(
+            configs_dir.join(format!("{}.toml", agent_id)),
+            toml::to_string(&tasks_config).unwrap(),
+        )
+        .unwrap();
+        config_manager
+            .lock()
+            .await
+            .reload_agent_config(agent_id)
+            .await
+            .unwrap();
+
+        let metrics: Vec<AggregatedMetrics> = (-10..=2)
+            .map(|minute| {
+                let period_start = current_minute + minute * 60;
+                AggregatedMetrics {
+                    task_name: "ping".to_string(),
+                    task_type: TaskType::Ping,
+                    period_start: period_start as u64,
+                    period_end: (period_start + 60) as u64,
+                    sample_count: 1,
+                    maintenance: true,
+                    adaptive: false,
+                    upstream_down: false,
+                    avg_queue_wait_ms: None,
+                    max_queue_wait_ms: None,
+                    labels: Default::default(),
+                    data: AggregatedMetricData::Ping(AggregatedPingMetric {
+                        avg_latency_ms: 10.0,
+                        max_latency_ms: 10.0,
+                        min_latency_ms: 10.0,
+                        packet_loss_percent: 0.0,
+                        successful_pings: 1,
+                        failed_pings: 0,
+                        domain: None,
+                        target_id: None,
+                        latency: None,
+                    }),
+                }
+            })
+            .collect();
+
+        let mut db = db.lock().await;
+        db.upsert_agent(agent_id, "checksum", Some("0.7.6"), Some(0))
+            .await
+            .unwrap();
+        db.store_metrics(agent_id, &metrics, &[]).await.unwrap();
+    }
+
+    let monitor =
+        HealthMonitor::new(db.clone(), config_manager, output_dir, "0.7.6".to_string()).unwrap();
+    let problematic = monitor.check_all_agents().await.unwrap();
+    assert_eq!(problematic, 1);
+
+    let checks = {
+        let mut db = db.lock().await;
+        get_recent_health_checks(db.get_connection().unwrap()).unwrap()
+    };
+
+    // Nothing is expected during maintenance, so the agent stays healthy
+    let maintained = checks
+        .iter()
+        .find(|c| c.agent_id == "maintained-agent")
+        .unwrap();
+    assert_eq!(maintained.expected_entries, 0);
+    assert_eq!(maintained.received_entries, 0);
+    assert!(!maintained.is_problematic);
+
+    // Maintenance-tagged entries never count as received
+    let untagged = checks
+        .iter()
+        .find(|c| c.agent_id == "untagged-agent")
+        .unwrap();
+    assert_eq!(untagged.expected_entries, 5);
+    assert_eq!(untagged.received_entries, 0);
+    assert!(untagged.is_problematic);
+}
+
+#[tokio::test]
+async fn test_suppressed_periods_not_expected() {
+    use shared::metrics::{AggregatedMetricData, AggregatedMetrics, TaskExecutionMetric};
+
+    let (db, config_manager, temp_dir) = setup_test_environment().await;
+    let output_dir = temp_dir.path().join("output");
+
+    let tasks_config = TasksConfig {
+        aggregation_window_seconds: 60,
+        maintenance_windows: Vec::new(),
+        tasks: vec![TaskConfig::new(
+            "ping",
+            30,
+            TaskParams::Ping(PingParams {
+                host: "8.8.8.8".to_string(),
+                timeout_seconds: 5,
+                target_id: None,
+            }),
+        )],
+    };
+    let agent_config_path = {
+        let cm = config_manager.lock().await;
+        let sc = cm.server_config.as_ref().unwrap();
+        PathBuf::from(&sc.agent_configs_dir).join("test-agent.toml")
+    };
+    std::fs::write(&agent_config_path, toml::to_string(&tasks_config).unwrap()).unwrap();
+    config_manager
+        .lock()
+        .await
+        .reload_agent_config("test-agent")
+        .await
+        .unwrap();
+
+    // The agent clock runs 30 seconds ahead. Every run was suppressed in the
+    // first two minutes; in the third one run started before the prerequisite
+    // went down.
+    let offset = 30;
+    let execution = |period_start: u64, runs_started: u32, suppressed_runs: u32| {
+        AggregatedMetrics::new(
+            "ping".to_string(),
+            TaskType::Ping,
+            period_start + offset,
+            period_start + offset + 60,
+            runs_started,
+            AggregatedMetricData::Execution(TaskExecutionMetric {
+                runs_started,
+                suppressed_runs,
+                ..Default::default()
+            }),
+        )
+    };
+    {
+        let mut db = db.lock().await;
+        db.upsert_agent("test-agent", "checksum", Some("0.7.6"), Some(offset as i64))
+            .await
+            .unwrap();
+        db.store_metrics(
+            "test-agent",
+            &[
+                execution(1_800, 0, 2),
+                execution(1_860, 0, 2),
+                execution(1_920, 1, 1),
+            ],
+            &[],
+        )
+        .await
+        .unwrap();
+    }
+
+    let monitor = HealthMonitor::new(db, config_manager, output_dir, "0.7.6".to_string()).unwrap();
+    let expected = monitor
+        .calculate_expected_entries("test-agent", 1_800, 2_100, offset as i64)
+        .await
+        .unwrap();
+    assert_eq!(expected, 3);
+}
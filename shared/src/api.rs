@@ -4,6 +4,7 @@
 //! endpoints between the agent and central server.
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
 /// Status of agent configuration on the server
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -37,8 +38,45 @@ pub struct MetricsRequest {
     pub timestamp_utc: String,
     pub config_checksum: String,
     pub metrics: Vec<crate::metrics::AggregatedMetrics>,
+    /// Raw measurements of tasks with `upload_raw` enabled
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub raw_metrics: Vec<crate::metrics::MetricData>,
     #[serde(default)]
     pub agent_version: Option<String>,
+    /// Labels from the agent configuration (site, region, ISP, role, ...)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub agent_labels: BTreeMap<String, String>,
+    /// Facts about the host the agent runs on (absent from older agents)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_facts: Option<HostFacts>,
+}
+
+/// Facts about the host an agent runs on, reported with each metrics push
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct HostFacts {
+    pub hostname: String,
+    /// Operating system name and version, e.g. "Debian GNU/Linux 12 (bookworm)"
+    pub os: String,
+    /// Kernel release, if known on this platform
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kernel: Option<String>,
+    /// Addresses of the non-loopback interfaces, sorted by interface name
+    #[serde(default)]
+    pub interfaces: Vec<InterfaceAddress>,
+    /// IPv4 default gateway, if known on this platform
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_gateway: Option<String>,
+    /// Egress address seen by the agent's public IP lookup, if configured
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub public_ip: Option<String>,
+}
+
+/// An address assigned to a network interface
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct InterfaceAddress {
+    pub name: String,
+    pub address: String,
+    pub prefix_len: u8,
 }
 
 /// Response body for POST /api/v1/metrics endpoint
@@ -99,6 +137,9 @@ pub struct ConfigUploadResponse {
 pub struct BandwidthTestRequest {
     pub agent_id: String,
     pub timestamp_utc: String,
+    /// Number of concurrent transfer streams the agent will open
+    #[serde(default = "crate::defaults::default_bandwidth_parallel_streams")]
+    pub parallel_streams: u32,
 }
 
 /// Response body for POST /api/v1/bandwidth_test endpoint
@@ -110,6 +151,32 @@ pub struct BandwidthTestResponse {
     pub data_size_bytes: Option<u64>,
 }
 
+/// Response body for POST /api/v1/bandwidth_upload endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BandwidthUploadResponse {
+    pub status: String,
+    /// Number of bytes the server received and discarded
+    pub bytes_received: u64,
+}
+
+/// Request body for POST /api/v1/bandwidth_complete endpoint
+///
+/// Releases the slot of a coordinated test that measured against another
+/// target and therefore never transferred data through the server.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BandwidthCompleteRequest {
+    pub agent_id: String,
+    pub timestamp_utc: String,
+}
+
+/// Response body for POST /api/v1/bandwidth_complete endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BandwidthCompleteResponse {
+    pub status: String,
+    /// Whether the agent held a slot that was released
+    pub released: bool,
+}
+
 /// Bandwidth test action from server
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 #[serde(rename_all = "snake_case")]
@@ -136,6 +203,8 @@ pub mod endpoints {
     pub const CONFIG_UPLOAD: &str = "/api/v1/config/upload";
     pub const BANDWIDTH_TEST: &str = "/api/v1/bandwidth_test";
     pub const BANDWIDTH_DOWNLOAD: &str = "/api/v1/bandwidth_download";
+    pub const BANDWIDTH_UPLOAD: &str = "/api/v1/bandwidth_upload";
+    pub const BANDWIDTH_COMPLETE: &str = "/api/v1/bandwidth_complete";
 }
 
 impl<T> ApiResponse<T> {
@@ -6,11 +6,36 @@
 
 // Task-specific defaults
 
+/// Default number of consecutive successes ending adaptive scheduling (2)
+pub fn default_on_failure_recovery_successes() -> u32 {
+    2
+}
+
 /// Default ping task timeout (1 second)
 pub fn default_ping_timeout() -> u32 {
     1
 }
 
+/// Default TCP response read timeout (5 seconds)
+pub fn default_tcp_read_timeout() -> u32 {
+    5
+}
+
+/// Default TCP sweep connect timeout per target (2 seconds)
+pub fn default_tcp_sweep_timeout() -> u32 {
+    2
+}
+
+/// Default number of concurrent connects in a TCP sweep (32)
+pub fn default_tcp_sweep_concurrency() -> u32 {
+    32
+}
+
+/// Default NTP task timeout (5 seconds)
+pub fn default_ntp_timeout() -> u32 {
+    5
+}
+
 /// Default HTTP task timeout (10 seconds)
 pub fn default_http_timeout() -> u32 {
     10
@@ -31,6 +56,51 @@ pub fn default_bandwidth_max_retries() -> u32 {
     10
 }
 
+/// Default number of parallel bandwidth test streams (single stream)
+pub fn default_bandwidth_parallel_streams() -> u32 {
+    1
+}
+
+/// Default number of packets per UDP probe run (50 packets)
+pub fn default_udp_probe_packet_count() -> u32 {
+    50
+}
+
+/// Default interval between UDP probe packets (20 ms, one voice frame)
+pub fn default_udp_probe_interval_ms() -> u32 {
+    20
+}
+
+/// Default UDP probe payload size (160 bytes, one 20 ms G.711 frame)
+pub fn default_udp_probe_payload_size() -> u32 {
+    160
+}
+
+/// Default wait for late UDP probe replies after the last packet (2 seconds)
+pub fn default_udp_probe_timeout() -> u32 {
+    2
+}
+
+/// Default number of TWAMP test packets per run (10 packets)
+pub fn default_twamp_packet_count() -> u32 {
+    10
+}
+
+/// Default interval between TWAMP test packets (100 ms)
+pub fn default_twamp_interval_ms() -> u32 {
+    100
+}
+
+/// Default TWAMP sender packet size (41 bytes, the size of an unauthenticated reflector packet)
+pub fn default_twamp_packet_size() -> u32 {
+    41
+}
+
+/// Default wait for late TWAMP replies after the last packet (2 seconds)
+pub fn default_twamp_timeout() -> u32 {
+    2
+}
+
 /// Default SQL query timeout (30 seconds)
 #[cfg(feature = "sql-tasks")]
 pub fn default_sql_timeout() -> u32 {
@@ -47,6 +117,12 @@ pub fn default_snmp_community() -> String {
     "public".to_string()
 }
 
+/// Default SNMP trap receiver listen address (UDP port 162 on all interfaces)
+#[cfg(feature = "snmp-tasks")]
+pub fn default_snmp_trap_listen_address() -> String {
+    "0.0.0.0:162".to_string()
+}
+
 /// Default maximum JSON result size for SQL queries (64 KB)
 #[cfg(feature = "sql-tasks")]
 pub fn default_sql_json_max_size() -> usize {
@@ -61,6 +137,26 @@ pub fn default_sql_max_rows() -> usize {
 
 // Server configuration defaults
 
+/// Default listen address of the agent bandwidth reflector
+pub fn default_bandwidth_reflector_listen_address() -> String {
+    "0.0.0.0:8788".to_string()
+}
+
+/// Default largest test the bandwidth reflector serves or accepts per stream (100 MB)
+pub fn default_bandwidth_reflector_max_test_size_mb() -> u32 {
+    100
+}
+
+/// Default listen address of the agent UDP reflector
+pub fn default_udp_reflector_listen_address() -> String {
+    "0.0.0.0:8789".to_string()
+}
+
+/// Default listen address of the agent TWAMP-Light responder (the TWAMP well-known port)
+pub fn default_twamp_responder_listen_address() -> String {
+    "0.0.0.0:862".to_string()
+}
+
 /// Default bandwidth test size (10 MB)
 pub fn default_bandwidth_size() -> u32 {
     10
@@ -116,6 +212,16 @@ pub fn default_bandwidth_queue_position_multiplier() -> u64 {
     30
 }
 
+/// Default number of bandwidth tests allowed to run at once (1 = fully serialized)
+pub fn default_bandwidth_max_concurrent_tests() -> u32 {
+    1
+}
+
+/// Default number of concurrent bandwidth tests within one agent group
+pub fn default_bandwidth_group_max_concurrent() -> u32 {
+    1
+}
+
 /// Default maximum delay for bandwidth test queue (300 seconds / 5 minutes)
 pub fn default_bandwidth_max_delay() -> u64 {
     300
@@ -133,6 +239,11 @@ pub fn default_server_graceful_shutdown_timeout() -> u64 {
 
 // Agent configuration defaults
 
+/// Default aggregation window (60 seconds)
+pub fn default_aggregation_window() -> u32 {
+    60
+}
+
 /// Default metrics flush interval (5 seconds)
 pub fn default_metrics_flush_interval() -> u32 {
     5
@@ -203,6 +314,16 @@ pub fn default_http_client_refresh_interval() -> u64 {
     3600
 }
 
+/// Default maximum size of a batch of raw measurements (1024 KB / 1 MB)
+pub fn default_raw_metrics_batch_max_size_kb() -> usize {
+    1024
+}
+
+/// Default host facts refresh interval (900 seconds / 15 minutes)
+pub fn default_host_facts_refresh_interval() -> u64 {
+    900
+}
+
 /// Default agent health monitoring enabled flag
 pub fn default_monitor_agents_health() -> bool {
     false
@@ -222,3 +343,43 @@ pub fn default_health_check_threshold() -> f64 {
 pub fn default_health_check_retention_days() -> u32 {
     30
 }
+
+/// Default maximum agent clock skew (30 seconds)
+pub fn default_health_check_max_clock_skew() -> u64 {
+    30
+}
+
+/// Default metric rollups enabled flag
+pub fn default_rollups_enabled() -> bool {
+    true
+}
+
+/// Default rollup interval (300 seconds / 5 minutes)
+pub fn default_rollup_interval() -> u64 {
+    300
+}
+
+/// Default rollup lookback for late metrics (2 hours)
+pub fn default_rollup_lookback_hours() -> u32 {
+    2
+}
+
+/// Default raw measurement retention on the server (3 days)
+pub fn default_raw_data_retention_days() -> u32 {
+    3
+}
+
+/// Default 5-minute rollup retention (90 days)
+pub fn default_rollup_5m_retention_days() -> u32 {
+    90
+}
+
+/// Default hourly rollup retention (365 days)
+pub fn default_rollup_1h_retention_days() -> u32 {
+    365
+}
+
+/// Default daily rollup retention (1825 days / 5 years)
+pub fn default_rollup_1d_retention_days() -> u32 {
+    1825
+}
@@ -7,6 +7,7 @@ pub mod api;
 pub mod config;
 pub mod defaults;
 pub mod metrics;
+pub mod schedule;
 pub mod utils;
 
 // Re-export commonly used types for convenience
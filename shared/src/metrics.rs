@@ -4,6 +4,7 @@
 //! and transmit monitoring data between agent and server components.
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 /// Custom serialization module for HashMap<u16, u32> to handle JSON limitations
@@ -38,6 +39,13 @@ pub struct MetricData {
     pub task_type: crate::config::TaskType,
     /// Timestamp when the measurement was taken (Unix timestamp)
     pub timestamp: u64,
+    /// Labels of the task at the time of the measurement
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// Agent-local row identifier of an uploaded measurement, lets the server
+    /// discard duplicate deliveries
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub row_id: Option<i64>,
     /// Raw measurement data
     pub data: RawMetricData,
 }
@@ -56,6 +64,24 @@ pub struct AggregatedMetrics {
     pub period_end: u64,
     /// Number of raw measurements included in this aggregation
     pub sample_count: u32,
+    /// True if the period overlaps a maintenance window of the task
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub maintenance: bool,
+    /// True if the task ran on its faster on-failure schedule during the period
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub adaptive: bool,
+    /// True if runs of the task were suppressed during the period because a prerequisite task was failing
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub upstream_down: bool,
+    /// Average time runs of the task waited for a concurrency slot (agent self-metric)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avg_queue_wait_ms: Option<f64>,
+    /// Longest time a run of the task waited for a concurrency slot (agent self-metric)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_queue_wait_ms: Option<f64>,
+    /// Labels of the task (site, service, owner, environment...)
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
     /// Aggregated measurement data
     pub data: AggregatedMetricData,
 }
@@ -76,6 +102,10 @@ pub enum RawMetricData {
     TlsHandshake(RawTlsMetric),
     DnsQuery(RawDnsMetric),
     Bandwidth(RawBandwidthMetric),
+    UdpProbe(RawUdpProbeMetric),
+    Twamp(RawTwampMetric),
+    TcpSweep(RawTcpSweepMetric),
+    Ntp(RawNtpMetric),
     SqlQuery(RawSqlQueryMetric),
     Snmp(RawSnmpMetric),
     /// Unknown metric type - used for forward compatibility when receiving
@@ -98,14 +128,83 @@ pub enum AggregatedMetricData {
     TlsHandshake(AggregatedTlsMetric),
     DnsQuery(AggregatedDnsMetric),
     Bandwidth(AggregatedBandwidthMetric),
+    UdpProbe(AggregatedUdpProbeMetric),
+    Twamp(AggregatedTwampMetric),
+    TcpSweep(AggregatedTcpSweepMetric),
+    Ntp(AggregatedNtpMetric),
     SqlQuery(AggregatedSqlQueryMetric),
     Snmp(AggregatedSnmpMetric),
+    /// SNMP trap/inform event (forwarded individually, not aggregated)
+    SnmpTrap(SnmpTrapEventMetric),
+    /// Missed, delayed and timed-out runs of a task over the period
+    Execution(TaskExecutionMetric),
     /// Unknown metric type - used for forward compatibility when receiving
     /// metrics from agents with newer/different feature flags
     #[serde(other)]
     Unknown,
 }
 
+/// Upper bounds in milliseconds of the latency histogram buckets
+///
+/// A sample falls in the first bucket whose bound is greater than or equal to it.
+/// `LatencyDistribution::histogram` has one more bucket for samples above the last bound.
+pub const LATENCY_HISTOGRAM_BOUNDS_MS: [f64; 11] = [
+    1.0, 2.0, 5.0, 10.0, 20.0, 50.0, 100.0, 200.0, 500.0, 1000.0, 2000.0,
+];
+
+/// Latency percentiles and histogram of the successful samples in a period
+///
+/// Percentiles use the nearest-rank method, so each one is an observed sample.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LatencyDistribution {
+    /// Median latency in milliseconds
+    pub p50_ms: f64,
+    /// 90th percentile latency in milliseconds
+    pub p90_ms: f64,
+    /// 95th percentile latency in milliseconds
+    pub p95_ms: f64,
+    /// 99th percentile latency in milliseconds
+    pub p99_ms: f64,
+    /// Sample counts per bucket of `LATENCY_HISTOGRAM_BOUNDS_MS`, plus the overflow bucket
+    pub histogram: Vec<u32>,
+}
+
+impl LatencyDistribution {
+    /// Compute the distribution of latency samples in milliseconds
+    ///
+    /// Returns None if there are no finite samples.
+    pub fn from_samples(samples: &[f64]) -> Option<Self> {
+        let mut sorted: Vec<f64> = samples.iter().copied().filter(|v| v.is_finite()).collect();
+        if sorted.is_empty() {
+            return None;
+        }
+        sorted.sort_by(|a, b| a.total_cmp(b));
+
+        let mut histogram = vec![0u32; LATENCY_HISTOGRAM_BOUNDS_MS.len() + 1];
+        for sample in &sorted {
+            let bucket = LATENCY_HISTOGRAM_BOUNDS_MS
+                .iter()
+                .position(|bound| sample <= bound)
+                .unwrap_or(LATENCY_HISTOGRAM_BOUNDS_MS.len());
+            histogram[bucket] += 1;
+        }
+
+        Some(Self {
+            p50_ms: percentile(&sorted, 50.0),
+            p90_ms: percentile(&sorted, 90.0),
+            p95_ms: percentile(&sorted, 95.0),
+            p99_ms: percentile(&sorted, 99.0),
+            histogram,
+        })
+    }
+}
+
+/// Nearest-rank percentile of sorted, non-empty samples
+fn percentile(sorted: &[f64], percentile: f64) -> f64 {
+    let rank = (percentile / 100.0 * sorted.len() as f64).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
 /// Raw ping measurement data
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct RawPingMetric {
@@ -144,6 +243,9 @@ pub struct AggregatedPingMetric {
     /// Optional target identifier for grouping/filtering
     #[serde(skip_serializing_if = "Option::is_none")]
     pub target_id: Option<String>,
+    /// Distribution of round-trip times (only successful pings)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<LatencyDistribution>,
 }
 
 /// Raw TCP connection measurement data
@@ -151,9 +253,15 @@ pub struct AggregatedPingMetric {
 pub struct RawTcpMetric {
     /// TCP connection time in milliseconds (None if connection failed)
     pub connect_time_ms: Option<f64>,
-    /// Whether the connection was successful
+    /// Time from connecting (or writing `send`) until the response arrived or
+    /// matched `expect`, in milliseconds (None without `send`/`expect`, or if
+    /// no matching response arrived)
+    pub response_time_ms: Option<f64>,
+    /// Whether the response matched `expect` (None without `expect`)
+    pub expect_matched: Option<bool>,
+    /// Whether the connection was successful (and the response check passed, if any)
     pub success: bool,
-    /// Error message if the connection failed
+    /// Error message if the connection or the response check failed
     pub error: Option<String>,
     /// Host:port that was connected to
     pub host: String,
@@ -182,6 +290,9 @@ pub struct AggregatedTcpMetric {
     /// Optional target identifier for grouping/filtering
     #[serde(skip_serializing_if = "Option::is_none")]
     pub target_id: Option<String>,
+    /// Distribution of connection times
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<LatencyDistribution>,
 }
 
 /// Raw HTTP GET measurement data
@@ -243,6 +354,9 @@ pub struct AggregatedHttpMetric {
     /// Optional target identifier for grouping/filtering
     #[serde(skip_serializing_if = "Option::is_none")]
     pub target_id: Option<String>,
+    /// Distribution of total request times (only successful requests)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<LatencyDistribution>,
 }
 
 /// Raw TLS handshake measurement data
@@ -285,6 +399,9 @@ pub struct AggregatedTlsMetric {
     /// Optional target identifier for grouping/filtering
     #[serde(skip_serializing_if = "Option::is_none")]
     pub target_id: Option<String>,
+    /// Distribution of TLS handshake times (only successful checks)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<LatencyDistribution>,
 }
 
 /// Raw HTTP content check measurement data
@@ -379,6 +496,9 @@ pub struct AggregatedDnsMetric {
     /// Optional target identifier for grouping/filtering
     #[serde(skip_serializing_if = "Option::is_none")]
     pub target_id: Option<String>,
+    /// Distribution of query times (only successful queries)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<LatencyDistribution>,
 }
 
 /// Raw bandwidth measurement data
@@ -390,6 +510,33 @@ pub struct RawBandwidthMetric {
     pub duration_ms: Option<f64>,
     /// Number of bytes downloaded
     pub bytes_downloaded: Option<u64>,
+    /// Measured upload bandwidth in Mbps
+    #[serde(default)]
+    pub upload_mbps: Option<f64>,
+    /// Upload duration in milliseconds
+    #[serde(default)]
+    pub upload_duration_ms: Option<f64>,
+    /// Number of bytes uploaded
+    #[serde(default)]
+    pub bytes_uploaded: Option<u64>,
+    /// Number of parallel streams used per direction
+    #[serde(default)]
+    pub parallel_streams: Option<u32>,
+    /// Download throughput of each stream in Mbps
+    #[serde(default)]
+    pub stream_mbps: Option<Vec<f64>>,
+    /// Aggregate download throughput sampled at fixed intervals during the test, in Mbps
+    #[serde(default)]
+    pub throughput_series_mbps: Option<Vec<f64>>,
+    /// Upload throughput of each stream in Mbps
+    #[serde(default)]
+    pub upload_stream_mbps: Option<Vec<f64>>,
+    /// Aggregate upload throughput sampled at fixed intervals during the test, in Mbps
+    #[serde(default)]
+    pub upload_throughput_series_mbps: Option<Vec<f64>>,
+    /// URL of the third-party server or reflector measured against (None = central server)
+    #[serde(default)]
+    pub target_url: Option<String>,
     /// Whether the test was successful
     pub success: bool,
     /// Error message if the test failed
@@ -408,6 +555,15 @@ pub struct AggregatedBandwidthMetric {
     pub max_bandwidth_mbps: f64,
     /// Minimum bandwidth in Mbps
     pub min_bandwidth_mbps: f64,
+    /// Average upload bandwidth in Mbps (None if no upload tests ran)
+    #[serde(default)]
+    pub avg_upload_mbps: Option<f64>,
+    /// Maximum upload bandwidth in Mbps
+    #[serde(default)]
+    pub max_upload_mbps: Option<f64>,
+    /// Minimum upload bandwidth in Mbps
+    #[serde(default)]
+    pub min_upload_mbps: Option<f64>,
     /// Number of successful tests
     pub successful_tests: u32,
     /// Number of failed tests
@@ -417,6 +573,313 @@ pub struct AggregatedBandwidthMetric {
     pub target_id: Option<String>,
 }
 
+/// Raw UDP probe measurement data from one run of the packet stream
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RawUdpProbeMetric {
+    /// Number of probe packets sent
+    pub packets_sent: u32,
+    /// Number of distinct probe packets whose reply was received
+    pub packets_received: u32,
+    /// Number of probes the reflector reports having received (None for plain echo servers)
+    pub packets_reflected: Option<u32>,
+    /// Round-trip packet loss percentage (0.0 to 100.0)
+    pub loss_percent: f64,
+    /// Loss on the way to the reflector (None for plain echo servers)
+    pub forward_loss_percent: Option<f64>,
+    /// Loss on the way back from the reflector (None for plain echo servers)
+    pub return_loss_percent: Option<f64>,
+    /// Number of duplicate replies received
+    pub duplicate_packets: u32,
+    /// Number of replies that arrived after a reply to a later probe
+    pub reordered_packets: u32,
+    /// Average round-trip time in milliseconds (None if nothing was received)
+    pub avg_rtt_ms: Option<f64>,
+    /// Minimum round-trip time in milliseconds
+    pub min_rtt_ms: Option<f64>,
+    /// Maximum round-trip time in milliseconds
+    pub max_rtt_ms: Option<f64>,
+    /// Interarrival jitter in milliseconds (RFC 3550 estimator over round-trip times)
+    pub jitter_ms: Option<f64>,
+    /// Whether at least one reply was received
+    pub success: bool,
+    /// Error message if the probe failed
+    pub error: Option<String>,
+    /// Host:port that was probed
+    pub host: String,
+    /// Optional target identifier for grouping/filtering
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_id: Option<String>,
+}
+
+/// Aggregated UDP probe metrics over a time period
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AggregatedUdpProbeMetric {
+    /// Average round-trip time in milliseconds (only successful runs)
+    pub avg_rtt_ms: f64,
+    /// Maximum round-trip time in milliseconds (only successful runs)
+    pub max_rtt_ms: f64,
+    /// Minimum round-trip time in milliseconds (only successful runs)
+    pub min_rtt_ms: f64,
+    /// Average jitter in milliseconds (only successful runs)
+    pub avg_jitter_ms: f64,
+    /// Maximum jitter in milliseconds (only successful runs)
+    pub max_jitter_ms: f64,
+    /// Round-trip packet loss percentage over all packets sent in the period
+    pub loss_percent: f64,
+    /// Average loss on the way to the reflector (None for plain echo servers)
+    #[serde(default)]
+    pub forward_loss_percent: Option<f64>,
+    /// Average loss on the way back from the reflector (None for plain echo servers)
+    #[serde(default)]
+    pub return_loss_percent: Option<f64>,
+    /// Total probe packets sent
+    pub packets_sent: u32,
+    /// Total distinct replies received
+    pub packets_received: u32,
+    /// Total duplicate replies received
+    pub duplicate_packets: u32,
+    /// Total reordered replies received
+    pub reordered_packets: u32,
+    /// Estimated Mean Opinion Score (1.0 to 4.5) for a voice call over this path
+    pub mos: f64,
+    /// Number of runs that received at least one reply
+    pub successful_probes: u32,
+    /// Number of runs that received no reply
+    pub failed_probes: u32,
+    /// Host:port that was probed (from first occurrence)
+    pub host: String,
+    /// Optional target identifier for grouping/filtering
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_id: Option<String>,
+}
+
+/// State of one host:port target in a TCP sweep
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TcpSweepState {
+    /// The connection was accepted
+    Open,
+    /// The connection was refused (nothing listening)
+    Closed,
+    /// No answer before the timeout, or the host was unreachable
+    Filtered,
+    /// The host name could not be resolved
+    Unresolved,
+}
+
+/// Result of one host:port target in a TCP sweep
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TcpSweepTargetResult {
+    /// Host:port that was connected to
+    pub target: String,
+    pub state: TcpSweepState,
+    /// TCP connection time in milliseconds (only open targets)
+    pub connect_time_ms: Option<f64>,
+}
+
+/// Raw TCP sweep measurement data from one sweep
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RawTcpSweepMetric {
+    /// Number of host:port targets swept
+    pub targets_total: u32,
+    pub open_count: u32,
+    pub closed_count: u32,
+    pub filtered_count: u32,
+    pub unresolved_count: u32,
+    /// Average connection time over open targets in milliseconds
+    pub avg_connect_time_ms: Option<f64>,
+    /// Minimum connection time over open targets in milliseconds
+    pub min_connect_time_ms: Option<f64>,
+    /// Maximum connection time over open targets in milliseconds
+    pub max_connect_time_ms: Option<f64>,
+    /// Wall-clock duration of the sweep in milliseconds
+    pub duration_ms: f64,
+    /// Per-target results, in the order of the configuration
+    pub results: Vec<TcpSweepTargetResult>,
+    /// Whether the sweep ran and every host name resolved
+    pub success: bool,
+    /// Error message if the sweep failed or host names did not resolve
+    pub error: Option<String>,
+    /// Optional target identifier for grouping/filtering
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_id: Option<String>,
+}
+
+/// Per-target summary of a TCP sweep over a time period
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TcpSweepTargetSummary {
+    /// Host:port that was connected to
+    pub target: String,
+    /// Percentage of sweeps in which the target was open (0.0 to 100.0)
+    pub open_percent: f64,
+    /// Average connection time in milliseconds (None if never open)
+    pub avg_connect_time_ms: Option<f64>,
+    /// State in the last sweep of the period
+    pub last_state: TcpSweepState,
+}
+
+/// Aggregated TCP sweep metrics over a time period
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AggregatedTcpSweepMetric {
+    /// Number of host:port targets (from the last sweep)
+    pub targets_total: u32,
+    /// Average number of open targets per sweep
+    pub avg_open_count: f64,
+    /// Lowest number of open targets in a sweep
+    pub min_open_count: u32,
+    /// Average number of closed targets per sweep
+    pub avg_closed_count: f64,
+    /// Average number of filtered targets per sweep
+    pub avg_filtered_count: f64,
+    /// Average number of unresolved targets per sweep
+    pub avg_unresolved_count: f64,
+    /// Average connection time over all open targets in milliseconds
+    pub avg_connect_time_ms: f64,
+    /// Maximum connection time over all open targets in milliseconds
+    pub max_connect_time_ms: f64,
+    /// Minimum connection time over all open targets in milliseconds
+    pub min_connect_time_ms: f64,
+    /// Number of sweeps in which every host name resolved
+    pub successful_sweeps: u32,
+    /// Number of sweeps that failed or had unresolved host names
+    pub failed_sweeps: u32,
+    /// Per-target summaries, in the order of the last sweep
+    pub targets: Vec<TcpSweepTargetSummary>,
+    /// Optional target identifier for grouping/filtering
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_id: Option<String>,
+}
+
+/// Raw NTP measurement data from one SNTP query
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RawNtpMetric {
+    /// Clock offset of the server relative to the agent in milliseconds
+    /// (positive when the agent's clock is behind)
+    pub offset_ms: Option<f64>,
+    /// Round-trip delay to the server in milliseconds, without server processing time
+    pub delay_ms: Option<f64>,
+    /// Stratum of the server (1 = primary reference, 2-15 = secondary)
+    pub stratum: Option<u8>,
+    /// Reference ID: reference clock code for stratum 1 (e.g., "GPS"),
+    /// upstream server IPv4 address for higher strata
+    pub reference_id: Option<String>,
+    /// Leap indicator (0 = none, 1 = last minute has 61 s, 2 = 59 s, 3 = unsynchronized)
+    pub leap_indicator: Option<u8>,
+    /// Whether the server answered with synchronized time
+    pub success: bool,
+    /// Error message if the query failed
+    pub error: Option<String>,
+    /// Result of the offset threshold evaluation
+    #[serde(default)]
+    pub status: ThresholdStatus,
+    /// Explanation when status is warning or critical
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_message: Option<String>,
+    /// NTP server that was queried
+    pub host: String,
+    /// Optional target identifier for grouping/filtering
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_id: Option<String>,
+}
+
+/// Aggregated NTP metrics over a time period
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AggregatedNtpMetric {
+    /// Average offset in milliseconds over successful queries
+    pub avg_offset_ms: f64,
+    /// Minimum (most negative) offset in milliseconds
+    pub min_offset_ms: f64,
+    /// Maximum (most positive) offset in milliseconds
+    pub max_offset_ms: f64,
+    /// Largest absolute offset in milliseconds
+    pub max_abs_offset_ms: f64,
+    /// Average round-trip delay in milliseconds
+    pub avg_delay_ms: f64,
+    /// Maximum round-trip delay in milliseconds
+    pub max_delay_ms: f64,
+    /// Stratum in the last successful query
+    pub stratum: Option<u8>,
+    /// Reference ID in the last successful query
+    pub reference_id: Option<String>,
+    /// Leap indicator in the last query that received a response
+    pub leap_indicator: Option<u8>,
+    /// Number of successful queries
+    pub successful_queries: u32,
+    /// Number of failed queries
+    pub failed_queries: u32,
+    /// Number of queries with status ok
+    pub ok_count: u32,
+    /// Number of queries with status warning
+    pub warning_count: u32,
+    /// Number of queries with status critical
+    pub critical_count: u32,
+    /// Number of queries with status error
+    pub error_count: u32,
+    /// NTP server that was queried
+    pub host: String,
+    /// Optional target identifier for grouping/filtering
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_id: Option<String>,
+}
+
+/// Raw TWAMP-Light measurement data from one run of test packets
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RawTwampMetric {
+    /// Number of test packets sent
+    pub packets_sent: u32,
+    /// Number of distinct test packets reflected back
+    pub packets_received: u32,
+    /// Packet loss percentage (0.0 to 100.0)
+    pub packet_loss_percent: f64,
+    /// Average two-way delay in milliseconds, excluding reflector processing time
+    pub avg_latency_ms: Option<f64>,
+    /// Minimum two-way delay in milliseconds
+    pub min_latency_ms: Option<f64>,
+    /// Maximum two-way delay in milliseconds
+    pub max_latency_ms: Option<f64>,
+    /// Interarrival jitter of the two-way delay in milliseconds (RFC 3550 estimator)
+    pub jitter_ms: Option<f64>,
+    /// Whether at least one test packet was reflected
+    pub success: bool,
+    /// Error message if the test failed
+    pub error: Option<String>,
+    /// Host:port of the reflector
+    pub host: String,
+    /// Optional target identifier for grouping/filtering
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_id: Option<String>,
+}
+
+/// Aggregated TWAMP-Light metrics over a time period
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AggregatedTwampMetric {
+    /// Average two-way delay in milliseconds (only successful runs)
+    pub avg_latency_ms: f64,
+    /// Maximum two-way delay in milliseconds (only successful runs)
+    pub max_latency_ms: f64,
+    /// Minimum two-way delay in milliseconds (only successful runs)
+    pub min_latency_ms: f64,
+    /// Packet loss percentage over all test packets sent in the period
+    pub packet_loss_percent: f64,
+    /// Average jitter in milliseconds (only successful runs)
+    pub avg_jitter_ms: f64,
+    /// Maximum jitter in milliseconds (only successful runs)
+    pub max_jitter_ms: f64,
+    /// Total test packets sent
+    pub packets_sent: u32,
+    /// Total test packets reflected back
+    pub packets_received: u32,
+    /// Number of runs with at least one reflected packet
+    pub successful_tests: u32,
+    /// Number of runs without any reflected packet
+    pub failed_tests: u32,
+    /// Host:port of the reflector (from first occurrence)
+    pub host: String,
+    /// Optional target identifier for grouping/filtering
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_id: Option<String>,
+}
+
 /// Raw SQL query measurement data
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct RawSqlQueryMetric {
@@ -445,6 +908,47 @@ pub struct RawSqlQueryMetric {
     /// Number of columns in the result
     #[serde(skip_serializing_if = "Option::is_none")]
     pub column_count: Option<u32>,
+    /// Time to open the database connection in milliseconds (near zero when reused from the pool)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub connect_time_ms: Option<f64>,
+    /// Query execution time in milliseconds, excluding connection setup
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub query_time_ms: Option<f64>,
+    /// Result of threshold and row count evaluation
+    #[serde(default)]
+    pub status: ThresholdStatus,
+    /// Explanation when status is warning or critical
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status_message: Option<String>,
+}
+
+/// Result of checking a measurement against warning and critical thresholds
+///
+/// Used by the SQL query and NTP tasks.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ThresholdStatus {
+    /// Measurement succeeded and no threshold was crossed
+    #[default]
+    Ok,
+    /// A warning threshold was crossed
+    Warning,
+    /// A critical threshold was crossed, or an expectation such as the row count was not met
+    Critical,
+    /// Measurement failed
+    Error,
+}
+
+impl ThresholdStatus {
+    /// Returns the status as a string slice for database storage
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ThresholdStatus::Ok => "ok",
+            ThresholdStatus::Warning => "warning",
+            ThresholdStatus::Critical => "critical",
+            ThresholdStatus::Error => "error",
+        }
+    }
 }
 
 /// Aggregated SQL query metrics over a time period
@@ -479,6 +983,27 @@ pub struct AggregatedSqlQueryMetric {
     /// Count of truncated JSON responses (JSON mode only)
     #[serde(default)]
     pub json_truncated_count: u32,
+    /// Average connection setup time in milliseconds
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avg_connect_time_ms: Option<f64>,
+    /// Average query execution time in milliseconds, excluding connection setup
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avg_query_time_ms: Option<f64>,
+    /// Number of runs with status ok
+    #[serde(default)]
+    pub ok_count: u32,
+    /// Number of runs with status warning
+    #[serde(default)]
+    pub warning_count: u32,
+    /// Number of runs with status critical
+    #[serde(default)]
+    pub critical_count: u32,
+    /// Number of runs with status error (failed queries)
+    #[serde(default)]
+    pub error_count: u32,
+    /// Distribution of query execution times (only successful queries)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<LatencyDistribution>,
 }
 
 /// Raw SNMP query measurement data
@@ -499,6 +1024,9 @@ pub struct RawSnmpMetric {
     /// Optional target identifier for grouping/filtering
     #[serde(skip_serializing_if = "Option::is_none")]
     pub target_id: Option<String>,
+    /// Numeric value (Integer, Counter, Gauge and TimeTicks types only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub numeric_value: Option<f64>,
 }
 
 /// Aggregated SNMP metrics over a time period
@@ -522,6 +1050,90 @@ pub struct AggregatedSnmpMetric {
     /// Optional target identifier for grouping/filtering
     #[serde(skip_serializing_if = "Option::is_none")]
     pub target_id: Option<String>,
+    /// Average numeric value (numeric SNMP types only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avg_value: Option<f64>,
+    /// Minimum numeric value (numeric SNMP types only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_value: Option<f64>,
+    /// Maximum numeric value (numeric SNMP types only)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_value: Option<f64>,
+    /// Distribution of response times (only successful queries)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency: Option<LatencyDistribution>,
+}
+
+/// A single variable binding carried by an SNMP notification
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SnmpVarbind {
+    /// OID of the variable in dotted notation
+    pub oid: String,
+    /// Value converted to string
+    pub value: String,
+    /// SNMP data type name (e.g., "Integer", "OctetString", "Counter32")
+    pub value_type: String,
+}
+
+/// Scheduling outcome of a task's runs over an aggregation period
+///
+/// Only sent for periods in which runs were skipped, suppressed, started late
+/// or timed out, so the server can tell a missing sample apart from a failed
+/// probe.
+/// `sample_count` of the entry is the number of runs started in the period.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct TaskExecutionMetric {
+    /// Runs started in the period
+    pub runs_started: u32,
+    /// Due runs skipped because the previous run was still running or waiting
+    pub skipped_overruns: u32,
+    /// Runs started more than a second after their scheduled time
+    pub delayed_starts: u32,
+    /// Largest delay between the scheduled and the actual start in milliseconds
+    pub max_start_delay_ms: f64,
+    /// Runs aborted by the task timeout, which leave no sample behind
+    pub timed_out_runs: u32,
+    /// Due runs skipped because a prerequisite task was down
+    #[serde(default)]
+    pub suppressed_runs: u32,
+}
+
+impl TaskExecutionMetric {
+    /// Returns true if no run was skipped, suppressed, delayed or timed out
+    pub fn is_clean(&self) -> bool {
+        self.skipped_overruns == 0
+            && self.suppressed_runs == 0
+            && self.delayed_starts == 0
+            && self.timed_out_runs == 0
+    }
+}
+
+/// SNMP trap or inform received by the agent's trap listener
+///
+/// Each notification is forwarded as its own entry with `sample_count` 1 and
+/// `period_start == period_end` set to the time it was received.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SnmpTrapEventMetric {
+    /// Agent-local event identifier, lets the server discard duplicate deliveries
+    #[serde(default)]
+    pub event_id: i64,
+    /// Address of the device that sent the notification (ip:port)
+    pub source_address: String,
+    /// SNMP version of the notification ("v1", "v2c" or "v3")
+    pub version: String,
+    /// PDU type ("trap" or "inform")
+    pub pdu_type: String,
+    /// Notification OID (snmpTrapOID.0, or the RFC 3584 translation for v1 traps)
+    pub trap_oid: String,
+    /// sysUpTime of the sender in hundredths of a second
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uptime_ticks: Option<u32>,
+    /// Remaining variable bindings of the notification
+    #[serde(default)]
+    pub varbinds: Vec<SnmpVarbind>,
+    /// Optional target identifier for grouping/filtering
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_id: Option<String>,
 }
 
 impl MetricData {
@@ -531,11 +1143,16 @@ impl MetricData {
             task_name,
             task_type,
             timestamp: current_timestamp(),
+            labels: BTreeMap::new(),
+            row_id: None,
             data,
         }
     }
 
     /// Check if this metric represents a successful measurement
+    ///
+    /// An NTP or SQL query whose result crossed a critical threshold counts as
+    /// failed, so that the scheduler reacts to it like to any other failing target.
     pub fn is_successful(&self) -> bool {
         match &self.data {
             RawMetricData::Ping(metric) => metric.success,
@@ -545,7 +1162,15 @@ impl MetricData {
             RawMetricData::HttpContent(metric) => metric.success,
             RawMetricData::DnsQuery(metric) => metric.success,
             RawMetricData::Bandwidth(metric) => metric.success,
-            RawMetricData::SqlQuery(metric) => metric.success,
+            RawMetricData::UdpProbe(metric) => metric.success,
+            RawMetricData::Twamp(metric) => metric.success,
+            RawMetricData::TcpSweep(metric) => metric.success,
+            RawMetricData::Ntp(metric) => {
+                metric.success && metric.status != ThresholdStatus::Critical
+            }
+            RawMetricData::SqlQuery(metric) => {
+                metric.success && metric.status != ThresholdStatus::Critical
+            }
             RawMetricData::Snmp(metric) => metric.success,
             RawMetricData::Unknown => false,
         }
@@ -568,6 +1193,12 @@ impl AggregatedMetrics {
             period_start,
             period_end,
             sample_count,
+            maintenance: false,
+            adaptive: false,
+            upstream_down: false,
+            avg_queue_wait_ms: None,
+            max_queue_wait_ms: None,
+            labels: BTreeMap::new(),
             data,
         }
     }
@@ -0,0 +1,469 @@
+//! Task schedule types: cron expressions, active time windows and maintenance windows
+//!
+//! All times are evaluated in UTC, without time zone or daylight saving
+//! support, so windows tied to local time shift by an hour at DST changes:
+//! - `CronSchedule` triggers tasks at the minutes matched by a cron expression
+//! - `TimeWindow` limits when a task runs (e.g. business hours only)
+//! - `MaintenanceWindow` tags results produced during planned work so they are
+//!   excluded from health checks
+
+use chrono::{DateTime, Datelike, Duration as ChronoDuration, NaiveDate, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Days searched for the next cron match (covers the 8-year gap between some leap days)
+const MAX_CRON_SEARCH_DAYS: i64 = 366 * 8 + 1;
+
+/// Minutes in a day, also accepted as the end of a time window ("24:00")
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// Day of the week used by time windows
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Weekday {
+    fn matches(self, day: chrono::Weekday) -> bool {
+        let index = match self {
+            Weekday::Mon => 0,
+            Weekday::Tue => 1,
+            Weekday::Wed => 2,
+            Weekday::Thu => 3,
+            Weekday::Fri => 4,
+            Weekday::Sat => 5,
+            Weekday::Sun => 6,
+        };
+        day.num_days_from_monday() == index
+    }
+}
+
+/// Weekly recurring window of time, in UTC
+///
+/// Used as a task's active window (the task only runs inside its windows).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TimeWindow {
+    /// Days the window starts on ("mon" to "sun"); empty means every day
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<Weekday>,
+    /// Start time of day, "HH:MM" (inclusive)
+    pub start: String,
+    /// End time of day, "HH:MM" (exclusive). An end before the start spans midnight.
+    pub end: String,
+}
+
+impl TimeWindow {
+    /// Validate the window
+    pub fn validate(&self) -> crate::Result<()> {
+        validate_recurring(&self.start, &self.end)
+    }
+
+    /// Returns true if the Unix timestamp falls inside the window
+    pub fn contains(&self, timestamp: u64) -> bool {
+        recurring_contains(&self.days, &self.start, &self.end, timestamp)
+    }
+
+    /// Returns true if the window overlaps the period `[from, to)` (Unix timestamps)
+    pub fn overlaps(&self, from: u64, to: u64) -> bool {
+        recurring_overlaps(&self.days, &self.start, &self.end, from, to)
+    }
+}
+
+/// Planned maintenance period during which results are tagged as maintenance
+///
+/// Either a one-off window (`starts_at` and `ends_at`, RFC 3339) or a weekly
+/// recurring window (`start` and `end` as "HH:MM" UTC, optionally limited to `days`).
+/// Tasks keep running during maintenance; their aggregated metrics are tagged
+/// and excluded from health ratios.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MaintenanceWindow {
+    /// Name of the window, used in logs and error messages
+    pub name: String,
+    /// Names of the tasks the window applies to; empty means all tasks
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tasks: Vec<String>,
+    /// Start of a one-off window, e.g. "2026-11-01T02:00:00Z"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub starts_at: Option<String>,
+    /// End of a one-off window (exclusive)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ends_at: Option<String>,
+    /// Days a recurring window starts on; empty means every day
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub days: Vec<Weekday>,
+    /// Start time of day of a recurring window, "HH:MM"
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start: Option<String>,
+    /// End time of day of a recurring window, "HH:MM" (exclusive)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end: Option<String>,
+}
+
+impl MaintenanceWindow {
+    /// Validate the window
+    pub fn validate(&self) -> crate::Result<()> {
+        let invalid = |message: String| -> crate::Result<()> {
+            Err(crate::MonitoringError::Validation(format!(
+                "Maintenance window '{}': {}",
+                self.name, message
+            ))
+            .into())
+        };
+
+        if self.name.is_empty() {
+            return Err(crate::MonitoringError::Validation(
+                "Maintenance window name cannot be empty.".to_string(),
+            )
+            .into());
+        }
+
+        let one_off = self.starts_at.is_some() || self.ends_at.is_some();
+        let recurring = self.start.is_some() || self.end.is_some() || !self.days.is_empty();
+
+        match (one_off, recurring) {
+            (true, true) => invalid(
+                "use either starts_at/ends_at (one-off) or start/end (recurring), not both."
+                    .to_string(),
+            ),
+            (false, false) => invalid(
+                "set starts_at/ends_at for a one-off window or start/end for a recurring one."
+                    .to_string(),
+            ),
+            (true, false) => {
+                let (Some(starts_at), Some(ends_at)) = (&self.starts_at, &self.ends_at) else {
+                    return invalid("one-off windows need both starts_at and ends_at.".to_string());
+                };
+                match (parse_rfc3339(starts_at), parse_rfc3339(ends_at)) {
+                    (Some(start), Some(end)) if start < end => Ok(()),
+                    (Some(_), Some(_)) => invalid("ends_at must be after starts_at.".to_string()),
+                    _ => invalid(format!(
+                        "starts_at '{}' and ends_at '{}' must be RFC 3339 timestamps (e.g., 2026-11-01T02:00:00Z).",
+                        starts_at, ends_at
+                    )),
+                }
+            }
+            (false, true) => {
+                let (Some(start), Some(end)) = (&self.start, &self.end) else {
+                    return invalid("recurring windows need both start and end.".to_string());
+                };
+                validate_recurring(start, end).or_else(|e| {
+                    let message = e.to_string();
+                    invalid(
+                        message
+                            .strip_prefix("Validation error: ")
+                            .unwrap_or(&message)
+                            .to_string(),
+                    )
+                })
+            }
+        }
+    }
+
+    /// Returns true if the window applies to the named task
+    pub fn applies_to(&self, task_name: &str) -> bool {
+        self.tasks.is_empty() || self.tasks.iter().any(|name| name == task_name)
+    }
+
+    /// Returns true if the window overlaps the period `[from, to)` (Unix timestamps)
+    pub fn overlaps(&self, from: u64, to: u64) -> bool {
+        if let (Some(starts_at), Some(ends_at)) = (&self.starts_at, &self.ends_at) {
+            return match (parse_rfc3339(starts_at), parse_rfc3339(ends_at)) {
+                (Some(start), Some(end)) => start < to as i64 && end > from as i64,
+                _ => false,
+            };
+        }
+
+        let (Some(start), Some(end)) = (&self.start, &self.end) else {
+            return false;
+        };
+        recurring_overlaps(&self.days, start, end, from, to)
+    }
+}
+
+/// Returns true if a weekly recurring window overlaps the period `[from, to)`
+fn recurring_overlaps(days: &[Weekday], start: &str, end: &str, from: u64, to: u64) -> bool {
+    // Recurring windows start and end on minute boundaries, so checking the
+    // period start and every minute boundary inside the period is exact
+    let first_boundary = (from / 60 + 1) * 60;
+    std::iter::once(from)
+        .chain((first_boundary..to).step_by(60))
+        .any(|timestamp| recurring_contains(days, start, end, timestamp))
+}
+
+/// Parsed cron expression, evaluated in UTC
+///
+/// Supports the standard five fields (minute, hour, day of month, month, day of
+/// week) with `*`, lists, ranges, steps and month/day names, plus the `@hourly`,
+/// `@daily`, `@weekly`, `@monthly` and `@yearly` shortcuts. As in standard cron,
+/// when both day fields are restricted a day matches if either of them matches.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CronSchedule {
+    minutes: u64,
+    hours: u64,
+    days_of_month: u64,
+    months: u64,
+    days_of_week: u64,
+    day_of_month_restricted: bool,
+    day_of_week_restricted: bool,
+}
+
+impl CronSchedule {
+    /// Parse a cron expression
+    pub fn parse(expression: &str) -> crate::Result<Self> {
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        if fields.len() != 5 {
+            return Err(crate::MonitoringError::Validation(format!(
+                "Invalid cron expression '{}': expected 5 fields (minute hour day-of-month month day-of-week), got {}.",
+                expression,
+                fields.len()
+            ))
+            .into());
+        }
+
+        let parse = |field: &str, name: &str, min: u32, max: u32, names: &[&str]| {
+            parse_cron_field(field, min, max, names).map_err(|message| {
+                crate::MonitoringError::Validation(format!(
+                    "Invalid cron expression '{}': {} field '{}' {}.",
+                    expression, name, field, message
+                ))
+            })
+        };
+
+        const MONTHS: [&str; 12] = [
+            "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+        ];
+        const DAYS: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+        let mut days_of_week = parse(fields[4], "day-of-week", 0, 7, &DAYS)?;
+        // 7 is an alias for Sunday
+        if days_of_week & (1 << 7) != 0 {
+            days_of_week = (days_of_week & !(1 << 7)) | 1;
+        }
+
+        let schedule = Self {
+            minutes: parse(fields[0], "minute", 0, 59, &[])?,
+            hours: parse(fields[1], "hour", 0, 23, &[])?,
+            days_of_month: parse(fields[2], "day-of-month", 1, 31, &[])?,
+            months: parse(fields[3], "month", 1, 12, &MONTHS)?,
+            days_of_week,
+            day_of_month_restricted: !fields[2].starts_with('*'),
+            day_of_week_restricted: !fields[4].starts_with('*'),
+        };
+
+        if schedule.next_after(0).is_none() {
+            return Err(crate::MonitoringError::Validation(format!(
+                "Invalid cron expression '{}': it never matches any date.",
+                expression
+            ))
+            .into());
+        }
+
+        Ok(schedule)
+    }
+
+    /// Returns true if the cron expression matches the minute containing the timestamp
+    pub fn matches(&self, timestamp: u64) -> bool {
+        let Some(time) = DateTime::<Utc>::from_timestamp(timestamp as i64, 0) else {
+            return false;
+        };
+        self.matches_day(time.date_naive())
+            && self.hours & (1 << time.hour()) != 0
+            && self.minutes & (1 << time.minute()) != 0
+    }
+
+    /// Returns the first matching minute strictly after the timestamp
+    pub fn next_after(&self, timestamp: u64) -> Option<u64> {
+        let candidate = (timestamp / 60 + 1) * 60;
+        let start = DateTime::<Utc>::from_timestamp(candidate as i64, 0)?;
+        let start_date = start.date_naive();
+        let start_minute_of_day = start.hour() * 60 + start.minute();
+
+        for day_offset in 0..MAX_CRON_SEARCH_DAYS {
+            let date = start_date.checked_add_signed(ChronoDuration::days(day_offset))?;
+            if !self.matches_day(date) {
+                continue;
+            }
+            let first_minute = if day_offset == 0 {
+                start_minute_of_day
+            } else {
+                0
+            };
+            for minute_of_day in first_minute..MINUTES_PER_DAY {
+                if self.hours & (1 << (minute_of_day / 60)) != 0
+                    && self.minutes & (1 << (minute_of_day % 60)) != 0
+                {
+                    let midnight = date.and_hms_opt(0, 0, 0)?.and_utc().timestamp();
+                    return Some((midnight + minute_of_day as i64 * 60) as u64);
+                }
+            }
+        }
+
+        None
+    }
+
+    /// Returns every matching minute in the period `[from, to)`
+    pub fn occurrences(&self, from: u64, to: u64) -> Vec<u64> {
+        let mut occurrences = Vec::new();
+        let mut next = if self.matches(from) && from.is_multiple_of(60) {
+            Some(from)
+        } else {
+            self.next_after(from)
+        };
+        while let Some(timestamp) = next.filter(|t| *t < to) {
+            occurrences.push(timestamp);
+            next = self.next_after(timestamp);
+        }
+        occurrences
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        if self.months & (1 << date.month()) == 0 {
+            return false;
+        }
+        let day_of_month = self.days_of_month & (1 << date.day()) != 0;
+        let day_of_week = self.days_of_week & (1 << date.weekday().num_days_from_sunday()) != 0;
+        if self.day_of_month_restricted && self.day_of_week_restricted {
+            day_of_month || day_of_week
+        } else {
+            day_of_month && day_of_week
+        }
+    }
+}
+
+/// Parse one cron field into a bit set of the allowed values
+///
+/// `names` maps names to values starting at `min` (e.g. "jan" = 1).
+fn parse_cron_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<u64, String> {
+    let value = |text: &str| -> Result<u32, String> {
+        let lower = text.to_ascii_lowercase();
+        if let Some(index) = names.iter().position(|name| *name == lower) {
+            return Ok(min + index as u32);
+        }
+        let parsed: u32 = text
+            .parse()
+            .map_err(|_| format!("contains invalid value '{}'", text))?;
+        if parsed < min || parsed > max {
+            return Err(format!(
+                "value {} is out of range ({}-{})",
+                parsed, min, max
+            ));
+        }
+        Ok(parsed)
+    };
+
+    let mut bits = 0u64;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step: u32 = step
+                    .parse()
+                    .map_err(|_| format!("contains invalid step '{}'", step))?;
+                if step == 0 {
+                    return Err("has a step of 0".to_string());
+                }
+                (range, step)
+            }
+            None => (item, 1),
+        };
+
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (value(start)?, value(end)?)
+        } else if item.contains('/') {
+            // "a/n" means from a to the end of the range every n
+            (value(range)?, max)
+        } else {
+            let single = value(range)?;
+            (single, single)
+        };
+
+        if start > end {
+            return Err(format!("has a reversed range '{}'", range));
+        }
+        for allowed in (start..=end).step_by(step as usize) {
+            bits |= 1 << allowed;
+        }
+    }
+
+    Ok(bits)
+}
+
+/// Parse a time of day "HH:MM" into minutes since midnight ("24:00" is allowed)
+fn parse_time_of_day(text: &str) -> Option<u32> {
+    let (hours, minutes) = text.split_once(':')?;
+    if hours.len() != 2 || minutes.len() != 2 {
+        return None;
+    }
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    let total = hours * 60 + minutes;
+    (minutes < 60 && total <= MINUTES_PER_DAY).then_some(total)
+}
+
+/// Parse an RFC 3339 timestamp into Unix seconds
+fn parse_rfc3339(text: &str) -> Option<i64> {
+    DateTime::parse_from_rfc3339(text)
+        .ok()
+        .map(|time| time.timestamp())
+}
+
+/// Validate the start and end times of a recurring window
+fn validate_recurring(start: &str, end: &str) -> crate::Result<()> {
+    match (parse_time_of_day(start), parse_time_of_day(end)) {
+        (Some(start_minutes), Some(end_minutes))
+            if start_minutes < MINUTES_PER_DAY && start_minutes != end_minutes =>
+        {
+            Ok(())
+        }
+        (Some(_), Some(_)) => Err(crate::MonitoringError::Validation(format!(
+            "Time window {}-{} is empty. Start and end must differ and start must be before 24:00.",
+            start, end
+        ))
+        .into()),
+        _ => Err(crate::MonitoringError::Validation(format!(
+            "Invalid time window {}-{}. Times must be \"HH:MM\" in UTC (e.g., \"08:00\").",
+            start, end
+        ))
+        .into()),
+    }
+}
+
+/// Returns true if the timestamp falls inside a recurring window
+///
+/// For windows spanning midnight, `days` refers to the day the window starts on.
+fn recurring_contains(days: &[Weekday], start: &str, end: &str, timestamp: u64) -> bool {
+    let (Some(start), Some(end), Some(time)) = (
+        parse_time_of_day(start),
+        parse_time_of_day(end),
+        DateTime::<Utc>::from_timestamp(timestamp as i64, 0),
+    ) else {
+        return false;
+    };
+
+    let minute_of_day = time.hour() * 60 + time.minute();
+    let today = time.weekday();
+    let day_allowed = |day: chrono::Weekday| days.is_empty() || days.iter().any(|d| d.matches(day));
+
+    if start < end {
+        day_allowed(today) && minute_of_day >= start && minute_of_day < end
+    } else {
+        (day_allowed(today) && minute_of_day >= start)
+            || (day_allowed(today.pred()) && minute_of_day < end)
+    }
+}
@@ -1,10 +1,12 @@
 //! Tests for configuration types and validation
 
 use crate::config::{
-    AgentConfig, BandwidthParams, HttpGetParams, PingParams, TaskConfig, TaskParams, TaskType,
-    TasksConfig, TcpParams, TlsHandshakeParams,
+    AgentConfig, BandwidthDirection, BandwidthParams, BandwidthTarget, HttpGetParams, PingParams,
+    TaskConfig, TaskParams, TaskType, TasksConfig, TcpParams, TlsHandshakeParams,
+    TwampResponderConfig, UdpProbeParams, UdpReflectorConfig, MAX_TASK_LABELS,
 };
 use std::collections::HashMap;
+use std::time::Duration;
 
 #[test]
 fn test_agent_config_validation() {
@@ -18,16 +20,26 @@ fn test_agent_config_validation() {
         metrics_flush_interval_seconds: 5,
         metrics_send_interval_seconds: 30,
         metrics_batch_size: 50,
+        raw_metrics_batch_max_size_kb: 1024,
         metrics_max_retries: 10,
         queue_cleanup_interval_seconds: 3600,
         data_cleanup_interval_seconds: 86400,
         max_concurrent_tasks: 50,
+        max_concurrent_per_type: HashMap::new(),
         http_response_max_size_mb: 100,
         http_client_timeout_seconds: 30,
         database_busy_timeout_seconds: 5,
         graceful_shutdown_timeout_seconds: 30,
         channel_buffer_size: 1000,
         http_client_refresh_interval_seconds: 3600,
+        labels: Default::default(),
+        public_ip_url: None,
+        host_facts_refresh_interval_seconds: 900,
+        bandwidth_reflector: None,
+        udp_reflector: None,
+        twamp_responder: None,
+        #[cfg(feature = "snmp-tasks")]
+        snmp_trap_receiver: None,
     };
 
     assert!(config.validate().is_ok());
@@ -47,36 +59,69 @@ fn test_agent_config_validation() {
     // Test zero retention days
     config.local_data_retention_days = 0;
     assert!(config.validate().is_err());
+
+    config.local_data_retention_days = 7;
+
+    // Test UDP reflector addresses
+    config.udp_reflector = Some(UdpReflectorConfig {
+        listen_address: "0.0.0.0:8789".to_string(),
+        allowed_sources: vec!["10.2.0.5".to_string(), "::1".to_string()],
+    });
+    assert!(config.validate().is_ok());
+    config.udp_reflector.as_mut().unwrap().allowed_sources = vec!["branch-b".to_string()];
+    assert!(config.validate().is_err());
+    config
+        .udp_reflector
+        .as_mut()
+        .unwrap()
+        .allowed_sources
+        .clear();
+    config.udp_reflector.as_mut().unwrap().listen_address = "8789".to_string();
+    assert!(config.validate().is_err());
+    config.udp_reflector = None;
+
+    // Test TWAMP responder addresses
+    config.twamp_responder = Some(TwampResponderConfig {
+        listen_address: "0.0.0.0:862".to_string(),
+        allowed_sources: vec!["10.2.0.5".to_string()],
+    });
+    assert!(config.validate().is_ok());
+    config.twamp_responder.as_mut().unwrap().allowed_sources = vec!["router-1".to_string()];
+    assert!(config.validate().is_err());
+    config.twamp_responder = Some(TwampResponderConfig {
+        listen_address: "862".to_string(),
+        allowed_sources: vec![],
+    });
+    assert!(config.validate().is_err());
 }
 
 #[test]
 fn test_task_config_validation() {
-    let ping_task = TaskConfig {
-        task_type: TaskType::Ping,
-        schedule_seconds: 10,
-        name: "Test Ping".to_string(),
-        timeout: None,
-        params: TaskParams::Ping(PingParams {
+    let ping_task = TaskConfig::new(
+        "Test Ping",
+        10,
+        TaskParams::Ping(PingParams {
             host: "8.8.8.8".to_string(),
             timeout_seconds: 1,
             target_id: None,
         }),
-    };
+    );
 
     assert!(ping_task.validate().is_ok());
 
     let http_task = TaskConfig {
-        task_type: TaskType::HttpGet,
-        schedule_seconds: 30,
-        name: "Test HTTP".to_string(),
         timeout: Some(15),
-        params: TaskParams::HttpGet(HttpGetParams {
-            url: "https://example.com".to_string(),
-            timeout_seconds: 10,
-            headers: HashMap::new(),
-            verify_ssl: false,
-            target_id: None,
-        }),
+        ..TaskConfig::new(
+            "Test HTTP",
+            30,
+            TaskParams::HttpGet(HttpGetParams {
+                url: "https://example.com".to_string(),
+                timeout_seconds: 10,
+                headers: HashMap::new(),
+                verify_ssl: false,
+                target_id: None,
+            }),
+        )
     };
 
     assert!(http_task.validate().is_ok());
@@ -85,48 +130,45 @@ fn test_task_config_validation() {
 #[test]
 fn test_get_effective_timeout() {
     let ping_task = TaskConfig {
-        task_type: TaskType::Ping,
-        schedule_seconds: 10,
-        name: "Test Ping".to_string(),
         timeout: Some(3),
-        params: TaskParams::Ping(PingParams {
-            host: "8.8.8.8".to_string(),
-            timeout_seconds: 1,
-            target_id: None,
-        }),
+        ..TaskConfig::new(
+            "Test Ping",
+            10,
+            TaskParams::Ping(PingParams {
+                host: "8.8.8.8".to_string(),
+                timeout_seconds: 1,
+                target_id: None,
+            }),
+        )
     };
 
     // Should use task-level timeout when specified
     assert_eq!(ping_task.get_effective_timeout(), 3);
 
-    let ping_task_no_override = TaskConfig {
-        task_type: TaskType::Ping,
-        schedule_seconds: 10,
-        name: "Test Ping No Override".to_string(),
-        timeout: None,
-        params: TaskParams::Ping(PingParams {
+    let ping_task_no_override = TaskConfig::new(
+        "Test Ping No Override",
+        10,
+        TaskParams::Ping(PingParams {
             host: "8.8.8.8".to_string(),
             timeout_seconds: 1,
             target_id: None,
         }),
-    };
+    );
 
     // Should use default timeout when task-level timeout is None
     assert_eq!(ping_task_no_override.get_effective_timeout(), 1);
 
-    let http_task = TaskConfig {
-        task_type: TaskType::HttpGet,
-        schedule_seconds: 30,
-        name: "Test HTTP".to_string(),
-        timeout: None,
-        params: TaskParams::HttpGet(HttpGetParams {
+    let http_task = TaskConfig::new(
+        "Test HTTP",
+        30,
+        TaskParams::HttpGet(HttpGetParams {
             url: "https://example.com".to_string(),
             timeout_seconds: 10,
             headers: HashMap::new(),
             verify_ssl: false,
             target_id: None,
         }),
-    };
+    );
 
     // Should use HTTP default timeout
     assert_eq!(http_task.get_effective_timeout(), 10);
@@ -134,32 +176,44 @@ fn test_get_effective_timeout() {
 
 #[test]
 fn test_bandwidth_task_minimum_schedule() {
-    let valid_bandwidth_task = TaskConfig {
-        task_type: TaskType::Bandwidth,
-        schedule_seconds: 60,
-        name: "Valid Bandwidth Test".to_string(),
-        timeout: None,
-        params: TaskParams::Bandwidth(BandwidthParams {
+    let valid_bandwidth_task = TaskConfig::new(
+        "Valid Bandwidth Test",
+        60,
+        TaskParams::Bandwidth(BandwidthParams {
             timeout_seconds: 60,
             max_retries: 10,
+            direction: BandwidthDirection::Download,
+            parallel_streams: 1,
+            url: None,
+            upload_url: None,
+            reflector_url: None,
+            reflector_api_key: None,
+            data_size_mb: None,
+            coordinate_with_server: None,
             target_id: None,
         }),
-    };
+    );
 
     // Should pass validation with 60 second schedule
     assert!(valid_bandwidth_task.validate().is_ok());
 
-    let invalid_bandwidth_task = TaskConfig {
-        task_type: TaskType::Bandwidth,
-        schedule_seconds: 30, // Less than 60 seconds
-        name: "Invalid Bandwidth Test".to_string(),
-        timeout: None,
-        params: TaskParams::Bandwidth(BandwidthParams {
+    let invalid_bandwidth_task = TaskConfig::new(
+        "Invalid Bandwidth Test",
+        30, // Less than 60 seconds
+        TaskParams::Bandwidth(BandwidthParams {
             timeout_seconds: 60,
             max_retries: 10,
+            direction: BandwidthDirection::Download,
+            parallel_streams: 1,
+            url: None,
+            upload_url: None,
+            reflector_url: None,
+            reflector_api_key: None,
+            data_size_mb: None,
+            coordinate_with_server: None,
             target_id: None,
         }),
-    };
+    );
 
     // Should fail validation with less than 60 second schedule
     assert!(invalid_bandwidth_task.validate().is_err());
@@ -168,6 +222,369 @@ fn test_bandwidth_task_minimum_schedule() {
     assert!(error_msg.contains("Bandwidth tasks must have schedule_seconds >= 60"));
 }
 
+#[test]
+fn test_bandwidth_direction_parsing() {
+    let toml_str = r#"
+[[tasks]]
+type = "bandwidth"
+name = "Default Direction"
+schedule_seconds = 300
+
+[[tasks]]
+type = "bandwidth"
+name = "Both Directions"
+schedule_seconds = 300
+direction = "both"
+"#;
+
+    let config: TasksConfig = toml::from_str(toml_str).unwrap();
+    let directions: Vec<BandwidthDirection> = config
+        .tasks
+        .iter()
+        .map(|task| match &task.params {
+            TaskParams::Bandwidth(p) => p.direction,
+            _ => panic!("Expected Bandwidth params"),
+        })
+        .collect();
+    assert_eq!(
+        directions,
+        vec![BandwidthDirection::Download, BandwidthDirection::Both]
+    );
+    assert!(BandwidthDirection::Both.includes_download());
+    assert!(BandwidthDirection::Both.includes_upload());
+    assert!(!BandwidthDirection::Upload.includes_download());
+
+    let invalid = r#"
+[[tasks]]
+type = "bandwidth"
+name = "Invalid Direction"
+schedule_seconds = 300
+direction = "sideways"
+"#;
+    assert!(toml::from_str::<TasksConfig>(invalid).is_err());
+}
+
+#[test]
+fn test_bandwidth_parallel_streams_validation() {
+    let mut task = TaskConfig::new(
+        "Parallel Bandwidth",
+        300,
+        TaskParams::Bandwidth(BandwidthParams {
+            timeout_seconds: 60,
+            max_retries: 10,
+            direction: BandwidthDirection::Both,
+            parallel_streams: 4,
+            url: None,
+            upload_url: None,
+            reflector_url: None,
+            reflector_api_key: None,
+            data_size_mb: None,
+            coordinate_with_server: None,
+            target_id: None,
+        }),
+    );
+    assert!(task.validate().is_ok());
+
+    for invalid in [0, 17] {
+        if let TaskParams::Bandwidth(params) = &mut task.params {
+            params.parallel_streams = invalid;
+        }
+        let error = task.validate().unwrap_err().to_string();
+        assert!(error.contains("parallel_streams"), "{}", error);
+    }
+}
+
+#[test]
+fn test_bandwidth_target_validation() {
+    let parse = |extra: &str| -> TaskConfig {
+        let toml_str = format!(
+            "[[tasks]]\ntype = \"bandwidth\"\nname = \"Target\"\nschedule_seconds = 300\n{}",
+            extra
+        );
+        toml::from_str::<TasksConfig>(&toml_str)
+            .unwrap()
+            .tasks
+            .remove(0)
+    };
+
+    // Default: central server, always coordinated, no derived target_id
+    let task = parse("");
+    let TaskParams::Bandwidth(params) = &task.params else {
+        panic!("expected bandwidth params");
+    };
+    assert_eq!(params.target(), BandwidthTarget::Server);
+    assert!(params.coordinates_with_server());
+    assert_eq!(params.effective_target_id(), None);
+
+    // Reflector: uncoordinated unless requested, grouped by host and port
+    let task = parse("reflector_url = \"http://10.2.0.5:8788\"\ndirection = \"both\"");
+    assert!(task.validate().is_ok());
+    let TaskParams::Bandwidth(params) = &task.params else {
+        panic!("expected bandwidth params");
+    };
+    assert_eq!(
+        params.target(),
+        BandwidthTarget::Reflector("http://10.2.0.5:8788")
+    );
+    assert!(!params.coordinates_with_server());
+    assert_eq!(
+        params.effective_target_id().as_deref(),
+        Some("10.2.0.5:8788")
+    );
+
+    // Third-party file with explicit coordination
+    let task = parse("url = \"https://cdn.example.com/10MB.bin\"\ncoordinate_with_server = true");
+    assert!(task.validate().is_ok());
+    let TaskParams::Bandwidth(params) = &task.params else {
+        panic!("expected bandwidth params");
+    };
+    assert!(params.coordinates_with_server());
+    assert_eq!(
+        params.effective_target_id().as_deref(),
+        Some("cdn.example.com")
+    );
+
+    for (extra, expected) in [
+        (
+            "url = \"https://cdn.example.com/f\"\nreflector_url = \"http://10.2.0.5:8788\"",
+            "Choose one target",
+        ),
+        (
+            "url = \"https://cdn.example.com/f\"\ndirection = \"upload\"",
+            "upload_url",
+        ),
+        (
+            "upload_url = \"https://cdn.example.com/u\"",
+            "requires 'url'",
+        ),
+        ("coordinate_with_server = false", "coordinate_with_server"),
+        ("data_size_mb = 20", "data_size_mb"),
+        (
+            "reflector_url = \"http://10.2.0.5:8788\"\ndata_size_mb = 0",
+            "data_size_mb",
+        ),
+        ("reflector_url = \"ftp://10.2.0.5\"", "http"),
+    ] {
+        let error = parse(extra).validate().unwrap_err().to_string();
+        assert!(error.contains(expected), "{}: {}", extra, error);
+    }
+}
+
+#[test]
+fn test_tcp_sweep_task_validation() {
+    let parse = |extra: &str| -> TaskConfig {
+        let toml_str = format!(
+            "[[tasks]]\ntype = \"tcp_sweep\"\nname = \"Web farm\"\nschedule_seconds = 60\nhosts = [\"10.0.1.0/30\", \"web-1.example.com\"]\nports = \"443,8000-8001\"\n{}",
+            extra
+        );
+        toml::from_str::<TasksConfig>(&toml_str)
+            .unwrap()
+            .tasks
+            .remove(0)
+    };
+
+    let task = parse("");
+    assert!(task.validate().is_ok());
+    assert_eq!(task.task_type, TaskType::TcpSweep);
+    let TaskParams::TcpSweep(params) = &task.params else {
+        panic!("expected TCP sweep params");
+    };
+    assert_eq!(params.timeout_seconds, 2);
+    assert_eq!(params.concurrency, 32);
+    // Network and broadcast addresses of the /30 are skipped
+    assert_eq!(
+        params.expand_hosts().unwrap(),
+        vec!["10.0.1.1", "10.0.1.2", "web-1.example.com"]
+    );
+    assert_eq!(params.expand_ports().unwrap(), vec![443, 8000, 8001]);
+    let targets = params.targets().unwrap();
+    assert_eq!(targets.len(), 9);
+    assert_eq!(targets[0], "10.0.1.1:443");
+    assert_eq!(targets[8], "web-1.example.com:8001");
+    assert_eq!(task.get_effective_timeout(), 2);
+
+    for (extra, expected) in [
+        ("timeout_seconds = 0", "timeout_seconds"),
+        ("concurrency = 0", "concurrency"),
+        ("concurrency = 257", "concurrency"),
+        ("concurrency = 1\ntimeout_seconds = 10", "schedule_seconds"),
+    ] {
+        let error = parse(extra).validate().unwrap_err().to_string();
+        assert!(error.contains(expected), "{}: {}", extra, error);
+    }
+
+    for (hosts, ports, expected) in [
+        (vec![], "443", "'hosts'"),
+        (vec!["10.0.1.0/33"], "443", "10.0.1.0/33"),
+        (vec!["10.0.0.0/8"], "443", "too large"),
+        (vec!["10.0.1.1"], "0", "'0'"),
+        (vec!["10.0.1.1"], "443-80", "443-80"),
+        (vec!["10.0.1.1"], "http", "'http'"),
+        (vec!["10.0.0.0/20"], "22,443", "at most 4096"),
+    ] {
+        let mut task = parse("");
+        if let TaskParams::TcpSweep(params) = &mut task.params {
+            params.hosts = hosts.into_iter().map(String::from).collect();
+            params.ports = ports.to_string();
+        }
+        let error = task.validate().unwrap_err().to_string();
+        assert!(error.contains(expected), "{}: {}", ports, error);
+    }
+
+    // IPv6 blocks keep every address and targets are bracketed
+    let mut task = parse("");
+    if let TaskParams::TcpSweep(params) = &mut task.params {
+        params.hosts = vec!["2001:db8::/127".to_string()];
+        params.ports = "22".to_string();
+        assert_eq!(
+            params.targets().unwrap(),
+            vec!["[2001:db8::]:22", "[2001:db8::1]:22"]
+        );
+    }
+}
+
+#[test]
+fn test_ntp_task_validation() {
+    let parse = |extra: &str| -> TaskConfig {
+        let toml_str = format!(
+            "[[tasks]]\ntype = \"ntp\"\nname = \"Pool NTP\"\nschedule_seconds = 60\nhost = \"pool.ntp.org\"\n{}",
+            extra
+        );
+        toml::from_str::<TasksConfig>(&toml_str)
+            .unwrap()
+            .tasks
+            .remove(0)
+    };
+
+    let task = parse("warn_offset_ms = 100.0\ncrit_offset_ms = 1000.0");
+    assert!(task.validate().is_ok());
+    assert_eq!(task.task_type, TaskType::Ntp);
+    let TaskParams::Ntp(params) = &task.params else {
+        panic!("expected NTP params");
+    };
+    assert_eq!(params.timeout_seconds, 5);
+    assert_eq!(params.warn_offset_ms, Some(100.0));
+    assert_eq!(params.server_address(), "pool.ntp.org:123");
+    assert_eq!(task.get_effective_timeout(), 5);
+
+    for (extra, expected) in [
+        ("timeout_seconds = 0", "timeout_seconds"),
+        ("warn_offset_ms = 0.0", "warn_offset_ms"),
+        ("crit_offset_ms = -5.0", "crit_offset_ms"),
+        (
+            "warn_offset_ms = 500.0\ncrit_offset_ms = 100.0",
+            "cannot be greater",
+        ),
+    ] {
+        let error = parse(extra).validate().unwrap_err().to_string();
+        assert!(error.contains(expected), "{}: {}", extra, error);
+    }
+
+    let mut task = parse("");
+    if let TaskParams::Ntp(params) = &mut task.params {
+        params.host = "10.0.0.1:1123".to_string();
+        assert_eq!(params.server_address(), "10.0.0.1:1123");
+        params.host = "2001:db8::1".to_string();
+        assert_eq!(params.server_address(), "[2001:db8::1]:123");
+        params.host.clear();
+    }
+    assert!(task.validate().unwrap_err().to_string().contains("'host'"));
+}
+
+#[test]
+fn test_udp_probe_task_validation() {
+    let parse = |extra: &str| -> TaskConfig {
+        let toml_str = format!(
+            "[[tasks]]\ntype = \"udp_probe\"\nname = \"Voice path\"\nschedule_seconds = 30\nhost = \"10.2.0.5:8789\"\n{}",
+            extra
+        );
+        toml::from_str::<TasksConfig>(&toml_str)
+            .unwrap()
+            .tasks
+            .remove(0)
+    };
+
+    // Defaults describe one second of a G.711 call
+    let task = parse("");
+    assert!(task.validate().is_ok());
+    let TaskParams::UdpProbe(params) = &task.params else {
+        panic!("expected UDP probe params");
+    };
+    assert_eq!(params.packet_count, 50);
+    assert_eq!(params.interval_ms, 20);
+    assert_eq!(params.payload_size, 160);
+    // The task timeout covers sending all packets plus the reply timeout
+    assert_eq!(params.run_duration_seconds(), 3);
+    assert_eq!(task.get_effective_timeout(), 3);
+
+    for (extra, expected) in [
+        ("packet_count = 0", "packet_count"),
+        ("interval_ms = 0", "interval_ms"),
+        ("payload_size = 27", "payload_size"),
+        ("payload_size = 1473", "payload_size"),
+        ("timeout_seconds = 0", "timeout_seconds"),
+        ("packet_count = 1000\ninterval_ms = 100", "schedule_seconds"),
+    ] {
+        let error = parse(extra).validate().unwrap_err().to_string();
+        assert!(error.contains(expected), "{}: {}", extra, error);
+    }
+
+    let mut task = parse("");
+    task.params = TaskParams::UdpProbe(UdpProbeParams {
+        host: String::new(),
+        packet_count: 50,
+        interval_ms: 20,
+        payload_size: 160,
+        timeout_seconds: 2,
+        target_id: None,
+    });
+    assert!(task.validate().unwrap_err().to_string().contains("'host'"));
+}
+
+#[test]
+fn test_twamp_task_validation() {
+    let parse = |extra: &str| -> TaskConfig {
+        let toml_str = format!(
+            "[[tasks]]\ntype = \"twamp\"\nname = \"Core router\"\nschedule_seconds = 30\nhost = \"10.0.0.1:862\"\n{}",
+            extra
+        );
+        toml::from_str::<TasksConfig>(&toml_str)
+            .unwrap()
+            .tasks
+            .remove(0)
+    };
+
+    let task = parse("");
+    assert!(task.validate().is_ok());
+    assert_eq!(task.task_type, TaskType::Twamp);
+    let TaskParams::Twamp(params) = &task.params else {
+        panic!("expected TWAMP params");
+    };
+    assert_eq!(params.packet_count, 10);
+    assert_eq!(params.interval_ms, 100);
+    assert_eq!(params.packet_size, 41);
+    assert_eq!(params.run_duration_seconds(), 3);
+    assert_eq!(task.get_effective_timeout(), 3);
+
+    for (extra, expected) in [
+        ("packet_count = 0", "packet_count"),
+        ("interval_ms = 1001", "interval_ms"),
+        ("packet_size = 13", "packet_size"),
+        ("packet_size = 1473", "packet_size"),
+        ("timeout_seconds = 0", "timeout_seconds"),
+        ("packet_count = 300", "schedule_seconds"),
+    ] {
+        let error = parse(extra).validate().unwrap_err().to_string();
+        assert!(error.contains(expected), "{}: {}", extra, error);
+    }
+
+    let mut task = parse("");
+    if let TaskParams::Twamp(params) = &mut task.params {
+        params.host.clear();
+    }
+    assert!(task.validate().unwrap_err().to_string().contains("'host'"));
+}
+
 #[test]
 fn test_task_params_ordering() {
     // Test that HttpContent (more specific) is correctly deserialized
@@ -264,32 +681,34 @@ url = "https://example.com"
 #[test]
 fn test_tcp_task_validation() {
     // Test valid TCP task
-    let tcp_task = TaskConfig {
-        task_type: TaskType::Tcp,
-        schedule_seconds: 60,
-        name: "SSH Port Check".to_string(),
-        timeout: None,
-        params: TaskParams::Tcp(TcpParams {
+    let tcp_task = TaskConfig::new(
+        "SSH Port Check",
+        60,
+        TaskParams::Tcp(TcpParams {
             host: "example.com:22".to_string(),
             timeout_seconds: 5,
+            send: None,
+            expect: None,
+            read_timeout_seconds: 5,
             target_id: Some("ssh-server".to_string()),
         }),
-    };
+    );
 
     assert!(tcp_task.validate().is_ok());
 
     // Test TCP task with missing host
-    let invalid_tcp_task = TaskConfig {
-        task_type: TaskType::Tcp,
-        schedule_seconds: 60,
-        name: "Invalid TCP".to_string(),
-        timeout: None,
-        params: TaskParams::Tcp(TcpParams {
+    let invalid_tcp_task = TaskConfig::new(
+        "Invalid TCP",
+        60,
+        TaskParams::Tcp(TcpParams {
             host: "".to_string(),
             timeout_seconds: 5,
+            send: None,
+            expect: None,
+            read_timeout_seconds: 5,
             target_id: None,
         }),
-    };
+    );
 
     assert!(invalid_tcp_task.validate().is_err());
 
@@ -312,44 +731,246 @@ timeout_seconds = 5
         TaskParams::Tcp(params) => {
             assert_eq!(params.host, "wilam.ovh:22");
             assert_eq!(params.timeout_seconds, 5);
+            assert!(!params.checks_response());
         }
         _ => panic!("Expected Tcp params"),
     }
+    assert_eq!(config.tasks[0].get_effective_timeout(), 5);
+
+    // Response checks add the read timeout to the task timeout
+    let toml_str = r#"
+[[tasks]]
+type = "tcp"
+name = "Redis"
+schedule_seconds = 30
+host = "cache.internal:6379"
+timeout_seconds = 3
+send = "PING\r\n"
+expect = '^\+PONG'
+"#;
+    let mut task = toml::from_str::<TasksConfig>(toml_str)
+        .unwrap()
+        .tasks
+        .remove(0);
+    assert!(task.validate().is_ok());
+    assert_eq!(task.get_effective_timeout(), 8);
+    let TaskParams::Tcp(params) = &mut task.params else {
+        panic!("Expected Tcp params");
+    };
+    assert_eq!(params.send.as_deref(), Some("PING\r\n"));
+    assert_eq!(params.read_timeout_seconds, 5);
+
+    params.expect = Some("(unclosed".to_string());
+    assert!(task.validate().unwrap_err().to_string().contains("expect"));
+    if let TaskParams::Tcp(params) = &mut task.params {
+        params.expect = None;
+        params.send = Some(String::new());
+    }
+    assert!(task.validate().unwrap_err().to_string().contains("'send'"));
+    if let TaskParams::Tcp(params) = &mut task.params {
+        params.send = Some("PING\r\n".to_string());
+        params.read_timeout_seconds = 0;
+    }
+    assert!(task
+        .validate()
+        .unwrap_err()
+        .to_string()
+        .contains("read_timeout_seconds"));
 }
 
 #[test]
 fn test_tls_handshake_task_validation() {
     // Test valid TLS handshake task
-    let tls_task = TaskConfig {
-        task_type: TaskType::TlsHandshake,
-        schedule_seconds: 60,
-        name: "TLS Check".to_string(),
-        timeout: None,
-        params: TaskParams::TlsHandshake(TlsHandshakeParams {
+    let tls_task = TaskConfig::new(
+        "TLS Check",
+        60,
+        TaskParams::TlsHandshake(TlsHandshakeParams {
             host: "example.com:443".to_string(),
             verify_ssl: true,
             target_id: None,
         }),
-    };
+    );
 
     assert!(tls_task.validate().is_ok());
 
     // Test TLS task with missing host
-    let invalid_tls_task = TaskConfig {
-        task_type: TaskType::TlsHandshake,
-        schedule_seconds: 60,
-        name: "Invalid TLS".to_string(),
-        timeout: None,
-        params: TaskParams::TlsHandshake(TlsHandshakeParams {
+    let invalid_tls_task = TaskConfig::new(
+        "Invalid TLS",
+        60,
+        TaskParams::TlsHandshake(TlsHandshakeParams {
             host: "".to_string(),
             verify_ssl: true,
             target_id: None,
         }),
-    };
+    );
 
     assert!(invalid_tls_task.validate().is_err());
 }
 
+#[test]
+fn test_aggregation_window_validation() {
+    let toml_str = r#"
+[[tasks]]
+type = "ping"
+name = "Ping"
+schedule_seconds = 10
+host = "8.8.8.8"
+"#;
+
+    // Defaults to one-minute windows
+    let mut config: TasksConfig = toml::from_str(toml_str).unwrap();
+    assert_eq!(config.aggregation_window_seconds, 60);
+    assert!(config.validate().is_ok());
+
+    let config_5m: TasksConfig =
+        toml::from_str(&format!("aggregation_window_seconds = 300\n{}", toml_str)).unwrap();
+    assert_eq!(config_5m.aggregation_window_seconds, 300);
+    assert!(config_5m.validate().is_ok());
+
+    for window in [10, 3600] {
+        config.aggregation_window_seconds = window;
+        assert!(config.validate().is_ok(), "window {}", window);
+    }
+
+    // Too short, too long, or not tiling the hour
+    for window in [0, 5, 7200, 7, 420] {
+        config.aggregation_window_seconds = window;
+        assert!(config.validate().is_err(), "window {}", window);
+    }
+
+    // Also rejected without tasks
+    let mut empty = TasksConfig::new();
+    empty.aggregation_window_seconds = 35;
+    assert!(empty.validate().is_err());
+}
+
+#[test]
+fn test_schedules_and_maintenance_windows_from_toml() {
+    let toml_str = r#"
+[[maintenance_windows]]
+name = "ISP migration"
+starts_at = "2026-11-01T02:00:00Z"
+ends_at = "2026-11-01T06:00:00Z"
+
+[[maintenance_windows]]
+name = "Sunday patching"
+tasks = ["Business Ping"]
+days = ["sun"]
+start = "02:00"
+end = "04:00"
+
+[[tasks]]
+type = "ping"
+name = "Business Ping"
+schedule_seconds = 30
+host = "8.8.8.8"
+
+[[tasks.active_windows]]
+days = ["mon", "tue", "wed", "thu", "fri"]
+start = "08:00"
+end = "18:00"
+
+[[tasks]]
+type = "ping"
+name = "Nightly Ping"
+schedule_seconds = 60
+cron = "30 2 * * *"
+host = "1.1.1.1"
+"#;
+
+    let config: TasksConfig = toml::from_str(toml_str).unwrap();
+    assert!(config.validate().is_ok());
+    assert_eq!(config.maintenance_windows.len(), 2);
+    assert_eq!(config.tasks[0].active_windows.len(), 1);
+    assert_eq!(config.tasks[1].cron.as_deref(), Some("30 2 * * *"));
+
+    // Serialization round trip keeps the schedule fields
+    let reparsed: TasksConfig = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+    assert_eq!(config, reparsed);
+
+    // 2026-11-01 is a Sunday: both windows cover 03:00 for the business task
+    let sunday_3am = 1_793_502_000;
+    assert!(config.in_maintenance("Business Ping", sunday_3am, sunday_3am + 60));
+    assert!(config.in_maintenance("Nightly Ping", sunday_3am, sunday_3am + 60));
+    // Only the recurring window applies a week later, and only to its task
+    let next_sunday_3am = sunday_3am + 7 * 86_400;
+    assert!(config.in_maintenance("Business Ping", next_sunday_3am, next_sunday_3am + 60));
+    assert!(!config.in_maintenance("Nightly Ping", next_sunday_3am, next_sunday_3am + 60));
+
+    // Invalid cron expressions, time windows and unknown task references are rejected
+    let mut invalid = config.clone();
+    invalid.tasks[1].cron = Some("61 * * * *".to_string());
+    assert!(invalid.validate().is_err());
+
+    let mut invalid = config.clone();
+    invalid.tasks[0].active_windows[0].end = "8pm".to_string();
+    assert!(invalid.validate().is_err());
+
+    let mut invalid = config.clone();
+    invalid.maintenance_windows[1].tasks = vec!["Missing".to_string()];
+    assert!(invalid.validate().is_err());
+
+    let mut invalid = config;
+    invalid.maintenance_windows[0].start = Some("01:00".to_string());
+    assert!(invalid.validate().is_err());
+}
+
+#[test]
+fn test_on_failure_schedule_from_toml() {
+    let toml_str = r#"
+[[tasks]]
+type = "http_get"
+name = "Portal"
+schedule_seconds = 300
+on_failure_schedule_seconds = 30
+on_failure_max_duration_seconds = 1800
+url = "https://example.com"
+
+[[tasks]]
+type = "bandwidth"
+name = "Bandwidth"
+schedule_seconds = 3600
+on_failure_schedule_seconds = 600
+on_failure_recovery_successes = 3
+"#;
+
+    let config: TasksConfig = toml::from_str(toml_str).unwrap();
+    assert!(config.validate().is_ok());
+    assert_eq!(
+        config.tasks[0].on_failure_schedule_duration(),
+        Some(Duration::from_secs(30))
+    );
+    assert_eq!(config.tasks[0].on_failure_max_duration_seconds, Some(1800));
+    assert_eq!(config.tasks[0].effective_recovery_successes(), 2);
+    assert_eq!(config.tasks[1].effective_recovery_successes(), 3);
+
+    let reparsed: TasksConfig = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+    assert_eq!(config, reparsed);
+
+    // The faster schedule must be faster than the normal one
+    let mut invalid = config.clone();
+    invalid.tasks[0].on_failure_schedule_seconds = Some(300);
+    assert!(invalid.validate().is_err());
+
+    // and respect the minimum schedule of the task type
+    let mut invalid = config.clone();
+    invalid.tasks[1].on_failure_schedule_seconds = Some(30);
+    assert!(invalid.validate().is_err());
+
+    let mut invalid = config.clone();
+    invalid.tasks[0].on_failure_max_duration_seconds = Some(0);
+    assert!(invalid.validate().is_err());
+
+    let mut invalid = config.clone();
+    invalid.tasks[1].on_failure_recovery_successes = Some(0);
+    assert!(invalid.validate().is_err());
+
+    // Limits without a faster schedule are rejected
+    let mut invalid = config;
+    invalid.tasks[0].on_failure_schedule_seconds = None;
+    assert!(invalid.validate().is_err());
+}
+
 #[test]
 fn test_toml_serialization() {
     let config = AgentConfig {
@@ -362,19 +983,315 @@ fn test_toml_serialization() {
         metrics_flush_interval_seconds: 5,
         metrics_send_interval_seconds: 30,
         metrics_batch_size: 50,
+        raw_metrics_batch_max_size_kb: 1024,
         metrics_max_retries: 10,
         queue_cleanup_interval_seconds: 3600,
         data_cleanup_interval_seconds: 86400,
         max_concurrent_tasks: 50,
+        max_concurrent_per_type: HashMap::new(),
         http_response_max_size_mb: 100,
         http_client_timeout_seconds: 30,
         database_busy_timeout_seconds: 5,
         graceful_shutdown_timeout_seconds: 30,
         channel_buffer_size: 1000,
         http_client_refresh_interval_seconds: 3600,
+        labels: Default::default(),
+        public_ip_url: None,
+        host_facts_refresh_interval_seconds: 900,
+        bandwidth_reflector: None,
+        udp_reflector: None,
+        twamp_responder: None,
+        #[cfg(feature = "snmp-tasks")]
+        snmp_trap_receiver: None,
     };
 
     let toml_str = toml::to_string(&config).unwrap();
     let parsed: AgentConfig = toml::from_str(&toml_str).unwrap();
     assert_eq!(config, parsed);
 }
+
+#[test]
+fn test_agent_labels_from_toml() {
+    let toml_str = r#"
+agent_id = "paris-01"
+central_server_url = "https://example.com"
+api_key = "secret"
+local_data_retention_days = 7
+public_ip_url = "https://api.ipify.org"
+
+[labels]
+site = "paris"
+region = "eu-west"
+isp = "orange"
+"#;
+
+    let config: AgentConfig = toml::from_str(toml_str).unwrap();
+    assert!(config.validate().is_ok());
+    assert_eq!(config.labels.len(), 3);
+    assert_eq!(config.labels.get("isp").map(String::as_str), Some("orange"));
+    assert_eq!(
+        config.public_ip_url.as_deref(),
+        Some("https://api.ipify.org")
+    );
+    assert_eq!(config.host_facts_refresh_interval_seconds, 900);
+
+    let mut invalid = config.clone();
+    invalid
+        .labels
+        .insert("data center".to_string(), "dc1".to_string());
+    assert!(invalid.validate().is_err());
+
+    let mut invalid = config.clone();
+    invalid.public_ip_url = Some("not a url".to_string());
+    assert!(invalid.validate().is_err());
+
+    let mut invalid = config;
+    invalid.host_facts_refresh_interval_seconds = 0;
+    assert!(invalid.validate().is_err());
+}
+
+#[test]
+#[cfg(feature = "snmp-tasks")]
+fn test_snmp_value_checks_from_toml() {
+    let toml_str = r#"
+[[tasks]]
+type = "snmp"
+name = "Router CPU"
+schedule_seconds = 60
+host = "192.168.1.1"
+oid = "1.3.6.1.4.1.9.9.109.1.1.1.1.8.1"
+min_value = 0.0
+max_value = 80.0
+"#;
+
+    let config: TasksConfig = toml::from_str(toml_str).unwrap();
+    assert!(config.tasks[0].validate().is_ok());
+    match &config.tasks[0].params {
+        TaskParams::Snmp(params) => {
+            assert_eq!(params.min_value, Some(0.0));
+            assert_eq!(params.max_value, Some(80.0));
+            assert_eq!(params.expected_value, None);
+        }
+        _ => panic!("Expected Snmp params"),
+    }
+
+    // An inverted range is rejected
+    let toml_str = r#"
+[[tasks]]
+type = "snmp"
+name = "Router CPU"
+schedule_seconds = 60
+host = "192.168.1.1"
+oid = "1.3.6.1.4.1.9.9.109.1.1.1.1.8.1"
+min_value = 80.0
+max_value = 10.0
+"#;
+
+    let config: TasksConfig = toml::from_str(toml_str).unwrap();
+    assert!(config.tasks[0].validate().is_err());
+}
+
+#[test]
+#[cfg(feature = "sql-tasks")]
+fn test_sql_threshold_validation() {
+    let toml_str = r#"
+[[tasks]]
+type = "sql_query"
+name = "Replica lag"
+schedule_seconds = 60
+query = "SELECT lag_seconds FROM replication_status"
+database_url = "localhost:5432/app"
+database_type = "postgresql"
+warn_above = 300.0
+crit_above = 600.0
+expected_row_count = 1
+"#;
+
+    let config: TasksConfig = toml::from_str(toml_str).unwrap();
+    assert!(config.tasks[0].validate().is_ok());
+    match &config.tasks[0].params {
+        TaskParams::SqlQuery(params) => {
+            assert_eq!(params.warn_above, Some(300.0));
+            assert_eq!(params.crit_above, Some(600.0));
+            assert_eq!(params.warn_below, None);
+            assert_eq!(params.expected_row_count, Some(1));
+        }
+        _ => panic!("Expected SqlQuery params"),
+    }
+
+    // Warning threshold beyond the critical one is rejected
+    let inverted = toml_str.replace("warn_above = 300.0", "warn_above = 900.0");
+    let config: TasksConfig = toml::from_str(&inverted).unwrap();
+    assert!(config.tasks[0].validate().is_err());
+
+    // Value thresholds cannot be used in JSON mode
+    let json_mode = toml_str.replace("expected_row_count = 1", "mode = \"json\"");
+    let config: TasksConfig = toml::from_str(&json_mode).unwrap();
+    assert!(config.tasks[0].validate().is_err());
+}
+
+#[test]
+fn test_task_dependencies() {
+    let toml_str = r#"
+[[tasks]]
+type = "ping"
+name = "Gateway"
+schedule_seconds = 10
+host = "192.168.1.1"
+
+[[tasks]]
+type = "ping"
+name = "ISP"
+schedule_seconds = 10
+host = "8.8.8.8"
+depends_on = ["Gateway"]
+
+[[tasks]]
+type = "http_get"
+name = "Portal"
+schedule_seconds = 60
+url = "https://example.com"
+depends_on = ["Gateway", "ISP"]
+"#;
+
+    let config: TasksConfig = toml::from_str(toml_str).unwrap();
+    assert!(config.validate().is_ok());
+    assert_eq!(config.tasks[2].depends_on, vec!["Gateway", "ISP"]);
+    assert!(config.tasks[0].depends_on.is_empty());
+
+    let reparsed: TasksConfig = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+    assert_eq!(config, reparsed);
+
+    let mut unknown = config.clone();
+    unknown.tasks[1].depends_on = vec!["Router".to_string()];
+    assert!(unknown.validate().is_err());
+
+    let mut itself = config.clone();
+    itself.tasks[0].depends_on = vec!["Gateway".to_string()];
+    assert!(itself.validate().is_err());
+
+    let mut cycle = config;
+    cycle.tasks[0].depends_on = vec!["Portal".to_string()];
+    let error = cycle.validate().unwrap_err().to_string();
+    assert!(error.contains("Gateway -> Portal -> Gateway"), "{}", error);
+}
+
+#[test]
+fn test_concurrency_limits_and_priorities_from_toml() {
+    let agent_toml = r#"
+agent_id = "branch-01"
+central_server_url = "https://monitoring.example.com"
+api_key = "secret-key"
+local_data_retention_days = 7
+max_concurrent_tasks = 20
+
+[max_concurrent_per_type]
+http_get = 4
+bandwidth = 1
+"#;
+    let mut agent_config: AgentConfig = toml::from_str(agent_toml).unwrap();
+    assert!(agent_config.validate().is_ok());
+    assert_eq!(
+        agent_config.max_concurrent_per_type.get(&TaskType::HttpGet),
+        Some(&4)
+    );
+    assert_eq!(
+        agent_config
+            .max_concurrent_per_type
+            .get(&TaskType::Bandwidth),
+        Some(&1)
+    );
+    let reparsed: AgentConfig = toml::from_str(&toml::to_string(&agent_config).unwrap()).unwrap();
+    assert_eq!(agent_config, reparsed);
+
+    agent_config
+        .max_concurrent_per_type
+        .insert(TaskType::Ping, 0);
+    assert!(agent_config.validate().is_err());
+
+    // Unknown task types are rejected when parsing
+    assert!(toml::from_str::<AgentConfig>(&format!("{}\nsmoke_signal = 1\n", agent_toml)).is_err());
+
+    let tasks_toml = r#"
+[[tasks]]
+type = "ping"
+name = "Gateway"
+schedule_seconds = 10
+host = "192.168.1.1"
+priority = 10
+
+[[tasks]]
+type = "http_get"
+name = "Portal"
+schedule_seconds = 60
+url = "https://example.com"
+"#;
+    let tasks_config: TasksConfig = toml::from_str(tasks_toml).unwrap();
+    assert!(tasks_config.validate().is_ok());
+    assert_eq!(tasks_config.tasks[0].priority, Some(10));
+    assert_eq!(tasks_config.tasks[1].priority, None);
+    assert!(
+        toml::from_str::<TasksConfig>(&tasks_toml.replace("priority = 10", "priority = 256"))
+            .is_err()
+    );
+}
+
+#[test]
+fn test_task_labels_from_toml() {
+    let toml_str = r#"
+[[tasks]]
+type = "ping"
+name = "Gateway"
+schedule_seconds = 10
+host = "192.168.1.1"
+labels = { site = "paris", environment = "prod" }
+
+[[tasks]]
+type = "http_get"
+name = "Portal"
+schedule_seconds = 60
+url = "https://example.com"
+
+[tasks.tags]
+service = "portal"
+owner = "web-team"
+"#;
+
+    let config: TasksConfig = toml::from_str(toml_str).unwrap();
+    assert!(config.validate().is_ok());
+    assert_eq!(
+        config.tasks[0].labels.get("site").map(String::as_str),
+        Some("paris")
+    );
+    // `tags` is an alias of `labels`
+    assert_eq!(
+        config.tasks[1].labels.get("owner").map(String::as_str),
+        Some("web-team")
+    );
+    assert!(!toml::to_string(&config).unwrap().contains("tags"));
+
+    let reparsed: TasksConfig = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+    assert_eq!(config, reparsed);
+
+    // Labels and tags cannot both be set
+    let both = toml_str.replace("[tasks.tags]", "labels = { site = \"lyon\" }\n[tasks.tags]");
+    assert!(toml::from_str::<TasksConfig>(&both).is_err());
+
+    let mut invalid_name = config.clone();
+    invalid_name.tasks[0]
+        .labels
+        .insert("data center".to_string(), "dc1".to_string());
+    assert!(invalid_name.validate().is_err());
+
+    let mut empty_value = config.clone();
+    empty_value.tasks[0]
+        .labels
+        .insert("region".to_string(), String::new());
+    assert!(empty_value.validate().is_err());
+
+    let mut too_many = config;
+    too_many.tasks[0].labels = (0..=MAX_TASK_LABELS)
+        .map(|index| (format!("label{}", index), "value".to_string()))
+        .collect();
+    assert!(too_many.validate().is_err());
+}
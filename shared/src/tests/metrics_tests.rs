@@ -3,7 +3,8 @@
 use crate::config::TaskType;
 use crate::metrics::{
     calculate_percentage, AggregatedHttpMetric, AggregatedMetricData, AggregatedMetrics,
-    AggregatedPingMetric, MetricData, RawMetricData, RawPingMetric,
+    AggregatedPingMetric, LatencyDistribution, MetricData, RawMetricData, RawNtpMetric,
+    RawPingMetric, ThresholdStatus,
 };
 use std::collections::HashMap;
 
@@ -61,6 +62,67 @@ fn test_ping_metric_success_detection() {
     assert!(!failed_metric.is_successful());
 }
 
+#[test]
+fn test_critical_ntp_offset_is_not_successful() {
+    let ntp_metric = |status| {
+        MetricData::new(
+            "Clock".to_string(),
+            TaskType::Ntp,
+            RawMetricData::Ntp(RawNtpMetric {
+                offset_ms: Some(1500.0),
+                delay_ms: Some(12.0),
+                stratum: Some(2),
+                reference_id: Some("192.0.2.1".to_string()),
+                leap_indicator: Some(0),
+                success: true,
+                error: None,
+                status,
+                status_message: None,
+                host: "ntp.example.com".to_string(),
+                target_id: None,
+            }),
+        )
+    };
+
+    assert!(ntp_metric(ThresholdStatus::Ok).is_successful());
+    assert!(ntp_metric(ThresholdStatus::Warning).is_successful());
+    assert!(!ntp_metric(ThresholdStatus::Critical).is_successful());
+}
+
+#[test]
+#[cfg(feature = "sql-tasks")]
+fn test_critical_sql_query_is_not_successful() {
+    use crate::config::SqlQueryMode;
+    use crate::metrics::RawSqlQueryMetric;
+
+    let sql_metric = |status| {
+        MetricData::new(
+            "Replication lag".to_string(),
+            TaskType::SqlQuery,
+            RawMetricData::SqlQuery(RawSqlQueryMetric {
+                total_time_ms: Some(3.0),
+                row_count: Some(1),
+                success: true,
+                error: None,
+                target_id: None,
+                mode: SqlQueryMode::Value,
+                value: Some(900.0),
+                json_result: None,
+                json_truncated: false,
+                column_count: Some(1),
+                connect_time_ms: None,
+                query_time_ms: Some(2.0),
+                status,
+                status_message: None,
+            }),
+        )
+    };
+
+    assert!(sql_metric(ThresholdStatus::Ok).is_successful());
+    assert!(sql_metric(ThresholdStatus::Warning).is_successful());
+    assert!(!sql_metric(ThresholdStatus::Critical).is_successful());
+}
+
 #[test]
 fn test_percentage_calculation() {
     assert_eq!(calculate_percentage(50, 100), 50.0);
@@ -80,6 +142,7 @@ fn test_serialization() {
         failed_pings: 3,
         domain: Some("example.com".to_string()),
         target_id: None,
+        latency: None,
     };
 
     let aggregated = AggregatedMetrics::new(
@@ -118,6 +181,7 @@ fn test_http_status_code_distribution_serialization() {
         ssl_valid_percent: Some(100.0),
         avg_ssl_cert_days_until_expiry: Some(90.0),
         target_id: None,
+        latency: None,
     };
 
     let aggregated = AggregatedMetrics::new(
@@ -154,3 +218,38 @@ fn test_http_status_code_distribution_serialization() {
         panic!("Expected HttpGet metric data");
     }
 }
+
+#[test]
+fn test_latency_distribution_from_samples() {
+    assert_eq!(LatencyDistribution::from_samples(&[]), None);
+    assert_eq!(LatencyDistribution::from_samples(&[f64::NAN]), None);
+
+    // 1..=100 ms in reverse order, plus one sample past the last bucket bound
+    let mut samples: Vec<f64> = (1..=100).rev().map(|ms| ms as f64).collect();
+    samples.push(5000.0);
+
+    let latency = LatencyDistribution::from_samples(&samples).unwrap();
+    assert_eq!(latency.p50_ms, 51.0);
+    assert_eq!(latency.p90_ms, 91.0);
+    assert_eq!(latency.p95_ms, 96.0);
+    assert_eq!(latency.p99_ms, 100.0);
+    assert_eq!(
+        latency.histogram,
+        vec![1, 1, 3, 5, 10, 30, 50, 0, 0, 0, 0, 1]
+    );
+
+    let single = LatencyDistribution::from_samples(&[42.0]).unwrap();
+    assert_eq!(single.p50_ms, 42.0);
+    assert_eq!(single.p99_ms, 42.0);
+}
+
+#[test]
+fn test_aggregated_metric_without_latency_deserializes() {
+    // Payload from an agent that predates latency distributions
+    let json = r#"{"avg_latency_ms":15.5,"max_latency_ms":25.1,"min_latency_ms":10.2,"packet_loss_percent":0.0,"successful_pings":60,"failed_pings":0,"domain":null,"target_id":null}"#;
+    let ping_metric: AggregatedPingMetric = serde_json::from_str(json).unwrap();
+    assert_eq!(ping_metric.latency, None);
+    assert!(!serde_json::to_string(&ping_metric)
+        .unwrap()
+        .contains("latency\":"));
+}
@@ -3,5 +3,6 @@
 mod api_tests;
 mod config_tests;
 mod metrics_tests;
+mod schedule_tests;
 pub mod test_utils;
 mod utils_tests;
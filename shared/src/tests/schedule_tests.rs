@@ -0,0 +1,179 @@
+//! Tests for cron schedules, time windows and maintenance windows
+
+use crate::schedule::{CronSchedule, MaintenanceWindow, TimeWindow, Weekday};
+
+/// Monday 2024-01-01 00:00:00 UTC
+const MONDAY: u64 = 1_704_067_200;
+const HOUR: u64 = 3600;
+const DAY: u64 = 86_400;
+
+#[test]
+fn test_cron_next_after() {
+    let every_five = CronSchedule::parse("*/5 * * * *").unwrap();
+    assert_eq!(every_five.next_after(MONDAY), Some(MONDAY + 300));
+    assert_eq!(every_five.next_after(MONDAY + 299), Some(MONDAY + 300));
+    assert_eq!(every_five.next_after(MONDAY + 300), Some(MONDAY + 600));
+
+    // Weekdays at 08:30
+    let weekdays = CronSchedule::parse("30 8 * * mon-fri").unwrap();
+    assert_eq!(weekdays.next_after(MONDAY), Some(MONDAY + 8 * HOUR + 1800));
+    let friday_evening = MONDAY + 4 * DAY + 18 * HOUR;
+    assert_eq!(
+        weekdays.next_after(friday_evening),
+        Some(MONDAY + 7 * DAY + 8 * HOUR + 1800)
+    );
+
+    // Shortcuts and day 7 as Sunday
+    assert_eq!(
+        CronSchedule::parse("@daily").unwrap().next_after(MONDAY),
+        Some(MONDAY + DAY)
+    );
+    assert_eq!(
+        CronSchedule::parse("0 0 * * 7").unwrap(),
+        CronSchedule::parse("0 0 * * sun").unwrap()
+    );
+
+    // Leap days are found years ahead (2024-02-29, then 2028-02-29)
+    let leap_day = CronSchedule::parse("0 0 29 feb *").unwrap();
+    assert_eq!(leap_day.next_after(MONDAY), Some(1_709_164_800));
+    assert_eq!(leap_day.next_after(1_709_164_800), Some(1_835_395_200));
+}
+
+#[test]
+fn test_cron_day_fields() {
+    // Both day fields restricted: either may match (1st of the month or any Monday)
+    let either = CronSchedule::parse("0 12 1 * mon").unwrap();
+    assert!(either.matches(MONDAY + 12 * HOUR));
+    assert!(either.matches(MONDAY + 7 * DAY + 12 * HOUR));
+    assert!(!either.matches(MONDAY + DAY + 12 * HOUR));
+
+    // Only day of month restricted: Mondays other than the 1st do not match
+    let first_only = CronSchedule::parse("0 12 1 * *").unwrap();
+    assert!(!first_only.matches(MONDAY + 7 * DAY + 12 * HOUR));
+}
+
+#[test]
+fn test_cron_occurrences() {
+    let quarter_hours = CronSchedule::parse("0,15,30,45 * * * *").unwrap();
+    assert_eq!(
+        quarter_hours.occurrences(MONDAY, MONDAY + HOUR),
+        vec![MONDAY, MONDAY + 900, MONDAY + 1800, MONDAY + 2700]
+    );
+    // A period starting mid-minute excludes the minute it starts in
+    assert_eq!(
+        quarter_hours.occurrences(MONDAY + 10, MONDAY + 1000),
+        vec![MONDAY + 900]
+    );
+}
+
+#[test]
+fn test_invalid_cron_expressions() {
+    for expression in [
+        "",
+        "* * * *",
+        "* * * * * *",
+        "60 * * * *",
+        "* 24 * * *",
+        "* * 0 * *",
+        "* * * 13 *",
+        "*/0 * * * *",
+        "10-5 * * * *",
+        "abc * * * *",
+        "0 0 30 feb *",
+    ] {
+        assert!(
+            CronSchedule::parse(expression).is_err(),
+            "expression '{}'",
+            expression
+        );
+    }
+}
+
+#[test]
+fn test_time_window_contains() {
+    let business_hours = TimeWindow {
+        days: vec![
+            Weekday::Mon,
+            Weekday::Tue,
+            Weekday::Wed,
+            Weekday::Thu,
+            Weekday::Fri,
+        ],
+        start: "08:00".to_string(),
+        end: "18:00".to_string(),
+    };
+    assert!(business_hours.validate().is_ok());
+    assert!(business_hours.contains(MONDAY + 8 * HOUR));
+    assert!(business_hours.contains(MONDAY + 18 * HOUR - 1));
+    assert!(!business_hours.contains(MONDAY + 18 * HOUR));
+    assert!(!business_hours.contains(MONDAY + 5 * DAY + 12 * HOUR)); // Saturday
+
+    // A period overlaps the window even if the window opens after the period starts
+    assert!(business_hours.overlaps(MONDAY + 7 * HOUR + 55 * 60, MONDAY + 8 * HOUR + 5 * 60));
+    assert!(!business_hours.overlaps(MONDAY + 7 * HOUR, MONDAY + 8 * HOUR));
+
+    // Windows spanning midnight belong to the day they start on
+    let friday_night = TimeWindow {
+        days: vec![Weekday::Fri],
+        start: "22:00".to_string(),
+        end: "02:00".to_string(),
+    };
+    assert!(friday_night.contains(MONDAY + 4 * DAY + 23 * HOUR));
+    assert!(friday_night.contains(MONDAY + 5 * DAY + HOUR));
+    assert!(!friday_night.contains(MONDAY + HOUR)); // Monday night after Sunday
+    assert!(!friday_night.contains(MONDAY + 5 * DAY + 23 * HOUR));
+
+    for (start, end) in [("08:00", "08:00"), ("8:00", "18:00"), ("08:00", "24:01")] {
+        let window = TimeWindow {
+            days: Vec::new(),
+            start: start.to_string(),
+            end: end.to_string(),
+        };
+        assert!(window.validate().is_err(), "window {}-{}", start, end);
+    }
+}
+
+#[test]
+fn test_maintenance_window_overlaps() {
+    let one_off = MaintenanceWindow {
+        name: "migration".to_string(),
+        tasks: Vec::new(),
+        starts_at: Some("2024-01-01T02:00:00Z".to_string()),
+        ends_at: Some("2024-01-01T03:00:00+00:00".to_string()),
+        days: Vec::new(),
+        start: None,
+        end: None,
+    };
+    assert!(one_off.validate().is_ok());
+    assert!(one_off.applies_to("any task"));
+    assert!(one_off.overlaps(MONDAY + 2 * HOUR - 60, MONDAY + 2 * HOUR + 1));
+    assert!(!one_off.overlaps(MONDAY + 2 * HOUR - 60, MONDAY + 2 * HOUR));
+    assert!(!one_off.overlaps(MONDAY + 3 * HOUR, MONDAY + 3 * HOUR + 60));
+
+    // Recurring window overlapping only the last minute of a five-minute period
+    let nightly = MaintenanceWindow {
+        name: "backups".to_string(),
+        tasks: vec!["db-check".to_string()],
+        starts_at: None,
+        ends_at: None,
+        days: Vec::new(),
+        start: Some("01:04".to_string()),
+        end: Some("01:30".to_string()),
+    };
+    assert!(nightly.validate().is_ok());
+    assert!(nightly.applies_to("db-check"));
+    assert!(!nightly.applies_to("ping"));
+    assert!(nightly.overlaps(MONDAY + HOUR, MONDAY + HOUR + 300));
+    assert!(!nightly.overlaps(MONDAY + HOUR - 300, MONDAY + HOUR + 240));
+
+    // Incomplete or mixed definitions are rejected
+    let mut incomplete = one_off.clone();
+    incomplete.ends_at = None;
+    assert!(incomplete.validate().is_err());
+    let mut reversed = one_off.clone();
+    reversed.ends_at = Some("2024-01-01T01:00:00Z".to_string());
+    assert!(reversed.validate().is_err());
+    let mut mixed = one_off;
+    mixed.days = vec![Weekday::Sun];
+    assert!(mixed.validate().is_err());
+}
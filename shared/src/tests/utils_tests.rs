@@ -1,6 +1,9 @@
 //! Tests for utility functions
 
-use crate::utils::{calculate_checksum, encode_base64, validate_agent_id, validate_url};
+use crate::utils::{
+    bandwidth_stream_size, calculate_checksum, encode_base64, estimate_mos, fill_incompressible,
+    validate_agent_id, validate_url,
+};
 
 use super::test_utils::{
     calculate_backoff_delay, current_timestamp, decode_base64, format_duration, sanitize_file_path,
@@ -127,3 +130,52 @@ fn test_current_timestamp() {
     assert!(ts2 > ts1);
     assert!(ts2 - ts1 >= 1);
 }
+
+#[test]
+fn test_fill_incompressible() {
+    let mut first = vec![0u8; 1000];
+    let mut again = vec![0u8; 1000];
+    fill_incompressible(42, 0, &mut first);
+    fill_incompressible(42, 0, &mut again);
+    // Same seed and chunk produce the same data
+    assert_eq!(first, again);
+
+    let mut next_chunk = vec![0u8; 1000];
+    let mut other_seed = vec![0u8; 1000];
+    fill_incompressible(42, 1, &mut next_chunk);
+    fill_incompressible(43, 0, &mut other_seed);
+    assert_ne!(first, next_chunk);
+    assert_ne!(first, other_seed);
+
+    // Bytes are spread over the whole range
+    let distinct: std::collections::HashSet<u8> = first.iter().copied().collect();
+    assert!(distinct.len() > 200);
+}
+
+#[test]
+fn test_bandwidth_stream_size() {
+    assert_eq!(bandwidth_stream_size(10_485_760, 1), 10_485_760);
+    assert_eq!(bandwidth_stream_size(10_485_760, 4), 2_621_440);
+    assert_eq!(bandwidth_stream_size(10, 3), 3);
+    // Zero streams is treated as one
+    assert_eq!(bandwidth_stream_size(100, 0), 100);
+}
+
+#[test]
+fn test_estimate_mos() {
+    // A clean LAN path scores close to the G.711 maximum
+    let clean = estimate_mos(1.0, 0.2, 0.0);
+    assert!(clean > 4.3 && clean <= 4.5, "clean path MOS: {}", clean);
+
+    // Loss, jitter and latency each reduce the score
+    assert!(estimate_mos(1.0, 0.2, 5.0) < clean);
+    assert!(estimate_mos(1.0, 30.0, 0.0) < clean);
+    assert!(estimate_mos(400.0, 0.2, 0.0) < estimate_mos(100.0, 0.2, 0.0));
+
+    // 10% loss is a noticeably degraded call
+    let lossy = estimate_mos(20.0, 2.0, 10.0);
+    assert!(lossy > 3.0 && lossy < 3.6, "lossy path MOS: {}", lossy);
+
+    // Total loss bottoms out at 1.0
+    assert_eq!(estimate_mos(20.0, 2.0, 100.0), 1.0);
+}
@@ -114,3 +114,46 @@ pub fn validate_url(url_str: &str, https_only: bool) -> crate::Result<()> {
 
     Ok(())
 }
+
+/// Size of each stream when `total_bytes` of bandwidth test data is split across streams
+pub fn bandwidth_stream_size(total_bytes: u64, streams: u32) -> u64 {
+    total_bytes / u64::from(streams.max(1))
+}
+
+/// Fill `buf` with incompressible pseudo-random bytes for bandwidth tests
+///
+/// Output is fully determined by `seed` and `chunk_index`, so test data of any
+/// size can be generated chunk by chunk without keeping it in memory. Uses
+/// SplitMix64, which is fast and defeats compression, but is not suitable for
+/// anything security related.
+pub fn fill_incompressible(seed: u64, chunk_index: u64, buf: &mut [u8]) {
+    let mut state = seed ^ chunk_index.wrapping_mul(0xD1B5_4A32_D192_ED03);
+    for block in buf.chunks_mut(8) {
+        state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        block.copy_from_slice(&z.to_le_bytes()[..block.len()]);
+    }
+}
+
+/// Estimate the Mean Opinion Score of a voice call from network measurements
+///
+/// Uses the simplified ITU-T G.107 E-model commonly applied to ping-style
+/// measurements: the R-factor starts at 93.2 and is reduced by one-way delay
+/// (with jitter counted twice, as a jitter buffer would add it, plus 10 ms of
+/// codec delay) and by 2.5 points per percent of packet loss. One-way delay is
+/// taken as half the round-trip time. Returns a score between 1.0 and 4.5.
+pub fn estimate_mos(rtt_ms: f64, jitter_ms: f64, loss_percent: f64) -> f64 {
+    let effective_latency = rtt_ms / 2.0 + 2.0 * jitter_ms + 10.0;
+    let mut r_factor = if effective_latency < 160.0 {
+        93.2 - effective_latency / 40.0
+    } else {
+        93.2 - (effective_latency - 120.0) / 10.0
+    };
+    r_factor -= 2.5 * loss_percent;
+    let r_factor = r_factor.clamp(0.0, 100.0);
+    let mos = 1.0 + 0.035 * r_factor + 0.000007 * r_factor * (r_factor - 60.0) * (100.0 - r_factor);
+    mos.clamp(1.0, 4.5)
+}